@@ -205,6 +205,7 @@ impl<F: GLGraphicsBackend + 'static> GliumDrawer<F> {
                 [   x  ,    y   , 0.0, 1.0]
             ],
             tex: spec.texture,
+            tex_rect: spec.texture_rect,
         };
 
         target
@@ -232,6 +233,7 @@ pub struct RenderTextureSpec<'a> {
     texture_kind: usize,
     y_inverted: bool,
     surface_dimensions: (u32, u32),
+    texture_rect: [f32; 4],
     surface_location: (i32, i32),
     screen_size: (u32, u32),
     blending: glium::Blend,
@@ -284,10 +286,22 @@ impl<F: GLGraphicsBackend + 'static> GliumDrawer<F> {
                 if let Some(ref data) = attributes.user_data.get::<RefCell<SurfaceData>>() {
                     let mut data = data.borrow_mut();
                     let (sub_x, sub_y) = data.current_state.sub_location;
+                    let viewport = data.current_state.viewport;
                     if let Some(buffer_textures) = data.texture.as_mut() {
                         let texture_kind = buffer_textures.fragment;
                         let y_inverted = buffer_textures.y_inverted;
-                        let surface_dimensions = buffer_textures.dimensions;
+                        // the viewport of the surface crops and scales its buffer
+                        let buffer_dimensions = buffer_textures.dimensions;
+                        let buffer_dimensions = (buffer_dimensions.0 as i32, buffer_dimensions.1 as i32);
+                        let (width, height) = viewport.surface_size(buffer_dimensions);
+                        let surface_dimensions = (width as u32, height as u32);
+                        let source = viewport.source_region(buffer_dimensions);
+                        let texture_rect = [
+                            source.x as f32,
+                            source.y as f32,
+                            source.width as f32,
+                            source.height as f32,
+                        ];
                         if let Ok(ref texture) = buffer_textures.load_texture(&self) {
                             // we need to re-extract the subsurface offset, as the previous closure
                             // only passes it to our children
@@ -302,6 +316,7 @@ impl<F: GLGraphicsBackend + 'static> GliumDrawer<F> {
                                     texture_kind,
                                     y_inverted,
                                     surface_dimensions,
+                                    texture_rect,
                                     surface_location: (x, y),
                                     screen_size: screen_dimensions,
                                     blending: ::glium::Blend {
@@ -372,8 +387,11 @@ impl<F: GLGraphicsBackend + 'static> GliumDrawer<F> {
                 "Trying to display as a dnd icon a surface that does not have the DndIcon role."
             );
         }
+        let (dx, dy) = token
+            .with_role_data(surface, |role: &mut DnDIconRole| role.offset)
+            .unwrap_or((0, 0));
         let screen_dimensions = self.borrow().get_framebuffer_dimensions();
-        self.draw_surface_tree(frame, surface, (x, y), token, screen_dimensions);
+        self.draw_surface_tree(frame, surface, (x + dx, y + dy), token, screen_dimensions);
     }
 }
 
@@ -118,7 +118,11 @@ impl AnvilState {
             .window_map
             .borrow()
             .get_surface_under((location.0, location.1));
+        self.pointer
+            .relative_motion(evt.delta(), evt.delta_unaccel(), evt.time_usec());
         self.pointer.motion(*location, under, serial, evt.time());
+        // the pointer may be locked or confined by a client
+        *location = self.pointer.current_location();
     }
 
     fn on_pointer_move_absolute<B: InputBackend>(&mut self, evt: B::PointerMotionAbsoluteEvent) {
@@ -147,6 +151,7 @@ impl AnvilState {
         let serial = SCOUNTER.next_serial();
         let under = self.window_map.borrow().get_surface_under((x as f64, y as f64));
         self.pointer.motion((x, y), under, serial, evt.time());
+        *self.pointer_location.borrow_mut() = self.pointer.current_location();
     }
 
     #[cfg(feature = "udev")]
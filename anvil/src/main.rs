@@ -46,28 +46,43 @@ fn main() {
     let display = Rc::new(RefCell::new(Display::new()));
 
     let arg = ::std::env::args().nth(1);
-    match arg.as_ref().map(|s| &s[..]) {
+    let result = match arg.as_ref().map(|s| &s[..]) {
         #[cfg(feature = "winit")]
         Some("--winit") => {
             info!(log, "Starting anvil with winit backend");
-            if let Err(()) = winit::run_winit(display, &mut event_loop, log.clone()) {
+            winit::run_winit(display, &mut event_loop, log.clone()).map_err(|()| {
                 crit!(log, "Failed to initialize winit backend.");
-            }
+            })
         }
         #[cfg(feature = "udev")]
         Some("--tty-udev") => {
             info!(log, "Starting anvil on a tty using udev");
-            if let Err(()) = udev::run_udev(display, &mut event_loop, log.clone()) {
+            udev::run_udev(display, &mut event_loop, log.clone()).map_err(|()| {
                 crit!(log, "Failed to initialize tty backend.");
-            }
+            })
+        }
+        Some("--help") | Some("-h") => {
+            print_usage();
+            Ok(())
         }
         _ => {
-            println!("USAGE: anvil --backend");
-            println!();
-            println!("Possible backends are:");
-            for b in POSSIBLE_BACKENDS {
-                println!("\t{}", b);
-            }
+            print_usage();
+            Err(())
         }
+    };
+
+    if result.is_err() {
+        // make sure the logs are flushed before exiting
+        drop(log);
+        ::std::process::exit(1);
+    }
+}
+
+fn print_usage() {
+    println!("USAGE: anvil --backend");
+    println!();
+    println!("Possible backends are:");
+    for b in POSSIBLE_BACKENDS {
+        println!("\t{}", b);
     }
 }
@@ -39,12 +39,13 @@ macro_rules! opengl_programs(
 pub const VERTEX_SHADER: &str = r#"
 #version 100
 uniform lowp mat4 matrix;
+uniform lowp vec4 tex_rect;
 attribute lowp vec2 position;
 attribute lowp vec2 tex_coords;
 varying lowp vec2 v_tex_coords;
 void main() {
     gl_Position = matrix * vec4(position, 0.0, 1.0);
-    v_tex_coords = tex_coords;
+    v_tex_coords = tex_rect.xy + tex_coords * tex_rect.zw;
 }"#;
 
 pub const FRAGMENT_COUNT: usize = 5;
@@ -5,6 +5,7 @@ use std::{
 };
 
 use smithay::{
+    backend::input::TouchSlot,
     reexports::{
         wayland_protocols::xdg_shell::server::xdg_toplevel,
         wayland_server::{
@@ -15,11 +16,15 @@ use smithay::{
     utils::Rectangle,
     wayland::{
         compositor::{
-            compositor_init, roles::Role, BufferAssignment, CompositorToken, RegionAttributes,
-            SubsurfaceRole, SurfaceEvent, TraversalAction,
+            compositor_init, BufferAssignment, CompositorToken, RegionAttributes, SubsurfaceRole,
+            SurfaceEvent,
         },
         data_device::DnDIconRole,
-        seat::{AxisFrame, CursorImageRole, GrabStartData, PointerGrab, PointerInnerHandle, Seat},
+        seat::{
+            AxisFrame, CursorImageRole, DragGrab, GrabStartData, PointerGrab, PointerHandle,
+            PointerInnerHandle, Seat, TouchDragGrab, TouchGrab, TouchGrabStartData, TouchHandle,
+            TouchInnerHandle,
+        },
         shell::{
             legacy::{
                 wl_shell_init, ShellRequest, ShellState as WlShellState, ShellSurfaceKind, ShellSurfaceRole,
@@ -29,7 +34,8 @@ use smithay::{
                 XdgSurfacePendingState, XdgSurfaceRole,
             },
         },
-        Serial,
+        viewporter::Viewport,
+        Serial, SERIAL_COUNTER as SCOUNTER,
     },
 };
 
@@ -49,56 +55,6 @@ pub type MyWindowMap = WindowMap<Roles>;
 
 pub type MyCompositorToken = CompositorToken<Roles>;
 
-struct MoveSurfaceGrab {
-    start_data: GrabStartData,
-    window_map: Rc<RefCell<MyWindowMap>>,
-    toplevel: SurfaceKind<Roles>,
-    initial_window_location: (i32, i32),
-}
-
-impl PointerGrab for MoveSurfaceGrab {
-    fn motion(
-        &mut self,
-        _handle: &mut PointerInnerHandle<'_>,
-        location: (f64, f64),
-        _focus: Option<(wl_surface::WlSurface, (f64, f64))>,
-        _serial: Serial,
-        _time: u32,
-    ) {
-        let dx = location.0 - self.start_data.location.0;
-        let dy = location.1 - self.start_data.location.1;
-        let new_window_x = (self.initial_window_location.0 as f64 + dx) as i32;
-        let new_window_y = (self.initial_window_location.1 as f64 + dy) as i32;
-
-        self.window_map
-            .borrow_mut()
-            .set_location(&self.toplevel, (new_window_x, new_window_y));
-    }
-
-    fn button(
-        &mut self,
-        handle: &mut PointerInnerHandle<'_>,
-        button: u32,
-        state: ButtonState,
-        serial: Serial,
-        time: u32,
-    ) {
-        handle.button(button, state, serial, time);
-        if handle.current_pressed().is_empty() {
-            // No more buttons are pressed, release the grab.
-            handle.unset_grab(serial, time);
-        }
-    }
-
-    fn axis(&mut self, handle: &mut PointerInnerHandle<'_>, details: AxisFrame) {
-        handle.axis(details)
-    }
-
-    fn start_data(&self) -> &GrabStartData {
-        &self.start_data
-    }
-}
-
 bitflags::bitflags! {
     struct ResizeEdge: u32 {
         const NONE = 0;
@@ -141,8 +97,8 @@ impl From<ResizeEdge> for xdg_toplevel::ResizeEdge {
     }
 }
 
-struct ResizeSurfaceGrab {
-    start_data: GrabStartData,
+struct ResizeSurfaceGrab<S> {
+    start_data: S,
     ctoken: MyCompositorToken,
     toplevel: SurfaceKind<Roles>,
     edges: ResizeEdge,
@@ -150,18 +106,8 @@ struct ResizeSurfaceGrab {
     last_window_size: (i32, i32),
 }
 
-impl PointerGrab for ResizeSurfaceGrab {
-    fn motion(
-        &mut self,
-        _handle: &mut PointerInnerHandle<'_>,
-        location: (f64, f64),
-        _focus: Option<(wl_surface::WlSurface, (f64, f64))>,
-        serial: Serial,
-        _time: u32,
-    ) {
-        let mut dx = location.0 - self.start_data.location.0;
-        let mut dy = location.1 - self.start_data.location.1;
-
+impl<S> ResizeSurfaceGrab<S> {
+    fn resize(&mut self, (mut dx, mut dy): (f64, f64), serial: Serial) {
         let mut new_window_width = self.initial_window_size.0;
         let mut new_window_height = self.initial_window_size.1;
 
@@ -222,6 +168,60 @@ impl PointerGrab for ResizeSurfaceGrab {
         }
     }
 
+    fn release(&mut self, serial: Serial) {
+        if let SurfaceKind::Xdg(xdg) = &self.toplevel {
+            // Send the final configure without the resizing state.
+            xdg.send_configure(ToplevelConfigure {
+                size: Some(self.last_window_size),
+                states: vec![],
+                serial,
+            });
+
+            self.ctoken
+                .with_surface_data(self.toplevel.get_surface().unwrap(), |attrs| {
+                    let mut data = attrs
+                        .user_data
+                        .get::<RefCell<SurfaceData>>()
+                        .unwrap()
+                        .borrow_mut();
+                    if let ResizeState::Resizing(resize_data) = data.resize_state {
+                        data.resize_state = ResizeState::WaitingForFinalAck(resize_data, serial);
+                    } else {
+                        panic!("invalid resize state: {:?}", data.resize_state);
+                    }
+                });
+        } else {
+            self.ctoken
+                .with_surface_data(self.toplevel.get_surface().unwrap(), |attrs| {
+                    let mut data = attrs
+                        .user_data
+                        .get::<RefCell<SurfaceData>>()
+                        .unwrap()
+                        .borrow_mut();
+                    if let ResizeState::Resizing(resize_data) = data.resize_state {
+                        data.resize_state = ResizeState::WaitingForCommit(resize_data);
+                    } else {
+                        panic!("invalid resize state: {:?}", data.resize_state);
+                    }
+                });
+        }
+    }
+}
+
+impl PointerGrab for ResizeSurfaceGrab<GrabStartData> {
+    fn motion(
+        &mut self,
+        _handle: &mut PointerInnerHandle<'_>,
+        location: (f64, f64),
+        _focus: Option<(wl_surface::WlSurface, (f64, f64))>,
+        serial: Serial,
+        _time: u32,
+    ) {
+        let dx = location.0 - self.start_data.location.0;
+        let dy = location.1 - self.start_data.location.1;
+        self.resize((dx, dy), serial);
+    }
+
     fn button(
         &mut self,
         handle: &mut PointerInnerHandle<'_>,
@@ -234,43 +234,7 @@ impl PointerGrab for ResizeSurfaceGrab {
         if handle.current_pressed().is_empty() {
             // No more buttons are pressed, release the grab.
             handle.unset_grab(serial, time);
-
-            if let SurfaceKind::Xdg(xdg) = &self.toplevel {
-                // Send the final configure without the resizing state.
-                xdg.send_configure(ToplevelConfigure {
-                    size: Some(self.last_window_size),
-                    states: vec![],
-                    serial,
-                });
-
-                self.ctoken
-                    .with_surface_data(self.toplevel.get_surface().unwrap(), |attrs| {
-                        let mut data = attrs
-                            .user_data
-                            .get::<RefCell<SurfaceData>>()
-                            .unwrap()
-                            .borrow_mut();
-                        if let ResizeState::Resizing(resize_data) = data.resize_state {
-                            data.resize_state = ResizeState::WaitingForFinalAck(resize_data, serial);
-                        } else {
-                            panic!("invalid resize state: {:?}", data.resize_state);
-                        }
-                    });
-            } else {
-                self.ctoken
-                    .with_surface_data(self.toplevel.get_surface().unwrap(), |attrs| {
-                        let mut data = attrs
-                            .user_data
-                            .get::<RefCell<SurfaceData>>()
-                            .unwrap()
-                            .borrow_mut();
-                        if let ResizeState::Resizing(resize_data) = data.resize_state {
-                            data.resize_state = ResizeState::WaitingForCommit(resize_data);
-                        } else {
-                            panic!("invalid resize state: {:?}", data.resize_state);
-                        }
-                    });
-            }
+            self.release(serial);
         }
     }
 
@@ -283,6 +247,165 @@ impl PointerGrab for ResizeSurfaceGrab {
     }
 }
 
+impl TouchGrab for ResizeSurfaceGrab<TouchGrabStartData> {
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(wl_surface::WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.down(slot, location, focus, serial, time);
+    }
+
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, slot: Option<TouchSlot>, serial: Serial, time: u32) {
+        handle.up(slot, serial, time);
+        if slot == self.start_data.slot {
+            // The touch point driving the resize was lifted, release the grab.
+            handle.unset_grab();
+            self.release(serial);
+        }
+    }
+
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        _focus: Option<(wl_surface::WlSurface, (f64, f64))>,
+        time: u32,
+    ) {
+        if slot == self.start_data.slot {
+            let dx = location.0 - self.start_data.location.0;
+            let dy = location.1 - self.start_data.location.1;
+            self.resize((dx, dy), SCOUNTER.next_serial());
+        } else {
+            handle.motion(slot, location, time);
+        }
+    }
+
+    fn frame(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.frame()
+    }
+
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.cancel();
+        handle.unset_grab();
+        self.release(SCOUNTER.next_serial());
+    }
+
+    fn start_data(&self) -> &TouchGrabStartData {
+        &self.start_data
+    }
+}
+
+// The implicit grab of the pointer or of a touch point a client request responds to
+enum ImplicitGrab {
+    Pointer(PointerHandle, GrabStartData),
+    Touch(TouchHandle, TouchGrabStartData),
+}
+
+impl ImplicitGrab {
+    // Find the implicit grab with this serial, if it started on a surface of the client
+    fn find(seat: &Seat, serial: Serial, surface: &wl_surface::WlSurface) -> Option<ImplicitGrab> {
+        let same_client = |focus: &Option<(wl_surface::WlSurface, (f64, f64))>| {
+            focus
+                .as_ref()
+                .map(|(focus, _)| focus.as_ref().same_client_as(surface.as_ref()))
+                .unwrap_or(false)
+        };
+        if let Some(pointer) = seat.get_pointer().filter(|pointer| pointer.has_grab(serial)) {
+            let start_data = pointer.grab_start_data().unwrap();
+            // If the focus was for a different surface, ignore the request.
+            return if same_client(&start_data.focus) {
+                Some(ImplicitGrab::Pointer(pointer, start_data))
+            } else {
+                None
+            };
+        }
+        if let Some(touch) = seat.get_touch().filter(|touch| touch.has_grab(serial)) {
+            let start_data = touch.grab_start_data().unwrap();
+            return if same_client(&start_data.focus) {
+                Some(ImplicitGrab::Touch(touch, start_data))
+            } else {
+                None
+            };
+        }
+        None
+    }
+
+    // Interactively move the toplevel
+    fn start_move(self, serial: Serial, toplevel: SurfaceKind<Roles>, window_map: Rc<RefCell<MyWindowMap>>) {
+        let (initial_x, initial_y) = window_map.borrow().location(&toplevel).unwrap();
+        let move_to = move |(dx, dy): (f64, f64)| {
+            let location = ((initial_x as f64 + dx) as i32, (initial_y as f64 + dy) as i32);
+            window_map.borrow_mut().set_location(&toplevel, location);
+        };
+        match self {
+            ImplicitGrab::Pointer(pointer, start_data) => {
+                pointer.set_grab(DragGrab::new(start_data, move_to), serial)
+            }
+            ImplicitGrab::Touch(touch, start_data) => {
+                touch.set_grab(TouchDragGrab::new(start_data, move_to), serial)
+            }
+        }
+    }
+
+    // Interactively resize the toplevel
+    fn start_resize(
+        self,
+        serial: Serial,
+        toplevel: SurfaceKind<Roles>,
+        edges: ResizeEdge,
+        ctoken: MyCompositorToken,
+        window_map: &RefCell<MyWindowMap>,
+    ) {
+        let initial_window_location = window_map.borrow().location(&toplevel).unwrap();
+        let geometry = window_map.borrow().geometry(&toplevel).unwrap();
+        let initial_window_size = (geometry.width, geometry.height);
+
+        ctoken.with_surface_data(toplevel.get_surface().unwrap(), move |attrs| {
+            attrs
+                .user_data
+                .get::<RefCell<SurfaceData>>()
+                .unwrap()
+                .borrow_mut()
+                .resize_state = ResizeState::Resizing(ResizeData {
+                edges,
+                initial_window_location,
+                initial_window_size,
+            });
+        });
+
+        match self {
+            ImplicitGrab::Pointer(pointer, start_data) => pointer.set_grab(
+                ResizeSurfaceGrab {
+                    start_data,
+                    ctoken,
+                    toplevel,
+                    edges,
+                    initial_window_size,
+                    last_window_size: initial_window_size,
+                },
+                serial,
+            ),
+            ImplicitGrab::Touch(touch, start_data) => touch.set_grab(
+                ResizeSurfaceGrab {
+                    start_data,
+                    ctoken,
+                    toplevel,
+                    edges,
+                    initial_window_size,
+                    last_window_size: initial_window_size,
+                },
+                serial,
+            ),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct ShellHandles {
     pub token: CompositorToken<Roles>,
@@ -346,40 +469,14 @@ pub fn init_shell(display: &mut Display, buffer_utils: BufferUtils, log: ::slog:
                 serial,
             } => {
                 let seat = Seat::from_resource(&seat).unwrap();
-                // TODO: touch move.
-                let pointer = seat.get_pointer().unwrap();
-
-                // Check that this surface has a click grab.
-                if !pointer.has_grab(serial) {
-                    return;
-                }
-
-                let start_data = pointer.grab_start_data().unwrap();
-
-                // If the focus was for a different surface, ignore the request.
-                if start_data.focus.is_none()
-                    || !start_data
-                        .focus
-                        .as_ref()
-                        .unwrap()
-                        .0
-                        .as_ref()
-                        .same_client_as(surface.get_surface().unwrap().as_ref())
-                {
-                    return;
-                }
 
-                let toplevel = SurfaceKind::Xdg(surface);
-                let initial_window_location = xdg_window_map.borrow().location(&toplevel).unwrap();
-
-                let grab = MoveSurfaceGrab {
-                    start_data,
-                    window_map: xdg_window_map.clone(),
-                    toplevel,
-                    initial_window_location,
+                // Check that this surface has a click or touch grab.
+                let grab = match ImplicitGrab::find(&seat, serial, surface.get_surface().unwrap()) {
+                    Some(grab) => grab,
+                    None => return,
                 };
 
-                pointer.set_grab(grab, serial);
+                grab.start_move(serial, SurfaceKind::Xdg(surface), xdg_window_map.clone());
             }
             XdgRequest::Resize {
                 surface,
@@ -388,57 +485,20 @@ pub fn init_shell(display: &mut Display, buffer_utils: BufferUtils, log: ::slog:
                 edges,
             } => {
                 let seat = Seat::from_resource(&seat).unwrap();
-                // TODO: touch resize.
-                let pointer = seat.get_pointer().unwrap();
 
-                // Check that this surface has a click grab.
-                if !pointer.has_grab(serial) {
-                    return;
-                }
-
-                let start_data = pointer.grab_start_data().unwrap();
-
-                // If the focus was for a different surface, ignore the request.
-                if start_data.focus.is_none()
-                    || !start_data
-                        .focus
-                        .as_ref()
-                        .unwrap()
-                        .0
-                        .as_ref()
-                        .same_client_as(surface.get_surface().unwrap().as_ref())
-                {
-                    return;
-                }
-
-                let toplevel = SurfaceKind::Xdg(surface.clone());
-                let initial_window_location = xdg_window_map.borrow().location(&toplevel).unwrap();
-                let geometry = xdg_window_map.borrow().geometry(&toplevel).unwrap();
-                let initial_window_size = (geometry.width, geometry.height);
-
-                compositor_token.with_surface_data(surface.get_surface().unwrap(), move |attrs| {
-                    attrs
-                        .user_data
-                        .get::<RefCell<SurfaceData>>()
-                        .unwrap()
-                        .borrow_mut()
-                        .resize_state = ResizeState::Resizing(ResizeData {
-                        edges: edges.into(),
-                        initial_window_location,
-                        initial_window_size,
-                    });
-                });
-
-                let grab = ResizeSurfaceGrab {
-                    start_data,
-                    ctoken: compositor_token,
-                    toplevel,
-                    edges: edges.into(),
-                    initial_window_size,
-                    last_window_size: initial_window_size,
+                // Check that this surface has a click or touch grab.
+                let grab = match ImplicitGrab::find(&seat, serial, surface.get_surface().unwrap()) {
+                    Some(grab) => grab,
+                    None => return,
                 };
 
-                pointer.set_grab(grab, serial);
+                grab.start_resize(
+                    serial,
+                    SurfaceKind::Xdg(surface),
+                    edges.into(),
+                    compositor_token,
+                    &xdg_window_map,
+                );
             }
             XdgRequest::AckConfigure { surface, .. } => {
                 let waiting_for_serial = compositor_token.with_surface_data(&surface, |attrs| {
@@ -454,7 +514,7 @@ pub fn init_shell(display: &mut Display, buffer_utils: BufferUtils, log: ::slog:
                 if let Some(serial) = waiting_for_serial {
                     let acked = compositor_token
                         .with_role_data(&surface, |role: &mut XdgSurfaceRole| {
-                            !role.pending_configures.contains(&serial.into())
+                            !role.lifecycle.pending_configures().contains(&serial)
                         })
                         .unwrap();
 
@@ -506,40 +566,14 @@ pub fn init_shell(display: &mut Display, buffer_utils: BufferUtils, log: ::slog:
                     serial,
                 } => {
                     let seat = Seat::from_resource(&seat).unwrap();
-                    // TODO: touch move.
-                    let pointer = seat.get_pointer().unwrap();
-
-                    // Check that this surface has a click grab.
-                    if !pointer.has_grab(serial) {
-                        return;
-                    }
-
-                    let start_data = pointer.grab_start_data().unwrap();
-
-                    // If the focus was for a different surface, ignore the request.
-                    if start_data.focus.is_none()
-                        || !start_data
-                            .focus
-                            .as_ref()
-                            .unwrap()
-                            .0
-                            .as_ref()
-                            .same_client_as(surface.get_surface().unwrap().as_ref())
-                    {
-                        return;
-                    }
 
-                    let toplevel = SurfaceKind::Wl(surface);
-                    let initial_window_location = shell_window_map.borrow().location(&toplevel).unwrap();
-
-                    let grab = MoveSurfaceGrab {
-                        start_data,
-                        window_map: shell_window_map.clone(),
-                        toplevel,
-                        initial_window_location,
+                    // Check that this surface has a click or touch grab.
+                    let grab = match ImplicitGrab::find(&seat, serial, surface.get_surface().unwrap()) {
+                        Some(grab) => grab,
+                        None => return,
                     };
 
-                    pointer.set_grab(grab, serial);
+                    grab.start_move(serial, SurfaceKind::Wl(surface), shell_window_map.clone());
                 }
                 ShellRequest::Resize {
                     surface,
@@ -548,57 +582,20 @@ pub fn init_shell(display: &mut Display, buffer_utils: BufferUtils, log: ::slog:
                     edges,
                 } => {
                     let seat = Seat::from_resource(&seat).unwrap();
-                    // TODO: touch resize.
-                    let pointer = seat.get_pointer().unwrap();
-
-                    // Check that this surface has a click grab.
-                    if !pointer.has_grab(serial) {
-                        return;
-                    }
 
-                    let start_data = pointer.grab_start_data().unwrap();
-
-                    // If the focus was for a different surface, ignore the request.
-                    if start_data.focus.is_none()
-                        || !start_data
-                            .focus
-                            .as_ref()
-                            .unwrap()
-                            .0
-                            .as_ref()
-                            .same_client_as(surface.get_surface().unwrap().as_ref())
-                    {
-                        return;
-                    }
-
-                    let toplevel = SurfaceKind::Wl(surface.clone());
-                    let initial_window_location = shell_window_map.borrow().location(&toplevel).unwrap();
-                    let geometry = shell_window_map.borrow().geometry(&toplevel).unwrap();
-                    let initial_window_size = (geometry.width, geometry.height);
-
-                    compositor_token.with_surface_data(surface.get_surface().unwrap(), move |attrs| {
-                        attrs
-                            .user_data
-                            .get::<RefCell<SurfaceData>>()
-                            .unwrap()
-                            .borrow_mut()
-                            .resize_state = ResizeState::Resizing(ResizeData {
-                            edges: edges.into(),
-                            initial_window_location,
-                            initial_window_size,
-                        });
-                    });
-
-                    let grab = ResizeSurfaceGrab {
-                        start_data,
-                        ctoken: compositor_token,
-                        toplevel,
-                        edges: edges.into(),
-                        initial_window_size,
-                        last_window_size: initial_window_size,
+                    // Check that this surface has a click or touch grab.
+                    let grab = match ImplicitGrab::find(&seat, serial, surface.get_surface().unwrap()) {
+                        Some(grab) => grab,
+                        None => return,
                     };
 
-                    pointer.set_grab(grab, serial);
+                    grab.start_resize(
+                        serial,
+                        SurfaceKind::Wl(surface),
+                        edges.into(),
+                        compositor_token,
+                        &shell_window_map,
+                    );
                 }
                 _ => (),
             }
@@ -649,6 +646,7 @@ pub struct CommitedState {
     pub buffer: Option<wl_buffer::WlBuffer>,
     pub input_region: Option<RegionAttributes>,
     pub dimensions: Option<(i32, i32)>,
+    pub viewport: Viewport,
     pub frame_callback: Option<wl_callback::WlCallback>,
     pub sub_location: (i32, i32),
 }
@@ -721,7 +719,9 @@ impl SurfaceData {
 impl SurfaceData {
     /// Returns the size of the surface.
     pub fn size(&self) -> Option<(i32, i32)> {
-        self.current_state.dimensions
+        self.current_state
+            .dimensions
+            .map(|dimensions| self.current_state.viewport.surface_size(dimensions))
     }
 
     /// Checks if the surface's input region contains the point.
@@ -783,6 +783,8 @@ fn surface_commit(
     let sub_data = token
         .with_role_data(surface, |&mut role: &mut SubsurfaceRole| role)
         .ok();
+    let is_sync = token.is_sync_subsurface(surface);
+    let mut buffer_delta = None;
 
     let (refresh, apply_children) = token.with_surface_data(surface, |attributes| {
         attributes
@@ -807,13 +809,15 @@ fn surface_commit(
 
         data.geometry = geometry;
         next_state.input_region = attributes.input_region.clone();
+        next_state.viewport = attributes.viewport;
         data.min_size = min_size;
         data.max_size = max_size;
 
         // we retrieve the contents of the associated buffer and copy it
         match attributes.buffer.take() {
-            Some(BufferAssignment::NewBuffer { buffer, .. }) => {
+            Some(BufferAssignment::NewBuffer { buffer, delta }) => {
                 // new contents
+                buffer_delta = Some(delta);
                 next_state.dimensions = buffer_utils.dimensions(&buffer);
                 next_state.buffer = Some(buffer);
             }
@@ -834,7 +838,7 @@ fn surface_commit(
 
         data.apply_cache(next_state);
 
-        let apply_children = if let Some(SubsurfaceRole { sync: true, .. }) = sub_data {
+        let apply_children = if is_sync {
             false
         } else {
             data.apply_from_cache();
@@ -844,40 +848,21 @@ fn surface_commit(
         (window_map.borrow().find(surface), apply_children)
     });
 
+    // the buffer offset moves a dnd icon relative to the pointer
+    if let Some((dx, dy)) = buffer_delta {
+        let _ = token.with_role_data(surface, |role: &mut DnDIconRole| {
+            role.offset.0 += dx;
+            role.offset.1 += dy;
+        });
+    }
+
     // Apply the cached state of all sync children
     if apply_children {
-        token.with_surface_tree_upward(
-            surface,
-            true,
-            |_, _, role, &is_root| {
-                // only process children if the surface is sync or we are the root
-                if is_root {
-                    // we are the root
-                    TraversalAction::DoChildren(false)
-                } else if let Ok(sub_data) = Role::<SubsurfaceRole>::data(role) {
-                    if sub_data.sync || is_root {
-                        TraversalAction::DoChildren(false)
-                    } else {
-                        // if we are not sync, we won't apply from cache and don't process
-                        // the children
-                        TraversalAction::SkipChildren
-                    }
-                } else {
-                    unreachable!();
-                }
-            },
-            |_, attributes, role, _| {
-                // only apply from cache if we are a sync subsurface
-                if let Ok(sub_data) = Role::<SubsurfaceRole>::data(role) {
-                    if sub_data.sync {
-                        if let Some(data) = attributes.user_data.get::<RefCell<SurfaceData>>() {
-                            data.borrow_mut().apply_from_cache();
-                        }
-                    }
-                }
-            },
-            |_, _, _, _| true,
-        )
+        token.with_synchronized_children(surface, |_, attributes| {
+            if let Some(data) = attributes.user_data.get::<RefCell<SurfaceData>>() {
+                data.borrow_mut().apply_from_cache();
+            }
+        });
     }
 
     if let Some(toplevel) = refresh {
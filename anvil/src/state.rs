@@ -17,9 +17,16 @@ use smithay::{
     },
     wayland::{
         compositor::CompositorToken,
-        data_device::{default_action_chooser, init_data_device, set_data_device_focus, DataDeviceEvent},
+        data_device::{
+            default_action_chooser, init_data_device, init_primary_selection, set_data_device_focus,
+            DataDeviceEvent,
+        },
+        output::xdg::init_xdg_output_manager,
+        pointer_constraints::init_pointer_constraints,
+        relative_pointer::init_relative_pointer_manager,
         seat::{CursorImageStatus, KeyboardHandle, PointerHandle, Seat, XkbConfig},
         shm::init_shm_global,
+        viewporter::init_viewporter,
     },
 };
 
@@ -121,6 +128,13 @@ impl AnvilState {
             shell_handles.token,
             log.clone(),
         );
+        init_primary_selection(&mut display.borrow_mut(), |_| {}, log.clone());
+
+        init_xdg_output_manager(&mut display.borrow_mut(), log.clone());
+
+        init_viewporter(&mut display.borrow_mut(), shell_handles.token, log.clone());
+        init_pointer_constraints(&mut display.borrow_mut(), shell_handles.token, log.clone());
+        init_relative_pointer_manager(&mut display.borrow_mut(), log.clone());
 
         // init input
         #[cfg(feature = "udev")]
@@ -152,6 +166,9 @@ impl AnvilState {
                 set_data_device_focus(seat, focus.and_then(|s| s.as_ref().client()))
             })
             .expect("Failed to initialize the keyboard");
+        if let Err(err) = keyboard.enable_key_repeat(&handle) {
+            warn!(log, "Failed to enable key repeat for old clients"; "err" => format!("{:?}", err));
+        }
 
         AnvilState {
             running: Arc::new(AtomicBool::new(true)),
@@ -1,5 +1,5 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::hash_map::{Entry, HashMap},
     io::Error as IoError,
     os::unix::io::{AsRawFd, RawFd},
@@ -18,7 +18,10 @@ use smithay::{
     backend::{
         drm::{
             atomic::{AtomicDrmDevice, AtomicDrmSurface},
-            common::fallback::{FallbackDevice, FallbackSurface},
+            common::{
+                edid::EdidInfo,
+                fallback::{FallbackDevice, FallbackSurface},
+            },
             device_bind,
             egl::{EglDevice, EglSurface},
             eglstream::{egl::EglStreamDeviceBackend, EglStreamDevice, EglStreamSurface},
@@ -27,7 +30,8 @@ use smithay::{
             DevPath, Device, DeviceHandler, Surface,
         },
         graphics::{CursorBackend, SwapBuffersError},
-        libinput::{LibinputInputBackend, LibinputSessionInterface},
+        input::{Device as _, InputConfig as _, InputEvent, KeyboardLeds},
+        libinput::{LibinputEvent, LibinputInputBackend, LibinputSessionInterface},
         session::{auto::AutoSession, Session, Signal as SessionSignal},
         udev::{primary_gpu, UdevBackend, UdevEvent},
     },
@@ -175,13 +179,33 @@ pub fn run_udev(
     let mut libinput_backend = LibinputInputBackend::new(libinput_context, log.clone());
     libinput_backend.link(session_signal);
 
+    /*
+     * Keep the leds of the keyboards in sync with the lock keys
+     */
+    let keyboard_leds = Rc::new(Cell::new(KeyboardLeds::default()));
+    state.keyboard.add_layout_listener({
+        let keyboard_leds = keyboard_leds.clone();
+        move |layout| keyboard_leds.set(layout.leds())
+    });
+    let mut applied_leds = KeyboardLeds::default();
+
     /*
      * Bind all our objects that get driven by the event loop
      */
     let libinput_event_source = event_loop
         .handle()
-        .insert_source(libinput_backend, move |event, _, anvil_state| {
-            anvil_state.process_input_event(event)
+        .insert_source(libinput_backend, move |event, config, anvil_state| {
+            if let InputEvent::Special(LibinputEvent::NewDevice(ref device)) = event {
+                device.clone().update_leds(applied_leds);
+            }
+            anvil_state.process_input_event(event);
+            let leds = keyboard_leds.get();
+            if leds != applied_leds {
+                for device in config.devices() {
+                    device.update_leds(leds);
+                }
+                applied_leds = leds;
+            }
         })
         .unwrap();
     let session_event_source = event_loop
@@ -242,20 +266,20 @@ impl MyOutput {
         device_id: dev_t,
         crtc: crtc::Handle,
         conn: ConnectorInfo,
+        edid: Option<EdidInfo>,
         logger: ::slog::Logger,
     ) -> MyOutput {
-        let (output, global) = Output::new(
-            display,
-            format!("{:?}", conn.interface()),
-            PhysicalProperties {
+        let physical = match edid {
+            Some(edid) => edid.physical_properties(wl_output::Subpixel::Unknown),
+            None => PhysicalProperties {
                 width: conn.size().unwrap_or((0, 0)).0 as i32,
                 height: conn.size().unwrap_or((0, 0)).1 as i32,
                 subpixel: wl_output::Subpixel::Unknown,
                 make: "Smithay".into(),
                 model: "Generic DRM".into(),
             },
-            logger,
-        );
+        };
+        let (output, global) = Output::new(display, format!("{:?}", conn.interface()), physical, logger);
 
         let mode = conn.modes()[0];
         let (w, h) = mode.size();
@@ -356,11 +380,13 @@ impl<Data: 'static> UdevHandlerImpl<Data> {
                             buffer_utils.clone(),
                             logger.clone(),
                         );
+                        let edid = device.get_connector_edid(connector_info.handle()).ok().flatten();
                         output_map.push(MyOutput::new(
                             display,
                             device.device_id(),
                             crtc,
                             connector_info,
+                            edid,
                             logger.clone(),
                         ));
 
@@ -5,57 +5,14 @@ use smithay::{
     utils::Rectangle,
     wayland::{
         compositor::{roles::Role, CompositorToken, SubsurfaceRole, TraversalAction},
-        shell::{
-            legacy::{ShellSurface, ShellSurfaceRole},
-            xdg::{ToplevelSurface, XdgSurfaceRole},
-        },
+        shell::{legacy::ShellSurfaceRole, xdg::XdgSurfaceRole},
         Serial,
     },
 };
 
 use crate::shell::SurfaceData;
 
-pub enum Kind<R> {
-    Xdg(ToplevelSurface<R>),
-    Wl(ShellSurface<R>),
-}
-
-// We implement Clone manually because #[derive(..)] would require R: Clone.
-impl<R> Clone for Kind<R> {
-    fn clone(&self) -> Self {
-        match self {
-            Kind::Xdg(xdg) => Kind::Xdg(xdg.clone()),
-            Kind::Wl(wl) => Kind::Wl(wl.clone()),
-        }
-    }
-}
-
-impl<R> Kind<R>
-where
-    R: Role<SubsurfaceRole> + Role<XdgSurfaceRole> + Role<ShellSurfaceRole> + 'static,
-{
-    pub fn alive(&self) -> bool {
-        match *self {
-            Kind::Xdg(ref t) => t.alive(),
-            Kind::Wl(ref t) => t.alive(),
-        }
-    }
-    pub fn get_surface(&self) -> Option<&wl_surface::WlSurface> {
-        match *self {
-            Kind::Xdg(ref t) => t.get_surface(),
-            Kind::Wl(ref t) => t.get_surface(),
-        }
-    }
-
-    /// Do this handle and the other one actually refer to the same toplevel surface?
-    pub fn equals(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Kind::Xdg(a), Kind::Xdg(b)) => a.equals(b),
-            (Kind::Wl(a), Kind::Wl(b)) => a.equals(b),
-            _ => false,
-        }
-    }
-}
+pub use smithay::wayland::shell::Toplevel as Kind;
 
 struct Window<R> {
     location: (i32, i32),
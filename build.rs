@@ -28,6 +28,8 @@ fn gl_generate() {
                 "EGL_EXT_platform_wayland",
                 "EGL_EXT_platform_device",
                 "EGL_KHR_image_base",
+                "EGL_EXT_image_dma_buf_import",
+                "EGL_EXT_image_dma_buf_import_modifiers",
                 "EGL_EXT_output_base",
                 "EGL_EXT_output_drm",
                 "EGL_EXT_device_drm",
@@ -7,8 +7,8 @@
 //!
 //! This api is objectively better than the outdated legacy-api, but not supported by every driver.
 //! Initialization will fail, if the api is unsupported. The legacy-api is also wrapped by smithay
-//! and may be used instead in these cases. Currently there are no features in smithay that are
-//! exclusive to the atomic api.
+//! and may be used instead in these cases. The only feature in smithay that is exclusive to the
+//! atomic api is the support for [`writeback`] connectors.
 //!
 //! Usually this implementation will wrapped into a [`GbmDevice`](::backend::drm::gbm::GbmDevice).
 //! Take a look at `anvil`s source code for an example of this.
@@ -38,9 +38,13 @@ use failure::{Fail, ResultExt};
 use nix::libc::dev_t;
 use nix::sys::stat::fstat;
 
-use super::{common::Error, DevPath, Device, DeviceHandler, RawDevice};
+use super::{
+    common::{typed_property::PropertyCache, Error},
+    DevPath, Device, DeviceHandler, RawDevice, VblankTime,
+};
 
 mod surface;
+pub mod writeback;
 pub use self::surface::AtomicDrmSurface;
 use self::surface::AtomicDrmSurfaceInternal;
 
@@ -66,19 +70,12 @@ type OldState = (
     Vec<(plane::Handle, PropertyValueSet)>,
 );
 
-type Mapping = (
-    HashMap<connector::Handle, HashMap<String, property::Handle>>,
-    HashMap<crtc::Handle, HashMap<String, property::Handle>>,
-    HashMap<framebuffer::Handle, HashMap<String, property::Handle>>,
-    HashMap<plane::Handle, HashMap<String, property::Handle>>,
-);
-
 pub(in crate::backend::drm) struct Dev<A: AsRawFd + 'static> {
     fd: A,
     privileged: bool,
     active: Arc<AtomicBool>,
     old_state: OldState,
-    prop_mapping: Mapping,
+    prop_mapping: PropertyCache,
     logger: ::slog::Logger,
 }
 
@@ -159,43 +156,16 @@ impl<A: AsRawFd + 'static> Dev<A> {
             })
     }
 
-    /// Create a mapping of property names and handles for given handles of a given drm resource type.
+    /// Fill the property cache for given handles of a given drm resource type.
     /// You may use this to easily lookup properties by name instead of going through this procedure manually.
-    fn map_props<T>(
-        &self,
-        handles: &[T],
-        mapping: &mut HashMap<T, HashMap<String, property::Handle>>,
-    ) -> Result<(), Error>
+    fn map_props<T>(&self, handles: &[T], mapping: &mut PropertyCache) -> Result<(), Error>
     where
         A: AsRawFd + 'static,
-        T: ResourceHandle + Eq + std::hash::Hash,
+        T: ResourceHandle,
     {
         handles
             .iter()
-            .map(|x| (x, self.get_properties(*x)))
-            .try_for_each(|(handle, props)| {
-                let mut map = HashMap::new();
-                match props {
-                    Ok(props) => {
-                        let (prop_handles, _) = props.as_props_and_values();
-                        for prop in prop_handles {
-                            if let Ok(info) = self.get_property(*prop) {
-                                let name = info.name().to_string_lossy().into_owned();
-                                map.insert(name, *prop);
-                            }
-                        }
-                        mapping.insert(*handle, map);
-                        Ok(())
-                    }
-                    Err(err) => Err(err),
-                }
-            })
-            .compat()
-            .map_err(|source| Error::Access {
-                errmsg: "Error reading properties on {:?}",
-                dev: self.dev_path(),
-                source,
-            })
+            .try_for_each(|handle| mapping.properties(self, *handle).map(|_| ()))
     }
 }
 
@@ -233,7 +203,7 @@ impl<A: AsRawFd + 'static> AtomicDrmDevice<A> {
             privileged: true,
             active: active.clone(),
             old_state: (Vec::new(), Vec::new(), Vec::new(), Vec::new()),
-            prop_mapping: (HashMap::new(), HashMap::new(), HashMap::new(), HashMap::new()),
+            prop_mapping: PropertyCache::new(),
             logger: log.clone(),
         };
 
@@ -285,7 +255,7 @@ impl<A: AsRawFd + 'static> AtomicDrmDevice<A> {
         let planes = plane_handles.planes();
 
         let mut old_state = dev.old_state.clone();
-        let mut mapping = dev.prop_mapping.clone();
+        let mut mapping = PropertyCache::new();
 
         // This helper function takes a snapshot of the current device properties.
         // (everything in the atomic api is set via properties.)
@@ -297,10 +267,10 @@ impl<A: AsRawFd + 'static> AtomicDrmDevice<A> {
         // And because the mapping is not consistent across devices,
         // we also need to lookup the handle for a property name.
         // And we do this a fair bit, so lets cache that mapping.
-        dev.map_props(res_handles.connectors(), &mut mapping.0)?;
-        dev.map_props(res_handles.crtcs(), &mut mapping.1)?;
-        dev.map_props(res_handles.framebuffers(), &mut mapping.2)?;
-        dev.map_props(planes, &mut mapping.3)?;
+        dev.map_props(res_handles.connectors(), &mut mapping)?;
+        dev.map_props(res_handles.crtcs(), &mut mapping)?;
+        dev.map_props(res_handles.framebuffers(), &mut mapping)?;
+        dev.map_props(planes, &mut mapping)?;
 
         dev.old_state = old_state;
         dev.prop_mapping = mapping;
@@ -323,32 +293,23 @@ impl<A: AsRawFd + 'static> AtomicDrmDevice<A> {
             for conn in res_handles.connectors() {
                 let prop = dev
                     .prop_mapping
-                    .0
-                    .get(&conn)
-                    .expect("Unknown handle")
-                    .get("CRTC_ID")
+                    .cached(*conn, "CRTC_ID")
                     .expect("Unknown property CRTC_ID");
-                req.add_property(*conn, *prop, property::Value::CRTC(None));
+                req.add_property(*conn, prop, property::Value::CRTC(None));
             }
             // A crtc without a connector has no mode, we also need to reset that.
             // Otherwise the commit will not be accepted.
             for crtc in res_handles.crtcs() {
                 let active_prop = dev
                     .prop_mapping
-                    .1
-                    .get(&crtc)
-                    .expect("Unknown handle")
-                    .get("ACTIVE")
+                    .cached(*crtc, "ACTIVE")
                     .expect("Unknown property ACTIVE");
                 let mode_prop = dev
                     .prop_mapping
-                    .1
-                    .get(&crtc)
-                    .expect("Unknown handle")
-                    .get("MODE_ID")
+                    .cached(*crtc, "MODE_ID")
                     .expect("Unknown property MODE_ID");
-                req.add_property(*crtc, *mode_prop, property::Value::Unknown(0));
-                req.add_property(*crtc, *active_prop, property::Value::Boolean(false));
+                req.add_property(*crtc, mode_prop, property::Value::Unknown(0));
+                req.add_property(*crtc, active_prop, property::Value::Boolean(false));
             }
             dev.atomic_commit(&[AtomicCommitFlags::AllowModeset], req)
                 .compat()
@@ -443,7 +404,13 @@ impl<A: AsRawFd + 'static> Device for AtomicDrmDevice<A> {
                         {
                             trace!(self.logger, "Handling event for backend {:?}", event.crtc);
                             if let Some(handler) = self.handler.as_ref() {
-                                handler.borrow_mut().vblank(event.crtc);
+                                handler.borrow_mut().vblank_with_time(
+                                    event.crtc,
+                                    VblankTime {
+                                        sequence: event.frame,
+                                        time: event.duration,
+                                    },
+                                );
                             }
                         } else {
                             self.backends.borrow_mut().remove(&event.crtc);
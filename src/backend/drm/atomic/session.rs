@@ -146,32 +146,23 @@ impl<A: AsRawFd + 'static> AtomicDrmDeviceObserver<A> {
             for conn in res_handles.connectors() {
                 let prop = dev
                     .prop_mapping
-                    .0
-                    .get(&conn)
-                    .expect("Unknown handle")
-                    .get("CRTC_ID")
+                    .cached(*conn, "CRTC_ID")
                     .expect("Unknown property CRTC_ID");
-                req.add_property(*conn, *prop, property::Value::CRTC(None));
+                req.add_property(*conn, prop, property::Value::CRTC(None));
             }
             // A crtc without a connector has no mode, we also need to reset that.
             // Otherwise the commit will not be accepted.
             for crtc in res_handles.crtcs() {
                 let mode_prop = dev
                     .prop_mapping
-                    .1
-                    .get(&crtc)
-                    .expect("Unknown handle")
-                    .get("MODE_ID")
+                    .cached(*crtc, "MODE_ID")
                     .expect("Unknown property MODE_ID");
                 let active_prop = dev
                     .prop_mapping
-                    .1
-                    .get(&crtc)
-                    .expect("Unknown handle")
-                    .get("ACTIVE")
+                    .cached(*crtc, "ACTIVE")
                     .expect("Unknown property ACTIVE");
-                req.add_property(*crtc, *active_prop, property::Value::Boolean(false));
-                req.add_property(*crtc, *mode_prop, property::Value::Unknown(0));
+                req.add_property(*crtc, active_prop, property::Value::Boolean(false));
+                req.add_property(*crtc, mode_prop, property::Value::Unknown(0));
             }
             dev.atomic_commit(&[AtomicCommitFlags::AllowModeset], req)
                 .compat()
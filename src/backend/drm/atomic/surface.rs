@@ -115,17 +115,13 @@ impl<A: AsRawFd + 'static> AtomicDrmSurfaceInternal<A> {
         // If they don't match, `commit_pending` will return true and they will be changed on the next `commit`.
         let mut current_connectors = HashSet::new();
         for conn in res_handles.connectors() {
-            let crtc_prop = dev
-                .prop_mapping
-                .0
-                .get(&conn)
-                .expect("Unknown handle")
-                .get("CRTC_ID")
-                .ok_or_else(|| Error::UnknownProperty {
-                    handle: (*conn).into(),
-                    name: "CRTC_ID",
-                })
-                .map(|x| *x)?;
+            let crtc_prop =
+                dev.prop_mapping
+                    .cached(*conn, "CRTC_ID")
+                    .ok_or_else(|| Error::UnknownProperty {
+                        handle: (*conn).into(),
+                        name: "CRTC_ID",
+                    })?;
             if let (Ok(crtc_prop_info), Ok(props)) = (dev.get_property(crtc_prop), dev.get_properties(*conn))
             {
                 let (ids, vals) = props.as_props_and_values();
@@ -210,6 +206,47 @@ impl<A: AsRawFd + 'static> AtomicDrmSurfaceInternal<A> {
 
         Ok(fb)
     }
+
+    pub(crate) fn test_mode(&self, mode: Mode) -> Result<(), Error> {
+        if !self.dev.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let pending = self.pending.read().unwrap();
+
+        let blob = self
+            .create_property_blob(mode)
+            .compat()
+            .map_err(|source| Error::Access {
+                errmsg: "Failed to create Property Blob for mode",
+                dev: self.dev_path(),
+                source,
+            })?;
+
+        let result = self
+            .create_test_buffer(&mode)
+            .and_then(|test_fb| {
+                self.build_request(
+                    &mut pending.connectors.iter(),
+                    &mut [].iter(),
+                    &self.planes,
+                    Some(test_fb),
+                    Some(mode),
+                    Some(blob),
+                )
+            })
+            .and_then(|req| {
+                self.atomic_commit(
+                    &[AtomicCommitFlags::AllowModeset, AtomicCommitFlags::TestOnly],
+                    req,
+                )
+                .compat()
+                .map_err(|_| Error::TestFailed(self.crtc))
+            });
+
+        let _ = self.dev.destroy_property_blob(blob.into());
+        result
+    }
 }
 
 impl<A: AsRawFd + 'static> Drop for AtomicDrmSurfaceInternal<A> {
@@ -243,32 +280,23 @@ impl<A: AsRawFd + 'static> Drop for AtomicDrmSurfaceInternal<A> {
             let prop = self
                 .dev
                 .prop_mapping
-                .0
-                .get(&conn)
-                .expect("Unknown Handle")
-                .get("CRTC_ID")
+                .cached(*conn, "CRTC_ID")
                 .expect("Unknown property CRTC_ID");
-            req.add_property(*conn, *prop, property::Value::CRTC(None));
+            req.add_property(*conn, prop, property::Value::CRTC(None));
         }
         let active_prop = self
             .dev
             .prop_mapping
-            .1
-            .get(&self.crtc)
-            .expect("Unknown Handle")
-            .get("ACTIVE")
+            .cached(self.crtc, "ACTIVE")
             .expect("Unknown property ACTIVE");
         let mode_prop = self
             .dev
             .prop_mapping
-            .1
-            .get(&self.crtc)
-            .expect("Unknown Handle")
-            .get("MODE_ID")
+            .cached(self.crtc, "MODE_ID")
             .expect("Unknown property MODE_ID");
 
-        req.add_property(self.crtc, *active_prop, property::Value::Boolean(false));
-        req.add_property(self.crtc, *mode_prop, property::Value::Unknown(0));
+        req.add_property(self.crtc, active_prop, property::Value::Boolean(false));
+        req.add_property(self.crtc, mode_prop, property::Value::Unknown(0));
         if let Err(err) = self.atomic_commit(&[AtomicCommitFlags::AllowModeset], req) {
             warn!(self.logger, "Unable to disable connectors: {}", err);
         }
@@ -665,29 +693,21 @@ impl<A: AsRawFd + 'static> AtomicDrmSurfaceInternal<A> {
     ) -> Result<property::Handle, Error> {
         (*self.dev)
             .prop_mapping
-            .0
-            .get(&handle)
-            .expect("Unknown handle")
-            .get(name)
+            .cached(handle, name)
             .ok_or_else(|| Error::UnknownProperty {
                 handle: handle.into(),
                 name,
             })
-            .map(|x| *x)
     }
 
     fn crtc_prop_handle(&self, handle: crtc::Handle, name: &'static str) -> Result<property::Handle, Error> {
         (*self.dev)
             .prop_mapping
-            .1
-            .get(&handle)
-            .expect("Unknown handle")
-            .get(name)
+            .cached(handle, name)
             .ok_or_else(|| Error::UnknownProperty {
                 handle: handle.into(),
                 name,
             })
-            .map(|x| *x)
     }
 
     #[allow(dead_code)]
@@ -698,15 +718,11 @@ impl<A: AsRawFd + 'static> AtomicDrmSurfaceInternal<A> {
     ) -> Result<property::Handle, Error> {
         (*self.dev)
             .prop_mapping
-            .2
-            .get(&handle)
-            .expect("Unknown handle")
-            .get(name)
+            .cached(handle, name)
             .ok_or_else(|| Error::UnknownProperty {
                 handle: handle.into(),
                 name,
             })
-            .map(|x| *x)
     }
 
     fn plane_prop_handle(
@@ -716,15 +732,11 @@ impl<A: AsRawFd + 'static> AtomicDrmSurfaceInternal<A> {
     ) -> Result<property::Handle, Error> {
         (*self.dev)
             .prop_mapping
-            .3
-            .get(&handle)
-            .expect("Unknown handle")
-            .get(name)
+            .cached(handle, name)
             .ok_or_else(|| Error::UnknownProperty {
                 handle: handle.into(),
                 name,
             })
-            .map(|x| *x)
     }
 
     // If a mode is set a matching blob needs to be set (the inverse is not true)
@@ -932,17 +944,16 @@ impl<A: AsRawFd + 'static> AtomicDrmSurfaceInternal<A> {
                     .unwrap_or(false)
             })
             .filter_map(|plane| {
+                let type_prop = card.prop_mapping.cached(plane, "type")?;
                 if let Ok(props) = card.get_properties(plane) {
                     let (ids, vals) = props.as_props_and_values();
                     for (&id, &val) in ids.iter().zip(vals.iter()) {
-                        if let Ok(info) = card.get_property(id) {
-                            if info.name().to_str().map(|x| x == "type").unwrap_or(false) {
-                                if val == (PlaneType::Primary as u32).into() {
-                                    return Some((PlaneType::Primary, plane));
-                                }
-                                if val == (PlaneType::Cursor as u32).into() {
-                                    return Some((PlaneType::Cursor, plane));
-                                }
+                        if id == type_prop {
+                            if val == (PlaneType::Primary as u32).into() {
+                                return Some((PlaneType::Primary, plane));
+                            }
+                            if val == (PlaneType::Cursor as u32).into() {
+                                return Some((PlaneType::Cursor, plane));
                             }
                         }
                     }
@@ -1007,6 +1018,17 @@ pub struct AtomicDrmSurface<A: AsRawFd + 'static>(
     pub(in crate::backend::drm) Arc<AtomicDrmSurfaceInternal<A>>,
 );
 
+impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
+    /// Tests if the given [`Mode`] could be used with the pending connectors of this surface
+    ///
+    /// This does an atomic test commit without changing the pending state, which
+    /// makes it useful to validate custom modes (see [`common::mode`](crate::backend::drm::common::mode))
+    /// before calling [`use_mode`](Surface::use_mode).
+    pub fn test_mode(&self, mode: Mode) -> Result<(), Error> {
+        self.0.test_mode(mode)
+    }
+}
+
 impl<A: AsRawFd + 'static> AsRawFd for AtomicDrmSurface<A> {
     fn as_raw_fd(&self) -> RawFd {
         self.0.as_raw_fd()
@@ -0,0 +1,216 @@
+//!
+//! Support for writeback connectors.
+//!
+//! Writeback connectors are virtual connectors exposed by some display controllers,
+//! which write the composited output of a crtc back into a framebuffer instead of
+//! sending it to a monitor. This allows capturing the contents of an output without
+//! any additional GPU work, which is useful for screen recording or mirroring.
+//!
+//! Writeback connectors are hidden from userspace unless requested, use
+//! [`enable_writeback_connectors`] on an [`AtomicDrmDevice`](super::AtomicDrmDevice)
+//! to make them visible. They are then listed together with all other connectors of the device,
+//! so make sure to skip them (see [`WritebackConnector::probe`]) when looking for outputs.
+//!
+//! To capture a frame, [`queue`](WritebackConnector::queue) a framebuffer of one of the
+//! [supported formats](WritebackConnector::formats), for example created from a dmabuf.
+//! The returned [`WritebackJob`] provides a fence, that signals once the framebuffer contains the
+//! next frame of the crtc.
+//!
+
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use drm::control::{
+    atomic::AtomicModeReq, connector, crtc, framebuffer, property, AtomicCommitFlags, Device as ControlDevice,
+};
+use failure::ResultExt;
+
+use crate::backend::drm::{
+    common::{typed_property::PropertyCache, Error},
+    DevPath,
+};
+
+/// `DRM_CLIENT_CAP_WRITEBACK_CONNECTORS`
+const CLIENT_CAP_WRITEBACK_CONNECTORS: u64 = 5;
+
+/// Make the writeback connectors of a device visible
+///
+/// This requires the device to have atomic mode-setting enabled, which is always
+/// the case for an [`AtomicDrmDevice`](super::AtomicDrmDevice).
+pub fn enable_writeback_connectors<D>(dev: &D) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    drm_ffi::set_capability(dev.as_raw_fd(), CLIENT_CAP_WRITEBACK_CONNECTORS, true)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Error enabling WritebackConnectors",
+            dev: dev.dev_path(),
+            source,
+        })?;
+    Ok(())
+}
+
+/// A writeback connector of a device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WritebackConnector {
+    handle: connector::Handle,
+    formats: Vec<u32>,
+    crtc_prop: property::Handle,
+    fb_prop: property::Handle,
+    fence_prop: property::Handle,
+}
+
+impl WritebackConnector {
+    /// Check if a connector is a writeback connector
+    ///
+    /// Returns `None` for regular connectors.
+    pub fn probe<D>(dev: &D, connector: connector::Handle) -> Result<Option<WritebackConnector>, Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        // the properties of the connector are read once
+        let mut props = PropertyCache::new();
+        let fb_prop = match props.handle(dev, connector, "WRITEBACK_FB_ID")? {
+            Some(prop) => prop,
+            None => return Ok(None),
+        };
+        let required = |name| {
+            props
+                .cached(connector, name)
+                .ok_or_else(|| Error::UnknownProperty {
+                    handle: connector.into(),
+                    name,
+                })
+        };
+        let fence_prop = required("WRITEBACK_OUT_FENCE_PTR")?;
+        let crtc_prop = required("CRTC_ID")?;
+
+        let formats = match props.get(dev, connector, "WRITEBACK_PIXEL_FORMATS")? {
+            Some(prop) => match prop.value() {
+                property::Value::Blob(blob) if blob != 0 => {
+                    let data = dev
+                        .get_property_blob(blob)
+                        .compat()
+                        .map_err(|source| Error::Access {
+                            errmsg: "Error reading writeback formats",
+                            dev: dev.dev_path(),
+                            source,
+                        })?;
+                    data.chunks_exact(4)
+                        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+                        .collect()
+                }
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+
+        Ok(Some(WritebackConnector {
+            handle: connector,
+            formats,
+            crtc_prop,
+            fb_prop,
+            fence_prop,
+        }))
+    }
+
+    /// Handle of the connector
+    pub fn handle(&self) -> connector::Handle {
+        self.handle
+    }
+
+    /// Pixel formats supported by this connector, as fourcc codes
+    pub fn formats(&self) -> &[u32] {
+        &self.formats
+    }
+
+    /// Queue a framebuffer to receive the next frame of a crtc
+    ///
+    /// The crtc needs to be active. The framebuffer must use one of the supported
+    /// [`formats`](WritebackConnector::formats) and have the size of the current mode of the crtc.
+    ///
+    /// Attaching the connector to a crtc for the first time may require a modeset, which
+    /// will only be allowed if `allow_modeset` is set.
+    pub fn queue<D>(
+        &self,
+        dev: &D,
+        crtc: crtc::Handle,
+        framebuffer: framebuffer::Handle,
+        allow_modeset: bool,
+    ) -> Result<WritebackJob, Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        let mut fence: i32 = -1;
+        let mut req = AtomicModeReq::new();
+        req.add_property(self.handle, self.crtc_prop, property::Value::CRTC(Some(crtc)));
+        req.add_property(
+            self.handle,
+            self.fb_prop,
+            property::Value::Framebuffer(Some(framebuffer)),
+        );
+        // the kernel writes the file descriptor of the fence into this pointer during the commit
+        req.add_raw_property(
+            self.handle.into(),
+            self.fence_prop,
+            &mut fence as *mut i32 as usize as u64,
+        );
+
+        let flags: &[AtomicCommitFlags] = if allow_modeset {
+            &[AtomicCommitFlags::AllowModeset]
+        } else {
+            &[]
+        };
+        dev.atomic_commit(flags, req)
+            .compat()
+            .map_err(|source| Error::Access {
+                errmsg: "Error queuing writeback framebuffer",
+                dev: dev.dev_path(),
+                source,
+            })?;
+
+        Ok(WritebackJob { framebuffer, fence })
+    }
+}
+
+/// A pending capture of a frame into a framebuffer
+///
+/// The framebuffer must not be destroyed or reused, until the capture is done.
+#[derive(Debug)]
+pub struct WritebackJob {
+    framebuffer: framebuffer::Handle,
+    fence: RawFd,
+}
+
+impl WritebackJob {
+    /// Framebuffer receiving the frame
+    pub fn framebuffer(&self) -> framebuffer::Handle {
+        self.framebuffer
+    }
+
+    /// Check if the capture is done, without blocking
+    pub fn is_done(&self) -> bool {
+        if self.fence < 0 {
+            return true;
+        }
+        let mut fds = [nix::poll::PollFd::new(self.fence, nix::poll::PollFlags::POLLIN)];
+        matches!(nix::poll::poll(&mut fds, 0), Ok(n) if n > 0)
+    }
+}
+
+/// The fence becomes readable, once the capture is done.
+///
+/// You can use this to insert the job into your event loop.
+impl AsRawFd for WritebackJob {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fence
+    }
+}
+
+impl Drop for WritebackJob {
+    fn drop(&mut self) {
+        if self.fence >= 0 {
+            let _ = nix::unistd::close(self.fence);
+        }
+    }
+}
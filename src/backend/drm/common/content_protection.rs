@@ -0,0 +1,215 @@
+//!
+//! Content protection (HDCP) via the `Content Protection` and `HDCP Content Type`
+//! connector properties.
+//!
+//! Requesting protection for a connector with [`set_content_protection`] makes the kernel
+//! try to authenticate the link to the monitor. The state switches from
+//! [`Desired`](ContentProtection::Desired) to [`Enabled`](ContentProtection::Enabled) once
+//! the link is encrypted, and back to `Desired` if the protection is lost (e.g. because the
+//! monitor was unplugged). Protected content should only be shown on outputs whose
+//! connectors are `Enabled`.
+//!
+//! The kernel sends a `udev` change event for the connector whenever the state changes,
+//! use a [`ContentProtectionMonitor`] to find out what changed.
+//!
+
+use drm::control::{connector, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::{find_property, tracker::ConnectorTracker, Error};
+use crate::backend::drm::DevPath;
+
+/// Content protection state of a connector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentProtection {
+    /// Content protection is not requested
+    Undesired,
+    /// Content protection is requested, but not (yet) established
+    Desired,
+    /// The link is protected
+    Enabled,
+}
+
+impl ContentProtection {
+    fn from_raw(value: u64) -> Option<ContentProtection> {
+        match value {
+            0 => Some(ContentProtection::Undesired),
+            1 => Some(ContentProtection::Desired),
+            2 => Some(ContentProtection::Enabled),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> u64 {
+        match self {
+            ContentProtection::Undesired => 0,
+            ContentProtection::Desired => 1,
+            ContentProtection::Enabled => 2,
+        }
+    }
+}
+
+/// Type of the content to protect
+///
+/// Type 1 content requires HDCP 2.2 or newer, type 0 content may also be protected using HDCP 1.4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HdcpContentType {
+    /// HDCP type 0 content
+    Type0,
+    /// HDCP type 1 content
+    Type1,
+}
+
+impl HdcpContentType {
+    fn from_raw(value: u64) -> Option<HdcpContentType> {
+        match value {
+            0 => Some(HdcpContentType::Type0),
+            1 => Some(HdcpContentType::Type1),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> u64 {
+        match self {
+            HdcpContentType::Type0 => 0,
+            HdcpContentType::Type1 => 1,
+        }
+    }
+}
+
+/// Read the current content protection state of a connector
+///
+/// Returns `None` if the connector does not support content protection.
+pub fn content_protection<D>(
+    dev: &D,
+    connector: connector::Handle,
+) -> Result<Option<ContentProtection>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    Ok(find_property(dev, connector, "Content Protection")?
+        .and_then(|(_, _, value)| ContentProtection::from_raw(value)))
+}
+
+/// Request or revoke content protection for a connector
+///
+/// Only [`Undesired`](ContentProtection::Undesired) and [`Desired`](ContentProtection::Desired)
+/// may be requested, the kernel switches to `Enabled` by itself.
+///
+/// Fails with [`Error::UnknownProperty`] if the connector does not support content protection.
+pub fn set_content_protection<D>(
+    dev: &D,
+    connector: connector::Handle,
+    state: ContentProtection,
+) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let state = match state {
+        ContentProtection::Enabled => ContentProtection::Desired,
+        x => x,
+    };
+    set_property(dev, connector, "Content Protection", state.to_raw())
+}
+
+/// Read the content type a connector is protecting
+///
+/// Returns `None` if the connector does not support content types.
+pub fn hdcp_content_type<D>(dev: &D, connector: connector::Handle) -> Result<Option<HdcpContentType>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    Ok(find_property(dev, connector, "HDCP Content Type")?
+        .and_then(|(_, _, value)| HdcpContentType::from_raw(value)))
+}
+
+/// Change the content type a connector is protecting
+///
+/// Changing the type while content protection is enabled makes the kernel re-authenticate
+/// the link. Fails with [`Error::UnknownProperty`] if the connector does not support content types.
+pub fn set_hdcp_content_type<D>(
+    dev: &D,
+    connector: connector::Handle,
+    content_type: HdcpContentType,
+) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    set_property(dev, connector, "HDCP Content Type", content_type.to_raw())
+}
+
+fn set_property<D>(dev: &D, connector: connector::Handle, name: &'static str, value: u64) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let (prop, _, _) = find_property(dev, connector, name)?.ok_or_else(|| Error::UnknownProperty {
+        handle: connector.into(),
+        name,
+    })?;
+    dev.set_property(connector, prop, value)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Failed to set content protection property",
+            dev: dev.dev_path(),
+            source,
+        })
+}
+
+/// Tracker for the content protection state of a set of connectors
+///
+/// [`ContentProtectionMonitor::refresh`] reports all connectors whose state changed since the
+/// last time they were checked. You'll typically call it when receiving a change event
+/// for the device from `udev`.
+#[derive(Debug, Default)]
+pub struct ContentProtectionMonitor {
+    states: ConnectorTracker<ContentProtection>,
+}
+
+impl ContentProtectionMonitor {
+    /// Create a new monitor not tracking any connector yet
+    pub fn new() -> ContentProtectionMonitor {
+        ContentProtectionMonitor::default()
+    }
+
+    /// Last known content protection state of a connector
+    pub fn get(&self, connector: connector::Handle) -> Option<ContentProtection> {
+        self.states.get(connector)
+    }
+
+    /// Request or revoke content protection for a connector and remember it
+    pub fn set<D>(
+        &mut self,
+        dev: &D,
+        connector: connector::Handle,
+        state: ContentProtection,
+    ) -> Result<(), Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        set_content_protection(dev, connector, state)?;
+        if let Some(state) = content_protection(dev, connector)? {
+            self.states.track(connector, state);
+        }
+        Ok(())
+    }
+
+    /// Stop tracking a connector, e.g. because it was disconnected
+    pub fn remove(&mut self, connector: connector::Handle) {
+        self.states.untrack(connector);
+    }
+
+    /// Read the content protection state of the given connectors and return those that changed
+    ///
+    /// Connectors seen for the first time are not reported, only recorded.
+    pub fn refresh<D>(
+        &mut self,
+        dev: &D,
+        connectors: &[connector::Handle],
+    ) -> Result<Vec<(connector::Handle, ContentProtection)>, Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        self.states
+            .refresh(connectors, |conn| content_protection(dev, conn))
+    }
+}
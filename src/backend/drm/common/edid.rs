@@ -0,0 +1,234 @@
+//!
+//! Parsing of EDID (Extended Display Identification Data) blobs.
+//!
+//! Monitors describe themselves via EDID, which the kernel exposes as the `EDID` blob
+//! property of a [`connector`](drm::control::connector). This module extracts the
+//! information compositors usually need to identify an output from the base block:
+//! its manufacturer, model and serial as well as its physical size.
+//!
+//! Use [`Device::get_connector_edid`](crate::backend::drm::Device::get_connector_edid) to read
+//! and parse the EDID of a connector of a drm device, or [`connector_edid`] for any open device,
+//! and [`EdidInfo::physical_properties`] to advertise it through a `wl_output`.
+//!
+
+use drm::control::{connector, property, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::{find_property, Error};
+use crate::backend::drm::DevPath;
+
+/// Length of the EDID base block
+const BLOCK_LENGTH: usize = 128;
+/// Fixed header every EDID base block starts with
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+/// Errors thrown when parsing an EDID blob
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum EdidError {
+    /// The blob is shorter than an EDID base block
+    #[error("EDID blob is too short ({0} bytes)")]
+    TooShort(usize),
+    /// The blob does not start with the EDID header
+    #[error("EDID blob has an invalid header")]
+    InvalidHeader,
+    /// The checksum of the base block does not match
+    #[error("EDID base block has an invalid checksum")]
+    InvalidChecksum,
+}
+
+/// Identification information of a monitor parsed from its EDID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdidInfo {
+    /// Three-letter PNP id of the manufacturer (e.g. `"DEL"`)
+    pub manufacturer: String,
+    /// Product code assigned by the manufacturer
+    pub product_code: u16,
+    /// Model name of the monitor
+    ///
+    /// Taken from the display name descriptor, if present, otherwise this is the
+    /// product code formatted as hexadecimal.
+    pub model: String,
+    /// Serial number of the monitor
+    ///
+    /// Taken from the serial number descriptor, if present, otherwise this is the
+    /// numeric serial of the base block. `None` if neither is set.
+    pub serial: Option<String>,
+    /// Physical size of the monitor in millimeters
+    ///
+    /// EDID only provides the size in centimeters, `(0, 0)` means the size is unknown
+    /// or variable (e.g. projectors).
+    pub physical_size: (u32, u32),
+}
+
+impl EdidInfo {
+    /// Parse the base block of an EDID blob
+    pub fn parse(edid: &[u8]) -> Result<EdidInfo, EdidError> {
+        if edid.len() < BLOCK_LENGTH {
+            return Err(EdidError::TooShort(edid.len()));
+        }
+        let block = &edid[..BLOCK_LENGTH];
+        if block[..8] != HEADER {
+            return Err(EdidError::InvalidHeader);
+        }
+        if block.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(EdidError::InvalidChecksum);
+        }
+
+        // three 5-bit letters, 'A' being 1, stored big-endian
+        let id = u16::from_be_bytes([block[8], block[9]]);
+        let manufacturer = [(id >> 10) & 0x1F, (id >> 5) & 0x1F, id & 0x1F]
+            .iter()
+            .map(|&c| (b'A' - 1 + c as u8) as char)
+            .collect::<String>();
+        let product_code = u16::from_le_bytes([block[10], block[11]]);
+        let serial_number = u32::from_le_bytes([block[12], block[13], block[14], block[15]]);
+        let physical_size = (block[21] as u32 * 10, block[22] as u32 * 10);
+
+        let mut name = None;
+        let mut serial = None;
+        for descriptor in block[54..126].chunks(18) {
+            // display descriptors start with a zero pixel clock
+            if descriptor[0] != 0 || descriptor[1] != 0 {
+                continue;
+            }
+            match descriptor[3] {
+                0xFC => name = descriptor_string(&descriptor[5..]),
+                0xFF => serial = descriptor_string(&descriptor[5..]),
+                _ => {}
+            }
+        }
+
+        Ok(EdidInfo {
+            manufacturer,
+            product_code,
+            model: name.unwrap_or_else(|| format!("0x{:04X}", product_code)),
+            serial: serial.or_else(|| {
+                if serial_number != 0 {
+                    Some(serial_number.to_string())
+                } else {
+                    None
+                }
+            }),
+            physical_size,
+        })
+    }
+}
+
+// Descriptor strings are up to 13 bytes, terminated by a line feed and padded with spaces
+fn descriptor_string(data: &[u8]) -> Option<String> {
+    let text = data
+        .iter()
+        .take_while(|&&c| c != b'\n')
+        .map(|&c| c as char)
+        .collect::<String>();
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_owned())
+    }
+}
+
+/// Read and parse the EDID of a given connector
+///
+/// Returns `Ok(None)` if the connector has no EDID (e.g. because nothing is connected)
+/// or the EDID could not be parsed.
+pub fn connector_edid<D>(dev: &D, connector: connector::Handle) -> Result<Option<EdidInfo>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let blob = match find_property(dev, connector, "EDID")? {
+        Some((_, info, val)) => match info.value_type().convert_value(val) {
+            property::Value::Blob(blob) if blob != 0 => blob,
+            _ => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    let data = dev
+        .get_property_blob(blob)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Error reading EDID blob",
+            dev: dev.dev_path(),
+            source,
+        })?;
+    Ok(EdidInfo::parse(&data).ok())
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl EdidInfo {
+    /// Create the [`PhysicalProperties`](crate::wayland::output::PhysicalProperties) of an output
+    /// from this EDID
+    pub fn physical_properties(
+        &self,
+        subpixel: wayland_server::protocol::wl_output::Subpixel,
+    ) -> crate::wayland::output::PhysicalProperties {
+        crate::wayland::output::PhysicalProperties {
+            width: self.physical_size.0 as i32,
+            height: self.physical_size.1 as i32,
+            subpixel,
+            make: self.manufacturer.clone(),
+            model: self.model.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EdidError, EdidInfo};
+
+    fn edid(name: Option<&str>, serial: Option<&str>) -> Vec<u8> {
+        let mut data = vec![0u8; 128];
+        data[..8].copy_from_slice(&[0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00]);
+        // "DEL"
+        data[8] = 0x10;
+        data[9] = 0xAC;
+        data[10] = 0x34;
+        data[11] = 0x12;
+        data[12..16].copy_from_slice(&42u32.to_le_bytes());
+        data[21] = 53;
+        data[22] = 30;
+        let mut write_descriptor = |offset: usize, tag: u8, text: &str| {
+            data[offset + 3] = tag;
+            let mut bytes = text.as_bytes().to_vec();
+            bytes.push(b'\n');
+            bytes.resize(13, b' ');
+            data[offset + 5..offset + 18].copy_from_slice(&bytes);
+        };
+        if let Some(name) = name {
+            write_descriptor(72, 0xFC, name);
+        }
+        if let Some(serial) = serial {
+            write_descriptor(90, 0xFF, serial);
+        }
+        let sum = data[..127].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        data[127] = 0u8.wrapping_sub(sum);
+        data
+    }
+
+    #[test]
+    fn parse_descriptors() {
+        let info = EdidInfo::parse(&edid(Some("DELL U2415"), Some("ABC123"))).unwrap();
+        assert_eq!(info.manufacturer, "DEL");
+        assert_eq!(info.product_code, 0x1234);
+        assert_eq!(info.model, "DELL U2415");
+        assert_eq!(info.serial.as_deref(), Some("ABC123"));
+        assert_eq!(info.physical_size, (530, 300));
+    }
+
+    #[test]
+    fn parse_without_descriptors() {
+        let info = EdidInfo::parse(&edid(None, None)).unwrap();
+        assert_eq!(info.model, "0x1234");
+        assert_eq!(info.serial.as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert_eq!(EdidInfo::parse(&[0; 12]), Err(EdidError::TooShort(12)));
+        assert_eq!(EdidInfo::parse(&[0; 128]), Err(EdidError::InvalidHeader));
+        let mut data = edid(None, None);
+        data[20] ^= 0xFF;
+        assert_eq!(EdidInfo::parse(&data), Err(EdidError::InvalidChecksum));
+    }
+}
@@ -6,7 +6,7 @@
 use crate::backend::drm::egl::{Arguments as EglDeviceArguments, EglDevice, Error as EglDeviceError};
 #[cfg(all(feature = "backend_drm_atomic", feature = "backend_drm_legacy"))]
 use crate::backend::drm::{atomic::AtomicDrmDevice, legacy::LegacyDrmDevice};
-use crate::backend::drm::{common::Error, Device, DeviceHandler, RawDevice, RawSurface, Surface};
+use crate::backend::drm::{common::Error, Device, DeviceHandler, RawDevice, RawSurface, Surface, VblankTime};
 #[cfg(all(feature = "backend_drm_gbm", feature = "backend_drm_eglstream"))]
 use crate::backend::drm::{
     eglstream::{EglStreamDevice, Error as EglStreamError},
@@ -73,6 +73,9 @@ where
     fn vblank(&mut self, crtc: crtc::Handle) {
         self.0.vblank(crtc)
     }
+    fn vblank_with_time(&mut self, crtc: crtc::Handle, time: VblankTime) {
+        self.0.vblank_with_time(crtc, time)
+    }
     fn error(&mut self, error: E1) {
         self.0.error(EitherError::Either(error));
     }
@@ -105,6 +108,9 @@ where
     fn vblank(&mut self, crtc: crtc::Handle) {
         self.0.vblank(crtc)
     }
+    fn vblank_with_time(&mut self, crtc: crtc::Handle, time: VblankTime) {
+        self.0.vblank_with_time(crtc, time)
+    }
     fn error(&mut self, error: E2) {
         self.0.error(EitherError::Or(error));
     }
@@ -0,0 +1,296 @@
+//!
+//! Pixel formats and modifiers supported by planes.
+//!
+//! Planes report the formats they can scan out through their `IN_FORMATS` property, a blob
+//! listing every supported format along with the layout modifiers (tiling, compression, ...)
+//! it can be used with. Older drivers do not expose this property, in which case only the
+//! formats of the plane are known and buffers need to use an implicit modifier.
+//!
+//! [`PlaneFormats`] reads and caches these tables, so that you can check whether a client
+//! buffer can be scanned out on a plane before trying to import it, and advertise the
+//! formats your outputs can display directly to clients. [`try_direct_scanout`] uses it to
+//! only select buffers the primary plane supports.
+//!
+//! [`try_direct_scanout`]: crate::backend::drm::scanout::try_direct_scanout
+//!
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use drm::control::{crtc, plane, property, Device as ControlDevice, PlaneType};
+use failure::ResultExt;
+
+use super::{find_property, Error};
+use crate::backend::drm::DevPath;
+
+/// Modifier used for buffers whose layout is negotiated implicitly by the driver
+pub const DRM_FORMAT_MOD_INVALID: u64 = 0x00ff_ffff_ffff_ffff;
+/// Modifier of buffers using a plain linear layout
+pub const DRM_FORMAT_MOD_LINEAR: u64 = 0;
+
+/// A combination of a pixel format and a modifier a plane can scan out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlaneFormat {
+    /// The fourcc code of the pixel format
+    pub format: u32,
+    /// The layout modifier, [`DRM_FORMAT_MOD_INVALID`] if only implicit modifiers are supported
+    pub modifier: u64,
+}
+
+// size of struct drm_format_modifier_blob
+const BLOB_HEADER_SIZE: usize = 24;
+// size of struct drm_format_modifier
+const MODIFIER_SIZE: usize = 24;
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+/// Parse the contents of an `IN_FORMATS` blob
+///
+/// Returns `None` if the blob is malformed or uses an unknown version.
+pub fn parse_in_formats(data: &[u8]) -> Option<Vec<PlaneFormat>> {
+    if data.len() < BLOB_HEADER_SIZE || read_u32(data, 0)? != 1 {
+        return None;
+    }
+    let count_formats = read_u32(data, 8)? as usize;
+    let formats_offset = read_u32(data, 12)? as usize;
+    let count_modifiers = read_u32(data, 16)? as usize;
+    let modifiers_offset = read_u32(data, 20)? as usize;
+
+    let formats = (0..count_formats)
+        .map(|i| read_u32(data, formats_offset + i * 4))
+        .collect::<Option<Vec<u32>>>()?;
+
+    let mut result = Vec::new();
+    for i in 0..count_modifiers {
+        let base = modifiers_offset + i * MODIFIER_SIZE;
+        // each modifier applies to up to 64 formats, starting at `offset`
+        let mask = read_u64(data, base)?;
+        let offset = read_u32(data, base + 8)? as usize;
+        let modifier = read_u64(data, base + 16)?;
+        for bit in 0..64 {
+            if mask & (1u64 << bit) != 0 {
+                let format = *formats.get(offset + bit)?;
+                result.push(PlaneFormat { format, modifier });
+            }
+        }
+    }
+    Some(result)
+}
+
+/// Cache of the formats supported by the planes of a device
+#[derive(Debug, Default)]
+pub struct PlaneFormats {
+    planes: HashMap<plane::Handle, Vec<PlaneFormat>>,
+    primary_planes: HashMap<crtc::Handle, Option<plane::Handle>>,
+}
+
+impl PlaneFormats {
+    /// Create a new empty cache
+    pub fn new() -> PlaneFormats {
+        PlaneFormats::default()
+    }
+
+    /// Access the formats supported by a plane
+    ///
+    /// They are read from the device the first time a plane is queried. If the plane has no
+    /// `IN_FORMATS` property, its formats are reported with [`DRM_FORMAT_MOD_INVALID`].
+    pub fn formats<D>(&mut self, dev: &D, plane: plane::Handle) -> Result<&[PlaneFormat], Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        if !self.planes.contains_key(&plane) {
+            let formats = read_plane_formats(dev, plane)?;
+            self.planes.insert(plane, formats);
+        }
+        Ok(&self.planes[&plane])
+    }
+
+    /// Access the formats supported by the primary plane of a crtc
+    ///
+    /// Returns `Ok(None)` if the device does not expose the primary plane, which requires the
+    /// universal planes client capability (enabled by the atomic backend).
+    pub fn primary_formats<D>(&mut self, dev: &D, crtc: crtc::Handle) -> Result<Option<&[PlaneFormat]>, Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        let plane = match self.primary_planes.get(&crtc) {
+            Some(&plane) => plane,
+            None => {
+                let plane = find_primary_plane(dev, crtc)?;
+                self.primary_planes.insert(crtc, plane);
+                plane
+            }
+        };
+        match plane {
+            Some(plane) => self.formats(dev, plane).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Check whether a plane supports a format with the given modifier
+    pub fn supports<D>(
+        &mut self,
+        dev: &D,
+        plane: plane::Handle,
+        format: u32,
+        modifier: u64,
+    ) -> Result<bool, Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        Ok(self
+            .formats(dev, plane)?
+            .iter()
+            .any(|f| f.format == format && f.modifier == modifier))
+    }
+
+    /// Forget all cached formats
+    pub fn clear(&mut self) {
+        self.planes.clear();
+        self.primary_planes.clear();
+    }
+}
+
+fn find_primary_plane<D>(dev: &D, crtc: crtc::Handle) -> Result<Option<plane::Handle>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let resources = dev.resource_handles().compat().map_err(|source| Error::Access {
+        errmsg: "Error loading resource handles",
+        dev: dev.dev_path(),
+        source,
+    })?;
+    let planes = dev.plane_handles().compat().map_err(|source| Error::Access {
+        errmsg: "Error loading plane handles",
+        dev: dev.dev_path(),
+        source,
+    })?;
+    for &plane in planes.planes() {
+        let compatible = dev
+            .get_plane(plane)
+            .map(|info| resources.filter_crtcs(info.possible_crtcs()).contains(&crtc))
+            .unwrap_or(false);
+        if !compatible {
+            continue;
+        }
+        if let Some((_, _, val)) = find_property(dev, plane, "type")? {
+            if val == (PlaneType::Primary as u32).into() {
+                return Ok(Some(plane));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn read_plane_formats<D>(dev: &D, plane: plane::Handle) -> Result<Vec<PlaneFormat>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    if let Some((_, info, val)) = find_property(dev, plane, "IN_FORMATS")? {
+        if let property::Value::Blob(blob) = info.value_type().convert_value(val) {
+            if blob != 0 {
+                let data = dev
+                    .get_property_blob(blob)
+                    .compat()
+                    .map_err(|source| Error::Access {
+                        errmsg: "Error reading IN_FORMATS blob",
+                        dev: dev.dev_path(),
+                        source,
+                    })?;
+                if let Some(formats) = parse_in_formats(&data) {
+                    return Ok(formats);
+                }
+            }
+        }
+    }
+
+    let info = dev.get_plane(plane).compat().map_err(|source| Error::Access {
+        errmsg: "Error loading plane info",
+        dev: dev.dev_path(),
+        source,
+    })?;
+    Ok(info
+        .formats()
+        .iter()
+        .map(|&format| PlaneFormat {
+            format,
+            modifier: DRM_FORMAT_MOD_INVALID,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_in_formats, PlaneFormat, DRM_FORMAT_MOD_LINEAR};
+
+    const XR24: u32 = 0x3432_5258;
+    const AR24: u32 = 0x3432_5241;
+    const NV12: u32 = 0x3231_564e;
+    const TILED: u64 = 0x0100_0000_0000_0001;
+
+    fn blob(formats: &[u32], modifiers: &[(u64, u32, u64)]) -> Vec<u8> {
+        let formats_offset = 24u32;
+        let modifiers_offset = formats_offset + formats.len() as u32 * 4;
+        let mut data = Vec::new();
+        for v in &[
+            1,
+            0,
+            formats.len() as u32,
+            formats_offset,
+            modifiers.len() as u32,
+            modifiers_offset,
+        ] {
+            data.extend_from_slice(&v.to_ne_bytes());
+        }
+        for f in formats {
+            data.extend_from_slice(&f.to_ne_bytes());
+        }
+        for &(mask, offset, modifier) in modifiers {
+            data.extend_from_slice(&mask.to_ne_bytes());
+            data.extend_from_slice(&offset.to_ne_bytes());
+            data.extend_from_slice(&0u32.to_ne_bytes());
+            data.extend_from_slice(&modifier.to_ne_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn parses_modifier_masks() {
+        let data = blob(
+            &[XR24, AR24, NV12],
+            &[(0b111, 0, DRM_FORMAT_MOD_LINEAR), (0b011, 0, TILED)],
+        );
+        let formats = parse_in_formats(&data).unwrap();
+        assert_eq!(formats.len(), 5);
+        assert!(formats.contains(&PlaneFormat {
+            format: NV12,
+            modifier: DRM_FORMAT_MOD_LINEAR
+        }));
+        assert!(formats.contains(&PlaneFormat {
+            format: AR24,
+            modifier: TILED
+        }));
+        assert!(!formats.contains(&PlaneFormat {
+            format: NV12,
+            modifier: TILED
+        }));
+    }
+
+    #[test]
+    fn rejects_malformed_blobs() {
+        assert!(parse_in_formats(&[0; 8]).is_none());
+        let mut data = blob(&[XR24], &[(0b11, 0, TILED)]);
+        // the mask references a format past the end of the list
+        assert!(parse_in_formats(&data).is_none());
+        data[0] = 2;
+        assert!(parse_in_formats(&data).is_none());
+    }
+}
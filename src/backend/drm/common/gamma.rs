@@ -0,0 +1,73 @@
+//!
+//! Gamma lookup tables of crtcs.
+//!
+//! The gamma table of a crtc maps the intensity of each color channel of the framebuffer to
+//! the intensity sent to the monitor. Modifying it allows to change the color temperature or
+//! the brightness of an output without re-rendering, which is what redshift-style tools do
+//! through the [`gamma_control`](crate::wayland::gamma_control) protocol.
+//!
+
+use drm::control::{crtc, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::Error;
+use crate::backend::drm::DevPath;
+
+/// Number of entries of the gamma table of each color channel of a crtc
+///
+/// A size of 0 means the gamma table of the crtc cannot be changed.
+pub fn gamma_size<D>(dev: &D, crtc: crtc::Handle) -> Result<u32, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    dev.get_crtc(crtc)
+        .compat()
+        .map(|info| info.gamma_length())
+        .map_err(|source| Error::Access {
+            errmsg: "Error loading crtc info",
+            dev: dev.dev_path(),
+            source,
+        })
+}
+
+/// Retrieve the current gamma table of a crtc
+///
+/// Returns the red, green and blue channels, each of [`gamma_size`] entries.
+pub fn gamma<D>(dev: &D, crtc: crtc::Handle) -> Result<(Vec<u16>, Vec<u16>, Vec<u16>), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let size = gamma_size(dev, crtc)? as usize;
+    let (mut red, mut green, mut blue) = (vec![0; size], vec![0; size], vec![0; size]);
+    dev.get_gamma(crtc, &mut red, &mut green, &mut blue)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Failed to get gamma table",
+            dev: dev.dev_path(),
+            source,
+        })?;
+    Ok((red, green, blue))
+}
+
+/// Set the gamma table of a crtc
+///
+/// Each channel must have exactly [`gamma_size`] entries. Save the table returned by [`gamma`]
+/// beforehand to restore it later.
+pub fn set_gamma<D>(
+    dev: &D,
+    crtc: crtc::Handle,
+    red: &[u16],
+    green: &[u16],
+    blue: &[u16],
+) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    dev.set_gamma(crtc, red, green, blue)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Failed to set gamma table",
+            dev: dev.dev_path(),
+            source,
+        })
+}
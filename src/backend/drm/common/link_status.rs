@@ -0,0 +1,153 @@
+//!
+//! Link failure recovery via the `link-status` connector property.
+//!
+//! The kernel sets the `link-status` property of a connector to `Bad` when link training of a
+//! DisplayPort connection fails after a modeset, which typically happens with flaky cables or
+//! monitors. The output then stays black until the mode is set again, which makes the driver
+//! retrain the link, possibly at a lower bandwidth.
+//!
+//! The kernel sends a `udev` change event for the connector when its link goes bad. Call
+//! [`LinkStatusMonitor::recover`] for the surfaces of the device when receiving it: the
+//! connectors of the surface with a bad link are reset and the current mode is set again,
+//! and the connectors that were retrained are returned so you can notify the rest of your
+//! compositor (e.g. to repaint the output).
+//!
+//! Nothing in smithay calls it for you: neither the drm devices nor the `udev` backend watch
+//! the link status. Call it on the `UdevEvent::Changed` events of the `udev` backend for the
+//! device, or poll it periodically if you do not use the `udev` backend.
+//!
+
+use drm::control::{connector, framebuffer, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::{find_property, tracker::ConnectorTracker, Error};
+use crate::backend::drm::{DevPath, RawSurface, Surface};
+
+/// Number of times in a row a link is retrained before giving up
+const MAX_RETRAINS: u32 = 3;
+
+/// Status of the link of a connector
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkStatus {
+    /// The link is working
+    Good,
+    /// Link training failed, the mode needs to be set again
+    Bad,
+}
+
+impl LinkStatus {
+    fn from_raw(value: u64) -> Option<LinkStatus> {
+        match value {
+            0 => Some(LinkStatus::Good),
+            1 => Some(LinkStatus::Bad),
+            _ => None,
+        }
+    }
+}
+
+/// Read the current link status of a connector
+///
+/// Returns `None` if the connector does not report its link status.
+pub fn link_status<D>(dev: &D, connector: connector::Handle) -> Result<Option<LinkStatus>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    Ok(find_property(dev, connector, "link-status")?.and_then(|(_, _, value)| LinkStatus::from_raw(value)))
+}
+
+/// Reset the link status of a connector to `Good`, before setting its mode again
+///
+/// Fails with [`Error::UnknownProperty`] if the connector does not report its link status.
+pub fn reset_link_status<D>(dev: &D, connector: connector::Handle) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let (prop, _, _) =
+        find_property(dev, connector, "link-status")?.ok_or_else(|| Error::UnknownProperty {
+            handle: connector.into(),
+            name: "link-status",
+        })?;
+    dev.set_property(connector, prop, 0)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Failed to set link-status property",
+            dev: dev.dev_path(),
+            source,
+        })
+}
+
+/// Tracker recovering connectors from link failures
+///
+/// A connector whose link keeps failing is only retrained a few times in a row, after which
+/// [`LinkStatusMonitor::recover`] leaves it alone until its link is good again or it is
+/// [removed](LinkStatusMonitor::remove) from the monitor.
+#[derive(Debug, Default)]
+pub struct LinkStatusMonitor {
+    // number of retrains since the link was last seen good
+    retrains: ConnectorTracker<u32>,
+}
+
+impl LinkStatusMonitor {
+    /// Create a new monitor not tracking any connector yet
+    pub fn new() -> LinkStatusMonitor {
+        LinkStatusMonitor::default()
+    }
+
+    /// Stop tracking a connector, e.g. because it was disconnected
+    pub fn remove(&mut self, connector: connector::Handle) {
+        self.retrains.untrack(connector);
+    }
+
+    /// Retrain the links of the connectors of a surface that failed
+    ///
+    /// If any current connector of the surface has a bad link, its status is reset and the
+    /// current mode and connectors of the surface are committed again with the given framebuffer.
+    /// Changes pending on the surface are not applied, and are still pending afterwards.
+    /// Returns the connectors that were retrained, empty if all links were good.
+    pub fn recover<S>(
+        &mut self,
+        surface: &S,
+        framebuffer: framebuffer::Handle,
+    ) -> Result<Vec<connector::Handle>, <S as Surface>::Error>
+    where
+        S: RawSurface,
+        <S as Surface>::Error: From<Error>,
+    {
+        let current_connectors = surface.current_connectors().into_iter().collect::<Vec<_>>();
+        let mut bad = Vec::new();
+        for &conn in &current_connectors {
+            match link_status(surface, conn)? {
+                Some(LinkStatus::Bad) => {
+                    let retrains = self.retrains.get(conn).unwrap_or(0);
+                    if retrains < MAX_RETRAINS {
+                        self.retrains.track(conn, retrains + 1);
+                        bad.push(conn);
+                    }
+                }
+                _ => {
+                    self.retrains.untrack(conn);
+                }
+            }
+        }
+        if bad.is_empty() {
+            return Ok(bad);
+        }
+        for &conn in &bad {
+            reset_link_status(surface, conn)?;
+        }
+        // commit the current state, not whatever the user has pending
+        let pending_connectors = surface.pending_connectors().into_iter().collect::<Vec<_>>();
+        let pending_mode = surface.pending_mode();
+        let result = surface
+            .set_connectors(&current_connectors)
+            .and_then(|_| surface.use_mode(surface.current_mode()))
+            .and_then(|_| surface.commit(framebuffer));
+        // the pending state is restored even if the commit failed
+        let restored = surface
+            .set_connectors(&pending_connectors)
+            .and_then(|_| surface.use_mode(pending_mode));
+        result?;
+        restored?;
+        Ok(bad)
+    }
+}
@@ -3,11 +3,26 @@
 //! and [`Surface`](::backend::drm::Surface) implementations of the `backend::drm` module.
 //!
 
+use crate::backend::drm::DevPath;
 use crate::backend::graphics::SwapBuffersError;
-use drm::control::{connector, crtc, Mode, RawResourceHandle};
+use drm::control::{
+    connector, crtc, property, Device as ControlDevice, Mode, RawResourceHandle, ResourceHandle,
+};
+use failure::ResultExt;
 use std::path::PathBuf;
 
+pub mod content_protection;
+pub mod edid;
 pub mod fallback;
+pub mod formats;
+pub mod gamma;
+pub mod link_status;
+pub mod mode;
+pub mod power;
+pub mod tile;
+mod tracker;
+pub mod typed_property;
+pub mod vrr;
 
 /// Errors thrown by the [`LegacyDrmDevice`](::backend::drm::legacy::LegacyDrmDevice),
 /// [`AtomicDrmDevice`](::backend::drm::atomic::AtomicDrmDevice)
@@ -95,3 +110,35 @@ impl Into<SwapBuffersError> for Error {
         }
     }
 }
+
+/// Look up a property of a drm resource by name
+///
+/// Returns the handle, info and current raw value of the property, or `None` if the
+/// resource has no property of that name.
+pub(crate) fn find_property<D, T>(
+    dev: &D,
+    handle: T,
+    name: &str,
+) -> Result<Option<(property::Handle, property::Info, property::RawValue)>, Error>
+where
+    D: ControlDevice + DevPath,
+    T: ResourceHandle,
+{
+    let props = dev
+        .get_properties(handle)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Error reading properties",
+            dev: dev.dev_path(),
+            source,
+        })?;
+    let (ids, vals) = props.as_props_and_values();
+    for (&id, &val) in ids.iter().zip(vals.iter()) {
+        if let Ok(info) = dev.get_property(id) {
+            if info.name().to_str() == Ok(name) {
+                return Ok(Some((id, info, val)));
+            }
+        }
+    }
+    Ok(None)
+}
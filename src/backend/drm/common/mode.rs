@@ -0,0 +1,336 @@
+//!
+//! Generation and selection of display modes.
+//!
+//! Connectors advertise a list of [`Mode`]s they support, but some monitors work
+//! fine with modes they do not advertise (e.g. higher refresh rates or custom resolutions).
+//! This module allows you to describe custom modes as [`ModeTiming`]s, either directly from a
+//! modeline or generated using the VESA CVT or GTF formulas, and convert them into [`Mode`]s
+//! you can pass to [`Surface::use_mode`](crate::backend::drm::Surface::use_mode).
+//!
+//! Custom modes are not guaranteed to work. Use a validating surface method like
+//! [`AtomicDrmSurface::test_mode`](crate::backend::drm::atomic::AtomicDrmSurface::test_mode)
+//! before applying them.
+//!
+//! The [`ModeFilter`] can be used to restrict and order the advertised modes of a connector
+//! according to the preferences of your compositor.
+//!
+
+use drm::control::Mode;
+use std::os::raw::c_char;
+
+// Flags and types as defined by the kernel (`drm_mode.h`)
+const DRM_MODE_FLAG_PHSYNC: u32 = 1;
+const DRM_MODE_FLAG_NHSYNC: u32 = 1 << 1;
+const DRM_MODE_FLAG_PVSYNC: u32 = 1 << 2;
+const DRM_MODE_FLAG_NVSYNC: u32 = 1 << 3;
+const DRM_MODE_TYPE_USERDEF: u32 = 1 << 5;
+
+/// Granularity of horizontal timings in pixels
+const CELL_GRANULARITY: u32 = 8;
+/// Minimal vertical front porch in lines
+const MIN_V_PORCH: u32 = 3;
+/// Minimal duration of vertical sync and back porch in µs
+const MIN_VSYNC_BP: f64 = 550.0;
+/// Minimal vertical back porch in lines
+const MIN_V_BPORCH: u32 = 6;
+/// Blanking formula gradient and offset (`M'` and `C'`)
+const M_PRIME: f64 = 300.0;
+const C_PRIME: f64 = 30.0;
+/// Minimal duration of the vertical blanking in µs for reduced blanking
+const RB_MIN_V_BLANK: f64 = 460.0;
+/// Horizontal blanking and sync width in pixels for reduced blanking
+const RB_H_BLANK: u32 = 160;
+const RB_H_SYNC: u32 = 32;
+/// Pixel clock granularity of CVT in kHz
+const CVT_CLOCK_STEP: u32 = 250;
+
+/// Detailed timings of a display mode
+///
+/// This is the information contained in an X11 modeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeTiming {
+    /// Pixel clock in kHz
+    pub clock: u32,
+    /// Visible width in pixels
+    pub hdisplay: u16,
+    /// Start of the horizontal sync pulse
+    pub hsync_start: u16,
+    /// End of the horizontal sync pulse
+    pub hsync_end: u16,
+    /// Total width including blanking
+    pub htotal: u16,
+    /// Visible height in lines
+    pub vdisplay: u16,
+    /// Start of the vertical sync pulse
+    pub vsync_start: u16,
+    /// End of the vertical sync pulse
+    pub vsync_end: u16,
+    /// Total height including blanking
+    pub vtotal: u16,
+    /// Whether the horizontal sync pulse is positive
+    pub hsync_positive: bool,
+    /// Whether the vertical sync pulse is positive
+    pub vsync_positive: bool,
+}
+
+impl ModeTiming {
+    /// Generate timings using the VESA Coordinated Video Timings formula
+    ///
+    /// `refresh` is given in Hz. Reduced blanking lowers the required pixel clock and should
+    /// be preferred for digital connections (DisplayPort, HDMI, DVI), as long as the monitor supports it.
+    pub fn cvt(width: u16, height: u16, refresh: f64, reduced_blanking: bool) -> ModeTiming {
+        let hdisplay = u32::from(width) - u32::from(width) % CELL_GRANULARITY;
+        let vdisplay = u32::from(height);
+        let vsync = cvt_vsync_width(u32::from(width), vdisplay);
+
+        let (hperiod, htotal, hsync_start, hsync_end, vtotal, vsync_start) = if reduced_blanking {
+            let hperiod = (1_000_000.0 / refresh - RB_MIN_V_BLANK) / f64::from(vdisplay);
+            let vbi_lines = ((RB_MIN_V_BLANK / hperiod) as u32 + 1).max(MIN_V_PORCH + vsync + MIN_V_BPORCH);
+            let hsync_end = hdisplay + RB_H_BLANK / 2;
+            (
+                hperiod,
+                hdisplay + RB_H_BLANK,
+                hsync_end - RB_H_SYNC,
+                hsync_end,
+                vdisplay + vbi_lines,
+                vdisplay + MIN_V_PORCH,
+            )
+        } else {
+            let hperiod = (1_000_000.0 / refresh - MIN_VSYNC_BP) / f64::from(vdisplay + MIN_V_PORCH);
+            let vsync_bp = ((MIN_VSYNC_BP / hperiod) as u32 + 1).max(vsync + MIN_V_BPORCH);
+            let blank_percentage = (C_PRIME - M_PRIME * hperiod / 1000.0).max(20.0);
+            let mut hblank = (f64::from(hdisplay) * blank_percentage / (100.0 - blank_percentage)) as u32;
+            hblank -= hblank % (2 * CELL_GRANULARITY);
+            let htotal = hdisplay + hblank;
+            let hsync_end = hdisplay + hblank / 2;
+            let hsync_width = htotal * 8 / 100 / CELL_GRANULARITY * CELL_GRANULARITY;
+            let hsync_start = hsync_end - hsync_width;
+            (
+                hperiod,
+                htotal,
+                hsync_start,
+                hsync_end,
+                vdisplay + vsync_bp + MIN_V_PORCH,
+                vdisplay + MIN_V_PORCH,
+            )
+        };
+
+        let mut clock = (f64::from(htotal) * 1000.0 / hperiod) as u32;
+        clock -= clock % CVT_CLOCK_STEP;
+
+        ModeTiming {
+            clock,
+            hdisplay: hdisplay as u16,
+            hsync_start: hsync_start as u16,
+            hsync_end: hsync_end as u16,
+            htotal: htotal as u16,
+            vdisplay: vdisplay as u16,
+            vsync_start: vsync_start as u16,
+            vsync_end: (vsync_start + vsync) as u16,
+            vtotal: vtotal as u16,
+            hsync_positive: reduced_blanking,
+            vsync_positive: !reduced_blanking,
+        }
+    }
+
+    /// Generate timings using the VESA Generalized Timing Formula
+    ///
+    /// `refresh` is given in Hz. GTF predates CVT and is mostly useful for older analog monitors.
+    pub fn gtf(width: u16, height: u16, refresh: f64) -> ModeTiming {
+        let cell = f64::from(CELL_GRANULARITY);
+        let hdisplay = (f64::from(width) / cell).round() * cell;
+        let vdisplay = f64::from(height);
+
+        let hperiod_est = (1.0 / refresh - MIN_VSYNC_BP / 1_000_000.0) / (vdisplay + 1.0) * 1_000_000.0;
+        let vsync_bp = (MIN_VSYNC_BP / hperiod_est).round();
+        let vtotal = vdisplay + vsync_bp + 1.0;
+        let refresh_est = 1.0 / hperiod_est / vtotal * 1_000_000.0;
+        let hperiod = hperiod_est / (refresh / refresh_est);
+
+        let duty_cycle = C_PRIME - M_PRIME * hperiod / 1000.0;
+        let hblank = (hdisplay * duty_cycle / (100.0 - duty_cycle) / (2.0 * cell)).round() * 2.0 * cell;
+        let htotal = hdisplay + hblank;
+        let hsync = (0.08 * htotal / cell).round() * cell;
+        let hsync_start = hdisplay + hblank / 2.0 - hsync;
+
+        ModeTiming {
+            clock: (htotal / hperiod * 1000.0) as u32,
+            hdisplay: hdisplay as u16,
+            hsync_start: hsync_start as u16,
+            hsync_end: (hsync_start + hsync) as u16,
+            htotal: htotal as u16,
+            vdisplay: vdisplay as u16,
+            vsync_start: (vdisplay + 1.0) as u16,
+            vsync_end: (vdisplay + 4.0) as u16,
+            vtotal: vtotal as u16,
+            hsync_positive: false,
+            vsync_positive: true,
+        }
+    }
+
+    /// Refresh rate resulting from these timings in mHz
+    pub fn refresh(&self) -> u32 {
+        let pixels = u64::from(self.htotal) * u64::from(self.vtotal);
+        if pixels == 0 {
+            return 0;
+        }
+        (u64::from(self.clock) * 1_000_000 / pixels) as u32
+    }
+}
+
+// vsync width is used to encode the aspect ratio in CVT timings
+fn cvt_vsync_width(width: u32, height: u32) -> u32 {
+    if width * 3 == height * 4 {
+        4
+    } else if width * 9 == height * 16 {
+        5
+    } else if width * 10 == height * 16 {
+        6
+    } else if width * 4 == height * 5 || width * 9 == height * 15 {
+        7
+    } else {
+        10
+    }
+}
+
+impl From<ModeTiming> for Mode {
+    fn from(timing: ModeTiming) -> Mode {
+        let mut flags = 0;
+        flags |= if timing.hsync_positive {
+            DRM_MODE_FLAG_PHSYNC
+        } else {
+            DRM_MODE_FLAG_NHSYNC
+        };
+        flags |= if timing.vsync_positive {
+            DRM_MODE_FLAG_PVSYNC
+        } else {
+            DRM_MODE_FLAG_NVSYNC
+        };
+
+        let mut name = [0 as c_char; 32];
+        let text = format!("{}x{}", timing.hdisplay, timing.vdisplay);
+        for (dst, src) in name.iter_mut().zip(text.bytes().take(31)) {
+            *dst = src as c_char;
+        }
+
+        Mode::from(drm_ffi::drm_mode_modeinfo {
+            clock: timing.clock,
+            hdisplay: timing.hdisplay,
+            hsync_start: timing.hsync_start,
+            hsync_end: timing.hsync_end,
+            htotal: timing.htotal,
+            hskew: 0,
+            vdisplay: timing.vdisplay,
+            vsync_start: timing.vsync_start,
+            vsync_end: timing.vsync_end,
+            vtotal: timing.vtotal,
+            vscan: 0,
+            vrefresh: (timing.refresh() + 500) / 1000,
+            flags,
+            type_: DRM_MODE_TYPE_USERDEF,
+            name,
+        })
+    }
+}
+
+/// Filter restricting and ordering a list of [`Mode`]s
+///
+/// Modes not matching the constraints are removed, the remaining ones are sorted
+/// by descending resolution. Modes of the same resolution are ordered by their distance
+/// to the preferred refresh rate, if any, or by descending refresh rate otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModeFilter {
+    /// Maximum size of the modes
+    pub max_size: Option<(u16, u16)>,
+    /// Minimum refresh rate in Hz
+    pub min_refresh: Option<u32>,
+    /// Maximum refresh rate in Hz
+    pub max_refresh: Option<u32>,
+    /// Preferred refresh rate in Hz
+    pub preferred_refresh: Option<u32>,
+}
+
+impl ModeFilter {
+    /// Check if a mode matches the constraints of this filter
+    pub fn matches(&self, mode: &Mode) -> bool {
+        let (w, h) = mode.size();
+        let refresh = mode.vrefresh();
+        self.max_size.map(|(mw, mh)| w <= mw && h <= mh).unwrap_or(true)
+            && self.min_refresh.map(|min| refresh >= min).unwrap_or(true)
+            && self.max_refresh.map(|max| refresh <= max).unwrap_or(true)
+    }
+
+    /// Filter and sort the given modes
+    pub fn apply<I: IntoIterator<Item = Mode>>(&self, modes: I) -> Vec<Mode> {
+        let mut modes = modes
+            .into_iter()
+            .filter(|mode| self.matches(mode))
+            .collect::<Vec<_>>();
+        modes.sort_by_key(|mode| {
+            let (w, h) = mode.size();
+            let refresh = mode.vrefresh();
+            let refresh_key = match self.preferred_refresh {
+                Some(preferred) => (i64::from(refresh) - i64::from(preferred)).abs(),
+                None => -i64::from(refresh),
+            };
+            (-(i64::from(w) * i64::from(h)), refresh_key)
+        });
+        modes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModeTiming;
+
+    fn timings(t: ModeTiming) -> (u32, [u16; 8]) {
+        (
+            t.clock,
+            [
+                t.hdisplay,
+                t.hsync_start,
+                t.hsync_end,
+                t.htotal,
+                t.vdisplay,
+                t.vsync_start,
+                t.vsync_end,
+                t.vtotal,
+            ],
+        )
+    }
+
+    #[test]
+    fn cvt_matches_reference() {
+        // cvt 1920 1080 60
+        assert_eq!(
+            timings(ModeTiming::cvt(1920, 1080, 60.0, false)),
+            (173_000, [1920, 2048, 2248, 2576, 1080, 1083, 1088, 1120])
+        );
+        // cvt -r 1920 1080 60
+        let rb = ModeTiming::cvt(1920, 1080, 60.0, true);
+        assert_eq!(
+            timings(rb),
+            (138_500, [1920, 1968, 2000, 2080, 1080, 1083, 1088, 1111])
+        );
+        assert!(rb.hsync_positive && !rb.vsync_positive);
+        assert_eq!((rb.refresh() + 500) / 1000, 60);
+    }
+
+    #[test]
+    fn cvt_aligned_hsync() {
+        // 8% of htotal is already a multiple of the cell granularity here,
+        // the sync pulse must not be shortened by an extra cell
+        assert_eq!(
+            timings(ModeTiming::cvt(640, 480, 60.0, false)),
+            (23_750, [640, 656, 720, 800, 480, 483, 487, 500])
+        );
+    }
+
+    #[test]
+    fn gtf_matches_reference() {
+        // gtf 1920 1080 60
+        let (clock, t) = timings(ModeTiming::gtf(1920, 1080, 60.0));
+        assert_eq!(clock / 100, 1727);
+        assert_eq!(t, [1920, 2040, 2248, 2576, 1080, 1081, 1084, 1118]);
+    }
+}
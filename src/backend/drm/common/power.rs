@@ -0,0 +1,134 @@
+//!
+//! Output power management via the `DPMS` connector property.
+//!
+//! Use [`set_power_state`] to blank or unblank individual outputs, e.g. when the
+//! session becomes idle, and [`PowerStateMonitor`] to notice power state changes that were
+//! not triggered by your compositor (e.g. another drm master or the kernel turning
+//! an output off).
+//!
+//! Note that turning an output off does not release its crtc. Surfaces of powered off
+//! outputs will fail to page-flip, so you should stop rendering to them until they are turned
+//! on again.
+//!
+
+use drm::control::{connector, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::{find_property, tracker::ConnectorTracker, Error};
+use crate::backend::drm::DevPath;
+
+/// Power state of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PowerState {
+    /// The output is powered on
+    On,
+    /// The output is blanked, but can resume quickly
+    Standby,
+    /// The output is in a low power state, that may take longer to resume from
+    Suspend,
+    /// The output is powered off
+    Off,
+}
+
+impl PowerState {
+    fn from_raw(value: u64) -> Option<PowerState> {
+        match value {
+            0 => Some(PowerState::On),
+            1 => Some(PowerState::Standby),
+            2 => Some(PowerState::Suspend),
+            3 => Some(PowerState::Off),
+            _ => None,
+        }
+    }
+
+    fn to_raw(self) -> u64 {
+        match self {
+            PowerState::On => 0,
+            PowerState::Standby => 1,
+            PowerState::Suspend => 2,
+            PowerState::Off => 3,
+        }
+    }
+}
+
+/// Read the current power state of a connector
+///
+/// Returns `None` if the connector does not support power management.
+pub fn power_state<D>(dev: &D, connector: connector::Handle) -> Result<Option<PowerState>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    Ok(find_property(dev, connector, "DPMS")?.and_then(|(_, _, value)| PowerState::from_raw(value)))
+}
+
+/// Change the power state of a connector
+///
+/// Fails with [`Error::UnknownProperty`] if the connector does not support power management.
+pub fn set_power_state<D>(dev: &D, connector: connector::Handle, state: PowerState) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let (prop, _, _) = find_property(dev, connector, "DPMS")?.ok_or_else(|| Error::UnknownProperty {
+        handle: connector.into(),
+        name: "DPMS",
+    })?;
+    dev.set_property(connector, prop, state.to_raw())
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Failed to set DPMS property",
+            dev: dev.dev_path(),
+            source,
+        })
+}
+
+/// Tracker for the power state of a set of connectors
+///
+/// Changes done through [`PowerStateMonitor::set`] are remembered, while [`PowerStateMonitor::refresh`]
+/// reports all connectors whose power state changed by external means since the last time they were
+/// checked. You'll typically call `refresh` when receiving a change event for the device
+/// from `udev` or when resuming the session.
+#[derive(Debug, Default)]
+pub struct PowerStateMonitor {
+    states: ConnectorTracker<PowerState>,
+}
+
+impl PowerStateMonitor {
+    /// Create a new monitor not tracking any connector yet
+    pub fn new() -> PowerStateMonitor {
+        PowerStateMonitor::default()
+    }
+
+    /// Last known power state of a connector
+    pub fn get(&self, connector: connector::Handle) -> Option<PowerState> {
+        self.states.get(connector)
+    }
+
+    /// Change the power state of a connector and remember it
+    pub fn set<D>(&mut self, dev: &D, connector: connector::Handle, state: PowerState) -> Result<(), Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        set_power_state(dev, connector, state)?;
+        self.states.track(connector, state);
+        Ok(())
+    }
+
+    /// Stop tracking a connector, e.g. because it was disconnected
+    pub fn remove(&mut self, connector: connector::Handle) {
+        self.states.untrack(connector);
+    }
+
+    /// Read the power state of the given connectors and return those that changed
+    ///
+    /// Connectors seen for the first time are not reported, only recorded.
+    pub fn refresh<D>(
+        &mut self,
+        dev: &D,
+        connectors: &[connector::Handle],
+    ) -> Result<Vec<(connector::Handle, PowerState)>, Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        self.states.refresh(connectors, |conn| power_state(dev, conn))
+    }
+}
@@ -0,0 +1,106 @@
+//!
+//! Tiled monitors via the `TILE` connector property.
+//!
+//! Some high resolution monitors (e.g. 8K displays) are driven through several connectors,
+//! each displaying a tile of the whole picture. The kernel describes the tile of each such
+//! connector in its `TILE` blob, which [`connector_tile`] reads. Connectors sharing the same
+//! [`group_id`](TileInfo::group_id) belong to the same monitor, and should be combined into a
+//! single logical output, for example using an [`OutputMapping`](crate::utils::output_mapping::OutputMapping).
+//!
+
+use drm::control::{connector, property, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::{find_property, Error};
+use crate::backend::drm::DevPath;
+
+/// The tile of a monitor a connector is displaying
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileInfo {
+    /// Identifier of the group of tiles forming the monitor
+    pub group_id: u32,
+    /// Whether the tiles are displayed by a single physical monitor
+    pub single_monitor: bool,
+    /// Number of tiles in each row and column of the monitor
+    pub num_tiles: (u32, u32),
+    /// Column and row of this tile
+    pub location: (u32, u32),
+    /// Size of this tile in pixels
+    pub size: (u32, u32),
+}
+
+impl TileInfo {
+    /// Parse the content of a `TILE` blob
+    ///
+    /// It is a string of eight colon-separated integers.
+    pub fn parse(data: &[u8]) -> Option<TileInfo> {
+        let text = std::str::from_utf8(data).ok()?.trim_end_matches('\0');
+        let fields = text
+            .split(':')
+            .map(|field| field.trim().parse::<u32>().ok())
+            .collect::<Option<Vec<u32>>>()?;
+        if fields.len() != 8 {
+            return None;
+        }
+        Some(TileInfo {
+            group_id: fields[0],
+            single_monitor: fields[1] & 1 != 0,
+            num_tiles: (fields[2], fields[3]),
+            location: (fields[4], fields[5]),
+            size: (fields[6], fields[7]),
+        })
+    }
+
+    /// Location of the top-left corner of this tile in the picture of the monitor, in pixels
+    pub fn offset(&self) -> (u32, u32) {
+        (self.location.0 * self.size.0, self.location.1 * self.size.1)
+    }
+}
+
+/// Read the tile a connector is displaying
+///
+/// Returns `Ok(None)` if the connector is not part of a tiled monitor.
+pub fn connector_tile<D>(dev: &D, connector: connector::Handle) -> Result<Option<TileInfo>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let blob = match find_property(dev, connector, "TILE")? {
+        Some((_, info, val)) => match info.value_type().convert_value(val) {
+            property::Value::Blob(blob) if blob != 0 => blob,
+            _ => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+    let data = dev
+        .get_property_blob(blob)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Error reading TILE blob",
+            dev: dev.dev_path(),
+            source,
+        })?;
+    Ok(TileInfo::parse(&data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TileInfo;
+
+    #[test]
+    fn parse() {
+        let tile = TileInfo::parse(b"3:1:2:1:1:0:3840:4320\0").unwrap();
+        assert_eq!(
+            tile,
+            TileInfo {
+                group_id: 3,
+                single_monitor: true,
+                num_tiles: (2, 1),
+                location: (1, 0),
+                size: (3840, 4320),
+            }
+        );
+        assert_eq!(tile.offset(), (3840, 0));
+        assert_eq!(TileInfo::parse(b"3:1:2:1"), None);
+        assert_eq!(TileInfo::parse(b"garbage"), None);
+    }
+}
@@ -0,0 +1,69 @@
+//!
+//! Tracking of the last known state of a set of connectors.
+//!
+
+use std::collections::HashMap;
+
+use drm::control::connector;
+
+/// Last known state of a set of connectors
+///
+/// This is the common base of the monitors of connector properties, which remember the state
+/// they last saw or set for each connector and report the connectors whose state changed.
+#[derive(Debug)]
+pub(crate) struct ConnectorTracker<T> {
+    states: HashMap<connector::Handle, T>,
+}
+
+impl<T: Copy + PartialEq> ConnectorTracker<T> {
+    pub(crate) fn new() -> ConnectorTracker<T> {
+        ConnectorTracker {
+            states: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&self, connector: connector::Handle) -> Option<T> {
+        self.states.get(&connector).copied()
+    }
+
+    pub(crate) fn track(&mut self, connector: connector::Handle, state: T) {
+        self.states.insert(connector, state);
+    }
+
+    pub(crate) fn untrack(&mut self, connector: connector::Handle) {
+        self.states.remove(&connector);
+    }
+
+    /// Read the state of the given connectors with `read` and return those that changed
+    ///
+    /// Connectors seen for the first time are not reported, only recorded. Connectors for
+    /// which `read` returns `None` are skipped.
+    pub(crate) fn refresh<F, E>(
+        &mut self,
+        connectors: &[connector::Handle],
+        mut read: F,
+    ) -> Result<Vec<(connector::Handle, T)>, E>
+    where
+        F: FnMut(connector::Handle) -> Result<Option<T>, E>,
+    {
+        let mut changed = Vec::new();
+        for &conn in connectors {
+            let state = match read(conn)? {
+                Some(state) => state,
+                None => continue,
+            };
+            if let Some(old) = self.states.insert(conn, state) {
+                if old != state {
+                    changed.push((conn, state));
+                }
+            }
+        }
+        Ok(changed)
+    }
+}
+
+impl<T: Copy + PartialEq> Default for ConnectorTracker<T> {
+    fn default() -> Self {
+        ConnectorTracker::new()
+    }
+}
@@ -0,0 +1,248 @@
+//!
+//! Typed access to the properties of drm objects.
+//!
+//! Everything in the atomic api, and many features of the legacy api, are controlled via
+//! properties of the drm objects (connectors, crtcs, planes, ...). Properties are identified by
+//! handles, which are not consistent across devices, so they have to be looked up by name.
+//!
+//! A [`PropertyCache`] does these lookups once per object and remembers the result. It also
+//! allows you to enumerate all properties of an object, including driver-specific ones,
+//! to read their values and to stage new values in an atomic request.
+//!
+
+use std::collections::HashMap;
+
+use drm::control::{
+    atomic::AtomicModeReq, property, Device as ControlDevice, RawResourceHandle, ResourceHandle,
+};
+use failure::ResultExt;
+
+use super::Error;
+use crate::backend::drm::DevPath;
+
+/// A property of a drm object and its current value
+#[derive(Debug, Clone)]
+pub struct Property {
+    handle: property::Handle,
+    name: String,
+    value_type: property::ValueType,
+    raw_value: property::RawValue,
+}
+
+impl Property {
+    /// Handle of the property
+    pub fn handle(&self) -> property::Handle {
+        self.handle
+    }
+
+    /// Name of the property
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type of the values of this property
+    pub fn value_type(&self) -> &property::ValueType {
+        &self.value_type
+    }
+
+    /// Raw value of the property at the time it was read
+    pub fn raw_value(&self) -> property::RawValue {
+        self.raw_value
+    }
+
+    /// Value of the property at the time it was read, decoded according to its type
+    pub fn value(&self) -> property::Value<'_> {
+        self.value_type.convert_value(self.raw_value)
+    }
+}
+
+/// Cache of the property handles of drm objects
+///
+/// The cache is filled lazily, the first time a property of an object is accessed. Handles of
+/// properties never change for a given device, but objects may appear or disappear (e.g. mst
+/// connectors), use [`PropertyCache::invalidate`] once an object is gone.
+#[derive(Debug, Default)]
+pub struct PropertyCache {
+    objects: HashMap<RawResourceHandle, HashMap<String, property::Handle>>,
+}
+
+impl PropertyCache {
+    /// Create a new empty cache
+    pub fn new() -> PropertyCache {
+        PropertyCache::default()
+    }
+
+    /// Read all properties of an object, along with their current value
+    ///
+    /// This also fills the cache for this object.
+    pub fn properties<D, T>(&mut self, dev: &D, object: T) -> Result<Vec<Property>, Error>
+    where
+        D: ControlDevice + DevPath,
+        T: ResourceHandle,
+    {
+        let props = dev
+            .get_properties(object)
+            .compat()
+            .map_err(|source| Error::Access {
+                errmsg: "Error reading properties",
+                dev: dev.dev_path(),
+                source,
+            })?;
+        let (handles, values) = props.as_props_and_values();
+
+        let mut properties = Vec::with_capacity(handles.len());
+        let mut mapping = HashMap::with_capacity(handles.len());
+        for (&handle, &raw_value) in handles.iter().zip(values.iter()) {
+            let info = dev
+                .get_property(handle)
+                .compat()
+                .map_err(|source| Error::Access {
+                    errmsg: "Error reading property info",
+                    dev: dev.dev_path(),
+                    source,
+                })?;
+            let name = info.name().to_string_lossy().into_owned();
+            mapping.insert(name.clone(), handle);
+            properties.push(Property {
+                handle,
+                name,
+                value_type: info.value_type(),
+                raw_value,
+            });
+        }
+        self.objects.insert(object.into(), mapping);
+        Ok(properties)
+    }
+
+    /// Read a single property of an object by name
+    ///
+    /// Returns `None` if the object has no property of that name. Only the first access to an
+    /// object reads the information of all its properties, later accesses only read the value.
+    pub fn get<D, T>(&mut self, dev: &D, object: T, name: &str) -> Result<Option<Property>, Error>
+    where
+        D: ControlDevice + DevPath,
+        T: ResourceHandle,
+    {
+        let handle = match self.handle(dev, object, name)? {
+            Some(handle) => handle,
+            None => return Ok(None),
+        };
+        let props = dev
+            .get_properties(object)
+            .compat()
+            .map_err(|source| Error::Access {
+                errmsg: "Error reading properties",
+                dev: dev.dev_path(),
+                source,
+            })?;
+        let (handles, values) = props.as_props_and_values();
+        let raw_value = match handles.iter().zip(values.iter()).find(|(&h, _)| h == handle) {
+            Some((_, &raw_value)) => raw_value,
+            None => return Ok(None),
+        };
+        let info = dev
+            .get_property(handle)
+            .compat()
+            .map_err(|source| Error::Access {
+                errmsg: "Error reading property info",
+                dev: dev.dev_path(),
+                source,
+            })?;
+        Ok(Some(Property {
+            handle,
+            name: name.to_string(),
+            value_type: info.value_type(),
+            raw_value,
+        }))
+    }
+
+    /// Lookup the handle of a property of an object by name
+    ///
+    /// Returns `None` if the object has no property of that name.
+    pub fn handle<D, T>(&mut self, dev: &D, object: T, name: &str) -> Result<Option<property::Handle>, Error>
+    where
+        D: ControlDevice + DevPath,
+        T: ResourceHandle,
+    {
+        let raw: RawResourceHandle = object.into();
+        if !self.objects.contains_key(&raw) {
+            self.properties(dev, object)?;
+        }
+        Ok(self.cached(object, name))
+    }
+
+    /// Lookup the handle of a property of an object by name, without accessing the device
+    ///
+    /// Returns `None` if the object is not in the cache yet, or has no property of that name.
+    pub fn cached<T: ResourceHandle>(&self, object: T, name: &str) -> Option<property::Handle> {
+        let raw: RawResourceHandle = object.into();
+        self.objects.get(&raw).and_then(|map| map.get(name)).copied()
+    }
+
+    /// Stage a new value of a property in an atomic request
+    ///
+    /// Fails with [`Error::UnknownProperty`] if the object has no property of that name.
+    pub fn stage<D, T>(
+        &mut self,
+        dev: &D,
+        req: &mut AtomicModeReq,
+        object: T,
+        name: &'static str,
+        value: property::Value<'_>,
+    ) -> Result<(), Error>
+    where
+        D: ControlDevice + DevPath,
+        T: ResourceHandle,
+    {
+        let prop = self.require(dev, object, name)?;
+        req.add_property(object, prop, value);
+        Ok(())
+    }
+
+    /// Immediately change the value of a property, without using an atomic request
+    ///
+    /// Fails with [`Error::UnknownProperty`] if the object has no property of that name.
+    pub fn set<D, T>(
+        &mut self,
+        dev: &D,
+        object: T,
+        name: &'static str,
+        value: property::RawValue,
+    ) -> Result<(), Error>
+    where
+        D: ControlDevice + DevPath,
+        T: ResourceHandle,
+    {
+        let prop = self.require(dev, object, name)?;
+        dev.set_property(object, prop, value)
+            .compat()
+            .map_err(|source| Error::Access {
+                errmsg: "Failed to set property",
+                dev: dev.dev_path(),
+                source,
+            })
+    }
+
+    /// Forget the cached properties of an object
+    pub fn invalidate<T: ResourceHandle>(&mut self, object: T) {
+        let raw: RawResourceHandle = object.into();
+        self.objects.remove(&raw);
+    }
+
+    /// Forget all cached properties
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+
+    fn require<D, T>(&mut self, dev: &D, object: T, name: &'static str) -> Result<property::Handle, Error>
+    where
+        D: ControlDevice + DevPath,
+        T: ResourceHandle,
+    {
+        self.handle(dev, object, name)?
+            .ok_or_else(|| Error::UnknownProperty {
+                handle: object.into(),
+                name,
+            })
+    }
+}
@@ -0,0 +1,98 @@
+//!
+//! Variable refresh rate (adaptive sync) via the `vrr_capable` connector and
+//! `VRR_ENABLED` crtc properties.
+//!
+//! With adaptive sync enabled, the monitor waits for the next page-flip instead of
+//! refreshing at a fixed rate (up to the minimum refresh rate it supports). This is commonly
+//! known as FreeSync or G-Sync and mostly useful for fullscreen games and videos, whose frame
+//! rate does not match the refresh rate of the output.
+//!
+//! Use [`set_output_vrr`] to toggle adaptive sync for an output, which checks that all of its
+//! connectors support it first.
+//!
+
+use drm::control::{connector, crtc, Device as ControlDevice};
+use failure::ResultExt;
+
+use super::{find_property, Error};
+use crate::backend::drm::DevPath;
+
+/// Check if a connector, and the monitor connected to it, support variable refresh rates
+///
+/// Returns `false` if the driver does not expose the `vrr_capable` property.
+pub fn vrr_capable<D>(dev: &D, connector: connector::Handle) -> Result<bool, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    Ok(find_property(dev, connector, "vrr_capable")?
+        .map(|(_, _, value)| value != 0)
+        .unwrap_or(false))
+}
+
+/// Check if variable refresh rates are currently enabled on a crtc
+///
+/// Returns `None` if the driver does not expose the `VRR_ENABLED` property.
+pub fn vrr_enabled<D>(dev: &D, crtc: crtc::Handle) -> Result<Option<bool>, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    Ok(find_property(dev, crtc, "VRR_ENABLED")?.map(|(_, _, value)| value != 0))
+}
+
+/// Enable or disable variable refresh rates on a crtc
+///
+/// This does not check if the connectors driven by the crtc support it, see [`set_output_vrr`]
+/// for that. Fails with [`Error::UnknownProperty`] if the driver does not support variable
+/// refresh rates.
+pub fn set_vrr_enabled<D>(dev: &D, crtc: crtc::Handle, enabled: bool) -> Result<(), Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let (prop, _, _) = find_property(dev, crtc, "VRR_ENABLED")?.ok_or_else(|| Error::UnknownProperty {
+        handle: crtc.into(),
+        name: "VRR_ENABLED",
+    })?;
+    dev.set_property(crtc, prop, enabled as u64)
+        .compat()
+        .map_err(|source| Error::Access {
+            errmsg: "Failed to set VRR_ENABLED property",
+            dev: dev.dev_path(),
+            source,
+        })
+}
+
+/// Toggle variable refresh rates for an output
+///
+/// Adaptive sync is only enabled if all `connectors` driven by `crtc` are capable of it,
+/// disabling it always succeeds if the driver supports the property.
+///
+/// Returns whether adaptive sync is enabled on the output afterwards.
+pub fn set_output_vrr<D>(
+    dev: &D,
+    crtc: crtc::Handle,
+    connectors: &[connector::Handle],
+    enabled: bool,
+) -> Result<bool, Error>
+where
+    D: ControlDevice + DevPath,
+{
+    let enabled = if enabled && !connectors.is_empty() {
+        let mut capable = true;
+        for &conn in connectors {
+            capable &= vrr_capable(dev, conn)?;
+        }
+        capable
+    } else {
+        false
+    };
+
+    match vrr_enabled(dev, crtc)? {
+        Some(current) if current == enabled => Ok(enabled),
+        Some(_) => set_vrr_enabled(dev, crtc, enabled).map(|_| enabled),
+        None if !enabled => Ok(false),
+        None => Err(Error::UnknownProperty {
+            handle: crtc.into(),
+            name: "VRR_ENABLED",
+        }),
+    }
+}
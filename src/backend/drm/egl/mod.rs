@@ -21,7 +21,7 @@ use std::sync::{Arc, Weak as WeakArc};
 #[cfg(feature = "use_system_lib")]
 use wayland_server::Display;
 
-use super::{Device, DeviceHandler, Surface};
+use super::{Device, DeviceHandler, Surface, VblankTime};
 use crate::backend::egl::native::{Backend, NativeDisplay, NativeSurface};
 #[cfg(feature = "use_system_lib")]
 use crate::backend::egl::{display::EGLBufferReader, EGLGraphicsBackend};
@@ -162,6 +162,9 @@ where
     fn vblank(&mut self, crtc: crtc::Handle) {
         self.handler.vblank(crtc)
     }
+    fn vblank_with_time(&mut self, crtc: crtc::Handle, time: VblankTime) {
+        self.handler.vblank_with_time(crtc, time)
+    }
     fn error(&mut self, error: <<D as Device>::Surface as Surface>::Error) {
         self.handler.error(Error::Underlying(error));
     }
@@ -15,7 +15,7 @@
 //! For detailed overview of these abstractions take a look at the module documentation of backend::drm.
 //!
 
-use super::{Device, DeviceHandler, RawDevice, Surface};
+use super::{Device, DeviceHandler, RawDevice, Surface, VblankTime};
 
 use drm::buffer::format::PixelFormat;
 use drm::control::{
@@ -233,15 +233,12 @@ struct InternalDeviceHandler<D: RawDevice + ControlDevice + 'static> {
     logger: ::slog::Logger,
 }
 
-impl<D: RawDevice + ControlDevice + 'static> DeviceHandler for InternalDeviceHandler<D> {
-    type Device = D;
-
-    fn vblank(&mut self, crtc: crtc::Handle) {
+impl<D: RawDevice + ControlDevice + 'static> InternalDeviceHandler<D> {
+    // returns whether the event concerns a surface still alive
+    fn flipped(&self, crtc: crtc::Handle) -> bool {
         if let Some(backends) = self.backends.upgrade() {
             if let Some(surface) = backends.borrow().get(&crtc) {
-                if surface.upgrade().is_some() {
-                    self.handler.vblank(crtc);
-                }
+                return surface.upgrade().is_some();
             } else {
                 warn!(
                     self.logger,
@@ -249,6 +246,22 @@ impl<D: RawDevice + ControlDevice + 'static> DeviceHandler for InternalDeviceHan
                 );
             }
         }
+        false
+    }
+}
+
+impl<D: RawDevice + ControlDevice + 'static> DeviceHandler for InternalDeviceHandler<D> {
+    type Device = D;
+
+    fn vblank(&mut self, crtc: crtc::Handle) {
+        if self.flipped(crtc) {
+            self.handler.vblank(crtc);
+        }
+    }
+    fn vblank_with_time(&mut self, crtc: crtc::Handle, time: VblankTime) {
+        if self.flipped(crtc) {
+            self.handler.vblank_with_time(crtc, time);
+        }
     }
     fn error(&mut self, error: <<D as Device>::Surface as Surface>::Error) {
         self.handler.error(Error::Underlying(error))
@@ -0,0 +1,309 @@
+//!
+//! Coordination of the output optimizations useful for fullscreen games.
+//!
+//! Several features of the drm backend only make sense while a single fullscreen
+//! application is displayed on an output:
+//!
+//! - [direct scanout](super::scanout) of its buffers, avoiding composition,
+//! - tearing (asynchronous page-flips), reducing latency at the cost of visual artifacts,
+//! - [variable refresh rates](super::common::vrr), matching the refresh rate of the output
+//!   to the frame rate of the application,
+//! - using the cursor plane, so that the pointer does not force composition.
+//!
+//! [`GameMode`] bundles them into a single switch: every frame you describe the fullscreen
+//! surface of the output (if any) as a [`GameModeCandidate`] and it decides which
+//! optimizations to use, reporting them as [`GameModeStatus`] so you can display them for
+//! debugging. Enabling them is still up to you, except for variable refresh rates, which
+//! can be applied using [`GameMode::apply_vrr`].
+//!
+
+use std::fmt;
+
+use drm::control::{connector, crtc, Device as ControlDevice};
+
+use super::{
+    common::{vrr::set_output_vrr, Error},
+    DevPath,
+};
+
+/// Optimizations game mode is allowed to use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameModeConfig {
+    /// Scan out the buffers of the fullscreen surface directly
+    pub direct_scanout: bool,
+    /// Allow tearing, if the surface opted into it
+    pub tearing: bool,
+    /// Enable variable refresh rates
+    pub vrr: bool,
+    /// Display the cursor using the cursor plane
+    pub cursor_plane: bool,
+}
+
+impl Default for GameModeConfig {
+    fn default() -> Self {
+        GameModeConfig {
+            direct_scanout: true,
+            tearing: false,
+            vrr: true,
+            cursor_plane: true,
+        }
+    }
+}
+
+/// Description of the fullscreen surface of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameModeCandidate {
+    /// Whether the surface is opaque and covers the whole output
+    pub covers_output: bool,
+    /// Whether the client allows its surface to be displayed with tearing
+    pub allows_tearing: bool,
+    /// Whether the client uses buffers that can be scanned out (e.g. dmabufs)
+    pub scanout_capable: bool,
+}
+
+/// Optimizations currently used by game mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GameModeStatus {
+    /// Direct scanout should be attempted
+    pub direct_scanout: bool,
+    /// Page-flips may tear
+    pub tearing: bool,
+    /// Variable refresh rates should be enabled
+    pub vrr: bool,
+    /// The cursor should be displayed using the cursor plane
+    pub cursor_plane: bool,
+}
+
+impl GameModeStatus {
+    /// Whether any optimization is in use
+    pub fn is_active(&self) -> bool {
+        self.direct_scanout || self.tearing || self.vrr || self.cursor_plane
+    }
+}
+
+impl fmt::Display for GameModeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let active = [
+            (self.direct_scanout, "direct scanout"),
+            (self.tearing, "tearing"),
+            (self.vrr, "vrr"),
+            (self.cursor_plane, "cursor plane"),
+        ]
+        .iter()
+        .filter(|(active, _)| *active)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+        if active.is_empty() {
+            write!(f, "inactive")
+        } else {
+            write!(f, "{}", active.join(", "))
+        }
+    }
+}
+
+/// Game mode policy of a single output
+pub struct GameMode {
+    config: GameModeConfig,
+    enabled: bool,
+    status: GameModeStatus,
+    // last requested vrr state and its outcome
+    vrr_applied: Option<(bool, bool)>,
+    log: ::slog::Logger,
+}
+
+impl GameMode {
+    /// Create the game mode policy of an output, initially disabled
+    pub fn new<L>(config: GameModeConfig, logger: L) -> GameMode
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        GameMode {
+            config,
+            enabled: false,
+            status: GameModeStatus::default(),
+            vrr_applied: None,
+            log: crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_drm_game_mode")),
+        }
+    }
+
+    /// Access the current configuration
+    pub fn config(&self) -> GameModeConfig {
+        self.config
+    }
+
+    /// Change the configuration, which is taken into account on the next [`update`](GameMode::update)
+    pub fn set_config(&mut self, config: GameModeConfig) {
+        self.config = config;
+    }
+
+    /// Whether game mode is switched on
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Switch game mode on or off, which is taken into account on the next [`update`](GameMode::update)
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Optimizations currently in use
+    pub fn status(&self) -> GameModeStatus {
+        self.status
+    }
+
+    /// Decide which optimizations to use for the current frame
+    ///
+    /// `candidate` describes the fullscreen surface of the output, `None` if there is none.
+    /// Variable refresh rates stay off once [`apply_vrr`](GameMode::apply_vrr) found the
+    /// output does not support them, until the next [`mode_changed`](GameMode::mode_changed).
+    pub fn update(&mut self, candidate: Option<&GameModeCandidate>) -> GameModeStatus {
+        let vrr_unsupported = self.vrr_applied == Some((true, false));
+        let status = match candidate {
+            Some(candidate) if self.enabled => GameModeStatus {
+                direct_scanout: self.config.direct_scanout
+                    && candidate.covers_output
+                    && candidate.scanout_capable,
+                tearing: self.config.tearing && candidate.allows_tearing,
+                vrr: self.config.vrr && !vrr_unsupported,
+                cursor_plane: self.config.cursor_plane,
+            },
+            _ => GameModeStatus::default(),
+        };
+        if status != self.status {
+            debug!(self.log, "Game mode changed: {}", status);
+            self.status = status;
+        }
+        status
+    }
+
+    /// Report that direct scanout failed for the current frame
+    ///
+    /// Direct scanout stays disabled in the reported status until the next
+    /// [`update`](GameMode::update).
+    pub fn scanout_failed(&mut self) {
+        if self.status.direct_scanout {
+            trace!(self.log, "Direct scanout failed, compositing");
+            self.status.direct_scanout = false;
+        }
+    }
+
+    /// Enable or disable variable refresh rates on the output according to the current status
+    ///
+    /// The property is only changed if necessary. If the output does not support variable
+    /// refresh rates, the status is updated to reflect that, and they are not requested
+    /// anymore until the next [`mode_changed`](GameMode::mode_changed). Errors setting the
+    /// property are returned, and it is set again on the next call.
+    pub fn apply_vrr<D>(
+        &mut self,
+        dev: &D,
+        crtc: crtc::Handle,
+        connectors: &[connector::Handle],
+    ) -> Result<(), Error>
+    where
+        D: ControlDevice + DevPath,
+    {
+        if let Some((requested, enabled)) = self.vrr_applied {
+            // disabling vrr on an output without support for it is a no-op
+            if requested == self.status.vrr || (requested && !enabled) {
+                self.status.vrr = enabled;
+                return Ok(());
+            }
+        }
+        let enabled = match set_output_vrr(dev, crtc, connectors, self.status.vrr) {
+            Ok(enabled) => enabled,
+            Err(Error::UnknownProperty { .. }) => false,
+            Err(err) => return Err(err),
+        };
+        if self.status.vrr && !enabled {
+            debug!(self.log, "Output does not support variable refresh rates");
+        }
+        self.vrr_applied = Some((self.status.vrr, enabled));
+        self.status.vrr = enabled;
+        Ok(())
+    }
+
+    /// Notify that the mode or the connectors of the output changed
+    ///
+    /// Call it after [`use_mode`](super::Surface::use_mode) or after changing the connectors
+    /// of the surface: the support of variable refresh rates depends on them, so they are
+    /// requested again on the next [`update`](GameMode::update), and the property is set
+    /// again by the next [`apply_vrr`](GameMode::apply_vrr).
+    pub fn mode_changed(&mut self) {
+        if self.vrr_applied.take() == Some((true, false)) {
+            debug!(self.log, "Output mode changed, checking vrr support again");
+        }
+    }
+}
+
+impl fmt::Debug for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GameMode")
+            .field("config", &self.config)
+            .field("enabled", &self.enabled)
+            .field("status", &self.status)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameMode, GameModeCandidate, GameModeConfig, GameModeStatus};
+
+    const CANDIDATE: GameModeCandidate = GameModeCandidate {
+        covers_output: true,
+        allows_tearing: true,
+        scanout_capable: true,
+    };
+
+    #[test]
+    fn inactive_unless_enabled() {
+        let mut game_mode = GameMode::new(GameModeConfig::default(), None);
+        assert!(!game_mode.update(Some(&CANDIDATE)).is_active());
+        game_mode.set_enabled(true);
+        assert!(game_mode.update(Some(&CANDIDATE)).is_active());
+        assert!(!game_mode.update(None).is_active());
+    }
+
+    #[test]
+    fn respects_config_and_candidate() {
+        let mut game_mode = GameMode::new(GameModeConfig::default(), None);
+        game_mode.set_enabled(true);
+        let status = game_mode.update(Some(&GameModeCandidate {
+            scanout_capable: false,
+            ..CANDIDATE
+        }));
+        assert_eq!(
+            status,
+            GameModeStatus {
+                direct_scanout: false,
+                tearing: false,
+                vrr: true,
+                cursor_plane: true,
+            }
+        );
+        assert_eq!(status.to_string(), "vrr, cursor plane");
+
+        game_mode.set_config(GameModeConfig {
+            tearing: true,
+            ..GameModeConfig::default()
+        });
+        assert!(game_mode.update(Some(&CANDIDATE)).tearing);
+        game_mode.scanout_failed();
+        assert!(!game_mode.status().direct_scanout);
+    }
+
+    #[test]
+    fn unsupported_vrr_stays_off() {
+        let mut game_mode = GameMode::new(GameModeConfig::default(), None);
+        game_mode.set_enabled(true);
+        assert!(game_mode.update(Some(&CANDIDATE)).vrr);
+        // as recorded by `apply_vrr` on an output without vrr support
+        game_mode.vrr_applied = Some((true, false));
+        assert!(!game_mode.update(Some(&CANDIDATE)).vrr);
+        assert!(!game_mode.status().vrr);
+        assert!(!game_mode.update(Some(&CANDIDATE)).vrr);
+        // the new mode may support it
+        game_mode.mode_changed();
+        assert!(game_mode.update(Some(&CANDIDATE)).vrr);
+    }
+}
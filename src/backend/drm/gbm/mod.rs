@@ -11,7 +11,7 @@
 //! For detailed overview of these abstractions take a look at the module documentation of backend::drm.
 //!
 
-use super::{Device, DeviceHandler, RawDevice, ResourceHandles, Surface};
+use super::{Device, DeviceHandler, RawDevice, ResourceHandles, Surface, VblankTime};
 use crate::backend::graphics::SwapBuffersError;
 
 use drm::control::{connector, crtc, encoder, framebuffer, plane, Device as ControlDevice, Mode};
@@ -131,17 +131,16 @@ struct InternalDeviceHandler<D: RawDevice + ControlDevice + 'static> {
     logger: ::slog::Logger,
 }
 
-impl<D: RawDevice + ControlDevice + 'static> DeviceHandler for InternalDeviceHandler<D> {
-    type Device = D;
-
-    fn vblank(&mut self, crtc: crtc::Handle) {
+impl<D: RawDevice + ControlDevice + 'static> InternalDeviceHandler<D> {
+    // returns whether the event concerns a surface still alive
+    fn flipped(&mut self, crtc: crtc::Handle) -> bool {
         if let Some(backends) = self.backends.upgrade() {
             if let Some(surface) = backends.borrow().get(&crtc) {
                 if let Some(surface) = surface.upgrade() {
                     // here we unlock the buffer again, that was locked during rendering,
                     // to make sure it is always unlocked after a successful page_flip.
                     surface.unlock_buffer();
-                    self.handler.vblank(crtc);
+                    return true;
                 }
             } else {
                 warn!(
@@ -150,6 +149,22 @@ impl<D: RawDevice + ControlDevice + 'static> DeviceHandler for InternalDeviceHan
                 );
             }
         }
+        false
+    }
+}
+
+impl<D: RawDevice + ControlDevice + 'static> DeviceHandler for InternalDeviceHandler<D> {
+    type Device = D;
+
+    fn vblank(&mut self, crtc: crtc::Handle) {
+        if self.flipped(crtc) {
+            self.handler.vblank(crtc);
+        }
+    }
+    fn vblank_with_time(&mut self, crtc: crtc::Handle, time: VblankTime) {
+        if self.flipped(crtc) {
+            self.handler.vblank_with_time(crtc, time);
+        }
     }
     fn error(&mut self, error: <<D as Device>::Surface as Surface>::Error) {
         self.handler.error(Error::Underlying(error))
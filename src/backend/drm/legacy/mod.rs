@@ -17,7 +17,7 @@
 //! For detailed overview of these abstractions take a look at the module documentation of backend::drm.
 //!
 
-use super::{common::Error, DevPath, Device, DeviceHandler, RawDevice};
+use super::{common::Error, DevPath, Device, DeviceHandler, RawDevice, VblankTime};
 
 use drm::control::{
     connector, crtc, encoder, framebuffer, plane, Device as ControlDevice, Event, Mode, ResourceHandles,
@@ -352,7 +352,13 @@ impl<A: AsRawFd + 'static> Device for LegacyDrmDevice<A> {
                         {
                             trace!(self.logger, "Handling event for backend {:?}", event.crtc);
                             if let Some(handler) = self.handler.as_ref() {
-                                handler.borrow_mut().vblank(event.crtc);
+                                handler.borrow_mut().vblank_with_time(
+                                    event.crtc,
+                                    VblankTime {
+                                        sequence: event.frame,
+                                        time: event.duration,
+                                    },
+                                );
                             }
                         } else {
                             self.backends.borrow_mut().remove(&event.crtc);
@@ -158,8 +158,9 @@ use nix::libc::dev_t;
 
 use std::error::Error;
 use std::iter::IntoIterator;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::PathBuf;
+use std::time::Duration;
 
 use calloop::{generic::Generic, InsertError, LoopHandle, Source};
 
@@ -171,10 +172,25 @@ pub mod common;
 pub mod egl;
 #[cfg(feature = "backend_drm_eglstream")]
 pub mod eglstream;
+#[cfg(feature = "backend_drm")]
+pub mod game_mode;
 #[cfg(feature = "backend_drm_gbm")]
 pub mod gbm;
 #[cfg(feature = "backend_drm_legacy")]
 pub mod legacy;
+#[cfg(feature = "backend_drm")]
+pub mod scanout;
+#[cfg(feature = "backend_drm")]
+pub mod scheduling;
+
+/// Timing of a vblank event, as reported by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VblankTime {
+    /// Sequence number of the vblank
+    pub sequence: u32,
+    /// Time of the vblank, on the `CLOCK_MONOTONIC` clock
+    pub time: Duration,
+}
 
 /// Trait to receive events of a bound [`Device`]
 ///
@@ -185,6 +201,15 @@ pub trait DeviceHandler {
 
     /// A vblank blank event on the provided crtc has happend
     fn vblank(&mut self, crtc: crtc::Handle);
+    /// A vblank event on the provided crtc has happend, at the given time
+    ///
+    /// Devices reporting the timing of their page-flips call this instead of
+    /// [`vblank`](DeviceHandler::vblank). The default implementation ignores the timing
+    /// and calls `vblank`.
+    fn vblank_with_time(&mut self, crtc: crtc::Handle, time: VblankTime) {
+        let _ = time;
+        self.vblank(crtc)
+    }
     /// An error happend while processing events
     fn error(&mut self, error: <<<Self as DeviceHandler>::Device as Device>::Surface as Surface>::Error);
 }
@@ -251,8 +276,31 @@ pub trait Device: AsRawFd + DevPath {
 
     /// Retrieve the information for a plane
     fn get_plane_info(&self, plane: plane::Handle) -> Result<plane::Info, DrmError>;
+
+    /// Read and parse the EDID of a connector, to identify the monitor connected to it
+    ///
+    /// Returns `Ok(None)` if the connector has no EDID or it could not be parsed, see
+    /// [`connector_edid`](common::edid::connector_edid).
+    fn get_connector_edid(
+        &self,
+        conn: connector::Handle,
+    ) -> Result<Option<common::edid::EdidInfo>, common::Error> {
+        common::edid::connector_edid(&BorrowedFd(self.as_raw_fd()), conn)
+    }
 }
 
+// the file descriptor of a device, to access it through the drm-rs traits
+struct BorrowedFd(RawFd);
+
+impl AsRawFd for BorrowedFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl BasicDevice for BorrowedFd {}
+impl ControlDevice for BorrowedFd {}
+
 /// Marker trait for [`Device`]s able to provide [`RawSurface`]s
 pub trait RawDevice: Device<Surface = <Self as RawDevice>::Surface> {
     /// Associated [`RawSurface`] of this [`RawDevice`] type
@@ -0,0 +1,277 @@
+//!
+//! Helpers for direct scanout of client buffers.
+//!
+//! When a single client buffer covers an entire output with opaque content, there is no
+//! need to composite it with OpenGL: the buffer can be imported as a [`framebuffer`]
+//! and put directly onto the primary plane of the [`crtc`](drm::control::crtc).
+//! This saves a full copy of the frame and a lot of GPU time, which is especially
+//! noticeable for fullscreen games and video players.
+//!
+//! This module does not import buffers itself, as that depends on the buffer management
+//! api you are using (e.g. gbm's `import_buffer_object_from_dma_buf` followed by
+//! `add_framebuffer`). Use [`PlaneFormats::primary_formats`] to check whether the format and
+//! modifier of a client buffer are supported by the primary plane before importing it. Then
+//! you describe the elements you would otherwise render as a list of [`ScanoutElement`]s,
+//! ordered from the top-most to the bottom-most one, and [`scanout_decision`] tells you
+//! whether a single one of them can be scanned out.
+//!
+//! [`try_direct_scanout`] wraps this and page-flips a [`RawSurface`] to the selected
+//! framebuffer, returning `false` whenever you need to fall back to compositing
+//! (something overlaps the candidate, it is not opaque, it does not match the mode, its
+//! format is not supported by the primary plane, or the page-flip itself was rejected by
+//! the driver).
+
+use drm::control::framebuffer;
+
+use super::{
+    common::formats::{PlaneFormat, PlaneFormats},
+    RawSurface, Surface,
+};
+use crate::utils::Rectangle;
+
+/// An element of the scene that would be composited onto an output
+#[derive(Debug, Clone, Copy)]
+pub struct ScanoutElement {
+    /// Location and size of this element in the output coordinate space
+    pub geometry: Rectangle,
+    /// Whether the contents of this element are fully opaque
+    ///
+    /// For client surfaces this is the case if the buffer format has no alpha channel
+    /// or the opaque region of the surface covers it completely.
+    pub opaque: bool,
+    /// The framebuffer holding the contents of this element, if it could be imported
+    ///
+    /// This should be `None` for anything that is not a client dmabuf suitable for scanout
+    /// (shm buffers, buffers with a transform or scale, subsurface trees, ...).
+    pub framebuffer: Option<framebuffer::Handle>,
+    /// The format and modifier of the framebuffer, if known
+    ///
+    /// [`try_direct_scanout`] does not select framebuffers whose format is not supported by
+    /// the primary plane.
+    pub format: Option<PlaneFormat>,
+}
+
+/// Result of evaluating a scene for direct scanout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanoutDecision {
+    /// The provided framebuffer covers the whole output and can be scanned out directly
+    Direct(framebuffer::Handle),
+    /// The scene needs to be composited
+    Composite,
+}
+
+// Unlike `Rectangle::overlaps`, rectangles only touching each other do not intersect.
+fn intersects(a: &Rectangle, b: &Rectangle) -> bool {
+    a.width > 0
+        && a.height > 0
+        && b.width > 0
+        && b.height > 0
+        && a.x < b.x + b.width
+        && b.x < a.x + a.width
+        && a.y < b.y + b.height
+        && b.y < a.y + a.height
+}
+
+/// Decide whether the given scene can be displayed by direct scanout
+///
+/// `elements` need to be ordered from the top-most to the bottom-most one. Elements that
+/// do not intersect the output are ignored, as are elements fully occluded by the scanout
+/// candidate.
+///
+/// The top-most visible element is selected if it has a framebuffer, is opaque and exactly
+/// covers an output of the given `output_size`. In any other case composition is required.
+pub fn scanout_decision(output_size: (i32, i32), elements: &[ScanoutElement]) -> ScanoutDecision {
+    let output = Rectangle {
+        x: 0,
+        y: 0,
+        width: output_size.0,
+        height: output_size.1,
+    };
+
+    let candidate = match elements.iter().find(|e| intersects(&e.geometry, &output)) {
+        Some(element) => element,
+        None => return ScanoutDecision::Composite,
+    };
+
+    let geo = candidate.geometry;
+    let covers_output = geo.x == 0 && geo.y == 0 && geo.width == output.width && geo.height == output.height;
+
+    match candidate.framebuffer {
+        Some(fb) if candidate.opaque && covers_output => ScanoutDecision::Direct(fb),
+        _ => ScanoutDecision::Composite,
+    }
+}
+
+// Remove the framebuffers the primary plane cannot scan out from the candidates
+fn filter_unsupported(elements: &[ScanoutElement], supported: Option<&[PlaneFormat]>) -> Vec<ScanoutElement> {
+    elements
+        .iter()
+        .map(|element| match (element.format, supported) {
+            (Some(format), Some(supported)) if !supported.contains(&format) => ScanoutElement {
+                framebuffer: None,
+                ..*element
+            },
+            _ => *element,
+        })
+        .collect()
+}
+
+/// Try to display the given scene on a [`RawSurface`] by direct scanout
+///
+/// Evaluates the scene using [`scanout_decision`] against the pending mode of the surface
+/// and page-flips to the selected framebuffer. Elements whose [`format`](ScanoutElement::format)
+/// is not supported by the primary plane of the surface, as cached in `formats`, are not
+/// selected. Returns `true` if the flip was queued, in which case you must not render this
+/// frame yourself and will receive a `vblank` event as usual.
+///
+/// Returns `false` if the scene is not suitable for direct scanout, if the surface requires a
+/// modeset or if the driver rejected the framebuffer. You then need to composite the frame.
+pub fn try_direct_scanout<S, L>(
+    surface: &S,
+    formats: &mut PlaneFormats,
+    elements: &[ScanoutElement],
+    logger: L,
+) -> bool
+where
+    S: RawSurface,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_drm_scanout"));
+
+    // A modeset might change the size of the primary plane, never skip it.
+    if surface.commit_pending() {
+        trace!(log, "Pending commit on crtc {:?}, compositing", surface.crtc());
+        return false;
+    }
+
+    let supported = match formats.primary_formats(surface, surface.crtc()) {
+        Ok(supported) => supported,
+        Err(err) => {
+            debug!(log, "Failed to read the formats of the primary plane: {}", err);
+            None
+        }
+    };
+    let elements = filter_unsupported(elements, supported);
+
+    let size = surface.pending_mode().size();
+    let fb = match scanout_decision((size.0 as i32, size.1 as i32), &elements) {
+        ScanoutDecision::Direct(fb) => fb,
+        ScanoutDecision::Composite => return false,
+    };
+
+    match RawSurface::page_flip(surface, fb) {
+        Ok(()) => {
+            trace!(log, "Scanning out {:?} directly on crtc {:?}", fb, surface.crtc());
+            true
+        }
+        Err(err) => {
+            debug!(
+                log,
+                "Direct scanout of {:?} failed, falling back to composition: {}", fb, err
+            );
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{filter_unsupported, scanout_decision, ScanoutDecision, ScanoutElement};
+    use crate::backend::drm::common::formats::{PlaneFormat, DRM_FORMAT_MOD_INVALID};
+    use crate::utils::Rectangle;
+    use drm::control::{framebuffer, from_u32};
+
+    fn fb(id: u32) -> framebuffer::Handle {
+        from_u32(id).unwrap()
+    }
+
+    fn element(x: i32, y: i32, width: i32, height: i32, opaque: bool, id: Option<u32>) -> ScanoutElement {
+        ScanoutElement {
+            geometry: Rectangle { x, y, width, height },
+            opaque,
+            framebuffer: id.map(fb),
+            format: None,
+        }
+    }
+
+    #[test]
+    fn fullscreen_opaque_is_scanned_out() {
+        let elements = [
+            element(0, 0, 1920, 1080, true, Some(1)),
+            element(100, 100, 200, 200, false, None),
+        ];
+        assert_eq!(
+            scanout_decision((1920, 1080), &elements),
+            ScanoutDecision::Direct(fb(1))
+        );
+    }
+
+    #[test]
+    fn overlapping_element_forces_composition() {
+        let elements = [
+            element(10, 10, 24, 24, false, None),
+            element(0, 0, 1920, 1080, true, Some(1)),
+        ];
+        assert_eq!(
+            scanout_decision((1920, 1080), &elements),
+            ScanoutDecision::Composite
+        );
+    }
+
+    #[test]
+    fn offscreen_elements_are_ignored() {
+        let elements = [
+            element(1920, 0, 1280, 1024, false, None),
+            element(0, 0, 1920, 1080, true, Some(1)),
+        ];
+        assert_eq!(
+            scanout_decision((1920, 1080), &elements),
+            ScanoutDecision::Direct(fb(1))
+        );
+    }
+
+    #[test]
+    fn mismatching_or_translucent_candidates_are_rejected() {
+        let wrong_size = [element(0, 0, 1280, 720, true, Some(1))];
+        assert_eq!(
+            scanout_decision((1920, 1080), &wrong_size),
+            ScanoutDecision::Composite
+        );
+        let translucent = [element(0, 0, 1920, 1080, false, Some(1))];
+        assert_eq!(
+            scanout_decision((1920, 1080), &translucent),
+            ScanoutDecision::Composite
+        );
+        let not_imported = [element(0, 0, 1920, 1080, true, None)];
+        assert_eq!(
+            scanout_decision((1920, 1080), &not_imported),
+            ScanoutDecision::Composite
+        );
+    }
+
+    #[test]
+    fn unsupported_format_forces_composition() {
+        let xr24 = PlaneFormat {
+            format: 0x3432_5258,
+            modifier: DRM_FORMAT_MOD_INVALID,
+        };
+        let nv12 = PlaneFormat {
+            format: 0x3231_564e,
+            modifier: DRM_FORMAT_MOD_INVALID,
+        };
+        let mut elements = [element(0, 0, 1920, 1080, true, Some(1))];
+        elements[0].format = Some(nv12);
+
+        let filtered = filter_unsupported(&elements, Some(&[xr24][..]));
+        assert_eq!(
+            scanout_decision((1920, 1080), &filtered),
+            ScanoutDecision::Composite
+        );
+        // the formats of the plane are unknown, try anyway
+        let filtered = filter_unsupported(&elements, None);
+        assert_eq!(
+            scanout_decision((1920, 1080), &filtered),
+            ScanoutDecision::Direct(fb(1))
+        );
+    }
+}
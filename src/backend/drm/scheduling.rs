@@ -0,0 +1,336 @@
+//!
+//! Repaint scheduling of outputs.
+//!
+//! Desktop outputs are usually repainted after a vblank, whenever their content changed.
+//! Fixed-function embedded panels, like instrument clusters or signage, often need
+//! something else:
+//!
+//! - repainting at a fixed rate, whether or not anything changed, paced by a timer rather
+//!   than by the vblank events of the panel,
+//! - or only repainting when the compositor explicitly asks for it.
+//!
+//! [`RepaintScheduler`] tracks the state of a single output and tells you when it should be
+//! repainted according to its [`RepaintMode`], which can be changed at runtime. Report the
+//! events of the output to it, check [`should_repaint`](RepaintScheduler::should_repaint) when
+//! processing them, and for fixed rates arm a timer (for example with
+//! [`Scheduler`](crate::utils::timer::Scheduler)) at [`next_deadline`](RepaintScheduler::next_deadline).
+//!
+//! All times are given on the `CLOCK_MONOTONIC` clock, like the ones of [`VblankTime`](super::VblankTime).
+//!
+//! [`RepaintSchedulers`] groups the schedulers of all outputs, and lets you force repaints after
+//! changes of the compositor itself, like switching themes, that no client damage reports:
+//! [`schedule_redraw`](RepaintSchedulers::schedule_redraw) for a single output, or
+//! [`damage_all`](RepaintSchedulers::damage_all) for all of them.
+//!
+
+use std::{collections::HashMap, fmt, hash::Hash, time::Duration};
+
+/// How an output is repainted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepaintMode {
+    /// Repaint after a vblank, if the content of the output changed
+    Vsync,
+    /// Repaint at a fixed interval, regardless of damage
+    ///
+    /// Repaints are paced by the interval instead of the vblank events. If the previous
+    /// frame is still waiting to be displayed when the interval elapses, the repaint is
+    /// delayed until its vblank.
+    FixedRate(Duration),
+    /// Only repaint when [`request_repaint`](RepaintScheduler::request_repaint) was called
+    OnDemand,
+}
+
+impl RepaintMode {
+    /// Repaint at a fixed rate of `hz` frames per second
+    pub fn fixed_hz(hz: u32) -> RepaintMode {
+        RepaintMode::FixedRate(Duration::from_secs(1) / hz.max(1))
+    }
+}
+
+impl Default for RepaintMode {
+    fn default() -> Self {
+        RepaintMode::Vsync
+    }
+}
+
+/// Repaint scheduling state of a single output
+pub struct RepaintScheduler {
+    mode: RepaintMode,
+    damaged: bool,
+    requested: bool,
+    // a page-flip was submitted and its vblank was not received yet
+    frame_pending: bool,
+    last_repaint: Option<Duration>,
+    log: ::slog::Logger,
+}
+
+impl RepaintScheduler {
+    /// Create the scheduling state of an output, initially needing a repaint
+    pub fn new<L>(mode: RepaintMode, logger: L) -> RepaintScheduler
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        RepaintScheduler {
+            mode,
+            damaged: true,
+            requested: true,
+            frame_pending: false,
+            last_repaint: None,
+            log: crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_drm_scheduling")),
+        }
+    }
+
+    /// Access the current repaint mode
+    pub fn mode(&self) -> RepaintMode {
+        self.mode
+    }
+
+    /// Change the repaint mode
+    ///
+    /// The output is repainted as soon as possible in the new mode.
+    pub fn set_mode(&mut self, mode: RepaintMode) {
+        if mode != self.mode {
+            debug!(self.log, "Changing repaint mode"; "mode" => format!("{:?}", mode));
+            self.mode = mode;
+            self.requested = true;
+            self.last_repaint = None;
+        }
+    }
+
+    /// Report that the content of the output changed
+    ///
+    /// It is ignored in [`RepaintMode::OnDemand`].
+    pub fn damage(&mut self) {
+        self.damaged = true;
+    }
+
+    /// Ask for the output to be repainted, in every mode
+    pub fn request_repaint(&mut self) {
+        self.requested = true;
+    }
+
+    /// Whether the output should be repainted now
+    pub fn should_repaint(&self, now: Duration) -> bool {
+        if self.frame_pending {
+            // the previous page-flip must complete first
+            return false;
+        }
+        match self.mode {
+            RepaintMode::Vsync => self.damaged || self.requested,
+            RepaintMode::FixedRate(interval) => {
+                self.requested
+                    || self
+                        .last_repaint
+                        .map(|last| now >= last + interval)
+                        .unwrap_or(true)
+            }
+            RepaintMode::OnDemand => self.requested,
+        }
+    }
+
+    /// When the output should next be repainted, if known in advance
+    ///
+    /// Only fixed rates have deadlines, `None` is returned in the other modes, as well as
+    /// when a repaint is already due.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        match self.mode {
+            RepaintMode::FixedRate(interval) if !self.requested => {
+                self.last_repaint.map(|last| last + interval)
+            }
+            _ => None,
+        }
+    }
+
+    /// Report that the output was repainted and a page-flip submitted at `now`
+    pub fn frame_submitted(&mut self, now: Duration) {
+        self.damaged = false;
+        self.requested = false;
+        self.frame_pending = true;
+        self.last_repaint = Some(match (self.mode, self.last_repaint) {
+            // keep a steady pace, unless we fell behind by more than a frame
+            (RepaintMode::FixedRate(interval), Some(last)) if now < last + interval * 2 => {
+                (last + interval).min(now)
+            }
+            _ => now,
+        });
+    }
+
+    /// Report that nothing was submitted after all, for example because the repaint failed
+    pub fn frame_skipped(&mut self) {
+        self.frame_pending = false;
+    }
+
+    /// Report the vblank of the last submitted frame
+    pub fn vblank(&mut self) {
+        self.frame_pending = false;
+    }
+}
+
+impl fmt::Debug for RepaintScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RepaintScheduler")
+            .field("mode", &self.mode)
+            .field("damaged", &self.damaged)
+            .field("requested", &self.requested)
+            .field("frame_pending", &self.frame_pending)
+            .finish()
+    }
+}
+
+/// The repaint schedulers of a set of outputs
+///
+/// Outputs are identified by any key you like, typically the crtc they are displayed on.
+#[derive(Debug)]
+pub struct RepaintSchedulers<K: Eq + Hash> {
+    schedulers: HashMap<K, RepaintScheduler>,
+}
+
+impl<K: Eq + Hash> Default for RepaintSchedulers<K> {
+    fn default() -> Self {
+        RepaintSchedulers {
+            schedulers: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> RepaintSchedulers<K> {
+    /// Create an empty set of schedulers
+    pub fn new() -> RepaintSchedulers<K> {
+        RepaintSchedulers::default()
+    }
+
+    /// Add the scheduler of an output, replacing the previous one if any
+    pub fn insert(&mut self, output: K, scheduler: RepaintScheduler) {
+        self.schedulers.insert(output, scheduler);
+    }
+
+    /// Remove the scheduler of an output
+    pub fn remove(&mut self, output: &K) -> Option<RepaintScheduler> {
+        self.schedulers.remove(output)
+    }
+
+    /// Access the scheduler of an output
+    pub fn get(&self, output: &K) -> Option<&RepaintScheduler> {
+        self.schedulers.get(output)
+    }
+
+    /// Mutably access the scheduler of an output
+    pub fn get_mut(&mut self, output: &K) -> Option<&mut RepaintScheduler> {
+        self.schedulers.get_mut(output)
+    }
+
+    /// Force a repaint of an output, whatever its repaint mode
+    ///
+    /// Returns `false` if the output is unknown.
+    pub fn schedule_redraw(&mut self, output: &K) -> bool {
+        match self.schedulers.get_mut(output) {
+            Some(scheduler) => {
+                scheduler.request_repaint();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Force a repaint of all outputs, whatever their repaint mode
+    pub fn damage_all(&mut self) {
+        for scheduler in self.schedulers.values_mut() {
+            scheduler.request_repaint();
+        }
+    }
+
+    /// The outputs that should be repainted now
+    pub fn due(&self, now: Duration) -> Vec<K> {
+        self.schedulers
+            .iter()
+            .filter(|(_, scheduler)| scheduler.should_repaint(now))
+            .map(|(output, _)| output.clone())
+            .collect()
+    }
+
+    /// The earliest deadline of the outputs with a fixed repaint rate
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.schedulers
+            .values()
+            .filter_map(|scheduler| scheduler.next_deadline())
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RepaintMode, RepaintScheduler, RepaintSchedulers};
+    use std::time::Duration;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn vsync_repaints_on_damage() {
+        let mut scheduler = RepaintScheduler::new(RepaintMode::Vsync, None);
+        assert!(scheduler.should_repaint(ms(0)));
+        scheduler.frame_submitted(ms(0));
+        scheduler.damage();
+        // waiting for the vblank
+        assert!(!scheduler.should_repaint(ms(5)));
+        scheduler.vblank();
+        assert!(scheduler.should_repaint(ms(16)));
+        scheduler.frame_submitted(ms(16));
+        scheduler.vblank();
+        assert!(!scheduler.should_repaint(ms(32)));
+        assert_eq!(scheduler.next_deadline(), None);
+    }
+
+    #[test]
+    fn fixed_rate_ignores_damage() {
+        let mut scheduler = RepaintScheduler::new(RepaintMode::fixed_hz(50), None);
+        scheduler.frame_submitted(ms(0));
+        scheduler.vblank();
+        assert_eq!(scheduler.next_deadline(), Some(ms(20)));
+        assert!(!scheduler.should_repaint(ms(10)));
+        assert!(scheduler.should_repaint(ms(20)));
+        // woken up late, the pace is kept
+        scheduler.frame_submitted(ms(23));
+        scheduler.vblank();
+        assert_eq!(scheduler.next_deadline(), Some(ms(40)));
+        // too late, start over
+        scheduler.frame_submitted(ms(100));
+        assert_eq!(scheduler.next_deadline(), Some(ms(120)));
+    }
+
+    #[test]
+    fn on_demand_only_when_requested() {
+        let mut scheduler = RepaintScheduler::new(RepaintMode::OnDemand, None);
+        scheduler.frame_submitted(ms(0));
+        scheduler.vblank();
+        scheduler.damage();
+        assert!(!scheduler.should_repaint(ms(100)));
+        scheduler.request_repaint();
+        assert!(scheduler.should_repaint(ms(100)));
+
+        scheduler.frame_submitted(ms(100));
+        scheduler.vblank();
+        scheduler.set_mode(RepaintMode::Vsync);
+        assert!(scheduler.should_repaint(ms(110)));
+    }
+
+    #[test]
+    fn forced_redraws() {
+        let mut schedulers = RepaintSchedulers::new();
+        for output in 0..2 {
+            let mut scheduler = RepaintScheduler::new(RepaintMode::OnDemand, None);
+            scheduler.frame_submitted(ms(0));
+            scheduler.vblank();
+            schedulers.insert(output, scheduler);
+        }
+        assert!(schedulers.due(ms(10)).is_empty());
+        assert!(schedulers.schedule_redraw(&1));
+        assert!(!schedulers.schedule_redraw(&2));
+        assert_eq!(schedulers.due(ms(10)), vec![1]);
+        schedulers.damage_all();
+        let mut due = schedulers.due(ms(10));
+        due.sort_unstable();
+        assert_eq!(due, vec![0, 1]);
+    }
+}
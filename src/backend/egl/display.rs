@@ -10,6 +10,11 @@ use std::sync::Arc;
 
 use nix::libc::c_int;
 
+#[cfg(all(feature = "backend_drm", feature = "wayland_frontend"))]
+use crate::{
+    backend::egl::EglExtensionNotSupportedError,
+    wayland::dmabuf::{BufferFlags, BufferInfo, Format as DmabufFormat, DRM_FORMAT_MOD_INVALID},
+};
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::{protocol::wl_buffer::WlBuffer, Display};
 #[cfg(feature = "use_system_lib")]
@@ -397,6 +402,218 @@ impl<B: native::Backend, N: native::NativeDisplay<B>> EGLDisplay<B, N> {
         self.extensions.clone()
     }
 
+    /// Returns the dmabuf formats and modifiers this display can import
+    ///
+    /// The formats that can be imported without explicit modifier are listed with the
+    /// [`DRM_FORMAT_MOD_INVALID`] modifier. The formats and modifiers that can only be sampled
+    /// as external textures are left out, as the renderer cannot bind them. The result can directly be used to initialize
+    /// the [`dmabuf` global](::wayland::dmabuf::init_dmabuf_global).
+    ///
+    /// Requires the `EGL_EXT_image_dma_buf_import_modifiers` extension.
+    #[cfg(all(feature = "backend_drm", feature = "wayland_frontend"))]
+    pub fn dmabuf_formats(&self) -> Result<Vec<DmabufFormat>, Error> {
+        if !self
+            .extensions
+            .iter()
+            .any(|s| s == "EGL_EXT_image_dma_buf_import_modifiers")
+        {
+            return Err(Error::EglExtensionNotSupported(&[
+                "EGL_EXT_image_dma_buf_import_modifiers",
+            ]));
+        }
+
+        let mut num: i32 = 0;
+        wrap_egl_call(|| unsafe {
+            ffi::egl::QueryDmaBufFormatsEXT(**self.display, 0, ::std::ptr::null_mut(), &mut num)
+        })
+        .map_err(Error::DmabufFormatsQueryFailed)?;
+        let mut fourccs: Vec<i32> = vec![0; num as usize];
+        wrap_egl_call(|| unsafe {
+            ffi::egl::QueryDmaBufFormatsEXT(**self.display, num, fourccs.as_mut_ptr(), &mut num)
+        })
+        .map_err(Error::DmabufFormatsQueryFailed)?;
+        fourccs.truncate(num as usize);
+
+        let mut formats = Vec::new();
+        for fourcc in fourccs {
+            let format = match ::drm::buffer::format::PixelFormat::from_raw(fourcc as u32) {
+                Some(format) => format,
+                None => {
+                    debug!(self.logger, "Skipping unknown dmabuf format {:x}", fourcc);
+                    continue;
+                }
+            };
+            let mut num: i32 = 0;
+            wrap_egl_call(|| unsafe {
+                ffi::egl::QueryDmaBufModifiersEXT(
+                    **self.display,
+                    fourcc,
+                    0,
+                    ::std::ptr::null_mut(),
+                    ::std::ptr::null_mut(),
+                    &mut num,
+                )
+            })
+            .map_err(Error::DmabufFormatsQueryFailed)?;
+            let mut modifiers: Vec<u64> = vec![0; num as usize];
+            let mut external_only: Vec<ffi::egl::types::EGLBoolean> = vec![0; num as usize];
+            wrap_egl_call(|| unsafe {
+                ffi::egl::QueryDmaBufModifiersEXT(
+                    **self.display,
+                    fourcc,
+                    num,
+                    modifiers.as_mut_ptr(),
+                    external_only.as_mut_ptr(),
+                    &mut num,
+                )
+            })
+            .map_err(Error::DmabufFormatsQueryFailed)?;
+            modifiers.truncate(num as usize);
+            external_only.truncate(num as usize);
+
+            // buffers that can only be sampled as `GL_TEXTURE_EXTERNAL_OES` cannot be bound by the
+            // renderer, do not let clients allocate them
+            let all_external = !modifiers.is_empty() && external_only.iter().all(|&external| external != 0);
+            if all_external {
+                debug!(self.logger, "Skipping external-only dmabuf format {:x}", fourcc);
+                continue;
+            }
+            let mut modifiers = modifiers
+                .into_iter()
+                .zip(external_only)
+                .filter(|&(_, external)| external == 0)
+                .map(|(modifier, _)| modifier)
+                .collect::<Vec<_>>();
+
+            let plane_count = dmabuf_plane_count(fourcc as u32);
+            // implicit modifiers are always supported for the formats the display can import
+            modifiers.push(DRM_FORMAT_MOD_INVALID);
+            formats.extend(modifiers.into_iter().map(|modifier| DmabufFormat {
+                format,
+                modifier,
+                plane_count,
+            }));
+        }
+        debug!(self.logger, "Supported dmabuf formats: {}", formats.len());
+        Ok(formats)
+    }
+
+    /// Import a dmabuf submitted by a client, to render it
+    ///
+    /// Requires the `EGL_EXT_image_dma_buf_import` extension, and
+    /// `EGL_EXT_image_dma_buf_import_modifiers` for the buffers using explicit modifiers.
+    #[cfg(all(feature = "backend_drm", feature = "wayland_frontend"))]
+    pub fn create_image_from_dmabuf(
+        &self,
+        buffer: &BufferInfo,
+    ) -> ::std::result::Result<EGLImages, BufferAccessError> {
+        if !self
+            .extensions
+            .iter()
+            .any(|s| s == "EGL_EXT_image_dma_buf_import")
+        {
+            return Err(EglExtensionNotSupportedError(&["EGL_EXT_image_dma_buf_import"]).into());
+        }
+        const PLANE_ATTRIBS: [[u32; 5]; 4] = [
+            [
+                ffi::egl::DMA_BUF_PLANE0_FD_EXT,
+                ffi::egl::DMA_BUF_PLANE0_OFFSET_EXT,
+                ffi::egl::DMA_BUF_PLANE0_PITCH_EXT,
+                ffi::egl::DMA_BUF_PLANE0_MODIFIER_LO_EXT,
+                ffi::egl::DMA_BUF_PLANE0_MODIFIER_HI_EXT,
+            ],
+            [
+                ffi::egl::DMA_BUF_PLANE1_FD_EXT,
+                ffi::egl::DMA_BUF_PLANE1_OFFSET_EXT,
+                ffi::egl::DMA_BUF_PLANE1_PITCH_EXT,
+                ffi::egl::DMA_BUF_PLANE1_MODIFIER_LO_EXT,
+                ffi::egl::DMA_BUF_PLANE1_MODIFIER_HI_EXT,
+            ],
+            [
+                ffi::egl::DMA_BUF_PLANE2_FD_EXT,
+                ffi::egl::DMA_BUF_PLANE2_OFFSET_EXT,
+                ffi::egl::DMA_BUF_PLANE2_PITCH_EXT,
+                ffi::egl::DMA_BUF_PLANE2_MODIFIER_LO_EXT,
+                ffi::egl::DMA_BUF_PLANE2_MODIFIER_HI_EXT,
+            ],
+            [
+                ffi::egl::DMA_BUF_PLANE3_FD_EXT,
+                ffi::egl::DMA_BUF_PLANE3_OFFSET_EXT,
+                ffi::egl::DMA_BUF_PLANE3_PITCH_EXT,
+                ffi::egl::DMA_BUF_PLANE3_MODIFIER_LO_EXT,
+                ffi::egl::DMA_BUF_PLANE3_MODIFIER_HI_EXT,
+            ],
+        ];
+
+        let mut out: Vec<c_int> = Vec::with_capacity(7 + 10 * buffer.planes.len());
+        out.extend(&[
+            ffi::egl::WIDTH as i32,
+            buffer.width,
+            ffi::egl::HEIGHT as i32,
+            buffer.height,
+            ffi::egl::LINUX_DRM_FOURCC_EXT as i32,
+            buffer.format as i32,
+        ]);
+        for plane in &buffer.planes {
+            let attribs = PLANE_ATTRIBS
+                .get(plane.plane_idx as usize)
+                .ok_or(BufferAccessError::EGLImageCreationFailed(EGLError::BadParameter))?;
+            out.extend(&[
+                attribs[0] as i32,
+                plane.fd,
+                attribs[1] as i32,
+                plane.offset as i32,
+                attribs[2] as i32,
+                plane.stride as i32,
+            ]);
+            if plane.modifier != DRM_FORMAT_MOD_INVALID {
+                if !self
+                    .extensions
+                    .iter()
+                    .any(|s| s == "EGL_EXT_image_dma_buf_import_modifiers")
+                {
+                    return Err(
+                        EglExtensionNotSupportedError(&["EGL_EXT_image_dma_buf_import_modifiers"]).into(),
+                    );
+                }
+                out.extend(&[
+                    attribs[3] as i32,
+                    plane.modifier as u32 as i32,
+                    attribs[4] as i32,
+                    (plane.modifier >> 32) as u32 as i32,
+                ]);
+            }
+        }
+        out.push(ffi::egl::NONE as i32);
+
+        let image = wrap_egl_call(|| unsafe {
+            ffi::egl::CreateImageKHR(
+                **self.display,
+                ffi::egl::NO_CONTEXT,
+                ffi::egl::LINUX_DMA_BUF_EXT,
+                ::std::ptr::null_mut(),
+                out.as_ptr(),
+            )
+        })
+        .map_err(BufferAccessError::EGLImageCreationFailed)?;
+
+        Ok(EGLImages {
+            display: self.display.clone(),
+            width: buffer.width as u32,
+            height: buffer.height as u32,
+            y_inverted: buffer.flags.contains(BufferFlags::Y_INVERT),
+            // multi-planar formats are converted to RGB by the driver when sampled
+            format: if buffer.planes.len() > 1 {
+                Format::External
+            } else {
+                Format::RGBA
+            },
+            images: vec![image],
+            #[cfg(feature = "renderer_gl")]
+            gl: gl_ffi::Gles2::load_with(|s| get_proc_address(s) as *const _),
+        })
+    }
+
     /// Borrow the underlying native display.
     ///
     /// This follows the same semantics as [`std::cell:RefCell`](std::cell::RefCell).
@@ -417,6 +634,42 @@ impl<B: native::Backend, N: native::NativeDisplay<B>> EGLDisplay<B, N> {
     }
 }
 
+// number of planes of a dmabuf format without explicit modifier
+#[cfg(all(feature = "backend_drm", feature = "wayland_frontend"))]
+fn dmabuf_plane_count(fourcc: u32) -> u32 {
+    fn code(name: &[u8; 4]) -> u32 {
+        name[0] as u32 | (name[1] as u32) << 8 | (name[2] as u32) << 16 | (name[3] as u32) << 24
+    }
+    match fourcc {
+        x if x == code(b"NV12")
+            || x == code(b"NV21")
+            || x == code(b"NV16")
+            || x == code(b"NV61")
+            || x == code(b"NV24")
+            || x == code(b"NV42")
+            || x == code(b"P010")
+            || x == code(b"P012")
+            || x == code(b"P016") =>
+        {
+            2
+        }
+        x if x == code(b"YUV9")
+            || x == code(b"YVU9")
+            || x == code(b"YU11")
+            || x == code(b"YV11")
+            || x == code(b"YU12")
+            || x == code(b"YV12")
+            || x == code(b"YU16")
+            || x == code(b"YV16")
+            || x == code(b"YU24")
+            || x == code(b"YV24") =>
+        {
+            3
+        }
+        _ => 1,
+    }
+}
+
 #[cfg(feature = "use_system_lib")]
 impl<B: native::Backend, N: native::NativeDisplay<B>> EGLGraphicsBackend for EGLDisplay<B, N> {
     /// Binds this EGL display to the given Wayland display.
@@ -45,6 +45,9 @@ pub enum Error {
     /// Failed to create `EGLImages` from the buffer
     #[error("Failed to create `EGLImages` from the buffer")]
     EGLImageCreationFailed,
+    /// Failed to query the dmabuf formats supported by the display
+    #[error("Failed to query the supported dmabuf formats. Err: {0:}")]
+    DmabufFormatsQueryFailed(#[source] EGLError),
 }
 
 /// Raw EGL error
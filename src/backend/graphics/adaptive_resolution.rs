@@ -0,0 +1,218 @@
+//! Adaptive resolution of the composition
+//!
+//! Underpowered GPUs driving high resolution panels may not be able to compose every frame at
+//! the native resolution of the output in time. Instead of dropping frames, the composition
+//! can be rendered at a reduced internal resolution and upscaled onto the output.
+//!
+//! [`AdaptiveResolution`] decides this scale: report how long the GPU took to render each
+//! frame with [`frame_rendered`](AdaptiveResolution::frame_rendered), and it lowers the scale
+//! after several frames over budget, and raises it back towards the native resolution once
+//! rendering is comfortably within budget again. Render your frame at
+//! [`render_size`](AdaptiveResolution::render_size), then upscale it onto the output (the
+//! `glium` module provides a `ReducedResolutionBuffer` for this).
+//!
+//! ```
+//! # extern crate smithay;
+//! use std::time::Duration;
+//! use smithay::backend::graphics::adaptive_resolution::{AdaptiveResolution, AdaptiveResolutionConfig};
+//!
+//! let mut adaptive = AdaptiveResolution::new(AdaptiveResolutionConfig::for_refresh_rate(60));
+//! assert_eq!(adaptive.render_size((3840, 2160)), (3840, 2160));
+//! // the GPU keeps missing the budget of a 60Hz output
+//! for _ in 0..10 {
+//!     adaptive.frame_rendered(Duration::from_millis(25));
+//! }
+//! assert!(adaptive.scale() < 1.0);
+//! ```
+
+use std::time::Duration;
+
+/// Configuration of an [`AdaptiveResolution`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveResolutionConfig {
+    /// Time available to render a frame, usually a bit less than the refresh interval
+    pub budget: Duration,
+    /// The lowest scale the resolution can be reduced to
+    pub min_scale: f64,
+    /// How much the scale changes at once
+    pub step: f64,
+    /// Number of consecutive frames over budget before the scale is lowered
+    pub downscale_after: u32,
+    /// Number of consecutive frames within `headroom` of the budget before the scale is raised
+    pub upscale_after: u32,
+    /// Fraction of the budget rendering must stay under for the scale to be raised
+    ///
+    /// Keeping this well under `1.0` prevents oscillating between two scales.
+    pub headroom: f64,
+}
+
+impl AdaptiveResolutionConfig {
+    /// A configuration suited to an output refreshing `hz` times per second
+    ///
+    /// The budget is 90% of the refresh interval, and the resolution can be lowered down to
+    /// half of the native one.
+    pub fn for_refresh_rate(hz: u32) -> AdaptiveResolutionConfig {
+        AdaptiveResolutionConfig {
+            budget: Duration::from_millis(900) / hz.max(1),
+            min_scale: 0.5,
+            step: 0.125,
+            downscale_after: 3,
+            upscale_after: 60,
+            headroom: 0.6,
+        }
+    }
+}
+
+/// Decides the internal resolution of the composition of an output
+#[derive(Debug, Clone)]
+pub struct AdaptiveResolution {
+    config: AdaptiveResolutionConfig,
+    enabled: bool,
+    scale: f64,
+    over_budget: u32,
+    under_budget: u32,
+}
+
+impl AdaptiveResolution {
+    /// Create a new controller, starting at the native resolution
+    pub fn new(config: AdaptiveResolutionConfig) -> AdaptiveResolution {
+        AdaptiveResolution {
+            config,
+            enabled: true,
+            scale: 1.0,
+            over_budget: 0,
+            under_budget: 0,
+        }
+    }
+
+    /// The current configuration
+    pub fn config(&self) -> &AdaptiveResolutionConfig {
+        &self.config
+    }
+
+    /// Change the configuration, for example after a mode change
+    pub fn set_config(&mut self, config: AdaptiveResolutionConfig) {
+        self.config = config;
+        self.scale = self.scale.max(config.min_scale);
+        self.over_budget = 0;
+        self.under_budget = 0;
+    }
+
+    /// Enable or disable the adaptation
+    ///
+    /// While disabled, the composition is always rendered at the native resolution.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.scale = 1.0;
+            self.over_budget = 0;
+            self.under_budget = 0;
+        }
+    }
+
+    /// Whether the adaptation is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The current scale of the internal resolution, `1.0` being the native resolution
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Whether the composition is currently rendered below the native resolution
+    pub fn is_reduced(&self) -> bool {
+        self.scale < 1.0
+    }
+
+    /// The size to render the composition of an output of the given native size at
+    pub fn render_size(&self, size: (u32, u32)) -> (u32, u32) {
+        if !self.is_reduced() {
+            return size;
+        }
+        let scale = |length: u32| ((f64::from(length) * self.scale).round() as u32).max(1);
+        (scale(size.0), scale(size.1))
+    }
+
+    /// Report the time the GPU took to render a frame
+    ///
+    /// Returns the new scale if it changed, in which case the next frame should be rendered
+    /// at the new [`render_size`](AdaptiveResolution::render_size).
+    pub fn frame_rendered(&mut self, render_time: Duration) -> Option<f64> {
+        if !self.enabled {
+            return None;
+        }
+        let budget = self.config.budget;
+        if render_time > budget {
+            self.under_budget = 0;
+            self.over_budget += 1;
+            if self.over_budget >= self.config.downscale_after && self.scale > self.config.min_scale {
+                self.over_budget = 0;
+                self.scale = (self.scale - self.config.step).max(self.config.min_scale);
+                return Some(self.scale);
+            }
+        } else if render_time.as_secs_f64() <= budget.as_secs_f64() * self.config.headroom {
+            self.over_budget = 0;
+            self.under_budget += 1;
+            if self.under_budget >= self.config.upscale_after && self.scale < 1.0 {
+                self.under_budget = 0;
+                self.scale = (self.scale + self.config.step).min(1.0);
+                return Some(self.scale);
+            }
+        } else {
+            // within budget, but not by enough to raise the resolution
+            self.over_budget = 0;
+            self.under_budget = 0;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn down_and_up() {
+        let config = AdaptiveResolutionConfig {
+            budget: Duration::from_millis(10),
+            min_scale: 0.5,
+            step: 0.25,
+            downscale_after: 2,
+            upscale_after: 3,
+            headroom: 0.5,
+        };
+        let mut adaptive = AdaptiveResolution::new(config);
+        let slow = Duration::from_millis(15);
+        let fast = Duration::from_millis(4);
+
+        assert_eq!(adaptive.frame_rendered(slow), None);
+        assert_eq!(adaptive.frame_rendered(slow), Some(0.75));
+        assert_eq!(adaptive.frame_rendered(slow), None);
+        assert_eq!(adaptive.frame_rendered(slow), Some(0.5));
+        // already at the minimum
+        assert_eq!(adaptive.frame_rendered(slow), None);
+        assert_eq!(adaptive.frame_rendered(slow), None);
+        assert_eq!(adaptive.render_size((3840, 2160)), (1920, 1080));
+
+        // frames close to the budget reset the count
+        adaptive.frame_rendered(fast);
+        adaptive.frame_rendered(fast);
+        adaptive.frame_rendered(Duration::from_millis(8));
+        adaptive.frame_rendered(fast);
+        adaptive.frame_rendered(fast);
+        assert_eq!(adaptive.frame_rendered(fast), Some(0.75));
+
+        adaptive.set_enabled(false);
+        assert_eq!(adaptive.render_size((3840, 2160)), (3840, 2160));
+        assert_eq!(adaptive.frame_rendered(slow), None);
+    }
+
+    #[test]
+    fn refresh_rate_budget() {
+        let budget = |hz| AdaptiveResolutionConfig::for_refresh_rate(hz).budget;
+        assert_eq!(budget(60), Duration::from_millis(15));
+        assert_eq!(budget(0), Duration::from_millis(900));
+        assert_eq!(budget(u32::MAX), Duration::from_nanos(0));
+    }
+}
@@ -4,7 +4,10 @@ use crate::backend::graphics::{gl::GLGraphicsBackend, SwapBuffersError};
 use glium::{
     backend::{Backend, Context, Facade},
     debug::DebugCallbackBehavior,
-    SwapBuffersError as GliumSwapBuffersError,
+    framebuffer::SimpleFrameBuffer,
+    texture::{Texture2d, TextureCreationError},
+    uniforms::MagnifySamplerFilter,
+    Surface, SwapBuffersError as GliumSwapBuffersError,
 };
 use std::{
     cell::{Cell, Ref, RefCell, RefMut},
@@ -241,3 +244,52 @@ impl glium::Surface for Frame {
         self.0.blit_color(source_rect, target, target_rect, filter)
     }
 }
+
+/// An offscreen buffer to compose an output at a reduced resolution
+///
+/// Render the composition into its [`surface`](ReducedResolutionBuffer::surface), then upscale
+/// it onto the frame of the output with [`upscale_to`](ReducedResolutionBuffer::upscale_to),
+/// typically at the size given by an
+/// [`AdaptiveResolution`](crate::backend::graphics::adaptive_resolution::AdaptiveResolution).
+pub struct ReducedResolutionBuffer {
+    texture: Texture2d,
+}
+
+impl ReducedResolutionBuffer {
+    /// Allocate a buffer of the given size
+    pub fn new<F: Facade>(
+        facade: &F,
+        size: (u32, u32),
+    ) -> Result<ReducedResolutionBuffer, TextureCreationError> {
+        Ok(ReducedResolutionBuffer {
+            texture: Texture2d::empty(facade, size.0, size.1)?,
+        })
+    }
+
+    /// Size of this buffer
+    ///
+    /// Allocate a new buffer once the render size changes.
+    pub fn size(&self) -> (u32, u32) {
+        self.texture.dimensions()
+    }
+
+    /// The surface to render the composition into
+    pub fn surface(&self) -> SimpleFrameBuffer<'_> {
+        self.texture.as_surface()
+    }
+
+    /// Upscale the composition onto the whole target, filtering it with linear interpolation
+    pub fn upscale_to<S: Surface>(&self, target: &S) {
+        self.texture
+            .as_surface()
+            .fill(target, MagnifySamplerFilter::Linear);
+    }
+}
+
+impl ::std::fmt::Debug for ReducedResolutionBuffer {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("ReducedResolutionBuffer")
+            .field("size", &self.size())
+            .finish()
+    }
+}
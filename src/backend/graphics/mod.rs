@@ -2,6 +2,8 @@
 //!
 //! Note: Not every API may be supported by every backend
 
+pub mod adaptive_resolution;
+
 mod cursor;
 pub use self::cursor::*;
 
@@ -1,6 +1,6 @@
 //! Common traits for input backends to receive input from.
 
-use std::{error::Error, string::ToString};
+use std::{error::Error, path::PathBuf, string::ToString};
 
 /// A seat describes a group of input devices and at least one
 /// graphics device belonging together.
@@ -251,6 +251,21 @@ pub trait PointerMotionEvent: Event {
     fn delta_x(&self) -> f64;
     /// Delta on the y axis between the last and new pointer device position interpreted as pixel movement
     fn delta_y(&self) -> f64;
+
+    /// Delta between the last and new pointer device position, before pointer acceleration
+    ///
+    /// The default implementation returns the accelerated delta, for backends not applying any
+    /// acceleration.
+    fn delta_unaccel(&self) -> (f64, f64) {
+        self.delta()
+    }
+
+    /// Timestamp of the event in microseconds
+    ///
+    /// The default implementation derives it from the millisecond timestamp of the event.
+    fn time_usec(&self) -> u64 {
+        self.time() as u64 * 1000
+    }
 }
 
 impl PointerMotionEvent for UnusedEvent {
@@ -332,6 +347,10 @@ impl TouchSlot {
     pub(crate) fn new(id: u64) -> Self {
         TouchSlot { id }
     }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
 }
 
 /// Trait for touch events starting at a given position.
@@ -487,6 +506,712 @@ pub trait TouchFrameEvent: Event {}
 
 impl TouchFrameEvent for UnusedEvent {}
 
+/// Description of a graphics tablet
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TabletDescriptor {
+    /// Name of the tablet
+    pub name: String,
+    /// USB vendor and product ids of the tablet, if it is a USB device
+    pub usb_id: Option<(u32, u32)>,
+    /// Path of the device node of the tablet, if known
+    pub syspath: Option<PathBuf>,
+}
+
+/// Physical type of a tablet tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TabletToolType {
+    /// A generic pen
+    Pen,
+    /// The eraser end of a pen
+    Eraser,
+    /// A paintbrush-like tool
+    Brush,
+    /// A physical drawing tool, e.g. a pencil
+    Pencil,
+    /// An airbrush-like tool
+    Airbrush,
+    /// A mouse bound to the tablet
+    Mouse,
+    /// A mouse tool with a lens
+    Lens,
+    /// A tool of an unknown type
+    Unknown,
+}
+
+/// The axes a tablet tool provides in addition to its position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TabletToolCapabilities {
+    /// Tilt along the X and Y axes
+    pub tilt: bool,
+    /// Pressure applied on the tip
+    pub pressure: bool,
+    /// Distance from the surface of the tablet
+    pub distance: bool,
+    /// Rotation around the Z axis
+    pub rotation: bool,
+    /// Position of a slider on the tool
+    pub slider: bool,
+    /// Relative wheel on the tool
+    pub wheel: bool,
+}
+
+/// Description of a tablet tool
+///
+/// Tools with a hardware serial can be tracked across tablets, tools without one are
+/// identified by their type only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TabletToolDescriptor {
+    /// Physical type of the tool
+    pub tool_type: TabletToolType,
+    /// Unique serial number of the tool, 0 if unknown
+    pub hardware_serial: u64,
+    /// Wacom hardware id of the tool, 0 if unknown
+    pub hardware_id_wacom: u64,
+    /// Axes provided by the tool
+    pub capabilities: TabletToolCapabilities,
+}
+
+/// Whether a tablet tool is in proximity of its tablet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProximityState {
+    /// The tool left the detectable range of the tablet
+    Out,
+    /// The tool entered the detectable range of the tablet
+    In,
+}
+
+/// Whether the tip of a tablet tool touches its tablet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabletToolTipState {
+    /// The tip is in contact with the tablet
+    Down,
+    /// The tip is no longer in contact with the tablet
+    Up,
+}
+
+/// Trait for the events of tablet tools, carrying the state of all their axes
+pub trait TabletToolEvent: Event {
+    /// The tablet the event comes from
+    fn tablet(&self) -> TabletDescriptor;
+
+    /// The tool that generated the event
+    fn tool(&self) -> TabletToolDescriptor;
+
+    /// Delta between the last and the new position of the tool
+    fn delta(&self) -> (f64, f64) {
+        (self.delta_x(), self.delta_y())
+    }
+
+    /// Delta on the x axis between the last and the new position of the tool
+    fn delta_x(&self) -> f64;
+    /// Delta on the y axis between the last and the new position of the tool
+    fn delta_y(&self) -> f64;
+
+    /// Position of the tool on the tablet, in millimeters from its top-left corner
+    fn position(&self) -> (f64, f64) {
+        (self.x(), self.y())
+    }
+
+    /// Position of the tool on the x axis, in millimeters
+    fn x(&self) -> f64;
+    /// Position of the tool on the y axis, in millimeters
+    fn y(&self) -> f64;
+
+    /// Position of the tool, transformed into a given coordinate space
+    fn position_transformed(&self, coordinate_space: (u32, u32)) -> (f64, f64) {
+        (
+            self.x_transformed(coordinate_space.0),
+            self.y_transformed(coordinate_space.1),
+        )
+    }
+
+    /// Position of the tool on the x axis, transformed into a space of the given width
+    fn x_transformed(&self, width: u32) -> f64;
+    /// Position of the tool on the y axis, transformed into a space of the given height
+    fn y_transformed(&self, height: u32) -> f64;
+
+    /// Distance of the tool from the tablet, normalized between 0 and 1
+    fn distance(&self) -> f64;
+    /// Whether the distance changed in this event
+    fn distance_has_changed(&self) -> bool;
+
+    /// Pressure applied on the tip, normalized between 0 and 1
+    fn pressure(&self) -> f64;
+    /// Whether the pressure changed in this event
+    fn pressure_has_changed(&self) -> bool;
+
+    /// Tilt of the tool along the x and y axes, in degrees from the perpendicular
+    fn tilt(&self) -> (f64, f64) {
+        (self.tilt_x(), self.tilt_y())
+    }
+
+    /// Tilt of the tool along the x axis, in degrees
+    fn tilt_x(&self) -> f64;
+    /// Tilt of the tool along the y axis, in degrees
+    fn tilt_y(&self) -> f64;
+    /// Whether the tilt changed in this event
+    fn tilt_has_changed(&self) -> bool;
+
+    /// Rotation of the tool around the z axis, in degrees clockwise from its logical neutral position
+    fn rotation(&self) -> f64;
+    /// Whether the rotation changed in this event
+    fn rotation_has_changed(&self) -> bool;
+
+    /// Position of the slider of the tool, normalized between -1 and 1
+    fn slider_position(&self) -> f64;
+    /// Whether the slider position changed in this event
+    fn slider_has_changed(&self) -> bool;
+
+    /// Delta of the wheel of the tool, in degrees
+    fn wheel_delta(&self) -> f64;
+    /// Delta of the wheel of the tool, in discrete steps
+    fn wheel_delta_discrete(&self) -> i32;
+    /// Whether the wheel moved in this event
+    fn wheel_has_changed(&self) -> bool;
+}
+
+impl TabletToolEvent for UnusedEvent {
+    fn tablet(&self) -> TabletDescriptor {
+        match *self {}
+    }
+    fn tool(&self) -> TabletToolDescriptor {
+        match *self {}
+    }
+    fn delta_x(&self) -> f64 {
+        match *self {}
+    }
+    fn delta_y(&self) -> f64 {
+        match *self {}
+    }
+    fn x(&self) -> f64 {
+        match *self {}
+    }
+    fn y(&self) -> f64 {
+        match *self {}
+    }
+    fn x_transformed(&self, _width: u32) -> f64 {
+        match *self {}
+    }
+    fn y_transformed(&self, _height: u32) -> f64 {
+        match *self {}
+    }
+    fn distance(&self) -> f64 {
+        match *self {}
+    }
+    fn distance_has_changed(&self) -> bool {
+        match *self {}
+    }
+    fn pressure(&self) -> f64 {
+        match *self {}
+    }
+    fn pressure_has_changed(&self) -> bool {
+        match *self {}
+    }
+    fn tilt_x(&self) -> f64 {
+        match *self {}
+    }
+    fn tilt_y(&self) -> f64 {
+        match *self {}
+    }
+    fn tilt_has_changed(&self) -> bool {
+        match *self {}
+    }
+    fn rotation(&self) -> f64 {
+        match *self {}
+    }
+    fn rotation_has_changed(&self) -> bool {
+        match *self {}
+    }
+    fn slider_position(&self) -> f64 {
+        match *self {}
+    }
+    fn slider_has_changed(&self) -> bool {
+        match *self {}
+    }
+    fn wheel_delta(&self) -> f64 {
+        match *self {}
+    }
+    fn wheel_delta_discrete(&self) -> i32 {
+        match *self {}
+    }
+    fn wheel_has_changed(&self) -> bool {
+        match *self {}
+    }
+}
+
+/// Trait for axis events of tablet tools
+pub trait TabletToolAxisEvent: TabletToolEvent {}
+
+impl TabletToolAxisEvent for UnusedEvent {}
+
+/// Trait for tablet tools entering or leaving the proximity of their tablet
+pub trait TabletToolProximityEvent: TabletToolEvent {
+    /// Whether the tool entered or left the proximity of the tablet
+    fn state(&self) -> ProximityState;
+}
+
+impl TabletToolProximityEvent for UnusedEvent {
+    fn state(&self) -> ProximityState {
+        match *self {}
+    }
+}
+
+/// Trait for the tip of tablet tools touching or leaving their tablet
+pub trait TabletToolTipEvent: TabletToolEvent {
+    /// Whether the tip touched or left the tablet
+    fn tip_state(&self) -> TabletToolTipState;
+}
+
+impl TabletToolTipEvent for UnusedEvent {
+    fn tip_state(&self) -> TabletToolTipState {
+        match *self {}
+    }
+}
+
+/// Trait for button events of tablet tools
+pub trait TabletToolButtonEvent: TabletToolEvent {
+    /// The button that changed state, as a linux input event code
+    fn button(&self) -> u32;
+    /// Number of times this button is currently pressed on all the devices of the seat
+    fn seat_button_count(&self) -> u32;
+    /// Whether the button was pressed or released
+    fn button_state(&self) -> MouseButtonState;
+}
+
+impl TabletToolButtonEvent for UnusedEvent {
+    fn button(&self) -> u32 {
+        match *self {}
+    }
+    fn seat_button_count(&self) -> u32 {
+        match *self {}
+    }
+    fn button_state(&self) -> MouseButtonState {
+        match *self {}
+    }
+}
+
+/// Description of the pad of a graphics tablet
+///
+/// All its buttons, rings and strips are considered part of a single mode group.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TabletPadDescriptor {
+    /// Name of the pad
+    pub name: String,
+    /// Path of the device node of the pad, if known
+    pub syspath: Option<PathBuf>,
+    /// Number of buttons of the pad
+    pub buttons: u32,
+    /// Number of rings of the pad
+    pub rings: u32,
+    /// Number of strips of the pad
+    pub strips: u32,
+    /// Number of modes the pad can switch between
+    pub modes: u32,
+}
+
+/// Source of an event of a ring or strip of a tablet pad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabletPadAxisSource {
+    /// The ring or strip was touched by a finger
+    Finger,
+    /// The source is unknown
+    Unknown,
+}
+
+/// Trait for the events of tablet pads
+pub trait TabletPadEvent: Event {
+    /// The pad that generated the event
+    fn pad(&self) -> TabletPadDescriptor;
+    /// The current mode of the pad
+    fn mode(&self) -> u32;
+}
+
+impl TabletPadEvent for UnusedEvent {
+    fn pad(&self) -> TabletPadDescriptor {
+        match *self {}
+    }
+    fn mode(&self) -> u32 {
+        match *self {}
+    }
+}
+
+/// Trait for button events of tablet pads
+pub trait TabletPadButtonEvent: TabletPadEvent {
+    /// Index of the button that changed state
+    fn button_number(&self) -> u32;
+    /// Whether the button was pressed or released
+    fn button_state(&self) -> MouseButtonState;
+}
+
+impl TabletPadButtonEvent for UnusedEvent {
+    fn button_number(&self) -> u32 {
+        match *self {}
+    }
+    fn button_state(&self) -> MouseButtonState {
+        match *self {}
+    }
+}
+
+/// Trait for ring events of tablet pads
+pub trait TabletPadRingEvent: TabletPadEvent {
+    /// Index of the ring
+    fn number(&self) -> u32;
+    /// Position of the ring in degrees clockwise from its northern-most point
+    ///
+    /// `None` once the finger was lifted from the ring.
+    fn position(&self) -> Option<f64>;
+    /// Source of the event
+    fn source(&self) -> TabletPadAxisSource;
+}
+
+impl TabletPadRingEvent for UnusedEvent {
+    fn number(&self) -> u32 {
+        match *self {}
+    }
+    fn position(&self) -> Option<f64> {
+        match *self {}
+    }
+    fn source(&self) -> TabletPadAxisSource {
+        match *self {}
+    }
+}
+
+/// Trait for strip events of tablet pads
+pub trait TabletPadStripEvent: TabletPadEvent {
+    /// Index of the strip
+    fn number(&self) -> u32;
+    /// Position of the finger on the strip, normalized between 0 (top or left) and 1
+    ///
+    /// `None` once the finger was lifted from the strip.
+    fn position(&self) -> Option<f64>;
+    /// Source of the event
+    fn source(&self) -> TabletPadAxisSource;
+}
+
+impl TabletPadStripEvent for UnusedEvent {
+    fn number(&self) -> u32 {
+        match *self {}
+    }
+    fn position(&self) -> Option<f64> {
+        match *self {}
+    }
+    fn source(&self) -> TabletPadAxisSource {
+        match *self {}
+    }
+}
+
+/// Trait for the events starting a touchpad gesture
+pub trait GestureBeginEvent: Event {
+    /// Number of fingers taking part in the gesture
+    fn fingers(&self) -> u32;
+}
+
+impl GestureBeginEvent for UnusedEvent {
+    fn fingers(&self) -> u32 {
+        match *self {}
+    }
+}
+
+/// Trait for the events ending a touchpad gesture
+pub trait GestureEndEvent: Event {
+    /// Whether the gesture was cancelled rather than completed
+    ///
+    /// A gesture is cancelled for example when a finger is added or lifted before its end.
+    fn cancelled(&self) -> bool;
+}
+
+impl GestureEndEvent for UnusedEvent {
+    fn cancelled(&self) -> bool {
+        match *self {}
+    }
+}
+
+/// Trait for the events starting a swipe gesture
+pub trait GestureSwipeBeginEvent: GestureBeginEvent {}
+
+impl GestureSwipeBeginEvent for UnusedEvent {}
+
+/// Trait for the events updating a swipe gesture
+pub trait GestureSwipeUpdateEvent: Event {
+    /// Motion of the logical center of the gesture since the last event
+    fn delta(&self) -> (f64, f64) {
+        (self.delta_x(), self.delta_y())
+    }
+    /// Motion of the logical center of the gesture on the x axis since the last event
+    fn delta_x(&self) -> f64;
+    /// Motion of the logical center of the gesture on the y axis since the last event
+    fn delta_y(&self) -> f64;
+}
+
+impl GestureSwipeUpdateEvent for UnusedEvent {
+    fn delta_x(&self) -> f64 {
+        match *self {}
+    }
+    fn delta_y(&self) -> f64 {
+        match *self {}
+    }
+}
+
+/// Trait for the events ending a swipe gesture
+pub trait GestureSwipeEndEvent: GestureEndEvent {}
+
+impl GestureSwipeEndEvent for UnusedEvent {}
+
+/// Trait for the events starting a pinch gesture
+pub trait GesturePinchBeginEvent: GestureBeginEvent {}
+
+impl GesturePinchBeginEvent for UnusedEvent {}
+
+/// Trait for the events updating a pinch gesture
+pub trait GesturePinchUpdateEvent: Event {
+    /// Motion of the logical center of the gesture since the last event
+    fn delta(&self) -> (f64, f64) {
+        (self.delta_x(), self.delta_y())
+    }
+    /// Motion of the logical center of the gesture on the x axis since the last event
+    fn delta_x(&self) -> f64;
+    /// Motion of the logical center of the gesture on the y axis since the last event
+    fn delta_y(&self) -> f64;
+    /// Distance between the fingers relative to their distance when the gesture began
+    fn scale(&self) -> f64;
+    /// Angle the fingers rotated by since the last event, in degrees clockwise
+    fn rotation(&self) -> f64;
+}
+
+impl GesturePinchUpdateEvent for UnusedEvent {
+    fn delta_x(&self) -> f64 {
+        match *self {}
+    }
+    fn delta_y(&self) -> f64 {
+        match *self {}
+    }
+    fn scale(&self) -> f64 {
+        match *self {}
+    }
+    fn rotation(&self) -> f64 {
+        match *self {}
+    }
+}
+
+/// Trait for the events ending a pinch gesture
+pub trait GesturePinchEndEvent: GestureEndEvent {}
+
+impl GesturePinchEndEvent for UnusedEvent {}
+
+/// Trait for the events starting a hold gesture
+///
+/// A hold gesture is reported when fingers rest on the touchpad without moving, for example
+/// to stop a kinetic scrolling. It has no update events.
+pub trait GestureHoldBeginEvent: GestureBeginEvent {}
+
+impl GestureHoldBeginEvent for UnusedEvent {}
+
+/// Trait for the events ending a hold gesture
+///
+/// A hold gesture is cancelled when the fingers start moving, typically into a swipe or
+/// a pinch gesture.
+pub trait GestureHoldEndEvent: GestureEndEvent {}
+
+impl GestureHoldEndEvent for UnusedEvent {}
+
+/// A physical switch of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Switch {
+    /// The lid of a laptop
+    ///
+    /// It is on when the lid is closed.
+    Lid,
+    /// The tablet mode of a convertible laptop
+    ///
+    /// It is on when the device is used as a tablet, with its keyboard folded or detached
+    /// and the touchscreen as its primary input.
+    TabletMode,
+}
+
+/// State of a [`Switch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchState {
+    /// The switch is off
+    Off,
+    /// The switch is on
+    On,
+}
+
+/// Trait for the events of a switch changing state
+pub trait SwitchToggleEvent: Event {
+    /// The switch that changed state
+    ///
+    /// `None` if the switch is not known to smithay.
+    fn switch(&self) -> Option<Switch>;
+    /// The new state of the switch
+    fn state(&self) -> SwitchState;
+}
+
+impl SwitchToggleEvent for UnusedEvent {
+    fn switch(&self) -> Option<Switch> {
+        match *self {}
+    }
+    fn state(&self) -> SwitchState {
+        match *self {}
+    }
+}
+
+/// Capability of an input device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceCapability {
+    /// The device has keys
+    Keyboard,
+    /// The device moves a pointer
+    Pointer,
+    /// The device is a touchscreen
+    Touch,
+    /// The device is the tool of a graphics tablet
+    TabletTool,
+    /// The device is the pad of a graphics tablet
+    TabletPad,
+    /// The device reports touchpad gestures
+    Gesture,
+    /// The device has switches
+    Switch,
+}
+
+/// Acceleration profile of a pointer device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccelProfile {
+    /// The pointer moves by a constant factor of the motion of the device
+    Flat,
+    /// The factor applied to the motion of the device depends on its speed
+    Adaptive,
+}
+
+/// Method used to generate scroll events from a pointer device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScrollMethod {
+    /// The device does not scroll, besides its physical wheels
+    NoScroll,
+    /// Moving two fingers on a touchpad scrolls
+    TwoFinger,
+    /// Moving a finger along the edges of a touchpad scrolls
+    Edge,
+    /// Moving the device while holding a button scrolls
+    OnButtonDown,
+}
+
+/// Errors when configuring an input device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum DeviceConfigError {
+    /// The device does not support this configuration
+    #[error("The configuration is not supported by the device")]
+    Unsupported,
+    /// The configuration value is invalid for this device
+    #[error("The configuration value is invalid for the device")]
+    Invalid,
+}
+
+/// State of the leds of a keyboard
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct KeyboardLeds {
+    /// The caps lock led
+    pub caps_lock: bool,
+    /// The num lock led
+    pub num_lock: bool,
+    /// The scroll lock led
+    pub scroll_lock: bool,
+}
+
+/// An input device of a backend
+///
+/// The configuration methods have defaults for devices that do not support any configuration:
+/// the getters report the option as unavailable and the setters fail with
+/// [`DeviceConfigError::Unsupported`].
+pub trait Device {
+    /// Name of the device
+    fn name(&self) -> String;
+    /// Check whether the device has a capability
+    fn has_capability(&self, capability: DeviceCapability) -> bool;
+
+    /// Number of fingers supported for tapping, tapping is unavailable if this is 0
+    fn tap_finger_count(&self) -> u32 {
+        0
+    }
+    /// Whether tapping the touchpad generates clicks
+    fn tap_enabled(&self) -> bool {
+        false
+    }
+    /// Enable or disable tapping to click
+    fn set_tap_enabled(&mut self, _enabled: bool) -> Result<(), DeviceConfigError> {
+        Err(DeviceConfigError::Unsupported)
+    }
+
+    /// Whether the device supports natural scrolling
+    fn has_natural_scroll(&self) -> bool {
+        false
+    }
+    /// Whether natural scrolling is enabled
+    ///
+    /// With natural scrolling, the content moves in the direction of the fingers rather than
+    /// the scroll bars.
+    fn natural_scroll_enabled(&self) -> bool {
+        false
+    }
+    /// Enable or disable natural scrolling
+    fn set_natural_scroll_enabled(&mut self, _enabled: bool) -> Result<(), DeviceConfigError> {
+        Err(DeviceConfigError::Unsupported)
+    }
+
+    /// The acceleration profiles supported by the device
+    fn accel_profiles(&self) -> Vec<AccelProfile> {
+        Vec::new()
+    }
+    /// The current acceleration profile of the device, if it supports acceleration
+    fn accel_profile(&self) -> Option<AccelProfile> {
+        None
+    }
+    /// Change the acceleration profile of the device
+    fn set_accel_profile(&mut self, _profile: AccelProfile) -> Result<(), DeviceConfigError> {
+        Err(DeviceConfigError::Unsupported)
+    }
+    /// The current acceleration speed of the device, in the `[-1.0, 1.0]` range
+    fn accel_speed(&self) -> f64 {
+        0.0
+    }
+    /// Change the acceleration speed of the device, in the `[-1.0, 1.0]` range
+    ///
+    /// `0.0` is the default speed of the device.
+    fn set_accel_speed(&mut self, _speed: f64) -> Result<(), DeviceConfigError> {
+        Err(DeviceConfigError::Unsupported)
+    }
+
+    /// The scroll methods supported by the device
+    fn scroll_methods(&self) -> Vec<ScrollMethod> {
+        Vec::new()
+    }
+    /// The current scroll method of the device
+    fn scroll_method(&self) -> ScrollMethod {
+        ScrollMethod::NoScroll
+    }
+    /// Change the scroll method of the device
+    fn set_scroll_method(&mut self, _method: ScrollMethod) -> Result<(), DeviceConfigError> {
+        Err(DeviceConfigError::Unsupported)
+    }
+
+    /// Light the leds of a keyboard device
+    ///
+    /// Input backends do not track the lock keys themselves: update the leds of all the
+    /// keyboards of a seat whenever the state of its keymap changes, and of new devices as
+    /// they are added. Devices without leds ignore this.
+    fn update_leds(&mut self, _leds: KeyboardLeds) {}
+}
+
+/// Interface to configure the devices of an input backend
+pub trait InputConfig {
+    /// Type of the devices of the backend
+    type Device: Device;
+
+    /// Access the list of the currently known devices
+    fn devices(&mut self) -> &mut [Self::Device];
+}
+
 /// Trait that describes objects providing a source of input events. All input backends
 /// need to implement this and provide the same base guarantees about the precision of
 /// given events.
@@ -514,12 +1239,44 @@ pub trait InputBackend: Sized {
     type TouchCancelEvent: TouchCancelEvent;
     /// Type representing touch frame events
     type TouchFrameEvent: TouchFrameEvent;
+    /// Type representing axis events of tablet tools
+    type TabletToolAxisEvent: TabletToolAxisEvent;
+    /// Type representing proximity events of tablet tools
+    type TabletToolProximityEvent: TabletToolProximityEvent;
+    /// Type representing tip events of tablet tools
+    type TabletToolTipEvent: TabletToolTipEvent;
+    /// Type representing button events of tablet tools
+    type TabletToolButtonEvent: TabletToolButtonEvent;
+    /// Type representing button events of tablet pads
+    type TabletPadButtonEvent: TabletPadButtonEvent;
+    /// Type representing ring events of tablet pads
+    type TabletPadRingEvent: TabletPadRingEvent;
+    /// Type representing strip events of tablet pads
+    type TabletPadStripEvent: TabletPadStripEvent;
+    /// Type representing swipe gestures beginning
+    type GestureSwipeBeginEvent: GestureSwipeBeginEvent;
+    /// Type representing swipe gestures updating
+    type GestureSwipeUpdateEvent: GestureSwipeUpdateEvent;
+    /// Type representing swipe gestures ending
+    type GestureSwipeEndEvent: GestureSwipeEndEvent;
+    /// Type representing pinch gestures beginning
+    type GesturePinchBeginEvent: GesturePinchBeginEvent;
+    /// Type representing pinch gestures updating
+    type GesturePinchUpdateEvent: GesturePinchUpdateEvent;
+    /// Type representing pinch gestures ending
+    type GesturePinchEndEvent: GesturePinchEndEvent;
+    /// Type representing hold gestures beginning
+    type GestureHoldBeginEvent: GestureHoldBeginEvent;
+    /// Type representing hold gestures ending
+    type GestureHoldEndEvent: GestureHoldEndEvent;
+    /// Type representing switches changing state
+    type SwitchToggleEvent: SwitchToggleEvent;
 
     /// Special events that are custom to this backend
     type SpecialEvent;
 
-    /// Backend-specific type allowing you to configure it
-    type InputConfig: ?Sized;
+    /// Backend-specific type allowing you to configure its devices
+    type InputConfig: InputConfig;
 
     /// Get the list of currently known Seats
     fn seats(&self) -> Vec<Seat>;
@@ -617,6 +1374,121 @@ pub enum InputEvent<B: InputBackend> {
         /// The touch frame event
         event: B::TouchFrameEvent,
     },
+    /// The axes of a tablet tool changed
+    TabletToolAxis {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet tool axis event
+        event: B::TabletToolAxisEvent,
+    },
+    /// A tablet tool entered or left the proximity of its tablet
+    TabletToolProximity {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet tool proximity event
+        event: B::TabletToolProximityEvent,
+    },
+    /// The tip of a tablet tool touched or left its tablet
+    TabletToolTip {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet tool tip event
+        event: B::TabletToolTipEvent,
+    },
+    /// A button of a tablet tool was pressed or released
+    TabletToolButton {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet tool button event
+        event: B::TabletToolButtonEvent,
+    },
+    /// A button of a tablet pad was pressed or released
+    TabletPadButton {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet pad button event
+        event: B::TabletPadButtonEvent,
+    },
+    /// A ring of a tablet pad was actionned
+    TabletPadRing {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet pad ring event
+        event: B::TabletPadRingEvent,
+    },
+    /// A strip of a tablet pad was actionned
+    TabletPadStrip {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The tablet pad strip event
+        event: B::TabletPadStripEvent,
+    },
+    /// A swipe gesture began
+    GestureSwipeBegin {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The swipe gesture begin event
+        event: B::GestureSwipeBeginEvent,
+    },
+    /// A swipe gesture was updated
+    GestureSwipeUpdate {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The swipe gesture update event
+        event: B::GestureSwipeUpdateEvent,
+    },
+    /// A swipe gesture ended
+    GestureSwipeEnd {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The swipe gesture end event
+        event: B::GestureSwipeEndEvent,
+    },
+    /// A pinch gesture began
+    GesturePinchBegin {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The pinch gesture begin event
+        event: B::GesturePinchBeginEvent,
+    },
+    /// A pinch gesture was updated
+    GesturePinchUpdate {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The pinch gesture update event
+        event: B::GesturePinchUpdateEvent,
+    },
+    /// A pinch gesture ended
+    GesturePinchEnd {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The pinch gesture end event
+        event: B::GesturePinchEndEvent,
+    },
+    /// A hold gesture began
+    GestureHoldBegin {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The hold gesture begin event
+        event: B::GestureHoldBeginEvent,
+    },
+    /// A hold gesture ended
+    GestureHoldEnd {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The hold gesture end event
+        event: B::GestureHoldEndEvent,
+    },
+    /// A switch changed state
+    ///
+    /// This is typically used to blank the internal panel when the lid is closed, or to adapt
+    /// the interface to touch input in tablet mode.
+    SwitchToggle {
+        /// Seat that generated the event
+        seat: Seat,
+        /// The switch toggle event
+        event: B::SwitchToggleEvent,
+    },
     /// Special event specific of this backend
     Special(B::SpecialEvent),
 }
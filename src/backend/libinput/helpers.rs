@@ -1,11 +1,19 @@
 use crate::backend::input::{self as backend, InputEvent};
 use input as libinput;
 use input::event::{
-    device::DeviceEvent, keyboard::KeyboardEvent, pointer::PointerEvent, touch::TouchEvent, EventTrait,
+    device::DeviceEvent,
+    gesture::{GestureEvent, GesturePinchEvent, GestureSwipeEvent},
+    keyboard::KeyboardEvent,
+    pointer::PointerEvent,
+    switch::SwitchEvent,
+    tablet_pad::TabletPadEvent,
+    tablet_tool::TabletToolEvent,
+    touch::TouchEvent,
+    EventTrait,
 };
 use slog::Logger;
 
-use super::{LibinputConfig, LibinputEvent, LibinputInputBackend};
+use super::{LibinputConfig, LibinputEvent, LibinputInputBackend, SeatAssignment};
 use std::{
     collections::hash_map::{DefaultHasher, Entry, HashMap},
     hash::{Hash, Hasher},
@@ -16,6 +24,7 @@ pub fn on_device_event<F>(
     callback: &mut F,
     seats: &mut HashMap<libinput::Seat, backend::Seat>,
     config: &mut LibinputConfig,
+    seat_assignment: &mut Option<SeatAssignment>,
     event: DeviceEvent,
     logger: &Logger,
 ) where
@@ -23,7 +32,28 @@ pub fn on_device_event<F>(
 {
     match event {
         DeviceEvent::Added(device_added_event) => {
-            let added = device_added_event.device();
+            let mut added = device_added_event.device();
+
+            if let Some(name) = seat_assignment.as_mut().and_then(|rule| rule(&added)) {
+                if name != added.seat().logical_name() {
+                    info!(
+                        logger,
+                        "Assigning device {:?} to seat {:?}",
+                        added.sysname(),
+                        name
+                    );
+                    // libinput removes the device and adds it again on its new seat
+                    if added.set_seat_logical_name(&name).is_ok() {
+                        return;
+                    }
+                    warn!(
+                        logger,
+                        "Failed to assign device {:?} to seat {:?}",
+                        added.sysname(),
+                        name
+                    );
+                }
+            }
 
             let new_caps = backend::SeatCapabilities {
                 pointer: added.has_capability(libinput::DeviceCapability::Pointer),
@@ -68,6 +98,11 @@ pub fn on_device_event<F>(
         DeviceEvent::Removed(device_removed_event) => {
             let removed = device_removed_event.device();
 
+            // devices moved to another seat before being reported are ignored
+            if !config.devices.contains(&removed) {
+                return;
+            }
+
             // remove device
             config.devices.retain(|dev| *dev != removed);
 
@@ -275,3 +310,166 @@ pub fn on_pointer_event<F>(
         warn!(logger, "Received pointer event of non existing Seat");
     }
 }
+
+#[inline(always)]
+pub fn on_tablet_tool_event<F>(
+    callback: &mut F,
+    seats: &HashMap<libinput::Seat, backend::Seat>,
+    config: &mut LibinputConfig,
+    event: TabletToolEvent,
+    logger: &Logger,
+) where
+    F: FnMut(InputEvent<LibinputInputBackend>, &mut LibinputConfig),
+{
+    let device_seat = event.device().seat();
+    if let Some(seat) = seats.get(&device_seat).cloned() {
+        match event {
+            TabletToolEvent::Axis(axis_event) => {
+                callback(
+                    InputEvent::TabletToolAxis {
+                        seat,
+                        event: axis_event,
+                    },
+                    config,
+                );
+            }
+            TabletToolEvent::Proximity(proximity_event) => {
+                callback(
+                    InputEvent::TabletToolProximity {
+                        seat,
+                        event: proximity_event,
+                    },
+                    config,
+                );
+            }
+            TabletToolEvent::Tip(tip_event) => {
+                callback(
+                    InputEvent::TabletToolTip {
+                        seat,
+                        event: tip_event,
+                    },
+                    config,
+                );
+            }
+            TabletToolEvent::Button(button_event) => {
+                callback(
+                    InputEvent::TabletToolButton {
+                        seat,
+                        event: button_event,
+                    },
+                    config,
+                );
+            }
+        }
+    } else {
+        warn!(logger, "Received tablet tool event of non existing Seat");
+    }
+}
+
+#[inline(always)]
+pub fn on_tablet_pad_event<F>(
+    callback: &mut F,
+    seats: &HashMap<libinput::Seat, backend::Seat>,
+    config: &mut LibinputConfig,
+    event: TabletPadEvent,
+    logger: &Logger,
+) where
+    F: FnMut(InputEvent<LibinputInputBackend>, &mut LibinputConfig),
+{
+    let device_seat = event.device().seat();
+    if let Some(seat) = seats.get(&device_seat).cloned() {
+        match event {
+            TabletPadEvent::Button(button_event) => {
+                callback(
+                    InputEvent::TabletPadButton {
+                        seat,
+                        event: button_event,
+                    },
+                    config,
+                );
+            }
+            TabletPadEvent::Ring(ring_event) => {
+                callback(
+                    InputEvent::TabletPadRing {
+                        seat,
+                        event: ring_event,
+                    },
+                    config,
+                );
+            }
+            TabletPadEvent::Strip(strip_event) => {
+                callback(
+                    InputEvent::TabletPadStrip {
+                        seat,
+                        event: strip_event,
+                    },
+                    config,
+                );
+            }
+        }
+    } else {
+        warn!(logger, "Received tablet pad event of non existing Seat");
+    }
+}
+
+#[inline(always)]
+pub fn on_gesture_event<F>(
+    callback: &mut F,
+    seats: &HashMap<libinput::Seat, backend::Seat>,
+    config: &mut LibinputConfig,
+    event: GestureEvent,
+    logger: &Logger,
+) where
+    F: FnMut(InputEvent<LibinputInputBackend>, &mut LibinputConfig),
+{
+    let device_seat = event.device().seat();
+    if let Some(seat) = seats.get(&device_seat).cloned() {
+        let event = match event {
+            GestureEvent::Swipe(GestureSwipeEvent::Begin(event)) => {
+                InputEvent::GestureSwipeBegin { seat, event }
+            }
+            GestureEvent::Swipe(GestureSwipeEvent::Update(event)) => {
+                InputEvent::GestureSwipeUpdate { seat, event }
+            }
+            GestureEvent::Swipe(GestureSwipeEvent::End(event)) => InputEvent::GestureSwipeEnd { seat, event },
+            GestureEvent::Pinch(GesturePinchEvent::Begin(event)) => {
+                InputEvent::GesturePinchBegin { seat, event }
+            }
+            GestureEvent::Pinch(GesturePinchEvent::Update(event)) => {
+                InputEvent::GesturePinchUpdate { seat, event }
+            }
+            GestureEvent::Pinch(GesturePinchEvent::End(event)) => InputEvent::GesturePinchEnd { seat, event },
+        };
+        callback(event, config);
+    } else {
+        warn!(logger, "Received gesture event of non existing Seat");
+    }
+}
+
+#[inline(always)]
+pub fn on_switch_event<F>(
+    callback: &mut F,
+    seats: &HashMap<libinput::Seat, backend::Seat>,
+    config: &mut LibinputConfig,
+    event: SwitchEvent,
+    logger: &Logger,
+) where
+    F: FnMut(InputEvent<LibinputInputBackend>, &mut LibinputConfig),
+{
+    let device_seat = event.device().seat();
+    if let Some(seat) = seats.get(&device_seat).cloned() {
+        match event {
+            SwitchEvent::Toggle(toggle_event) => {
+                callback(
+                    InputEvent::SwitchToggle {
+                        seat,
+                        event: toggle_event,
+                    },
+                    config,
+                );
+            }
+        }
+    } else {
+        warn!(logger, "Received switch event of non existing Seat");
+    }
+}
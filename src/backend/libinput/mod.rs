@@ -1,7 +1,10 @@
 //! Implementation of input backend trait for types provided by `libinput`
 
 mod helpers;
-use helpers::{on_device_event, on_keyboard_event, on_pointer_event, on_touch_event};
+use helpers::{
+    on_device_event, on_gesture_event, on_keyboard_event, on_pointer_event, on_switch_event,
+    on_tablet_pad_event, on_tablet_tool_event, on_touch_event,
+};
 
 use crate::backend::input::{self as backend, Axis, InputBackend, InputEvent};
 #[cfg(feature = "backend_session")]
@@ -12,12 +15,11 @@ use crate::{
 use input as libinput;
 use input::event;
 
-#[cfg(feature = "backend_session")]
-use std::path::Path;
 use std::{
     collections::hash_map::HashMap,
     io::Error as IoError,
     os::unix::io::{AsRawFd, RawFd},
+    path::Path,
 };
 
 use calloop::{EventSource, Interest, Mode, Poll, Readiness, Token};
@@ -36,10 +38,13 @@ pub struct LibinputInputBackend {
     context: libinput::Libinput,
     config: LibinputConfig,
     seats: HashMap<libinput::Seat, backend::Seat>,
+    seat_assignment: Option<SeatAssignment>,
     links: Vec<SignalToken>,
     logger: ::slog::Logger,
 }
 
+pub(crate) type SeatAssignment = Box<dyn FnMut(&libinput::Device) -> Option<String>>;
+
 impl LibinputInputBackend {
     /// Initialize a new [`LibinputInputBackend`] from a given already initialized
     /// [libinput context](libinput::Libinput).
@@ -53,10 +58,31 @@ impl LibinputInputBackend {
             context,
             config: LibinputConfig { devices: Vec::new() },
             seats: HashMap::new(),
+            seat_assignment: None,
             links: Vec::new(),
             logger: log,
         }
     }
+
+    /// Set the rule assigning new devices to seats
+    ///
+    /// The rule is called for every new device, and returns the logical name of the seat the
+    /// device should be assigned to, or `None` to leave it on the seat libinput assigned it to
+    /// (`"default"` unless set otherwise by udev). A device moved to another seat is not
+    /// reported until libinput adds it again on the new seat, which is created if it did not
+    /// exist yet.
+    ///
+    /// The rule is called again for the re-added device and must be idempotent: it has to
+    /// return the seat the device is now on (or `None`), otherwise the device keeps being moved.
+    ///
+    /// All the logical seats of a libinput context share the same physical seat, this allows
+    /// splitting its devices between several users.
+    pub fn set_seat_assignment<F>(&mut self, rule: F)
+    where
+        F: FnMut(&libinput::Device) -> Option<String> + 'static,
+    {
+        self.seat_assignment = Some(Box::new(rule));
+    }
 }
 
 #[cfg(feature = "backend_session")]
@@ -158,6 +184,12 @@ impl backend::PointerMotionEvent for event::pointer::PointerMotionEvent {
     fn delta_y(&self) -> f64 {
         self.dy()
     }
+    fn delta_unaccel(&self) -> (f64, f64) {
+        (self.dx_unaccelerated(), self.dy_unaccelerated())
+    }
+    fn time_usec(&self) -> u64 {
+        event::pointer::PointerEventTrait::time_usec(self)
+    }
 }
 
 impl backend::Event for event::pointer::PointerMotionAbsoluteEvent {
@@ -272,6 +304,344 @@ impl backend::Event for event::touch::TouchFrameEvent {
 
 impl backend::TouchFrameEvent for event::touch::TouchFrameEvent {}
 
+fn tablet_descriptor(device: &libinput::Device) -> backend::TabletDescriptor {
+    backend::TabletDescriptor {
+        name: device.name().to_owned(),
+        usb_id: Some((device.id_vendor(), device.id_product())),
+        syspath: Some(Path::new("/dev/input").join(device.sysname())),
+    }
+}
+
+fn tool_descriptor(tool: &event::tablet_tool::TabletTool) -> backend::TabletToolDescriptor {
+    use event::tablet_tool::TabletToolType;
+    backend::TabletToolDescriptor {
+        tool_type: match tool.tool_type() {
+            Some(TabletToolType::Pen) => backend::TabletToolType::Pen,
+            Some(TabletToolType::Eraser) => backend::TabletToolType::Eraser,
+            Some(TabletToolType::Brush) => backend::TabletToolType::Brush,
+            Some(TabletToolType::Pencil) => backend::TabletToolType::Pencil,
+            Some(TabletToolType::Airbrush) => backend::TabletToolType::Airbrush,
+            Some(TabletToolType::Mouse) => backend::TabletToolType::Mouse,
+            Some(TabletToolType::Lens) => backend::TabletToolType::Lens,
+            _ => backend::TabletToolType::Unknown,
+        },
+        hardware_serial: tool.serial(),
+        hardware_id_wacom: tool.tool_id(),
+        capabilities: backend::TabletToolCapabilities {
+            tilt: tool.has_tilt(),
+            pressure: tool.has_pressure(),
+            distance: tool.has_distance(),
+            rotation: tool.has_rotation(),
+            slider: tool.has_slider(),
+            wheel: tool.has_wheel(),
+        },
+    }
+}
+
+fn pad_descriptor(device: &libinput::Device) -> backend::TabletPadDescriptor {
+    let count = |n: i32| n.max(0) as u32;
+    backend::TabletPadDescriptor {
+        name: device.name().to_owned(),
+        syspath: Some(Path::new("/dev/input").join(device.sysname())),
+        buttons: count(device.tablet_pad_number_of_buttons()),
+        rings: count(device.tablet_pad_number_of_rings()),
+        strips: count(device.tablet_pad_number_of_strips()),
+        modes: device
+            .tablet_pad_mode_group(0)
+            .map(|group| group.number_of_modes())
+            .unwrap_or(1),
+    }
+}
+
+// all tablet tool events carry the state of every axis
+macro_rules! impl_tablet_tool_event {
+    ($event:ty) => {
+        impl backend::Event for $event {
+            fn time(&self) -> u32 {
+                event::tablet_tool::TabletToolEventTrait::time(self)
+            }
+        }
+
+        impl backend::TabletToolEvent for $event {
+            fn tablet(&self) -> backend::TabletDescriptor {
+                tablet_descriptor(&event::EventTrait::device(self))
+            }
+            fn tool(&self) -> backend::TabletToolDescriptor {
+                tool_descriptor(&event::tablet_tool::TabletToolEventTrait::tool(self))
+            }
+            fn delta_x(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::dx(self)
+            }
+            fn delta_y(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::dy(self)
+            }
+            fn x(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::x(self)
+            }
+            fn y(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::y(self)
+            }
+            fn x_transformed(&self, width: u32) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::x_transformed(self, width)
+            }
+            fn y_transformed(&self, height: u32) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::y_transformed(self, height)
+            }
+            fn distance(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::distance(self)
+            }
+            fn distance_has_changed(&self) -> bool {
+                event::tablet_tool::TabletToolEventTrait::distance_has_changed(self)
+            }
+            fn pressure(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::pressure(self)
+            }
+            fn pressure_has_changed(&self) -> bool {
+                event::tablet_tool::TabletToolEventTrait::pressure_has_changed(self)
+            }
+            fn tilt_x(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::tilt_x(self)
+            }
+            fn tilt_y(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::tilt_y(self)
+            }
+            fn tilt_has_changed(&self) -> bool {
+                event::tablet_tool::TabletToolEventTrait::tilt_x_has_changed(self)
+                    || event::tablet_tool::TabletToolEventTrait::tilt_y_has_changed(self)
+            }
+            fn rotation(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::rotation(self)
+            }
+            fn rotation_has_changed(&self) -> bool {
+                event::tablet_tool::TabletToolEventTrait::rotation_has_changed(self)
+            }
+            fn slider_position(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::slider_position(self)
+            }
+            fn slider_has_changed(&self) -> bool {
+                event::tablet_tool::TabletToolEventTrait::slider_has_changed(self)
+            }
+            fn wheel_delta(&self) -> f64 {
+                event::tablet_tool::TabletToolEventTrait::wheel_delta(self)
+            }
+            fn wheel_delta_discrete(&self) -> i32 {
+                event::tablet_tool::TabletToolEventTrait::wheel_delta_discrete(self)
+            }
+            fn wheel_has_changed(&self) -> bool {
+                event::tablet_tool::TabletToolEventTrait::wheel_has_changed(self)
+            }
+        }
+    };
+}
+
+impl_tablet_tool_event!(event::tablet_tool::TabletToolAxisEvent);
+impl_tablet_tool_event!(event::tablet_tool::TabletToolProximityEvent);
+impl_tablet_tool_event!(event::tablet_tool::TabletToolTipEvent);
+impl_tablet_tool_event!(event::tablet_tool::TabletToolButtonEvent);
+
+impl backend::TabletToolAxisEvent for event::tablet_tool::TabletToolAxisEvent {}
+
+impl backend::TabletToolProximityEvent for event::tablet_tool::TabletToolProximityEvent {
+    fn state(&self) -> backend::ProximityState {
+        match self.proximity_state() {
+            event::tablet_tool::ProximityState::In => backend::ProximityState::In,
+            event::tablet_tool::ProximityState::Out => backend::ProximityState::Out,
+        }
+    }
+}
+
+impl backend::TabletToolTipEvent for event::tablet_tool::TabletToolTipEvent {
+    fn tip_state(&self) -> backend::TabletToolTipState {
+        match event::tablet_tool::TabletToolTipEvent::tip_state(self) {
+            event::tablet_tool::TipState::Down => backend::TabletToolTipState::Down,
+            event::tablet_tool::TipState::Up => backend::TabletToolTipState::Up,
+        }
+    }
+}
+
+impl backend::TabletToolButtonEvent for event::tablet_tool::TabletToolButtonEvent {
+    fn button(&self) -> u32 {
+        event::tablet_tool::TabletToolButtonEvent::button(self)
+    }
+
+    fn seat_button_count(&self) -> u32 {
+        event::tablet_tool::TabletToolButtonEvent::seat_button_count(self)
+    }
+
+    fn button_state(&self) -> backend::MouseButtonState {
+        event::tablet_tool::TabletToolButtonEvent::button_state(self).into()
+    }
+}
+
+macro_rules! impl_tablet_pad_event {
+    ($event:ty) => {
+        impl backend::Event for $event {
+            fn time(&self) -> u32 {
+                event::tablet_pad::TabletPadEventTrait::time(self)
+            }
+        }
+
+        impl backend::TabletPadEvent for $event {
+            fn pad(&self) -> backend::TabletPadDescriptor {
+                pad_descriptor(&event::EventTrait::device(self))
+            }
+            fn mode(&self) -> u32 {
+                event::tablet_pad::TabletPadEventTrait::mode(self)
+            }
+        }
+    };
+}
+
+impl_tablet_pad_event!(event::tablet_pad::TabletPadButtonEvent);
+impl_tablet_pad_event!(event::tablet_pad::TabletPadRingEvent);
+impl_tablet_pad_event!(event::tablet_pad::TabletPadStripEvent);
+
+impl backend::TabletPadButtonEvent for event::tablet_pad::TabletPadButtonEvent {
+    fn button_number(&self) -> u32 {
+        event::tablet_pad::TabletPadButtonEvent::button_number(self)
+    }
+
+    fn button_state(&self) -> backend::MouseButtonState {
+        event::tablet_pad::TabletPadButtonEvent::button_state(self).into()
+    }
+}
+
+impl backend::TabletPadRingEvent for event::tablet_pad::TabletPadRingEvent {
+    fn number(&self) -> u32 {
+        event::tablet_pad::TabletPadRingEvent::number(self)
+    }
+
+    fn position(&self) -> Option<f64> {
+        // libinput reports -1 once the finger is lifted
+        Some(event::tablet_pad::TabletPadRingEvent::position(self)).filter(|&position| position >= 0.0)
+    }
+
+    fn source(&self) -> backend::TabletPadAxisSource {
+        match event::tablet_pad::TabletPadRingEvent::source(self) {
+            event::tablet_pad::RingAxisSource::Finger => backend::TabletPadAxisSource::Finger,
+            _ => backend::TabletPadAxisSource::Unknown,
+        }
+    }
+}
+
+impl backend::TabletPadStripEvent for event::tablet_pad::TabletPadStripEvent {
+    fn number(&self) -> u32 {
+        event::tablet_pad::TabletPadStripEvent::number(self)
+    }
+
+    fn position(&self) -> Option<f64> {
+        // libinput reports -1 once the finger is lifted
+        Some(event::tablet_pad::TabletPadStripEvent::position(self)).filter(|&position| position >= 0.0)
+    }
+
+    fn source(&self) -> backend::TabletPadAxisSource {
+        match event::tablet_pad::TabletPadStripEvent::source(self) {
+            event::tablet_pad::StripAxisSource::Finger => backend::TabletPadAxisSource::Finger,
+            _ => backend::TabletPadAxisSource::Unknown,
+        }
+    }
+}
+
+macro_rules! impl_gesture_event {
+    ($event:ty) => {
+        impl backend::Event for $event {
+            fn time(&self) -> u32 {
+                event::gesture::GestureEventTrait::time(self)
+            }
+        }
+    };
+}
+
+impl_gesture_event!(event::gesture::GestureSwipeBeginEvent);
+impl_gesture_event!(event::gesture::GestureSwipeUpdateEvent);
+impl_gesture_event!(event::gesture::GestureSwipeEndEvent);
+impl_gesture_event!(event::gesture::GesturePinchBeginEvent);
+impl_gesture_event!(event::gesture::GesturePinchUpdateEvent);
+impl_gesture_event!(event::gesture::GesturePinchEndEvent);
+
+impl backend::GestureBeginEvent for event::gesture::GestureSwipeBeginEvent {
+    fn fingers(&self) -> u32 {
+        event::gesture::GestureEventTrait::finger_count(self) as u32
+    }
+}
+
+impl backend::GestureSwipeBeginEvent for event::gesture::GestureSwipeBeginEvent {}
+
+impl backend::GestureSwipeUpdateEvent for event::gesture::GestureSwipeUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        event::gesture::GestureEventCoordinates::dx(self)
+    }
+
+    fn delta_y(&self) -> f64 {
+        event::gesture::GestureEventCoordinates::dy(self)
+    }
+}
+
+impl backend::GestureEndEvent for event::gesture::GestureSwipeEndEvent {
+    fn cancelled(&self) -> bool {
+        event::gesture::GestureEndEvent::cancelled(self)
+    }
+}
+
+impl backend::GestureSwipeEndEvent for event::gesture::GestureSwipeEndEvent {}
+
+impl backend::GestureBeginEvent for event::gesture::GesturePinchBeginEvent {
+    fn fingers(&self) -> u32 {
+        event::gesture::GestureEventTrait::finger_count(self) as u32
+    }
+}
+
+impl backend::GesturePinchBeginEvent for event::gesture::GesturePinchBeginEvent {}
+
+impl backend::GesturePinchUpdateEvent for event::gesture::GesturePinchUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        event::gesture::GestureEventCoordinates::dx(self)
+    }
+
+    fn delta_y(&self) -> f64 {
+        event::gesture::GestureEventCoordinates::dy(self)
+    }
+
+    fn scale(&self) -> f64 {
+        event::gesture::GesturePinchEventTrait::scale(self)
+    }
+
+    fn rotation(&self) -> f64 {
+        self.angle_delta()
+    }
+}
+
+impl backend::GestureEndEvent for event::gesture::GesturePinchEndEvent {
+    fn cancelled(&self) -> bool {
+        event::gesture::GestureEndEvent::cancelled(self)
+    }
+}
+
+impl backend::GesturePinchEndEvent for event::gesture::GesturePinchEndEvent {}
+
+impl backend::Event for event::switch::SwitchToggleEvent {
+    fn time(&self) -> u32 {
+        event::switch::SwitchEventTrait::time(self)
+    }
+}
+
+impl backend::SwitchToggleEvent for event::switch::SwitchToggleEvent {
+    fn switch(&self) -> Option<backend::Switch> {
+        match event::switch::SwitchToggleEvent::switch(self) {
+            Some(event::switch::Switch::Lid) => Some(backend::Switch::Lid),
+            Some(event::switch::Switch::TabletMode) => Some(backend::Switch::TabletMode),
+            _ => None,
+        }
+    }
+
+    fn state(&self) -> backend::SwitchState {
+        match self.switch_state() {
+            event::switch::SwitchState::Off => backend::SwitchState::Off,
+            event::switch::SwitchState::On => backend::SwitchState::On,
+        }
+    }
+}
+
 /// Special events generated by Libinput
 pub enum LibinputEvent {
     /// A new device was plugged in
@@ -288,13 +658,88 @@ pub struct LibinputConfig {
     devices: Vec<libinput::Device>,
 }
 
-impl LibinputConfig {
-    /// Access the list of current devices
-    pub fn devices(&mut self) -> &mut [libinput::Device] {
+impl backend::InputConfig for LibinputConfig {
+    type Device = libinput::Device;
+
+    fn devices(&mut self) -> &mut [libinput::Device] {
         &mut self.devices
     }
 }
 
+impl backend::Device for libinput::Device {
+    fn name(&self) -> String {
+        libinput::Device::name(self).into()
+    }
+
+    fn has_capability(&self, capability: backend::DeviceCapability) -> bool {
+        libinput::Device::has_capability(self, capability.into())
+    }
+
+    fn tap_finger_count(&self) -> u32 {
+        self.config_tap_finger_count()
+    }
+
+    fn tap_enabled(&self) -> bool {
+        self.config_tap_enabled()
+    }
+
+    fn set_tap_enabled(&mut self, enabled: bool) -> Result<(), backend::DeviceConfigError> {
+        self.config_tap_set_enabled(enabled).map_err(Into::into)
+    }
+
+    fn has_natural_scroll(&self) -> bool {
+        self.config_scroll_has_natural_scroll()
+    }
+
+    fn natural_scroll_enabled(&self) -> bool {
+        self.config_scroll_natural_scroll_enabled()
+    }
+
+    fn set_natural_scroll_enabled(&mut self, enabled: bool) -> Result<(), backend::DeviceConfigError> {
+        self.config_scroll_set_natural_scroll_enabled(enabled)
+            .map_err(Into::into)
+    }
+
+    fn accel_profiles(&self) -> Vec<backend::AccelProfile> {
+        self.config_accel_profiles().into_iter().map(Into::into).collect()
+    }
+
+    fn accel_profile(&self) -> Option<backend::AccelProfile> {
+        self.config_accel_profile().map(Into::into)
+    }
+
+    fn set_accel_profile(
+        &mut self,
+        profile: backend::AccelProfile,
+    ) -> Result<(), backend::DeviceConfigError> {
+        self.config_accel_set_profile(profile.into()).map_err(Into::into)
+    }
+
+    fn accel_speed(&self) -> f64 {
+        self.config_accel_speed()
+    }
+
+    fn set_accel_speed(&mut self, speed: f64) -> Result<(), backend::DeviceConfigError> {
+        self.config_accel_set_speed(speed).map_err(Into::into)
+    }
+
+    fn scroll_methods(&self) -> Vec<backend::ScrollMethod> {
+        self.config_scroll_methods().into_iter().map(Into::into).collect()
+    }
+
+    fn scroll_method(&self) -> backend::ScrollMethod {
+        self.config_scroll_method().into()
+    }
+
+    fn set_scroll_method(&mut self, method: backend::ScrollMethod) -> Result<(), backend::DeviceConfigError> {
+        self.config_scroll_set_method(method.into()).map_err(Into::into)
+    }
+
+    fn update_leds(&mut self, leds: backend::KeyboardLeds) {
+        self.led_update(leds.into())
+    }
+}
+
 impl InputBackend for LibinputInputBackend {
     type EventError = IoError;
 
@@ -308,6 +753,23 @@ impl InputBackend for LibinputInputBackend {
     type TouchMotionEvent = event::touch::TouchMotionEvent;
     type TouchCancelEvent = event::touch::TouchCancelEvent;
     type TouchFrameEvent = event::touch::TouchFrameEvent;
+    type TabletToolAxisEvent = event::tablet_tool::TabletToolAxisEvent;
+    type TabletToolProximityEvent = event::tablet_tool::TabletToolProximityEvent;
+    type TabletToolTipEvent = event::tablet_tool::TabletToolTipEvent;
+    type TabletToolButtonEvent = event::tablet_tool::TabletToolButtonEvent;
+    type TabletPadButtonEvent = event::tablet_pad::TabletPadButtonEvent;
+    type TabletPadRingEvent = event::tablet_pad::TabletPadRingEvent;
+    type TabletPadStripEvent = event::tablet_pad::TabletPadStripEvent;
+    type GestureSwipeBeginEvent = event::gesture::GestureSwipeBeginEvent;
+    type GestureSwipeUpdateEvent = event::gesture::GestureSwipeUpdateEvent;
+    type GestureSwipeEndEvent = event::gesture::GestureSwipeEndEvent;
+    type GesturePinchBeginEvent = event::gesture::GesturePinchBeginEvent;
+    type GesturePinchUpdateEvent = event::gesture::GesturePinchUpdateEvent;
+    type GesturePinchEndEvent = event::gesture::GesturePinchEndEvent;
+    // hold gestures require libinput 1.19, which our bindings do not expose
+    type GestureHoldBeginEvent = backend::UnusedEvent;
+    type GestureHoldEndEvent = backend::UnusedEvent;
+    type SwitchToggleEvent = event::switch::SwitchToggleEvent;
 
     type SpecialEvent = LibinputEvent;
     type InputConfig = LibinputConfig;
@@ -333,6 +795,7 @@ impl InputBackend for LibinputInputBackend {
                         &mut callback,
                         &mut self.seats,
                         &mut self.config,
+                        &mut self.seat_assignment,
                         device_event,
                         &self.logger,
                     );
@@ -364,6 +827,42 @@ impl InputBackend for LibinputInputBackend {
                         &self.logger,
                     );
                 }
+                libinput::Event::Tablet(tablet_tool_event) => {
+                    on_tablet_tool_event(
+                        &mut callback,
+                        &self.seats,
+                        &mut self.config,
+                        tablet_tool_event,
+                        &self.logger,
+                    );
+                }
+                libinput::Event::TabletPad(tablet_pad_event) => {
+                    on_tablet_pad_event(
+                        &mut callback,
+                        &self.seats,
+                        &mut self.config,
+                        tablet_pad_event,
+                        &self.logger,
+                    );
+                }
+                libinput::Event::Gesture(gesture_event) => {
+                    on_gesture_event(
+                        &mut callback,
+                        &self.seats,
+                        &mut self.config,
+                        gesture_event,
+                        &self.logger,
+                    );
+                }
+                libinput::Event::Switch(switch_event) => {
+                    on_switch_event(
+                        &mut callback,
+                        &self.seats,
+                        &mut self.config,
+                        switch_event,
+                        &self.logger,
+                    );
+                }
                 _ => {} //FIXME: What to do with the rest.
             }
         }
@@ -371,6 +870,79 @@ impl InputBackend for LibinputInputBackend {
     }
 }
 
+impl From<backend::DeviceCapability> for libinput::DeviceCapability {
+    fn from(capability: backend::DeviceCapability) -> Self {
+        match capability {
+            backend::DeviceCapability::Keyboard => libinput::DeviceCapability::Keyboard,
+            backend::DeviceCapability::Pointer => libinput::DeviceCapability::Pointer,
+            backend::DeviceCapability::Touch => libinput::DeviceCapability::Touch,
+            backend::DeviceCapability::TabletTool => libinput::DeviceCapability::TabletTool,
+            backend::DeviceCapability::TabletPad => libinput::DeviceCapability::TabletPad,
+            backend::DeviceCapability::Gesture => libinput::DeviceCapability::Gesture,
+            backend::DeviceCapability::Switch => libinput::DeviceCapability::Switch,
+        }
+    }
+}
+
+impl From<libinput::AccelProfile> for backend::AccelProfile {
+    fn from(libinput: libinput::AccelProfile) -> Self {
+        match libinput {
+            libinput::AccelProfile::Flat => backend::AccelProfile::Flat,
+            libinput::AccelProfile::Adaptive => backend::AccelProfile::Adaptive,
+        }
+    }
+}
+
+impl From<backend::AccelProfile> for libinput::AccelProfile {
+    fn from(profile: backend::AccelProfile) -> Self {
+        match profile {
+            backend::AccelProfile::Flat => libinput::AccelProfile::Flat,
+            backend::AccelProfile::Adaptive => libinput::AccelProfile::Adaptive,
+        }
+    }
+}
+
+impl From<libinput::ScrollMethod> for backend::ScrollMethod {
+    fn from(libinput: libinput::ScrollMethod) -> Self {
+        match libinput {
+            libinput::ScrollMethod::NoScroll => backend::ScrollMethod::NoScroll,
+            libinput::ScrollMethod::TwoFinger => backend::ScrollMethod::TwoFinger,
+            libinput::ScrollMethod::Edge => backend::ScrollMethod::Edge,
+            libinput::ScrollMethod::OnButtonDown => backend::ScrollMethod::OnButtonDown,
+        }
+    }
+}
+
+impl From<backend::ScrollMethod> for libinput::ScrollMethod {
+    fn from(method: backend::ScrollMethod) -> Self {
+        match method {
+            backend::ScrollMethod::NoScroll => libinput::ScrollMethod::NoScroll,
+            backend::ScrollMethod::TwoFinger => libinput::ScrollMethod::TwoFinger,
+            backend::ScrollMethod::Edge => libinput::ScrollMethod::Edge,
+            backend::ScrollMethod::OnButtonDown => libinput::ScrollMethod::OnButtonDown,
+        }
+    }
+}
+
+impl From<backend::KeyboardLeds> for libinput::Led {
+    fn from(leds: backend::KeyboardLeds) -> Self {
+        let mut led = libinput::Led::empty();
+        led.set(libinput::Led::CAPSLOCK, leds.caps_lock);
+        led.set(libinput::Led::NUMLOCK, leds.num_lock);
+        led.set(libinput::Led::SCROLLLOCK, leds.scroll_lock);
+        led
+    }
+}
+
+impl From<libinput::DeviceConfigError> for backend::DeviceConfigError {
+    fn from(libinput: libinput::DeviceConfigError) -> Self {
+        match libinput {
+            libinput::DeviceConfigError::Unsupported => backend::DeviceConfigError::Unsupported,
+            libinput::DeviceConfigError::Invalid => backend::DeviceConfigError::Invalid,
+        }
+    }
+}
+
 impl From<event::keyboard::KeyState> for backend::KeyState {
     fn from(libinput: event::keyboard::KeyState) -> Self {
         match libinput {
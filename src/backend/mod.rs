@@ -13,6 +13,7 @@
 //!
 //! - winit
 //! - libinput
+//! - virtual_input, for tests
 
 pub mod graphics;
 pub mod input;
@@ -27,5 +28,6 @@ pub mod libinput;
 pub mod session;
 #[cfg(feature = "backend_udev")]
 pub mod udev;
+pub mod virtual_input;
 #[cfg(feature = "backend_winit")]
 pub mod winit;
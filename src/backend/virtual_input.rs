@@ -0,0 +1,1609 @@
+//! Implementation of an input backend whose events are pushed programmatically
+//!
+//! This backend does not read any device: the events are pushed by the compositor itself with
+//! [`VirtualInputBackend::push`], or loaded from a script. It is meant for integration tests,
+//! which can simulate complex input sequences deterministically, and drive the compositor
+//! through the same [`InputBackend`] interface as real backends.
+//!
+//! The backend has a single seat named `"virtual"`, with keyboard, pointer and touch
+//! capabilities, and its device also reports touchpad gestures and switches. Events are timestamped with a virtual clock, starting at 0 and only advanced
+//! by [`VirtualEvent::Wait`]. Absolute positions (of the pointer and touch points) are given
+//! in normalized device coordinates, `(0.0, 0.0)` being the top-left and `(1.0, 1.0)` the
+//! bottom-right corner of the target coordinate space.
+//!
+//! ## Scripts
+//!
+//! Scripts list one event per line, empty lines and lines starting with `#` are ignored:
+//!
+//! ```text
+//! # type an uppercase A
+//! key 42 pressed
+//! key 30 pressed
+//! wait 20
+//! key 30 released
+//! key 42 released
+//! # click in the middle of the screen
+//! motion_absolute 0.5 0.5
+//! button left pressed
+//! button left released
+//! motion 10 -5
+//! # scrolling down with a touchpad, then with a wheel
+//! axis finger 0 15
+//! axis wheel 0 15 discrete 0 1
+//! # a touch point moving across the screen
+//! touch_down 0 0.1 0.1
+//! touch_frame
+//! touch_motion 0 0.2 0.1
+//! touch_frame
+//! touch_up 0
+//! touch_frame
+//! # a three fingers swipe to the left, and a cancelled pinch
+//! swipe_begin 3
+//! swipe_update -20 0
+//! swipe_end
+//! pinch_begin 2
+//! pinch_update 0 0 1.5 10
+//! pinch_end cancelled
+//! # closing the lid
+//! switch lid on
+//! ```
+//!
+//! Keys are given as linux input event codes, buttons either as `left`, `middle`, `right` or
+//! as a number, and `wait` advances the virtual clock by the given number of milliseconds.
+//! Scrolling with `axis` takes its source (`continuous`, `finger`, `wheel` or `wheel_tilt`),
+//! then the horizontal and vertical amounts in pixels. Wheels also give their amounts in steps
+//! after `discrete`, and may omit the amounts in pixels: `axis wheel discrete 0 -1`.
+//!
+//! Gestures begin with their number of fingers, and swipes and pinches are updated with the
+//! motion of their center, pinches also with their scale and rotation in degrees. Their end
+//! may be followed by `cancelled`. Switches are either the `lid` or the `tablet_mode`, turned
+//! `on` or `off`.
+//!
+//! ## Recording and replay
+//!
+//! To reproduce input-related bugs, the events of any input backend can be recorded in this
+//! format with an [`InputRecorder`], and replayed by this backend. By default all pushed events
+//! are delivered at once, regardless of their timestamps. With
+//! [`set_playback_speed`](VirtualInputBackend::set_playback_speed), the waits between the
+//! events are honored instead, in real time or accelerated.
+//!
+//! Only the events this backend can emulate can be recorded: keyboard, pointer, touch, gesture
+//! and switch events. Tablets are not emulated, as their tools and axes cannot be described
+//! by a line of script. The recorder returns an error for their events and the
+//! backend-specific ones, so that incomplete recordings do not go unnoticed.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    convert::Infallible,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::backend::input::{
+    Axis, AxisSource, Device, DeviceCapability, Event as BackendEvent, GestureBeginEvent, GestureEndEvent,
+    GestureHoldBeginEvent, GestureHoldEndEvent, GesturePinchBeginEvent, GesturePinchEndEvent,
+    GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent,
+    InputBackend, InputConfig, InputEvent, KeyState, KeyboardKeyEvent, MouseButton, MouseButtonState,
+    PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent, Seat,
+    SeatCapabilities, Switch, SwitchState, SwitchToggleEvent, TouchCancelEvent, TouchDownEvent,
+    TouchFrameEvent, TouchMotionEvent, TouchSlot, TouchUpEvent, UnusedEvent,
+};
+
+/// An event to push to the [`VirtualInputBackend`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VirtualEvent {
+    /// Advance the virtual clock by this number of milliseconds
+    Wait(u32),
+    /// A key was pressed or released
+    Key {
+        /// Code of the key, see `linux/input-event-codes.h`
+        key_code: u32,
+        /// Whether the key was pressed or released
+        state: KeyState,
+    },
+    /// The pointer moved relatively to its previous position
+    PointerMotion {
+        /// Motion of the pointer
+        delta: (f64, f64),
+    },
+    /// The pointer moved to an absolute position
+    PointerMotionAbsolute {
+        /// New position of the pointer, in normalized device coordinates
+        position: (f64, f64),
+    },
+    /// A button was pressed or released
+    PointerButton {
+        /// The button
+        button: MouseButton,
+        /// Whether the button was pressed or released
+        state: MouseButtonState,
+    },
+    /// The pointer scrolled
+    PointerAxis {
+        /// Source of the scroll
+        source: AxisSource,
+        /// Amount of scrolling in pixels, along the horizontal and vertical axes
+        ///
+        /// It must be set for continuous sources, and may be `None` for wheels that only
+        /// report steps.
+        amount: Option<(f64, f64)>,
+        /// Amount of scrolling in steps, along the horizontal and vertical axes
+        ///
+        /// It must be set for wheels, and `None` for continuous sources.
+        amount_discrete: Option<(f64, f64)>,
+    },
+    /// A touch point went down
+    TouchDown {
+        /// Identifier of the touch point
+        slot: u64,
+        /// Position of the touch point, in normalized device coordinates
+        position: (f64, f64),
+    },
+    /// A touch point moved
+    TouchMotion {
+        /// Identifier of the touch point
+        slot: u64,
+        /// New position of the touch point, in normalized device coordinates
+        position: (f64, f64),
+    },
+    /// A touch point was lifted
+    TouchUp {
+        /// Identifier of the touch point
+        slot: u64,
+    },
+    /// A touch point was cancelled
+    TouchCancel {
+        /// Identifier of the touch point
+        slot: u64,
+    },
+    /// The end of a set of touch events
+    TouchFrame,
+    /// A swipe gesture began
+    GestureSwipeBegin {
+        /// Number of fingers taking part in the gesture
+        fingers: u32,
+    },
+    /// A swipe gesture was updated
+    GestureSwipeUpdate {
+        /// Motion of the center of the gesture
+        delta: (f64, f64),
+    },
+    /// A swipe gesture ended
+    GestureSwipeEnd {
+        /// Whether the gesture was cancelled rather than completed
+        cancelled: bool,
+    },
+    /// A pinch gesture began
+    GesturePinchBegin {
+        /// Number of fingers taking part in the gesture
+        fingers: u32,
+    },
+    /// A pinch gesture was updated
+    GesturePinchUpdate {
+        /// Motion of the center of the gesture
+        delta: (f64, f64),
+        /// Distance between the fingers relative to their distance when the gesture began
+        scale: f64,
+        /// Angle the fingers rotated by, in degrees clockwise
+        rotation: f64,
+    },
+    /// A pinch gesture ended
+    GesturePinchEnd {
+        /// Whether the gesture was cancelled rather than completed
+        cancelled: bool,
+    },
+    /// A hold gesture began
+    GestureHoldBegin {
+        /// Number of fingers taking part in the gesture
+        fingers: u32,
+    },
+    /// A hold gesture ended
+    GestureHoldEnd {
+        /// Whether the gesture was cancelled rather than completed
+        cancelled: bool,
+    },
+    /// A switch changed state
+    SwitchToggle {
+        /// The switch
+        switch: Switch,
+        /// The new state of the switch
+        state: SwitchState,
+    },
+}
+
+/// Errors when loading a script of events
+#[derive(Debug, thiserror::Error)]
+pub enum ScriptError {
+    /// The script could not be read
+    #[error("The script could not be read")]
+    Io(#[from] io::Error),
+    /// A line of the script is invalid
+    #[error("Invalid event at line {line}: {message}")]
+    Parse {
+        /// Number of the line, starting at 1
+        line: usize,
+        /// Description of the error
+        message: String,
+    },
+}
+
+fn parse_event(line: &str) -> Result<VirtualEvent, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    let number = |index: usize| -> Result<f64, String> {
+        let word = words
+            .get(index)
+            .ok_or_else(|| format!("missing argument {} of {:?}", index, words[0]))?;
+        word.parse().map_err(|_| format!("invalid number {:?}", word))
+    };
+    let integer = |index: usize| -> Result<u64, String> {
+        let word = words
+            .get(index)
+            .ok_or_else(|| format!("missing argument {} of {:?}", index, words[0]))?;
+        word.parse().map_err(|_| format!("invalid integer {:?}", word))
+    };
+    let pressed = |index: usize| -> Result<bool, String> {
+        match words.get(index) {
+            Some(&"pressed") => Ok(true),
+            Some(&"released") => Ok(false),
+            Some(word) => Err(format!("invalid state {:?}", word)),
+            None => Err(format!("missing state of {:?}", words[0])),
+        }
+    };
+    let source = |index: usize| -> Result<AxisSource, String> {
+        match words.get(index) {
+            Some(&"continuous") => Ok(AxisSource::Continuous),
+            Some(&"finger") => Ok(AxisSource::Finger),
+            Some(&"wheel") => Ok(AxisSource::Wheel),
+            Some(&"wheel_tilt") => Ok(AxisSource::WheelTilt),
+            Some(word) => Err(format!("invalid axis source {:?}", word)),
+            None => Err(format!("missing axis source of {:?}", words[0])),
+        }
+    };
+    let cancelled = |index: usize| -> Result<bool, String> {
+        match words.get(index) {
+            Some(&"cancelled") => Ok(true),
+            Some(word) => Err(format!("unexpected argument {:?}", word)),
+            None => Ok(false),
+        }
+    };
+
+    let event = match words[0] {
+        "wait" => VirtualEvent::Wait(integer(1)? as u32),
+        "key" => VirtualEvent::Key {
+            key_code: integer(1)? as u32,
+            state: if pressed(2)? {
+                KeyState::Pressed
+            } else {
+                KeyState::Released
+            },
+        },
+        "motion" => VirtualEvent::PointerMotion {
+            delta: (number(1)?, number(2)?),
+        },
+        "motion_absolute" => VirtualEvent::PointerMotionAbsolute {
+            position: (number(1)?, number(2)?),
+        },
+        "button" => VirtualEvent::PointerButton {
+            button: match words.get(1) {
+                Some(&"left") => MouseButton::Left,
+                Some(&"middle") => MouseButton::Middle,
+                Some(&"right") => MouseButton::Right,
+                _ => MouseButton::Other(integer(1)? as u8),
+            },
+            state: if pressed(2)? {
+                MouseButtonState::Pressed
+            } else {
+                MouseButtonState::Released
+            },
+        },
+        "axis" => {
+            let source = source(1)?;
+            let mut index = 2;
+            let mut amount = None;
+            if words.len() > index && words[index] != "discrete" {
+                amount = Some((number(index)?, number(index + 1)?));
+                index += 2;
+            }
+            let mut amount_discrete = None;
+            if words.get(index) == Some(&"discrete") {
+                amount_discrete = Some((number(index + 1)?, number(index + 2)?));
+            }
+            match source {
+                AxisSource::Continuous | AxisSource::Finger if amount.is_none() => {
+                    return Err("missing amount of a continuous scroll".into())
+                }
+                AxisSource::Continuous | AxisSource::Finger if amount_discrete.is_some() => {
+                    return Err("discrete amount of a continuous scroll".into())
+                }
+                AxisSource::Wheel | AxisSource::WheelTilt if amount_discrete.is_none() => {
+                    return Err("missing discrete amount of a wheel scroll".into())
+                }
+                _ => {}
+            }
+            VirtualEvent::PointerAxis {
+                source,
+                amount,
+                amount_discrete,
+            }
+        }
+        "touch_down" => VirtualEvent::TouchDown {
+            slot: integer(1)?,
+            position: (number(2)?, number(3)?),
+        },
+        "touch_motion" => VirtualEvent::TouchMotion {
+            slot: integer(1)?,
+            position: (number(2)?, number(3)?),
+        },
+        "touch_up" => VirtualEvent::TouchUp { slot: integer(1)? },
+        "touch_cancel" => VirtualEvent::TouchCancel { slot: integer(1)? },
+        "touch_frame" => VirtualEvent::TouchFrame,
+        "swipe_begin" => VirtualEvent::GestureSwipeBegin {
+            fingers: integer(1)? as u32,
+        },
+        "swipe_update" => VirtualEvent::GestureSwipeUpdate {
+            delta: (number(1)?, number(2)?),
+        },
+        "swipe_end" => VirtualEvent::GestureSwipeEnd {
+            cancelled: cancelled(1)?,
+        },
+        "pinch_begin" => VirtualEvent::GesturePinchBegin {
+            fingers: integer(1)? as u32,
+        },
+        "pinch_update" => VirtualEvent::GesturePinchUpdate {
+            delta: (number(1)?, number(2)?),
+            scale: number(3)?,
+            rotation: number(4)?,
+        },
+        "pinch_end" => VirtualEvent::GesturePinchEnd {
+            cancelled: cancelled(1)?,
+        },
+        "hold_begin" => VirtualEvent::GestureHoldBegin {
+            fingers: integer(1)? as u32,
+        },
+        "hold_end" => VirtualEvent::GestureHoldEnd {
+            cancelled: cancelled(1)?,
+        },
+        "switch" => VirtualEvent::SwitchToggle {
+            switch: match words.get(1) {
+                Some(&"lid") => Switch::Lid,
+                Some(&"tablet_mode") => Switch::TabletMode,
+                Some(word) => return Err(format!("invalid switch {:?}", word)),
+                None => return Err("missing switch".into()),
+            },
+            state: match words.get(2) {
+                Some(&"on") => SwitchState::On,
+                Some(&"off") => SwitchState::Off,
+                Some(word) => return Err(format!("invalid state {:?}", word)),
+                None => return Err("missing state of \"switch\"".into()),
+            },
+        },
+        word => return Err(format!("unknown event {:?}", word)),
+    };
+    Ok(event)
+}
+
+impl fmt::Display for VirtualEvent {
+    /// Formats the event as a line of script
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pressed = |pressed: bool| if pressed { "pressed" } else { "released" };
+        let source = |source: AxisSource| match source {
+            AxisSource::Continuous => "continuous",
+            AxisSource::Finger => "finger",
+            AxisSource::Wheel => "wheel",
+            AxisSource::WheelTilt => "wheel_tilt",
+        };
+        let cancelled = |cancelled: bool| if cancelled { " cancelled" } else { "" };
+        match *self {
+            VirtualEvent::Wait(duration) => write!(f, "wait {}", duration),
+            VirtualEvent::Key { key_code, state } => {
+                write!(f, "key {} {}", key_code, pressed(state == KeyState::Pressed))
+            }
+            VirtualEvent::PointerMotion { delta } => write!(f, "motion {} {}", delta.0, delta.1),
+            VirtualEvent::PointerMotionAbsolute { position } => {
+                write!(f, "motion_absolute {} {}", position.0, position.1)
+            }
+            VirtualEvent::PointerButton { button, state } => {
+                match button {
+                    MouseButton::Left => write!(f, "button left")?,
+                    MouseButton::Middle => write!(f, "button middle")?,
+                    MouseButton::Right => write!(f, "button right")?,
+                    MouseButton::Other(button) => write!(f, "button {}", button)?,
+                }
+                write!(f, " {}", pressed(state == MouseButtonState::Pressed))
+            }
+            VirtualEvent::PointerAxis {
+                source: s,
+                amount,
+                amount_discrete,
+            } => {
+                write!(f, "axis {}", source(s))?;
+                if let Some((horizontal, vertical)) = amount {
+                    write!(f, " {} {}", horizontal, vertical)?;
+                }
+                if let Some((horizontal, vertical)) = amount_discrete {
+                    write!(f, " discrete {} {}", horizontal, vertical)?;
+                }
+                Ok(())
+            }
+            VirtualEvent::TouchDown { slot, position } => {
+                write!(f, "touch_down {} {} {}", slot, position.0, position.1)
+            }
+            VirtualEvent::TouchMotion { slot, position } => {
+                write!(f, "touch_motion {} {} {}", slot, position.0, position.1)
+            }
+            VirtualEvent::TouchUp { slot } => write!(f, "touch_up {}", slot),
+            VirtualEvent::TouchCancel { slot } => write!(f, "touch_cancel {}", slot),
+            VirtualEvent::TouchFrame => write!(f, "touch_frame"),
+            VirtualEvent::GestureSwipeBegin { fingers } => write!(f, "swipe_begin {}", fingers),
+            VirtualEvent::GestureSwipeUpdate { delta } => write!(f, "swipe_update {} {}", delta.0, delta.1),
+            VirtualEvent::GestureSwipeEnd { cancelled: c } => write!(f, "swipe_end{}", cancelled(c)),
+            VirtualEvent::GesturePinchBegin { fingers } => write!(f, "pinch_begin {}", fingers),
+            VirtualEvent::GesturePinchUpdate {
+                delta,
+                scale,
+                rotation,
+            } => write!(f, "pinch_update {} {} {} {}", delta.0, delta.1, scale, rotation),
+            VirtualEvent::GesturePinchEnd { cancelled: c } => write!(f, "pinch_end{}", cancelled(c)),
+            VirtualEvent::GestureHoldBegin { fingers } => write!(f, "hold_begin {}", fingers),
+            VirtualEvent::GestureHoldEnd { cancelled: c } => write!(f, "hold_end{}", cancelled(c)),
+            VirtualEvent::SwitchToggle { switch, state } => {
+                let switch = match switch {
+                    Switch::Lid => "lid",
+                    Switch::TabletMode => "tablet_mode",
+                };
+                let state = match state {
+                    SwitchState::On => "on",
+                    SwitchState::Off => "off",
+                };
+                write!(f, "switch {} {}", switch, state)
+            }
+        }
+    }
+}
+
+/// Parse a script of events
+///
+/// See the [module-level documentation](self) for its format.
+pub fn parse_script(script: &str) -> Result<Vec<VirtualEvent>, ScriptError> {
+    script
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, text)| parse_event(text).map_err(|message| ScriptError::Parse { line, message }))
+        .collect()
+}
+
+/// Keyboard event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualKeyboardKeyEvent {
+    time: u32,
+    key_code: u32,
+    state: KeyState,
+    count: u32,
+}
+
+impl BackendEvent for VirtualKeyboardKeyEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl KeyboardKeyEvent for VirtualKeyboardKeyEvent {
+    fn key_code(&self) -> u32 {
+        self.key_code
+    }
+
+    fn state(&self) -> KeyState {
+        self.state
+    }
+
+    fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Relative pointer motion event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualPointerMotionEvent {
+    time: u32,
+    delta: (f64, f64),
+}
+
+impl BackendEvent for VirtualPointerMotionEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl PointerMotionEvent for VirtualPointerMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+}
+
+/// Absolute pointer motion event of the virtual backend
+///
+/// Its position is in normalized device coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualPointerMotionAbsoluteEvent {
+    time: u32,
+    position: (f64, f64),
+}
+
+impl BackendEvent for VirtualPointerMotionAbsoluteEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl PointerMotionAbsoluteEvent for VirtualPointerMotionAbsoluteEvent {
+    fn x(&self) -> f64 {
+        self.position.0
+    }
+
+    fn y(&self) -> f64 {
+        self.position.1
+    }
+
+    fn x_transformed(&self, width: u32) -> f64 {
+        self.position.0 * f64::from(width)
+    }
+
+    fn y_transformed(&self, height: u32) -> f64 {
+        self.position.1 * f64::from(height)
+    }
+}
+
+/// Pointer button event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualPointerButtonEvent {
+    time: u32,
+    button: MouseButton,
+    state: MouseButtonState,
+}
+
+impl BackendEvent for VirtualPointerButtonEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl PointerButtonEvent for VirtualPointerButtonEvent {
+    fn button(&self) -> MouseButton {
+        self.button
+    }
+
+    fn state(&self) -> MouseButtonState {
+        self.state
+    }
+}
+
+/// Pointer axis event of the virtual backend
+///
+/// Like libinput, both axes are reported, with an amount of `0.0` for an axis that did not
+/// scroll.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualPointerAxisEvent {
+    time: u32,
+    source: AxisSource,
+    amount: Option<(f64, f64)>,
+    amount_discrete: Option<(f64, f64)>,
+}
+
+// the amount of a scroll along one axis
+fn axis_amount(amount: Option<(f64, f64)>, axis: Axis) -> Option<f64> {
+    amount.map(|(horizontal, vertical)| match axis {
+        Axis::Horizontal => horizontal,
+        Axis::Vertical => vertical,
+    })
+}
+
+impl BackendEvent for VirtualPointerAxisEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl PointerAxisEvent for VirtualPointerAxisEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        axis_amount(self.amount, axis)
+    }
+
+    fn amount_discrete(&self, axis: Axis) -> Option<f64> {
+        axis_amount(self.amount_discrete, axis)
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source
+    }
+}
+
+/// Touch down event of the virtual backend
+///
+/// Its position is in normalized device coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualTouchDownEvent {
+    time: u32,
+    slot: u64,
+    position: (f64, f64),
+}
+
+impl BackendEvent for VirtualTouchDownEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl TouchDownEvent for VirtualTouchDownEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+
+    fn x(&self) -> f64 {
+        self.position.0
+    }
+
+    fn y(&self) -> f64 {
+        self.position.1
+    }
+
+    fn x_transformed(&self, width: u32) -> f64 {
+        self.position.0 * f64::from(width)
+    }
+
+    fn y_transformed(&self, height: u32) -> f64 {
+        self.position.1 * f64::from(height)
+    }
+}
+
+/// Touch motion event of the virtual backend
+///
+/// Its position is in normalized device coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualTouchMotionEvent {
+    time: u32,
+    slot: u64,
+    position: (f64, f64),
+}
+
+impl BackendEvent for VirtualTouchMotionEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl TouchMotionEvent for VirtualTouchMotionEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+
+    fn x(&self) -> f64 {
+        self.position.0
+    }
+
+    fn y(&self) -> f64 {
+        self.position.1
+    }
+
+    fn x_transformed(&self, width: u32) -> f64 {
+        self.position.0 * f64::from(width)
+    }
+
+    fn y_transformed(&self, height: u32) -> f64 {
+        self.position.1 * f64::from(height)
+    }
+}
+
+/// Touch up event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualTouchUpEvent {
+    time: u32,
+    slot: u64,
+}
+
+impl BackendEvent for VirtualTouchUpEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl TouchUpEvent for VirtualTouchUpEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+}
+
+/// Touch cancel event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualTouchCancelEvent {
+    time: u32,
+    slot: u64,
+}
+
+impl BackendEvent for VirtualTouchCancelEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl TouchCancelEvent for VirtualTouchCancelEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+}
+
+/// Touch frame event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualTouchFrameEvent {
+    time: u32,
+}
+
+impl BackendEvent for VirtualTouchFrameEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl TouchFrameEvent for VirtualTouchFrameEvent {}
+
+/// Gesture begin event of the virtual backend
+///
+/// It begins swipe, pinch and hold gestures alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualGestureBeginEvent {
+    time: u32,
+    fingers: u32,
+}
+
+impl BackendEvent for VirtualGestureBeginEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl GestureBeginEvent for VirtualGestureBeginEvent {
+    fn fingers(&self) -> u32 {
+        self.fingers
+    }
+}
+
+impl GestureSwipeBeginEvent for VirtualGestureBeginEvent {}
+impl GesturePinchBeginEvent for VirtualGestureBeginEvent {}
+impl GestureHoldBeginEvent for VirtualGestureBeginEvent {}
+
+/// Swipe gesture update event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualGestureSwipeUpdateEvent {
+    time: u32,
+    delta: (f64, f64),
+}
+
+impl BackendEvent for VirtualGestureSwipeUpdateEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl GestureSwipeUpdateEvent for VirtualGestureSwipeUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+}
+
+/// Pinch gesture update event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualGesturePinchUpdateEvent {
+    time: u32,
+    delta: (f64, f64),
+    scale: f64,
+    rotation: f64,
+}
+
+impl BackendEvent for VirtualGesturePinchUpdateEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl GesturePinchUpdateEvent for VirtualGesturePinchUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+
+    fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    fn rotation(&self) -> f64 {
+        self.rotation
+    }
+}
+
+/// Gesture end event of the virtual backend
+///
+/// It ends swipe, pinch and hold gestures alike.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualGestureEndEvent {
+    time: u32,
+    cancelled: bool,
+}
+
+impl BackendEvent for VirtualGestureEndEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl GestureEndEvent for VirtualGestureEndEvent {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl GestureSwipeEndEvent for VirtualGestureEndEvent {}
+impl GesturePinchEndEvent for VirtualGestureEndEvent {}
+impl GestureHoldEndEvent for VirtualGestureEndEvent {}
+
+/// Switch toggle event of the virtual backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VirtualSwitchToggleEvent {
+    time: u32,
+    switch: Switch,
+    state: SwitchState,
+}
+
+impl BackendEvent for VirtualSwitchToggleEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+impl SwitchToggleEvent for VirtualSwitchToggleEvent {
+    fn switch(&self) -> Option<Switch> {
+        Some(self.switch)
+    }
+
+    fn state(&self) -> SwitchState {
+        self.state
+    }
+}
+
+/// The input device of the virtual backend
+///
+/// It is a single keyboard, pointer, touch, gesture and switch device, which cannot be
+/// configured.
+#[derive(Debug)]
+pub struct VirtualDevice;
+
+impl Device for VirtualDevice {
+    fn name(&self) -> String {
+        "virtual".into()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        matches!(
+            capability,
+            DeviceCapability::Keyboard
+                | DeviceCapability::Pointer
+                | DeviceCapability::Touch
+                | DeviceCapability::Gesture
+                | DeviceCapability::Switch
+        )
+    }
+}
+
+/// Input config of the virtual backend
+///
+/// This backend only has a single [`VirtualDevice`], which does not allow any configuration.
+#[derive(Debug)]
+pub struct VirtualInputConfig {
+    device: VirtualDevice,
+}
+
+impl InputConfig for VirtualInputConfig {
+    type Device = VirtualDevice;
+
+    fn devices(&mut self) -> &mut [VirtualDevice] {
+        ::std::slice::from_mut(&mut self.device)
+    }
+}
+
+// the real time reference of a replay
+#[derive(Debug)]
+struct Playback {
+    speed: f64,
+    start: Instant,
+    start_time: u32,
+}
+
+impl Playback {
+    // the virtual time that should have been reached by now
+    fn current_time(&self) -> u32 {
+        let elapsed = self.start.elapsed().as_secs_f64() * 1000.0 * self.speed;
+        self.start_time.wrapping_add(elapsed as u32)
+    }
+}
+
+/// Input backend whose events are pushed programmatically
+///
+/// The pushed events are delivered by the next call to
+/// [`dispatch_new_events`](InputBackend::dispatch_new_events), which also reports the seat of
+/// the backend the first time it is called.
+#[derive(Debug)]
+pub struct VirtualInputBackend {
+    seat: Seat,
+    seat_announced: bool,
+    time: u32,
+    playback: Option<Playback>,
+    pending: VecDeque<VirtualEvent>,
+    pressed_keys: HashSet<u32>,
+    config: VirtualInputConfig,
+    logger: ::slog::Logger,
+}
+
+impl VirtualInputBackend {
+    /// Create a new virtual input backend
+    pub fn new<L>(logger: L) -> VirtualInputBackend
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_virtual_input"));
+        VirtualInputBackend {
+            seat: Seat::new(
+                0,
+                "virtual",
+                SeatCapabilities {
+                    pointer: true,
+                    keyboard: true,
+                    touch: true,
+                },
+            ),
+            seat_announced: false,
+            time: 0,
+            playback: None,
+            pending: VecDeque::new(),
+            pressed_keys: HashSet::new(),
+            config: VirtualInputConfig {
+                device: VirtualDevice,
+            },
+            logger: log,
+        }
+    }
+
+    /// Push an event, to be delivered by the next dispatch
+    pub fn push(&mut self, event: VirtualEvent) {
+        self.pending.push_back(event);
+    }
+
+    /// Push all the events of a script
+    ///
+    /// See the [module-level documentation](self) for its format. If the script is invalid, none
+    /// of its events are pushed.
+    pub fn push_script(&mut self, script: &str) -> Result<(), ScriptError> {
+        let events = parse_script(script)?;
+        debug!(self.logger, "Pushing {} scripted events", events.len());
+        self.pending.extend(events);
+        Ok(())
+    }
+
+    /// Push all the events of a script file
+    pub fn load_script<P: AsRef<Path>>(&mut self, path: P) -> Result<(), ScriptError> {
+        let script = fs::read_to_string(path)?;
+        self.push_script(&script)
+    }
+
+    /// The current time of the virtual clock, in milliseconds
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// Whether some pushed events were not dispatched yet
+    pub fn has_pending_events(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Honor the waits between the events, at the given speed
+    ///
+    /// With `Some(1.0)` the events are replayed in real time, with `Some(2.0)` twice as fast,
+    /// and so on: each dispatch only delivers the events whose time has come. With `None`, the
+    /// default, all the pending events are delivered at once.
+    pub fn set_playback_speed(&mut self, speed: Option<f64>) {
+        self.playback = speed.filter(|&speed| speed > 0.0).map(|speed| Playback {
+            speed,
+            start: Instant::now(),
+            start_time: self.time,
+        });
+    }
+
+    /// The real time until the next pending event is due
+    ///
+    /// `None` if there are no pending events. Use it to schedule the next dispatch when
+    /// replaying events with [`set_playback_speed`](VirtualInputBackend::set_playback_speed).
+    pub fn time_until_next_event(&self) -> Option<Duration> {
+        let mut wait = 0u32;
+        let mut events = self.pending.iter();
+        while let VirtualEvent::Wait(duration) = *events.next()? {
+            wait = wait.saturating_add(duration);
+        }
+        let playback = match self.playback {
+            Some(ref playback) => playback,
+            None => return Some(Duration::from_millis(0)),
+        };
+        let elapsed = playback.current_time().wrapping_sub(self.time);
+        if elapsed >= wait {
+            return Some(Duration::from_millis(0));
+        }
+        Some(Duration::from_secs_f64(
+            f64::from(wait - elapsed) / 1000.0 / playback.speed,
+        ))
+    }
+}
+
+impl InputBackend for VirtualInputBackend {
+    type EventError = Infallible;
+
+    type KeyboardKeyEvent = VirtualKeyboardKeyEvent;
+    type PointerAxisEvent = VirtualPointerAxisEvent;
+    type PointerButtonEvent = VirtualPointerButtonEvent;
+    type PointerMotionEvent = VirtualPointerMotionEvent;
+    type PointerMotionAbsoluteEvent = VirtualPointerMotionAbsoluteEvent;
+    type TouchDownEvent = VirtualTouchDownEvent;
+    type TouchUpEvent = VirtualTouchUpEvent;
+    type TouchMotionEvent = VirtualTouchMotionEvent;
+    type TouchCancelEvent = VirtualTouchCancelEvent;
+    type TouchFrameEvent = VirtualTouchFrameEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+    type TabletPadButtonEvent = UnusedEvent;
+    type TabletPadRingEvent = UnusedEvent;
+    type TabletPadStripEvent = UnusedEvent;
+    type GestureSwipeBeginEvent = VirtualGestureBeginEvent;
+    type GestureSwipeUpdateEvent = VirtualGestureSwipeUpdateEvent;
+    type GestureSwipeEndEvent = VirtualGestureEndEvent;
+    type GesturePinchBeginEvent = VirtualGestureBeginEvent;
+    type GesturePinchUpdateEvent = VirtualGesturePinchUpdateEvent;
+    type GesturePinchEndEvent = VirtualGestureEndEvent;
+    type GestureHoldBeginEvent = VirtualGestureBeginEvent;
+    type GestureHoldEndEvent = VirtualGestureEndEvent;
+    type SwitchToggleEvent = VirtualSwitchToggleEvent;
+
+    type SpecialEvent = UnusedEvent;
+    type InputConfig = VirtualInputConfig;
+
+    fn seats(&self) -> Vec<Seat> {
+        vec![self.seat.clone()]
+    }
+
+    fn input_config(&mut self) -> &mut Self::InputConfig {
+        &mut self.config
+    }
+
+    fn dispatch_new_events<F>(&mut self, mut callback: F) -> Result<(), Infallible>
+    where
+        F: FnMut(InputEvent<Self>, &mut VirtualInputConfig),
+    {
+        if !self.seat_announced {
+            self.seat_announced = true;
+            callback(InputEvent::NewSeat(self.seat.clone()), &mut self.config);
+        }
+
+        while let Some(event) = self.pending.pop_front() {
+            let seat = self.seat.clone();
+            let time = self.time;
+            let event = match event {
+                VirtualEvent::Wait(duration) => {
+                    if let Some(ref playback) = self.playback {
+                        let elapsed = playback.current_time().wrapping_sub(self.time);
+                        if elapsed < duration {
+                            // wait for the next dispatch to deliver the next events
+                            self.time = self.time.wrapping_add(elapsed);
+                            self.pending.push_front(VirtualEvent::Wait(duration - elapsed));
+                            break;
+                        }
+                    }
+                    self.time = self.time.wrapping_add(duration);
+                    continue;
+                }
+                VirtualEvent::Key { key_code, state } => {
+                    match state {
+                        KeyState::Pressed => self.pressed_keys.insert(key_code),
+                        KeyState::Released => self.pressed_keys.remove(&key_code),
+                    };
+                    InputEvent::Keyboard {
+                        seat,
+                        event: VirtualKeyboardKeyEvent {
+                            time,
+                            key_code,
+                            state,
+                            count: self.pressed_keys.len() as u32,
+                        },
+                    }
+                }
+                VirtualEvent::PointerMotion { delta } => InputEvent::PointerMotion {
+                    seat,
+                    event: VirtualPointerMotionEvent { time, delta },
+                },
+                VirtualEvent::PointerMotionAbsolute { position } => InputEvent::PointerMotionAbsolute {
+                    seat,
+                    event: VirtualPointerMotionAbsoluteEvent { time, position },
+                },
+                VirtualEvent::PointerButton { button, state } => InputEvent::PointerButton {
+                    seat,
+                    event: VirtualPointerButtonEvent { time, button, state },
+                },
+                VirtualEvent::PointerAxis {
+                    source,
+                    amount,
+                    amount_discrete,
+                } => InputEvent::PointerAxis {
+                    seat,
+                    event: VirtualPointerAxisEvent {
+                        time,
+                        source,
+                        amount,
+                        amount_discrete,
+                    },
+                },
+                VirtualEvent::TouchDown { slot, position } => InputEvent::TouchDown {
+                    seat,
+                    event: VirtualTouchDownEvent { time, slot, position },
+                },
+                VirtualEvent::TouchMotion { slot, position } => InputEvent::TouchMotion {
+                    seat,
+                    event: VirtualTouchMotionEvent { time, slot, position },
+                },
+                VirtualEvent::TouchUp { slot } => InputEvent::TouchUp {
+                    seat,
+                    event: VirtualTouchUpEvent { time, slot },
+                },
+                VirtualEvent::TouchCancel { slot } => InputEvent::TouchCancel {
+                    seat,
+                    event: VirtualTouchCancelEvent { time, slot },
+                },
+                VirtualEvent::TouchFrame => InputEvent::TouchFrame {
+                    seat,
+                    event: VirtualTouchFrameEvent { time },
+                },
+                VirtualEvent::GestureSwipeBegin { fingers } => InputEvent::GestureSwipeBegin {
+                    seat,
+                    event: VirtualGestureBeginEvent { time, fingers },
+                },
+                VirtualEvent::GestureSwipeUpdate { delta } => InputEvent::GestureSwipeUpdate {
+                    seat,
+                    event: VirtualGestureSwipeUpdateEvent { time, delta },
+                },
+                VirtualEvent::GestureSwipeEnd { cancelled } => InputEvent::GestureSwipeEnd {
+                    seat,
+                    event: VirtualGestureEndEvent { time, cancelled },
+                },
+                VirtualEvent::GesturePinchBegin { fingers } => InputEvent::GesturePinchBegin {
+                    seat,
+                    event: VirtualGestureBeginEvent { time, fingers },
+                },
+                VirtualEvent::GesturePinchUpdate {
+                    delta,
+                    scale,
+                    rotation,
+                } => InputEvent::GesturePinchUpdate {
+                    seat,
+                    event: VirtualGesturePinchUpdateEvent {
+                        time,
+                        delta,
+                        scale,
+                        rotation,
+                    },
+                },
+                VirtualEvent::GesturePinchEnd { cancelled } => InputEvent::GesturePinchEnd {
+                    seat,
+                    event: VirtualGestureEndEvent { time, cancelled },
+                },
+                VirtualEvent::GestureHoldBegin { fingers } => InputEvent::GestureHoldBegin {
+                    seat,
+                    event: VirtualGestureBeginEvent { time, fingers },
+                },
+                VirtualEvent::GestureHoldEnd { cancelled } => InputEvent::GestureHoldEnd {
+                    seat,
+                    event: VirtualGestureEndEvent { time, cancelled },
+                },
+                VirtualEvent::SwitchToggle { switch, state } => InputEvent::SwitchToggle {
+                    seat,
+                    event: VirtualSwitchToggleEvent { time, switch, state },
+                },
+            };
+            callback(event, &mut self.config);
+        }
+        Ok(())
+    }
+}
+
+/// Errors when recording an event
+#[derive(Debug, thiserror::Error)]
+pub enum RecordError {
+    /// The event could not be written
+    #[error("The event could not be written")]
+    Io(#[from] io::Error),
+    /// The virtual backend cannot emulate this kind of event
+    #[error("{0} events cannot be replayed by the virtual backend")]
+    Unsupported(&'static str),
+}
+
+/// Records the events of an input backend as a script
+///
+/// Pass all the events of your input backend to [`record`](InputRecorder::record), and replay
+/// the resulting script with a [`VirtualInputBackend`]. The timestamps of the events are
+/// recorded as waits between them.
+#[derive(Debug)]
+pub struct InputRecorder<W: Write> {
+    writer: W,
+    last_time: Option<u32>,
+}
+
+impl InputRecorder<BufWriter<File>> {
+    /// Create a recorder writing to a new file
+    ///
+    /// The file is truncated if it already exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<InputRecorder<BufWriter<File>>> {
+        Ok(InputRecorder::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> InputRecorder<W> {
+    /// Create a recorder writing to the given writer
+    pub fn new(writer: W) -> InputRecorder<W> {
+        InputRecorder {
+            writer,
+            last_time: None,
+        }
+    }
+
+    /// Record an event
+    ///
+    /// Absolute positions are recorded in normalized device coordinates. The seat events are
+    /// skipped, as the virtual backend has a single seat of its own.
+    ///
+    /// Events that a [`VirtualInputBackend`] cannot emulate are not recorded, and
+    /// [`RecordError::Unsupported`] is returned: tablet and backend-specific events, and the
+    /// toggles of switches unknown to smithay. The recording can go on, but its replay will miss these events.
+    pub fn record<B: InputBackend>(&mut self, event: &InputEvent<B>) -> Result<(), RecordError> {
+        let (time, event) = match recorded_event(event)? {
+            Some(recorded) => recorded,
+            None => return Ok(()),
+        };
+        if let Some(last_time) = self.last_time {
+            // events of different devices may not be perfectly ordered
+            if time > last_time {
+                writeln!(self.writer, "{}", VirtualEvent::Wait(time - last_time))?;
+            }
+        }
+        self.last_time = Some(self.last_time.map(|last| last.max(time)).unwrap_or(time));
+        writeln!(self.writer, "{}", event)?;
+        Ok(())
+    }
+
+    /// Flush the recorded events to the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Retrieve the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// the timestamp of an event, and the virtual event emulating it
+//
+// The seat events are skipped, as the virtual backend has its own seat.
+fn recorded_event<B: InputBackend>(
+    event: &InputEvent<B>,
+) -> Result<Option<(u32, VirtualEvent)>, RecordError> {
+    let slot = |slot: Option<TouchSlot>| slot.map(|slot| slot.id()).unwrap_or(0);
+    let recorded = match event {
+        InputEvent::Keyboard { event, .. } => (
+            event.time(),
+            VirtualEvent::Key {
+                key_code: event.key_code(),
+                state: event.state(),
+            },
+        ),
+        InputEvent::PointerMotion { event, .. } => (
+            event.time(),
+            VirtualEvent::PointerMotion {
+                delta: (event.delta_x(), event.delta_y()),
+            },
+        ),
+        InputEvent::PointerMotionAbsolute { event, .. } => (
+            event.time(),
+            VirtualEvent::PointerMotionAbsolute {
+                position: (event.x_transformed(1), event.y_transformed(1)),
+            },
+        ),
+        InputEvent::PointerButton { event, .. } => (
+            event.time(),
+            VirtualEvent::PointerButton {
+                button: event.button(),
+                state: event.state(),
+            },
+        ),
+        InputEvent::PointerAxis { event, .. } => (
+            event.time(),
+            VirtualEvent::PointerAxis {
+                source: event.source(),
+                amount: both_axes(|axis| event.amount(axis)),
+                amount_discrete: both_axes(|axis| event.amount_discrete(axis)),
+            },
+        ),
+        InputEvent::TouchDown { event, .. } => (
+            event.time(),
+            VirtualEvent::TouchDown {
+                slot: slot(event.slot()),
+                position: (event.x_transformed(1), event.y_transformed(1)),
+            },
+        ),
+        InputEvent::TouchMotion { event, .. } => (
+            event.time(),
+            VirtualEvent::TouchMotion {
+                slot: slot(event.slot()),
+                position: (event.x_transformed(1), event.y_transformed(1)),
+            },
+        ),
+        InputEvent::TouchUp { event, .. } => (
+            event.time(),
+            VirtualEvent::TouchUp {
+                slot: slot(event.slot()),
+            },
+        ),
+        InputEvent::TouchCancel { event, .. } => (
+            event.time(),
+            VirtualEvent::TouchCancel {
+                slot: slot(event.slot()),
+            },
+        ),
+        InputEvent::TouchFrame { event, .. } => (event.time(), VirtualEvent::TouchFrame),
+        InputEvent::NewSeat(_) | InputEvent::SeatChanged(_) | InputEvent::SeatRemoved(_) => return Ok(None),
+        InputEvent::TabletToolAxis { .. }
+        | InputEvent::TabletToolProximity { .. }
+        | InputEvent::TabletToolTip { .. }
+        | InputEvent::TabletToolButton { .. }
+        | InputEvent::TabletPadButton { .. }
+        | InputEvent::TabletPadRing { .. }
+        | InputEvent::TabletPadStrip { .. } => return Err(RecordError::Unsupported("tablet")),
+        InputEvent::GestureSwipeBegin { event, .. } => (
+            event.time(),
+            VirtualEvent::GestureSwipeBegin {
+                fingers: event.fingers(),
+            },
+        ),
+        InputEvent::GestureSwipeUpdate { event, .. } => (
+            event.time(),
+            VirtualEvent::GestureSwipeUpdate { delta: event.delta() },
+        ),
+        InputEvent::GestureSwipeEnd { event, .. } => (
+            event.time(),
+            VirtualEvent::GestureSwipeEnd {
+                cancelled: event.cancelled(),
+            },
+        ),
+        InputEvent::GesturePinchBegin { event, .. } => (
+            event.time(),
+            VirtualEvent::GesturePinchBegin {
+                fingers: event.fingers(),
+            },
+        ),
+        InputEvent::GesturePinchUpdate { event, .. } => (
+            event.time(),
+            VirtualEvent::GesturePinchUpdate {
+                delta: event.delta(),
+                scale: event.scale(),
+                rotation: event.rotation(),
+            },
+        ),
+        InputEvent::GesturePinchEnd { event, .. } => (
+            event.time(),
+            VirtualEvent::GesturePinchEnd {
+                cancelled: event.cancelled(),
+            },
+        ),
+        InputEvent::GestureHoldBegin { event, .. } => (
+            event.time(),
+            VirtualEvent::GestureHoldBegin {
+                fingers: event.fingers(),
+            },
+        ),
+        InputEvent::GestureHoldEnd { event, .. } => (
+            event.time(),
+            VirtualEvent::GestureHoldEnd {
+                cancelled: event.cancelled(),
+            },
+        ),
+        InputEvent::SwitchToggle { event, .. } => match event.switch() {
+            Some(switch) => (
+                event.time(),
+                VirtualEvent::SwitchToggle {
+                    switch,
+                    state: event.state(),
+                },
+            ),
+            None => return Err(RecordError::Unsupported("unknown switch")),
+        },
+        InputEvent::Special(_) => return Err(RecordError::Unsupported("backend-specific")),
+    };
+    Ok(Some(recorded))
+}
+
+// the amounts of a scroll along both axes, an axis without amount did not scroll
+fn both_axes<F: Fn(Axis) -> Option<f64>>(amount: F) -> Option<(f64, f64)> {
+    match (amount(Axis::Horizontal), amount(Axis::Vertical)) {
+        (None, None) => None,
+        (horizontal, vertical) => Some((horizontal.unwrap_or(0.0), vertical.unwrap_or(0.0))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_script, InputRecorder, ScriptError, VirtualEvent, VirtualInputBackend};
+    use crate::backend::input::{
+        Axis, AxisSource, Event, InputBackend, InputEvent, KeyState, KeyboardKeyEvent, MouseButton,
+        MouseButtonState, PointerAxisEvent,
+    };
+
+    #[test]
+    fn parse_events() {
+        let events = parse_script(
+            "# comment\n\
+             key 30 pressed\n\
+             \n\
+             wait 16\n\
+             button right released\n\
+             axis wheel_tilt discrete -1 0\n\
+             touch_down 2 0.5 0.25\n",
+        )
+        .unwrap();
+        assert_eq!(
+            events[0],
+            VirtualEvent::Key {
+                key_code: 30,
+                state: KeyState::Pressed
+            }
+        );
+        assert_eq!(events[1], VirtualEvent::Wait(16));
+        assert_eq!(
+            events[2],
+            VirtualEvent::PointerButton {
+                button: MouseButton::Right,
+                state: MouseButtonState::Released
+            }
+        );
+        assert_eq!(
+            events[3],
+            VirtualEvent::PointerAxis {
+                source: AxisSource::WheelTilt,
+                amount: None,
+                amount_discrete: Some((-1.0, 0.0)),
+            }
+        );
+        assert_eq!(
+            events[4],
+            VirtualEvent::TouchDown {
+                slot: 2,
+                position: (0.5, 0.25)
+            }
+        );
+        match parse_script("key 30 pressed\nmotion 1") {
+            Err(ScriptError::Parse { line: 2, .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // wheels must report their steps
+        match parse_script("axis wheel 0 15") {
+            Err(ScriptError::Parse { line: 1, .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match parse_script("swipe_begin 3\nswipe_end aborted") {
+            Err(ScriptError::Parse { line: 2, .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dispatch_in_order() {
+        let mut backend = VirtualInputBackend::new(None);
+        backend
+            .push_script("key 42 pressed\nkey 30 pressed\nwait 20\nkey 30 released\naxis continuous 0 15")
+            .unwrap();
+        let mut keys = Vec::new();
+        let mut seats = 0;
+        let mut axis = None;
+        backend
+            .dispatch_new_events(|event, _| match event {
+                InputEvent::NewSeat(_) => seats += 1,
+                InputEvent::Keyboard { event, .. } => {
+                    keys.push((event.time(), event.key_code(), event.count()))
+                }
+                InputEvent::PointerAxis { event, .. } => {
+                    axis = Some((
+                        event.amount(Axis::Horizontal),
+                        event.amount(Axis::Vertical),
+                        event.amount_discrete(Axis::Vertical),
+                    ))
+                }
+                _ => panic!("unexpected event"),
+            })
+            .unwrap();
+        assert_eq!(seats, 1);
+        assert_eq!(keys, vec![(0, 42, 1), (0, 30, 2), (20, 30, 1)]);
+        // the idle axis is reported as well
+        assert_eq!(axis, Some((Some(0.0), Some(15.0), None)));
+        assert!(!backend.has_pending_events());
+        assert_eq!(backend.time(), 20);
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let script = "key 30 pressed\n\
+                      wait 15\n\
+                      button 8 released\n\
+                      axis wheel_tilt discrete -1 0\n\
+                      wait 1\n\
+                      axis wheel 15 -30 discrete 1 -2\n\
+                      axis finger 0 0.5\n\
+                      touch_down 3 0.25 0.75\n\
+                      touch_frame\n\
+                      wait 4\n\
+                      swipe_begin 3\n\
+                      swipe_update -20 0.5\n\
+                      swipe_end\n\
+                      pinch_begin 2\n\
+                      pinch_update 1 -1 1.5 -10\n\
+                      pinch_end cancelled\n\
+                      hold_begin 4\n\
+                      hold_end\n\
+                      switch tablet_mode on\n";
+        let mut backend = VirtualInputBackend::new(None);
+        backend.push_script(script).unwrap();
+        let mut recorder = InputRecorder::new(Vec::new());
+        backend
+            .dispatch_new_events(|event, _| recorder.record(&event).unwrap())
+            .unwrap();
+        let recorded = String::from_utf8(recorder.into_inner()).unwrap();
+        assert_eq!(parse_script(&recorded).unwrap(), parse_script(script).unwrap());
+    }
+
+    #[test]
+    fn playback_honors_waits() {
+        let mut backend = VirtualInputBackend::new(None);
+        backend
+            .push_script("key 30 pressed\nwait 60000\nkey 30 released")
+            .unwrap();
+        backend.set_playback_speed(Some(1.0));
+        let mut keys = 0;
+        backend.dispatch_new_events(|_, _| keys += 1).unwrap();
+        // the seat and the first key
+        assert_eq!(keys, 2);
+        assert!(backend.has_pending_events());
+        assert!(backend.time_until_next_event().unwrap().as_secs() > 50);
+        // delivering everything at once
+        backend.set_playback_speed(None);
+        backend.dispatch_new_events(|_, _| keys += 1).unwrap();
+        assert_eq!(keys, 3);
+        assert_eq!(backend.time(), 60000);
+        assert_eq!(backend.time_until_next_event(), None);
+    }
+}
@@ -6,10 +6,10 @@ use crate::backend::{
     egl::{context::GlAttributes, native, EGLContext, EGLSurface, Error as EGLError, SurfaceCreationError},
     graphics::{gl::GLGraphicsBackend, CursorBackend, PixelFormat, SwapBuffersError},
     input::{
-        Axis, AxisSource, Event as BackendEvent, InputBackend, InputEvent, KeyState, KeyboardKeyEvent,
-        MouseButton, MouseButtonState, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent,
-        Seat, SeatCapabilities, TouchCancelEvent, TouchDownEvent, TouchMotionEvent, TouchSlot, TouchUpEvent,
-        UnusedEvent,
+        Axis, AxisSource, Device, DeviceCapability, Event as BackendEvent, InputBackend, InputConfig,
+        InputEvent, KeyState, KeyboardKeyEvent, MouseButton, MouseButtonState, PointerAxisEvent,
+        PointerButtonEvent, PointerMotionAbsoluteEvent, Seat, SeatCapabilities, TouchCancelEvent,
+        TouchDownEvent, TouchMotionEvent, TouchSlot, TouchUpEvent, UnusedEvent,
     },
 };
 use nix::libc::c_void;
@@ -99,6 +99,7 @@ pub struct WinitInputBackend {
     seat: Seat,
     logger: ::slog::Logger,
     size: Rc<RefCell<WindowSize>>,
+    config: WinitInputConfig,
 }
 
 /// Create a new [`WinitGraphicsBackend`], which implements the [`EGLGraphicsBackend`]
@@ -221,6 +222,7 @@ where
             ),
             logger: log.new(o!("smithay_winit_component" => "input")),
             size,
+            config: WinitInputConfig { device: WinitDevice },
         },
     ))
 }
@@ -613,10 +615,40 @@ impl TouchCancelEvent for WinitTouchCancelledEvent {
     }
 }
 
+/// The input device of the winit backend
+///
+/// The window is seen as a single keyboard, pointer and touch device, which cannot be configured.
+#[derive(Debug)]
+pub struct WinitDevice;
+
+impl Device for WinitDevice {
+    fn name(&self) -> String {
+        "winit".into()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        matches!(
+            capability,
+            DeviceCapability::Keyboard | DeviceCapability::Pointer | DeviceCapability::Touch
+        )
+    }
+}
+
 /// Input config for Winit
 ///
-/// This backend does not allow any input configuration, so this type does nothing.
-pub struct WinitInputConfig;
+/// This backend only has a single [`WinitDevice`], which does not allow any configuration.
+#[derive(Debug)]
+pub struct WinitInputConfig {
+    device: WinitDevice,
+}
+
+impl InputConfig for WinitInputConfig {
+    type Device = WinitDevice;
+
+    fn devices(&mut self) -> &mut [WinitDevice] {
+        ::std::slice::from_mut(&mut self.device)
+    }
+}
 
 impl InputBackend for WinitInputBackend {
     type EventError = WinitInputError;
@@ -631,6 +663,22 @@ impl InputBackend for WinitInputBackend {
     type TouchMotionEvent = WinitTouchMovedEvent;
     type TouchCancelEvent = WinitTouchCancelledEvent;
     type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+    type TabletPadButtonEvent = UnusedEvent;
+    type TabletPadRingEvent = UnusedEvent;
+    type TabletPadStripEvent = UnusedEvent;
+    type GestureSwipeBeginEvent = UnusedEvent;
+    type GestureSwipeUpdateEvent = UnusedEvent;
+    type GestureSwipeEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = UnusedEvent;
+    type GesturePinchUpdateEvent = UnusedEvent;
+    type GesturePinchEndEvent = UnusedEvent;
+    type GestureHoldBeginEvent = UnusedEvent;
+    type GestureHoldEndEvent = UnusedEvent;
+    type SwitchToggleEvent = UnusedEvent;
 
     type SpecialEvent = WinitEvent;
     type InputConfig = WinitInputConfig;
@@ -640,9 +688,7 @@ impl InputBackend for WinitInputBackend {
     }
 
     fn input_config(&mut self) -> &mut Self::InputConfig {
-        /// So much effort to return a useless singleton!
-        static mut CONFIG: WinitInputConfig = WinitInputConfig;
-        unsafe { &mut CONFIG }
+        &mut self.config
     }
 
     /// Processes new events of the underlying event loop to drive the set [`InputHandler`].
@@ -676,7 +722,8 @@ impl InputBackend for WinitInputBackend {
             let window = &self.window;
             let logger = &self.logger;
             let window_size = &self.size;
-            let mut callback = move |event| callback(event, &mut WinitInputConfig);
+            let config = &mut self.config;
+            let mut callback = move |event| callback(event, &mut *config);
 
             self.events_loop
                 .run_return(move |event, _target, control_flow| match event {
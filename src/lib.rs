@@ -6,6 +6,44 @@
 //! whether the `slog-stdlog` is enabled. If yes, the module will log to the global logger of the
 //! `log` crate. If not, the logs will discarded. This cargo feature is part of the default set of
 //! features of Smithay.
+//!
+//! ## Cargo features
+//!
+//! Smithay is split into cargo features, so that you only build (and link to) the parts you
+//! actually use. Most of them are enabled by default, those marked *(default)* below or enabled
+//! by them. Use `default-features = false` to pick them individually:
+//!
+//! - `wayland_frontend` *(default)*: the [`wayland`] module, implementing the wayland protocols
+//!   (requires `libxkbcommon`). The protocols the compositor and seat handlers rely on, like the
+//!   viewporter, presentation time, pointer constraints and keyboard shortcuts inhibition, are
+//!   always built with it, the other protocol families have their own features:
+//!   - `wayland_input_protocols` *(default)*: the relative pointer, pointer gestures and tablet
+//!     protocols,
+//!   - `wayland_idle_inhibit` *(default)*: the idle inhibition protocol,
+//!   - `wayland_wlr_protocols` *(default)*: the wlroots gamma control, screencopy and virtual
+//!     pointer protocols,
+//! - `xwayland` *(default)*: the [`xwayland`] module, to start and manage an XWayland server,
+//! - `backend_drm`: the base of the [drm backend](backend::drm), with the `backend_drm_legacy`,
+//!   `backend_drm_atomic`, `backend_drm_gbm`, `backend_drm_egl` and `backend_drm_eglstream`
+//!   features *(default)* enabling the corresponding device implementations,
+//! - `backend_egl`: the [egl backend](backend::egl), loading `libEGL` at runtime,
+//! - `backend_winit` *(default)*: the [winit backend](backend::winit), to run as a window of
+//!   another wayland compositor or X11 server,
+//! - `backend_libinput` *(default)*: the [libinput backend](backend::libinput),
+//! - `backend_udev` *(default)*: the [udev backend](backend::udev), to discover and hotplug
+//!   devices,
+//! - `backend_session`: the [session backend](backend::session), with `backend_session_logind`
+//!   *(default)* or `backend_session_elogind` adding support for logind sessions,
+//! - `renderer_gl`, `renderer_glium` *(default)* and `renderer_software`: the corresponding
+//!   [rendering traits](backend::graphics),
+//! - `use_system_lib`: use the system `libwayland-server`, required to share clients with
+//!   C libraries like `libEGL`,
+//! - `xcursor_theme`: the [`cursor`](utils::cursor) utilities, loading xcursor themes.
+//!
+//! An embedded compositor driving a single drm device and rendering with gbm and egl, for
+//! example, only needs `wayland_frontend`, `backend_drm_atomic`, `backend_drm_gbm`,
+//! `backend_drm_egl`, `backend_libinput`, `backend_udev` and `backend_session`, and will not
+//! pull the winit and X11 dependencies.
 
 // `error_chain!` can recurse deeply
 #![recursion_limit = "1024"]
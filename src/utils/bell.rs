@@ -0,0 +1,192 @@
+//! Audible and visual bell
+//!
+//! Terminals and other applications ring the bell to draw the attention of the user.
+//! People who cannot hear the bell, or who disabled sounds, rely on a visual replacement,
+//! usually a short flash of the screen or of the window that rang it.
+//!
+//! [`Bell`] decides how to react to a bell, rate-limiting clients ringing it repeatedly, and
+//! tracks the visual flash: while it is running, [`Bell::flash_opacity`] gives you the
+//! opacity of a white overlay to draw over the output or window.
+//!
+//! This module does not receive bells nor render anything by itself:
+//!
+//! - smithay implements no Wayland bell protocol, and XWayland only reports the bells of X11
+//!   clients as `XkbBellNotify` events to an X11 window manager, so call [`Bell::ring`] from
+//!   the bell source of your compositor, e.g. the XKB events received by your window manager;
+//! - draw the overlay with your own renderer, and play the sound yourself, e.g. using the
+//!   `bell` sound of the XDG sound theme.
+//!
+//! ```
+//! use smithay::utils::bell::{Bell, BellConfig};
+//! use std::time::Instant;
+//!
+//! let mut bell = Bell::new(BellConfig {
+//!     audible: false,
+//!     visual: true,
+//!     ..BellConfig::default()
+//! });
+//!
+//! // when a client rings the bell
+//! let now = Instant::now();
+//! if let Some(ring) = bell.ring(now) {
+//!     assert!(ring.visual);
+//! }
+//!
+//! // when rendering a frame
+//! if let Some(opacity) = bell.flash_opacity(Instant::now()) {
+//!     // draw a white rectangle with the given opacity on top of everything
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+/// Configuration of the bell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BellConfig {
+    /// Whether a sound should be played
+    pub audible: bool,
+    /// Whether the screen should flash
+    pub visual: bool,
+    /// Duration of the visual flash
+    pub flash_duration: Duration,
+    /// Opacity of the flash overlay when it starts, between `0.0` and `1.0`
+    pub flash_opacity: f32,
+    /// Bells rung less than this interval after the previous one are ignored
+    pub min_interval: Duration,
+}
+
+impl Default for BellConfig {
+    fn default() -> Self {
+        BellConfig {
+            audible: true,
+            visual: false,
+            flash_duration: Duration::from_millis(150),
+            flash_opacity: 0.5,
+            min_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// How to react to a bell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BellRing {
+    /// A sound should be played
+    pub audible: bool,
+    /// A flash was started
+    pub visual: bool,
+}
+
+/// State of the bell of an output, or of the whole compositor
+#[derive(Debug, Clone)]
+pub struct Bell {
+    config: BellConfig,
+    last_ring: Option<Instant>,
+    flash_start: Option<Instant>,
+}
+
+impl Bell {
+    /// Create a new bell with the given configuration
+    pub fn new(config: BellConfig) -> Bell {
+        Bell {
+            config,
+            last_ring: None,
+            flash_start: None,
+        }
+    }
+
+    /// Access the current configuration
+    pub fn config(&self) -> BellConfig {
+        self.config
+    }
+
+    /// Change the configuration
+    pub fn set_config(&mut self, config: BellConfig) {
+        self.config = config;
+        if !config.visual {
+            self.flash_start = None;
+        }
+    }
+
+    /// Ring the bell
+    ///
+    /// Returns `None` if the bell is disabled or was rung too recently, otherwise how you
+    /// should react to it. If a flash is started, schedule a redraw of the affected outputs.
+    pub fn ring(&mut self, now: Instant) -> Option<BellRing> {
+        if !self.config.audible && !self.config.visual {
+            return None;
+        }
+        if let Some(last) = self.last_ring {
+            if now.saturating_duration_since(last) < self.config.min_interval {
+                return None;
+            }
+        }
+        self.last_ring = Some(now);
+        if self.config.visual {
+            self.flash_start = Some(now);
+        }
+        Some(BellRing {
+            audible: self.config.audible,
+            visual: self.config.visual,
+        })
+    }
+
+    /// Opacity of the flash overlay at the given time, or `None` if no flash is running
+    ///
+    /// The flash fades out linearly. Keep redrawing the affected outputs as long as this
+    /// returns `Some`.
+    pub fn flash_opacity(&mut self, now: Instant) -> Option<f32> {
+        let start = self.flash_start?;
+        let elapsed = now.saturating_duration_since(start);
+        if elapsed >= self.config.flash_duration {
+            self.flash_start = None;
+            return None;
+        }
+        let progress = elapsed.as_secs_f32() / self.config.flash_duration.as_secs_f32();
+        Some(self.config.flash_opacity * (1.0 - progress))
+    }
+}
+
+impl Default for Bell {
+    fn default() -> Self {
+        Bell::new(BellConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bell, BellConfig, BellRing};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn rate_limited() {
+        let mut bell = Bell::default();
+        let now = Instant::now();
+        assert_eq!(
+            bell.ring(now),
+            Some(BellRing {
+                audible: true,
+                visual: false
+            })
+        );
+        assert_eq!(bell.ring(now + Duration::from_millis(50)), None);
+        assert!(bell.ring(now + Duration::from_millis(150)).is_some());
+        assert_eq!(bell.flash_opacity(now), None);
+    }
+
+    #[test]
+    fn flash_fades_out() {
+        let mut bell = Bell::new(BellConfig {
+            visual: true,
+            flash_duration: Duration::from_millis(100),
+            flash_opacity: 1.0,
+            ..BellConfig::default()
+        });
+        let now = Instant::now();
+        assert!(bell.ring(now).unwrap().visual);
+        assert_eq!(bell.flash_opacity(now), Some(1.0));
+        let half = bell.flash_opacity(now + Duration::from_millis(50)).unwrap();
+        assert!((half - 0.5).abs() < 0.01);
+        assert_eq!(bell.flash_opacity(now + Duration::from_millis(100)), None);
+        assert_eq!(bell.flash_opacity(now + Duration::from_millis(50)), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Xcursor theme loading
+//!
+//! When no client sets the cursor image, the compositor needs to draw its own cursor, usually
+//! taken from the Xcursor theme selected by the user. [`CursorTheme`] loads the images of such a
+//! theme, rasterized at the right size for the scale of each output, and allows changing the
+//! theme or its size at runtime:
+//!
+//! ```no_run
+//! # extern crate smithay;
+//! use std::time::Duration;
+//! use smithay::utils::cursor::CursorTheme;
+//!
+//! let mut theme = CursorTheme::from_env(None);
+//! // redraw the cursor as soon as the theme changes
+//! theme.add_change_listener(|name, size| { /* schedule a redraw */ });
+//!
+//! // on an output with a scale of 2, 100ms into the animation of the cursor
+//! if let Some(frame) = theme.frame("default", 2, Duration::from_millis(100)) {
+//!     /* upload frame.pixels_rgba as your cursor */
+//! }
+//!
+//! theme.set_size(48);
+//! ```
+//!
+//! Clients draw their own cursors using the `XCURSOR_THEME` and `XCURSOR_SIZE` environment
+//! variables, which you need to set for the clients you spawn. Notifying running clients of
+//! a theme change requires the cursor-shape protocol, which is not supported yet.
+
+use std::{collections::HashMap, env, fs, rc::Rc, time::Duration};
+
+/// Size of the cursors if neither specified nor set in the environment
+pub const DEFAULT_SIZE: u32 = 24;
+
+/// A single image of a cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorFrame {
+    /// Width of the image in pixels
+    pub width: u32,
+    /// Height of the image in pixels
+    pub height: u32,
+    /// Position of the hotspot in the image
+    pub hotspot: (u32, u32),
+    /// Duration this image is shown in an animated cursor
+    pub delay: Duration,
+    /// The pixels of the image, in RGBA order
+    pub pixels_rgba: Vec<u8>,
+    // nominal size of the image in the theme
+    nominal_size: u32,
+}
+
+/// A loaded Xcursor theme
+pub struct CursorTheme {
+    name: String,
+    size: u32,
+    theme: xcursor::CursorTheme,
+    // all the images of an icon, of every size
+    icons: HashMap<String, Option<Rc<Vec<CursorFrame>>>>,
+    listeners: Vec<Box<dyn FnMut(&str, u32)>>,
+    log: ::slog::Logger,
+}
+
+impl CursorTheme {
+    /// Load a cursor theme by name, at the given size in logical pixels
+    pub fn new<L>(name: &str, size: u32, logger: L) -> CursorTheme
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "cursor_theme"));
+        info!(log, "Loading cursor theme"; "name" => name, "size" => size);
+        CursorTheme {
+            name: name.into(),
+            size,
+            theme: xcursor::CursorTheme::load(name),
+            icons: HashMap::new(),
+            listeners: Vec::new(),
+            log,
+        }
+    }
+
+    /// Load the cursor theme set in the `XCURSOR_THEME` and `XCURSOR_SIZE` environment variables
+    pub fn from_env<L>(logger: L) -> CursorTheme
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let name = env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into());
+        let size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_SIZE);
+        CursorTheme::new(&name, size, logger)
+    }
+
+    /// Name of the theme
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Size of the cursors in logical pixels
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Switch to another theme
+    ///
+    /// The images of the new theme are loaded on demand, and the change listeners are notified
+    /// so that you can redraw the cursor.
+    pub fn set_theme(&mut self, name: &str) {
+        if self.name == name {
+            return;
+        }
+        info!(self.log, "Changing cursor theme"; "name" => name);
+        self.name = name.into();
+        self.theme = xcursor::CursorTheme::load(name);
+        self.icons.clear();
+        self.notify();
+    }
+
+    /// Change the size of the cursors
+    ///
+    /// The change listeners are notified so that you can redraw the cursor.
+    pub fn set_size(&mut self, size: u32) {
+        if self.size == size {
+            return;
+        }
+        info!(self.log, "Changing cursor size"; "size" => size);
+        self.size = size;
+        self.notify();
+    }
+
+    /// Register a closure to be called whenever the theme or size of the cursors changes
+    ///
+    /// It is called with the name of the theme and the size of the cursors.
+    pub fn add_change_listener<F>(&mut self, listener: F)
+    where
+        F: FnMut(&str, u32) + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// The images of an animated cursor, for an output with the given scale
+    ///
+    /// Returns `None` if the icon does not exist in this theme. Static cursors have a
+    /// single image.
+    pub fn frames(&mut self, icon: &str, scale: i32) -> Option<Vec<CursorFrame>> {
+        let target = self.size * scale.max(1) as u32;
+        self.load_icon(icon).map(|images| select_size(&images, target))
+    }
+
+    /// The image of a cursor to display at a given time of its animation, for an output with
+    /// the given scale
+    ///
+    /// Returns `None` if the icon does not exist in this theme.
+    pub fn frame(&mut self, icon: &str, scale: i32, time: Duration) -> Option<CursorFrame> {
+        self.frames(icon, scale)
+            .and_then(|frames| select_frame(frames, time))
+    }
+
+    fn load_icon(&mut self, icon: &str) -> Option<Rc<Vec<CursorFrame>>> {
+        if let Some(images) = self.icons.get(icon) {
+            return images.clone();
+        }
+        let images = self
+            .theme
+            .load_icon(icon)
+            .and_then(|path| fs::read(path).ok())
+            .and_then(|content| xcursor::parser::parse_xcursor(&content))
+            .map(|images| {
+                Rc::new(
+                    images
+                        .into_iter()
+                        .map(|image| CursorFrame {
+                            width: image.width,
+                            height: image.height,
+                            hotspot: (image.xhot, image.yhot),
+                            delay: Duration::from_millis(image.delay as u64),
+                            pixels_rgba: image.pixels_rgba,
+                            nominal_size: image.size,
+                        })
+                        .collect(),
+                )
+            });
+        if images.is_none() {
+            warn!(self.log, "Cursor icon not found in theme"; "icon" => icon, "theme" => &self.name);
+        }
+        self.icons.insert(icon.into(), images.clone());
+        images
+    }
+
+    fn notify(&mut self) {
+        for listener in &mut self.listeners {
+            listener(&self.name, self.size);
+        }
+    }
+}
+
+impl ::std::fmt::Debug for CursorTheme {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("CursorTheme")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+// the images of the size closest to the target, preferring larger ones to scale down
+fn select_size(images: &[CursorFrame], target: u32) -> Vec<CursorFrame> {
+    let nominal_size = images
+        .iter()
+        .map(|image| image.nominal_size)
+        .min_by_key(|&size| ((size as i64 - target as i64).abs(), size < target));
+    images
+        .iter()
+        .filter(|image| Some(image.nominal_size) == nominal_size)
+        .cloned()
+        .collect()
+}
+
+fn select_frame(frames: Vec<CursorFrame>, time: Duration) -> Option<CursorFrame> {
+    let total: u128 = frames.iter().map(|f| f.delay.as_millis()).sum();
+    if total == 0 {
+        return frames.into_iter().next();
+    }
+    let mut time = time.as_millis() % total;
+    for frame in frames {
+        if time < frame.delay.as_millis() {
+            return Some(frame);
+        }
+        time -= frame.delay.as_millis();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(nominal_size: u32, delay: u64) -> CursorFrame {
+        CursorFrame {
+            width: nominal_size,
+            height: nominal_size,
+            hotspot: (0, 0),
+            delay: Duration::from_millis(delay),
+            pixels_rgba: Vec::new(),
+            nominal_size,
+        }
+    }
+
+    #[test]
+    fn size_selection() {
+        let images = vec![image(24, 0), image(32, 0), image(48, 0)];
+        assert_eq!(select_size(&images, 24)[0].nominal_size, 24);
+        assert_eq!(select_size(&images, 40)[0].nominal_size, 48);
+        assert_eq!(select_size(&images, 30)[0].nominal_size, 32);
+        assert_eq!(select_size(&images, 96)[0].nominal_size, 48);
+    }
+
+    #[test]
+    fn animation() {
+        let mut frames = vec![image(24, 50), image(24, 100)];
+        frames[1].hotspot = (1, 1);
+        let at = |ms| {
+            select_frame(frames.clone(), Duration::from_millis(ms))
+                .unwrap()
+                .hotspot
+        };
+        assert_eq!(at(0), (0, 0));
+        assert_eq!(at(60), (1, 1));
+        assert_eq!(at(160), (0, 0));
+    }
+}
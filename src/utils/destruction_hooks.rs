@@ -0,0 +1,40 @@
+use std::sync::Mutex;
+
+/// Hooks invoked when an object is destroyed
+///
+/// `F` is the type of the hooks, `dyn FnOnce(&T)` or `dyn FnOnce(&T) + Send` for objects that
+/// can be sent to other threads. The hooks are taken out before being invoked, so that they
+/// can access the object and register new hooks without deadlocking.
+pub(crate) struct DestructionHooks<F: ?Sized> {
+    hooks: Mutex<Vec<Box<F>>>,
+}
+
+impl<F: ?Sized> DestructionHooks<F> {
+    pub(crate) fn new() -> DestructionHooks<F> {
+        DestructionHooks {
+            hooks: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn add(&self, hook: Box<F>) {
+        self.hooks.lock().unwrap().push(hook);
+    }
+
+    pub(crate) fn take(&self) -> Vec<Box<F>> {
+        ::std::mem::take(&mut *self.hooks.lock().unwrap())
+    }
+}
+
+impl<T: ?Sized> DestructionHooks<dyn FnOnce(&T)> {
+    pub(crate) fn invoke(&self, object: &T) {
+        for hook in self.take() {
+            hook(object);
+        }
+    }
+}
+
+impl<F: ?Sized> Default for DestructionHooks<F> {
+    fn default() -> Self {
+        DestructionHooks::new()
+    }
+}
@@ -0,0 +1,145 @@
+/// State following the double-buffering rules of the Wayland protocol
+///
+/// Requests of most protocols extending `wl_surface` do not take effect immediately, but
+/// modify a *pending* state that is applied when the surface is committed. For synchronized
+/// sub-surfaces, the committed state is *cached* instead, and only applied once the parent
+/// surface is committed.
+///
+/// `DoubleBuffered` keeps these states apart, so that protocol implementations only need to
+/// decide when to call [`commit`](DoubleBuffered::commit) (or [`cache`](DoubleBuffered::cache)
+/// and [`apply_cached`](DoubleBuffered::apply_cached)), and cannot accidentally apply a pending
+/// value early.
+///
+/// ```
+/// # extern crate smithay;
+/// use smithay::utils::DoubleBuffered;
+///
+/// let mut scale = DoubleBuffered::new(1);
+/// *scale.pending_mut() = 2;
+/// assert_eq!(*scale.current(), 1);
+/// assert!(scale.commit());
+/// assert_eq!(*scale.current(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DoubleBuffered<T> {
+    current: T,
+    // only set if the pending state was modified since the last commit
+    pending: Option<T>,
+    cached: Option<T>,
+}
+
+impl<T: Clone> DoubleBuffered<T> {
+    /// Create a new state, with the given value as current state
+    pub fn new(current: T) -> DoubleBuffered<T> {
+        DoubleBuffered {
+            current,
+            pending: None,
+            cached: None,
+        }
+    }
+
+    /// The current state, as of the last commit
+    pub fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// The pending state, which is the current state if it was not modified since the last commit
+    pub fn pending(&self) -> &T {
+        self.pending
+            .as_ref()
+            .or_else(|| self.cached.as_ref())
+            .unwrap_or(&self.current)
+    }
+
+    /// Modify the pending state
+    pub fn pending_mut(&mut self) -> &mut T {
+        if self.pending.is_none() {
+            self.pending = Some(self.pending().clone());
+        }
+        self.pending.as_mut().unwrap()
+    }
+
+    /// Replace the pending state
+    pub fn set_pending(&mut self, value: T) {
+        self.pending = Some(value);
+    }
+
+    /// Whether the pending state was modified since the last commit
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Apply the pending state, returning whether it was modified since the last commit
+    pub fn commit(&mut self) -> bool {
+        match self.pending.take() {
+            Some(pending) => {
+                self.cached = None;
+                self.current = pending;
+                true
+            }
+            None => self.apply_cached(),
+        }
+    }
+
+    /// Cache the pending state instead of applying it, as done by synchronized sub-surfaces
+    ///
+    /// Returns whether it was modified since the last commit.
+    pub fn cache(&mut self) -> bool {
+        match self.pending.take() {
+            Some(pending) => {
+                self.cached = Some(pending);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Apply the cached state, returning whether there was one
+    pub fn apply_cached(&mut self) -> bool {
+        match self.cached.take() {
+            Some(cached) => {
+                self.current = cached;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Discard the modifications of the pending state since the last commit
+    pub fn rollback(&mut self) {
+        self.pending = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DoubleBuffered;
+
+    #[test]
+    fn commit_and_rollback() {
+        let mut state = DoubleBuffered::new(vec![1]);
+        state.pending_mut().push(2);
+        assert_eq!(state.current(), &vec![1]);
+        assert_eq!(state.pending(), &vec![1, 2]);
+        state.rollback();
+        assert!(!state.has_pending());
+        assert!(!state.commit());
+
+        state.set_pending(vec![3]);
+        assert!(state.commit());
+        assert_eq!(state.current(), &vec![3]);
+    }
+
+    #[test]
+    fn cached() {
+        let mut state = DoubleBuffered::new(0);
+        state.set_pending(1);
+        assert!(state.cache());
+        assert_eq!(*state.current(), 0);
+        // pending modifications start from the cached state
+        *state.pending_mut() += 1;
+        assert!(state.cache());
+        assert!(state.apply_cached());
+        assert_eq!(*state.current(), 2);
+    }
+}
@@ -0,0 +1,190 @@
+//! Tracking of user activity to detect idleness
+//!
+//! An [`IdleTracker`] records the last time the user interacted with a seat, and notifies its
+//! listeners once no activity happened for their timeout, and again when activity resumes.
+//! This is typically used to dim or turn off outputs and to lock the session.
+//!
+//! Report every input event with [`IdleTracker::activity`], and call [`IdleTracker::check`]
+//! when the deadline it returns is reached, for example with a
+//! [`Scheduler`](crate::utils::timer::Scheduler) timer. While clients inhibit idling (see
+//! the `idle_inhibit` module of the wayland frontend), use [`IdleTracker::set_inhibited`].
+//!
+//! ```
+//! # extern crate smithay;
+//! use std::time::{Duration, Instant};
+//! use smithay::utils::idle::{IdleEvent, IdleTracker};
+//!
+//! let start = Instant::now();
+//! let mut tracker = IdleTracker::new(start);
+//! tracker.add_listener(Duration::from_secs(300), |event| match event {
+//!     IdleEvent::Idle => { /* turn off the outputs */ }
+//!     IdleEvent::Resumed => { /* turn them back on */ }
+//! });
+//!
+//! // on every input event
+//! tracker.activity(start + Duration::from_secs(10));
+//! // check again at the returned deadline
+//! assert_eq!(tracker.check(start + Duration::from_secs(20)), Some(start + Duration::from_secs(310)));
+//! ```
+
+use std::time::{Duration, Instant};
+
+/// A change of the idle state reported to a listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleEvent {
+    /// No activity happened for the timeout of the listener
+    Idle,
+    /// Activity resumed after the listener was notified of idleness
+    Resumed,
+}
+
+/// Identifier of a listener of an [`IdleTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdleListenerId(usize);
+
+struct Listener {
+    id: IdleListenerId,
+    timeout: Duration,
+    idle: bool,
+    callback: Box<dyn FnMut(IdleEvent)>,
+}
+
+/// Tracker of the activity of a seat
+pub struct IdleTracker {
+    last_activity: Instant,
+    inhibited: bool,
+    listeners: Vec<Listener>,
+    next_id: usize,
+}
+
+impl IdleTracker {
+    /// Create a tracker, considering the user active at `now`
+    pub fn new(now: Instant) -> IdleTracker {
+        IdleTracker {
+            last_activity: now,
+            inhibited: false,
+            listeners: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Register a closure to be notified once no activity happened for `timeout`
+    pub fn add_listener<F>(&mut self, timeout: Duration, callback: F) -> IdleListenerId
+    where
+        F: FnMut(IdleEvent) + 'static,
+    {
+        let id = IdleListenerId(self.next_id);
+        self.next_id += 1;
+        self.listeners.push(Listener {
+            id,
+            timeout,
+            idle: false,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Unregister a listener
+    pub fn remove_listener(&mut self, id: IdleListenerId) {
+        self.listeners.retain(|listener| listener.id != id);
+    }
+
+    /// Time of the last activity
+    pub fn last_activity(&self) -> Instant {
+        self.last_activity
+    }
+
+    /// Report activity of the user, typically an input event
+    ///
+    /// Listeners that were notified of idleness are notified that activity resumed.
+    pub fn activity(&mut self, now: Instant) {
+        self.last_activity = now;
+        for listener in &mut self.listeners {
+            if listener.idle {
+                listener.idle = false;
+                (listener.callback)(IdleEvent::Resumed);
+            }
+        }
+    }
+
+    /// Prevent listeners from being notified of idleness, or allow it again
+    ///
+    /// Inhibiting idleness counts as activity, so that the timeouts start over once it is
+    /// allowed again.
+    pub fn set_inhibited(&mut self, inhibited: bool, now: Instant) {
+        if self.inhibited != inhibited {
+            self.inhibited = inhibited;
+            self.activity(now);
+        }
+    }
+
+    /// Whether idleness is currently inhibited
+    pub fn is_inhibited(&self) -> bool {
+        self.inhibited
+    }
+
+    /// Notify the listeners whose timeout elapsed
+    ///
+    /// Returns when it should be called again, `None` if no listener is waiting for a
+    /// timeout, or if idleness is inhibited.
+    pub fn check(&mut self, now: Instant) -> Option<Instant> {
+        if self.inhibited {
+            return None;
+        }
+        let mut next = None;
+        for listener in &mut self.listeners {
+            if listener.idle {
+                continue;
+            }
+            let deadline = self.last_activity + listener.timeout;
+            if deadline <= now {
+                listener.idle = true;
+                (listener.callback)(IdleEvent::Idle);
+            } else if next.map(|next| deadline < next).unwrap_or(true) {
+                next = Some(deadline);
+            }
+        }
+        next
+    }
+}
+
+impl ::std::fmt::Debug for IdleTracker {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("IdleTracker")
+            .field("last_activity", &self.last_activity)
+            .field("inhibited", &self.inhibited)
+            .field("listeners", &self.listeners.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[test]
+    fn idle_and_resume() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut tracker = IdleTracker::new(start);
+        let log = events.clone();
+        tracker.add_listener(Duration::from_secs(10), move |e| log.borrow_mut().push((10, e)));
+        let log = events.clone();
+        let long = tracker.add_listener(Duration::from_secs(60), move |e| log.borrow_mut().push((60, e)));
+
+        assert_eq!(tracker.check(secs(5)), Some(secs(10)));
+        assert_eq!(tracker.check(secs(10)), Some(secs(60)));
+        assert_eq!(*events.borrow(), vec![(10, IdleEvent::Idle)]);
+
+        tracker.activity(secs(20));
+        assert_eq!(events.borrow()[1], (10, IdleEvent::Resumed));
+        tracker.remove_listener(long);
+        assert_eq!(tracker.check(secs(25)), Some(secs(30)));
+
+        tracker.set_inhibited(true, secs(26));
+        assert_eq!(tracker.check(secs(100)), None);
+        assert_eq!(events.borrow().len(), 2);
+    }
+}
@@ -1,5 +1,14 @@
 //! Various utilities functions and types
 
+pub mod bell;
+#[cfg(feature = "xcursor_theme")]
+pub mod cursor;
+mod destruction_hooks;
+mod double_buffered;
+pub mod idle;
+pub mod output_mapping;
 mod rectangle;
+pub mod timer;
 
-pub use self::rectangle::Rectangle;
+pub(crate) use self::destruction_hooks::DestructionHooks;
+pub use self::{double_buffered::DoubleBuffered, rectangle::Rectangle};
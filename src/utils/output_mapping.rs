@@ -0,0 +1,295 @@
+//! Mapping between physical displays and logical outputs
+//!
+//! Usually each connected display (a *head*) is exposed to clients as a single output. Some
+//! setups need something else:
+//!
+//! - an ultrawide monitor can be split into several logical outputs, so that clients maximize
+//!   and fullscreen their windows on one half of it,
+//! - several heads can be combined into a single logical output, for video walls or for tiled
+//!   monitors driven through several connectors (see the `TILE` connector property in
+//!   `backend::drm::common::tile`).
+//!
+//! An [`OutputMapping`] describes which part of which logical output each head displays. Your
+//! output layout works with the logical outputs, and when repainting a head you render the
+//! parts of the logical outputs returned by [`OutputMapping::regions_for_head`], each into its
+//! own area of the head.
+//!
+//! ## Integration
+//!
+//! The mapping is only a description, nothing in smithay applies it: the
+//! [`Output`](crate::wayland::output::Output) globals, the drm backend and anvil all still
+//! work with one output per head. Using it in your compositor takes the following steps:
+//!
+//! - create one `Output` global per [`LogicalOutput`], with its [`size`](LogicalOutput::size)
+//!   as mode, instead of one per connector, and place the logical outputs in your layout,
+//! - for tiled monitors, group the connectors by the [`group_id`] of their
+//!   [`connector_tile`] and register each group with [`OutputMapping::add_tiled`], using the
+//!   [`offset`] and [`size`] of each tile,
+//! - when repainting a head, render each region returned by
+//!   [`OutputMapping::regions_for_head`]: draw the contents of the logical output within
+//!   [`Tile::source`], translated and clipped to [`Tile::destination`].
+//!
+//! [`group_id`]: crate::backend::drm::common::tile::TileInfo::group_id
+//! [`connector_tile`]: crate::backend::drm::common::tile::connector_tile
+//! [`offset`]: crate::backend::drm::common::tile::TileInfo::offset
+//! [`size`]: crate::backend::drm::common::tile::TileInfo::size
+//!
+//! ```
+//! # extern crate smithay;
+//! use smithay::utils::output_mapping::{OutputMapping, SplitDirection};
+//!
+//! let mut mapping = OutputMapping::new();
+//! // a 5120x1440 monitor split into two halves
+//! mapping.add_split(&["DP-1-left", "DP-1-right"], "DP-1", (5120, 1440), SplitDirection::Horizontal);
+//! // two monitors side by side forming a single output
+//! mapping.add_tiled("wall", &[("HDMI-1", (0, 0), (1920, 1080)), ("HDMI-2", (1920, 0), (1920, 1080))]);
+//!
+//! assert_eq!(mapping.output("DP-1-right").unwrap().size, (2560, 1440));
+//! assert_eq!(mapping.output("wall").unwrap().size, (3840, 1080));
+//! assert_eq!(mapping.regions_for_head(&"DP-1").len(), 2);
+//! ```
+
+use crate::utils::Rectangle;
+
+/// Direction in which a head is split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitDirection {
+    /// The logical outputs are side by side
+    Horizontal,
+    /// The logical outputs are stacked on top of each other
+    Vertical,
+}
+
+/// A part of a logical output displayed by a head
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tile<H> {
+    /// The head displaying this part
+    pub head: H,
+    /// The displayed part, in the coordinates of the logical output
+    pub source: Rectangle,
+    /// Where it is displayed, in the pixel coordinates of the head
+    pub destination: Rectangle,
+}
+
+/// A logical output, as exposed to clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogicalOutput<H> {
+    /// Name of the output
+    pub name: String,
+    /// Size of the output in pixels
+    pub size: (i32, i32),
+    /// The parts of the output and the heads displaying them
+    pub tiles: Vec<Tile<H>>,
+}
+
+/// Mapping between heads and logical outputs
+///
+/// Heads are identified by any value you like, typically a connector handle or name.
+#[derive(Debug, Clone)]
+pub struct OutputMapping<H> {
+    outputs: Vec<LogicalOutput<H>>,
+}
+
+impl<H> Default for OutputMapping<H> {
+    fn default() -> Self {
+        OutputMapping { outputs: Vec::new() }
+    }
+}
+
+impl<H: Clone + PartialEq> OutputMapping<H> {
+    /// Create an empty mapping
+    pub fn new() -> OutputMapping<H> {
+        OutputMapping::default()
+    }
+
+    /// Expose a whole head as a logical output
+    pub fn add_head(&mut self, name: &str, head: H, size: (i32, i32)) {
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            width: size.0,
+            height: size.1,
+        };
+        self.outputs.push(LogicalOutput {
+            name: name.into(),
+            size,
+            tiles: vec![Tile {
+                head,
+                source: rect,
+                destination: rect,
+            }],
+        });
+    }
+
+    /// Split a head into evenly sized logical outputs, one for each name
+    ///
+    /// If the size of the head is not a multiple of the number of outputs, the last one
+    /// gets the remaining pixels.
+    pub fn add_split(&mut self, names: &[&str], head: H, size: (i32, i32), direction: SplitDirection) {
+        let count = names.len() as i32;
+        if count == 0 {
+            return;
+        }
+        let length = match direction {
+            SplitDirection::Horizontal => size.0,
+            SplitDirection::Vertical => size.1,
+        };
+        let step = length / count;
+        for (i, name) in names.iter().enumerate() {
+            let i = i as i32;
+            let part = if i == count - 1 { length - step * i } else { step };
+            let (destination, part_size) = match direction {
+                SplitDirection::Horizontal => (
+                    Rectangle {
+                        x: step * i,
+                        y: 0,
+                        width: part,
+                        height: size.1,
+                    },
+                    (part, size.1),
+                ),
+                SplitDirection::Vertical => (
+                    Rectangle {
+                        x: 0,
+                        y: step * i,
+                        width: size.0,
+                        height: part,
+                    },
+                    (size.0, part),
+                ),
+            };
+            self.outputs.push(LogicalOutput {
+                name: (*name).into(),
+                size: part_size,
+                tiles: vec![Tile {
+                    head: head.clone(),
+                    source: Rectangle {
+                        x: 0,
+                        y: 0,
+                        width: part_size.0,
+                        height: part_size.1,
+                    },
+                    destination,
+                }],
+            });
+        }
+    }
+
+    /// Combine several heads into a single logical output
+    ///
+    /// Each head is given with the location of its top-left corner in the logical output and
+    /// its size. The logical output is the bounding box of all of them.
+    pub fn add_tiled(&mut self, name: &str, heads: &[(H, (i32, i32), (i32, i32))]) {
+        let tiles: Vec<Tile<H>> = heads
+            .iter()
+            .map(|(head, (x, y), (width, height))| Tile {
+                head: head.clone(),
+                source: Rectangle {
+                    x: *x,
+                    y: *y,
+                    width: *width,
+                    height: *height,
+                },
+                destination: Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: *width,
+                    height: *height,
+                },
+            })
+            .collect();
+        let width = tiles
+            .iter()
+            .map(|t| t.source.x + t.source.width)
+            .max()
+            .unwrap_or(0);
+        let height = tiles
+            .iter()
+            .map(|t| t.source.y + t.source.height)
+            .max()
+            .unwrap_or(0);
+        self.outputs.push(LogicalOutput {
+            name: name.into(),
+            size: (width, height),
+            tiles,
+        });
+    }
+
+    /// Remove the logical outputs displayed by a head, e.g. because it was disconnected
+    ///
+    /// Returns the names of the removed outputs.
+    pub fn remove_head(&mut self, head: &H) -> Vec<String> {
+        let mut removed = Vec::new();
+        self.outputs.retain(|output| {
+            let keep = !output.tiles.iter().any(|t| &t.head == head);
+            if !keep {
+                removed.push(output.name.clone());
+            }
+            keep
+        });
+        removed
+    }
+
+    /// Remove a logical output by name
+    pub fn remove_output(&mut self, name: &str) {
+        self.outputs.retain(|output| output.name != name);
+    }
+
+    /// All the logical outputs
+    pub fn outputs(&self) -> &[LogicalOutput<H>] {
+        &self.outputs
+    }
+
+    /// Access a logical output by name
+    pub fn output(&self, name: &str) -> Option<&LogicalOutput<H>> {
+        self.outputs.iter().find(|output| output.name == name)
+    }
+
+    /// What a head displays
+    ///
+    /// For each part of a logical output displayed by the head, returns the output along with
+    /// the tile describing which part of it to render and where.
+    pub fn regions_for_head(&self, head: &H) -> Vec<(&LogicalOutput<H>, &Tile<H>)> {
+        self.outputs
+            .iter()
+            .flat_map(|output| {
+                output
+                    .tiles
+                    .iter()
+                    .filter(move |t| &t.head == head)
+                    .map(move |t| (output, t))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        let mut mapping = OutputMapping::new();
+        mapping.add_split(&["a", "b", "c"], 1, (1000, 500), SplitDirection::Horizontal);
+        let sizes: Vec<_> = mapping.outputs().iter().map(|o| o.size).collect();
+        assert_eq!(sizes, vec![(333, 500), (333, 500), (334, 500)]);
+        let regions = mapping.regions_for_head(&1);
+        assert_eq!(regions[2].1.destination.x, 666);
+        assert_eq!(regions[2].1.source.x, 0);
+    }
+
+    #[test]
+    fn tiled_and_removal() {
+        let mut mapping = OutputMapping::new();
+        mapping.add_head("single", 0, (800, 600));
+        mapping.add_tiled("wall", &[(1, (0, 0), (1920, 1080)), (2, (0, 1080), (1920, 1080))]);
+        assert_eq!(mapping.output("wall").unwrap().size, (1920, 2160));
+        let regions = mapping.regions_for_head(&2);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].1.source.y, 1080);
+        assert_eq!(regions[0].1.destination.y, 0);
+
+        assert_eq!(mapping.remove_head(&2), vec!["wall".to_string()]);
+        assert_eq!(mapping.outputs().len(), 1);
+    }
+}
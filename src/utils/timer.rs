@@ -0,0 +1,207 @@
+//! Timer and idle callback helpers on top of the calloop event loop
+//!
+//! A [`Scheduler`] is a single calloop timer source, on which any number of one-shot or
+//! repeating callbacks can be scheduled. The callbacks are given mutable access to the shared
+//! data of the event loop, just like the callbacks of other event sources.
+//!
+//! ```no_run
+//! use smithay::reexports::calloop::EventLoop;
+//! use smithay::utils::timer::Scheduler;
+//! use std::time::Duration;
+//!
+//! let event_loop = EventLoop::<u32>::new().unwrap();
+//! let scheduler = Scheduler::new(&event_loop.handle()).unwrap();
+//!
+//! // increment the counter every second
+//! let token = scheduler.repeating(Duration::from_secs(1), |counter| *counter += 1);
+//! // and print it once, in five seconds
+//! scheduler.oneshot(Duration::from_secs(5), |counter| println!("{}", counter));
+//!
+//! // stop incrementing it
+//! token.cancel();
+//! ```
+
+use std::{cell::Cell, fmt, io, rc::Rc, time::Duration};
+
+use calloop::{
+    timer::{Timeout, Timer, TimerHandle},
+    Idle, LoopHandle, Source,
+};
+
+/// What to do with a timer once its callback was invoked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutAction {
+    /// Drop the timer
+    Drop,
+    /// Invoke the callback again after the given duration
+    Reschedule(Duration),
+}
+
+struct Task<Data> {
+    cancelled: Rc<Cell<bool>>,
+    // the pending timeout of the task, to remove it from the timer when cancelled
+    timeout: Rc<Cell<Option<Timeout>>>,
+    callback: Box<dyn FnMut(&mut Data) -> TimeoutAction>,
+}
+
+/// Token of a callback scheduled on a [`Scheduler`], allowing to cancel it
+///
+/// Dropping the token does *not* cancel the callback.
+#[derive(Clone)]
+pub struct TimerToken {
+    cancelled: Rc<Cell<bool>>,
+    remove: Rc<dyn Fn()>,
+}
+
+impl TimerToken {
+    /// Cancel the callback, it will not be invoked anymore
+    ///
+    /// The callback is removed from the timer source right away, and dropped.
+    pub fn cancel(&self) {
+        if !self.cancelled.replace(true) {
+            (self.remove)();
+        }
+    }
+
+    /// Whether the callback was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+}
+
+impl fmt::Debug for TimerToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimerToken")
+            .field("cancelled", &self.cancelled.get())
+            .finish()
+    }
+}
+
+/// A source of timers for the event loop
+///
+/// Cloning the scheduler gives you another handle to the same timer source.
+pub struct Scheduler<Data> {
+    handle: LoopHandle<Data>,
+    timers: TimerHandle<Task<Data>>,
+    source: Rc<Cell<Option<Source<Timer<Task<Data>>>>>>,
+}
+
+impl<Data: 'static> Scheduler<Data> {
+    /// Create a new scheduler and insert its timer source into the event loop
+    pub fn new(handle: &LoopHandle<Data>) -> io::Result<Scheduler<Data>> {
+        let timer = Timer::new()?;
+        let timers = timer.handle();
+        let source = handle
+            .insert_source(timer, |mut task: Task<Data>, timers, data| {
+                task.timeout.set(None);
+                if task.cancelled.get() {
+                    return;
+                }
+                if let TimeoutAction::Reschedule(delay) = (task.callback)(data) {
+                    if !task.cancelled.get() {
+                        let timeout = task.timeout.clone();
+                        timeout.set(Some(timers.add_timeout(delay, task)));
+                    }
+                }
+            })
+            .map_err(|err| err.error)?;
+        Ok(Scheduler {
+            handle: handle.clone(),
+            timers,
+            source: Rc::new(Cell::new(Some(source))),
+        })
+    }
+
+    /// Schedule a callback to be invoked after `delay`
+    ///
+    /// The return value of the callback decides whether it is invoked again.
+    pub fn schedule<F>(&self, delay: Duration, callback: F) -> TimerToken
+    where
+        F: FnMut(&mut Data) -> TimeoutAction + 'static,
+    {
+        let cancelled = Rc::new(Cell::new(false));
+        let timeout = Rc::new(Cell::new(None));
+        timeout.set(Some(self.timers.add_timeout(
+            delay,
+            Task {
+                cancelled: cancelled.clone(),
+                timeout: timeout.clone(),
+                callback: Box::new(callback),
+            },
+        )));
+        let timers = self.timers.clone();
+        TimerToken {
+            cancelled,
+            remove: Rc::new(move || {
+                if let Some(timeout) = timeout.take() {
+                    timers.cancel_timeout(&timeout);
+                }
+            }),
+        }
+    }
+
+    /// Schedule a callback to be invoked once, after `delay`
+    pub fn oneshot<F>(&self, delay: Duration, callback: F) -> TimerToken
+    where
+        F: FnOnce(&mut Data) + 'static,
+    {
+        let mut callback = Some(callback);
+        self.schedule(delay, move |data| {
+            if let Some(callback) = callback.take() {
+                callback(data);
+            }
+            TimeoutAction::Drop
+        })
+    }
+
+    /// Schedule a callback to be invoked every `interval`, until it is cancelled
+    ///
+    /// The interval is measured from the invocation of the callback, if the event loop is
+    /// late the following invocations are shifted accordingly.
+    pub fn repeating<F>(&self, interval: Duration, mut callback: F) -> TimerToken
+    where
+        F: FnMut(&mut Data) + 'static,
+    {
+        self.schedule(interval, move |data| {
+            callback(data);
+            TimeoutAction::Reschedule(interval)
+        })
+    }
+
+    /// Schedule a callback to be invoked once the event loop is idle
+    ///
+    /// The callback is invoked after all pending events of the current dispatch have been
+    /// processed, which makes it suitable to coalesce work triggered by several events, like
+    /// rendering a frame.
+    pub fn idle<F>(&self, callback: F) -> Idle
+    where
+        F: FnOnce(&mut Data) + 'static,
+    {
+        self.handle.insert_idle(callback)
+    }
+
+    /// Remove the timer source from the event loop
+    ///
+    /// All pending callbacks are dropped, for this scheduler and all its clones.
+    pub fn remove(self) {
+        if let Some(source) = self.source.take() {
+            self.handle.remove(source);
+        }
+    }
+}
+
+impl<Data> Clone for Scheduler<Data> {
+    fn clone(&self) -> Scheduler<Data> {
+        Scheduler {
+            handle: self.handle.clone(),
+            timers: self.timers.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+impl<Data> fmt::Debug for Scheduler<Data> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler").finish()
+    }
+}
@@ -0,0 +1,274 @@
+//! Back-pressure handling for clients that stopped processing events
+//!
+//! A client that does not read from its socket (for example because it was suspended or is
+//! stuck in a long computation) will let all events sent to it pile up in the compositor,
+//! growing its memory usage without bounds. This module tracks, for each client, how many
+//! events were sent to it since it last showed signs of life, and classifies clients
+//! exceeding a configurable high-water mark as stalled.
+//!
+//! `wayland-server` does not expose how full the outgoing buffer of a client is, so any request
+//! received from the client is considered as proof that it is processing its events. A
+//! convenient way to probe an idle client is to send it a ping (for example using
+//! [`ShellClient::send_ping`](crate::wayland::shell::xdg::ShellClient::send_ping)) and to
+//! report the pong as activity.
+//!
+//! ## Usage
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::backpressure::{BackpressureConfig, BackpressureMonitor, EventPriority};
+//! # use wayland_server::protocol::wl_callback::WlCallback;
+//!
+//! let mut monitor = BackpressureMonitor::new(BackpressureConfig::default(), None);
+//! # fn wrap(mut monitor: BackpressureMonitor, callback: WlCallback, time: u32) {
+//! // before sending a frame callback
+//! if let Some(client) = callback.as_ref().client() {
+//!     if monitor.event_sent(&client, EventPriority::Low) {
+//!         callback.done(time);
+//!     }
+//! }
+//!
+//! // regularly, for example once per frame
+//! for client in monitor.refresh() {
+//!     /* this client exceeded the timeout and was disconnected or warned about */
+//! }
+//! # }
+//! ```
+//!
+//! When receiving requests from a client, you need to notify the monitor using
+//! [`BackpressureMonitor::client_activity`].
+
+use std::time::{Duration, Instant};
+
+use wayland_server::Client;
+
+/// What to do with clients that stay stalled for longer than the configured timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StallAction {
+    /// Only log a warning
+    Warn,
+    /// Disconnect the client
+    Disconnect,
+}
+
+/// Configuration of the back-pressure handling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackpressureConfig {
+    /// Number of events sent since the last activity of a client after which it is considered stalled
+    pub high_water_mark: usize,
+    /// Duration a client may stay stalled before `action` is taken
+    pub timeout: Duration,
+    /// Action taken once a client reached the timeout
+    pub action: StallAction,
+}
+
+impl Default for BackpressureConfig {
+    fn default() -> Self {
+        BackpressureConfig {
+            high_water_mark: 1024,
+            timeout: Duration::from_secs(10),
+            action: StallAction::Warn,
+        }
+    }
+}
+
+/// Priority of an event sent to a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPriority {
+    /// Events required for correctness, that are always sent (configures, key events, ...)
+    High,
+    /// Events that can be dropped for stalled clients (frame callbacks, pointer motion, ...)
+    Low,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QueueState {
+    unanswered: usize,
+    stalled_since: Option<Instant>,
+    reported: bool,
+}
+
+impl QueueState {
+    fn new() -> QueueState {
+        QueueState {
+            unanswered: 0,
+            stalled_since: None,
+            reported: false,
+        }
+    }
+
+    fn activity(&mut self) {
+        *self = QueueState::new();
+    }
+
+    // returns whether the event should be sent
+    fn event(&mut self, priority: EventPriority, high_water_mark: usize, now: Instant) -> bool {
+        if self.stalled_since.is_some() && priority == EventPriority::Low {
+            return false;
+        }
+        self.unanswered += 1;
+        if self.stalled_since.is_none() && self.unanswered >= high_water_mark {
+            self.stalled_since = Some(now);
+        }
+        true
+    }
+
+    // returns whether the timeout was reached for the first time
+    fn check_timeout(&mut self, timeout: Duration, now: Instant) -> bool {
+        match self.stalled_since {
+            Some(since) if !self.reported && now.duration_since(since) >= timeout => {
+                self.reported = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Tracker of the outgoing events of clients
+pub struct BackpressureMonitor {
+    config: BackpressureConfig,
+    clients: Vec<(Client, QueueState)>,
+    log: ::slog::Logger,
+}
+
+impl BackpressureMonitor {
+    /// Create a new monitor with the given configuration
+    pub fn new<L>(config: BackpressureConfig, logger: L) -> BackpressureMonitor
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        BackpressureMonitor {
+            config,
+            clients: Vec::new(),
+            log: crate::slog_or_fallback(logger).new(o!("smithay_module" => "backpressure")),
+        }
+    }
+
+    /// Access the current configuration
+    pub fn config(&self) -> BackpressureConfig {
+        self.config
+    }
+
+    /// Change the configuration
+    pub fn set_config(&mut self, config: BackpressureConfig) {
+        self.config = config;
+    }
+
+    fn state(&mut self, client: &Client) -> &mut QueueState {
+        let idx = match self.clients.iter().position(|(c, _)| c.equals(client)) {
+            Some(idx) => idx,
+            None => {
+                self.clients.push((client.clone(), QueueState::new()));
+                self.clients.len() - 1
+            }
+        };
+        &mut self.clients[idx].1
+    }
+
+    /// Notify that a request was received from a client
+    ///
+    /// This marks the client as responsive again.
+    pub fn client_activity(&mut self, client: &Client) {
+        let state = self.state(client);
+        let was_stalled = state.stalled_since.is_some();
+        state.activity();
+        if was_stalled {
+            debug!(self.log, "Client is responsive again");
+        }
+    }
+
+    /// Notify that an event is about to be sent to a client
+    ///
+    /// Returns `false` if the event should not be sent, because the client is stalled
+    /// and the event has a low priority.
+    pub fn event_sent(&mut self, client: &Client, priority: EventPriority) -> bool {
+        let high_water_mark = self.config.high_water_mark;
+        let state = self.state(client);
+        let was_stalled = state.stalled_since.is_some();
+        let send = state.event(priority, high_water_mark, Instant::now());
+        let is_stalled = state.stalled_since.is_some();
+        if !was_stalled && is_stalled {
+            debug!(
+                self.log,
+                "Client stopped processing events, dropping low priority events"
+            );
+        }
+        send
+    }
+
+    /// Whether a client is currently considered stalled
+    pub fn is_stalled(&self, client: &Client) -> bool {
+        self.clients
+            .iter()
+            .find(|(c, _)| c.equals(client))
+            .map(|(_, state)| state.stalled_since.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Check for clients that stayed stalled for longer than the timeout
+    ///
+    /// The configured [`StallAction`] is applied to these clients, which are then returned.
+    /// Each client is only reported once per stall. This also forgets about clients that
+    /// disconnected in the meantime.
+    pub fn refresh(&mut self) -> Vec<Client> {
+        self.clients.retain(|(client, _)| client.alive());
+        let now = Instant::now();
+        let timeout = self.config.timeout;
+        let mut timed_out = Vec::new();
+        for (client, state) in &mut self.clients {
+            if state.check_timeout(timeout, now) {
+                timed_out.push(client.clone());
+            }
+        }
+        for client in &timed_out {
+            match self.config.action {
+                StallAction::Warn => {
+                    warn!(self.log, "Client did not process its events for {:?}", timeout);
+                }
+                StallAction::Disconnect => {
+                    warn!(
+                        self.log,
+                        "Client did not process its events for {:?}, disconnecting it", timeout
+                    );
+                    client.kill();
+                }
+            }
+        }
+        timed_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventPriority, QueueState};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn stalls_at_high_water_mark() {
+        let now = Instant::now();
+        let mut state = QueueState::new();
+        assert!(state.event(EventPriority::Low, 2, now));
+        assert!(state.event(EventPriority::Low, 2, now));
+        assert_eq!(state.stalled_since, Some(now));
+        assert!(!state.event(EventPriority::Low, 2, now));
+        assert!(state.event(EventPriority::High, 2, now));
+        state.activity();
+        assert!(state.event(EventPriority::Low, 2, now));
+    }
+
+    #[test]
+    fn reports_timeout_once() {
+        let now = Instant::now();
+        let mut state = QueueState::new();
+        assert!(!state.check_timeout(Duration::from_secs(1), now));
+        state.event(EventPriority::Low, 1, now);
+        assert!(!state.check_timeout(Duration::from_secs(1), now));
+        let later = now + Duration::from_secs(1);
+        assert!(state.check_timeout(Duration::from_secs(1), later));
+        assert!(!state.check_timeout(Duration::from_secs(1), later));
+        state.activity();
+        state.event(EventPriority::Low, 1, later);
+        assert!(state.check_timeout(Duration::from_secs(1), later + Duration::from_secs(2)));
+    }
+}
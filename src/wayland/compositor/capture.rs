@@ -0,0 +1,185 @@
+//! Capture of the future contents of surfaces
+//!
+//! This module allows you to take a screenshot of a surface which is exactly the frame
+//! resulting from a future commit, which is mostly useful for automated testing of client
+//! applications: arm a capture, trigger some action in the client, and get back the contents
+//! the client committed in response once they are presented.
+//!
+//! A capture is armed with [`capture_next_commit`], and targets the next commit of the surface
+//! attaching a new buffer. The contents of the buffer are copied at commit time, as the client
+//! may reuse it as soon as it is released, but the capture only resolves once you notify that
+//! the committed state was presented using [`surface_presented`], typically at the same time as
+//! you send the frame callbacks of the surface.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::{CompositorToken, capture::{capture_next_commit, surface_presented}};
+//! # define_roles!(Roles);
+//! # fn wrap(token: CompositorToken<Roles>, surface: &wayland_server::protocol::wl_surface::WlSurface) {
+//! token.with_surface_data(surface, |attrs| {
+//!     capture_next_commit(attrs, |frame| match frame {
+//!         Ok(frame) => { /* compare frame.pixels to the expected contents */ },
+//!         Err(err) => { /* the surface could not be captured */ },
+//!     })
+//! });
+//! // later, once you presented a frame containing the surface
+//! token.with_surface_data(surface, |attrs| surface_presented(attrs));
+//! # }
+//! ```
+//!
+//! Only shm buffers can be captured this way. To capture whole outputs, see
+//! [`Output::capture_next_frame`](::wayland::output::Output::capture_next_frame), or
+//! [`Output::start_recording`](::wayland::output::Output::start_recording) to receive every
+//! frame for a screencast. Each output frame comes with the regions that changed since the
+//! previous one, so that encoders can skip unchanged frames.
+
+use std::{cell::RefCell, mem, time::Duration};
+
+use super::{BufferAssignment, SurfaceAttributes};
+use crate::{
+    utils::Rectangle,
+    wayland::shm::{copy_buffer_contents, BufferAccessError, BufferData},
+};
+
+/// Contents captured from a surface or an output
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    /// Sequence number of the captured frame
+    ///
+    /// For surfaces, this is the number of commits of the surface since a capture was first
+    /// armed on it, for outputs the number of frames presented on the output.
+    pub sequence: u64,
+    /// Presentation time of the frame, as a duration since the epoch of `CLOCK_MONOTONIC`
+    ///
+    /// Only known for outputs, and only if it was provided to
+    /// [`Output::frame_presented_with_metadata`](::wayland::output::Output::frame_presented_with_metadata).
+    pub presentation_time: Option<Duration>,
+    /// The regions that changed since the previous frame, in the pixel coordinates of the
+    /// captured contents
+    ///
+    /// `None` means that the whole frame must be considered changed. For outputs, the
+    /// previous frame is the previous presented frame, whether it was captured or not.
+    /// The damage of surface commits is not tracked, so it is always `None` for surfaces.
+    pub damage: Option<Vec<Rectangle>>,
+    /// Layout of the captured contents
+    ///
+    /// The offset is always 0.
+    pub data: BufferData,
+    /// The captured contents
+    pub pixels: Vec<u8>,
+}
+
+/// Errors that can occur while capturing a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum CaptureError {
+    /// The committed buffer is not a shm buffer
+    #[error("The committed buffer is not a shm buffer")]
+    UnsupportedBuffer,
+    /// The contents of the buffer could not be accessed
+    #[error("Accessing the contents of the buffer failed")]
+    BadMap,
+    /// The contents of the frame were not provided by the compositor
+    #[error("The contents of the frame are not available")]
+    Unavailable,
+    /// The surface or output was destroyed before the capture resolved
+    #[error("The capture target was destroyed")]
+    Destroyed,
+}
+
+type CaptureCallback = Box<dyn FnOnce(Result<CapturedFrame, CaptureError>)>;
+
+#[derive(Default)]
+struct CaptureState {
+    commits: u64,
+    armed: Vec<CaptureCallback>,
+    committed: Option<(Vec<CaptureCallback>, Result<CapturedFrame, CaptureError>)>,
+}
+
+impl Drop for CaptureState {
+    fn drop(&mut self) {
+        let committed = self.committed.take().map(|(callbacks, _)| callbacks);
+        for callback in self.armed.drain(..).chain(committed.into_iter().flatten()) {
+            callback(Err(CaptureError::Destroyed));
+        }
+    }
+}
+
+/// Arm a capture of the next commit of a surface attaching a new buffer
+///
+/// The callback is invoked with the captured contents once you call [`surface_presented`]
+/// after this commit. If the surface commits another buffer before that, the capture
+/// resolves with the newer contents, as they are the ones being presented.
+///
+/// The callback is invoked while the attributes of the surface are borrowed, so it must
+/// not access them through the [`CompositorToken`](super::CompositorToken).
+pub fn capture_next_commit<F>(attrs: &SurfaceAttributes, callback: F)
+where
+    F: FnOnce(Result<CapturedFrame, CaptureError>) + 'static,
+{
+    attrs
+        .user_data
+        .insert_if_missing(|| RefCell::new(CaptureState::default()));
+    if let Some(state) = attrs.user_data.get::<RefCell<CaptureState>>() {
+        state.borrow_mut().armed.push(Box::new(callback));
+    }
+}
+
+/// Resolve the captures of the last presented commit of a surface
+///
+/// Call this once the current state of the surface was presented. It is a no-op if no
+/// capture is waiting.
+pub fn surface_presented(attrs: &SurfaceAttributes) {
+    let committed = attrs
+        .user_data
+        .get::<RefCell<CaptureState>>()
+        .and_then(|state| state.borrow_mut().committed.take());
+    if let Some((mut callbacks, result)) = committed {
+        // every callback gets its own copy of the frame, the last one takes the original
+        let last = callbacks.pop();
+        for callback in callbacks {
+            callback(result.clone());
+        }
+        if let Some(callback) = last {
+            callback(result);
+        }
+    }
+}
+
+/// Count this commit and copy its buffer for the captures armed on the surface
+pub(crate) fn record_commit(attrs: &SurfaceAttributes) {
+    let state = match attrs.user_data.get::<RefCell<CaptureState>>() {
+        Some(state) => state,
+        None => return,
+    };
+    let mut state = state.borrow_mut();
+    state.commits += 1;
+    let buffer = match attrs.buffer {
+        Some(BufferAssignment::NewBuffer { ref buffer, .. })
+            if !state.armed.is_empty() || state.committed.is_some() =>
+        {
+            buffer
+        }
+        _ => return,
+    };
+    let sequence = state.commits;
+    let result = copy_buffer_contents(buffer)
+        .map(|(data, pixels)| CapturedFrame {
+            sequence,
+            presentation_time: None,
+            damage: None,
+            data,
+            pixels,
+        })
+        .map_err(|err| match err {
+            BufferAccessError::NotManaged => CaptureError::UnsupportedBuffer,
+            BufferAccessError::BadMap => CaptureError::BadMap,
+        });
+    let mut callbacks = mem::take(&mut state.armed);
+    // the previous commit was never presented, the captures waiting for it get
+    // the contents that will actually be presented instead
+    if let Some((older, _)) = state.committed.take() {
+        callbacks.extend(older);
+    }
+    state.committed = Some((callbacks, result));
+}
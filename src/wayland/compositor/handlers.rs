@@ -8,9 +8,29 @@ use wayland_server::{
 use super::{
     tree::{Location, SurfaceData},
     BufferAssignment, CompositorToken, Damage, Rectangle, RectangleKind, RegionAttributes, Role, RoleType,
-    SubsurfaceRole, SurfaceEvent,
+    SubsurfaceRole, SurfaceAttributes, SurfaceEvent,
 };
 
+/// Internal handlers run on the attributes of a surface when it is committed, in order,
+/// before the user implementation is invoked
+///
+/// They are also given whether the surface is an effectively synchronized sub-surface, whose
+/// state must be cached rather than applied.
+const COMMIT_HOOKS: &[fn(&mut SurfaceAttributes, bool)] = &[
+    |attrs, _| super::stats::record_commit(attrs),
+    |attrs, _| super::capture::record_commit(attrs),
+    |attrs, _| crate::wayland::presentation::record_commit(attrs),
+    crate::wayland::viewporter::apply_commit,
+    |attrs, sync| crate::wayland::pointer_constraints::apply_commit(attrs, sync),
+];
+
+/// Internal handlers applying the state cached by the synchronized sub-surfaces of a surface,
+/// once it is committed
+const CACHED_HOOKS: &[fn(&mut SurfaceAttributes)] = &[
+    crate::wayland::viewporter::apply_cached,
+    crate::wayland::pointer_constraints::apply_cached,
+];
+
 /*
  * wl_compositor
  */
@@ -21,7 +41,7 @@ pub(crate) fn implement_compositor<R, Impl>(
     implem: Rc<RefCell<Impl>>,
 ) -> wl_compositor::WlCompositor
 where
-    R: Default + Send + 'static,
+    R: Default + RoleType + Role<SubsurfaceRole> + Send + 'static,
     Impl: FnMut(SurfaceEvent, wl_surface::WlSurface, CompositorToken<R>) + 'static,
 {
     compositor.quick_assign(move |_compositor, request, _| match request {
@@ -61,7 +81,7 @@ impl<R> SurfaceImplem<R> {
 
 impl<R> SurfaceImplem<R>
 where
-    R: 'static,
+    R: RoleType + Role<SubsurfaceRole> + 'static,
 {
     fn receive_surface_request(&mut self, req: wl_surface::Request, surface: wl_surface::WlSurface) {
         match req {
@@ -101,6 +121,20 @@ where
                 SurfaceData::<R>::with_data(&surface, |d| d.input_region = attributes);
             }
             wl_surface::Request::Commit => {
+                let token = CompositorToken::<R>::make();
+                let sync = token.is_sync_subsurface(&surface);
+                SurfaceData::<R>::with_data(&surface, |d| {
+                    for hook in COMMIT_HOOKS {
+                        hook(d, sync);
+                    }
+                });
+                if !sync {
+                    token.with_synchronized_children(&surface, |_, d| {
+                        for hook in CACHED_HOOKS {
+                            hook(d);
+                        }
+                    });
+                }
                 let mut user_impl = self.implem.borrow_mut();
                 trace!(self.log, "Calling user implementation for wl_surface.commit");
                 (&mut *user_impl)(SurfaceEvent::Commit, surface, CompositorToken::make());
@@ -130,7 +164,7 @@ fn implement_surface<R, Impl>(
     implem: Rc<RefCell<Impl>>,
 ) -> wl_surface::WlSurface
 where
-    R: Default + Send + 'static,
+    R: Default + RoleType + Role<SubsurfaceRole> + Send + 'static,
     Impl: FnMut(SurfaceEvent, wl_surface::WlSurface, CompositorToken<R>) + 'static,
 {
     surface.quick_assign({
@@ -67,11 +67,21 @@
 //! This [`CompositorToken`](::wayland::compositor::CompositorToken) also provides access to the metadata associated with the role of the
 //! surfaces. See the documentation of the [`roles`](::wayland::compositor::roles) submodule
 //! for a detailed explanation.
+//!
+//! ### Synchronized sub-surfaces
+//!
+//! The state committed by a sub-surface in synchronized mode must only be applied together
+//! with the state of its parent. Use [`CompositorToken::is_sync_subsurface`] on commit to know
+//! if you need to cache the committed state instead of applying it, and
+//! [`CompositorToken::with_synchronized_children`] to apply the cached state of all synchronized
+//! sub-surfaces once the state of their parent is applied.
 
 use std::{cell::RefCell, rc::Rc, sync::Mutex};
 
+pub mod capture;
 mod handlers;
 pub mod roles;
+pub mod stats;
 mod tree;
 
 pub use self::tree::TraversalAction;
@@ -79,7 +89,10 @@ use self::{
     roles::{Role, RoleType, WrongRole},
     tree::SurfaceData,
 };
-use crate::utils::Rectangle;
+use crate::{
+    utils::{DestructionHooks, Rectangle},
+    wayland::viewporter::Viewport,
+};
 use wayland_server::{
     protocol::{
         wl_buffer, wl_callback, wl_compositor, wl_output, wl_region, wl_subcompositor, wl_surface::WlSurface,
@@ -164,6 +177,11 @@ pub struct SurfaceAttributes {
     /// An example possibility would be to trigger it once the frame
     /// associated with this commit has been displayed on the screen.
     pub frame_callback: Option<wl_callback::WlCallback>,
+    /// Cropping and scaling of the contents of the buffer
+    ///
+    /// It is set by clients using the [`viewporter`](::wayland::viewporter) protocol,
+    /// and defaults to displaying the whole buffer at its own size.
+    pub viewport: Viewport,
     /// User-controlled data
     ///
     /// This is your field to host whatever you need.
@@ -180,6 +198,7 @@ impl Default for SurfaceAttributes {
             input_region: None,
             damage: Damage::Full,
             frame_callback: None,
+            viewport: Viewport::default(),
             user_data: UserDataMap::new(),
         }
     }
@@ -295,6 +314,47 @@ impl<R: 'static> CompositorToken<R> {
     {
         SurfaceData::<R>::with_data(surface, f)
     }
+
+    /// Access typed user data of a surface
+    ///
+    /// The value of type `T` is stored in the [`UserDataMap`] of the surface attributes,
+    /// and initialized with its default value the first time it is accessed. It is dropped
+    /// along with the surface, so you don't need to clean it up yourself.
+    ///
+    /// The same restrictions as [`CompositorToken::with_surface_data`] apply.
+    pub fn with_surface_user_data<T, F, U>(self, surface: &WlSurface, f: F) -> U
+    where
+        T: Default + 'static,
+        F: FnOnce(&mut T) -> U,
+    {
+        self.with_surface_data(surface, |attrs| {
+            attrs.user_data.insert_if_missing(|| RefCell::new(T::default()));
+            let data = attrs.user_data.get::<RefCell<T>>().unwrap();
+            let mut data = data.borrow_mut();
+            f(&mut *data)
+        })
+    }
+
+    /// Register a hook to be invoked when a surface is destroyed
+    ///
+    /// Use it to clean up any state you keep about this surface outside of its
+    /// [`SurfaceAttributes`]. The hook is invoked with the dead surface, which can still
+    /// be compared to other surfaces.
+    pub fn add_destruction_hook<F>(self, surface: &WlSurface, hook: F)
+    where
+        F: FnOnce(&WlSurface) + 'static,
+    {
+        self.with_surface_data(surface, |attrs| {
+            attrs
+                .user_data
+                .insert_if_missing(DestructionHooks::<dyn FnOnce(&WlSurface)>::new);
+            attrs
+                .user_data
+                .get::<DestructionHooks<dyn FnOnce(&WlSurface)>>()
+                .unwrap()
+                .add(Box::new(hook));
+        })
+    }
 }
 
 impl<R> CompositorToken<R>
@@ -380,6 +440,72 @@ where
     pub fn get_children(self, surface: &WlSurface) -> Vec<WlSurface> {
         SurfaceData::<R>::get_children(surface)
     }
+
+    /// Check whether this surface is an effectively synchronized sub-surface
+    ///
+    /// A sub-surface is effectively synchronized if it is in synchronized mode itself, or if
+    /// any of its ancestors is. The state committed by such a sub-surface must not be applied
+    /// directly, but cached until the state of its parent is applied. See
+    /// [`with_synchronized_children`](CompositorToken::with_synchronized_children).
+    ///
+    /// Returns `false` for surfaces that are not sub-surfaces.
+    ///
+    /// If the surface is not managed by the `CompositorGlobal` that provided this token, this
+    /// will panic (having more than one compositor is not supported).
+    pub fn is_sync_subsurface(self, surface: &WlSurface) -> bool {
+        let mut surface = surface.clone();
+        loop {
+            match SurfaceData::<R>::with_role_data::<SubsurfaceRole, _, _>(&surface, |d| d.sync) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(_) => return false,
+            }
+            surface = match SurfaceData::<R>::get_parent(&surface) {
+                Some(parent) => parent,
+                None => return false,
+            };
+        }
+    }
+
+    /// Access the data of the synchronized sub-surfaces of a surface
+    ///
+    /// Once you applied the state committed by a surface, you need to apply the state cached
+    /// by its synchronized sub-surfaces as well. This calls the closure on all descendants of
+    /// this surface that are effectively synchronized, as defined by
+    /// [`is_sync_subsurface`](CompositorToken::is_sync_subsurface), in drawing order: the
+    /// sub-surfaces in synchronized mode, and all the descendants of those. The subtrees of
+    /// the other sub-surfaces are skipped, as they apply their state by themselves.
+    ///
+    /// If the surface is not managed by the `CompositorGlobal` that provided this token, this
+    /// will panic (having more than one compositor is not supported).
+    pub fn with_synchronized_children<F>(self, surface: &WlSurface, mut f: F)
+    where
+        F: FnMut(&WlSurface, &mut SurfaceAttributes),
+    {
+        fn is_sync<R: Role<SubsurfaceRole>>(role: &R) -> bool {
+            Role::<SubsurfaceRole>::data(role)
+                .map(|d| d.sync)
+                .unwrap_or(false)
+        }
+
+        // the value passed down is whether the parent is effectively synchronized, `None` for
+        // the root of the traversal
+        self.with_surface_tree_upward(
+            surface,
+            None,
+            |_, _, role, &parent_sync| match parent_sync {
+                None => TraversalAction::DoChildren(Some(false)),
+                Some(parent_sync) if parent_sync || is_sync(role) => TraversalAction::DoChildren(Some(true)),
+                Some(_) => TraversalAction::SkipChildren,
+            },
+            |surface, attributes, role, &parent_sync| {
+                if parent_sync.map(|parent_sync| parent_sync || is_sync(role)) == Some(true) {
+                    f(surface, attributes);
+                }
+            },
+            |_, _, _, _| true,
+        );
+    }
 }
 
 impl<R: RoleType + 'static> CompositorToken<R> {
@@ -527,6 +653,56 @@ pub enum SurfaceEvent {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::define_roles;
+    use std::os::unix::{io::IntoRawFd, net::UnixStream};
+
+    define_roles!(TestRoles);
+
+    #[test]
+    fn desync_child_of_sync_subsurface() {
+        let mut display = Display::new();
+        let (server, _client) = UnixStream::pair().unwrap();
+        let client = unsafe { display.create_client(server.into_raw_fd(), &mut ()) };
+        let surface = || {
+            let surface = (*client.create_resource::<WlSurface>(4).unwrap()).clone();
+            surface
+                .as_ref()
+                .user_data()
+                .set_threadsafe(SurfaceData::<TestRoles>::new);
+            SurfaceData::<TestRoles>::init(&surface);
+            surface
+        };
+        let set_sync = |surface: &WlSurface, sync: bool| {
+            SurfaceData::<TestRoles>::with_role_data::<SubsurfaceRole, _, _>(surface, |d| d.sync = sync)
+                .unwrap();
+        };
+        let token = CompositorToken::<TestRoles>::make();
+
+        // root <- sync <- nested desync, and root <- desync
+        let root = surface();
+        let sync = surface();
+        let nested = surface();
+        let desync = surface();
+        SurfaceData::<TestRoles>::set_parent(&sync, &root).unwrap();
+        SurfaceData::<TestRoles>::set_parent(&nested, &sync).unwrap();
+        SurfaceData::<TestRoles>::set_parent(&desync, &root).unwrap();
+        set_sync(&sync, true);
+        set_sync(&nested, false);
+        set_sync(&desync, false);
+
+        assert!(token.is_sync_subsurface(&sync));
+        assert!(token.is_sync_subsurface(&nested));
+        assert!(!token.is_sync_subsurface(&desync));
+        assert!(!token.is_sync_subsurface(&root));
+
+        let mut children = Vec::new();
+        token.with_synchronized_children(&root, |surface, _| children.push(surface.clone()));
+        assert_eq!(children, vec![sync.clone(), nested]);
+        // the desynchronized sub-surface applies the state of its own children
+        let mut children = Vec::new();
+        token.with_synchronized_children(&desync, |surface, _| children.push(surface.clone()));
+        assert!(children.is_empty());
+    }
 
     #[test]
     fn region_attributes_empty() {
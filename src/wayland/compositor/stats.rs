@@ -0,0 +1,166 @@
+//! Content update statistics of surfaces
+//!
+//! Every commit of a surface is recorded over a sliding window, allowing you to
+//! retrieve how often a surface is updated, how much of it is damaged each time
+//! and what kind of buffers it uses.
+//!
+//! This information is useful for heuristics like detecting "video-like" surfaces,
+//! which update often with full damage and are good candidates for hardware plane offload,
+//! or throttling surfaces that update often but are not visible.
+//!
+//! Statistics are recorded automatically by the compositor handlers. To retrieve them, use
+//! [`surface_stats`] on the attributes of a surface:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::{CompositorToken, stats::surface_stats};
+//! # define_roles!(Roles);
+//! # fn wrap(token: CompositorToken<Roles>, surface: &wayland_server::protocol::wl_surface::WlSurface) {
+//! let stats = token.with_surface_data(surface, |attrs| surface_stats(attrs));
+//! if stats.map(|s| s.is_video_like()).unwrap_or(false) {
+//!     /* try to put this surface on an overlay plane */
+//! }
+//! # }
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use super::{BufferAssignment, Damage, SurfaceAttributes};
+
+/// Length of the sliding window statistics are computed over
+const WINDOW: Duration = Duration::from_secs(1);
+/// Upper bound of recorded commits, to avoid unbounded growth with misbehaving clients
+const MAX_SAMPLES: usize = 256;
+
+/// Kind of buffer attached to a surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferKind {
+    /// A shared memory buffer
+    Shm,
+    /// Any other buffer (dmabuf, EGL buffer, ...)
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    time: Instant,
+    damage: Option<u64>,
+    buffer: Option<BufferKind>,
+}
+
+/// Recorded commits of a single surface
+#[derive(Debug, Default)]
+pub(crate) struct CommitHistory {
+    samples: VecDeque<Sample>,
+}
+
+impl CommitHistory {
+    fn record(&mut self, sample: Sample) {
+        while self
+            .samples
+            .front()
+            .map(|s| sample.time.duration_since(s.time) > WINDOW)
+            .unwrap_or(false)
+            || self.samples.len() >= MAX_SAMPLES
+        {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn stats(&self, now: Instant) -> SurfaceStats {
+        let samples = self
+            .samples
+            .iter()
+            .filter(|s| now.duration_since(s.time) <= WINDOW)
+            .collect::<Vec<_>>();
+        let damages = samples.iter().filter_map(|s| s.damage).collect::<Vec<_>>();
+        SurfaceStats {
+            commits_per_second: samples.len() as f64 / WINDOW.as_secs_f64(),
+            full_damage_ratio: if samples.is_empty() {
+                0.0
+            } else {
+                samples.iter().filter(|s| s.damage.is_none()).count() as f64 / samples.len() as f64
+            },
+            average_damage_area: if damages.is_empty() {
+                None
+            } else {
+                Some(damages.iter().sum::<u64>() as f64 / damages.len() as f64)
+            },
+            buffer_kind: self.samples.iter().rev().find_map(|s| s.buffer),
+        }
+    }
+}
+
+/// Statistics about the recent updates of a surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceStats {
+    /// Number of commits during the last second
+    pub commits_per_second: f64,
+    /// Fraction of the recent commits that damaged the whole surface
+    pub full_damage_ratio: f64,
+    /// Average damaged area in pixels of the recent commits not damaging the whole surface
+    ///
+    /// Surface and buffer coordinates are not distinguished, so this is only an approximation
+    /// for scaled buffers.
+    pub average_damage_area: Option<f64>,
+    /// Kind of the last buffer attached to the surface
+    pub buffer_kind: Option<BufferKind>,
+}
+
+impl SurfaceStats {
+    /// Whether the surface looks like it is displaying a video or game
+    ///
+    /// This is the case if it was updated at least 20 times during the last second,
+    /// mostly with full damage, using non-shm buffers.
+    pub fn is_video_like(&self) -> bool {
+        self.commits_per_second >= 20.0
+            && self.full_damage_ratio >= 0.8
+            && self.buffer_kind == Some(BufferKind::Other)
+    }
+}
+
+/// Add a sample for this commit to the history of the surface
+pub(crate) fn record_commit(attrs: &SurfaceAttributes) {
+    let damage = match attrs.damage {
+        Damage::Full => None,
+        Damage::Surface(rect) | Damage::Buffer(rect) => {
+            Some(rect.width.max(0) as u64 * rect.height.max(0) as u64)
+        }
+    };
+    let buffer = match attrs.buffer {
+        Some(BufferAssignment::NewBuffer { ref buffer, .. }) => {
+            if crate::wayland::shm::is_shm_buffer(buffer) {
+                Some(BufferKind::Shm)
+            } else {
+                Some(BufferKind::Other)
+            }
+        }
+        _ => None,
+    };
+    attrs
+        .user_data
+        .insert_if_missing(|| RefCell::new(CommitHistory::default()));
+    if let Some(history) = attrs.user_data.get::<RefCell<CommitHistory>>() {
+        history.borrow_mut().record(Sample {
+            time: Instant::now(),
+            damage,
+            buffer,
+        });
+    }
+}
+
+/// Retrieve the update statistics of a surface from its attributes
+///
+/// Returns `None` if the surface was never committed.
+pub fn surface_stats(attrs: &SurfaceAttributes) -> Option<SurfaceStats> {
+    attrs
+        .user_data
+        .get::<RefCell<CommitHistory>>()
+        .map(|history| history.borrow().stats(Instant::now()))
+}
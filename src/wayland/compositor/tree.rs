@@ -1,4 +1,5 @@
 use super::{roles::*, SubsurfaceRole, SurfaceAttributes};
+use crate::utils::DestructionHooks;
 use std::sync::Mutex;
 use wayland_server::protocol::wl_surface::WlSurface;
 
@@ -95,6 +96,17 @@ where
             let mut child_guard = child_mutex.lock().unwrap();
             child_guard.parent = None;
         }
+        // invoke the destruction hooks once the data is unlocked, so that they can access it
+        let hooks = my_data
+            .attributes
+            .user_data
+            .get::<DestructionHooks<dyn FnOnce(&WlSurface)>>()
+            .map(|hooks| hooks.take())
+            .unwrap_or_default();
+        ::std::mem::drop(my_data);
+        for hook in hooks {
+            hook(surface);
+        }
     }
 }
 
@@ -0,0 +1,414 @@
+//! Data control support
+//!
+//! The wlr data control protocol lets privileged clients such as clipboard managers read and
+//! set the selection and the primary selection of a seat, without having its keyboard focus.
+//! Its sources and offers share the selection state of the data devices: selections set
+//! through data control are offered to the focused client, and the control clients are
+//! notified of every new selection, whoever set it.
+
+use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc};
+
+use wayland_protocols::wlr::unstable::data_control::v1::server::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::{self, ZwlrDataControlManagerV1},
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+    zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+};
+use wayland_server::{protocol::wl_data_device_manager::DndAction, Display, Filter, Global, Interface, Main};
+
+use super::{
+    primary_selection::{PrimarySelection, PrimarySource},
+    transfer_allowed, with_source_metadata, DataDeviceData, DataDeviceEvent, DataTransfer, SeatData,
+    Selection, SourceMetadata,
+};
+use crate::wayland::seat::Seat;
+
+/// Events that are generated by the clients of the data control protocol
+pub enum DataControlEvent {
+    /// A client has set the selection of a seat
+    NewSelection(Option<ZwlrDataControlSourceV1>),
+    /// A client has set the primary selection of a seat
+    NewPrimarySelection(Option<ZwlrDataControlSourceV1>),
+}
+
+/// The MIME types offered by a data control source
+pub fn data_control_mime_types(source: &ZwlrDataControlSourceV1) -> Vec<String> {
+    source
+        .as_ref()
+        .user_data()
+        .get::<RefCell<SourceMetadata>>()
+        .map(|meta| meta.borrow().mime_types.clone())
+        .unwrap_or_default()
+}
+
+// advertise a new selection offering the given mime types, `receive` is called whenever the
+// client reads it
+fn offer_selection<F>(
+    device: &ZwlrDataControlDeviceV1,
+    mime_types: Option<Vec<String>>,
+    primary: bool,
+    mut receive: F,
+) where
+    F: FnMut(String, RawFd) + 'static,
+{
+    let mime_types = match mime_types {
+        Some(mime_types) => mime_types,
+        None => {
+            if primary {
+                device.primary_selection(None);
+            } else {
+                device.selection(None);
+            }
+            return;
+        }
+    };
+    let client = match device.as_ref().client() {
+        Some(client) => client,
+        None => return,
+    };
+    let version = device.as_ref().version().min(ZwlrDataControlOfferV1::VERSION);
+    let offer = match client.create_resource::<ZwlrDataControlOfferV1>(version) {
+        Some(offer) => offer,
+        None => return,
+    };
+    offer.quick_assign(move |_offer, req, _| {
+        // selection offers only care about the `receive` event
+        if let zwlr_data_control_offer_v1::Request::Receive { mime_type, fd } = req {
+            receive(mime_type, fd);
+        }
+    });
+    device.data_offer(&offer);
+    for mime_type in mime_types {
+        offer.offer(mime_type);
+    }
+    if primary {
+        device.primary_selection(Some(&offer));
+    } else {
+        device.selection(Some(&offer));
+    }
+}
+
+impl SeatData {
+    fn send_control_selection_to(&self, device: &ZwlrDataControlDeviceV1) {
+        let log = self.log.clone();
+        let filter = self.filter.clone();
+        let reader = device.as_ref().client();
+        let (mime_types, mut send): (_, Box<dyn FnMut(String, RawFd)>) = match self.selection {
+            Selection::Empty => (None, Box::new(|_, _| {})),
+            Selection::Client(ref source) => {
+                let source = source.clone();
+                (
+                    with_source_metadata(&source, |meta| meta.mime_types.clone()).ok(),
+                    Box::new(move |mime_type, fd| {
+                        let valid =
+                            with_source_metadata(&source, |meta| meta.mime_types.contains(&mime_type))
+                                .unwrap_or(false)
+                                && source.as_ref().is_alive();
+                        if valid {
+                            source.send(mime_type, fd);
+                        }
+                        let _ = ::nix::unistd::close(fd);
+                    }),
+                )
+            }
+            Selection::DataControl(ref source) => {
+                let source = source.clone();
+                (
+                    Some(data_control_mime_types(&source)),
+                    Box::new(move |mime_type, fd| {
+                        if source.as_ref().is_alive() && data_control_mime_types(&source).contains(&mime_type)
+                        {
+                            source.send(mime_type, fd);
+                        }
+                        let _ = ::nix::unistd::close(fd);
+                    }),
+                )
+            }
+            Selection::Compositor(ref meta) => {
+                let offered = meta.mime_types.clone();
+                // the callback of the compositor is only known to the data devices
+                let callback = self.known_devices.iter().find_map(|dd| {
+                    dd.as_ref()
+                        .user_data()
+                        .get::<DataDeviceData>()
+                        .map(|data| data.callback.clone())
+                });
+                (
+                    Some(meta.mime_types.clone()),
+                    Box::new(move |mime_type, fd| match callback {
+                        Some(ref callback) if offered.contains(&mime_type) => {
+                            (&mut *callback.borrow_mut())(DataDeviceEvent::SendSelection { mime_type, fd });
+                        }
+                        _ => {
+                            let _ = ::nix::unistd::close(fd);
+                        }
+                    }),
+                )
+            }
+        };
+        offer_selection(device, mime_types, false, move |mime_type, fd| {
+            if transfer_allowed(
+                &filter,
+                DataTransfer::ReadSelection {
+                    client: reader.clone(),
+                    mime_type: mime_type.clone(),
+                },
+            ) {
+                send(mime_type, fd);
+            } else {
+                debug!(log, "Denying a data control receive rejected by the filter.");
+                let _ = ::nix::unistd::close(fd);
+            }
+        });
+    }
+
+    fn send_control_primary_selection_to(&self, device: &ZwlrDataControlDeviceV1) {
+        if device.as_ref().version() < 2 {
+            return;
+        }
+        let log = self.log.clone();
+        let filter = self.filter.clone();
+        let reader = device.as_ref().client();
+        let (mime_types, mut send): (_, Box<dyn FnMut(String, RawFd)>) = match self.primary_selection {
+            PrimarySelection::Empty => (None, Box::new(|_, _| {})),
+            PrimarySelection::Client(ref source) => {
+                let source = source.clone();
+                (
+                    Some(source.mime_types()),
+                    Box::new(move |mime_type, fd| {
+                        if source.alive() && source.mime_types().contains(&mime_type) {
+                            source.send(mime_type, fd);
+                        }
+                        let _ = ::nix::unistd::close(fd);
+                    }),
+                )
+            }
+            PrimarySelection::Compositor(ref mime_types) => {
+                let offered = mime_types.clone();
+                // the callback of the compositor is only known to the primary selection devices
+                let callback = self.primary_devices.first().map(|device| device.callback());
+                (
+                    Some(mime_types.clone()),
+                    Box::new(move |mime_type, fd| match callback {
+                        Some(ref callback) if offered.contains(&mime_type) => {
+                            (&mut *callback.borrow_mut())(super::PrimarySelectionEvent::SendSelection {
+                                mime_type,
+                                fd,
+                            });
+                        }
+                        _ => {
+                            let _ = ::nix::unistd::close(fd);
+                        }
+                    }),
+                )
+            }
+        };
+        offer_selection(device, mime_types, true, move |mime_type, fd| {
+            if transfer_allowed(
+                &filter,
+                DataTransfer::ReadPrimarySelection {
+                    client: reader.clone(),
+                    mime_type: mime_type.clone(),
+                },
+            ) {
+                send(mime_type, fd);
+            } else {
+                debug!(log, "Denying a data control receive rejected by the filter.");
+                let _ = ::nix::unistd::close(fd);
+            }
+        });
+    }
+
+    pub(super) fn send_control_selection(&mut self) {
+        self.control_devices.retain(|device| device.as_ref().is_alive());
+        for device in &self.control_devices {
+            self.send_control_selection_to(device);
+        }
+    }
+
+    pub(super) fn send_control_primary_selection(&mut self) {
+        self.control_devices.retain(|device| device.as_ref().is_alive());
+        for device in &self.control_devices {
+            self.send_control_primary_selection_to(device);
+        }
+    }
+}
+
+/// Initialize the data control global
+///
+/// Clients of this global can read and set the selection and the primary selection of any
+/// seat at any time, so you may want to restrict it to trusted clients, or use
+/// [`set_data_device_filter`](super::set_data_device_filter) which also applies to them.
+///
+/// You can provide a callback to peek into the actions of the clients, see [`DataControlEvent`]
+/// for details. Selections set by the compositor are read through the callbacks given to
+/// [`init_data_device`](super::init_data_device) and
+/// [`init_primary_selection`](super::init_primary_selection).
+pub fn init_data_control<C, L>(
+    display: &mut Display,
+    callback: C,
+    logger: L,
+) -> Global<ZwlrDataControlManagerV1>
+where
+    C: FnMut(DataControlEvent) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "data_control_mgr"));
+    let callback = Rc::new(RefCell::new(callback));
+    // a source can only be set as a selection once
+    let used_sources = Rc::new(RefCell::new(Vec::<ZwlrDataControlSourceV1>::new()));
+
+    display.create_global::<ZwlrDataControlManagerV1, _>(
+        2,
+        Filter::new(
+            move |(manager, _version): (Main<ZwlrDataControlManagerV1>, u32), _, _| {
+                let callback = callback.clone();
+                let used_sources = used_sources.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_manager, req, _| match req {
+                    zwlr_data_control_manager_v1::Request::CreateDataSource { id } => {
+                        id.quick_assign(|source, req, _| match req {
+                            zwlr_data_control_source_v1::Request::Offer { mime_type } => {
+                                if let Some(meta) =
+                                    source.as_ref().user_data().get::<RefCell<SourceMetadata>>()
+                                {
+                                    meta.borrow_mut().mime_types.push(mime_type);
+                                }
+                            }
+                            zwlr_data_control_source_v1::Request::Destroy => {}
+                            _ => unreachable!(),
+                        });
+                        id.as_ref().user_data().set(|| {
+                            RefCell::new(SourceMetadata {
+                                mime_types: Vec::new(),
+                                dnd_action: DndAction::None,
+                            })
+                        });
+                    }
+                    zwlr_data_control_manager_v1::Request::GetDataDevice { id, seat } => {
+                        let seat = match Seat::from_resource(&seat) {
+                            Some(seat) => seat,
+                            None => {
+                                error!(log, "Unmanaged seat given to a data control device.");
+                                id.quick_assign(|_, _, _| {});
+                                return;
+                            }
+                        };
+                        // ensure the seat user_data is ready
+                        seat.user_data()
+                            .insert_if_missing(|| RefCell::new(SeatData::new(log.clone())));
+                        let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+
+                        let callback = callback.clone();
+                        let used_sources = used_sources.clone();
+                        let log = log.clone();
+                        let request_seat = seat.clone();
+                        id.quick_assign(move |device, req, _| match req {
+                            zwlr_data_control_device_v1::Request::SetSelection { source } => {
+                                if mark_used(&device, source.as_ref(), &used_sources) {
+                                    set_selection(&request_seat, &device, source, false, &callback, &log);
+                                }
+                            }
+                            zwlr_data_control_device_v1::Request::SetPrimarySelection { source } => {
+                                if device.as_ref().version() < 2 {
+                                    warn!(log, "Ignoring a primary selection set on a version 1 data control device.");
+                                    return;
+                                }
+                                if mark_used(&device, source.as_ref(), &used_sources) {
+                                    set_selection(&request_seat, &device, source, true, &callback, &log);
+                                }
+                            }
+                            zwlr_data_control_device_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        id.assign_destructor(Filter::new(move |device: ZwlrDataControlDeviceV1, _, _| {
+                            if let Some(seat_data) = seat.user_data().get::<RefCell<SeatData>>() {
+                                seat_data
+                                    .borrow_mut()
+                                    .control_devices
+                                    .retain(|d| d.as_ref().is_alive() && !d.as_ref().equals(device.as_ref()));
+                            }
+                        }));
+
+                        // the current selections are sent right away
+                        let mut seat_data = seat_data.borrow_mut();
+                        seat_data.send_control_selection_to(&id);
+                        seat_data.send_control_primary_selection_to(&id);
+                        seat_data.control_devices.push(id.deref().clone());
+                    }
+                    zwlr_data_control_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+// record a source given to a device, posting `used_source` if it was already given before
+fn mark_used(
+    device: &ZwlrDataControlDeviceV1,
+    source: Option<&ZwlrDataControlSourceV1>,
+    used_sources: &RefCell<Vec<ZwlrDataControlSourceV1>>,
+) -> bool {
+    let source = match source {
+        Some(source) => source,
+        None => return true,
+    };
+    let mut used_sources = used_sources.borrow_mut();
+    used_sources.retain(|used| used.as_ref().is_alive());
+    if used_sources
+        .iter()
+        .any(|used| used.as_ref().equals(source.as_ref()))
+    {
+        device.as_ref().post_error(
+            zwlr_data_control_device_v1::Error::UsedSource as u32,
+            "This source was already used.".into(),
+        );
+        return false;
+    }
+    used_sources.push(source.clone());
+    true
+}
+
+fn set_selection(
+    seat: &Seat,
+    device: &ZwlrDataControlDeviceV1,
+    source: Option<ZwlrDataControlSourceV1>,
+    primary: bool,
+    callback: &Rc<RefCell<impl FnMut(DataControlEvent)>>,
+    log: &::slog::Logger,
+) {
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    let filter = seat_data.borrow().filter.clone();
+    let client = device.as_ref().client();
+    let mime_types = source.as_ref().map(data_control_mime_types).unwrap_or_default();
+    let transfer = if primary {
+        DataTransfer::SetPrimarySelection { client, mime_types }
+    } else {
+        DataTransfer::SetSelection { client, mime_types }
+    };
+    if !transfer_allowed(&filter, transfer) {
+        debug!(
+            log,
+            "denying setting selection through data control rejected by the filter"
+        );
+        return;
+    }
+    if primary {
+        (&mut *callback.borrow_mut())(DataControlEvent::NewPrimarySelection(source.clone()));
+        seat_data.borrow_mut().set_primary_selection(
+            source
+                .map(|source| PrimarySelection::Client(PrimarySource::DataControl(source)))
+                .unwrap_or(PrimarySelection::Empty),
+        );
+    } else {
+        (&mut *callback.borrow_mut())(DataControlEvent::NewSelection(source.clone()));
+        seat_data
+            .borrow_mut()
+            .set_selection(source.map(Selection::DataControl).unwrap_or(Selection::Empty));
+    }
+}
@@ -5,16 +5,22 @@ use wayland_server::{
     Main,
 };
 
+use crate::backend::input::TouchSlot;
 use crate::wayland::{
     compositor::{roles::Role, CompositorToken},
-    seat::{AxisFrame, GrabStartData, PointerGrab, PointerInnerHandle, Seat},
-    Serial,
+    seat::{
+        AxisFrame, GrabStartData, PointerGrab, PointerInnerHandle, Seat, TouchGrab, TouchGrabStartData,
+        TouchInnerHandle,
+    },
+    Serial, SERIAL_COUNTER,
 };
 
 use super::{with_source_metadata, DataDeviceData, DnDIconRole, SeatData};
 
-pub(crate) struct DnDGrab<R> {
-    start_data: GrabStartData,
+// A drag'n'drop started by a client, driven either by the pointer or by a touch point
+// depending on the start data it is created with
+pub(crate) struct DnDGrab<R: Role<DnDIconRole> + 'static, S> {
+    start_data: S,
     data_source: Option<wl_data_source::WlDataSource>,
     current_focus: Option<wl_surface::WlSurface>,
     pending_offers: Vec<wl_data_offer::WlDataOffer>,
@@ -24,18 +30,20 @@ pub(crate) struct DnDGrab<R> {
     callback: Rc<RefCell<dyn FnMut(super::DataDeviceEvent)>>,
     token: CompositorToken<R>,
     seat: Seat,
+    // whether the user dropped, the drag'n'drop is cancelled otherwise
+    dropped: bool,
 }
 
-impl<R: Role<DnDIconRole> + 'static> DnDGrab<R> {
+impl<R: Role<DnDIconRole> + 'static, S> DnDGrab<R, S> {
     pub(crate) fn new(
-        start_data: GrabStartData,
+        start_data: S,
         source: Option<wl_data_source::WlDataSource>,
         origin: wl_surface::WlSurface,
         seat: Seat,
         icon: Option<wl_surface::WlSurface>,
         token: CompositorToken<R>,
         callback: Rc<RefCell<dyn FnMut(super::DataDeviceEvent)>>,
-    ) -> DnDGrab<R> {
+    ) -> DnDGrab<R, S> {
         DnDGrab {
             start_data,
             data_source: source,
@@ -47,43 +55,77 @@ impl<R: Role<DnDIconRole> + 'static> DnDGrab<R> {
             callback,
             token,
             seat,
+            dropped: false,
+        }
+    }
+
+    // whether events are sent to the data devices of this surface
+    fn sends_to(&self, surface: &wl_surface::WlSurface) -> bool {
+        // without data source, the drag'n'drop is restricted to the original client
+        self.data_source.is_some() || self.origin.as_ref().same_client_as(&surface.as_ref())
+    }
+
+    // leave the current focus, and disable the offers made to it
+    fn leave(&mut self, seat_data: &SeatData) {
+        if let Some(surface) = self.current_focus.take() {
+            if self.sends_to(&surface) {
+                for device in &seat_data.known_devices {
+                    if device.as_ref().same_client_as(&surface.as_ref()) {
+                        device.leave();
+                    }
+                }
+            }
+        }
+        self.pending_offers.clear();
+        if let Some(offer_data) = self.offer_data.take() {
+            offer_data.borrow_mut().active = false;
         }
     }
 }
 
-impl<R: Role<DnDIconRole> + 'static> PointerGrab for DnDGrab<R> {
-    fn motion(
+impl<R: Role<DnDIconRole> + 'static, S> Drop for DnDGrab<R, S> {
+    fn drop(&mut self) {
+        let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap();
+        if !self.dropped {
+            // the grab ended before the drop, abandon the drag'n'drop
+            self.leave(&seat_data.borrow());
+            if let Some(ref source) = self.data_source {
+                // cancelled is only sent for failed drag'n'drops since version 3
+                if source.as_ref().version() >= 3 {
+                    source.cancelled();
+                }
+            }
+            (&mut *self.callback.borrow_mut())(super::DataDeviceEvent::DnDDropped);
+        }
+        {
+            // a newer drag'n'drop may already have replaced the icon
+            let mut seat_data = seat_data.borrow_mut();
+            if seat_data.dnd_icon == self.icon {
+                seat_data.dnd_icon = None;
+            }
+        }
+        if let Some(icon) = self.icon.take() {
+            if icon.as_ref().is_alive() {
+                self.token.remove_role::<super::DnDIconRole>(&icon).unwrap();
+            }
+        }
+    }
+}
+
+impl<R: Role<DnDIconRole> + 'static, S> DnDGrab<R, S> {
+    // the dragged data moved, send the appropriate events to the surface under it
+    fn motion_to(
         &mut self,
-        _handle: &mut PointerInnerHandle<'_>,
         location: (f64, f64),
         focus: Option<(wl_surface::WlSurface, (f64, f64))>,
         serial: Serial,
         time: u32,
     ) {
         let (x, y) = location;
-        let seat_data = self
-            .seat
-            .user_data()
-            .get::<RefCell<SeatData>>()
-            .unwrap()
-            .borrow_mut();
+        let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap().borrow();
         if focus.as_ref().map(|&(ref s, _)| s) != self.current_focus.as_ref() {
             // focus changed, we need to make a leave if appropriate
-            if let Some(surface) = self.current_focus.take() {
-                // only leave if there is a data source or we are on the original client
-                if self.data_source.is_some() || self.origin.as_ref().same_client_as(&surface.as_ref()) {
-                    for device in &seat_data.known_devices {
-                        if device.as_ref().same_client_as(&surface.as_ref()) {
-                            device.leave();
-                        }
-                    }
-                    // disable the offers
-                    self.pending_offers.clear();
-                    if let Some(offer_data) = self.offer_data.take() {
-                        offer_data.borrow_mut().active = false;
-                    }
-                }
-            }
+            self.leave(&seat_data);
         }
         if let Some((surface, (sx, sy))) = focus {
             // early return if the surface is no longer valid
@@ -139,7 +181,7 @@ impl<R: Role<DnDIconRole> + 'static> PointerGrab for DnDGrab<R> {
                     self.offer_data = Some(offer_data);
                 } else {
                     // only send if we are on a surface of the same client
-                    if self.origin.as_ref().same_client_as(&surface.as_ref()) {
+                    if self.sends_to(&surface) {
                         for device in &seat_data.known_devices {
                             if device.as_ref().same_client_as(&surface.as_ref()) {
                                 device.enter(serial.into(), &surface, x - sx, y - sy, None);
@@ -150,7 +192,7 @@ impl<R: Role<DnDIconRole> + 'static> PointerGrab for DnDGrab<R> {
                 self.current_focus = Some(surface);
             } else {
                 // make a move
-                if self.data_source.is_some() || self.origin.as_ref().same_client_as(&surface.as_ref()) {
+                if self.sends_to(&surface) {
                     for device in &seat_data.known_devices {
                         if device.as_ref().same_client_as(&surface.as_ref()) {
                             device.motion(time, x - sx, y - sy);
@@ -161,62 +203,71 @@ impl<R: Role<DnDIconRole> + 'static> PointerGrab for DnDGrab<R> {
         }
     }
 
-    fn button(
-        &mut self,
-        handle: &mut PointerInnerHandle<'_>,
-        _button: u32,
-        _state: wl_pointer::ButtonState,
-        serial: Serial,
-        time: u32,
-    ) {
-        if handle.current_pressed().is_empty() {
-            // the user dropped, proceed to the drop
-            let seat_data = self
-                .seat
-                .user_data()
-                .get::<RefCell<SeatData>>()
-                .unwrap()
-                .borrow_mut();
-            let validated = if let Some(ref data) = self.offer_data {
+    // the user released the dragged data, drop it if the target accepted it
+    //
+    // Otherwise the drag'n'drop is cancelled when the grab is dropped.
+    fn release(&mut self) {
+        let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap().borrow();
+        let validated = match (&self.data_source, &self.offer_data) {
+            // the target must have accepted a mime type and an action of a source still alive
+            (Some(source), Some(data)) => {
                 let data = data.borrow();
-                data.accepted && (!data.chosen_action.is_empty())
-            } else {
-                false
-            };
+                source.as_ref().is_alive() && data.accepted && !data.chosen_action.is_empty()
+            }
+            // a drag'n'drop without source is handled by its client
+            (None, _) => self
+                .current_focus
+                .as_ref()
+                .map(|surface| self.sends_to(surface))
+                .unwrap_or(false),
+            _ => false,
+        };
+        if validated {
             if let Some(ref surface) = self.current_focus {
-                if self.data_source.is_some() || self.origin.as_ref().same_client_as(&surface.as_ref()) {
-                    for device in &seat_data.known_devices {
-                        if device.as_ref().same_client_as(surface.as_ref()) {
-                            if validated {
-                                device.drop();
-                            } else {
-                                device.leave();
-                            }
-                        }
+                for device in &seat_data.known_devices {
+                    if device.as_ref().same_client_as(surface.as_ref()) {
+                        device.drop();
                     }
                 }
             }
             if let Some(ref offer_data) = self.offer_data {
-                let mut data = offer_data.borrow_mut();
-                if validated {
-                    data.dropped = true;
-                } else {
-                    data.active = false;
-                }
+                offer_data.borrow_mut().dropped = true;
             }
             if let Some(ref source) = self.data_source {
-                source.dnd_drop_performed();
-                if !validated {
-                    source.cancelled();
+                if source.as_ref().version() >= 3 {
+                    source.dnd_drop_performed();
                 }
             }
+            self.dropped = true;
+            ::std::mem::drop(seat_data);
             (&mut *self.callback.borrow_mut())(super::DataDeviceEvent::DnDDropped);
-            if let Some(icon) = self.icon.take() {
-                if icon.as_ref().is_alive() {
-                    self.token.remove_role::<super::DnDIconRole>(&icon).unwrap();
-                }
-            }
-            // in all cases abandon the drop
+        }
+    }
+}
+
+impl<R: Role<DnDIconRole> + 'static> PointerGrab for DnDGrab<R, GrabStartData> {
+    fn motion(
+        &mut self,
+        _handle: &mut PointerInnerHandle<'_>,
+        location: (f64, f64),
+        focus: Option<(wl_surface::WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        self.motion_to(location, focus, serial, time);
+    }
+
+    fn button(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        _button: u32,
+        _state: wl_pointer::ButtonState,
+        serial: Serial,
+        time: u32,
+    ) {
+        if handle.current_pressed().is_empty() {
+            // the user dropped, proceed to the drop
+            self.release();
             // no more buttons are pressed, release the grab
             handle.unset_grab(serial, time);
         }
@@ -232,6 +283,57 @@ impl<R: Role<DnDIconRole> + 'static> PointerGrab for DnDGrab<R> {
     }
 }
 
+impl<R: Role<DnDIconRole> + 'static> TouchGrab for DnDGrab<R, TouchGrabStartData> {
+    fn down(
+        &mut self,
+        _handle: &mut TouchInnerHandle<'_>,
+        _slot: Option<TouchSlot>,
+        _location: (f64, f64),
+        _focus: Option<(wl_surface::WlSurface, (f64, f64))>,
+        _serial: Serial,
+        _time: u32,
+    ) {
+        // other touch points are ignored during the drag'n'drop
+    }
+
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, slot: Option<TouchSlot>, serial: Serial, time: u32) {
+        if slot != self.start_data.slot {
+            return;
+        }
+        // the user dropped, proceed to the drop
+        self.release();
+        // end the touch point of the client that started the drag'n'drop
+        handle.up(slot, serial, time);
+        handle.frame();
+        handle.unset_grab();
+    }
+
+    fn motion(
+        &mut self,
+        _handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(wl_surface::WlSurface, (f64, f64))>,
+        time: u32,
+    ) {
+        if slot == self.start_data.slot {
+            self.motion_to(location, focus, SERIAL_COUNTER.next_serial(), time);
+        }
+    }
+
+    fn frame(&mut self, _handle: &mut TouchInnerHandle<'_>) {}
+
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        // the drag'n'drop is cancelled as the grab is dropped
+        handle.cancel();
+        handle.unset_grab();
+    }
+
+    fn start_data(&self) -> &TouchGrabStartData {
+        &self.start_data
+    }
+}
+
 struct OfferData {
     active: bool,
     dropped: bool,
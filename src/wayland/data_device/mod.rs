@@ -24,6 +24,14 @@
 //!   allows you to set the contents of the selection for your clients
 //! - the freestanding function [`start_dnd`](::wayland::data_device::start_dnd) allows you to initiate a drag'n'drop event from the compositor
 //!   itself and receive interactions of clients with it via an other dedicated callback.
+//! - the freestanding function [`set_data_device_filter`](::wayland::data_device::set_data_device_filter)
+//!   allows you to deny transfers initiated by clients, for example to prevent background clients
+//!   from reading the clipboard.
+//! - the freestanding function [`init_primary_selection`](::wayland::data_device::init_primary_selection)
+//!   adds support for the primary selection (middle-click paste), which follows the same focus
+//!   as the data devices.
+//! - the freestanding function [`init_data_control`](::wayland::data_device::init_data_control)
+//!   lets clipboard managers read and set the selections of a seat without having its focus.
 //!
 //! The module also defines the `DnDIconRole` that you need to insert into your compositor roles enum, to
 //! represent surfaces that are used as a DnD icon.
@@ -55,6 +63,10 @@
 
 use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc};
 
+use wayland_protocols::wlr::unstable::data_control::v1::server::{
+    zwlr_data_control_device_v1::ZwlrDataControlDeviceV1,
+    zwlr_data_control_source_v1::ZwlrDataControlSourceV1,
+};
 use wayland_server::{
     protocol::{
         wl_data_device,
@@ -70,11 +82,17 @@ use crate::wayland::{
     Serial,
 };
 
+mod data_control;
 mod data_source;
 mod dnd_grab;
+mod primary_selection;
 mod server_dnd_grab;
 
+pub use self::data_control::{data_control_mime_types, init_data_control, DataControlEvent};
 pub use self::data_source::{with_source_metadata, SourceMetadata};
+pub use self::primary_selection::{
+    init_primary_selection, set_primary_selection, PrimarySelectionEvent, PrimarySource,
+};
 pub use self::server_dnd_grab::ServerDndEvent;
 
 /// Events that are generated by interactions of the clients with the data device
@@ -94,7 +112,8 @@ pub enum DataDeviceEvent {
     },
     /// The drag'n'drop action was finished by the user releasing the buttons
     ///
-    /// At this point, any pointer icon should be removed.
+    /// At this point, any pointer icon should be removed. This is also generated if the
+    /// drag'n'drop is cancelled before the drop, for example if its pointer grab is replaced.
     ///
     /// Note that this event will only be genrated for client-initiated drag'n'drop session.
     DnDDropped,
@@ -107,13 +126,73 @@ pub enum DataDeviceEvent {
     },
 }
 
+/// A data transfer initiated by a client, that your filter can allow or deny
+///
+/// See [`set_data_device_filter`].
+#[derive(Clone)]
+pub enum DataTransfer {
+    /// A client wants to set the selection
+    SetSelection {
+        /// The client setting the selection
+        client: Option<Client>,
+        /// The mime types offered by the new selection, empty if it is being cleared
+        mime_types: Vec<String>,
+    },
+    /// A client wants to start a drag'n'drop
+    StartDrag {
+        /// The client starting the drag'n'drop
+        client: Option<Client>,
+        /// The mime types offered by the dragged data, empty if the client did not
+        /// provide a data source
+        mime_types: Vec<String>,
+    },
+    /// A client wants to read the contents of the selection
+    ReadSelection {
+        /// The client reading the selection
+        client: Option<Client>,
+        /// The requested mime type
+        mime_type: String,
+    },
+    /// A client wants to set the primary selection
+    SetPrimarySelection {
+        /// The client setting the primary selection
+        client: Option<Client>,
+        /// The mime types offered by the new primary selection, empty if it is being cleared
+        mime_types: Vec<String>,
+    },
+    /// A client wants to read the contents of the primary selection
+    ReadPrimarySelection {
+        /// The client reading the primary selection
+        client: Option<Client>,
+        /// The requested mime type
+        mime_type: String,
+    },
+}
+
+type TransferFilter = Rc<RefCell<Option<Box<dyn FnMut(&DataTransfer) -> bool>>>>;
+
+fn transfer_allowed(filter: &TransferFilter, transfer: DataTransfer) -> bool {
+    match *filter.borrow_mut() {
+        Some(ref mut filter) => filter(&transfer),
+        None => true,
+    }
+}
+
 /// The role applied to surfaces used as DnD icons
 #[derive(Default)]
-pub struct DnDIconRole;
+pub struct DnDIconRole {
+    /// Location of the top-left corner of the icon relative to the pointer or touch point
+    ///
+    /// It starts at `(0, 0)`, and you should add to it the `delta` of every buffer attached to
+    /// the icon (see [`BufferAssignment`](crate::wayland::compositor::BufferAssignment)), so
+    /// that clients can move their icon relative to the pointer.
+    pub offset: (i32, i32),
+}
 
 enum Selection {
     Empty,
     Client(wl_data_source::WlDataSource),
+    DataControl(ZwlrDataControlSourceV1),
     Compositor(SourceMetadata),
 }
 
@@ -122,17 +201,34 @@ struct SeatData {
     selection: Selection,
     log: ::slog::Logger,
     current_focus: Option<Client>,
+    filter: TransferFilter,
+    primary_devices: Vec<primary_selection::PrimaryDevice>,
+    primary_selection: primary_selection::PrimarySelection,
+    control_devices: Vec<ZwlrDataControlDeviceV1>,
+    // the icon of the drag'n'drop in progress, if any
+    dnd_icon: Option<wl_surface::WlSurface>,
 }
 
 impl SeatData {
     fn set_selection(&mut self, new_selection: Selection) {
+        if let Selection::DataControl(ref old) = self.selection {
+            let replaced = match new_selection {
+                Selection::DataControl(ref new) => !old.as_ref().equals(new.as_ref()),
+                _ => true,
+            };
+            if replaced && old.as_ref().is_alive() {
+                old.cancelled();
+            }
+        }
         self.selection = new_selection;
         self.send_selection();
+        self.send_control_selection();
     }
 
     fn set_focus(&mut self, new_focus: Option<Client>) {
         self.current_focus = new_focus;
         self.send_selection();
+        self.send_primary_selection();
     }
 
     fn send_selection(&mut self) {
@@ -142,10 +238,10 @@ impl SeatData {
         };
         // first sanitize the selection, reseting it to null if the client holding
         // it dropped it
-        let cleanup = if let Selection::Client(ref data_source) = self.selection {
-            !data_source.as_ref().is_alive()
-        } else {
-            false
+        let cleanup = match self.selection {
+            Selection::Client(ref data_source) => !data_source.as_ref().is_alive(),
+            Selection::DataControl(ref data_source) => !data_source.as_ref().is_alive(),
+            _ => false,
         };
         if cleanup {
             self.selection = Selection::Empty;
@@ -163,44 +259,32 @@ impl SeatData {
                 }
             }
             Selection::Client(ref data_source) => {
-                for dd in &self.known_devices {
-                    // skip data devices not belonging to our client
-                    if dd.as_ref().client().map(|c| !c.equals(client)).unwrap_or(true) {
-                        continue;
-                    }
-                    let source = data_source.clone();
-                    let log = self.log.clone();
-                    // create a corresponding data offer
-                    let offer = client
-                        .create_resource::<wl_data_offer::WlDataOffer>(dd.as_ref().version())
-                        .unwrap();
-                    offer.quick_assign(move |_offer, req, _| {
-                        // selection data offers only care about the `receive` event
-                        if let wl_data_offer::Request::Receive { fd, mime_type } = req {
-                            // check if the source and associated mime type is still valid
-                            let valid =
-                                with_source_metadata(&source, |meta| meta.mime_types.contains(&mime_type))
-                                    .unwrap_or(false)
-                                    && source.as_ref().is_alive();
-                            if !valid {
-                                // deny the receive
-                                debug!(log, "Denying a wl_data_offer.receive with invalid source.");
-                            } else {
-                                source.send(mime_type, fd);
-                            }
-                            let _ = ::nix::unistd::close(fd);
+                let (source, sender) = (data_source.clone(), data_source.clone());
+                self.offer_source_selection(
+                    client,
+                    move || {
+                        if source.as_ref().is_alive() {
+                            with_source_metadata(&source, |meta| meta.mime_types.clone()).unwrap_or_default()
+                        } else {
+                            Vec::new()
                         }
-                    });
-                    // advertize the offer to the client
-                    dd.data_offer(&offer);
-                    with_source_metadata(data_source, |meta| {
-                        for mime_type in meta.mime_types.iter().cloned() {
-                            offer.offer(mime_type);
+                    },
+                    move |mime_type, fd| sender.send(mime_type, fd),
+                );
+            }
+            Selection::DataControl(ref data_source) => {
+                let (source, sender) = (data_source.clone(), data_source.clone());
+                self.offer_source_selection(
+                    client,
+                    move || {
+                        if source.as_ref().is_alive() {
+                            data_control_mime_types(&source)
+                        } else {
+                            Vec::new()
                         }
-                    })
-                    .unwrap();
-                    dd.selection(Some(&offer));
-                }
+                    },
+                    move |mime_type, fd| sender.send(mime_type, fd),
+                );
             }
             Selection::Compositor(ref meta) => {
                 for dd in &self.known_devices {
@@ -209,6 +293,7 @@ impl SeatData {
                         continue;
                     }
                     let log = self.log.clone();
+                    let filter = self.filter.clone();
                     let offer_meta = meta.clone();
                     let callback = dd
                         .as_ref()
@@ -221,7 +306,7 @@ impl SeatData {
                     let offer = client
                         .create_resource::<wl_data_offer::WlDataOffer>(dd.as_ref().version())
                         .unwrap();
-                    offer.quick_assign(move |_offer, req, _| {
+                    offer.quick_assign(move |offer, req, _| {
                         // selection data offers only care about the `receive` event
                         if let wl_data_offer::Request::Receive { fd, mime_type } = req {
                             // check if the associated mime type is valid
@@ -229,6 +314,15 @@ impl SeatData {
                                 // deny the receive
                                 debug!(log, "Denying a wl_data_offer.receive with invalid source.");
                                 let _ = ::nix::unistd::close(fd);
+                            } else if !transfer_allowed(
+                                &filter,
+                                DataTransfer::ReadSelection {
+                                    client: offer.as_ref().client(),
+                                    mime_type: mime_type.clone(),
+                                },
+                            ) {
+                                debug!(log, "Denying a wl_data_offer.receive rejected by the filter.");
+                                let _ = ::nix::unistd::close(fd);
                             } else {
                                 (&mut *callback.borrow_mut())(DataDeviceEvent::SendSelection {
                                     mime_type,
@@ -247,6 +341,58 @@ impl SeatData {
             }
         }
     }
+
+    // advertise a selection held by a client source to the data devices of `client`
+    //
+    // `mime_types` gives the types currently offered by the source, none once it is destroyed,
+    // and `send` asks the source to write the selection to a file descriptor
+    fn offer_source_selection<M, S>(&self, client: &Client, mime_types: M, send: S)
+    where
+        M: Fn() -> Vec<String> + Clone + 'static,
+        S: Fn(String, RawFd) + Clone + 'static,
+    {
+        for dd in &self.known_devices {
+            // skip data devices not belonging to our client
+            if dd.as_ref().client().map(|c| !c.equals(client)).unwrap_or(true) {
+                continue;
+            }
+            let offered = mime_types.clone();
+            let send = send.clone();
+            let log = self.log.clone();
+            let filter = self.filter.clone();
+            // create a corresponding data offer
+            let offer = client
+                .create_resource::<wl_data_offer::WlDataOffer>(dd.as_ref().version())
+                .unwrap();
+            offer.quick_assign(move |offer, req, _| {
+                // selection data offers only care about the `receive` event
+                if let wl_data_offer::Request::Receive { fd, mime_type } = req {
+                    // check if the source and associated mime type is still valid
+                    if !offered().contains(&mime_type) {
+                        // deny the receive
+                        debug!(log, "Denying a wl_data_offer.receive with invalid source.");
+                    } else if !transfer_allowed(
+                        &filter,
+                        DataTransfer::ReadSelection {
+                            client: offer.as_ref().client(),
+                            mime_type: mime_type.clone(),
+                        },
+                    ) {
+                        debug!(log, "Denying a wl_data_offer.receive rejected by the filter.");
+                    } else {
+                        send(mime_type, fd);
+                    }
+                    let _ = ::nix::unistd::close(fd);
+                }
+            });
+            // advertize the offer to the client
+            dd.data_offer(&offer);
+            for mime_type in mime_types() {
+                offer.offer(mime_type);
+            }
+            dd.selection(Some(&offer));
+        }
+    }
 }
 
 impl SeatData {
@@ -256,6 +402,11 @@ impl SeatData {
             selection: Selection::Empty,
             log,
             current_focus: None,
+            filter: Rc::new(RefCell::new(None)),
+            primary_devices: Vec::new(),
+            primary_selection: primary_selection::PrimarySelection::Empty,
+            control_devices: Vec::new(),
+            dnd_icon: None,
         }
     }
 }
@@ -336,6 +487,43 @@ pub fn set_data_device_selection(seat: &Seat, mime_types: Vec<String>) {
         }));
 }
 
+/// Set a filter deciding which data transfers initiated by clients are allowed on this seat
+///
+/// The filter is called before a client sets the selection, starts a drag'n'drop or reads the
+/// selection, and the transfer is denied if it returns `false`. This replaces any previously
+/// set filter. Transfers initiated by the compositor itself are not filtered.
+pub fn set_data_device_filter<F>(seat: &Seat, filter: F)
+where
+    F: FnMut(&DataTransfer) -> bool + 'static,
+{
+    // TODO: same question as in set_data_device_focus
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "data_device_mgr")),
+        ))
+    });
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    *seat_data.borrow().filter.borrow_mut() = Some(Box::new(filter));
+}
+
+/// The icon of the drag'n'drop initiated by a client in progress on this seat, if any
+///
+/// The icon should be drawn at the location of the pointer, moved by the
+/// [`offset`](DnDIconRole::offset) of its role. It is no longer returned once the
+/// drag'n'drop ends, with a drop or otherwise.
+pub fn current_dnd_icon(seat: &Seat) -> Option<wl_surface::WlSurface> {
+    seat.user_data()
+        .get::<RefCell<SeatData>>()
+        .and_then(|seat_data| seat_data.borrow().dnd_icon.clone())
+        .filter(|icon| icon.as_ref().is_alive())
+}
+
+fn source_mime_types(source: Option<&wl_data_source::WlDataSource>) -> Vec<String> {
+    source
+        .and_then(|source| with_source_metadata(source, |meta| meta.mime_types.clone()).ok())
+        .unwrap_or_default()
+}
+
 /// Start a drag'n'drop from a ressource controlled by the compositor
 ///
 /// You'll receive events generated by the interaction of clients with your
@@ -443,41 +631,80 @@ where
             icon,
             serial,
         } => {
-            /* TODO: handle the icon */
             let serial = Serial::from(serial);
-            if let Some(pointer) = seat.get_pointer() {
-                if pointer.has_grab(serial) {
-                    if let Some(ref icon) = icon {
-                        if token.give_role::<DnDIconRole>(icon).is_err() {
-                            dd.as_ref().post_error(
-                                wl_data_device::Error::Role as u32,
-                                "Given surface already has an other role".into(),
-                            );
-                            return;
-                        }
-                    }
-                    // The StartDrag is in response to a pointer implicit grab, all is good
-                    (&mut *callback.borrow_mut())(DataDeviceEvent::DnDStarted {
-                        source: source.clone(),
-                        icon: icon.clone(),
-                    });
-                    let start_data = pointer.grab_start_data().unwrap();
-                    pointer.set_grab(
-                        dnd_grab::DnDGrab::new(
-                            start_data,
-                            source,
-                            origin,
-                            seat.clone(),
-                            icon,
-                            token,
-                            callback.clone(),
-                        ),
-                        serial,
+            // the drag'n'drop must be in response to an implicit grab of the pointer or
+            // of a touch point
+            let pointer = seat.get_pointer().filter(|pointer| pointer.has_grab(serial));
+            let touch = seat.get_touch().filter(|touch| touch.has_grab(serial));
+            if pointer.is_none() && touch.is_none() {
+                debug!(log, "denying drag from client without implicit grab");
+                return;
+            }
+            let filter = seat
+                .user_data()
+                .get::<RefCell<SeatData>>()
+                .unwrap()
+                .borrow()
+                .filter
+                .clone();
+            let allowed = transfer_allowed(
+                &filter,
+                DataTransfer::StartDrag {
+                    client: dd.as_ref().client(),
+                    mime_types: source_mime_types(source.as_ref()),
+                },
+            );
+            if !allowed {
+                debug!(log, "denying drag rejected by the filter");
+                return;
+            }
+            if let Some(ref icon) = icon {
+                if token.give_role::<DnDIconRole>(icon).is_err() {
+                    dd.as_ref().post_error(
+                        wl_data_device::Error::Role as u32,
+                        "Given surface already has an other role".into(),
                     );
                     return;
                 }
             }
-            debug!(log, "denying drag from client without implicit grab");
+            seat.user_data()
+                .get::<RefCell<SeatData>>()
+                .unwrap()
+                .borrow_mut()
+                .dnd_icon = icon.clone();
+            (&mut *callback.borrow_mut())(DataDeviceEvent::DnDStarted {
+                source: source.clone(),
+                icon: icon.clone(),
+            });
+            if let Some(pointer) = pointer {
+                let start_data = pointer.grab_start_data().unwrap();
+                pointer.set_grab(
+                    dnd_grab::DnDGrab::new(
+                        start_data,
+                        source,
+                        origin,
+                        seat.clone(),
+                        icon,
+                        token,
+                        callback.clone(),
+                    ),
+                    serial,
+                );
+            } else if let Some(touch) = touch {
+                let start_data = touch.grab_start_data().unwrap();
+                touch.set_grab(
+                    dnd_grab::DnDGrab::new(
+                        start_data,
+                        source,
+                        origin,
+                        seat.clone(),
+                        icon,
+                        token,
+                        callback.clone(),
+                    ),
+                    serial,
+                );
+            }
         }
         Request::SetSelection { source, .. } => {
             if let Some(keyboard) = seat.get_keyboard() {
@@ -489,6 +716,18 @@ where
                     .unwrap_or(false)
                 {
                     let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+                    let filter = seat_data.borrow().filter.clone();
+                    let allowed = transfer_allowed(
+                        &filter,
+                        DataTransfer::SetSelection {
+                            client: dd.as_ref().client(),
+                            mime_types: source_mime_types(source.as_ref()),
+                        },
+                    );
+                    if !allowed {
+                        debug!(log, "denying setting selection rejected by the filter");
+                        return;
+                    }
                     (&mut *callback.borrow_mut())(DataDeviceEvent::NewSelection(source.clone()));
                     // The client has kbd focus, it can set the selection
                     seat_data
@@ -0,0 +1,531 @@
+//! Primary selection support
+//!
+//! The primary selection is the selection of X11 tradition, set whenever the user selects some
+//! text and pasted with a middle click. It lives alongside the regular selection of the data
+//! devices, and follows the same focus.
+
+use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc};
+
+use wayland_protocols::{
+    misc::gtk_primary_selection::server::{
+        gtk_primary_selection_device as gtk_device, gtk_primary_selection_device_manager as gtk_manager,
+        gtk_primary_selection_offer as gtk_offer, gtk_primary_selection_source as gtk_source,
+    },
+    unstable::primary_selection::v1::server::{
+        zwp_primary_selection_device_manager_v1 as zwp_manager,
+        zwp_primary_selection_device_v1 as zwp_device, zwp_primary_selection_offer_v1 as zwp_offer,
+        zwp_primary_selection_source_v1 as zwp_source,
+    },
+    wlr::unstable::data_control::v1::server::zwlr_data_control_source_v1::ZwlrDataControlSourceV1,
+};
+use wayland_server::{
+    protocol::{wl_data_device_manager::DndAction, wl_seat},
+    Client, Display, Filter, Global, Main,
+};
+
+use super::{transfer_allowed, DataTransfer, SeatData, SourceMetadata};
+use crate::wayland::seat::Seat;
+
+/// A primary selection source provided by a client
+#[derive(Clone)]
+pub enum PrimarySource {
+    /// A source of the `zwp_primary_selection_v1` protocol
+    Zwp(zwp_source::ZwpPrimarySelectionSourceV1),
+    /// A source of the older `gtk_primary_selection` protocol
+    Gtk(gtk_source::GtkPrimarySelectionSource),
+    /// A source of the `zwlr_data_control_v1` protocol
+    DataControl(ZwlrDataControlSourceV1),
+}
+
+impl PrimarySource {
+    /// Is the source referred by this handle still alive?
+    pub fn alive(&self) -> bool {
+        match self {
+            PrimarySource::Zwp(s) => s.as_ref().is_alive(),
+            PrimarySource::Gtk(s) => s.as_ref().is_alive(),
+            PrimarySource::DataControl(s) => s.as_ref().is_alive(),
+        }
+    }
+
+    /// Do this handle and the other one actually refer to the same source?
+    pub fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PrimarySource::Zwp(a), PrimarySource::Zwp(b)) => a.as_ref().equals(b.as_ref()),
+            (PrimarySource::Gtk(a), PrimarySource::Gtk(b)) => a.as_ref().equals(b.as_ref()),
+            (PrimarySource::DataControl(a), PrimarySource::DataControl(b)) => a.as_ref().equals(b.as_ref()),
+            _ => false,
+        }
+    }
+
+    /// The MIME types offered by this source
+    pub fn mime_types(&self) -> Vec<String> {
+        let user_data = match self {
+            PrimarySource::Zwp(s) => s.as_ref().user_data(),
+            PrimarySource::Gtk(s) => s.as_ref().user_data(),
+            PrimarySource::DataControl(s) => s.as_ref().user_data(),
+        };
+        user_data
+            .get::<RefCell<SourceMetadata>>()
+            .map(|meta| meta.borrow().mime_types.clone())
+            .unwrap_or_default()
+    }
+
+    /// Ask the client to write the contents of the source in the given MIME type to `fd`
+    ///
+    /// The fd is not closed by this method.
+    pub fn send(&self, mime_type: String, fd: RawFd) {
+        match self {
+            PrimarySource::Zwp(s) => s.send(mime_type, fd),
+            PrimarySource::Gtk(s) => s.send(mime_type, fd),
+            PrimarySource::DataControl(s) => s.send(mime_type, fd),
+        }
+    }
+
+    fn cancelled(&self) {
+        match self {
+            PrimarySource::Zwp(s) => s.cancelled(),
+            PrimarySource::Gtk(s) => s.cancelled(),
+            PrimarySource::DataControl(s) => s.cancelled(),
+        }
+    }
+}
+
+/// Events that are generated by interactions of the clients with the primary selection
+pub enum PrimarySelectionEvent {
+    /// A client has set the primary selection
+    NewSelection(Option<PrimarySource>),
+    /// A client requested to read the server-set primary selection
+    SendSelection {
+        /// the requested mime type
+        mime_type: String,
+        /// the fd to write into
+        fd: RawFd,
+    },
+}
+
+pub(super) enum PrimarySelection {
+    Empty,
+    Client(PrimarySource),
+    Compositor(Vec<String>),
+}
+
+#[derive(Clone)]
+pub(super) enum PrimaryDevice {
+    Zwp(zwp_device::ZwpPrimarySelectionDeviceV1),
+    Gtk(gtk_device::GtkPrimarySelectionDevice),
+}
+
+struct PrimaryDeviceData {
+    callback: Rc<RefCell<dyn FnMut(PrimarySelectionEvent)>>,
+}
+
+impl PrimaryDevice {
+    fn client(&self) -> Option<Client> {
+        match self {
+            PrimaryDevice::Zwp(d) => d.as_ref().client(),
+            PrimaryDevice::Gtk(d) => d.as_ref().client(),
+        }
+    }
+
+    fn alive(&self) -> bool {
+        match self {
+            PrimaryDevice::Zwp(d) => d.as_ref().is_alive(),
+            PrimaryDevice::Gtk(d) => d.as_ref().is_alive(),
+        }
+    }
+
+    fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (PrimaryDevice::Zwp(a), PrimaryDevice::Zwp(b)) => a.as_ref().equals(b.as_ref()),
+            (PrimaryDevice::Gtk(a), PrimaryDevice::Gtk(b)) => a.as_ref().equals(b.as_ref()),
+            _ => false,
+        }
+    }
+
+    pub(super) fn callback(&self) -> Rc<RefCell<dyn FnMut(PrimarySelectionEvent)>> {
+        let user_data = match self {
+            PrimaryDevice::Zwp(d) => d.as_ref().user_data(),
+            PrimaryDevice::Gtk(d) => d.as_ref().user_data(),
+        };
+        user_data.get::<PrimaryDeviceData>().unwrap().callback.clone()
+    }
+
+    fn clear_selection(&self) {
+        match self {
+            PrimaryDevice::Zwp(d) => d.selection(None),
+            PrimaryDevice::Gtk(d) => d.selection(None),
+        }
+    }
+
+    // advertise a new selection offering the given mime types, `receive` is called
+    // whenever the client reads it
+    fn offer_selection<F>(&self, client: &Client, mime_types: &[String], mut receive: F)
+    where
+        F: FnMut(String, RawFd) + 'static,
+    {
+        match self {
+            PrimaryDevice::Zwp(d) => {
+                let offer = client
+                    .create_resource::<zwp_offer::ZwpPrimarySelectionOfferV1>(d.as_ref().version())
+                    .unwrap();
+                offer.quick_assign(move |_offer, req, _| {
+                    // selection offers only care about the `receive` event
+                    if let zwp_offer::Request::Receive { mime_type, fd } = req {
+                        receive(mime_type, fd);
+                    }
+                });
+                d.data_offer(&offer);
+                for mime_type in mime_types.iter().cloned() {
+                    offer.offer(mime_type);
+                }
+                d.selection(Some(&offer));
+            }
+            PrimaryDevice::Gtk(d) => {
+                let offer = client
+                    .create_resource::<gtk_offer::GtkPrimarySelectionOffer>(d.as_ref().version())
+                    .unwrap();
+                offer.quick_assign(move |_offer, req, _| {
+                    // selection offers only care about the `receive` event
+                    if let gtk_offer::Request::Receive { mime_type, fd } = req {
+                        receive(mime_type, fd);
+                    }
+                });
+                d.data_offer(&offer);
+                for mime_type in mime_types.iter().cloned() {
+                    offer.offer(mime_type);
+                }
+                d.selection(Some(&offer));
+            }
+        }
+    }
+}
+
+impl SeatData {
+    pub(super) fn set_primary_selection(&mut self, new_selection: PrimarySelection) {
+        if let PrimarySelection::Client(ref old) = self.primary_selection {
+            let replaced = match new_selection {
+                PrimarySelection::Client(ref new) => !old.equals(new),
+                _ => true,
+            };
+            if replaced {
+                old.cancelled();
+            }
+        }
+        self.primary_selection = new_selection;
+        self.send_primary_selection();
+        self.send_control_primary_selection();
+    }
+
+    pub(super) fn send_primary_selection(&mut self) {
+        let client = match self.current_focus.as_ref() {
+            Some(c) => c,
+            None => return,
+        };
+        // first sanitize the selection, reseting it to null if the client holding
+        // it dropped it
+        let cleanup = if let PrimarySelection::Client(ref source) = self.primary_selection {
+            !source.alive()
+        } else {
+            false
+        };
+        if cleanup {
+            self.primary_selection = PrimarySelection::Empty;
+        }
+        for device in &self.primary_devices {
+            // skip devices not belonging to our client
+            if device.client().map(|c| !c.equals(client)).unwrap_or(true) {
+                continue;
+            }
+            let log = self.log.clone();
+            let filter = self.filter.clone();
+            let reader = client.clone();
+            match self.primary_selection {
+                PrimarySelection::Empty => device.clear_selection(),
+                PrimarySelection::Client(ref source) => {
+                    let source = source.clone();
+                    let mime_types = source.mime_types();
+                    device.offer_selection(client, &mime_types, move |mime_type, fd| {
+                        // check if the source and associated mime type is still valid
+                        if !source.alive() || !source.mime_types().contains(&mime_type) {
+                            debug!(log, "Denying a primary selection receive with invalid source.");
+                        } else if !transfer_allowed(
+                            &filter,
+                            DataTransfer::ReadPrimarySelection {
+                                client: Some(reader.clone()),
+                                mime_type: mime_type.clone(),
+                            },
+                        ) {
+                            debug!(log, "Denying a primary selection receive rejected by the filter.");
+                        } else {
+                            source.send(mime_type, fd);
+                        }
+                        let _ = ::nix::unistd::close(fd);
+                    });
+                }
+                PrimarySelection::Compositor(ref mime_types) => {
+                    let offered = mime_types.clone();
+                    let callback = device.callback();
+                    device.offer_selection(client, mime_types, move |mime_type, fd| {
+                        if !offered.contains(&mime_type) {
+                            debug!(log, "Denying a primary selection receive with invalid source.");
+                            let _ = ::nix::unistd::close(fd);
+                        } else if !transfer_allowed(
+                            &filter,
+                            DataTransfer::ReadPrimarySelection {
+                                client: Some(reader.clone()),
+                                mime_type: mime_type.clone(),
+                            },
+                        ) {
+                            debug!(log, "Denying a primary selection receive rejected by the filter.");
+                            let _ = ::nix::unistd::close(fd);
+                        } else {
+                            (&mut *callback.borrow_mut())(PrimarySelectionEvent::SendSelection {
+                                mime_type,
+                                fd,
+                            });
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Initialize the primary selection globals
+///
+/// This creates the globals of both the `zwp_primary_selection_v1` protocol and its
+/// predecessor `gtk_primary_selection`, which is still used by some toolkits. The selection
+/// is shared between them, and follows the focus set with
+/// [`set_data_device_focus`](::wayland::data_device::set_data_device_focus).
+///
+/// You can provide a callback to peek into the actions of your clients over the primary selection,
+/// see [`PrimarySelectionEvent`] for details.
+pub fn init_primary_selection<C, L>(
+    display: &mut Display,
+    callback: C,
+    logger: L,
+) -> (
+    Global<zwp_manager::ZwpPrimarySelectionDeviceManagerV1>,
+    Global<gtk_manager::GtkPrimarySelectionDeviceManager>,
+)
+where
+    C: FnMut(PrimarySelectionEvent) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "primary_selection_mgr"));
+    let callback: Rc<RefCell<dyn FnMut(PrimarySelectionEvent)>> = Rc::new(RefCell::new(callback));
+
+    let zwp_global = display.create_global::<zwp_manager::ZwpPrimarySelectionDeviceManagerV1, _>(1, {
+        let callback = callback.clone();
+        let log = log.clone();
+        Filter::new(
+            move |(manager, _version): (Main<zwp_manager::ZwpPrimarySelectionDeviceManagerV1>, u32), _, _| {
+                let callback = callback.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_manager, req, _| match req {
+                    zwp_manager::Request::CreateSource { id } => {
+                        id.quick_assign(|source, req, _| {
+                            if let zwp_source::Request::Offer { mime_type } = req {
+                                if let Some(meta) =
+                                    source.as_ref().user_data().get::<RefCell<SourceMetadata>>()
+                                {
+                                    meta.borrow_mut().mime_types.push(mime_type);
+                                }
+                            }
+                        });
+                        id.as_ref().user_data().set(new_source_metadata);
+                    }
+                    zwp_manager::Request::GetDevice { id, seat } => {
+                        let seat = match register_device(
+                            PrimaryDevice::Zwp(id.deref().clone()),
+                            &seat,
+                            &callback,
+                            &log,
+                        ) {
+                            Some(seat) => seat,
+                            None => {
+                                id.quick_assign(|_, _, _| {});
+                                return;
+                            }
+                        };
+                        let log = log.clone();
+                        let destructor_seat = seat.clone();
+                        id.quick_assign(move |device, req, _| {
+                            if let zwp_device::Request::SetSelection { source, .. } = req {
+                                set_selection(
+                                    &seat,
+                                    &PrimaryDevice::Zwp(device.deref().clone()),
+                                    source.map(PrimarySource::Zwp),
+                                    &log,
+                                );
+                            }
+                        });
+                        id.assign_destructor(Filter::new(
+                            move |device: zwp_device::ZwpPrimarySelectionDeviceV1, _, _| {
+                                forget_device(&destructor_seat, &PrimaryDevice::Zwp(device))
+                            },
+                        ));
+                    }
+                    zwp_manager::Request::Destroy => {}
+                    _ => unreachable!(),
+                });
+            },
+        )
+    });
+
+    let gtk_global = display.create_global::<gtk_manager::GtkPrimarySelectionDeviceManager, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<gtk_manager::GtkPrimarySelectionDeviceManager>, u32), _, _| {
+                let callback = callback.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_manager, req, _| match req {
+                    gtk_manager::Request::CreateSource { id } => {
+                        id.quick_assign(|source, req, _| {
+                            if let gtk_source::Request::Offer { mime_type } = req {
+                                if let Some(meta) =
+                                    source.as_ref().user_data().get::<RefCell<SourceMetadata>>()
+                                {
+                                    meta.borrow_mut().mime_types.push(mime_type);
+                                }
+                            }
+                        });
+                        id.as_ref().user_data().set(new_source_metadata);
+                    }
+                    gtk_manager::Request::GetDevice { id, seat } => {
+                        let seat = match register_device(
+                            PrimaryDevice::Gtk(id.deref().clone()),
+                            &seat,
+                            &callback,
+                            &log,
+                        ) {
+                            Some(seat) => seat,
+                            None => {
+                                id.quick_assign(|_, _, _| {});
+                                return;
+                            }
+                        };
+                        let log = log.clone();
+                        let destructor_seat = seat.clone();
+                        id.quick_assign(move |device, req, _| {
+                            if let gtk_device::Request::SetSelection { source, .. } = req {
+                                set_selection(
+                                    &seat,
+                                    &PrimaryDevice::Gtk(device.deref().clone()),
+                                    source.map(PrimarySource::Gtk),
+                                    &log,
+                                );
+                            }
+                        });
+                        id.assign_destructor(Filter::new(
+                            move |device: gtk_device::GtkPrimarySelectionDevice, _, _| {
+                                forget_device(&destructor_seat, &PrimaryDevice::Gtk(device))
+                            },
+                        ));
+                    }
+                    gtk_manager::Request::Destroy => {}
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    );
+
+    (zwp_global, gtk_global)
+}
+
+/// Set a compositor-provided primary selection for this seat
+///
+/// You need to provide the available mime types for this selection.
+///
+/// Whenever a client requests to read the selection, your callback will
+/// receive a [`PrimarySelectionEvent::SendSelection`] event.
+pub fn set_primary_selection(seat: &Seat, mime_types: Vec<String>) {
+    // TODO: same question as in set_data_device_focus
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "data_device_mgr")),
+        ))
+    });
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    seat_data
+        .borrow_mut()
+        .set_primary_selection(PrimarySelection::Compositor(mime_types));
+}
+
+fn new_source_metadata() -> RefCell<SourceMetadata> {
+    RefCell::new(SourceMetadata {
+        mime_types: Vec::new(),
+        dnd_action: DndAction::None,
+    })
+}
+
+fn register_device(
+    device: PrimaryDevice,
+    seat: &wl_seat::WlSeat,
+    callback: &Rc<RefCell<dyn FnMut(PrimarySelectionEvent)>>,
+    log: &::slog::Logger,
+) -> Option<Seat> {
+    let seat = match Seat::from_resource(seat) {
+        Some(seat) => seat,
+        None => {
+            error!(log, "Unmanaged seat given to a primary selection device.");
+            return None;
+        }
+    };
+    let data = PrimaryDeviceData {
+        callback: callback.clone(),
+    };
+    match device {
+        PrimaryDevice::Zwp(ref d) => d.as_ref().user_data().set(|| data),
+        PrimaryDevice::Gtk(ref d) => d.as_ref().user_data().set(|| data),
+    }
+    // ensure the seat user_data is ready
+    seat.user_data()
+        .insert_if_missing(|| RefCell::new(SeatData::new(log.clone())));
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    seat_data.borrow_mut().primary_devices.push(device);
+    Some(seat)
+}
+
+fn forget_device(seat: &Seat, device: &PrimaryDevice) {
+    if let Some(seat_data) = seat.user_data().get::<RefCell<SeatData>>() {
+        seat_data
+            .borrow_mut()
+            .primary_devices
+            .retain(|d| d.alive() && !d.equals(device));
+    }
+}
+
+fn set_selection(seat: &Seat, device: &PrimaryDevice, source: Option<PrimarySource>, log: &::slog::Logger) {
+    let client = device.client();
+    // only the client with keyboard focus can set the selection
+    let focused = match (seat.get_keyboard(), client.as_ref()) {
+        (Some(keyboard), Some(client)) => keyboard.has_focus(client),
+        _ => false,
+    };
+    if !focused {
+        debug!(log, "denying setting primary selection by a non-focused client");
+        return;
+    }
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    let filter = seat_data.borrow().filter.clone();
+    let allowed = transfer_allowed(
+        &filter,
+        DataTransfer::SetPrimarySelection {
+            client,
+            mime_types: source.as_ref().map(PrimarySource::mime_types).unwrap_or_default(),
+        },
+    );
+    if !allowed {
+        debug!(log, "denying setting primary selection rejected by the filter");
+        return;
+    }
+    let callback = device.callback();
+    (&mut *callback.borrow_mut())(PrimarySelectionEvent::NewSelection(source.clone()));
+    // The client has kbd focus, it can set the selection
+    seat_data.borrow_mut().set_primary_selection(
+        source
+            .map(PrimarySelection::Client)
+            .unwrap_or(PrimarySelection::Empty),
+    );
+}
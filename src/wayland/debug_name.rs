@@ -0,0 +1,159 @@
+//! Human-readable names of clients, surfaces and outputs
+//!
+//! Protocol objects are only identified by their numeric ids, which makes logs hard to read
+//! once several clients are connected. This module lets the compositor attach names to
+//! clients, surfaces and outputs, and builds descriptions of them to use in logs and debugging
+//! tools.
+//!
+//! Clients also name themselves through the standard description requests: the app id of
+//! their toplevels names the client, and the title of a toplevel names its surface. Names
+//! set by the compositor always take precedence over these.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::CompositorToken;
+//! use smithay::wayland::debug_name::{describe_surface, set_client_name};
+//! # define_roles!(Roles);
+//! # fn wrap(token: CompositorToken<Roles>, client: wayland_server::Client, surface: wayland_server::protocol::wl_surface::WlSurface) {
+//!
+//! // for example a client spawned by the compositor
+//! set_client_name(&client, Some("panel".into()));
+//! // prints something like `wl_surface@12 "Clock" of "panel"`
+//! println!("{}", describe_surface(token, &surface));
+//! # }
+//! ```
+
+use std::{cell::RefCell, sync::Mutex};
+
+use wayland_server::{protocol::wl_surface::WlSurface, Client};
+
+use crate::wayland::{compositor::CompositorToken, output::Output};
+
+#[derive(Debug, Default, Clone)]
+struct DebugName {
+    name: Option<String>,
+    // whether the name was set by the compositor rather than guessed from the client requests
+    forced: bool,
+}
+
+impl DebugName {
+    fn set(&mut self, name: Option<String>) {
+        self.forced = name.is_some();
+        self.name = name;
+    }
+
+    fn suggest(&mut self, name: &str) {
+        if !self.forced && !name.is_empty() {
+            self.name = Some(name.into());
+        }
+    }
+}
+
+fn with_client_name<T, F: FnOnce(&mut DebugName) -> T>(client: &Client, f: F) -> T {
+    let map = client.data_map();
+    map.insert_if_missing(|| RefCell::new(DebugName::default()));
+    f(&mut map.get::<RefCell<DebugName>>().unwrap().borrow_mut())
+}
+
+/// Set the name of a client, or remove it with `None`
+pub fn set_client_name(client: &Client, name: Option<String>) {
+    with_client_name(client, |data| data.set(name));
+}
+
+/// The name of a client, if it has one
+pub fn client_name(client: &Client) -> Option<String> {
+    with_client_name(client, |data| data.name.clone())
+}
+
+pub(crate) fn suggest_client_name(client: &Client, name: &str) {
+    with_client_name(client, |data| data.suggest(name));
+}
+
+/// Set the name of a surface, or remove it with `None`
+pub fn set_surface_name<R: 'static>(token: CompositorToken<R>, surface: &WlSurface, name: Option<String>) {
+    token.with_surface_user_data(surface, |data: &mut DebugName| data.set(name));
+}
+
+/// The name of a surface, if it has one
+pub fn surface_name<R: 'static>(token: CompositorToken<R>, surface: &WlSurface) -> Option<String> {
+    if !surface.as_ref().is_alive() {
+        return None;
+    }
+    token.with_surface_user_data(surface, |data: &mut DebugName| data.name.clone())
+}
+
+pub(crate) fn suggest_surface_name<R: 'static>(token: CompositorToken<R>, surface: &WlSurface, name: &str) {
+    token.with_surface_user_data(surface, |data: &mut DebugName| data.suggest(name));
+}
+
+/// Set the name of an output, or remove it with `None`
+///
+/// Without a name, outputs are described by their [`name`](Output::name).
+pub fn set_output_name(output: &Output, name: Option<String>) {
+    output
+        .user_data()
+        .insert_if_missing_threadsafe(|| Mutex::new(DebugName::default()));
+    output
+        .user_data()
+        .get::<Mutex<DebugName>>()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .set(name);
+}
+
+/// The name of an output, if it was given one
+pub fn output_name(output: &Output) -> Option<String> {
+    output
+        .user_data()
+        .get::<Mutex<DebugName>>()
+        .and_then(|data| data.lock().unwrap().name.clone())
+}
+
+/// Describe a client, using its name if it has one
+pub fn describe_client(client: &Client) -> String {
+    match client_name(client) {
+        Some(name) => format!("{:?}", name),
+        None => "unnamed client".into(),
+    }
+}
+
+/// Describe a surface, using its name and the name of its client if they have one
+pub fn describe_surface<R: 'static>(token: CompositorToken<R>, surface: &WlSurface) -> String {
+    let mut description = format!("wl_surface@{}", surface.as_ref().id());
+    if let Some(name) = surface_name(token, surface) {
+        description.push_str(&format!(" {:?}", name));
+    }
+    if let Some(client) = surface.as_ref().client() {
+        description.push_str(&format!(" of {}", describe_client(&client)));
+    }
+    description
+}
+
+/// Describe an output, using its name if it was given one
+pub fn describe_output(output: &Output) -> String {
+    match output_name(output) {
+        Some(name) => format!("{:?} ({})", name, output.name()),
+        None => output.name(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DebugName;
+
+    #[test]
+    fn forced_names_take_precedence() {
+        let mut name = DebugName::default();
+        name.suggest("org.example.App");
+        assert_eq!(name.name.as_deref(), Some("org.example.App"));
+        name.set(Some("panel".into()));
+        name.suggest("org.example.Other");
+        assert_eq!(name.name.as_deref(), Some("panel"));
+        // once removed, the client can name itself again
+        name.set(None);
+        name.suggest("org.example.Other");
+        assert_eq!(name.name.as_deref(), Some("org.example.Other"));
+    }
+}
@@ -14,13 +14,22 @@
 //! - an implementation of the `DmabufHandler` trait
 //!
 //! The list of supported format is just a `Vec<Format>`, where you will enter all the (format, modifier)
-//! couples you support.
+//! couples you support. If you render with EGL, [`EGLDisplay::dmabuf_formats`] lists the couples it can
+//! import. Add a couple with the [`DRM_FORMAT_MOD_INVALID`] modifier for the formats clients can use with
+//! an implicit modifier, the buffers using any other couple are refused.
 //!
 //! The implementation of the `DmabufHandler` trait will be called whenever a client has finished setting up
 //! a dma buffer. You will be handled the full details of the client's submission as a `BufferInfo` struct,
 //! and you need to validate it and maybe import it into your renderer. The `BufferData` associated type
 //! allows you to store any metadata or handle to the resource you need into the created `wl_buffer`,
-//! user data, to then retrieve it when it is attached to a surface to re-identify the dmabuf.
+//! user data, to then retrieve it when it is attached to a surface to re-identify the dmabuf. With EGL,
+//! [`EGLDisplay::create_image_from_dmabuf`] imports the buffer for rendering.
+//!
+//! Only the version 3 of the protocol is supported, so clients cannot be sent per-surface format
+//! feedback.
+//!
+//! [`EGLDisplay::dmabuf_formats`]: ::backend::egl::display::EGLDisplay::dmabuf_formats
+//! [`EGLDisplay::create_image_from_dmabuf`]: ::backend::egl::display::EGLDisplay::create_image_from_dmabuf
 //!
 //! ```
 //! # extern crate wayland_server;
@@ -75,6 +84,8 @@ use wayland_protocols::unstable::linux_dmabuf::v1::server::{
 };
 use wayland_server::{protocol::wl_buffer, Display, Filter, Global, Main};
 
+pub use crate::backend::drm::common::formats::DRM_FORMAT_MOD_INVALID;
+
 /// Representation of a Dmabuf format, as advertized to the client
 pub struct Format {
     /// The format identifier.
@@ -246,9 +257,13 @@ where
                     }
                 });
 
-                // send the supported formats
+                // send the supported formats, each only once for clients not supporting modifiers
+                let mut sent = Vec::new();
                 for f in &*formats {
-                    dmabuf.format(f.format.as_raw());
+                    if !sent.contains(&f.format.as_raw()) {
+                        sent.push(f.format.as_raw());
+                        dmabuf.format(f.format.as_raw());
+                    }
                     if version >= 3 {
                         dmabuf.modifier(f.format.as_raw(), (f.modifier >> 32) as u32, f.modifier as u32);
                     }
@@ -420,13 +435,31 @@ fn buffer_basic_checks(
     height: i32,
 ) -> bool {
     // protocol_checks:
-    // This must be a known format
-    let format = match formats.iter().find(|f| f.format.as_raw() == format) {
+    // All planes must use the same modifier
+    let modifier = pending_planes
+        .first()
+        .map(|plane| plane.modifier)
+        .unwrap_or(DRM_FORMAT_MOD_INVALID);
+    if pending_planes.iter().any(|plane| plane.modifier != modifier) {
+        params.as_ref().post_error(
+            ParamError::InvalidFormat as u32,
+            "All planes must use the same modifier.".into(),
+        );
+        return false;
+    }
+    // This must be a known format, with a known modifier
+    let format = match formats
+        .iter()
+        .find(|f| f.format.as_raw() == format && f.modifier == modifier)
+    {
         Some(f) => f,
         None => {
             params.as_ref().post_error(
                 ParamError::InvalidFormat as u32,
-                format!("Format {:x} is not supported.", format),
+                format!(
+                    "Format {:x} with modifier {:x} is not supported.",
+                    format, modifier
+                ),
             );
             return false;
         }
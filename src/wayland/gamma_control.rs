@@ -0,0 +1,313 @@
+//! Handler for the wlr gamma control protocol
+//!
+//! This protocol lets clients such as redshift or gammastep set the gamma tables of outputs,
+//! to change their color temperature or brightness. A single client at a time can control the
+//! gamma of an output.
+//!
+//! The handler parses the tables sent by the clients, and forwards them to your implementation
+//! as [`GammaControlRequest`]s. With the DRM backend, the tables map directly to the gamma
+//! tables of the crtc driving the output: see the `backend::drm::common::gamma` module.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::gamma_control::{init_gamma_control_manager, GammaControlRequest};
+//! # let mut display = wayland_server::Display::new();
+//! init_gamma_control_manager(
+//!     &mut display,
+//!     |request| match request {
+//!         GammaControlRequest::New { control } => {
+//!             /* lookup the gamma size of the crtc driving control.output() */
+//!             control.set_gamma_size(256);
+//!         }
+//!         GammaControlRequest::SetGamma { control, ramps } => {
+//!             /* apply the ramps, or call control.fail() if they could not be */
+//!         }
+//!         GammaControlRequest::Restore { output } => {
+//!             /* restore the original gamma tables of the output */
+//!         }
+//!     },
+//!     None, /* insert a logger here */
+//! );
+//! ```
+
+use std::{cell::Cell, cell::RefCell, os::unix::io::RawFd, rc::Rc};
+
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, OFlag},
+    unistd,
+};
+use wayland_protocols::wlr::unstable::gamma_control::v1::server::{
+    zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+use wayland_server::{protocol::wl_output::WlOutput, Display, Filter, Global, Main};
+
+/// Gamma tables of the three color channels of an output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GammaRamps {
+    /// Table of the red channel
+    pub red: Vec<u16>,
+    /// Table of the green channel
+    pub green: Vec<u16>,
+    /// Table of the blue channel
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamps {
+    // the tables are sent as three consecutive arrays of native endian u16
+    fn from_bytes(bytes: &[u8], size: usize) -> Option<GammaRamps> {
+        if bytes.len() != size * 3 * 2 {
+            return None;
+        }
+        let mut values = bytes
+            .chunks_exact(2)
+            .map(|value| u16::from_ne_bytes([value[0], value[1]]));
+        let red = values.by_ref().take(size).collect();
+        let green = values.by_ref().take(size).collect();
+        let blue = values.collect();
+        Some(GammaRamps { red, green, blue })
+    }
+}
+
+// read exactly `len` bytes from the fd sent by the client, without blocking on it
+fn read_table(fd: RawFd, len: usize) -> Option<Vec<u8>> {
+    fcntl(fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)).ok()?;
+    let mut buffer = vec![0; len];
+    let mut read = 0;
+    while read < len {
+        match unistd::read(fd, &mut buffer[read..]) {
+            Ok(0) => return None,
+            Ok(n) => read += n,
+            Err(nix::Error::Sys(Errno::EINTR)) => {}
+            Err(_) => return None,
+        }
+    }
+    Some(buffer)
+}
+
+/// A gamma control of a client
+///
+/// It can be cloned and all clones refer to the same gamma control.
+#[derive(Clone)]
+pub struct GammaControl {
+    control: ZwlrGammaControlV1,
+    output: WlOutput,
+    size: Rc<Cell<Option<u32>>>,
+    failed: Rc<Cell<bool>>,
+}
+
+impl GammaControl {
+    /// The output whose gamma is controlled
+    pub fn output(&self) -> &WlOutput {
+        &self.output
+    }
+
+    /// Tell the client how many entries the gamma table of each color channel has
+    ///
+    /// This must be called once the control is created, the control is failed if the client
+    /// sets its tables before.
+    pub fn set_gamma_size(&self, size: u32) {
+        if self.size.replace(Some(size)).is_none() && self.control.as_ref().is_alive() {
+            self.control.gamma_size(size);
+        }
+    }
+
+    /// Tell the client it no longer controls the gamma of the output
+    ///
+    /// Call this if the gamma of the output cannot be changed, if the tables of the client
+    /// could not be applied, or to take back the control of the output. The control is then
+    /// inert, and you should restore the original gamma tables of the output yourself if the
+    /// client had set some.
+    pub fn fail(&self) {
+        if !self.failed.replace(true) && self.control.as_ref().is_alive() {
+            self.control.failed();
+        }
+    }
+
+    /// Whether the control was [`fail`](GammaControl::fail)ed
+    pub fn is_failed(&self) -> bool {
+        self.failed.get()
+    }
+}
+
+impl ::std::fmt::Debug for GammaControl {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("GammaControl")
+            .field("output", &self.output)
+            .field("size", &self.size.get())
+            .field("failed", &self.failed.get())
+            .finish()
+    }
+}
+
+/// A request of a client of the gamma control protocol
+#[derive(Debug)]
+pub enum GammaControlRequest {
+    /// A client wants to control the gamma of an output
+    ///
+    /// Answer with [`GammaControl::set_gamma_size`], or with [`GammaControl::fail`] if the gamma
+    /// of the output cannot be changed. Clients are failed automatically while another client
+    /// controls the same output.
+    New {
+        /// The new gamma control
+        control: GammaControl,
+    },
+    /// A client set the gamma tables of an output
+    SetGamma {
+        /// The gamma control of the client
+        control: GammaControl,
+        /// The new gamma tables, each of the size given to the control
+        ramps: GammaRamps,
+    },
+    /// A client that set the gamma tables of an output released it
+    ///
+    /// The original gamma tables of the output should be restored.
+    Restore {
+        /// The output to restore
+        output: WlOutput,
+    },
+}
+
+/// Create a new gamma control manager global
+pub fn init_gamma_control_manager<Impl, L>(
+    display: &mut Display,
+    implementation: Impl,
+    logger: L,
+) -> Global<ZwlrGammaControlManagerV1>
+where
+    Impl: FnMut(GammaControlRequest) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "gamma_control_handler"));
+    let implementation = Rc::new(RefCell::new(implementation));
+    // the controls currently holding an output
+    let controls = Rc::new(RefCell::new(Vec::<GammaControl>::new()));
+
+    display.create_global::<ZwlrGammaControlManagerV1, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwlrGammaControlManagerV1>, u32), _, _| {
+                let implementation = implementation.clone();
+                let controls = controls.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                        let control = GammaControl {
+                            control: (*id).clone(),
+                            output,
+                            size: Rc::new(Cell::new(None)),
+                            failed: Rc::new(Cell::new(false)),
+                        };
+                        // whether the tables of the client were applied to the output
+                        let applied = Rc::new(Cell::new(false));
+
+                        let request_implementation = implementation.clone();
+                        let request_control = control.clone();
+                        let request_applied = applied.clone();
+                        let request_log = log.clone();
+                        id.quick_assign(move |gamma_control, request, _| match request {
+                            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                                let control = request_control.clone();
+                                let size = control.size.get();
+                                let table = size.and_then(|size| read_table(fd, size as usize * 3 * 2));
+                                let _ = unistd::close(fd);
+                                if control.is_failed() {
+                                    return;
+                                }
+                                let size = match size {
+                                    Some(size) => size,
+                                    None => {
+                                        // the client cannot know the size of the tables yet
+                                        trace!(request_log, "Gamma tables set before the gamma size");
+                                        control.fail();
+                                        return;
+                                    }
+                                };
+                                let ramps =
+                                    table.and_then(|table| GammaRamps::from_bytes(&table, size as usize));
+                                let ramps = match ramps {
+                                    Some(ramps) => ramps,
+                                    None => {
+                                        gamma_control.as_ref().post_error(
+                                            zwlr_gamma_control_v1::Error::InvalidGamma as u32,
+                                            "The gamma tables do not match the gamma size.".into(),
+                                        );
+                                        return;
+                                    }
+                                };
+                                trace!(request_log, "Setting gamma tables");
+                                request_applied.set(true);
+                                (&mut *request_implementation.borrow_mut())(GammaControlRequest::SetGamma {
+                                    control,
+                                    ramps,
+                                });
+                            }
+                            zwlr_gamma_control_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+
+                        let destructor_implementation = implementation.clone();
+                        let destructor_controls = controls.clone();
+                        let destructor_control = control.clone();
+                        id.assign_destructor(Filter::new(move |_: ZwlrGammaControlV1, _, _| {
+                            destructor_controls
+                                .borrow_mut()
+                                .retain(|c| !c.control.as_ref().equals(destructor_control.control.as_ref()));
+                            if applied.get() && !destructor_control.is_failed() {
+                                (&mut *destructor_implementation.borrow_mut())(
+                                    GammaControlRequest::Restore {
+                                        output: destructor_control.output.clone(),
+                                    },
+                                );
+                            }
+                        }));
+
+                        let mut controls = controls.borrow_mut();
+                        // controls failed by the compositor no longer hold their output
+                        controls.retain(|c| !c.is_failed());
+                        if controls.iter().any(|c| c.output == control.output) {
+                            trace!(log, "Output gamma is already controlled by another client");
+                            control.fail();
+                            return;
+                        }
+                        controls.push(control.clone());
+                        drop(controls);
+                        (&mut *implementation.borrow_mut())(GammaControlRequest::New { control });
+                    }
+                    zwlr_gamma_control_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GammaRamps;
+
+    #[test]
+    fn gamma_ramps_from_bytes() {
+        let values: Vec<u16> = vec![0, 1, 2, 3, 4, 5];
+        let bytes = values
+            .iter()
+            .flat_map(|v| v.to_ne_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            GammaRamps::from_bytes(&bytes, 2),
+            Some(GammaRamps {
+                red: vec![0, 1],
+                green: vec![2, 3],
+                blue: vec![4, 5],
+            })
+        );
+        // the tables must match the gamma size exactly
+        assert_eq!(GammaRamps::from_bytes(&bytes, 3), None);
+        assert_eq!(GammaRamps::from_bytes(&bytes[..10], 2), None);
+    }
+}
@@ -0,0 +1,124 @@
+//! Handler for the idle inhibit protocol
+//!
+//! This protocol lets clients prevent the session from going idle (blanking the screen,
+//! locking the session...) while one of their surfaces is visible, which video players use
+//! while playing a video.
+//!
+//! Inhibitors are attached to surfaces, and should only be honored while their surface is
+//! visible to the user. Smithay does not know which surfaces are visible, so you provide
+//! this information when asking whether idling is inhibited, typically when your idle timer
+//! fires:
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::idle_inhibit::init_idle_inhibit_manager;
+//! # let mut display = wayland_server::Display::new();
+//! let (inhibit_handle, _global) = init_idle_inhibit_manager(&mut display, None /* insert a logger here */);
+//!
+//! // once the session would become idle
+//! let inhibited = inhibit_handle.is_inhibited(|surface| {
+//!     /* check whether the surface is mapped on an output that is turned on */
+//! #   true
+//! });
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::idle_inhibit::v1::server::{
+    zwp_idle_inhibit_manager_v1::{self, ZwpIdleInhibitManagerV1},
+    zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+
+/// A handle to the idle inhibitors of the clients
+///
+/// It can be cloned and all clones access the same inhibitors.
+#[derive(Clone)]
+pub struct IdleInhibitHandle {
+    inhibitors: Rc<RefCell<Vec<(ZwpIdleInhibitorV1, WlSurface)>>>,
+}
+
+impl IdleInhibitHandle {
+    /// The surfaces that currently have an inhibitor, whether they are visible or not
+    pub fn inhibiting_surfaces(&self) -> Vec<WlSurface> {
+        let mut surfaces: Vec<WlSurface> = Vec::new();
+        for (_, surface) in self.inhibitors.borrow().iter() {
+            if surface.as_ref().is_alive() && !surfaces.contains(surface) {
+                surfaces.push(surface.clone());
+            }
+        }
+        surfaces
+    }
+
+    /// Whether idling is currently inhibited
+    ///
+    /// It is if any surface with an inhibitor is visible according to `is_visible`.
+    pub fn is_inhibited<F>(&self, mut is_visible: F) -> bool
+    where
+        F: FnMut(&WlSurface) -> bool,
+    {
+        self.inhibitors
+            .borrow()
+            .iter()
+            .any(|(_, surface)| surface.as_ref().is_alive() && is_visible(surface))
+    }
+}
+
+impl ::std::fmt::Debug for IdleInhibitHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("IdleInhibitHandle")
+            .field("inhibitors", &self.inhibitors.borrow().len())
+            .finish()
+    }
+}
+
+/// Create a new idle inhibit manager global
+///
+/// Returns a handle to query the inhibitors of the clients, along with the global.
+pub fn init_idle_inhibit_manager<L>(
+    display: &mut Display,
+    logger: L,
+) -> (IdleInhibitHandle, Global<ZwpIdleInhibitManagerV1>)
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "idle_inhibit_handler"));
+    let handle = IdleInhibitHandle {
+        inhibitors: Rc::new(RefCell::new(Vec::new())),
+    };
+    let inhibitors = handle.inhibitors.clone();
+
+    let global = display.create_global::<ZwpIdleInhibitManagerV1, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpIdleInhibitManagerV1>, u32), _, _| {
+                let log = log.clone();
+                let inhibitors = inhibitors.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_idle_inhibit_manager_v1::Request::CreateInhibitor { id, surface } => {
+                        id.quick_assign(|_, request, _| match request {
+                            zwp_idle_inhibitor_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        let destructor_inhibitors = inhibitors.clone();
+                        id.assign_destructor(Filter::new(move |inhibitor: ZwpIdleInhibitorV1, _, _| {
+                            destructor_inhibitors
+                                .borrow_mut()
+                                .retain(|(i, _)| !i.as_ref().equals(inhibitor.as_ref()));
+                        }));
+                        trace!(log, "New idle inhibitor");
+                        inhibitors.borrow_mut().push(((*id).clone(), surface));
+                    }
+                    zwp_idle_inhibit_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    );
+
+    (handle, global)
+}
@@ -0,0 +1,236 @@
+//! Handler for the keyboard shortcuts inhibit protocol
+//!
+//! This protocol lets clients such as virtual machines and remote desktop viewers ask for the
+//! keybindings of the compositor to be suspended while one of their surfaces has the keyboard
+//! focus of a seat, so that all key combinations are forwarded to the remote system.
+//!
+//! Inhibitors are not honored by default: your implementation is notified of every new
+//! inhibitor, and decides whether to [`activate`](KeyboardShortcutsInhibitor::activate) it,
+//! for example after asking the user. Before running a keybinding, check whether shortcuts
+//! are inhibited for the focused surface. You should keep at least one keybinding that
+//! [`deactivate`](KeyboardShortcutsInhibitor::deactivate)s the inhibitors, so that users can
+//! regain control of the compositor.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::keyboard_shortcuts_inhibit::init_keyboard_shortcuts_inhibit_manager;
+//! # let mut display = wayland_server::Display::new();
+//! let (inhibit_handle, _global) = init_keyboard_shortcuts_inhibit_manager(
+//!     &mut display,
+//!     |inhibitor| {
+//!         /* ask the user, then */
+//!         inhibitor.activate();
+//!     },
+//!     None, /* insert a logger here */
+//! );
+//!
+//! // when a key combination matching a keybinding is pressed
+//! # let seat: smithay::wayland::seat::Seat = unimplemented!();
+//! # let focus: wayland_server::protocol::wl_surface::WlSurface = unimplemented!();
+//! if !inhibit_handle.is_inhibited(&seat, &focus) {
+//!     /* run the keybinding */
+//! }
+//! ```
+
+use std::{cell::Cell, cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::keyboard_shortcuts_inhibit::v1::server::{
+    zwp_keyboard_shortcuts_inhibit_manager_v1::{self, ZwpKeyboardShortcutsInhibitManagerV1},
+    zwp_keyboard_shortcuts_inhibitor_v1::{self, ZwpKeyboardShortcutsInhibitorV1},
+};
+use wayland_server::{
+    protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
+    Display, Filter, Global, Main,
+};
+
+use crate::wayland::seat::Seat;
+
+/// A request of a client to inhibit the keybindings of the compositor
+///
+/// It can be cloned and all clones refer to the same inhibitor.
+#[derive(Clone)]
+pub struct KeyboardShortcutsInhibitor {
+    inhibitor: ZwpKeyboardShortcutsInhibitorV1,
+    surface: WlSurface,
+    seat: WlSeat,
+    active: Rc<Cell<bool>>,
+}
+
+impl KeyboardShortcutsInhibitor {
+    /// The surface for which keybindings are inhibited
+    pub fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    /// The seat whose keybindings are inhibited
+    pub fn seat(&self) -> &WlSeat {
+        &self.seat
+    }
+
+    /// Whether the keybindings are currently inhibited
+    pub fn is_active(&self) -> bool {
+        self.active.get()
+    }
+
+    /// Honor this inhibitor, and notify its client
+    pub fn activate(&self) {
+        if !self.active.replace(true) && self.inhibitor.as_ref().is_alive() {
+            self.inhibitor.active();
+        }
+    }
+
+    /// Stop honoring this inhibitor, and notify its client
+    ///
+    /// The inhibitor is kept, and can be activated again later.
+    pub fn deactivate(&self) {
+        if self.active.replace(false) && self.inhibitor.as_ref().is_alive() {
+            self.inhibitor.inactive();
+        }
+    }
+}
+
+impl ::std::fmt::Debug for KeyboardShortcutsInhibitor {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("KeyboardShortcutsInhibitor")
+            .field("surface", &self.surface)
+            .field("seat", &self.seat)
+            .field("active", &self.active.get())
+            .finish()
+    }
+}
+
+/// A handle to the keyboard shortcuts inhibitors of the clients
+///
+/// It can be cloned and all clones access the same inhibitors.
+#[derive(Clone)]
+pub struct KeyboardShortcutsInhibitHandle {
+    inhibitors: Rc<RefCell<Vec<KeyboardShortcutsInhibitor>>>,
+}
+
+impl KeyboardShortcutsInhibitHandle {
+    /// The inhibitors of all the clients, active or not
+    pub fn inhibitors(&self) -> Vec<KeyboardShortcutsInhibitor> {
+        self.inhibitors.borrow().clone()
+    }
+
+    /// The inhibitor of a surface on a seat, if any
+    ///
+    /// The inhibitors created through any of the `wl_seat` resources of the seat are considered.
+    pub fn inhibitor(&self, seat: &Seat, surface: &WlSurface) -> Option<KeyboardShortcutsInhibitor> {
+        self.inhibitors
+            .borrow()
+            .iter()
+            .find(|i| i.surface.as_ref().equals(surface.as_ref()) && seat.owns(&i.seat))
+            .cloned()
+    }
+
+    /// Whether the keybindings of a seat are inhibited while a surface has its keyboard focus
+    pub fn is_inhibited(&self, seat: &Seat, surface: &WlSurface) -> bool {
+        self.inhibitor(seat, surface)
+            .map(|i| i.is_active())
+            .unwrap_or(false)
+    }
+
+    /// Deactivate all the inhibitors
+    ///
+    /// Bind this to a keybinding that is never inhibited, to let users regain control of the
+    /// compositor.
+    pub fn deactivate_all(&self) {
+        // the inhibitors are cloned so that clients can be notified without holding the borrow
+        for inhibitor in self.inhibitors() {
+            inhibitor.deactivate();
+        }
+    }
+}
+
+impl ::std::fmt::Debug for KeyboardShortcutsInhibitHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("KeyboardShortcutsInhibitHandle")
+            .field("inhibitors", &self.inhibitors.borrow().len())
+            .finish()
+    }
+}
+
+/// Create a new keyboard shortcuts inhibit manager global
+///
+/// The implementation is notified of every new inhibitor, and decides whether to activate it.
+/// Returns a handle to query the inhibitors of the clients, along with the global.
+pub fn init_keyboard_shortcuts_inhibit_manager<Impl, L>(
+    display: &mut Display,
+    implementation: Impl,
+    logger: L,
+) -> (
+    KeyboardShortcutsInhibitHandle,
+    Global<ZwpKeyboardShortcutsInhibitManagerV1>,
+)
+where
+    Impl: FnMut(KeyboardShortcutsInhibitor) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log =
+        crate::slog_or_fallback(logger).new(o!("smithay_module" => "keyboard_shortcuts_inhibit_handler"));
+    let handle = KeyboardShortcutsInhibitHandle {
+        inhibitors: Rc::new(RefCell::new(Vec::new())),
+    };
+    let implementation = Rc::new(RefCell::new(implementation));
+    let global_handle = handle.clone();
+
+    let global = display.create_global::<ZwpKeyboardShortcutsInhibitManagerV1, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpKeyboardShortcutsInhibitManagerV1>, u32), _, _| {
+                let log = log.clone();
+                let handle = global_handle.clone();
+                let implementation = implementation.clone();
+                manager.quick_assign(move |manager, request, _| match request {
+                    zwp_keyboard_shortcuts_inhibit_manager_v1::Request::InhibitShortcuts {
+                        id,
+                        surface,
+                        seat,
+                    } => {
+                        // the client may have bound the seat several times
+                        let already_inhibited = Seat::from_resource(&seat)
+                            .map(|seat| handle.inhibitor(&seat, &surface).is_some())
+                            .unwrap_or(false);
+                        if already_inhibited {
+                            manager.as_ref().post_error(
+                                zwp_keyboard_shortcuts_inhibit_manager_v1::Error::AlreadyInhibited as u32,
+                                "The surface already inhibits the shortcuts of this seat.".into(),
+                            );
+                            return;
+                        }
+                        id.quick_assign(|_, request, _| match request {
+                            zwp_keyboard_shortcuts_inhibitor_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        let inhibitors = handle.inhibitors.clone();
+                        id.assign_destructor(Filter::new(
+                            move |inhibitor: ZwpKeyboardShortcutsInhibitorV1, _, _| {
+                                inhibitors
+                                    .borrow_mut()
+                                    .retain(|i| !i.inhibitor.as_ref().equals(inhibitor.as_ref()));
+                            },
+                        ));
+                        trace!(log, "New keyboard shortcuts inhibitor");
+                        let inhibitor = KeyboardShortcutsInhibitor {
+                            inhibitor: (*id).clone(),
+                            surface,
+                            seat,
+                            active: Rc::new(Cell::new(false)),
+                        };
+                        handle.inhibitors.borrow_mut().push(inhibitor.clone());
+                        (&mut *implementation.borrow_mut())(inhibitor);
+                    }
+                    zwp_keyboard_shortcuts_inhibit_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    );
+
+    (handle, global)
+}
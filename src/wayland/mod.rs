@@ -14,15 +14,35 @@
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+pub mod backpressure;
 pub mod compositor;
 pub mod data_device;
+pub mod debug_name;
 #[cfg(feature = "backend_drm")]
 pub mod dmabuf;
 pub mod explicit_synchronization;
+#[cfg(feature = "wayland_wlr_protocols")]
+pub mod gamma_control;
+#[cfg(feature = "wayland_idle_inhibit")]
+pub mod idle_inhibit;
+pub mod keyboard_shortcuts_inhibit;
 pub mod output;
+pub mod pointer_constraints;
+#[cfg(feature = "wayland_input_protocols")]
+pub mod pointer_gestures;
+pub mod presentation;
+#[cfg(feature = "wayland_input_protocols")]
+pub mod relative_pointer;
+#[cfg(feature = "wayland_wlr_protocols")]
+pub mod screencopy;
 pub mod seat;
 pub mod shell;
 pub mod shm;
+#[cfg(feature = "wayland_input_protocols")]
+pub mod tablet_manager;
+pub mod viewporter;
+#[cfg(feature = "wayland_wlr_protocols")]
+pub mod virtual_pointer;
 
 /// A global [`SerialCounter`] for use in your compositor.
 ///
@@ -47,25 +47,40 @@
 //! output.add_mode(Mode { width: 800, height: 600, refresh: 60000 });
 //! output.add_mode(Mode { width: 1024, height: 768, refresh: 60000 });
 //! ```
+//!
+//! The logical position and size of your outputs can also be advertised with the
+//! xdg-output protocol, see the [`xdg`](::wayland::output::xdg) submodule.
 
 use std::{
     ops::Deref as _,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
+use wayland_protocols::unstable::xdg_output::v1::server::zxdg_output_v1::ZxdgOutputV1;
 use wayland_server::protocol::wl_output::{Subpixel, Transform};
 use wayland_server::{
     protocol::wl_output::{Mode as WMode, WlOutput},
-    Display, Filter, Global, Main,
+    Client, Display, Filter, Global, Main, UserDataMap,
+};
+
+use crate::{
+    utils::{DestructionHooks, Rectangle},
+    wayland::{
+        compositor::capture::{CaptureError, CapturedFrame},
+        shm::BufferData,
+    },
 };
 
+pub mod xdg;
+
 /// An output mode
 ///
 /// A possible combination of dimensions and refresh rate for an output.
 ///
 /// This should only describe the characteristics of the video driver,
 /// not taking into account any global scaling.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Mode {
     /// The width in pixels
     pub width: i32,
@@ -78,6 +93,7 @@ pub struct Mode {
 }
 
 /// The physical properties of an output
+#[derive(Debug, Clone)]
 pub struct PhysicalProperties {
     /// The width in millimeters
     pub width: i32,
@@ -102,6 +118,7 @@ struct Inner {
     modes: Vec<Mode>,
     current_mode: Option<Mode>,
     preferred_mode: Option<Mode>,
+    xdg_instances: Vec<ZxdgOutputV1>,
 }
 
 impl Inner {
@@ -149,6 +166,32 @@ impl Inner {
             self.transform,
         );
     }
+
+    // size of the output in the global compositor space
+    fn logical_size(&self) -> (i32, i32) {
+        let (width, height) = self
+            .current_mode
+            .map(|mode| (mode.width, mode.height))
+            .unwrap_or((0, 0));
+        let (width, height) = match self.transform {
+            Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+                (height, width)
+            }
+            _ => (width, height),
+        };
+        let scale = self.scale.max(1);
+        (width / scale, height / scale)
+    }
+
+    fn send_xdg_state(&self, xdg_output: &ZxdgOutputV1) {
+        xdg_output.logical_position(self.location.0, self.location.1);
+        let (width, height) = self.logical_size();
+        xdg_output.logical_size(width, height);
+        // starting with version 3, wl_output.done is used instead
+        if xdg_output.as_ref().version() < 3 {
+            xdg_output.done();
+        }
+    }
 }
 
 /// An output as seen by the clients
@@ -157,6 +200,65 @@ impl Inner {
 /// about any change in the properties of this output.
 pub struct Output {
     inner: Arc<Mutex<Inner>>,
+    user_data: UserDataMap,
+    destruction_hooks: DestructionHooks<dyn FnOnce(&str) + Send>,
+    captures: Mutex<Captures>,
+}
+
+type OutputCaptureCallback = Box<dyn FnOnce(Result<CapturedFrame, CaptureError>) + Send>;
+// the callbacks stay in the list while they run, so that stopping a recording or scheduling
+// a marker from a callback or another thread is not lost
+type RecordingCallback = Arc<Mutex<dyn FnMut(RecordingEvent) + Send>>;
+
+#[derive(Default)]
+struct Captures {
+    frames: u64,
+    armed: Vec<OutputCaptureCallback>,
+    recordings: Vec<(RecordingId, RecordingCallback)>,
+    next_recording: usize,
+    markers: Vec<String>,
+}
+
+/// Identifier of a recording started with [`Output::start_recording`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordingId(usize);
+
+/// An event of a recording of an output
+#[derive(Debug, Clone)]
+pub enum RecordingEvent {
+    /// A new frame was presented
+    Frame(CapturedFrame),
+    /// A frame identical to the previous one was presented
+    ///
+    /// Its contents are not read back, encoders producing variable-framerate recordings can
+    /// simply extend the duration of the previous frame.
+    Unchanged {
+        /// Sequence number of the frame
+        sequence: u64,
+        /// Presentation time of the frame, if known
+        presentation_time: Option<Duration>,
+    },
+    /// A marker scheduled with [`Output::schedule_marker`], reported right before the frame
+    /// it applies to
+    Marker(String),
+    /// The contents of a frame could not be captured
+    Error(CaptureError),
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        for callback in self.captures.get_mut().unwrap().armed.drain(..) {
+            callback(Err(CaptureError::Destroyed));
+        }
+        let hooks = self.destruction_hooks.take();
+        if hooks.is_empty() {
+            return;
+        }
+        let name = self.inner.lock().unwrap().name.clone();
+        for hook in hooks {
+            hook(&name);
+        }
+    }
 }
 
 impl Output {
@@ -189,9 +291,15 @@ impl Output {
             modes: Vec::new(),
             current_mode: None,
             preferred_mode: None,
+            xdg_instances: Vec::new(),
         }));
 
-        let output = Output { inner: inner.clone() };
+        let output = Output {
+            inner: inner.clone(),
+            user_data: UserDataMap::new(),
+            destruction_hooks: DestructionHooks::new(),
+            captures: Mutex::new(Captures::default()),
+        };
 
         let global = display.create_global(
             3,
@@ -215,6 +323,176 @@ impl Output {
         (output, global)
     }
 
+    /// Access the `UserDataMap` associated with this `Output`
+    ///
+    /// It allows you to attach your own state to the output, it is dropped along with it.
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.user_data
+    }
+
+    /// Register a hook to be invoked when this output is destroyed
+    ///
+    /// The output is destroyed when this handle is dropped. The hook is invoked with the
+    /// name of the output.
+    pub fn add_destruction_hook<F>(&self, hook: F)
+    where
+        F: FnOnce(&str) + Send + 'static,
+    {
+        self.destruction_hooks.add(Box::new(hook));
+    }
+
+    /// Arm a capture of the next frame presented on this output
+    ///
+    /// The callback is invoked with the contents of the frame once you call
+    /// [`Output::frame_presented`].
+    pub fn capture_next_frame<F>(&self, callback: F)
+    where
+        F: FnOnce(Result<CapturedFrame, CaptureError>) + Send + 'static,
+    {
+        self.captures.lock().unwrap().armed.push(Box::new(callback));
+    }
+
+    /// Whether a capture of the next frame of this output is armed
+    ///
+    /// You can use it to only read back the contents of your framebuffer when needed.
+    pub fn capture_armed(&self) -> bool {
+        let captures = self.captures.lock().unwrap();
+        !captures.armed.is_empty() || !captures.recordings.is_empty()
+    }
+
+    /// Start recording this output
+    ///
+    /// The callback is notified of every frame presented on this output, until
+    /// [`stop_recording`](Output::stop_recording) is called. It may stop recordings of this
+    /// output, including itself, but must not present frames on it.
+    pub fn start_recording<F>(&self, callback: F) -> RecordingId
+    where
+        F: FnMut(RecordingEvent) + Send + 'static,
+    {
+        let mut captures = self.captures.lock().unwrap();
+        let id = RecordingId(captures.next_recording);
+        captures.next_recording += 1;
+        captures.recordings.push((id, Arc::new(Mutex::new(callback))));
+        id
+    }
+
+    /// Stop a recording of this output
+    pub fn stop_recording(&self, id: RecordingId) {
+        self.captures.lock().unwrap().recordings.retain(|(i, _)| *i != id);
+    }
+
+    /// Insert a marker in the recordings of this output
+    ///
+    /// The marker is reported to the recordings right before the next presented frame, so that
+    /// the encoder can timestamp it, for example to add a chapter to the recording.
+    pub fn schedule_marker(&self, label: String) {
+        let mut captures = self.captures.lock().unwrap();
+        if !captures.recordings.is_empty() {
+            captures.markers.push(label);
+        }
+    }
+
+    /// Notify that a new frame was presented on this output
+    ///
+    /// If a capture is armed, `read_pixels` is called to retrieve the contents of the frame
+    /// from your renderer, and the captures are resolved with them. Returning `None` resolves
+    /// them with [`CaptureError::Unavailable`].
+    ///
+    /// The frame is considered entirely changed, use
+    /// [`frame_presented_with_metadata`](Output::frame_presented_with_metadata) to provide
+    /// more information to the recordings.
+    pub fn frame_presented<F>(&self, read_pixels: F)
+    where
+        F: FnOnce() -> Option<(BufferData, Vec<u8>)>,
+    {
+        self.frame_presented_with_metadata(None, None, read_pixels)
+    }
+
+    /// Notify that a new frame was presented on this output, along with its metadata
+    ///
+    /// `presentation_time` is the time the frame was displayed, as reported by your backend,
+    /// and `damage` the regions of the output that changed since the previous presented frame,
+    /// `None` meaning the whole output. If nothing changed, the recordings are notified with
+    /// [`RecordingEvent::Unchanged`] and `read_pixels` is only called for armed captures.
+    pub fn frame_presented_with_metadata<F>(
+        &self,
+        presentation_time: Option<Duration>,
+        damage: Option<&[Rectangle]>,
+        read_pixels: F,
+    ) where
+        F: FnOnce() -> Option<(BufferData, Vec<u8>)>,
+    {
+        let unchanged = damage.map(|damage| damage.is_empty()).unwrap_or(false);
+        let (sequence, mut callbacks, recordings, markers) = {
+            let mut captures = self.captures.lock().unwrap();
+            captures.frames += 1;
+            (
+                captures.frames,
+                ::std::mem::take(&mut captures.armed),
+                captures.recordings.clone(),
+                ::std::mem::take(&mut captures.markers),
+            )
+        };
+        if callbacks.is_empty() && (recordings.is_empty() || unchanged) {
+            self.notify_recordings(&recordings, &markers, || RecordingEvent::Unchanged {
+                sequence,
+                presentation_time,
+            });
+            return;
+        }
+        let result = read_pixels()
+            .map(|(data, pixels)| CapturedFrame {
+                sequence,
+                presentation_time,
+                damage: damage.map(|damage| damage.to_vec()),
+                data,
+                pixels,
+            })
+            .ok_or(CaptureError::Unavailable);
+        self.notify_recordings(&recordings, &markers, || match result {
+            Ok(_) if unchanged => RecordingEvent::Unchanged {
+                sequence,
+                presentation_time,
+            },
+            Ok(ref frame) => RecordingEvent::Frame(frame.clone()),
+            Err(err) => RecordingEvent::Error(err),
+        });
+        if let Some(last) = callbacks.pop() {
+            for callback in callbacks {
+                callback(result.clone());
+            }
+            last(result);
+        }
+    }
+
+    fn notify_recordings<F>(
+        &self,
+        recordings: &[(RecordingId, RecordingCallback)],
+        markers: &[String],
+        event: F,
+    ) where
+        F: Fn() -> RecordingEvent,
+    {
+        for (id, recording) in recordings {
+            // skip the recordings stopped by a previous callback
+            let running = self
+                .captures
+                .lock()
+                .unwrap()
+                .recordings
+                .iter()
+                .any(|(i, _)| i == id);
+            if !running {
+                continue;
+            }
+            let mut recording = recording.lock().unwrap();
+            for marker in markers {
+                (&mut *recording)(RecordingEvent::Marker(marker.clone()));
+            }
+            (&mut *recording)(event());
+        }
+    }
+
     /// Sets the preferred mode of this output
     ///
     /// If the provided mode was not previously known to this output, it is added to its
@@ -282,6 +560,11 @@ impl Output {
         if inner.preferred_mode == new_mode {
             flags |= WMode::Preferred;
         }
+        if new_mode.is_some() || new_transform.is_some() || new_scale.is_some() {
+            for xdg_output in &inner.xdg_instances {
+                inner.send_xdg_state(xdg_output);
+            }
+        }
         for output in &inner.instances {
             if let Some(mode) = new_mode {
                 output.mode(flags, mode.width, mode.height, mode.refresh);
@@ -300,6 +583,79 @@ impl Output {
         }
     }
 
+    /// Change the location of this output in the global compositor space
+    ///
+    /// This is advertised to clients as the position of the output, which they may use
+    /// to arrange their windows across outputs.
+    pub fn set_location(&self, location: (i32, i32)) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.location == location {
+            return;
+        }
+        inner.location = location;
+        for xdg_output in &inner.xdg_instances {
+            inner.send_xdg_state(xdg_output);
+        }
+        for output in &inner.instances {
+            inner.send_geometry(output);
+            if output.as_ref().version() >= 2 {
+                output.done();
+            }
+        }
+    }
+
+    /// Name of this output
+    pub fn name(&self) -> String {
+        self.inner.lock().unwrap().name.clone()
+    }
+
+    /// Location of this output in the global compositor space
+    pub fn location(&self) -> (i32, i32) {
+        self.inner.lock().unwrap().location
+    }
+
+    /// Current transform of this output
+    pub fn current_transform(&self) -> Transform {
+        self.inner.lock().unwrap().transform
+    }
+
+    /// Current scale of this output
+    pub fn current_scale(&self) -> i32 {
+        self.inner.lock().unwrap().scale
+    }
+
+    /// Current mode of this output, if any
+    pub fn current_mode(&self) -> Option<Mode> {
+        self.inner.lock().unwrap().current_mode
+    }
+
+    /// Preferred mode of this output, if any
+    pub fn preferred_mode(&self) -> Option<Mode> {
+        self.inner.lock().unwrap().preferred_mode
+    }
+
+    /// List of the modes known to this output
+    pub fn modes(&self) -> Vec<Mode> {
+        self.inner.lock().unwrap().modes.clone()
+    }
+
+    /// Physical properties of this output
+    pub fn physical_properties(&self) -> PhysicalProperties {
+        self.inner.lock().unwrap().physical.clone()
+    }
+
+    /// The [`wl_output`](WlOutput) instances of this [`Output`] bound by a given client
+    pub fn client_outputs(&self, client: &Client) -> Vec<WlOutput> {
+        self.inner
+            .lock()
+            .unwrap()
+            .instances
+            .iter()
+            .filter(|o| o.as_ref().client().map(|c| c.equals(client)).unwrap_or(false))
+            .cloned()
+            .collect()
+    }
+
     /// Check is given [`wl_output`](WlOutput) instance is managed by this [`Output`].
     pub fn owns(&self, output: &WlOutput) -> bool {
         self.inner
@@ -310,3 +666,13 @@ impl Output {
             .any(|o| o.as_ref().equals(output.as_ref()))
     }
 }
+
+// size of the current mode and scale of the output a wl_output was created by
+#[cfg(feature = "wayland_wlr_protocols")]
+pub(crate) fn output_mode_and_scale(output: &WlOutput) -> Option<((i32, i32), i32)> {
+    let inner = output.as_ref().user_data().get::<Arc<Mutex<Inner>>>()?;
+    let inner = inner.lock().unwrap();
+    inner
+        .current_mode
+        .map(|mode| ((mode.width, mode.height), inner.scale.max(1)))
+}
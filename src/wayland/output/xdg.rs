@@ -0,0 +1,88 @@
+//! Handler for the xdg-output protocol
+//!
+//! This protocol complements `wl_output` with the position and size of the outputs in the
+//! global compositor space, taking their scale and transform into account, which clients like
+//! layer-shell panels and XWayland need to arrange themselves.
+//!
+//! Its state is derived from your [`Output`](::wayland::output::Output)s, so all you need to
+//! do is to create the global:
+//!
+//! ```
+//! # extern crate wayland_server;
+//! # extern crate smithay;
+//! use smithay::wayland::output::xdg::init_xdg_output_manager;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_xdg_output_manager(&mut display, None /* insert a logger here */);
+//! ```
+
+use std::sync::{Arc, Mutex};
+
+use wayland_protocols::unstable::xdg_output::v1::server::{
+    zxdg_output_manager_v1::{self, ZxdgOutputManagerV1},
+    zxdg_output_v1::{self, ZxdgOutputV1},
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+use super::Inner;
+
+/// Create a new xdg-output global
+///
+/// The logical position and size advertised for each output are updated whenever you change
+/// the location or the current state of the [`Output`](::wayland::output::Output).
+pub fn init_xdg_output_manager<L>(display: &mut Display, logger: L) -> Global<ZxdgOutputManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xdg_output_handler"));
+
+    display.create_global::<ZxdgOutputManagerV1, _>(
+        3,
+        Filter::new(
+            move |(manager, _version): (Main<ZxdgOutputManagerV1>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zxdg_output_manager_v1::Request::GetXdgOutput { id, output } => {
+                        id.quick_assign(|_, request, _| match request {
+                            zxdg_output_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        let inner = match output.as_ref().user_data().get::<Arc<Mutex<Inner>>>() {
+                            Some(inner) => inner.clone(),
+                            None => {
+                                warn!(log, "xdg_output requested for an unmanaged wl_output");
+                                return;
+                            }
+                        };
+                        let destructor_inner = inner.clone();
+                        id.assign_destructor(Filter::new(move |xdg_output: ZxdgOutputV1, _, _| {
+                            destructor_inner
+                                .lock()
+                                .unwrap()
+                                .xdg_instances
+                                .retain(|o| !o.as_ref().equals(xdg_output.as_ref()));
+                        }));
+
+                        let mut inner = inner.lock().unwrap();
+                        trace!(log, "New xdg_output"; "name" => &inner.name);
+                        if id.as_ref().version() >= 2 {
+                            id.name(inner.name.clone());
+                            id.description(format!("{} {}", inner.physical.make, inner.physical.model));
+                        }
+                        inner.send_xdg_state(&id);
+                        if id.as_ref().version() >= 3 && output.as_ref().version() >= 2 {
+                            output.done();
+                        }
+                        inner.xdg_instances.push((*id).clone());
+                    }
+                    zxdg_output_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
@@ -0,0 +1,403 @@
+//! Handler for the pointer constraints protocol
+//!
+//! This protocol lets clients lock the pointer in place or confine it to a region of one of their
+//! surfaces, which games and 3D modeling tools use to rotate a camera with the mouse without the
+//! cursor leaving their window.
+//!
+//! Once the global is created, constraints are applied by the [`PointerHandle`] of the seat
+//! they were requested for:
+//!
+//! - A constraint activates when its surface has the pointer focus and the pointer is inside the
+//!   intersection of the region given by the client and the input region of the surface.
+//! - While a lock is active, the pointer does not move and the focused client does not receive
+//!   motion events, use the [relative motion](crate::wayland::relative_pointer) of your input
+//!   device instead.
+//! - While a confinement is active, the pointer cannot leave its region.
+//! - A constraint deactivates when its surface loses the pointer focus, or when you call
+//!   [`PointerHandle::deactivate_constraint`], for example on a compositor keybinding. A
+//!   `Oneshot` constraint never reactivates, a `Persistent` one reactivates the next time the
+//!   pointer enters its surface.
+//!
+//! As the location given to [`PointerHandle::motion`] may be adjusted, read it back with
+//! [`PointerHandle::current_location`] after each motion if you keep track of it yourself.
+//!
+//! ```
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::compositor_init;
+//! use smithay::wayland::pointer_constraints::init_pointer_constraints;
+//! # define_roles!(Roles);
+//! # let mut display = wayland_server::Display::new();
+//! # let (compositor_token, _, _) = compositor_init::<Roles, _, _>(&mut display, |_, _, _| {}, None);
+//! init_pointer_constraints(&mut display, compositor_token, None /* insert a logger here */);
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::pointer_constraints::v1::server::{
+    zwp_confined_pointer_v1::{self, ZwpConfinedPointerV1},
+    zwp_locked_pointer_v1::{self, ZwpLockedPointerV1},
+    zwp_pointer_constraints_v1::{self, Lifetime, ZwpPointerConstraintsV1},
+};
+use wayland_server::{
+    protocol::{wl_display, wl_pointer::WlPointer, wl_region::WlRegion, wl_surface::WlSurface},
+    Display, Filter, Global, Main,
+};
+
+use crate::{
+    utils::DoubleBuffered,
+    wayland::{
+        compositor::{CompositorToken, RegionAttributes, SurfaceAttributes},
+        seat::PointerHandle,
+    },
+};
+
+pub(crate) enum ConstraintObject {
+    Lock(ZwpLockedPointerV1),
+    Confine(ZwpConfinedPointerV1),
+}
+
+/// A lock or confinement of the pointer requested by a client
+pub(crate) struct PointerConstraint {
+    surface: WlSurface,
+    object: ConstraintObject,
+    lifetime: Lifetime,
+    region: DoubleBuffered<Option<RegionAttributes>>,
+    // the input region of the surface as of its last commit
+    input_region: Option<RegionAttributes>,
+    cursor_hint: DoubleBuffered<Option<(f64, f64)>>,
+    active: bool,
+    // deactivated by the compositor, until the pointer leaves the surface
+    suspended: bool,
+    // a oneshot constraint that was deactivated
+    defunct: bool,
+}
+
+pub(crate) type ConstraintRef = Rc<RefCell<PointerConstraint>>;
+
+impl PointerConstraint {
+    pub(crate) fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+
+    pub(crate) fn is_lock(&self) -> bool {
+        matches!(self.object, ConstraintObject::Lock(_))
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Where the client would like the pointer to be once unlocked, in surface-local coordinates
+    pub(crate) fn cursor_hint(&self) -> Option<(f64, f64)> {
+        *self.cursor_hint.current()
+    }
+
+    /// Whether a point in surface-local coordinates is inside the region of this constraint
+    pub(crate) fn contains(&self, (x, y): (f64, f64)) -> bool {
+        let point = (x.floor() as i32, y.floor() as i32);
+        self.region
+            .current()
+            .as_ref()
+            .map(|r| r.contains(point))
+            .unwrap_or(true)
+            && self
+                .input_region
+                .as_ref()
+                .map(|r| r.contains(point))
+                .unwrap_or(true)
+    }
+
+    /// Activate the constraint if it is allowed to, the pointer being on its surface at `location`
+    pub(crate) fn try_activate(&mut self, location: (f64, f64)) {
+        if self.active || self.suspended || self.defunct || !self.contains(location) {
+            return;
+        }
+        self.active = true;
+        match self.object {
+            ConstraintObject::Lock(ref lock) => lock.locked(),
+            ConstraintObject::Confine(ref confine) => confine.confined(),
+        }
+    }
+
+    /// Deactivate the constraint, `suspend` preventing a persistent one to reactivate before the
+    /// pointer leaves its surface
+    pub(crate) fn deactivate(&mut self, suspend: bool) {
+        self.suspended = suspend;
+        if !self.active {
+            return;
+        }
+        self.active = false;
+        if self.lifetime == Lifetime::Oneshot {
+            self.defunct = true;
+        }
+        match self.object {
+            ConstraintObject::Lock(ref lock) => lock.unlocked(),
+            ConstraintObject::Confine(ref confine) => confine.unconfined(),
+        }
+    }
+
+    /// The location a confined pointer moving from `old` to `new` ends up at, in surface-local
+    /// coordinates
+    pub(crate) fn confine(&self, old: (f64, f64), new: (f64, f64)) -> (f64, f64) {
+        confine(|point| self.contains(point), old, new)
+    }
+}
+
+// the constraints requested on a surface, to apply their pending state on commit
+#[derive(Default)]
+struct SurfaceConstraints(RefCell<Vec<ConstraintRef>>);
+
+/// Create a new pointer constraints global
+pub fn init_pointer_constraints<R, L>(
+    display: &mut Display,
+    token: CompositorToken<R>,
+    logger: L,
+) -> Global<ZwpPointerConstraintsV1>
+where
+    R: 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "pointer_constraints_handler"));
+
+    display.create_global::<ZwpPointerConstraintsV1, _>(
+        1,
+        Filter::new(
+            move |(constraints, _version): (Main<ZwpPointerConstraintsV1>, u32), _, _| {
+                let log = log.clone();
+                constraints.quick_assign(move |constraints, request, _| match request {
+                    zwp_pointer_constraints_v1::Request::LockPointer {
+                        id,
+                        surface,
+                        pointer,
+                        region,
+                        lifetime,
+                    } => {
+                        implement_lock(&id, surface.clone(), token);
+                        let object = ConstraintObject::Lock((*id).clone());
+                        if let Some(destroy) =
+                            new_constraint(&constraints, object, surface, pointer, region, lifetime, token)
+                        {
+                            trace!(log, "New pointer lock");
+                            id.assign_destructor(Filter::new(move |_: ZwpLockedPointerV1, _, _| destroy()));
+                        }
+                    }
+                    zwp_pointer_constraints_v1::Request::ConfinePointer {
+                        id,
+                        surface,
+                        pointer,
+                        region,
+                        lifetime,
+                    } => {
+                        implement_confine(&id, surface.clone(), token);
+                        let object = ConstraintObject::Confine((*id).clone());
+                        if let Some(destroy) =
+                            new_constraint(&constraints, object, surface, pointer, region, lifetime, token)
+                        {
+                            trace!(log, "New pointer confinement");
+                            id.assign_destructor(Filter::new(move |_: ZwpConfinedPointerV1, _, _| destroy()));
+                        }
+                    }
+                    zwp_pointer_constraints_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+// register a new constraint, returning the closure to call on its destruction
+fn new_constraint<R: 'static>(
+    constraints: &ZwpPointerConstraintsV1,
+    object: ConstraintObject,
+    surface: WlSurface,
+    pointer: WlPointer,
+    region: Option<WlRegion>,
+    lifetime: u32,
+    token: CompositorToken<R>,
+) -> Option<impl Fn()> {
+    // the seat has no pointer if there is no handle, the constraint then never activates
+    let handle = PointerHandle::from_resource(&pointer)?;
+    if handle.is_constrained_on(&surface) {
+        constraints.as_ref().post_error(
+            zwp_pointer_constraints_v1::Error::AlreadyConstrained as u32,
+            "This surface already has a pointer constraint for this seat.".into(),
+        );
+        return None;
+    }
+    let lifetime = match Lifetime::from_raw(lifetime) {
+        Some(lifetime) => lifetime,
+        None => {
+            constraints.as_ref().post_error(
+                wl_display::Error::InvalidMethod as u32,
+                "Invalid constraint lifetime.".into(),
+            );
+            return None;
+        }
+    };
+    let constraint = Rc::new(RefCell::new(PointerConstraint {
+        surface: surface.clone(),
+        object,
+        lifetime,
+        region: DoubleBuffered::new(region.map(|r| token.get_region_attributes(&r))),
+        input_region: token.with_surface_data(&surface, |attrs| attrs.input_region.clone()),
+        cursor_hint: DoubleBuffered::default(),
+        active: false,
+        suspended: false,
+        defunct: false,
+    }));
+    match constraint.borrow().object {
+        ConstraintObject::Lock(ref lock) => {
+            let user_data = constraint.clone();
+            lock.as_ref().user_data().set(move || user_data);
+        }
+        ConstraintObject::Confine(ref confine) => {
+            let user_data = constraint.clone();
+            confine.as_ref().user_data().set(move || user_data);
+        }
+    }
+    token.with_surface_data(&surface, |attrs| {
+        attrs.user_data.insert_if_missing(SurfaceConstraints::default);
+        let list = attrs.user_data.get::<SurfaceConstraints>().unwrap();
+        list.0.borrow_mut().push(constraint.clone());
+    });
+    handle.add_constraint(constraint.clone());
+
+    Some(move || {
+        handle.remove_constraint(&constraint);
+        if surface.as_ref().is_alive() {
+            token.with_surface_data(&surface, |attrs| {
+                if let Some(list) = attrs.user_data.get::<SurfaceConstraints>() {
+                    list.0.borrow_mut().retain(|c| !Rc::ptr_eq(c, &constraint));
+                }
+            });
+        }
+    })
+}
+
+fn implement_lock<R: 'static>(
+    lock: &Main<ZwpLockedPointerV1>,
+    surface: WlSurface,
+    token: CompositorToken<R>,
+) {
+    lock.quick_assign(move |lock, request, _| {
+        let constraint = match lock.as_ref().user_data().get::<ConstraintRef>() {
+            Some(constraint) => constraint.clone(),
+            None => return,
+        };
+        match request {
+            zwp_locked_pointer_v1::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            zwp_locked_pointer_v1::Request::SetCursorPositionHint { surface_x, surface_y } => {
+                constraint
+                    .borrow_mut()
+                    .cursor_hint
+                    .set_pending(Some((surface_x, surface_y)));
+            }
+            zwp_locked_pointer_v1::Request::SetRegion { region } => {
+                set_pending_region(&constraint, &surface, region, token);
+            }
+            _ => unreachable!(),
+        }
+    });
+}
+
+fn implement_confine<R: 'static>(
+    confine: &Main<ZwpConfinedPointerV1>,
+    surface: WlSurface,
+    token: CompositorToken<R>,
+) {
+    confine.quick_assign(move |confine, request, _| {
+        let constraint = match confine.as_ref().user_data().get::<ConstraintRef>() {
+            Some(constraint) => constraint.clone(),
+            None => return,
+        };
+        match request {
+            zwp_confined_pointer_v1::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            zwp_confined_pointer_v1::Request::SetRegion { region } => {
+                set_pending_region(&constraint, &surface, region, token);
+            }
+            _ => unreachable!(),
+        }
+    });
+}
+
+fn set_pending_region<R: 'static>(
+    constraint: &ConstraintRef,
+    surface: &WlSurface,
+    region: Option<WlRegion>,
+    token: CompositorToken<R>,
+) {
+    if surface.as_ref().is_alive() {
+        constraint
+            .borrow_mut()
+            .region
+            .set_pending(region.map(|r| token.get_region_attributes(&r)));
+    }
+}
+
+/// Apply the pending state of the constraints of a surface
+///
+/// The state of a synchronized sub-surface is cached instead, until [`apply_cached`] is called
+/// when its parent is committed.
+pub(crate) fn apply_commit(attrs: &SurfaceAttributes, sync: bool) {
+    if let Some(list) = attrs.user_data.get::<SurfaceConstraints>() {
+        for constraint in list.0.borrow().iter() {
+            let mut constraint = constraint.borrow_mut();
+            if sync {
+                constraint.region.cache();
+                constraint.cursor_hint.cache();
+            } else {
+                constraint.region.commit();
+                constraint.cursor_hint.commit();
+                constraint.input_region = attrs.input_region.clone();
+            }
+        }
+    }
+}
+
+/// Apply the state of the constraints cached by a synchronized sub-surface
+pub(crate) fn apply_cached(attrs: &mut SurfaceAttributes) {
+    if let Some(list) = attrs.user_data.get::<SurfaceConstraints>() {
+        for constraint in list.0.borrow().iter() {
+            let mut constraint = constraint.borrow_mut();
+            constraint.region.apply_cached();
+            constraint.cursor_hint.apply_cached();
+            constraint.input_region = attrs.input_region.clone();
+        }
+    }
+}
+
+// slide along the edges of the region when moving outside of it
+fn confine<F>(contains: F, old: (f64, f64), new: (f64, f64)) -> (f64, f64)
+where
+    F: Fn((f64, f64)) -> bool,
+{
+    [new, (new.0, old.1), (old.0, new.1)]
+        .iter()
+        .copied()
+        .find(|&point| contains(point))
+        .unwrap_or(old)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confinement() {
+        let inside = |(x, y): (f64, f64)| (0.0..100.0).contains(&x) && (0.0..50.0).contains(&y);
+        assert_eq!(confine(inside, (10.0, 10.0), (20.0, 20.0)), (20.0, 20.0));
+        // slides along the bottom edge
+        assert_eq!(confine(inside, (10.0, 40.0), (20.0, 60.0)), (20.0, 40.0));
+        // slides along the right edge
+        assert_eq!(confine(inside, (90.0, 10.0), (110.0, 20.0)), (90.0, 20.0));
+        // stuck in the corner
+        assert_eq!(confine(inside, (90.0, 40.0), (110.0, 60.0)), (90.0, 40.0));
+    }
+}
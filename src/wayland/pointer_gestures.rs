@@ -0,0 +1,87 @@
+//! Handler for the pointer gestures protocol
+//!
+//! This protocol lets clients receive the multi-finger gestures of touchpads, such as pinches
+//! to zoom in a browser or swipes to navigate its history.
+//!
+//! Once the global is created, forward the gestures of your input backend to the focused client
+//! with the `gesture_*` methods of the [`PointerHandle`](crate::wayland::seat::PointerHandle),
+//! for example [`gesture_swipe_begin`](crate::wayland::seat::PointerHandle::gesture_swipe_begin).
+//! Input backends report them as [`InputEvent::GestureSwipeBegin`](crate::backend::input::InputEvent)
+//! and the similar events.
+//!
+//! Hold gestures are not part of the version of the protocol supported here, their
+//! `InputEvent::GestureHold*` events are only meant to be used by the compositor itself.
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::pointer_gestures::init_pointer_gestures_manager;
+//! # let mut display = wayland_server::Display::new();
+//! init_pointer_gestures_manager(&mut display, None /* insert a logger here */);
+//! ```
+
+use wayland_protocols::unstable::pointer_gestures::v1::server::{
+    zwp_pointer_gesture_pinch_v1::{self, ZwpPointerGesturePinchV1},
+    zwp_pointer_gesture_swipe_v1::{self, ZwpPointerGestureSwipeV1},
+    zwp_pointer_gestures_v1::{self, ZwpPointerGesturesV1},
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+use crate::wayland::seat::PointerHandle;
+
+/// Create a new pointer gestures global
+pub fn init_pointer_gestures_manager<L>(display: &mut Display, logger: L) -> Global<ZwpPointerGesturesV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "pointer_gestures_handler"));
+
+    display.create_global::<ZwpPointerGesturesV1, _>(
+        2,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpPointerGesturesV1>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_pointer_gestures_v1::Request::GetSwipeGesture { id, pointer } => {
+                        id.quick_assign(|_, request, _| match request {
+                            zwp_pointer_gesture_swipe_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        // the seat has no pointer if there is no handle, no event is ever sent
+                        if let Some(handle) = PointerHandle::from_resource(&pointer) {
+                            trace!(log, "New swipe gesture");
+                            handle.new_swipe_gesture((*id).clone());
+                            id.assign_destructor(Filter::new(
+                                move |gesture: ZwpPointerGestureSwipeV1, _, _| {
+                                    handle.remove_swipe_gesture(&gesture)
+                                },
+                            ));
+                        }
+                    }
+                    zwp_pointer_gestures_v1::Request::GetPinchGesture { id, pointer } => {
+                        id.quick_assign(|_, request, _| match request {
+                            zwp_pointer_gesture_pinch_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        if let Some(handle) = PointerHandle::from_resource(&pointer) {
+                            trace!(log, "New pinch gesture");
+                            handle.new_pinch_gesture((*id).clone());
+                            id.assign_destructor(Filter::new(
+                                move |gesture: ZwpPointerGesturePinchV1, _, _| {
+                                    handle.remove_pinch_gesture(&gesture)
+                                },
+                            ));
+                        }
+                    }
+                    zwp_pointer_gestures_v1::Request::Release => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
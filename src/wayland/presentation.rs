@@ -0,0 +1,203 @@
+//! Handler for the presentation-time protocol
+//!
+//! This protocol lets clients know exactly when their content updates were displayed, and
+//! predict when the next ones will be, which video players need to keep audio and video in sync.
+//!
+//! Clients request feedback for the next commit of a surface. Once you displayed a frame
+//! containing the state of a surface, call [`surface_presented`] with the timing of the frame,
+//! typically built from the vblank event of your DRM device with
+//! [`PresentationTime::from_vblank`], or from the current time with [`PresentationTime::now`]
+//! if your backend does not report one. If a state is never displayed, because it was
+//! superseded by a newer commit before it could be, clients are notified automatically.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::CompositorToken;
+//! # use smithay::wayland::output::Output;
+//! use smithay::wayland::presentation::{init_presentation, surface_presented, PresentationTime};
+//! # define_roles!(Roles);
+//! # fn wrap(
+//! #     display: &mut wayland_server::Display,
+//! #     token: CompositorToken<Roles>,
+//! #     surface: &wayland_server::protocol::wl_surface::WlSurface,
+//! #     output: &Output,
+//! # ) {
+//! init_presentation(display, token, None /* insert a logger here */);
+//!
+//! // once the surface was displayed on the output
+//! let time = PresentationTime::now(None);
+//! token.with_surface_data(surface, |attrs| surface_presented(attrs, output, &time));
+//! # }
+//! ```
+
+use std::{cell::RefCell, mem, time::Duration};
+
+use wayland_protocols::presentation_time::server::{
+    wp_presentation::{self, WpPresentation},
+    wp_presentation_feedback::{Kind, WpPresentationFeedback},
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+#[cfg(feature = "backend_drm")]
+use crate::backend::drm::VblankTime;
+use crate::wayland::{
+    compositor::{CompositorToken, SurfaceAttributes},
+    output::Output,
+};
+
+/// Timing of a presented frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresentationTime {
+    /// Time the frame started being displayed, on the `CLOCK_MONOTONIC` clock
+    pub time: Duration,
+    /// Duration until the next frame can be displayed, if the output has a constant refresh rate
+    pub refresh: Option<Duration>,
+    /// Vblank counter of the output, 0 if there is none
+    pub sequence: u64,
+    /// How the frame was presented
+    pub flags: Kind,
+}
+
+impl PresentationTime {
+    /// Timing of a frame presented at a vblank event of a DRM device
+    #[cfg(feature = "backend_drm")]
+    pub fn from_vblank(vblank: VblankTime, refresh: Option<Duration>) -> PresentationTime {
+        PresentationTime {
+            time: vblank.time,
+            refresh,
+            sequence: vblank.sequence as u64,
+            flags: Kind::Vsync | Kind::HwClock | Kind::HwCompletion,
+        }
+    }
+
+    /// Timing of a frame presented now, for backends not reporting when frames are displayed
+    pub fn now(refresh: Option<Duration>) -> PresentationTime {
+        PresentationTime {
+            time: monotonic_time(),
+            refresh,
+            sequence: 0,
+            flags: Kind::empty(),
+        }
+    }
+}
+
+/// The current time on the `CLOCK_MONOTONIC` clock used by this protocol
+pub fn monotonic_time() -> Duration {
+    let mut ts = ::nix::libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // this cannot fail, CLOCK_MONOTONIC is always supported
+    unsafe { ::nix::libc::clock_gettime(::nix::libc::CLOCK_MONOTONIC, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+#[derive(Default)]
+struct Feedbacks {
+    // requested for the next commit
+    pending: Vec<WpPresentationFeedback>,
+    // waiting for the committed state to be presented
+    committed: Vec<WpPresentationFeedback>,
+}
+
+impl Drop for Feedbacks {
+    fn drop(&mut self) {
+        // the surface was destroyed
+        for feedback in self.pending.drain(..).chain(self.committed.drain(..)) {
+            feedback.discarded();
+        }
+    }
+}
+
+/// Create a new presentation-time global
+pub fn init_presentation<R, L>(
+    display: &mut Display,
+    token: CompositorToken<R>,
+    logger: L,
+) -> Global<WpPresentation>
+where
+    R: 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "presentation_handler"));
+
+    display.create_global::<WpPresentation, _>(
+        1,
+        Filter::new(
+            move |(presentation, _version): (Main<WpPresentation>, u32), _, _| {
+                let log = log.clone();
+                presentation.quick_assign(move |_presentation, request, _| match request {
+                    wp_presentation::Request::Feedback { surface, callback } => {
+                        // feedback objects have no requests
+                        callback.quick_assign(|_, _, _| {});
+                        trace!(log, "New presentation feedback");
+                        token.with_surface_data(&surface, |attrs| {
+                            attrs
+                                .user_data
+                                .insert_if_missing(|| RefCell::new(Feedbacks::default()));
+                            let feedbacks = attrs.user_data.get::<RefCell<Feedbacks>>().unwrap();
+                            feedbacks.borrow_mut().pending.push((*callback).clone());
+                        });
+                    }
+                    wp_presentation::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+                presentation.clock_id(::nix::libc::CLOCK_MONOTONIC as u32);
+            },
+        ),
+    )
+}
+
+/// Notify the clients that the current state of a surface was presented on an output
+///
+/// It is a no-op if no client requested feedback for this state.
+pub fn surface_presented(attrs: &SurfaceAttributes, output: &Output, time: &PresentationTime) {
+    let committed = match attrs.user_data.get::<RefCell<Feedbacks>>() {
+        Some(feedbacks) => mem::take(&mut feedbacks.borrow_mut().committed),
+        None => return,
+    };
+    let secs = time.time.as_secs();
+    let refresh = time.refresh.map(|r| r.as_nanos() as u32).unwrap_or(0);
+    for feedback in committed {
+        if let Some(client) = feedback.as_ref().client() {
+            for wl_output in output.client_outputs(&client) {
+                feedback.sync_output(&wl_output);
+            }
+        }
+        feedback.presented(
+            (secs >> 32) as u32,
+            secs as u32,
+            time.time.subsec_nanos(),
+            refresh,
+            (time.sequence >> 32) as u32,
+            time.sequence as u32,
+            time.flags,
+        );
+    }
+}
+
+/// Notify the clients that the current state of a surface will not be presented
+///
+/// Use it for example if the surface was hidden before being displayed.
+pub fn surface_discarded(attrs: &SurfaceAttributes) {
+    if let Some(feedbacks) = attrs.user_data.get::<RefCell<Feedbacks>>() {
+        for feedback in mem::take(&mut feedbacks.borrow_mut().committed) {
+            feedback.discarded();
+        }
+    }
+}
+
+/// Move the pending feedbacks of a surface to its committed state, discarding the ones it supersedes
+pub(crate) fn record_commit(attrs: &SurfaceAttributes) {
+    if let Some(feedbacks) = attrs.user_data.get::<RefCell<Feedbacks>>() {
+        let mut feedbacks = feedbacks.borrow_mut();
+        // the previous state was superseded before being presented
+        for feedback in mem::take(&mut feedbacks.committed) {
+            feedback.discarded();
+        }
+        feedbacks.committed = mem::take(&mut feedbacks.pending);
+    }
+}
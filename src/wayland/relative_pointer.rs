@@ -0,0 +1,68 @@
+//! Handler for the relative pointer protocol
+//!
+//! This protocol lets clients receive the raw motion of the pointer, unaffected by the edges of
+//! the outputs or by [pointer constraints](crate::wayland::pointer_constraints), which games use
+//! to rotate a camera while the pointer is locked.
+//!
+//! Once the global is created, report the motions of your pointer devices to the focused client
+//! with [`PointerHandle::relative_motion`](crate::wayland::seat::PointerHandle::relative_motion),
+//! in addition to the usual [`PointerHandle::motion`](crate::wayland::seat::PointerHandle::motion).
+//! Input backends provide both the accelerated and unaccelerated deltas in their
+//! [`PointerMotionEvent`](crate::backend::input::PointerMotionEvent)s.
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::relative_pointer::init_relative_pointer_manager;
+//! # let mut display = wayland_server::Display::new();
+//! init_relative_pointer_manager(&mut display, None /* insert a logger here */);
+//! ```
+
+use wayland_protocols::unstable::relative_pointer::v1::server::{
+    zwp_relative_pointer_manager_v1::{self, ZwpRelativePointerManagerV1},
+    zwp_relative_pointer_v1::{self, ZwpRelativePointerV1},
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+use crate::wayland::seat::PointerHandle;
+
+/// Create a new relative pointer manager global
+pub fn init_relative_pointer_manager<L>(
+    display: &mut Display,
+    logger: L,
+) -> Global<ZwpRelativePointerManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "relative_pointer_handler"));
+
+    display.create_global::<ZwpRelativePointerManagerV1, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpRelativePointerManagerV1>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_relative_pointer_manager_v1::Request::GetRelativePointer { id, pointer } => {
+                        id.quick_assign(|_, request, _| match request {
+                            zwp_relative_pointer_v1::Request::Destroy => {
+                                // All is already handled by our destructor
+                            }
+                            _ => unreachable!(),
+                        });
+                        // the seat has no pointer if there is no handle, no event is ever sent
+                        if let Some(handle) = PointerHandle::from_resource(&pointer) {
+                            trace!(log, "New relative pointer");
+                            handle.new_relative_pointer((*id).clone());
+                            id.assign_destructor(Filter::new(move |relative: ZwpRelativePointerV1, _, _| {
+                                handle.remove_relative_pointer(&relative)
+                            }));
+                        }
+                    }
+                    zwp_relative_pointer_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
@@ -0,0 +1,475 @@
+//! Handler for the wlr screencopy protocol
+//!
+//! This protocol lets clients such as screenshot tools and screen recorders copy the contents
+//! of an output, or of a region of it, into a buffer they provide.
+//!
+//! Clients first request a frame, to which the supported buffer formats are advertised, then
+//! ask for it to be copied into their buffer. The copy happens the next time you notify that a
+//! frame was presented on the output with [`ScreencopyManager::frame_presented`], reading the
+//! contents of the frame through your [`ScreencopySource`]. Clients copying "with damage" are
+//! only served once the contents of the region actually changed.
+//!
+//! Contents are always copied into shm buffers, using the shm format given when creating the
+//! global. Copies into dmabufs are only offered if you set a dmabuf format with
+//! [`ScreencopyManager::set_dmabuf_format`], and are delegated to your renderer.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # extern crate smithay;
+//! use std::time::Duration;
+//! use smithay::utils::Rectangle;
+//! use smithay::wayland::screencopy::{init_screencopy_manager, ScreencopySource};
+//! use wayland_server::protocol::wl_shm::Format;
+//! # let mut display = wayland_server::Display::new();
+//! # let output: smithay::wayland::output::Output = unimplemented!();
+//!
+//! struct Readback;
+//!
+//! impl ScreencopySource for Readback {
+//!     fn read_pixels(&mut self, region: Rectangle) -> Option<Vec<u8>> {
+//!         /* read the region from your framebuffer, as tightly packed Xrgb8888 rows */
+//! #       None
+//!     }
+//! }
+//!
+//! let (screencopy, _global) = init_screencopy_manager(&mut display, Format::Xrgb8888, None);
+//! // once a frame was presented on an output
+//! if screencopy.has_pending_copies(&output) {
+//!     screencopy.frame_presented(&output, Duration::from_secs(0), None, &mut Readback);
+//! }
+//! ```
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use wayland_protocols::wlr::unstable::screencopy::v1::server::{
+    zwlr_screencopy_frame_v1::{self, Flags, ZwlrScreencopyFrameV1},
+    zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+};
+use wayland_server::{
+    protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+    Display, Filter, Global, Main,
+};
+
+use crate::{
+    utils::Rectangle,
+    wayland::{
+        output::{output_mode_and_scale, Output},
+        shm::{buffer_data, bytes_per_pixel, write_buffer_contents},
+    },
+};
+
+/// Provider of the contents of the frames presented on an output
+pub trait ScreencopySource {
+    /// Read the pixels of a region of the presented frame
+    ///
+    /// The region is in the pixel coordinates of the output mode. The pixels must use the shm
+    /// format of the manager, with tightly packed rows. Returning `None` fails the copies.
+    fn read_pixels(&mut self, region: Rectangle) -> Option<Vec<u8>>;
+
+    /// Whether the rows returned by [`read_pixels`](ScreencopySource::read_pixels) go from the
+    /// bottom to the top of the region, as read back from OpenGL framebuffers
+    fn y_inverted(&self) -> bool {
+        false
+    }
+
+    /// Render a region of the presented frame into a dmabuf provided by a client
+    ///
+    /// Only called if a dmabuf format was set with
+    /// [`ScreencopyManager::set_dmabuf_format`]. Returns whether the copy succeeded.
+    fn copy_to_dmabuf(&mut self, buffer: &WlBuffer, region: Rectangle) -> bool {
+        let _ = (buffer, region);
+        false
+    }
+}
+
+// a frame whose contents were requested
+struct Frame {
+    frame: ZwlrScreencopyFrameV1,
+    output: WlOutput,
+    // in the pixel coordinates of the output mode
+    region: Rectangle,
+    overlay_cursor: bool,
+    // set once the client requested the copy
+    copy: Option<(WlBuffer, bool)>,
+}
+
+struct ManagerInner {
+    frames: Vec<Frame>,
+    shm_format: wl_shm::Format,
+    dmabuf_format: Option<u32>,
+    log: ::slog::Logger,
+}
+
+/// A handle to the screencopy manager global
+///
+/// It is created by [`init_screencopy_manager`], and can be cloned.
+#[derive(Clone)]
+pub struct ScreencopyManager {
+    inner: Rc<RefCell<ManagerInner>>,
+}
+
+impl ScreencopyManager {
+    fn new_frame(
+        &self,
+        frame: Main<ZwlrScreencopyFrameV1>,
+        overlay_cursor: bool,
+        output: WlOutput,
+        region: Option<Rectangle>,
+    ) {
+        let handle = self.clone();
+        frame.quick_assign(move |frame, request, _| match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => handle.copy(&frame, buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => handle.copy(&frame, buffer, true),
+            zwlr_screencopy_frame_v1::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            _ => unreachable!(),
+        });
+        let handle = self.clone();
+        frame.assign_destructor(Filter::new(move |frame: ZwlrScreencopyFrameV1, _, _| {
+            handle
+                .inner
+                .borrow_mut()
+                .frames
+                .retain(|f| !f.frame.as_ref().equals(frame.as_ref()));
+        }));
+
+        let mut inner = self.inner.borrow_mut();
+        let ((width, height), scale) = match output_mode_and_scale(&output) {
+            Some(state) => state,
+            None => {
+                debug!(inner.log, "Screencopy of an output without a current mode");
+                frame.failed();
+                return;
+            }
+        };
+        let full = Rectangle {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        };
+        // regions are given in the logical coordinates of the output
+        let region = match region {
+            Some(region) => intersection(
+                &full,
+                &Rectangle {
+                    x: region.x * scale,
+                    y: region.y * scale,
+                    width: region.width * scale,
+                    height: region.height * scale,
+                },
+            ),
+            None => Some(full),
+        };
+        let region = match region {
+            Some(region) => region,
+            None => {
+                debug!(inner.log, "Screencopy of a region outside of its output");
+                frame.failed();
+                return;
+            }
+        };
+
+        let bpp = bytes_per_pixel(inner.shm_format).unwrap_or(4);
+        frame.buffer(
+            inner.shm_format,
+            region.width as u32,
+            region.height as u32,
+            (region.width * bpp) as u32,
+        );
+        if frame.as_ref().version() >= 3 {
+            if let Some(format) = inner.dmabuf_format {
+                frame.linux_dmabuf(format, region.width as u32, region.height as u32);
+            }
+            frame.buffer_done();
+        }
+        trace!(inner.log, "New screencopy frame"; "region" => ?region);
+        inner.frames.push(Frame {
+            frame: (*frame).clone(),
+            output,
+            region,
+            overlay_cursor,
+            copy: None,
+        });
+    }
+
+    fn copy(&self, frame: &ZwlrScreencopyFrameV1, buffer: WlBuffer, with_damage: bool) {
+        let mut inner = self.inner.borrow_mut();
+        let shm_format = inner.shm_format;
+        let has_dmabuf = inner.dmabuf_format.is_some();
+        let pending = match inner
+            .frames
+            .iter_mut()
+            .find(|f| f.frame.as_ref().equals(frame.as_ref()))
+        {
+            Some(pending) => pending,
+            // the frame already failed
+            None => return,
+        };
+        if pending.copy.is_some() {
+            frame.as_ref().post_error(
+                zwlr_screencopy_frame_v1::Error::AlreadyUsed as u32,
+                "The frame was already copied.".into(),
+            );
+            return;
+        }
+        let valid = match buffer_data(&buffer) {
+            Some(data) => {
+                let bpp = bytes_per_pixel(shm_format).unwrap_or(4);
+                data.format == shm_format
+                    && data.width == pending.region.width
+                    && data.height == pending.region.height
+                    && data.stride >= pending.region.width * bpp
+            }
+            None => has_dmabuf,
+        };
+        if !valid {
+            frame.as_ref().post_error(
+                zwlr_screencopy_frame_v1::Error::InvalidBuffer as u32,
+                "The buffer does not match the advertised parameters.".into(),
+            );
+            return;
+        }
+        pending.copy = Some((buffer, with_damage));
+    }
+
+    /// Set the dmabuf format advertised to clients, as a fourcc code, or stop advertising one
+    ///
+    /// Only affects the frames requested afterwards.
+    pub fn set_dmabuf_format(&self, format: Option<u32>) {
+        self.inner.borrow_mut().dmabuf_format = format;
+    }
+
+    /// Whether clients are waiting for the next frame presented on an output
+    ///
+    /// You can use it to only read back the contents of your framebuffer when needed.
+    pub fn has_pending_copies(&self, output: &Output) -> bool {
+        self.inner
+            .borrow()
+            .frames
+            .iter()
+            .any(|f| f.copy.is_some() && output.owns(&f.output))
+    }
+
+    /// Whether a client asked for the cursor to be drawn in its copies of an output
+    ///
+    /// If so, render the cursor in the frames you present while copies are pending.
+    pub fn wants_cursor(&self, output: &Output) -> bool {
+        self.inner
+            .borrow()
+            .frames
+            .iter()
+            .any(|f| f.copy.is_some() && f.overlay_cursor && output.owns(&f.output))
+    }
+
+    /// Notify that a frame was presented on an output, and copy it to the clients waiting for it
+    ///
+    /// `presentation_time` is the time the frame was displayed, as a duration since the epoch
+    /// of `CLOCK_MONOTONIC`, and `damage` the regions of the output that changed since the
+    /// previous presented frame, in the pixel coordinates of its mode. `None` means the whole
+    /// output changed.
+    pub fn frame_presented<S: ScreencopySource>(
+        &self,
+        output: &Output,
+        presentation_time: Duration,
+        damage: Option<&[Rectangle]>,
+        source: &mut S,
+    ) {
+        let (ready, shm_format, log) = {
+            let mut inner = self.inner.borrow_mut();
+            let mut ready = Vec::new();
+            let mut i = 0;
+            while i < inner.frames.len() {
+                let frame = &inner.frames[i];
+                let is_ready = match frame.copy {
+                    Some((_, with_damage)) => {
+                        output.owns(&frame.output)
+                            && (!with_damage || region_damage(frame.region, damage).is_some())
+                    }
+                    None => false,
+                };
+                if is_ready {
+                    ready.push(inner.frames.remove(i));
+                } else {
+                    i += 1;
+                }
+            }
+            (ready, inner.shm_format, inner.log.clone())
+        };
+
+        for frame in ready {
+            let (buffer, with_damage) = frame.copy.clone().unwrap();
+            let copied = if buffer_data(&buffer).is_some() {
+                copy_to_shm(&buffer, frame.region, shm_format, source)
+            } else {
+                source.copy_to_dmabuf(&buffer, frame.region)
+            };
+            if !copied {
+                debug!(log, "Screencopy of a frame failed");
+                frame.frame.failed();
+                continue;
+            }
+            let flags = if source.y_inverted() {
+                Flags::YInvert
+            } else {
+                Flags::empty()
+            };
+            frame.frame.flags(flags);
+            if with_damage {
+                for rect in region_damage(frame.region, damage).unwrap_or_default() {
+                    frame.frame.damage(
+                        rect.x as u32,
+                        rect.y as u32,
+                        rect.width as u32,
+                        rect.height as u32,
+                    );
+                }
+            }
+            let secs = presentation_time.as_secs();
+            frame
+                .frame
+                .ready((secs >> 32) as u32, secs as u32, presentation_time.subsec_nanos());
+        }
+    }
+}
+
+impl ::std::fmt::Debug for ScreencopyManager {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("ScreencopyManager")
+            .field("frames", &inner.frames.len())
+            .field("shm_format", &inner.shm_format)
+            .field("dmabuf_format", &inner.dmabuf_format)
+            .finish()
+    }
+}
+
+// the damage of a frame inside a region, relative to the region, or `None` if it is unchanged
+fn region_damage(region: Rectangle, damage: Option<&[Rectangle]>) -> Option<Vec<Rectangle>> {
+    let damage = match damage {
+        Some(damage) => damage,
+        None => return Some(vec![Rectangle { x: 0, y: 0, ..region }]),
+    };
+    let rects = damage
+        .iter()
+        .filter_map(|rect| intersection(&region, rect))
+        .map(|rect| Rectangle {
+            x: rect.x - region.x,
+            y: rect.y - region.y,
+            ..rect
+        })
+        .collect::<Vec<_>>();
+    if rects.is_empty() {
+        None
+    } else {
+        Some(rects)
+    }
+}
+
+fn intersection(a: &Rectangle, b: &Rectangle) -> Option<Rectangle> {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    let width = (a.x + a.width).min(b.x + b.width) - x;
+    let height = (a.y + a.height).min(b.y + b.height) - y;
+    if width > 0 && height > 0 {
+        Some(Rectangle { x, y, width, height })
+    } else {
+        None
+    }
+}
+
+fn copy_to_shm<S: ScreencopySource>(
+    buffer: &WlBuffer,
+    region: Rectangle,
+    format: wl_shm::Format,
+    source: &mut S,
+) -> bool {
+    let data = match buffer_data(buffer) {
+        Some(data) => data,
+        None => return false,
+    };
+    let bpp = match bytes_per_pixel(format) {
+        Some(bpp) => bpp as usize,
+        None => return false,
+    };
+    let pixels = match source.read_pixels(region) {
+        Some(pixels) => pixels,
+        None => return false,
+    };
+    let row = region.width as usize * bpp;
+    let stride = data.stride as usize;
+    if pixels.len() < row * region.height as usize {
+        return false;
+    }
+    let contents = if stride == row {
+        pixels
+    } else {
+        let mut contents = vec![0; stride * region.height as usize];
+        for (src, dst) in pixels.chunks(row).zip(contents.chunks_mut(stride)) {
+            dst[..row].copy_from_slice(src);
+        }
+        contents
+    };
+    write_buffer_contents(buffer, &contents).is_ok()
+}
+
+/// Create a new screencopy manager global
+///
+/// Contents are copied into shm buffers of the given format, which should be the one your
+/// renderer reads back pixels in.
+pub fn init_screencopy_manager<L>(
+    display: &mut Display,
+    shm_format: wl_shm::Format,
+    logger: L,
+) -> (ScreencopyManager, Global<ZwlrScreencopyManagerV1>)
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "screencopy_handler"));
+    let manager = ScreencopyManager {
+        inner: Rc::new(RefCell::new(ManagerInner {
+            frames: Vec::new(),
+            shm_format,
+            dmabuf_format: None,
+            log,
+        })),
+    };
+
+    let handle = manager.clone();
+    let global = display.create_global::<ZwlrScreencopyManagerV1, _>(
+        3,
+        Filter::new(
+            move |(global, _version): (Main<ZwlrScreencopyManagerV1>, u32), _, _| {
+                let handle = handle.clone();
+                global.quick_assign(move |_global, request, _| match request {
+                    zwlr_screencopy_manager_v1::Request::CaptureOutput {
+                        frame,
+                        overlay_cursor,
+                        output,
+                    } => handle.new_frame(frame, overlay_cursor != 0, output, None),
+                    zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                        frame,
+                        overlay_cursor,
+                        output,
+                        x,
+                        y,
+                        width,
+                        height,
+                    } => handle.new_frame(
+                        frame,
+                        overlay_cursor != 0,
+                        output,
+                        Some(Rectangle { x, y, width, height }),
+                    ),
+                    zwlr_screencopy_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    );
+
+    (manager, global)
+}
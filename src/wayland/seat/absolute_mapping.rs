@@ -0,0 +1,187 @@
+use crate::utils::Rectangle;
+
+/// Rectangular area of an absolute pointing device, in normalized device coordinates
+///
+/// `(0.0, 0.0)` is the top-left and `(1.0, 1.0)` the bottom-right corner of the device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeviceArea {
+    /// horizontal position of the top-left corner of the area
+    pub x: f64,
+    /// vertical position of the top-left corner of the area
+    pub y: f64,
+    /// width of the area
+    pub width: f64,
+    /// height of the area
+    pub height: f64,
+}
+
+impl Default for DeviceArea {
+    fn default() -> Self {
+        DeviceArea {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        }
+    }
+}
+
+/// Mapping of an absolute pointing device to the compositor space
+///
+/// Absolute devices (tablets, touchscreen styluses, virtual pointers of remote desktop
+/// clients, ...) report positions relative to their own surface. By default this surface
+/// is stretched over the whole target area, this mapping allows to:
+///
+/// - only use a part of the device, see [`area`](AbsoluteMapping::area),
+/// - preserve the aspect ratio of the target, so that circles drawn on a tablet stay circles,
+/// - ignore a margin at the edges of the device, making it easier to reach the edges of the target,
+/// - restrict the device to a single output.
+///
+/// Feed it with normalized device positions, which you can retrieve from the events of
+/// your input backend using `position_transformed((1, 1))`, and forward the result to the seat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AbsoluteMapping {
+    /// Part of the device that is mapped to the target
+    ///
+    /// Positions outside of this area are ignored.
+    pub area: DeviceArea,
+    /// Fraction of the area at each edge that is clamped to the edges of the target
+    ///
+    /// For example, a value of `0.05` maps the inner 90% of the area onto the target.
+    pub dead_zone: f64,
+    /// Physical size of the device (in any unit), to preserve the aspect ratio of the target
+    ///
+    /// If set, the mapped area is shrunk (keeping it centered) until it has the same aspect
+    /// ratio as the target.
+    pub keep_aspect_ratio: Option<(f64, f64)>,
+    /// Restrict the device to this part of the compositor space, typically the geometry of an output
+    pub output: Option<Rectangle>,
+}
+
+impl Default for AbsoluteMapping {
+    fn default() -> Self {
+        AbsoluteMapping {
+            area: DeviceArea::default(),
+            dead_zone: 0.0,
+            keep_aspect_ratio: None,
+            output: None,
+        }
+    }
+}
+
+impl AbsoluteMapping {
+    /// Map a normalized device position into the compositor space
+    ///
+    /// `target` is the area the device should be mapped to, if it is not restricted to an
+    /// [`output`](AbsoluteMapping::output), typically the bounding box of all outputs.
+    ///
+    /// Returns `None` if the position lies outside of the mapped area of the device.
+    pub fn map(&self, position: (f64, f64), target: Rectangle) -> Option<(f64, f64)> {
+        let target = self.output.unwrap_or(target);
+        let area = self.effective_area(target);
+        if area.width <= 0.0 || area.height <= 0.0 {
+            return None;
+        }
+
+        let u = (position.0 - area.x) / area.width;
+        let v = (position.1 - area.y) / area.height;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        let dead_zone = self.dead_zone.max(0.0).min(0.49);
+        let scale = |c: f64| ((c - dead_zone) / (1.0 - 2.0 * dead_zone)).max(0.0).min(1.0);
+
+        Some((
+            target.x as f64 + scale(u) * target.width as f64,
+            target.y as f64 + scale(v) * target.height as f64,
+        ))
+    }
+
+    fn effective_area(&self, target: Rectangle) -> DeviceArea {
+        let (device_width, device_height) = match self.keep_aspect_ratio {
+            Some(size) if target.width > 0 && target.height > 0 => size,
+            _ => return self.area,
+        };
+        let area_ratio = (self.area.width * device_width) / (self.area.height * device_height);
+        let target_ratio = target.width as f64 / target.height as f64;
+        if !area_ratio.is_finite() || area_ratio <= 0.0 {
+            return self.area;
+        }
+
+        let mut area = self.area;
+        if area_ratio > target_ratio {
+            // the area is too wide, cut off the sides
+            area.width = self.area.width * target_ratio / area_ratio;
+            area.x += (self.area.width - area.width) / 2.0;
+        } else {
+            // the area is too high, cut off top and bottom
+            area.height = self.area.height * area_ratio / target_ratio;
+            area.y += (self.area.height - area.height) / 2.0;
+        }
+        area
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AbsoluteMapping, DeviceArea};
+    use crate::utils::Rectangle;
+
+    const TARGET: Rectangle = Rectangle {
+        x: 0,
+        y: 0,
+        width: 2000,
+        height: 1000,
+    };
+
+    #[test]
+    fn default_stretches() {
+        let mapping = AbsoluteMapping::default();
+        assert_eq!(mapping.map((0.5, 0.5), TARGET), Some((1000.0, 500.0)));
+        assert_eq!(mapping.map((1.0, 0.0), TARGET), Some((2000.0, 0.0)));
+    }
+
+    #[test]
+    fn area_and_output() {
+        let mapping = AbsoluteMapping {
+            area: DeviceArea {
+                x: 0.5,
+                y: 0.5,
+                width: 0.5,
+                height: 0.5,
+            },
+            output: Some(Rectangle {
+                x: 2000,
+                y: 0,
+                width: 1000,
+                height: 1000,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(mapping.map((0.25, 0.75), TARGET), None);
+        assert_eq!(mapping.map((0.75, 0.75), TARGET), Some((2500.0, 500.0)));
+    }
+
+    #[test]
+    fn aspect_ratio() {
+        // a square tablet mapped onto a 2:1 target only uses a centered band of it
+        let mapping = AbsoluteMapping {
+            keep_aspect_ratio: Some((100.0, 100.0)),
+            ..Default::default()
+        };
+        assert_eq!(mapping.map((0.5, 0.1), TARGET), None);
+        assert_eq!(mapping.map((0.0, 0.25), TARGET), Some((0.0, 0.0)));
+        assert_eq!(mapping.map((1.0, 0.75), TARGET), Some((2000.0, 1000.0)));
+    }
+
+    #[test]
+    fn dead_zone_clamps() {
+        let mapping = AbsoluteMapping {
+            dead_zone: 0.1,
+            ..Default::default()
+        };
+        assert_eq!(mapping.map((0.05, 0.95), TARGET), Some((0.0, 1000.0)));
+        assert_eq!(mapping.map((0.5, 0.5), TARGET), Some((1000.0, 500.0)));
+    }
+}
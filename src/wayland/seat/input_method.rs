@@ -0,0 +1,822 @@
+//! Input method support
+//!
+//! Input methods are clients composing text on behalf of other clients, for languages that
+//! cannot be typed directly on a keyboard. They bind the `zwp_input_method_v1` global of a seat,
+//! and are activated whenever a client enables a text input through `zwp_text_input_v1`.
+//!
+//! Several input methods can be registered on the same seat, in which case only one of them is
+//! active at a time. The [`InputMethodHandle`] lets you switch between them, or disable input
+//! methods altogether, either directly or through key bindings.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use wayland_protocols::unstable::{
+    input_method::v1::server::{
+        zwp_input_method_context_v1::{self, ZwpInputMethodContextV1},
+        zwp_input_method_v1::ZwpInputMethodV1,
+    },
+    text_input::v1::server::{
+        zwp_text_input_manager_v1::{self, ZwpTextInputManagerV1},
+        zwp_text_input_v1::{self, ZwpTextInputV1},
+    },
+};
+use wayland_server::{
+    protocol::{
+        wl_keyboard::{self, WlKeyboard},
+        wl_surface::WlSurface,
+    },
+    Display, Filter, Global, Main,
+};
+
+use super::{KeyboardHandle, Keysym, ModifiersState, Seat, SeatRc};
+use crate::backend::input::KeyState;
+
+/// A key combination triggering an input method action
+///
+/// Only the control, alt, shift and logo modifiers are compared, the state of caps lock and
+/// num lock is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImeBinding {
+    /// The modifiers that must be held
+    pub modifiers: ModifiersState,
+    /// The key that must be pressed
+    pub keysym: Keysym,
+}
+
+impl ImeBinding {
+    fn matches(&self, modifiers: &ModifiersState, keysym: Keysym) -> bool {
+        self.keysym == keysym
+            && self.modifiers.ctrl == modifiers.ctrl
+            && self.modifiers.alt == modifiers.alt
+            && self.modifiers.shift == modifiers.shift
+            && self.modifiers.logo == modifiers.logo
+    }
+}
+
+/// The key bindings handled by [`InputMethodHandle::handle_keybinding`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputMethodBindings {
+    /// Enable or disable input methods
+    pub toggle: Option<ImeBinding>,
+    /// Switch to the next registered input method
+    pub next: Option<ImeBinding>,
+    /// Switch to the previous registered input method
+    pub previous: Option<ImeBinding>,
+}
+
+// the text input currently activated on the seat
+struct ActiveTextInput {
+    text_input: ZwpTextInputV1,
+    // the state set by the client, sent to the input methods activated later
+    surrounding_text: Option<(String, u32, u32)>,
+    content_type: Option<(u32, u32)>,
+    preferred_language: Option<String>,
+    serial: u32,
+}
+
+struct InputMethodState {
+    input_methods: Vec<ZwpInputMethodV1>,
+    current: usize,
+    enabled: bool,
+    bindings: InputMethodBindings,
+    text_input: Option<ActiveTextInput>,
+    context: Option<ZwpInputMethodContextV1>,
+    grab: Option<WlKeyboard>,
+    seat: Weak<SeatRc>,
+    log: ::slog::Logger,
+}
+
+impl InputMethodState {
+    fn keyboard(&self) -> Option<KeyboardHandle> {
+        self.seat.upgrade().and_then(|arc| Seat { arc }.get_keyboard())
+    }
+
+    fn is_current_context(&self, context: &ZwpInputMethodContextV1) -> bool {
+        self.context
+            .as_ref()
+            .map(|c| c.as_ref().equals(context.as_ref()))
+            .unwrap_or(false)
+    }
+
+    fn is_active_text_input(&self, text_input: &ZwpTextInputV1) -> bool {
+        self.text_input
+            .as_ref()
+            .map(|t| t.text_input.as_ref().equals(text_input.as_ref()))
+            .unwrap_or(false)
+    }
+
+    fn release_grab(&mut self) {
+        if self.grab.take().is_some() {
+            if let Some(keyboard) = self.keyboard() {
+                keyboard.set_ime_grab(None);
+            }
+        }
+    }
+
+    // deactivate the current input method, and drop the text it was composing
+    fn deactivate(&mut self) {
+        if let Some(context) = self.context.take() {
+            trace!(self.log, "Deactivating input method"; "index" => self.current);
+            if let Some(input_method) = self.input_methods.get(self.current) {
+                input_method.deactivate(&context);
+            }
+            self.release_grab();
+            if let Some(ref active) = self.text_input {
+                active
+                    .text_input
+                    .preedit_string(active.serial, String::new(), String::new());
+            }
+        }
+    }
+}
+
+/// A handle to the input methods of a seat
+///
+/// It is created by [`init_input_method`], and can be cloned.
+#[derive(Clone)]
+pub struct InputMethodHandle {
+    inner: Rc<RefCell<InputMethodState>>,
+}
+
+impl InputMethodHandle {
+    // activate the current input method for the active text input, if enabled
+    fn activate(&self, inner: &mut InputMethodState) {
+        if !inner.enabled || inner.context.is_some() {
+            return;
+        }
+        let active = match inner.text_input {
+            Some(ref active) => active,
+            None => return,
+        };
+        let input_method = match inner.input_methods.get(inner.current) {
+            Some(input_method) => input_method,
+            None => return,
+        };
+        let context = match input_method
+            .as_ref()
+            .client()
+            .and_then(|client| client.create_resource::<ZwpInputMethodContextV1>(1))
+        {
+            Some(context) => context,
+            None => return,
+        };
+        trace!(inner.log, "Activating input method"; "index" => inner.current);
+        implement_context(&context, self.clone());
+        input_method.activate(&context);
+        if let Some((ref text, cursor, anchor)) = active.surrounding_text {
+            context.surrounding_text(text.clone(), cursor, anchor);
+        }
+        if let Some((hint, purpose)) = active.content_type {
+            context.content_type(hint, purpose);
+        }
+        if let Some(ref language) = active.preferred_language {
+            context.preferred_language(language.clone());
+        }
+        context.commit_state(active.serial);
+        inner.context = Some((*context).clone());
+    }
+
+    /// Enable or disable input methods on this seat
+    ///
+    /// While disabled, text inputs are not forwarded to any input method, and the keys are
+    /// directly sent to the focused client.
+    pub fn set_enabled(&self, enabled: bool) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.enabled == enabled {
+            return;
+        }
+        inner.enabled = enabled;
+        if enabled {
+            self.activate(&mut inner);
+        } else {
+            inner.deactivate();
+        }
+    }
+
+    /// Whether input methods are enabled on this seat
+    pub fn is_enabled(&self) -> bool {
+        self.inner.borrow().enabled
+    }
+
+    /// Enable input methods if they are disabled, and disable them otherwise
+    pub fn toggle(&self) {
+        let enabled = self.is_enabled();
+        self.set_enabled(!enabled);
+    }
+
+    /// The number of input methods registered on this seat
+    pub fn count(&self) -> usize {
+        self.inner.borrow().input_methods.len()
+    }
+
+    /// The index of the selected input method, in the order they were registered
+    ///
+    /// Returns `None` if no input method is registered.
+    pub fn current(&self) -> Option<usize> {
+        let inner = self.inner.borrow();
+        if inner.input_methods.is_empty() {
+            None
+        } else {
+            Some(inner.current)
+        }
+    }
+
+    /// Select the input method with the given index
+    ///
+    /// If a text input is active, the previous input method is deactivated and the text
+    /// it was composing is dropped, before the new one is activated. Does nothing if the
+    /// index is out of range.
+    pub fn select(&self, index: usize) {
+        let mut inner = self.inner.borrow_mut();
+        if index >= inner.input_methods.len() || index == inner.current {
+            return;
+        }
+        inner.deactivate();
+        inner.current = index;
+        self.activate(&mut inner);
+    }
+
+    /// Select the next registered input method, wrapping around
+    pub fn next(&self) {
+        let (current, count) = {
+            let inner = self.inner.borrow();
+            (inner.current, inner.input_methods.len())
+        };
+        self.select(cycle(current, count, true));
+    }
+
+    /// Select the previous registered input method, wrapping around
+    pub fn previous(&self) {
+        let (current, count) = {
+            let inner = self.inner.borrow();
+            (inner.current, inner.input_methods.len())
+        };
+        self.select(cycle(current, count, false));
+    }
+
+    /// Change the key bindings handled by [`handle_keybinding`](InputMethodHandle::handle_keybinding)
+    pub fn set_bindings(&self, bindings: InputMethodBindings) {
+        self.inner.borrow_mut().bindings = bindings;
+    }
+
+    /// Process a key binding
+    ///
+    /// Call it from the filter of [`KeyboardHandle::input`]. It returns `true` if the key
+    /// matches one of the bindings, in which case it should not be forwarded to the client.
+    /// The action of the binding is triggered when the key is pressed.
+    pub fn handle_keybinding(&self, modifiers: &ModifiersState, keysym: Keysym, state: KeyState) -> bool {
+        let bindings = self.inner.borrow().bindings;
+        let matches = |binding: Option<ImeBinding>| {
+            binding
+                .map(|binding| binding.matches(modifiers, keysym))
+                .unwrap_or(false)
+        };
+        let action: fn(&InputMethodHandle) = if matches(bindings.toggle) {
+            InputMethodHandle::toggle
+        } else if matches(bindings.next) {
+            InputMethodHandle::next
+        } else if matches(bindings.previous) {
+            InputMethodHandle::previous
+        } else {
+            return false;
+        };
+        if state == KeyState::Pressed {
+            action(self);
+        }
+        true
+    }
+
+    fn add_input_method(&self, input_method: ZwpInputMethodV1) {
+        let mut inner = self.inner.borrow_mut();
+        trace!(inner.log, "New input method"; "index" => inner.input_methods.len());
+        inner.input_methods.push(input_method);
+        self.activate(&mut inner);
+    }
+
+    fn remove_input_method(&self, input_method: &ZwpInputMethodV1) {
+        let mut inner = self.inner.borrow_mut();
+        let index = match inner
+            .input_methods
+            .iter()
+            .position(|im| im.as_ref().equals(input_method.as_ref()))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        trace!(inner.log, "Input method removed"; "index" => index);
+        if index == inner.current {
+            inner.deactivate();
+        }
+        inner.input_methods.remove(index);
+        if index < inner.current || inner.current >= inner.input_methods.len() {
+            inner.current = inner.current.saturating_sub(1);
+        }
+        self.activate(&mut inner);
+    }
+
+    fn activate_text_input(&self, text_input: &ZwpTextInputV1, surface: &WlSurface) {
+        let mut inner = self.inner.borrow_mut();
+        inner.deactivate();
+        if let Some(previous) = inner.text_input.take() {
+            previous.text_input.leave();
+        }
+        inner.text_input = Some(ActiveTextInput {
+            text_input: text_input.clone(),
+            surrounding_text: None,
+            content_type: None,
+            preferred_language: None,
+            serial: 0,
+        });
+        text_input.enter(surface);
+        self.activate(&mut inner);
+    }
+
+    fn deactivate_text_input(&self, text_input: &ZwpTextInputV1, send_leave: bool) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.is_active_text_input(text_input) {
+            inner.deactivate();
+            inner.text_input = None;
+            if send_leave {
+                text_input.leave();
+            }
+        }
+    }
+
+    // access the state of a text input and the context of its input method, if it is active
+    fn with_text_input<F>(&self, text_input: &ZwpTextInputV1, f: F)
+    where
+        F: FnOnce(&mut ActiveTextInput, Option<&ZwpInputMethodContextV1>),
+    {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.is_active_text_input(text_input) {
+            return;
+        }
+        let InputMethodState {
+            ref mut text_input,
+            ref context,
+            ..
+        } = *inner;
+        if let Some(active) = text_input.as_mut() {
+            f(active, context.as_ref());
+        }
+    }
+}
+
+impl ::std::fmt::Debug for InputMethodHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("InputMethodHandle")
+            .field("input_methods", &inner.input_methods.len())
+            .field("current", &inner.current)
+            .field("enabled", &inner.enabled)
+            .finish()
+    }
+}
+
+fn cycle(current: usize, count: usize, forward: bool) -> usize {
+    if count == 0 {
+        0
+    } else if forward {
+        (current + 1) % count
+    } else {
+        (current + count - 1) % count
+    }
+}
+
+/// Create the input method global of a seat
+///
+/// Input methods are enabled by default. You are provided an [`InputMethodHandle`] to switch
+/// between the registered input methods, which is also stored in the
+/// [`user_data`](Seat::user_data) of the seat, to be found by the text inputs of its clients.
+///
+/// ```no_run
+/// # extern crate wayland_server;
+/// # extern crate smithay;
+/// # use smithay::backend::input::KeyState;
+/// use smithay::wayland::seat::{
+///     init_input_method, init_text_input_manager, keysyms, ImeBinding, InputMethodBindings,
+///     ModifiersState, Seat,
+/// };
+/// # let mut display = wayland_server::Display::new();
+/// # let seat: Seat = unimplemented!();
+///
+/// let bindings = InputMethodBindings {
+///     // switch input methods with Super+Space
+///     next: Some(ImeBinding {
+///         modifiers: ModifiersState {
+///             ctrl: false,
+///             alt: false,
+///             shift: false,
+///             caps_lock: false,
+///             logo: true,
+///             num_lock: false,
+///         },
+///         keysym: keysyms::KEY_space,
+///     }),
+///     ..Default::default()
+/// };
+/// let (input_method, _global) = init_input_method(&mut display, &seat, bindings, None);
+/// init_text_input_manager(&mut display, None);
+///
+/// // in your keyboard input filter
+/// # let (modifiers, keysym, state): (ModifiersState, u32, KeyState) = unimplemented!();
+/// if input_method.handle_keybinding(&modifiers, keysym, state) {
+///     /* do not forward this key to the client */
+/// }
+/// ```
+pub fn init_input_method<L>(
+    display: &mut Display,
+    seat: &Seat,
+    bindings: InputMethodBindings,
+    logger: L,
+) -> (InputMethodHandle, Global<ZwpInputMethodV1>)
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "input_method_handler"));
+    let handle = InputMethodHandle {
+        inner: Rc::new(RefCell::new(InputMethodState {
+            input_methods: Vec::new(),
+            current: 0,
+            enabled: true,
+            bindings,
+            text_input: None,
+            context: None,
+            grab: None,
+            seat: Rc::downgrade(&seat.arc),
+            log,
+        })),
+    };
+    seat.user_data().insert_if_missing(|| handle.clone());
+
+    let global_handle = handle.clone();
+    let global = display.create_global::<ZwpInputMethodV1, _>(
+        1,
+        Filter::new(
+            move |(input_method, _version): (Main<ZwpInputMethodV1>, u32), _, _| {
+                // input methods have no requests
+                input_method.quick_assign(|_, _, _| {});
+                let destructor_handle = global_handle.clone();
+                input_method.assign_destructor(Filter::new(move |input_method: ZwpInputMethodV1, _, _| {
+                    destructor_handle.remove_input_method(&input_method);
+                }));
+                global_handle.add_input_method((*input_method).clone());
+            },
+        ),
+    );
+    (handle, global)
+}
+
+fn implement_context(context: &Main<ZwpInputMethodContextV1>, handle: InputMethodHandle) {
+    let destructor_handle = handle.clone();
+    context.quick_assign(move |context, request, _| {
+        let mut inner = handle.inner.borrow_mut();
+        if !inner.is_current_context(&context) {
+            // this input method was deactivated
+            return;
+        }
+        let keyboard = inner.keyboard();
+        let text_input = match inner.text_input {
+            Some(ref active) => active.text_input.clone(),
+            None => return,
+        };
+        match request {
+            zwp_input_method_context_v1::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            zwp_input_method_context_v1::Request::CommitString { serial, text } => {
+                text_input.commit_string(serial, text);
+            }
+            zwp_input_method_context_v1::Request::PreeditString { serial, text, commit } => {
+                text_input.preedit_string(serial, text, commit);
+            }
+            zwp_input_method_context_v1::Request::PreeditStyling { index, length, style } => {
+                text_input.preedit_styling(index, length, style);
+            }
+            zwp_input_method_context_v1::Request::PreeditCursor { index } => {
+                text_input.preedit_cursor(index);
+            }
+            zwp_input_method_context_v1::Request::DeleteSurroundingText { index, length } => {
+                text_input.delete_surrounding_text(index, length);
+            }
+            zwp_input_method_context_v1::Request::CursorPosition { index, anchor } => {
+                text_input.cursor_position(index, anchor);
+            }
+            zwp_input_method_context_v1::Request::ModifiersMap { map } => {
+                text_input.modifiers_map(map);
+            }
+            zwp_input_method_context_v1::Request::Keysym {
+                serial,
+                time,
+                sym,
+                state,
+                modifiers,
+            } => {
+                text_input.keysym(serial, time, sym, state, modifiers);
+            }
+            zwp_input_method_context_v1::Request::GrabKeyboard { keyboard: grab } => {
+                grab.quick_assign(|_, request, _| match request {
+                    wl_keyboard::Request::Release => {
+                        // All is already handled by our destructor
+                    }
+                    _ => unreachable!(),
+                });
+                let grab_handle = handle.clone();
+                grab.assign_destructor(Filter::new(move |grab: WlKeyboard, _, _| {
+                    let mut inner = grab_handle.inner.borrow_mut();
+                    let current = inner
+                        .grab
+                        .as_ref()
+                        .map(|g| g.as_ref().equals(grab.as_ref()))
+                        .unwrap_or(false);
+                    if current {
+                        inner.release_grab();
+                    }
+                }));
+                inner.release_grab();
+                if let Some(keyboard) = keyboard {
+                    keyboard.set_ime_grab(Some((*grab).clone()));
+                    inner.grab = Some((*grab).clone());
+                }
+            }
+            zwp_input_method_context_v1::Request::Key {
+                serial,
+                time,
+                key,
+                state,
+            } => {
+                if let (Some(keyboard), Some(state)) = (keyboard, wl_keyboard::KeyState::from_raw(state)) {
+                    keyboard.forward_key(serial, time, key, state);
+                }
+            }
+            zwp_input_method_context_v1::Request::Modifiers {
+                serial,
+                mods_depressed,
+                mods_latched,
+                mods_locked,
+                group,
+            } => {
+                if let Some(keyboard) = keyboard {
+                    keyboard.forward_modifiers(serial, (mods_depressed, mods_latched, mods_locked, group));
+                }
+            }
+            zwp_input_method_context_v1::Request::Language { serial, language } => {
+                text_input.language(serial, language);
+            }
+            zwp_input_method_context_v1::Request::TextDirection { serial, direction } => {
+                text_input.text_direction(serial, direction);
+            }
+            _ => unreachable!(),
+        }
+    });
+    context.assign_destructor(Filter::new(move |context: ZwpInputMethodContextV1, _, _| {
+        let mut inner = destructor_handle.inner.borrow_mut();
+        if inner.is_current_context(&context) {
+            // the input method gave up on the text input
+            inner.context = None;
+            inner.release_grab();
+        }
+    }));
+}
+
+/// Create the text input manager global
+///
+/// Text inputs are forwarded to the input methods of the seat they are activated on, as
+/// created by [`init_input_method`].
+pub fn init_text_input_manager<L>(display: &mut Display, logger: L) -> Global<ZwpTextInputManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "text_input_handler"));
+
+    display.create_global::<ZwpTextInputManagerV1, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpTextInputManagerV1>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_text_input_manager_v1::Request::CreateTextInput { id } => {
+                        implement_text_input(id, log.clone());
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+fn implement_text_input(text_input: Main<ZwpTextInputV1>, log: ::slog::Logger) {
+    // the input methods of the seat the text input is activated on
+    let seat_handle: Rc<RefCell<Option<InputMethodHandle>>> = Rc::new(RefCell::new(None));
+    let destructor_seat_handle = seat_handle.clone();
+    text_input.quick_assign(move |text_input, request, _| {
+        let text_input = &*text_input;
+        if let zwp_text_input_v1::Request::Activate { seat, surface } = request {
+            let handle = Seat::from_resource(&seat)
+                .and_then(|seat| seat.user_data().get::<InputMethodHandle>().cloned());
+            if let Some(previous) = seat_handle.borrow_mut().take() {
+                previous.deactivate_text_input(text_input, false);
+            }
+            match handle {
+                Some(handle) => {
+                    handle.activate_text_input(text_input, &surface);
+                    *seat_handle.borrow_mut() = Some(handle);
+                }
+                None => warn!(log, "Text input activated on a seat without input methods"),
+            }
+            return;
+        }
+        let handle = match *seat_handle.borrow() {
+            Some(ref handle) => handle.clone(),
+            None => return,
+        };
+        match request {
+            zwp_text_input_v1::Request::Deactivate { seat } => {
+                let same_seat = Seat::from_resource(&seat)
+                    .and_then(|seat| {
+                        seat.user_data()
+                            .get::<InputMethodHandle>()
+                            .map(|h| Rc::ptr_eq(&h.inner, &handle.inner))
+                    })
+                    .unwrap_or(false);
+                if same_seat {
+                    handle.deactivate_text_input(text_input, true);
+                    *seat_handle.borrow_mut() = None;
+                }
+            }
+            zwp_text_input_v1::Request::ShowInputPanel | zwp_text_input_v1::Request::HideInputPanel => {
+                // input panels are not supported
+            }
+            zwp_text_input_v1::Request::Reset => handle.with_text_input(text_input, |_, context| {
+                if let Some(context) = context {
+                    context.reset();
+                }
+            }),
+            zwp_text_input_v1::Request::SetSurroundingText { text, cursor, anchor } => handle
+                .with_text_input(text_input, |active, context| {
+                    if let Some(context) = context {
+                        context.surrounding_text(text.clone(), cursor, anchor);
+                    }
+                    active.surrounding_text = Some((text, cursor, anchor));
+                }),
+            zwp_text_input_v1::Request::SetContentType { hint, purpose } => {
+                handle.with_text_input(text_input, |active, context| {
+                    if let Some(context) = context {
+                        context.content_type(hint, purpose);
+                    }
+                    active.content_type = Some((hint, purpose));
+                })
+            }
+            zwp_text_input_v1::Request::SetCursorRectangle { .. } => {
+                // only useful to place input panels, which are not supported
+            }
+            zwp_text_input_v1::Request::SetPreferredLanguage { language } => {
+                handle.with_text_input(text_input, |active, context| {
+                    if let Some(context) = context {
+                        context.preferred_language(language.clone());
+                    }
+                    active.preferred_language = Some(language);
+                })
+            }
+            zwp_text_input_v1::Request::CommitState { serial } => {
+                handle.with_text_input(text_input, |active, context| {
+                    if let Some(context) = context {
+                        context.commit_state(serial);
+                    }
+                    active.serial = serial;
+                })
+            }
+            zwp_text_input_v1::Request::InvokeAction { button, index } => {
+                handle.with_text_input(text_input, |_, context| {
+                    if let Some(context) = context {
+                        context.invoke_action(button, index);
+                    }
+                })
+            }
+            _ => unreachable!(),
+        }
+    });
+    text_input.assign_destructor(Filter::new(move |text_input: ZwpTextInputV1, _, _| {
+        if let Some(handle) = destructor_seat_handle.borrow_mut().take() {
+            handle.deactivate_text_input(&text_input, false);
+        }
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{seat::keyboard::xkb::XkbState, SERIAL_COUNTER};
+    use std::os::unix::{io::IntoRawFd, net::UnixStream};
+
+    // a keymap with a single key, the toggle binding of the tests
+    const KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes {
+        minimum = 8;
+        maximum = 255;
+        <AD01> = 24;
+    };
+    xkb_types {
+        type "ONE_LEVEL" {
+            modifiers = none;
+            level_name[Level1] = "Any";
+        };
+    };
+    xkb_compatibility {
+    };
+    xkb_symbols {
+        key <AD01> { [ q ] };
+    };
+};"#;
+
+    // evdev keycode of the key of the keymap
+    const KEY_Q: u32 = 16;
+
+    fn modifiers(ctrl: bool, logo: bool) -> ModifiersState {
+        ModifiersState {
+            ctrl,
+            alt: false,
+            shift: false,
+            caps_lock: false,
+            logo,
+            num_lock: false,
+        }
+    }
+
+    #[test]
+    fn binding_ignores_locks() {
+        let binding = ImeBinding {
+            modifiers: modifiers(false, true),
+            keysym: super::super::keysyms::KEY_space,
+        };
+        let mut locked = modifiers(false, true);
+        locked.caps_lock = true;
+        locked.num_lock = true;
+        assert!(binding.matches(&locked, super::super::keysyms::KEY_space));
+        assert!(!binding.matches(&modifiers(true, true), super::super::keysyms::KEY_space));
+        assert!(!binding.matches(&modifiers(false, true), super::super::keysyms::KEY_a));
+    }
+
+    #[test]
+    fn toggle_binding_releases_grab() {
+        let log = ::slog::Logger::root(::slog::Discard, o!());
+        let mut display = Display::new();
+        let (server, _client) = UnixStream::pair().unwrap();
+        let client = unsafe { display.create_client(server.into_raw_fd(), &mut ()) };
+
+        let keyboard = KeyboardHandle::from_xkb(
+            XkbState::new_from_string(KEYMAP).unwrap(),
+            200,
+            25,
+            log.clone(),
+            |_| {},
+        );
+        let seat = Seat::with_name("seat0".into(), &log);
+        seat.arc.inner.borrow_mut().keyboard = Some(keyboard.clone());
+
+        let grab = client.create_resource::<WlKeyboard>(4).unwrap();
+        let context = client.create_resource::<ZwpInputMethodContextV1>(1).unwrap();
+        keyboard.set_ime_grab(Some((*grab).clone()));
+        let handle = InputMethodHandle {
+            inner: Rc::new(RefCell::new(InputMethodState {
+                input_methods: Vec::new(),
+                current: 0,
+                enabled: true,
+                bindings: InputMethodBindings {
+                    toggle: Some(ImeBinding {
+                        modifiers: modifiers(false, false),
+                        keysym: super::super::keysyms::KEY_q,
+                    }),
+                    ..Default::default()
+                },
+                text_input: None,
+                context: Some((*context).clone()),
+                grab: Some((*grab).clone()),
+                seat: Rc::downgrade(&seat.arc),
+                log,
+            })),
+        };
+
+        // the binding is processed while the keyboard handles the key
+        keyboard.input(
+            KEY_Q,
+            KeyState::Pressed,
+            SERIAL_COUNTER.next_serial(),
+            0,
+            |modifiers, keysym| !handle.handle_keybinding(modifiers, keysym, KeyState::Pressed),
+        );
+        assert!(!handle.is_enabled());
+        assert!(handle.inner.borrow().grab.is_none());
+    }
+
+    #[test]
+    fn cycling() {
+        assert_eq!(cycle(0, 3, true), 1);
+        assert_eq!(cycle(2, 3, true), 0);
+        assert_eq!(cycle(0, 3, false), 2);
+        assert_eq!(cycle(0, 0, true), 0);
+    }
+}
@@ -0,0 +1,455 @@
+//! Compositor key bindings
+//!
+//! [`KeyBindings`] maps key combinations to actions of the compositor. It processes the
+//! keystrokes of a seat in place of [`KeyboardHandle::input`](super::KeyboardHandle::input):
+//! the keys matching a binding are consumed, and never reach the focused client.
+//!
+//! When given the handle of the keyboard shortcuts inhibit protocol, the bindings are suspended
+//! while the focused surface has an active inhibitor, except for the bindings registered with
+//! [`bind_uninhibitable`](KeyBindings::bind_uninhibitable).
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use super::{Keysym, ModifiersState, Seat};
+use crate::{
+    backend::input::KeyState,
+    wayland::{keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitHandle, Serial},
+};
+
+/// A key combination triggering a key binding
+///
+/// The modifiers must match exactly: a combination is not triggered while other modifiers
+/// are held, so that for example `Logo+Return` and `Logo+Shift+Return` can be bound to
+/// different actions. Compositors that used to check for the modifiers they need, whatever
+/// the other modifiers, must bind each accepted combination explicitly.
+///
+/// Only the control, alt, shift and logo modifiers are compared, the state of caps lock and
+/// num lock is ignored, both when matching keystrokes and when comparing combinations. The
+/// keysym is the one produced by the key with the current modifiers but without the locks, so
+/// that for example `Shift+q` produces `KEY_Q`, but `q` produces `KEY_q` even while caps lock
+/// is on.
+#[derive(Debug, Clone, Copy)]
+pub struct KeyCombination {
+    /// The modifiers that must be held
+    pub modifiers: ModifiersState,
+    /// The key that must be pressed
+    pub keysym: Keysym,
+}
+
+impl KeyCombination {
+    // the compared parts of the combination
+    fn key(&self) -> (bool, bool, bool, bool, Keysym) {
+        (
+            self.modifiers.ctrl,
+            self.modifiers.alt,
+            self.modifiers.shift,
+            self.modifiers.logo,
+            self.keysym,
+        )
+    }
+
+    fn matches(&self, modifiers: &ModifiersState, keysym: Keysym) -> bool {
+        let other = KeyCombination {
+            modifiers: *modifiers,
+            keysym,
+        };
+        *self == other
+    }
+}
+
+impl PartialEq for KeyCombination {
+    fn eq(&self, other: &KeyCombination) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for KeyCombination {}
+
+impl Hash for KeyCombination {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+struct KeyBinding<A> {
+    combination: KeyCombination,
+    action: A,
+    inhibitable: bool,
+}
+
+/// A set of key bindings, mapping key combinations to actions of type `A`
+///
+/// ```no_run
+/// # extern crate wayland_server;
+/// use smithay::wayland::seat::{keysyms, KeyBindings, KeyCombination, ModifiersState, Seat};
+/// # use smithay::backend::input::KeyState;
+///
+/// #[derive(Clone)]
+/// enum Action {
+///     Quit,
+///     RunTerminal,
+/// }
+///
+/// let logo = ModifiersState {
+///     ctrl: false,
+///     alt: false,
+///     shift: false,
+///     caps_lock: false,
+///     logo: true,
+///     num_lock: false,
+/// };
+/// let mut bindings = KeyBindings::new();
+/// let logo_return = KeyCombination { modifiers: logo, keysym: keysyms::KEY_Return };
+/// let logo_q = KeyCombination { modifiers: logo, keysym: keysyms::KEY_q };
+/// bindings.bind(logo_return, Action::RunTerminal);
+/// // users can always quit, even if a client inhibits the keyboard shortcuts
+/// bindings.bind_uninhibitable(logo_q, Action::Quit);
+///
+/// // for each keystroke of the input backend
+/// # let seat: Seat = unimplemented!();
+/// # let (keycode, state, serial, time): (u32, KeyState, smithay::wayland::Serial, u32) = unimplemented!();
+/// match bindings.input(&seat, keycode, state, serial, time) {
+///     Some(Action::Quit) => { /* quit the compositor */ }
+///     Some(Action::RunTerminal) => { /* spawn a terminal */ }
+///     None => { /* the key was forwarded to the client, or was the release of a binding */ }
+/// }
+/// ```
+pub struct KeyBindings<A> {
+    bindings: Vec<KeyBinding<A>>,
+    // keys whose press triggered a binding, their release is consumed as well
+    consumed: Vec<u32>,
+    inhibit: Option<KeyboardShortcutsInhibitHandle>,
+}
+
+impl<A: Clone> KeyBindings<A> {
+    /// Create an empty set of key bindings
+    pub fn new() -> KeyBindings<A> {
+        KeyBindings {
+            bindings: Vec::new(),
+            consumed: Vec::new(),
+            inhibit: None,
+        }
+    }
+
+    /// Bind a key combination to an action
+    ///
+    /// It replaces any previous binding of the same combination. This binding is suspended
+    /// while the focused client inhibits the keyboard shortcuts.
+    pub fn bind(&mut self, combination: KeyCombination, action: A) {
+        self.insert(combination, action, true);
+    }
+
+    /// Bind a key combination to an action that cannot be inhibited by clients
+    ///
+    /// Use it for the bindings that let users regain control of the compositor, for example
+    /// to [`deactivate_all`](KeyboardShortcutsInhibitHandle::deactivate_all) the inhibitors.
+    pub fn bind_uninhibitable(&mut self, combination: KeyCombination, action: A) {
+        self.insert(combination, action, false);
+    }
+
+    /// Remove the binding of a key combination, returning its action
+    pub fn unbind(&mut self, combination: &KeyCombination) -> Option<A> {
+        let index = self
+            .bindings
+            .iter()
+            .position(|binding| binding.combination == *combination)?;
+        Some(self.bindings.remove(index).action)
+    }
+
+    /// Honor the keyboard shortcuts inhibitors of this handle
+    pub fn set_inhibit_handle(&mut self, handle: KeyboardShortcutsInhibitHandle) {
+        self.inhibit = Some(handle);
+    }
+
+    /// Process a keystroke of a seat
+    ///
+    /// Call it instead of [`KeyboardHandle::input`](super::KeyboardHandle::input) for the
+    /// keyboard of the seat. If the key matches a binding it is not forwarded to the client,
+    /// and the action of the binding is returned when the key is pressed. The release of that
+    /// key is consumed as well, even if the modifiers changed in between, so that clients never
+    /// receive unbalanced key events.
+    ///
+    /// Nothing happens if the seat has no keyboard.
+    pub fn input(
+        &mut self,
+        seat: &Seat,
+        keycode: u32,
+        state: KeyState,
+        serial: Serial,
+        time: u32,
+    ) -> Option<A> {
+        let keyboard = seat.get_keyboard()?;
+        let inhibited = keyboard
+            .current_focus()
+            .map(|focus| self.is_inhibited(seat, &focus))
+            .unwrap_or(false);
+        // the keysym the filter receives depends on the locks, the bindings must not
+        let keysym = keyboard.key_get_one_sym_without_locks(keycode);
+        let mut action = None;
+        keyboard.input(keycode, state, serial, time, |modifiers, _| {
+            let (forward, triggered) = self.process(keycode, state, modifiers, keysym, inhibited);
+            action = triggered;
+            forward
+        });
+        action
+    }
+
+    // decide whether a keystroke is forwarded to the client, and which action it triggers
+    fn process(
+        &mut self,
+        keycode: u32,
+        state: KeyState,
+        modifiers: &ModifiersState,
+        keysym: Keysym,
+        inhibited: bool,
+    ) -> (bool, Option<A>) {
+        match state {
+            KeyState::Pressed => {
+                let binding = self.bindings.iter().find(|binding| {
+                    (!inhibited || !binding.inhibitable) && binding.combination.matches(modifiers, keysym)
+                });
+                match binding {
+                    Some(binding) => {
+                        let action = binding.action.clone();
+                        if !self.consumed.contains(&keycode) {
+                            self.consumed.push(keycode);
+                        }
+                        (false, Some(action))
+                    }
+                    None => (true, None),
+                }
+            }
+            KeyState::Released => match self.consumed.iter().position(|&k| k == keycode) {
+                Some(index) => {
+                    self.consumed.remove(index);
+                    (false, None)
+                }
+                None => (true, None),
+            },
+        }
+    }
+
+    fn insert(&mut self, combination: KeyCombination, action: A, inhibitable: bool) {
+        self.unbind(&combination);
+        self.bindings.push(KeyBinding {
+            combination,
+            action,
+            inhibitable,
+        });
+    }
+
+    fn is_inhibited(&self, seat: &Seat, focus: &WlSurface) -> bool {
+        self.inhibit
+            .as_ref()
+            .map(|handle| handle.is_inhibited(seat, focus))
+            .unwrap_or(false)
+    }
+}
+
+impl<A: Clone> Default for KeyBindings<A> {
+    fn default() -> Self {
+        KeyBindings::new()
+    }
+}
+
+impl<A: fmt::Debug> fmt::Debug for KeyBindings<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyBindings")
+            .field(
+                "bindings",
+                &self
+                    .bindings
+                    .iter()
+                    .map(|binding| (&binding.combination, &binding.action))
+                    .collect::<Vec<_>>(),
+            )
+            .field("consumed", &self.consumed)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyBindings, KeyCombination};
+    use crate::{
+        backend::input::KeyState,
+        wayland::seat::{keyboard::xkb::XkbState, keysyms, ModifiersState},
+    };
+
+    // a keymap with a single letter key, affected by caps lock
+    const KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes {
+        minimum = 8;
+        maximum = 255;
+        <AD01> = 24;
+        <LCTL> = 37;
+        <CAPS> = 66;
+    };
+    xkb_types {
+        type "ONE_LEVEL" {
+            modifiers = none;
+            level_name[Level1] = "Any";
+        };
+        type "ALPHABETIC" {
+            modifiers = Shift+Lock;
+            map[Shift] = Level2;
+            map[Lock] = Level2;
+            level_name[Level1] = "Base";
+            level_name[Level2] = "Caps";
+        };
+    };
+    xkb_compatibility {
+        interpret Control_L {
+            action = SetMods(modifiers = Control);
+        };
+        interpret Caps_Lock {
+            action = LockMods(modifiers = Lock);
+        };
+    };
+    xkb_symbols {
+        key <AD01> { type = "ALPHABETIC", [ q, Q ] };
+        key <LCTL> { [ Control_L ] };
+        key <CAPS> { [ Caps_Lock ] };
+        modifier_map Control { <LCTL> };
+        modifier_map Lock { <CAPS> };
+    };
+};"#;
+
+    // evdev keycodes of the keys of the keymap
+    const KEY_Q: u32 = 16;
+    const KEY_LEFTCTRL: u32 = 29;
+
+    fn modifiers(ctrl: bool, shift: bool) -> ModifiersState {
+        ModifiersState {
+            ctrl,
+            alt: false,
+            shift,
+            caps_lock: true,
+            logo: false,
+            num_lock: false,
+        }
+    }
+
+    #[test]
+    fn bindings_consume_press_and_release() {
+        let mut bindings = KeyBindings::new();
+        bindings.bind(
+            KeyCombination {
+                modifiers: modifiers(true, false),
+                keysym: keysyms::KEY_q,
+            },
+            "quit",
+        );
+        // caps lock is ignored, but not the other modifiers
+        assert_eq!(
+            bindings.process(
+                24,
+                KeyState::Pressed,
+                &modifiers(true, true),
+                keysyms::KEY_q,
+                false
+            ),
+            (true, None)
+        );
+        assert_eq!(
+            bindings.process(
+                24,
+                KeyState::Released,
+                &modifiers(true, true),
+                keysyms::KEY_q,
+                false
+            ),
+            (true, None)
+        );
+        assert_eq!(
+            bindings.process(
+                24,
+                KeyState::Pressed,
+                &modifiers(true, false),
+                keysyms::KEY_q,
+                false
+            ),
+            (false, Some("quit"))
+        );
+        // the release is consumed even if ctrl was released first
+        assert_eq!(
+            bindings.process(
+                24,
+                KeyState::Released,
+                &modifiers(false, false),
+                keysyms::KEY_q,
+                false
+            ),
+            (false, None)
+        );
+    }
+
+    #[test]
+    fn inhibited_bindings() {
+        let mut bindings = KeyBindings::new();
+        let ctrl_q = KeyCombination {
+            modifiers: modifiers(true, false),
+            keysym: keysyms::KEY_q,
+        };
+        let ctrl_escape = KeyCombination {
+            modifiers: modifiers(true, false),
+            keysym: keysyms::KEY_Escape,
+        };
+        bindings.bind(ctrl_q, 1);
+        bindings.bind_uninhibitable(ctrl_escape, 2);
+        assert_eq!(
+            bindings.process(24, KeyState::Pressed, &ctrl_q.modifiers, ctrl_q.keysym, true),
+            (true, None)
+        );
+        assert_eq!(
+            bindings.process(
+                9,
+                KeyState::Pressed,
+                &ctrl_escape.modifiers,
+                ctrl_escape.keysym,
+                true
+            ),
+            (false, Some(2))
+        );
+        // binding again replaces the action, whatever the state of the locks
+        let mut ctrl_q_locked = ctrl_q;
+        ctrl_q_locked.modifiers.num_lock = true;
+        bindings.bind(ctrl_q_locked, 3);
+        assert_eq!(bindings.bindings.len(), 2);
+        assert_eq!(bindings.unbind(&ctrl_q), Some(3));
+        assert_eq!(bindings.unbind(&ctrl_q_locked), None);
+    }
+
+    #[test]
+    fn bindings_ignore_caps_lock_keysym() {
+        let mut bindings = KeyBindings::new();
+        bindings.bind(
+            KeyCombination {
+                modifiers: modifiers(true, false),
+                keysym: keysyms::KEY_q,
+            },
+            "quit",
+        );
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        state.set_locks(true, false);
+        state.key_input(KEY_LEFTCTRL, KeyState::Pressed);
+        // the keysym sent to clients follows caps lock, the one of the bindings does not
+        assert_eq!(state.key_get_one_sym(KEY_Q), keysyms::KEY_Q);
+        assert_eq!(state.key_get_one_sym_without_locks(KEY_Q), keysyms::KEY_q);
+        assert_eq!(
+            bindings.process(
+                KEY_Q,
+                KeyState::Pressed,
+                &state.modifiers_state(),
+                state.key_get_one_sym_without_locks(KEY_Q),
+                false
+            ),
+            (false, Some("quit"))
+        );
+    }
+}
@@ -0,0 +1,1040 @@
+//! Keyboard handling of a seat
+//!
+//! The keymap and the state of the keyboard are handled by xkbcommon, see the [`xkb`]
+//! module. The [`KeyboardHandle`] of a seat forwards the key events to the focused client.
+
+use crate::backend::input::KeyState;
+use crate::wayland::{Serial, SERIAL_COUNTER};
+use calloop::{
+    timer::{Timeout, Timer, TimerHandle},
+    LoopHandle,
+};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    ffi::OsStr,
+    io::{self, Error as IoError},
+    ops::Deref as _,
+    os::unix::io::AsRawFd,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+use thiserror::Error;
+use wayland_server::{
+    protocol::{
+        wl_keyboard::{KeyState as WlKeyState, KeymapFormat, Request, WlKeyboard},
+        wl_surface::WlSurface,
+    },
+    Client, Filter, Main,
+};
+pub use xkbcommon::xkb::{keysyms, Keysym};
+
+pub mod xkb;
+
+use self::xkb::XkbState;
+pub use self::xkb::{ComposedKey, LayoutState, ModifiersState, XkbConfig};
+
+enum GrabStatus {
+    None,
+    Active(Serial, Box<dyn KeyboardGrab>),
+    Borrowed,
+}
+
+struct KbdInternal {
+    known_kbds: Vec<WlKeyboard>,
+    focus: Option<WlSurface>,
+    // the focus last set by the compositor, restored once a grab ends
+    pending_focus: Option<WlSurface>,
+    grab: GrabStatus,
+    xkb: XkbState,
+    layout_state: LayoutState,
+    repeat_rate: i32,
+    repeat_delay: i32,
+    focus_hook: Box<dyn FnMut(Option<&WlSurface>)>,
+    // keyboard of an input method that grabbed the key events
+    ime_grab: Option<WlKeyboard>,
+    // timer of the compositor-side key repeat, if enabled
+    repeat_timer: Option<TimerHandle<u32>>,
+    repeating: Option<KeyRepeat>,
+    // the layout last active in each window, if layouts are remembered per window
+    window_layouts: Option<Vec<(WlSurface, u32)>>,
+}
+
+// a key repeated for the clients that predate wl_keyboard.repeat_info
+struct KeyRepeat {
+    keycode: u32,
+    // timestamp of the next repeated event
+    time: u32,
+    timeout: Timeout,
+}
+
+impl KbdInternal {
+    fn new(
+        xkb: XkbState,
+        repeat_rate: i32,
+        repeat_delay: i32,
+        focus_hook: Box<dyn FnMut(Option<&WlSurface>)>,
+    ) -> KbdInternal {
+        KbdInternal {
+            known_kbds: Vec::new(),
+            focus: None,
+            pending_focus: None,
+            grab: GrabStatus::None,
+            layout_state: xkb.layout_state(),
+            xkb,
+            repeat_rate,
+            repeat_delay,
+            focus_hook,
+            ime_grab: None,
+            repeat_timer: None,
+            repeating: None,
+            window_layouts: None,
+        }
+    }
+
+    // the delay between two repeated key events, if keys are repeated
+    fn repeat_interval(&self) -> Option<Duration> {
+        if self.repeat_rate > 0 {
+            Some(Duration::from_millis(1000 / self.repeat_rate as u64))
+        } else {
+            None
+        }
+    }
+
+    // whether a focused client relies on the compositor to repeat keys
+    fn needs_compositor_repeat(&self) -> bool {
+        let mut needed = false;
+        // repeat_info was only introduced in version 4
+        self.with_focused_kbds(|kbd, _| needed |= kbd.as_ref().version() < 4);
+        needed
+    }
+
+    fn start_repeat(&mut self, keycode: u32, time: u32) {
+        self.stop_repeat();
+        if self.repeat_interval().is_none()
+            || !self.xkb.key_repeats(keycode)
+            || !self.needs_compositor_repeat()
+        {
+            return;
+        }
+        if let Some(ref timer) = self.repeat_timer {
+            let delay = self.repeat_delay.max(0) as u32;
+            let timeout = timer.add_timeout(Duration::from_millis(u64::from(delay)), keycode);
+            self.repeating = Some(KeyRepeat {
+                keycode,
+                time: time.wrapping_add(delay),
+                timeout,
+            });
+        }
+    }
+
+    fn stop_repeat(&mut self) {
+        if let (Some(timer), Some(repeat)) = (self.repeat_timer.as_ref(), self.repeating.take()) {
+            timer.cancel_timeout(&repeat.timeout);
+        }
+    }
+
+    // send a repeated key event, and schedule the next one
+    fn repeat(&mut self, keycode: u32, timers: &TimerHandle<u32>) {
+        let interval = match self.repeat_interval() {
+            Some(interval) => interval,
+            None => return,
+        };
+        let time = match self.repeating {
+            Some(ref repeat) if repeat.keycode == keycode => repeat.time,
+            _ => return,
+        };
+        let serial = SERIAL_COUNTER.next_serial();
+        self.with_focused_kbds(|kbd, _| {
+            if kbd.as_ref().version() < 4 {
+                kbd.key(serial.into(), time, keycode, WlKeyState::Pressed);
+            }
+        });
+        self.repeating = Some(KeyRepeat {
+            keycode,
+            time: time.wrapping_add(interval.as_millis() as u32),
+            timeout: timers.add_timeout(interval, keycode),
+        });
+    }
+
+    // return the new layout state if it has changed
+    fn update_layout_state(&mut self) -> Option<LayoutState> {
+        let layout_state = self.xkb.layout_state();
+        if layout_state != self.layout_state {
+            self.layout_state = layout_state.clone();
+            Some(layout_state)
+        } else {
+            None
+        }
+    }
+
+    // send the current modifiers to the focused client, or to the input method grabbing the keys
+    fn send_modifiers(&self) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let (dep, la, lo, gr) = self.xkb.serialize_modifiers();
+        if let Some(ref grab) = self.ime_grab {
+            grab.modifiers(serial.into(), dep, la, lo, gr);
+        } else {
+            self.with_focused_kbds(|kbd, _| kbd.modifiers(serial.into(), dep, la, lo, gr));
+        }
+    }
+
+    fn with_focused_kbds<F>(&self, mut f: F)
+    where
+        F: FnMut(&WlKeyboard, &WlSurface),
+    {
+        if let Some(ref surface) = self.focus {
+            for kbd in &self.known_kbds {
+                if kbd.as_ref().same_client_as(surface.as_ref()) {
+                    f(kbd, surface);
+                }
+            }
+        }
+    }
+
+    fn with_grab<F>(&mut self, logger: &::slog::Logger, f: F)
+    where
+        F: FnOnce(KeyboardInnerHandle<'_>, &mut dyn KeyboardGrab),
+    {
+        let mut grab = ::std::mem::replace(&mut self.grab, GrabStatus::Borrowed);
+        match grab {
+            GrabStatus::Borrowed => panic!("Accessed a keyboard grab from within a keyboard grab access."),
+            GrabStatus::Active(_, ref mut handler) => {
+                f(KeyboardInnerHandle { inner: self, logger }, &mut **handler);
+            }
+            GrabStatus::None => {
+                f(KeyboardInnerHandle { inner: self, logger }, &mut DefaultGrab);
+            }
+        }
+
+        if let GrabStatus::Borrowed = self.grab {
+            // the grab has not been ended nor replaced, put it back in place
+            self.grab = grab;
+        }
+    }
+}
+
+/// Errors that can be encountered when creating a keyboard handler
+#[derive(Debug, Error)]
+pub enum Error {
+    /// libxkbcommon could not load the specified keymap
+    #[error("Libxkbcommon could not load the specified keymap")]
+    BadKeymap,
+    /// libxkbcommon could not load the compose table of the locale
+    #[error("Libxkbcommon could not load the compose table of the locale")]
+    BadComposeTable,
+    /// Smithay could not create a tempfile to share the keymap with clients
+    #[error("Failed to create tempfile to share the keymap: {0}")]
+    IoError(IoError),
+}
+
+/// Create a keyboard handler from a set of RMLVO rules
+pub(crate) fn create_keyboard_handler<F>(
+    xkb_config: XkbConfig<'_>,
+    repeat_delay: i32,
+    repeat_rate: i32,
+    logger: &::slog::Logger,
+    focus_hook: F,
+) -> Result<KeyboardHandle, Error>
+where
+    F: FnMut(Option<&WlSurface>) + 'static,
+{
+    let log = logger.new(o!("smithay_module" => "xkbcommon_handler"));
+    info!(log, "Initializing a xkbcommon handler with keymap query";
+        "rules" => xkb_config.rules, "model" => xkb_config.model, "layout" => xkb_config.layout,
+        "variant" => xkb_config.variant, "options" => &xkb_config.options
+    );
+    let xkb = XkbState::new_from_names(&xkb_config).map_err(|err| {
+        debug!(log, "Loading keymap failed");
+        err
+    })?;
+
+    info!(log, "Loaded Keymap"; "name" => xkb.keymap().layouts().next());
+
+    Ok(KeyboardHandle::from_xkb(
+        xkb,
+        repeat_delay,
+        repeat_rate,
+        log,
+        focus_hook,
+    ))
+}
+
+struct KbdRc {
+    internal: RefCell<KbdInternal>,
+    layout_listeners: RefCell<Vec<Box<dyn FnMut(&LayoutState)>>>,
+    focus_listeners: RefCell<Vec<Box<dyn FnMut(Option<&WlSurface>)>>>,
+    // focus changes not delivered to the focus listeners yet
+    focus_events: RefCell<VecDeque<Option<WlSurface>>>,
+    logger: ::slog::Logger,
+}
+
+/// An handle to a keyboard handler
+///
+/// It can be cloned and all clones manipulate the same internal state.
+///
+/// This handle gives you 2 main ways to interact with the keyboard handling:
+///
+/// - set the current focus for this keyboard: designing the surface that will receive the key inputs
+///   using the [`KeyboardHandle::set_focus`] method.
+/// - process key inputs from the input backend, allowing them to be caught at the compositor-level
+///   or forwarded to the client. See the documentation of the [`KeyboardHandle::input`] method for
+///   details.
+#[derive(Clone)]
+pub struct KeyboardHandle {
+    arc: Rc<KbdRc>,
+}
+
+impl KeyboardHandle {
+    pub(crate) fn from_xkb<F>(
+        xkb: XkbState,
+        repeat_delay: i32,
+        repeat_rate: i32,
+        logger: ::slog::Logger,
+        focus_hook: F,
+    ) -> KeyboardHandle
+    where
+        F: FnMut(Option<&WlSurface>) + 'static,
+    {
+        KeyboardHandle {
+            arc: Rc::new(KbdRc {
+                internal: RefCell::new(KbdInternal::new(
+                    xkb,
+                    repeat_rate,
+                    repeat_delay,
+                    Box::new(focus_hook),
+                )),
+                layout_listeners: RefCell::new(Vec::new()),
+                focus_listeners: RefCell::new(Vec::new()),
+                focus_events: RefCell::new(VecDeque::new()),
+                logger,
+            }),
+        }
+    }
+
+    /// Handle a keystroke
+    ///
+    /// All keystrokes from the input backend should be fed _in order_ to this method of the
+    /// keyboard handler. It will internally track the state of the keymap.
+    ///
+    /// The `filter` argument is expected to be a closure which will peek at the generated input
+    /// as interpreted by the keymap before it is forwarded to the focused client. If this closure
+    /// returns false, the input will not be sent to the client. This mechanism can be used to
+    /// implement compositor-level key bindings for example.
+    ///
+    /// The module [`wayland::seat::keysyms`](::wayland::seat::keysyms) exposes definitions of all possible keysyms
+    /// to be compared against. This includes non-character keysyms, such as XF86 special keys.
+    pub fn input<F>(&self, keycode: u32, state: KeyState, serial: Serial, time: u32, filter: F)
+    where
+        F: FnOnce(&ModifiersState, Keysym) -> bool,
+    {
+        self.input_with_text(keycode, state, serial, time, |modifiers, sym, _| {
+            filter(modifiers, sym)
+        })
+    }
+
+    /// Handle a keystroke, along with the text it produces
+    ///
+    /// This is the same as [`KeyboardHandle::input`], but the filter is also given the text
+    /// produced by key presses, with dead keys and compose sequences resolved if they were
+    /// enabled with [`KeyboardHandle::enable_compose`]. It is `None` for key releases.
+    ///
+    /// This is meant for the compositor-side handling of text, for example in the built-in
+    /// UI of the compositor. Clients always receive the raw key events, and resolve compose
+    /// sequences themselves.
+    pub fn input_with_text<F>(&self, keycode: u32, state: KeyState, serial: Serial, time: u32, filter: F)
+    where
+        F: FnOnce(&ModifiersState, Keysym, Option<&ComposedKey>) -> bool,
+    {
+        trace!(self.arc.logger, "Handling keystroke"; "keycode" => keycode, "state" => format_args!("{:?}", state));
+        let mut guard = self.arc.internal.borrow_mut();
+
+        let sym = guard.xkb.key_get_one_sym(keycode);
+        let composed = match state {
+            KeyState::Pressed => Some(guard.xkb.key_get_composed(keycode)),
+            KeyState::Released => None,
+        };
+
+        let mods_changed = guard.xkb.key_input(keycode, state);
+        match state {
+            KeyState::Pressed => guard.stop_repeat(),
+            KeyState::Released => {
+                if guard.repeating.as_ref().map(|r| r.keycode) == Some(keycode) {
+                    guard.stop_repeat();
+                }
+            }
+        }
+        let layout_changed = if mods_changed {
+            guard.update_layout_state()
+        } else {
+            None
+        };
+
+        let modifiers_state = guard.xkb.modifiers_state();
+        let modifiers = if mods_changed {
+            Some(guard.xkb.serialize_modifiers())
+        } else {
+            None
+        };
+        // the filter may use the keyboard, e.g. to change its focus or grab
+        ::std::mem::drop(guard);
+
+        trace!(self.arc.logger, "Calling input filter";
+            "mods_state" => format_args!("{:?}", modifiers_state),
+            "sym" => xkbcommon::xkb::keysym_get_name(sym)
+        );
+
+        if !filter(&modifiers_state, sym, composed.as_ref()) {
+            // the filter returned false, we do not forward to client
+            trace!(self.arc.logger, "Input was intercepted by filter");
+            self.notify_layout_listeners(layout_changed);
+            return;
+        }
+
+        // forward to client if no keybinding is triggered
+        self.with_grab(|mut handle, grab| {
+            grab.input(&mut handle, keycode, state, modifiers, serial, time);
+        });
+        self.notify_layout_listeners(layout_changed);
+    }
+
+    // pass an event to the current grab, and notify the focus listeners if it changed the focus
+    fn with_grab<F>(&self, f: F)
+    where
+        F: FnOnce(KeyboardInnerHandle<'_>, &mut dyn KeyboardGrab),
+    {
+        let mut guard = self.arc.internal.borrow_mut();
+        let old_focus = guard.focus.clone();
+        guard.with_grab(&self.arc.logger, f);
+        let focus = guard.focus.clone();
+        // focus changes may restore the layout of a window
+        let layout_changed = guard.update_layout_state();
+        ::std::mem::drop(guard);
+        if focus != old_focus {
+            self.notify_focus_listeners(focus);
+        }
+        self.notify_layout_listeners(layout_changed);
+    }
+
+    fn notify_focus_listeners(&self, focus: Option<WlSurface>) {
+        self.arc.focus_events.borrow_mut().push_back(focus);
+        // a listener changing the focus again: its change is delivered once all the listeners
+        // have been notified of the current one
+        let mut listeners = match self.arc.focus_listeners.try_borrow_mut() {
+            Ok(listeners) => listeners,
+            Err(_) => return,
+        };
+        loop {
+            let focus = self.arc.focus_events.borrow_mut().pop_front();
+            let focus = match focus {
+                Some(focus) => focus,
+                None => break,
+            };
+            for listener in listeners.iter_mut() {
+                listener(focus.as_ref());
+            }
+        }
+    }
+
+    fn notify_layout_listeners(&self, layout_state: Option<LayoutState>) {
+        if let Some(layout_state) = layout_state {
+            trace!(self.arc.logger, "Layout state changed";
+                "layout" => &layout_state.name, "caps_lock" => layout_state.caps_lock,
+                "num_lock" => layout_state.num_lock
+            );
+            for listener in self.arc.layout_listeners.borrow_mut().iter_mut() {
+                listener(&layout_state);
+            }
+        }
+    }
+
+    /// Set the current focus of this keyboard
+    ///
+    /// If the new focus is different from the previous one, any previous focus
+    /// will be sent a [`wl_keyboard::Event::Leave`](wayland_server::protocol::wl_keyboard::Event::Leave)
+    /// event, and if the new focus is not `None`,
+    /// a [`wl_keyboard::Event::Enter`](wayland_server::protocol::wl_keyboard::Event::Enter) event will be sent.
+    ///
+    /// While the keyboard is grabbed, the grab decides whether the focus actually changes. The
+    /// focus is restored when the grab is removed with [`KeyboardHandle::unset_grab`].
+    pub fn set_focus(&self, focus: Option<&WlSurface>, serial: Serial) {
+        self.arc.internal.borrow_mut().pending_focus = focus.cloned();
+        self.with_grab(|mut handle, grab| {
+            grab.set_focus(&mut handle, focus, serial);
+        });
+    }
+
+    /// Change the current grab on this keyboard to the provided grab
+    ///
+    /// Overwrites any current grab.
+    pub fn set_grab<G: KeyboardGrab + 'static>(&self, grab: G, serial: Serial) {
+        self.arc.internal.borrow_mut().grab = GrabStatus::Active(serial, Box::new(grab));
+    }
+
+    /// Remove any current grab on this keyboard, reseting it to the default behavior
+    ///
+    /// The focus last given to [`KeyboardHandle::set_focus`] is restored.
+    pub fn unset_grab(&self) {
+        let serial = SERIAL_COUNTER.next_serial();
+        self.with_grab(|mut handle, _| {
+            handle.unset_grab(serial, true);
+        });
+    }
+
+    /// Check if this keyboard is currently grabbed with this serial
+    pub fn has_grab(&self, serial: Serial) -> bool {
+        match self.arc.internal.borrow().grab {
+            GrabStatus::Active(s, _) => s == serial,
+            _ => false,
+        }
+    }
+
+    /// Check if this keyboard is currently being grabbed
+    pub fn is_grabbed(&self) -> bool {
+        match self.arc.internal.borrow().grab {
+            GrabStatus::None => false,
+            _ => true,
+        }
+    }
+
+    /// Returns the start data for the grab, if any.
+    pub fn grab_start_data(&self) -> Option<KeyboardGrabStartData> {
+        match self.arc.internal.borrow().grab {
+            GrabStatus::Active(_, ref g) => Some(g.start_data().clone()),
+            _ => None,
+        }
+    }
+
+    /// Check if given client currently has keyboard focus
+    pub fn has_focus(&self, client: &Client) -> bool {
+        self.arc
+            .internal
+            .borrow_mut()
+            .focus
+            .as_ref()
+            .and_then(|f| f.as_ref().client())
+            .map(|c| c.equals(client))
+            .unwrap_or(false)
+    }
+
+    /// Access the surface that currently has keyboard focus, if any
+    pub fn current_focus(&self) -> Option<WlSurface> {
+        self.arc.internal.borrow().focus.clone()
+    }
+
+    /// Access the current state of the keyboard modifiers
+    pub fn modifier_state(&self) -> ModifiersState {
+        self.arc.internal.borrow().xkb.modifiers_state()
+    }
+
+    /// Access the current state of the keyboard layout and lock keys
+    pub fn layout_state(&self) -> LayoutState {
+        self.arc.internal.borrow().layout_state.clone()
+    }
+
+    /// Lock or unlock caps lock and num lock
+    ///
+    /// The new state of the modifiers is sent to the focused client, and the layout listeners
+    /// are notified if the leds changed.
+    pub fn set_locks(&self, caps_lock: bool, num_lock: bool) {
+        let mut guard = self.arc.internal.borrow_mut();
+        if !guard.xkb.set_locks(caps_lock, num_lock) {
+            return;
+        }
+        guard.send_modifiers();
+        let layout_changed = guard.update_layout_state();
+        ::std::mem::drop(guard);
+        self.notify_layout_listeners(layout_changed);
+    }
+
+    /// Activate a layout of the keymap
+    ///
+    /// The new layout is sent to the focused client along with the modifiers, and the layout
+    /// listeners are notified. Indices beyond the layouts of the keymap are ignored.
+    pub fn set_layout(&self, index: u32) {
+        let mut guard = self.arc.internal.borrow_mut();
+        if !guard.xkb.set_layout(index) {
+            return;
+        }
+        guard.send_modifiers();
+        let layout_changed = guard.update_layout_state();
+        ::std::mem::drop(guard);
+        self.notify_layout_listeners(layout_changed);
+    }
+
+    /// The number of layouts of the keymap
+    ///
+    /// Use it to cycle through the layouts with [`KeyboardHandle::set_layout`].
+    pub fn layout_count(&self) -> u32 {
+        self.arc.internal.borrow().xkb.layout_count()
+    }
+
+    /// Remember the active layout of each window
+    ///
+    /// When enabled, the layout active when a window loses the keyboard focus is restored
+    /// when it gets the focus back. Windows that never had the focus start with the first
+    /// layout of the keymap.
+    pub fn set_per_window_layout(&self, enabled: bool) {
+        let mut guard = self.arc.internal.borrow_mut();
+        if enabled != guard.window_layouts.is_some() {
+            guard.window_layouts = if enabled { Some(Vec::new()) } else { None };
+        }
+    }
+
+    /// Switch to a new keymap compiled from a set of RMLVO names
+    ///
+    /// The new keymap is sent to all the clients. The keys currently pressed stay pressed in
+    /// the new keymap, and caps lock and num lock keep their state, so that the key events
+    /// that follow stay consistent for the focused client. The layouts remembered per window
+    /// are forgotten, as they refer to the previous keymap.
+    ///
+    /// On error, the previous keymap is kept.
+    pub fn change_keymap(&self, xkb_config: XkbConfig<'_>) -> Result<(), Error> {
+        let mut guard = self.arc.internal.borrow_mut();
+        guard.stop_repeat();
+        guard.xkb.change_keymap(&xkb_config)?;
+        if let Some(ref mut window_layouts) = guard.window_layouts {
+            window_layouts.clear();
+        }
+        let layout_changed = guard.update_layout_state();
+        ::std::mem::drop(guard);
+
+        let guard = self.arc.internal.borrow();
+        for kbd in guard.known_kbds.iter().chain(guard.ime_grab.iter()) {
+            self.send_keymap(kbd);
+        }
+        guard.send_modifiers();
+        ::std::mem::drop(guard);
+        self.notify_layout_listeners(layout_changed);
+        Ok(())
+    }
+
+    /// Register a listener to be notified of changes of the [`LayoutState`]
+    ///
+    /// The listener is invoked from [`KeyboardHandle::input`] whenever the active layout or
+    /// one of the lock keys changes, whether or not the key was intercepted by the input
+    /// filter. It can use this handle, but must not register new listeners.
+    ///
+    /// This is where the leds of the physical keyboards should be updated, with the
+    /// [`leds`](LayoutState::leds) of the new state.
+    pub fn add_layout_listener<F>(&self, listener: F)
+    where
+        F: FnMut(&LayoutState) + 'static,
+    {
+        self.arc.layout_listeners.borrow_mut().push(Box::new(listener));
+    }
+
+    /// Register a listener to be notified of changes of the keyboard focus
+    ///
+    /// Unlike the focus hook given when adding the keyboard to the seat, the listener is
+    /// invoked once the focus change is complete, and can use this handle. If it changes the
+    /// focus again, the listeners are notified of the new focus after this notification is
+    /// complete. It must not register new listeners.
+    pub fn add_focus_listener<F>(&self, listener: F)
+    where
+        F: FnMut(Option<&WlSurface>) + 'static,
+    {
+        self.arc.focus_listeners.borrow_mut().push(Box::new(listener));
+    }
+
+    // the keysym of a key in the current state, ignoring caps lock and num lock
+    pub(crate) fn key_get_one_sym_without_locks(&self, keycode: u32) -> Keysym {
+        self.arc
+            .internal
+            .borrow()
+            .xkb
+            .key_get_one_sym_without_locks(keycode)
+    }
+
+    /// Register a new keyboard to this handler
+    ///
+    /// The keymap will automatically be sent to it, as well as an enter event if its
+    /// client currently has keyboard focus.
+    ///
+    /// This should be done first, before anything else is done with this keyboard.
+    pub(crate) fn new_kbd(&self, kbd: WlKeyboard) {
+        if !self.send_keymap(&kbd) {
+            return;
+        }
+
+        let mut guard = self.arc.internal.borrow_mut();
+        if kbd.as_ref().version() >= 4 {
+            kbd.repeat_info(guard.repeat_rate, guard.repeat_delay);
+        }
+        if let Some(ref surface) = guard.focus {
+            // the client may bind the keyboard after it was given focus
+            if kbd.as_ref().same_client_as(surface.as_ref()) {
+                let serial = SERIAL_COUNTER.next_serial();
+                let (dep, la, lo, gr) = guard.xkb.serialize_modifiers();
+                kbd.enter(serial.into(), surface, guard.xkb.serialize_pressed_keys());
+                kbd.modifiers(serial.into(), dep, la, lo, gr);
+            }
+        }
+        guard.known_kbds.push(kbd);
+    }
+
+    // returns false if the keymap could not be sent
+    fn send_keymap(&self, kbd: &WlKeyboard) -> bool {
+        trace!(self.arc.logger, "Sending keymap to client");
+
+        // prepare a tempfile with the keymap, to send it to the client
+        let ret = self
+            .arc
+            .internal
+            .borrow()
+            .xkb
+            .keymap_file()
+            .map(|(f, size)| kbd.keymap(KeymapFormat::XkbV1, f.as_raw_fd(), size));
+
+        if let Err(e) = ret {
+            warn!(self.arc.logger,
+                "Failed write keymap to client in a tempfile";
+                "err" => format!("{:?}", e)
+            );
+            return false;
+        };
+        true
+    }
+
+    /// Redirect the key events to the keyboard grabbed by an input method, or stop doing so
+    pub(crate) fn set_ime_grab(&self, grab: Option<WlKeyboard>) {
+        if let Some(ref kbd) = grab {
+            if !self.send_keymap(kbd) {
+                return;
+            }
+            let guard = self.arc.internal.borrow();
+            if kbd.as_ref().version() >= 4 {
+                kbd.repeat_info(guard.repeat_rate, guard.repeat_delay);
+            }
+            let (dep, la, lo, gr) = guard.xkb.serialize_modifiers();
+            kbd.modifiers(SERIAL_COUNTER.next_serial().into(), dep, la, lo, gr);
+        }
+        self.arc.internal.borrow_mut().ime_grab = grab;
+    }
+
+    /// Send a key event forwarded by an input method to the focused client
+    pub(crate) fn forward_key(&self, serial: u32, time: u32, keycode: u32, state: WlKeyState) {
+        self.arc.internal.borrow().with_focused_kbds(|kbd, _| {
+            kbd.key(serial, time, keycode, state);
+        });
+    }
+
+    /// Send a modifiers event forwarded by an input method to the focused client
+    pub(crate) fn forward_modifiers(&self, serial: u32, (dep, la, lo, gr): (u32, u32, u32, u32)) {
+        self.arc.internal.borrow().with_focused_kbds(|kbd, _| {
+            kbd.modifiers(serial, dep, la, lo, gr);
+        });
+    }
+
+    /// Change the repeat info configured for this keyboard
+    ///
+    /// `rate` is the number of repeated key events per second, `0` disabling key repeat, and
+    /// `delay` is the time in milliseconds a key must be held before it is repeated.
+    pub fn change_repeat_info(&self, rate: i32, delay: i32) {
+        let mut guard = self.arc.internal.borrow_mut();
+        guard.stop_repeat();
+        guard.repeat_delay = delay;
+        guard.repeat_rate = rate;
+        for kbd in &guard.known_kbds {
+            // repeat_info was only introduced in version 4
+            if kbd.as_ref().version() >= 4 {
+                kbd.repeat_info(rate, delay);
+            }
+        }
+    }
+
+    /// Resolve dead keys and compose sequences in the text given to the filter of
+    /// [`KeyboardHandle::input_with_text`]
+    ///
+    /// The sequences are loaded from the compose table of the given locale, or if `None` of the
+    /// locale of the environment.
+    pub fn enable_compose(&self, locale: Option<&OsStr>) -> Result<(), Error> {
+        self.arc
+            .internal
+            .borrow_mut()
+            .xkb
+            .enable_compose(locale)
+            .map_err(|err| {
+                debug!(self.arc.logger, "Loading compose table failed");
+                err
+            })
+    }
+
+    /// The repeat rate and delay configured for this keyboard
+    pub fn repeat_info(&self) -> (i32, i32) {
+        let guard = self.arc.internal.borrow();
+        (guard.repeat_rate, guard.repeat_delay)
+    }
+
+    /// Repeat the held keys for the clients that do not do it themselves
+    ///
+    /// Clients binding `wl_keyboard` version 4 or later are sent the repeat info and repeat
+    /// the keys themselves. For older clients, this inserts a timer source in the event loop
+    /// that sends them the held key again, following the configured repeat rate and delay,
+    /// for as long as it is held and they have the keyboard focus.
+    ///
+    /// The timer source is never removed from the event loop, but does nothing once the
+    /// keyboard is dropped.
+    pub fn enable_key_repeat<Data: 'static>(&self, handle: &LoopHandle<Data>) -> io::Result<()> {
+        if self.arc.internal.borrow().repeat_timer.is_some() {
+            return Ok(());
+        }
+        let timer = Timer::new()?;
+        let timers = timer.handle();
+        let weak: Weak<KbdRc> = Rc::downgrade(&self.arc);
+        handle
+            .insert_source(timer, move |keycode, timers, _| {
+                if let Some(arc) = weak.upgrade() {
+                    arc.internal.borrow_mut().repeat(keycode, timers);
+                }
+            })
+            .map_err(|err| err.error)?;
+        self.arc.internal.borrow_mut().repeat_timer = Some(timers);
+        Ok(())
+    }
+}
+
+/// Data about the event that started the grab.
+#[derive(Clone)]
+pub struct KeyboardGrabStartData {
+    /// The focused surface, if any, at the start of the grab.
+    pub focus: Option<WlSurface>,
+}
+
+/// A trait to implement a keyboard grab
+///
+/// In some context, it is necessary to temporarily redirect all the keyboard input, for example
+/// while an `xdg_popup` grab is active, while an input method is composing text, or while the
+/// screen is locked.
+///
+/// This trait is the interface to intercept regular keyboard events and change them as needed, its
+/// interface mimics the [`KeyboardHandle`] interface. Keys intercepted by the filter given to
+/// [`KeyboardHandle::input`] never reach the grab.
+///
+/// If your logic decides that the grab should end, both [`KeyboardInnerHandle`] and [`KeyboardHandle`]
+/// have a method to change it.
+///
+/// When your grab ends (either as you requested it or if it was forcefully cancelled by the server),
+/// the struct implementing this trait will be dropped. As such you should put clean-up logic in the destructor,
+/// rather than trying to guess when the grab will end.
+pub trait KeyboardGrab {
+    /// A key was pressed or released
+    ///
+    /// `modifiers` is the new serialized state of the modifiers, if the key changed it.
+    fn input(
+        &mut self,
+        handle: &mut KeyboardInnerHandle<'_>,
+        keycode: u32,
+        state: KeyState,
+        modifiers: Option<(u32, u32, u32, u32)>,
+        serial: Serial,
+        time: u32,
+    );
+    /// The compositor changed the focus of the keyboard
+    fn set_focus(&mut self, handle: &mut KeyboardInnerHandle<'_>, focus: Option<&WlSurface>, serial: Serial);
+    /// The data about the event that started the grab.
+    fn start_data(&self) -> &KeyboardGrabStartData;
+}
+
+/// This inner handle is accessed from inside a keyboard grab logic, and directly
+/// sends event to the client
+pub struct KeyboardInnerHandle<'a> {
+    inner: &'a mut KbdInternal,
+    logger: &'a ::slog::Logger,
+}
+
+impl<'a> KeyboardInnerHandle<'a> {
+    /// Change the current grab on this keyboard to the provided grab
+    ///
+    /// Overwrites any current grab.
+    pub fn set_grab<G: KeyboardGrab + 'static>(&mut self, serial: Serial, grab: G) {
+        self.inner.grab = GrabStatus::Active(serial, Box::new(grab));
+    }
+
+    /// Remove any current grab on this keyboard, resetting it to the default behavior
+    ///
+    /// If `restore_focus` is `true`, the focus last set by the compositor with
+    /// [`KeyboardHandle::set_focus`] is restored.
+    pub fn unset_grab(&mut self, serial: Serial, restore_focus: bool) {
+        self.inner.grab = GrabStatus::None;
+        if restore_focus {
+            let focus = self.inner.pending_focus.clone();
+            self.set_focus(focus.as_ref(), serial);
+        }
+    }
+
+    /// Access the current focus of this keyboard
+    pub fn current_focus(&self) -> Option<&WlSurface> {
+        self.inner.focus.as_ref()
+    }
+
+    /// Access the current state of the keyboard modifiers
+    pub fn modifier_state(&self) -> ModifiersState {
+        self.inner.xkb.modifiers_state()
+    }
+
+    /// A list of the currently physically pressed keys
+    ///
+    /// This still includes keys that your grab have intercepted and not sent
+    /// to the client.
+    pub fn current_pressed(&self) -> &[u32] {
+        self.inner.xkb.pressed_keys()
+    }
+
+    /// Send a key event to the focused client
+    ///
+    /// While an input method grabs the keyboard, the event is sent to it instead.
+    pub fn input(
+        &mut self,
+        keycode: u32,
+        state: KeyState,
+        modifiers: Option<(u32, u32, u32, u32)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        let wl_state = match state {
+            KeyState::Pressed => WlKeyState::Pressed,
+            KeyState::Released => WlKeyState::Released,
+        };
+        if let Some(ref grab) = self.inner.ime_grab {
+            // the input method forwards the keys it does not handle to the client itself
+            grab.key(serial.into(), time, keycode, wl_state);
+            if let Some((dep, la, lo, gr)) = modifiers {
+                grab.modifiers(serial.into(), dep, la, lo, gr);
+            }
+            trace!(self.logger, "Input forwarded to the input method");
+            return;
+        }
+        self.inner.with_focused_kbds(|kbd, _| {
+            // key event must be sent before modifers event for libxkbcommon
+            // to process them correctly
+            kbd.key(serial.into(), time, keycode, wl_state);
+            if let Some((dep, la, lo, gr)) = modifiers {
+                kbd.modifiers(serial.into(), dep, la, lo, gr);
+            }
+        });
+        if state == KeyState::Pressed {
+            self.inner.start_repeat(keycode, time);
+        }
+        if self.inner.focus.is_some() {
+            trace!(self.logger, "Input forwarded to client");
+        } else {
+            trace!(self.logger, "No client currently focused");
+        }
+    }
+
+    /// Set the current focus of this keyboard
+    ///
+    /// If the new focus is different from the previous one, any previous focus
+    /// will be sent a [`wl_keyboard::Event::Leave`](wayland_server::protocol::wl_keyboard::Event::Leave)
+    /// event, and if the new focus is not `None`,
+    /// a [`wl_keyboard::Event::Enter`](wayland_server::protocol::wl_keyboard::Event::Enter) event will be sent.
+    pub fn set_focus(&mut self, focus: Option<&WlSurface>, serial: Serial) {
+        let same = self
+            .inner
+            .focus
+            .as_ref()
+            .and_then(|f| focus.map(|s| s.as_ref().equals(f.as_ref())))
+            .unwrap_or(false);
+
+        if same {
+            trace!(self.logger, "Focus unchanged");
+            return;
+        }
+
+        // the key repeat and compose sequences do not follow the focus
+        self.inner.stop_repeat();
+        self.inner.xkb.reset_compose();
+
+        // unset old focus
+        self.inner.with_focused_kbds(|kbd, s| {
+            kbd.leave(serial.into(), &s);
+        });
+
+        // set new focus
+        let old_focus = ::std::mem::replace(&mut self.inner.focus, focus.cloned());
+        if let Some(ref mut window_layouts) = self.inner.window_layouts {
+            let layout = self.inner.xkb.layout_state().index;
+            window_layouts
+                .retain(|(surface, _)| surface.as_ref().is_alive() && old_focus.as_ref() != Some(surface));
+            if let Some(old_focus) = old_focus {
+                window_layouts.push((old_focus, layout));
+            }
+            let layout = focus
+                .and_then(|focus| window_layouts.iter().find(|(surface, _)| surface == focus))
+                .map(|&(_, layout)| layout)
+                .unwrap_or(0);
+            self.inner.xkb.set_layout(layout);
+        }
+        let (dep, la, lo, gr) = self.inner.xkb.serialize_modifiers();
+        let keys = self.inner.xkb.serialize_pressed_keys();
+        self.inner.with_focused_kbds(|kbd, surface| {
+            kbd.enter(serial.into(), &surface, keys.clone());
+            // Modifiers must be send after enter event.
+            kbd.modifiers(serial.into(), dep, la, lo, gr);
+        });
+        {
+            let KbdInternal {
+                ref focus,
+                ref mut focus_hook,
+                ..
+            } = *self.inner;
+            focus_hook(focus.as_ref());
+        }
+        if self.inner.focus.is_some() {
+            trace!(self.logger, "Focus set to new surface");
+        } else {
+            trace!(self.logger, "Focus unset");
+        }
+    }
+}
+
+pub(crate) fn implement_keyboard(keyboard: Main<WlKeyboard>, handle: Option<&KeyboardHandle>) -> WlKeyboard {
+    keyboard.quick_assign(|_keyboard, request, _data| {
+        match request {
+            Request::Release => {
+                // Our destructors already handle it
+            }
+            _ => unreachable!(),
+        }
+    });
+
+    if let Some(h) = handle {
+        let arc = h.arc.clone();
+        keyboard.assign_destructor(Filter::new(move |keyboard: WlKeyboard, _, _| {
+            arc.internal
+                .borrow_mut()
+                .known_kbds
+                .retain(|k| !k.as_ref().equals(&keyboard.as_ref()))
+        }));
+    }
+
+    keyboard.deref().clone()
+}
+
+/*
+ * Grabs definition
+ */
+
+// The default grab, the behavior when no particular grab is in progress
+struct DefaultGrab;
+
+impl KeyboardGrab for DefaultGrab {
+    fn input(
+        &mut self,
+        handle: &mut KeyboardInnerHandle<'_>,
+        keycode: u32,
+        state: KeyState,
+        modifiers: Option<(u32, u32, u32, u32)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.input(keycode, state, modifiers, serial, time);
+    }
+    fn set_focus(&mut self, handle: &mut KeyboardInnerHandle<'_>, focus: Option<&WlSurface>, serial: Serial) {
+        handle.set_focus(focus, serial);
+    }
+    fn start_data(&self) -> &KeyboardGrabStartData {
+        unreachable!()
+    }
+}
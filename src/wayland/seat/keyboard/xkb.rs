@@ -0,0 +1,788 @@
+//! Keymap handling through xkbcommon
+//!
+//! [`XkbState`] compiles a keymap, follows the key events of a keyboard to track the pressed
+//! keys and the state of the modifiers and leds, and translates keycodes to keysyms and text.
+//! It is what the [`KeyboardHandle`](super::KeyboardHandle) of a seat uses internally, and can
+//! be used on its own by compositors that need to interpret keys outside of a seat, for
+//! example to implement keybindings on a virtual keyboard.
+//!
+//! All keycodes are evdev keycodes, as given by the input backends. They are offset by 8
+//! internally, as the evdev XKB rules reflect X's broken keycode system, which starts at 8.
+
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{Error as IoError, Write},
+};
+
+use tempfile::tempfile;
+use xkbcommon::xkb::{self, compose};
+
+use super::Error;
+use crate::backend::input::{KeyState, KeyboardLeds};
+
+/// Represents the current state of the keyboard modifiers
+///
+/// Each field of this struct represents a modifier and is `true` if this modifier is active.
+///
+/// For some modifiers, this means that the key is currently pressed, others are toggled
+/// (like caps lock).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ModifiersState {
+    /// The "control" key
+    pub ctrl: bool,
+    /// The "alt" key
+    pub alt: bool,
+    /// The "shift" key
+    pub shift: bool,
+    /// The "Caps lock" key
+    pub caps_lock: bool,
+    /// The "logo" key
+    ///
+    /// Also known as the "windows" key on most keyboards
+    pub logo: bool,
+    /// The "Num lock" key
+    pub num_lock: bool,
+}
+
+impl ModifiersState {
+    fn new() -> ModifiersState {
+        ModifiersState {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            caps_lock: false,
+            logo: false,
+            num_lock: false,
+        }
+    }
+
+    fn update_with(&mut self, state: &xkb::State) {
+        self.ctrl = state.mod_name_is_active(&xkb::MOD_NAME_CTRL, xkb::STATE_MODS_EFFECTIVE);
+        self.alt = state.mod_name_is_active(&xkb::MOD_NAME_ALT, xkb::STATE_MODS_EFFECTIVE);
+        self.shift = state.mod_name_is_active(&xkb::MOD_NAME_SHIFT, xkb::STATE_MODS_EFFECTIVE);
+        self.caps_lock = state.mod_name_is_active(&xkb::MOD_NAME_CAPS, xkb::STATE_MODS_EFFECTIVE);
+        self.logo = state.mod_name_is_active(&xkb::MOD_NAME_LOGO, xkb::STATE_MODS_EFFECTIVE);
+        self.num_lock = state.mod_name_is_active(&xkb::MOD_NAME_NUM, xkb::STATE_MODS_EFFECTIVE);
+    }
+}
+
+/// State of the keyboard layout and of the lock keys
+///
+/// This is what compositors typically display in an on-screen notification when the user
+/// switches layouts or toggles caps lock, or forward to status bars. Register a listener
+/// with [`KeyboardHandle::add_layout_listener`](super::KeyboardHandle::add_layout_listener)
+/// to be notified of its changes instead of polling it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutState {
+    /// Index of the active layout in the keymap
+    pub index: u32,
+    /// Human-readable name of the active layout, like `English (US)`
+    pub name: String,
+    /// Short code of the active layout, like `us`
+    ///
+    /// This is taken from the layout list of the [`XkbConfig`], and is empty if the layout
+    /// was not specified there.
+    pub short_name: String,
+    /// Whether caps lock is active
+    pub caps_lock: bool,
+    /// Whether num lock is active
+    pub num_lock: bool,
+    /// Whether scroll lock is active
+    pub scroll_lock: bool,
+}
+
+impl LayoutState {
+    /// The state of the leds of the keyboard
+    pub fn leds(&self) -> KeyboardLeds {
+        KeyboardLeds {
+            caps_lock: self.caps_lock,
+            num_lock: self.num_lock,
+            scroll_lock: self.scroll_lock,
+        }
+    }
+
+    fn new(keymap: &xkb::Keymap, state: &xkb::State, short_names: &[String]) -> LayoutState {
+        let index = state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE);
+        LayoutState {
+            index,
+            name: keymap.layout_get_name(index).to_owned(),
+            short_name: short_names.get(index as usize).cloned().unwrap_or_default(),
+            caps_lock: state.led_name_is_active(&xkb::LED_NAME_CAPS),
+            num_lock: state.led_name_is_active(&xkb::LED_NAME_NUM),
+            scroll_lock: state.led_name_is_active(&xkb::LED_NAME_SCROLL),
+        }
+    }
+}
+
+/// Configuration for xkbcommon.
+///
+/// For the fields that are not set ("" or None, as set in the `Default` impl), xkbcommon will use
+/// the values from the environment variables `XKB_DEFAULT_RULES`, `XKB_DEFAULT_MODEL`,
+/// `XKB_DEFAULT_LAYOUT`, `XKB_DEFAULT_VARIANT` and `XKB_DEFAULT_OPTIONS`.
+///
+/// For details, see the [documentation at xkbcommon.org][docs].
+///
+/// [docs]: https://xkbcommon.org/doc/current/structxkb__rule__names.html
+#[derive(Clone, Debug)]
+pub struct XkbConfig<'a> {
+    /// The rules file to use.
+    ///
+    /// The rules file describes how to interpret the values of the model, layout, variant and
+    /// options fields.
+    pub rules: &'a str,
+    /// The keyboard model by which to interpret keycodes and LEDs.
+    pub model: &'a str,
+    /// A comma separated list of layouts (languages) to include in the keymap.
+    pub layout: &'a str,
+    /// A comma separated list of variants, one per layout, which may modify or augment the
+    /// respective layout in various ways.
+    pub variant: &'a str,
+    /// A comma separated list of options, through which the user specifies non-layout related
+    /// preferences, like which key combinations are used for switching layouts, or which key is the
+    /// Compose key.
+    pub options: Option<String>,
+    /// Whether caps lock is active when the keyboard is created
+    pub caps_lock: bool,
+    /// Whether num lock is active when the keyboard is created
+    ///
+    /// Most desktop keyboards have a separate numeric keypad, for which users often expect
+    /// num lock to be initially active.
+    pub num_lock: bool,
+}
+
+impl<'a> Default for XkbConfig<'a> {
+    fn default() -> Self {
+        Self {
+            rules: "",
+            model: "",
+            layout: "",
+            variant: "",
+            options: None,
+            caps_lock: false,
+            num_lock: false,
+        }
+    }
+}
+
+/// The text produced by a key press, once dead keys and compose sequences are resolved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposedKey {
+    /// The key is not part of a compose sequence
+    Key {
+        /// The keysym of the key
+        keysym: xkb::Keysym,
+        /// The text of the key, empty if it produces none
+        text: String,
+    },
+    /// The key started or continued a compose sequence, it produces nothing yet
+    Composing,
+    /// The key completed a compose sequence
+    Composed {
+        /// The keysym of the sequence, if it has a single one
+        keysym: Option<xkb::Keysym>,
+        /// The text of the sequence, empty if it produces none
+        text: String,
+    },
+    /// The key cancelled the compose sequence, it produces nothing
+    Cancelled,
+}
+
+// the locale of the compose table, as the setlocale of the C library would choose it
+fn compose_locale<F: Fn(&str) -> Option<OsString>>(var: F) -> OsString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| "C".into())
+}
+
+// compile a keymap, along with the short names of its layouts
+fn compile_names(
+    context: &xkb::Context,
+    config: &XkbConfig<'_>,
+) -> Result<(xkb::Keymap, Vec<String>), Error> {
+    let keymap = xkb::Keymap::new_from_names(
+        context,
+        &config.rules,
+        &config.model,
+        &config.layout,
+        &config.variant,
+        config.options.clone(),
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or(Error::BadKeymap)?;
+    let layout_short_names = config
+        .layout
+        .split(',')
+        .map(|layout| layout.trim().to_owned())
+        .collect();
+    Ok((keymap, layout_short_names))
+}
+
+/// A compiled keymap and the state of a keyboard using it
+///
+/// xkbcommon is not thread-safe, so this state cannot be sent to another thread.
+pub struct XkbState {
+    context: xkb::Context,
+    // only set if compose sequences are enabled
+    compose: Option<compose::State>,
+    keymap: xkb::Keymap,
+    state: xkb::State,
+    // the keymap serialized in the text format sent to the clients
+    keymap_string: String,
+    pressed_keys: Vec<u32>,
+    mods_state: ModifiersState,
+    layout_short_names: Vec<String>,
+}
+
+impl XkbState {
+    /// Compile a keymap from a set of RMLVO names
+    pub fn new_from_names(config: &XkbConfig<'_>) -> Result<XkbState, Error> {
+        // we create a new contex for each keymap because libxkbcommon is actually NOT threadsafe
+        // so confining it inside the XkbState allows us to use Rusts mutability rules to make
+        // sure nothing goes wrong.
+        //
+        // FIXME: This is an issue with the xkbcommon-rs crate that does not reflect this
+        // non-threadsafety properly.
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let (keymap, layout_short_names) = compile_names(&context, config)?;
+        let mut xkb = XkbState::with_keymap(context, keymap, layout_short_names);
+        xkb.set_locks(config.caps_lock, config.num_lock);
+        Ok(xkb)
+    }
+
+    /// Switch to a new keymap compiled from a set of RMLVO names
+    ///
+    /// The keys currently pressed are pressed again in the new keymap, and the state of caps
+    /// lock and num lock is preserved, so that the key events that follow stay consistent. The
+    /// initial locks of the config are ignored, and any compose sequence in progress is
+    /// aborted. On error, the previous keymap is kept.
+    pub fn change_keymap(&mut self, config: &XkbConfig<'_>) -> Result<(), Error> {
+        let (keymap, layout_short_names) = compile_names(&self.context, config)?;
+        self.switch_keymap(keymap, layout_short_names);
+        Ok(())
+    }
+
+    /// Switch to a new keymap described in the xkb text format
+    ///
+    /// This behaves like [`change_keymap`](XkbState::change_keymap).
+    pub fn change_keymap_from_string(&mut self, keymap: &str) -> Result<(), Error> {
+        let keymap = xkb::Keymap::new_from_string(
+            &self.context,
+            keymap.to_owned(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or(Error::BadKeymap)?;
+        self.switch_keymap(keymap, Vec::new());
+        Ok(())
+    }
+
+    fn switch_keymap(&mut self, keymap: xkb::Keymap, layout_short_names: Vec<String>) {
+        let ModifiersState {
+            caps_lock, num_lock, ..
+        } = self.mods_state;
+        let pressed_keys = ::std::mem::take(&mut self.pressed_keys);
+        self.state = xkb::State::new(&keymap);
+        self.keymap_string = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        self.keymap = keymap;
+        self.layout_short_names = layout_short_names;
+        self.mods_state.update_with(&self.state);
+        for keycode in pressed_keys {
+            self.key_input(keycode, KeyState::Pressed);
+        }
+        self.set_locks(caps_lock, num_lock);
+        self.reset_compose();
+    }
+
+    /// Compile a keymap from its description in the xkb text format
+    ///
+    /// The layouts of such a keymap have no short names.
+    pub fn new_from_string(keymap: &str) -> Result<XkbState, Error> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_string(
+            &context,
+            keymap.to_owned(),
+            xkb::KEYMAP_FORMAT_TEXT_V1,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .ok_or(Error::BadKeymap)?;
+        Ok(XkbState::with_keymap(context, keymap, Vec::new()))
+    }
+
+    fn with_keymap(context: xkb::Context, keymap: xkb::Keymap, layout_short_names: Vec<String>) -> XkbState {
+        let state = xkb::State::new(&keymap);
+        let keymap_string = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        let mut mods_state = ModifiersState::new();
+        mods_state.update_with(&state);
+        XkbState {
+            context,
+            compose: None,
+            keymap,
+            state,
+            keymap_string,
+            pressed_keys: Vec::new(),
+            mods_state,
+            layout_short_names,
+        }
+    }
+
+    /// Update the state with a key event
+    ///
+    /// Returns `true` if the state of the modifiers, of the layout or of the leds changed.
+    pub fn key_input(&mut self, keycode: u32, state: KeyState) -> bool {
+        // track pressed keys as xkbcommon does not seem to expose it :(
+        let direction = match state {
+            KeyState::Pressed => {
+                self.pressed_keys.push(keycode);
+                xkb::KeyDirection::Down
+            }
+            KeyState::Released => {
+                self.pressed_keys.retain(|&k| k != keycode);
+                xkb::KeyDirection::Up
+            }
+        };
+
+        let state_components = self.state.update_key(keycode + 8, direction);
+
+        if state_components != 0 {
+            self.mods_state.update_with(&self.state);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Lock or unlock the caps lock and num lock modifiers
+    ///
+    /// Returns `true` if the state of the modifiers or of the leds changed. The modifiers
+    /// that are missing from the keymap are ignored.
+    pub fn set_locks(&mut self, caps_lock: bool, num_lock: bool) -> bool {
+        let mut locked = self.state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        for &(name, active) in &[(xkb::MOD_NAME_CAPS, caps_lock), (xkb::MOD_NAME_NUM, num_lock)] {
+            let index = self.keymap.mod_get_index(name);
+            if index == xkb::MOD_INVALID {
+                continue;
+            }
+            if active {
+                locked |= 1 << index;
+            } else {
+                locked &= !(1 << index);
+            }
+        }
+        let state_components = self.state.update_mask(
+            self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED),
+            self.state.serialize_mods(xkb::STATE_MODS_LATCHED),
+            locked,
+            self.state.serialize_layout(xkb::STATE_LAYOUT_DEPRESSED),
+            self.state.serialize_layout(xkb::STATE_LAYOUT_LATCHED),
+            self.state.serialize_layout(xkb::STATE_LAYOUT_LOCKED),
+        );
+        if state_components != 0 {
+            self.mods_state.update_with(&self.state);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Activate a layout of the keymap
+    ///
+    /// Returns `true` if the active layout changed. Indices beyond the layouts of the keymap
+    /// are ignored.
+    pub fn set_layout(&mut self, index: u32) -> bool {
+        if index >= self.keymap.num_layouts() {
+            return false;
+        }
+        let state_components = self.state.update_mask(
+            self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED),
+            self.state.serialize_mods(xkb::STATE_MODS_LATCHED),
+            self.state.serialize_mods(xkb::STATE_MODS_LOCKED),
+            0,
+            0,
+            index,
+        );
+        if state_components != 0 {
+            self.mods_state.update_with(&self.state);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The number of layouts of the keymap
+    pub fn layout_count(&self) -> u32 {
+        self.keymap.num_layouts()
+    }
+
+    /// Resolve dead keys and compose sequences in [`key_get_composed`](XkbState::key_get_composed)
+    ///
+    /// The sequences are loaded from the compose table of the given locale, or if `None` of the
+    /// locale set by the `LC_ALL`, `LC_CTYPE` or `LANG` environment variables. The compose
+    /// table can be customized by users with their `~/.XCompose` file.
+    pub fn enable_compose(&mut self, locale: Option<&OsStr>) -> Result<(), Error> {
+        let locale = match locale {
+            Some(locale) => locale.to_owned(),
+            None => compose_locale(|name| env::var_os(name)),
+        };
+        let table = compose::Table::new_from_locale(&self.context, &locale, compose::COMPILE_NO_FLAGS)
+            .map_err(|_| Error::BadComposeTable)?;
+        self.compose = Some(compose::State::new(&table, compose::STATE_NO_FLAGS));
+        Ok(())
+    }
+
+    /// Stop resolving compose sequences
+    pub fn disable_compose(&mut self) {
+        self.compose = None;
+    }
+
+    /// Abort the compose sequence in progress, if any
+    pub fn reset_compose(&mut self) {
+        if let Some(ref mut compose) = self.compose {
+            compose.reset();
+        }
+    }
+
+    /// The text produced by a key press, resolving dead keys and compose sequences if enabled
+    ///
+    /// This advances the compose sequence, so it must be called once for every key press,
+    /// before the state is updated with [`key_input`](XkbState::key_input). Without compose
+    /// support, this always returns [`ComposedKey::Key`].
+    pub fn key_get_composed(&mut self, keycode: u32) -> ComposedKey {
+        let keysym = self.key_get_one_sym(keycode);
+        let text = self.key_get_utf8(keycode);
+        let compose = match self.compose {
+            Some(ref mut compose) => compose,
+            None => return ComposedKey::Key { keysym, text },
+        };
+        if let compose::FeedResult::Ignored = compose.feed(keysym) {
+            // modifiers do not interrupt sequences
+            return ComposedKey::Key { keysym, text };
+        }
+        match compose.status() {
+            compose::Status::Composing => ComposedKey::Composing,
+            compose::Status::Composed => {
+                let composed = ComposedKey::Composed {
+                    keysym: compose.keysym(),
+                    text: compose.utf8().unwrap_or_default(),
+                };
+                compose.reset();
+                composed
+            }
+            compose::Status::Cancelled => {
+                compose.reset();
+                ComposedKey::Cancelled
+            }
+            compose::Status::Nothing => ComposedKey::Key { keysym, text },
+        }
+    }
+
+    /// The keys currently pressed, in the order they were pressed
+    pub fn pressed_keys(&self) -> &[u32] {
+        &self.pressed_keys
+    }
+
+    /// The current state of the modifiers
+    pub fn modifiers_state(&self) -> ModifiersState {
+        self.mods_state
+    }
+
+    /// The current state of the layout and of the leds
+    pub fn layout_state(&self) -> LayoutState {
+        LayoutState::new(&self.keymap, &self.state, &self.layout_short_names)
+    }
+
+    /// The state of the caps lock, num lock and scroll lock leds
+    pub fn leds(&self) -> KeyboardLeds {
+        KeyboardLeds {
+            caps_lock: self.state.led_name_is_active(&xkb::LED_NAME_CAPS),
+            num_lock: self.state.led_name_is_active(&xkb::LED_NAME_NUM),
+            scroll_lock: self.state.led_name_is_active(&xkb::LED_NAME_SCROLL),
+        }
+    }
+
+    /// Whether the led with the given name, like [`xkb::LED_NAME_CAPS`], is lit
+    pub fn led_is_active(&self, name: &str) -> bool {
+        self.state.led_name_is_active(name)
+    }
+
+    /// Whether a key should be repeated while it is held down
+    pub fn key_repeats(&self, keycode: u32) -> bool {
+        self.keymap.key_repeats(keycode + 8)
+    }
+
+    /// The main keysym a key produces in the current state
+    ///
+    /// This is `keysyms::KEY_NoSymbol` if the key produces no keysym or more than one.
+    pub fn key_get_one_sym(&self, keycode: u32) -> xkb::Keysym {
+        self.state.key_get_one_sym(keycode + 8)
+    }
+
+    /// The main keysym a key produces in the current state, ignoring caps lock and num lock
+    ///
+    /// This is the keysym the key would produce if neither lock was active, for example
+    /// `KEY_q` rather than `KEY_Q` while caps lock is on.
+    pub fn key_get_one_sym_without_locks(&self, keycode: u32) -> xkb::Keysym {
+        let mut mask = 0;
+        for &name in &[xkb::MOD_NAME_CAPS, xkb::MOD_NAME_NUM] {
+            let index = self.keymap.mod_get_index(name);
+            if index != xkb::MOD_INVALID && self.state.mod_index_is_active(index, xkb::STATE_MODS_EFFECTIVE) {
+                mask |= 1 << index;
+            }
+        }
+        if mask == 0 {
+            return self.key_get_one_sym(keycode);
+        }
+        // evaluate the key in a copy of the state without the locks
+        let mut state = xkb::State::new(&self.keymap);
+        state.update_mask(
+            self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED) & !mask,
+            self.state.serialize_mods(xkb::STATE_MODS_LATCHED) & !mask,
+            self.state.serialize_mods(xkb::STATE_MODS_LOCKED) & !mask,
+            self.state.serialize_layout(xkb::STATE_LAYOUT_DEPRESSED),
+            self.state.serialize_layout(xkb::STATE_LAYOUT_LATCHED),
+            self.state.serialize_layout(xkb::STATE_LAYOUT_LOCKED),
+        );
+        state.key_get_one_sym(keycode + 8)
+    }
+
+    /// All the keysyms a key produces in the current state
+    pub fn key_get_syms(&self, keycode: u32) -> Vec<xkb::Keysym> {
+        self.state.key_get_syms(keycode + 8).to_vec()
+    }
+
+    /// The text a key produces in the current state
+    ///
+    /// This is empty if the key does not produce any text, like modifiers or function keys.
+    pub fn key_get_utf8(&self, keycode: u32) -> String {
+        self.state.key_get_utf8(keycode + 8)
+    }
+
+    /// The modifiers in the format of the `wl_keyboard.modifiers` event
+    ///
+    /// Returns the depressed, latched and locked modifiers, and the locked layout.
+    pub fn serialize_modifiers(&self) -> (u32, u32, u32, u32) {
+        let mods_depressed = self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let mods_latched = self.state.serialize_mods(xkb::STATE_MODS_LATCHED);
+        let mods_locked = self.state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        let layout_locked = self.state.serialize_layout(xkb::STATE_LAYOUT_LOCKED);
+
+        (mods_depressed, mods_latched, mods_locked, layout_locked)
+    }
+
+    /// The pressed keys in the format of the `wl_keyboard.enter` event
+    pub fn serialize_pressed_keys(&self) -> Vec<u8> {
+        self.pressed_keys
+            .iter()
+            .flat_map(|key| key.to_ne_bytes().to_vec())
+            .collect()
+    }
+
+    /// The keymap in the xkb text format
+    pub fn keymap_string(&self) -> &str {
+        &self.keymap_string
+    }
+
+    /// Write the keymap to a new file, to be sent with the `wl_keyboard.keymap` event
+    ///
+    /// Returns the file along with the size of the keymap.
+    pub fn keymap_file(&self) -> Result<(File, u32), IoError> {
+        let mut file = tempfile()?;
+        file.write_all(self.keymap_string.as_bytes())?;
+        file.flush()?;
+        Ok((file, self.keymap_string.len() as u32))
+    }
+
+    /// The underlying xkbcommon keymap
+    pub fn keymap(&self) -> &xkb::Keymap {
+        &self.keymap
+    }
+
+    /// The underlying xkbcommon state
+    pub fn state(&self) -> &xkb::State {
+        &self.state
+    }
+}
+
+impl ::std::fmt::Debug for XkbState {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("XkbState")
+            .field("compose", &self.compose.is_some())
+            .field("pressed_keys", &self.pressed_keys)
+            .field("mods_state", &self.mods_state)
+            .field("layout_short_names", &self.layout_short_names)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compose_locale, ComposedKey, XkbState};
+    use crate::backend::input::KeyState;
+    use std::ffi::OsString;
+    use xkbcommon::xkb::keysyms;
+
+    // a self-contained keymap, so that the tests do not depend on xkeyboard-config
+    const KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes {
+        minimum = 8;
+        maximum = 255;
+        <AE01> = 10;
+        <LFSH> = 50;
+        <CAPS> = 66;
+        indicator 1 = "Caps Lock";
+    };
+    xkb_types {
+        type "ONE_LEVEL" {
+            modifiers = none;
+            level_name[Level1] = "Any";
+        };
+        type "TWO_LEVEL" {
+            modifiers = Shift;
+            map[Shift] = Level2;
+            level_name[Level1] = "Base";
+            level_name[Level2] = "Shift";
+        };
+    };
+    xkb_compatibility {
+        interpret Shift_L {
+            action = SetMods(modifiers = Shift);
+        };
+        interpret Caps_Lock {
+            action = LockMods(modifiers = Lock);
+        };
+        indicator "Caps Lock" {
+            whichModState = locked;
+            modifiers = Lock;
+        };
+    };
+    xkb_symbols {
+        key <AE01> { [ 1, exclam ] };
+        key <LFSH> { [ Shift_L ] };
+        key <CAPS> { [ Caps_Lock ] };
+        modifier_map Shift { <LFSH> };
+        modifier_map Lock { <CAPS> };
+    };
+};"#;
+
+    // evdev keycodes of the keys of the keymap
+    const KEY_1: u32 = 2;
+    const KEY_LEFTSHIFT: u32 = 42;
+    const KEY_CAPSLOCK: u32 = 58;
+
+    #[test]
+    fn translate_keys() {
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        assert_eq!(state.key_get_one_sym(KEY_1), keysyms::KEY_1);
+        assert_eq!(state.key_get_utf8(KEY_1), "1");
+
+        assert!(state.key_input(KEY_LEFTSHIFT, KeyState::Pressed));
+        assert!(state.modifiers_state().shift);
+        assert_eq!(state.pressed_keys(), &[KEY_LEFTSHIFT]);
+        assert_eq!(state.key_get_one_sym(KEY_1), keysyms::KEY_exclam);
+        assert_eq!(state.key_get_utf8(KEY_1), "!");
+
+        assert!(state.key_input(KEY_LEFTSHIFT, KeyState::Released));
+        assert!(!state.modifiers_state().shift);
+        assert!(state.pressed_keys().is_empty());
+        // keys without effect on the state do not change it
+        assert!(!state.key_input(KEY_1, KeyState::Pressed));
+        assert_eq!(state.serialize_pressed_keys(), KEY_1.to_ne_bytes().to_vec());
+    }
+
+    #[test]
+    fn keys_without_compose() {
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        assert_eq!(
+            state.key_get_composed(KEY_1),
+            ComposedKey::Key {
+                keysym: keysyms::KEY_1,
+                text: "1".into()
+            }
+        );
+    }
+
+    #[test]
+    fn compose_locale_from_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        assert_eq!(compose_locale(env(&[])), "C");
+        assert_eq!(compose_locale(env(&[("LANG", "fr_FR.UTF-8")])), "fr_FR.UTF-8");
+        assert_eq!(
+            compose_locale(env(&[("LANG", "fr_FR.UTF-8"), ("LC_CTYPE", "de_DE.UTF-8")])),
+            "de_DE.UTF-8"
+        );
+        // empty variables are skipped
+        assert_eq!(
+            compose_locale(env(&[("LANG", "fr_FR.UTF-8"), ("LC_ALL", "")])),
+            "fr_FR.UTF-8"
+        );
+    }
+
+    #[test]
+    fn track_leds() {
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        assert!(!state.layout_state().caps_lock);
+        state.key_input(KEY_CAPSLOCK, KeyState::Pressed);
+        state.key_input(KEY_CAPSLOCK, KeyState::Released);
+        assert!(state.layout_state().caps_lock);
+        assert!(state.modifiers_state().caps_lock);
+        let (_, _, locked, _) = state.serialize_modifiers();
+        assert_ne!(locked, 0);
+    }
+
+    #[test]
+    fn set_locks() {
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        assert!(state.set_locks(true, false));
+        assert!(state.modifiers_state().caps_lock);
+        assert!(state.leds().caps_lock);
+        // nothing changes when the locks are already in place
+        assert!(!state.set_locks(true, false));
+        assert!(state.set_locks(false, true));
+        assert!(state.modifiers_state().num_lock);
+        assert!(!state.leds().caps_lock);
+        // the keymap has no num lock led
+        assert!(!state.leds().num_lock);
+    }
+
+    #[test]
+    fn change_keymap_keeps_state() {
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        state.key_input(KEY_CAPSLOCK, KeyState::Pressed);
+        state.key_input(KEY_CAPSLOCK, KeyState::Released);
+        state.key_input(KEY_LEFTSHIFT, KeyState::Pressed);
+        state.change_keymap_from_string(KEYMAP).unwrap();
+        assert_eq!(state.pressed_keys(), &[KEY_LEFTSHIFT]);
+        assert!(state.modifiers_state().shift);
+        assert!(state.modifiers_state().caps_lock);
+        assert!(state.change_keymap_from_string("invalid").is_err());
+        assert!(state.modifiers_state().shift);
+    }
+
+    #[test]
+    fn set_layout() {
+        let mut state = XkbState::new_from_string(KEYMAP).unwrap();
+        assert_eq!(state.layout_count(), 1);
+        // the layout is already active
+        assert!(!state.set_layout(0));
+        assert!(!state.set_layout(1));
+        assert_eq!(state.layout_state().index, 0);
+    }
+
+    #[test]
+    fn serialize_keymap() {
+        let state = XkbState::new_from_string(KEYMAP).unwrap();
+        let (_, size) = state.keymap_file().unwrap();
+        assert_eq!(size as usize, state.keymap_string().len());
+        // the serialized keymap can be compiled again
+        assert!(XkbState::new_from_string(state.keymap_string()).is_ok());
+    }
+}
@@ -0,0 +1,278 @@
+use wayland_server::protocol::{wl_pointer::ButtonState, wl_surface::WlSurface};
+
+use super::PointerHandle;
+use crate::backend::input::TouchSlot;
+use crate::wayland::SERIAL_COUNTER;
+
+/// Linux event code of the right mouse button (`BTN_RIGHT`)
+const BTN_RIGHT: u32 = 0x111;
+
+/// Configuration of the touch long-press emulation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LongPressConfig {
+    /// Time in milliseconds a touch point needs to be held to trigger a right-click
+    pub delay: u32,
+    /// Distance a touch point may move before the long-press is cancelled
+    pub slop: f64,
+}
+
+impl Default for LongPressConfig {
+    fn default() -> Self {
+        LongPressConfig {
+            delay: 600,
+            slop: 8.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Pending {
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        time: u32,
+    },
+    Triggered {
+        slot: Option<TouchSlot>,
+    },
+    Cancelled,
+}
+
+/// Progress of a pending long-press
+///
+/// Use this to draw some feedback at the touch location while the user holds their finger down.
+/// smithay does not provide a render element for it, draw it with your own renderer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LongPressProgress {
+    /// Location of the touch point in the global compositor space
+    pub location: (f64, f64),
+    /// Fraction of the configured delay that has already elapsed, between `0.0` and `1.0`
+    pub progress: f64,
+}
+
+/// Emulation of a right-click by holding a touch point
+///
+/// Many applications do not handle touch input themselves and have no way to
+/// open context menus on touchscreens. This state machine detects a single touch
+/// point being held still for a configurable delay, so you can convert it into a
+/// right-click for surfaces without touch support using [`emulate_right_click`].
+///
+/// Feed it with the touch events of a seat and regularly call [`update`](LongPressEmulation::update)
+/// (e.g. once per frame or from a timer) while a long-press is [`pending`](LongPressEmulation::progress).
+/// Additional touch points or moving further than the configured slop cancel the long-press.
+///
+/// A [`TouchHandle`](super::TouchHandle) can run this emulation for you, see
+/// [`TouchHandle::set_long_press`](super::TouchHandle::set_long_press).
+#[derive(Debug)]
+pub struct LongPressEmulation {
+    config: LongPressConfig,
+    state: State,
+    active_points: usize,
+}
+
+impl LongPressEmulation {
+    /// Create a new long-press emulation with the given configuration
+    pub fn new(config: LongPressConfig) -> LongPressEmulation {
+        LongPressEmulation {
+            config,
+            state: State::Idle,
+            active_points: 0,
+        }
+    }
+
+    /// Access the current configuration
+    pub fn config(&self) -> LongPressConfig {
+        self.config
+    }
+
+    /// Change the configuration
+    ///
+    /// This only takes effect for touch sequences started afterwards.
+    pub fn set_config(&mut self, config: LongPressConfig) {
+        self.config = config;
+    }
+
+    /// Notify a new touch point
+    pub fn touch_down(&mut self, slot: Option<TouchSlot>, location: (f64, f64), time: u32) {
+        self.active_points += 1;
+        self.state = match self.state {
+            State::Idle if self.active_points == 1 => State::Pending { slot, location, time },
+            State::Triggered { slot } => State::Triggered { slot },
+            _ => State::Cancelled,
+        };
+    }
+
+    /// Notify the motion of a touch point
+    pub fn touch_motion(&mut self, slot: Option<TouchSlot>, location: (f64, f64)) {
+        if let State::Pending {
+            slot: pending_slot,
+            location: start,
+            ..
+        } = self.state
+        {
+            let (dx, dy) = (location.0 - start.0, location.1 - start.1);
+            if pending_slot == slot && (dx * dx + dy * dy).sqrt() > self.config.slop {
+                self.state = State::Cancelled;
+            }
+        }
+    }
+
+    /// Notify that a touch point was lifted
+    ///
+    /// Returns `true` if this touch point triggered a right-click, in which case you
+    /// should not forward the touch up event to the client.
+    pub fn touch_up(&mut self, slot: Option<TouchSlot>) -> bool {
+        self.active_points = self.active_points.saturating_sub(1);
+        let consumed = match self.state {
+            State::Triggered { slot: triggered } => triggered == slot,
+            _ => false,
+        };
+        match self.state {
+            State::Pending { slot: pending, .. } if pending == slot => self.state = State::Cancelled,
+            _ => {}
+        }
+        if self.active_points == 0 {
+            self.state = State::Idle;
+        }
+        consumed
+    }
+
+    /// Notify that the current touch sequence was cancelled
+    pub fn touch_cancel(&mut self) {
+        self.active_points = 0;
+        self.state = State::Idle;
+    }
+
+    /// Check if the pending long-press has elapsed
+    ///
+    /// Returns the location at which the right-click should happen exactly once per
+    /// touch sequence.
+    pub fn update(&mut self, time: u32) -> Option<(f64, f64)> {
+        match self.state {
+            State::Pending {
+                slot,
+                location,
+                time: start,
+            } if time.wrapping_sub(start) >= self.config.delay => {
+                self.state = State::Triggered { slot };
+                Some(location)
+            }
+            _ => None,
+        }
+    }
+
+    /// Progress of the currently pending long-press, if any
+    pub fn progress(&self, time: u32) -> Option<LongPressProgress> {
+        match self.state {
+            State::Pending {
+                location,
+                time: start,
+                ..
+            } => {
+                let progress = if self.config.delay == 0 {
+                    1.0
+                } else {
+                    f64::from(time.wrapping_sub(start)) / f64::from(self.config.delay)
+                };
+                Some(LongPressProgress {
+                    location,
+                    progress: progress.min(1.0),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Send an emulated right-click on the given pointer
+///
+/// This moves the pointer to the provided location and focus, then presses and
+/// releases the right button.
+pub fn emulate_right_click(
+    pointer: &PointerHandle,
+    location: (f64, f64),
+    focus: Option<(WlSurface, (f64, f64))>,
+    time: u32,
+) {
+    pointer.motion(location, focus, SERIAL_COUNTER.next_serial(), time);
+    pointer.button(
+        BTN_RIGHT,
+        ButtonState::Pressed,
+        SERIAL_COUNTER.next_serial(),
+        time,
+    );
+    pointer.button(
+        BTN_RIGHT,
+        ButtonState::Released,
+        SERIAL_COUNTER.next_serial(),
+        time,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LongPressConfig, LongPressEmulation};
+    use crate::backend::input::TouchSlot;
+    use crate::wayland::{seat::touch::create_touch_handler, SERIAL_COUNTER};
+
+    fn emulation() -> LongPressEmulation {
+        LongPressEmulation::new(LongPressConfig {
+            delay: 500,
+            slop: 5.0,
+        })
+    }
+
+    #[test]
+    fn triggers_after_delay() {
+        let mut lp = emulation();
+        let slot = Some(TouchSlot::new(0));
+        lp.touch_down(slot, (10.0, 10.0), 1000);
+        assert_eq!(lp.update(1200), None);
+        assert_eq!(lp.progress(1250).unwrap().progress, 0.5);
+        lp.touch_motion(slot, (12.0, 12.0));
+        assert_eq!(lp.update(1500), Some((10.0, 10.0)));
+        assert_eq!(lp.update(1600), None);
+        assert!(lp.touch_up(slot));
+    }
+
+    #[test]
+    fn cancelled_by_motion_or_second_finger() {
+        let mut lp = emulation();
+        let slot = Some(TouchSlot::new(0));
+        lp.touch_down(slot, (10.0, 10.0), 1000);
+        lp.touch_motion(slot, (20.0, 10.0));
+        assert_eq!(lp.update(2000), None);
+        assert!(!lp.touch_up(slot));
+
+        lp.touch_down(slot, (10.0, 10.0), 3000);
+        lp.touch_down(Some(TouchSlot::new(1)), (50.0, 10.0), 3100);
+        assert_eq!(lp.update(4000), None);
+        assert!(!lp.touch_up(slot));
+        assert!(!lp.touch_up(Some(TouchSlot::new(1))));
+    }
+
+    #[test]
+    fn touch_handle_emulation() {
+        let touch = create_touch_handler();
+        let slot = Some(TouchSlot::new(0));
+        touch.down(slot, (10.0, 10.0), None, SERIAL_COUNTER.next_serial(), 1000);
+        assert_eq!(touch.long_press_update(2000), None);
+        assert!(!touch.up(slot, SERIAL_COUNTER.next_serial(), 2000));
+
+        touch.set_long_press(Some(LongPressConfig {
+            delay: 500,
+            slop: 5.0,
+        }));
+        touch.down(slot, (10.0, 10.0), None, SERIAL_COUNTER.next_serial(), 3000);
+        assert_eq!(touch.long_press_progress(3250).unwrap().progress, 0.5);
+        assert_eq!(touch.long_press_update(3500), Some((10.0, 10.0)));
+        assert_eq!(touch.long_press_progress(3600), None);
+        assert!(touch.up(slot, SERIAL_COUNTER.next_serial(), 3600));
+        assert!(!touch.is_active());
+
+        touch.set_long_press(None);
+        touch.down(slot, (10.0, 10.0), None, SERIAL_COUNTER.next_serial(), 4000);
+        assert_eq!(touch.long_press_update(5000), None);
+    }
+}
@@ -32,27 +32,80 @@
 //!
 //! Once the seat is initialized, you can add capabilities to it.
 //!
-//! Currently, only pointer and keyboard capabilities are supported by
-//! smithay.
+//! Pointer, keyboard and touch capabilities are supported by smithay.
 //!
 //! You can add these capabilities via methods of the [`Seat`](::wayland::seat::Seat) struct:
-//! [`add_keyboard`](::wayland::seat::Seat::add_keyboard), [`add_pointer`](::wayland::seat::Seat::add_pointer).
+//! [`add_keyboard`](::wayland::seat::Seat::add_keyboard), [`add_pointer`](::wayland::seat::Seat::add_pointer),
+//! [`add_touch`](::wayland::seat::Seat::add_touch).
 //! These methods return handles that can be cloned and sent across thread, so you can keep one around
 //! in your event-handling code to forward inputs to your clients.
+//!
+//! ### Multiple seats
+//!
+//! Several seats can be created on the same display, for example to let multiple users share a
+//! compositor. Each seat has its own focus and grabs, as well as its own selection and
+//! drag'n'drop when the [`data_device`](::wayland::data_device) is initialized for it.
+//!
+//! Input backends report the seat that generated each event, which you can map to the
+//! [`name`](::wayland::seat::Seat::name) of your seats to forward the event to the right
+//! handles. With libinput, devices can be assigned to seats with
+//! `LibinputInputBackend::set_seat_assignment`.
+//!
+//! ### Key bindings
+//!
+//! Compositor key bindings can be registered in [`KeyBindings`](::wayland::seat::KeyBindings),
+//! which consumes the matching keystrokes before they reach clients, and suspends the bindings
+//! while the focused client inhibits the keyboard shortcuts.
+//!
+//! ### Input methods
+//!
+//! Input methods composing text for other clients can be registered on a seat with
+//! [`init_input_method`](::wayland::seat::init_input_method), and the text inputs of clients
+//! forwarded to them with [`init_text_input_manager`](::wayland::seat::init_text_input_manager).
+//! The returned [`InputMethodHandle`](::wayland::seat::InputMethodHandle) allows switching
+//! between several input methods, or disabling them, with configurable key bindings.
+//!
+//! Clients using the newer `zwp_text_input_v3` protocol are supported through
+//! [`init_text_input_manager_v3`](::wayland::seat::init_text_input_manager_v3) and
+//! [`init_text_input_v3`](::wayland::seat::init_text_input_v3), which let the compositor itself
+//! send composed text, for example from an on-screen keyboard.
 
 use std::{cell::RefCell, ops::Deref as _, rc::Rc};
 
-mod keyboard;
+mod absolute_mapping;
+mod input_method;
+mod key_bindings;
+pub mod keyboard;
+mod long_press;
 mod pointer;
+mod pointer_accel;
+mod text_input;
+mod touch;
 
 pub use self::{
-    keyboard::{keysyms, Error as KeyboardError, KeyboardHandle, Keysym, ModifiersState, XkbConfig},
+    absolute_mapping::{AbsoluteMapping, DeviceArea},
+    input_method::{
+        init_input_method, init_text_input_manager, ImeBinding, InputMethodBindings, InputMethodHandle,
+    },
+    key_bindings::{KeyBindings, KeyCombination},
+    keyboard::{
+        keysyms, ComposedKey, Error as KeyboardError, KeyboardGrab, KeyboardGrabStartData, KeyboardHandle,
+        KeyboardInnerHandle, Keysym, LayoutState, ModifiersState, XkbConfig,
+    },
+    long_press::{emulate_right_click, LongPressConfig, LongPressEmulation, LongPressProgress},
     pointer::{
-        AxisFrame, CursorImageRole, CursorImageStatus, GrabStartData, PointerGrab, PointerHandle,
+        AxisFrame, CursorImageRole, CursorImageStatus, DragGrab, GrabStartData, PointerGrab, PointerHandle,
         PointerInnerHandle,
     },
+    pointer_accel::PointerAccel,
+    text_input::{
+        init_text_input_manager_v3, init_text_input_v3, SurroundingText, TextInputEvent, TextInputHandle,
+        TextInputState,
+    },
+    touch::{TouchDragGrab, TouchGrab, TouchGrabStartData, TouchHandle, TouchInnerHandle},
 };
 
+use crate::utils::DestructionHooks;
 use crate::wayland::compositor::{roles::Role, CompositorToken};
 
 use wayland_server::{
@@ -63,6 +116,7 @@ use wayland_server::{
 struct Inner {
     pointer: Option<PointerHandle>,
     keyboard: Option<KeyboardHandle>,
+    touch: Option<TouchHandle>,
     known_seats: Vec<wl_seat::WlSeat>,
 }
 
@@ -71,6 +125,13 @@ pub(crate) struct SeatRc {
     user_data: UserDataMap,
     pub(crate) log: ::slog::Logger,
     name: String,
+    destruction_hooks: DestructionHooks<dyn FnOnce(&str)>,
+}
+
+impl Drop for SeatRc {
+    fn drop(&mut self) {
+        self.destruction_hooks.invoke(&self.name);
+    }
 }
 
 impl Inner {
@@ -82,6 +143,9 @@ impl Inner {
         if self.keyboard.is_some() {
             caps |= wl_seat::Capability::Keyboard;
         }
+        if self.touch.is_some() {
+            caps |= wl_seat::Capability::Touch;
+        }
         caps
     }
 
@@ -127,18 +191,8 @@ impl Seat {
         R: Role<CursorImageRole> + 'static,
         L: Into<Option<::slog::Logger>>,
     {
-        let log = crate::slog_or_fallback(logger);
-        let arc = Rc::new(SeatRc {
-            inner: RefCell::new(Inner {
-                pointer: None,
-                keyboard: None,
-                known_seats: Vec::new(),
-            }),
-            log: log.new(o!("smithay_module" => "seat_handler", "seat_name" => name.clone())),
-            name,
-            user_data: UserDataMap::new(),
-        });
-        let seat = Seat { arc: arc.clone() };
+        let seat = Seat::with_name(name, &crate::slog_or_fallback(logger));
+        let arc = seat.arc.clone();
         let global = display.create_global(
             5,
             Filter::new(move |(new_seat, _version), _, _| {
@@ -154,6 +208,24 @@ impl Seat {
         (seat, global)
     }
 
+    // a seat not advertized to the clients yet
+    fn with_name(name: String, log: &::slog::Logger) -> Seat {
+        Seat {
+            arc: Rc::new(SeatRc {
+                inner: RefCell::new(Inner {
+                    pointer: None,
+                    keyboard: None,
+                    touch: None,
+                    known_seats: Vec::new(),
+                }),
+                log: log.new(o!("smithay_module" => "seat_handler", "seat_name" => name.clone())),
+                name,
+                user_data: UserDataMap::new(),
+                destruction_hooks: DestructionHooks::new(),
+            }),
+        }
+    }
+
     /// Attempt to retrieve a [`Seat`] from an existing resource
     pub fn from_resource(seat: &wl_seat::WlSeat) -> Option<Seat> {
         seat.as_ref()
@@ -163,11 +235,27 @@ impl Seat {
             .map(|arc| Seat { arc })
     }
 
+    /// The name of this seat, as advertized to clients
+    pub fn name(&self) -> &str {
+        &self.arc.name
+    }
+
     /// Acces the `UserDataMap` associated with this `Seat`
     pub fn user_data(&self) -> &UserDataMap {
         &self.arc.user_data
     }
 
+    /// Register a hook to be invoked when this seat is destroyed
+    ///
+    /// The seat is destroyed once its global was destroyed, all the handles to it were
+    /// dropped and all clients released it. The hook is invoked with the name of the seat.
+    pub fn add_destruction_hook<F>(&self, hook: F)
+    where
+        F: FnOnce(&str) + 'static,
+    {
+        self.arc.destruction_hooks.add(Box::new(hook));
+    }
+
     /// Adds the pointer capability to this seat
     ///
     /// You are provided a [`PointerHandle`], which allows you to send input events
@@ -318,6 +406,44 @@ impl Seat {
         }
     }
 
+    /// Adds the touch capability to this seat
+    ///
+    /// You are provided a [`TouchHandle`], which allows you to send input events
+    /// to this touch device. This handle can be cloned.
+    ///
+    /// Calling this method on a seat that already has a touch capability
+    /// will overwrite it, and will be seen by the clients as if the
+    /// touch device was unplugged and a new one was plugged.
+    pub fn add_touch(&mut self) -> TouchHandle {
+        let mut inner = self.arc.inner.borrow_mut();
+        let touch = self::touch::create_touch_handler();
+        if inner.touch.is_some() {
+            // there is already a touch device, remove it and notify the clients
+            // of the change
+            inner.touch = None;
+            inner.send_all_caps();
+        }
+        inner.touch = Some(touch.clone());
+        inner.send_all_caps();
+        touch
+    }
+
+    /// Access the touch device of this seat if any
+    pub fn get_touch(&self) -> Option<TouchHandle> {
+        self.arc.inner.borrow_mut().touch.clone()
+    }
+
+    /// Remove the touch capability from this seat
+    ///
+    /// Clients will be appropriately notified.
+    pub fn remove_touch(&mut self) {
+        let mut inner = self.arc.inner.borrow_mut();
+        if inner.touch.is_some() {
+            inner.touch = None;
+            inner.send_all_caps();
+        }
+    }
+
     /// Checks whether a given [`WlSeat`](wl_seat::WlSeat) is associated with this [`Seat`]
     pub fn owns(&self, seat: &wl_seat::WlSeat) -> bool {
         let inner = self.arc.inner.borrow_mut();
@@ -361,8 +487,13 @@ where
                     // same as pointer, should error but cannot
                 }
             }
-            wl_seat::Request::GetTouch { .. } => {
-                // TODO
+            wl_seat::Request::GetTouch { id } => {
+                let touch = self::touch::implement_touch(id, inner.touch.as_ref());
+                if let Some(ref touch_handle) = inner.touch {
+                    touch_handle.new_touch(touch);
+                } else {
+                    // same as pointer, should error but cannot
+                }
             }
             wl_seat::Request::Release => {
                 // Our destructors already handle it
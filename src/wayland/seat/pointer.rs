@@ -1,5 +1,12 @@
 use std::{cell::RefCell, ops::Deref as _, rc::Rc};
 
+use wayland_protocols::unstable::{
+    pointer_gestures::v1::server::{
+        zwp_pointer_gesture_pinch_v1::ZwpPointerGesturePinchV1,
+        zwp_pointer_gesture_swipe_v1::ZwpPointerGestureSwipeV1,
+    },
+    relative_pointer::v1::server::zwp_relative_pointer_v1::ZwpRelativePointerV1,
+};
 use wayland_server::{
     protocol::{
         wl_pointer::{self, Axis, AxisSource, ButtonState, Request, WlPointer},
@@ -9,6 +16,7 @@ use wayland_server::{
 };
 
 use crate::wayland::compositor::{roles::Role, CompositorToken};
+use crate::wayland::pointer_constraints::ConstraintRef;
 use crate::wayland::Serial;
 
 /// The role representing a surface set as the pointer cursor
@@ -43,6 +51,12 @@ struct PointerInternal {
     grab: GrabStatus,
     pressed_buttons: Vec<u32>,
     image_callback: Box<dyn FnMut(CursorImageStatus)>,
+    constraints: Vec<ConstraintRef>,
+    relative_pointers: Vec<ZwpRelativePointerV1>,
+    swipe_gestures: Vec<ZwpPointerGestureSwipeV1>,
+    pinch_gestures: Vec<ZwpPointerGesturePinchV1>,
+    // the surface that was focused when the current gesture began
+    gesture_focus: Option<WlSurface>,
 }
 
 impl PointerInternal {
@@ -78,6 +92,36 @@ impl PointerInternal {
             grab: GrabStatus::None,
             pressed_buttons: Vec::new(),
             image_callback: Box::new(wrapper) as Box<_>,
+            constraints: Vec::new(),
+            relative_pointers: Vec::new(),
+            swipe_gestures: Vec::new(),
+            pinch_gestures: Vec::new(),
+            gesture_focus: None,
+        }
+    }
+
+    // the constraint currently restricting the motion of the pointer
+    fn active_constraint(&self) -> Option<ConstraintRef> {
+        self.constraints
+            .iter()
+            .find(|c| {
+                let c = c.borrow();
+                c.is_active() && c.surface().as_ref().is_alive()
+            })
+            .cloned()
+    }
+
+    // activate the constraint of the focused surface and deactivate the others
+    fn update_constraints(&mut self) {
+        for constraint in &self.constraints {
+            let mut constraint = constraint.borrow_mut();
+            match self.focus {
+                Some((ref surface, (sx, sy))) if constraint.surface() == surface => {
+                    let (x, y) = self.location;
+                    constraint.try_activate((x - sx, y - sy));
+                }
+                _ => constraint.deactivate(false),
+            }
         }
     }
 
@@ -136,6 +180,222 @@ impl PointerHandle {
         guard.known_pointers.push(pointer);
     }
 
+    /// Retrieve the handle of the seat a `wl_pointer` was created from
+    pub(crate) fn from_resource(pointer: &WlPointer) -> Option<PointerHandle> {
+        pointer.as_ref().user_data().get::<PointerHandle>().cloned()
+    }
+
+    pub(crate) fn is_constrained_on(&self, surface: &WlSurface) -> bool {
+        self.inner
+            .borrow()
+            .constraints
+            .iter()
+            .any(|c| c.borrow().surface() == surface)
+    }
+
+    pub(crate) fn add_constraint(&self, constraint: ConstraintRef) {
+        let mut inner = self.inner.borrow_mut();
+        inner.constraints.push(constraint);
+        inner.update_constraints();
+    }
+
+    pub(crate) fn remove_constraint(&self, constraint: &ConstraintRef) {
+        let mut inner = self.inner.borrow_mut();
+        inner.constraints.retain(|c| !Rc::ptr_eq(c, constraint));
+        let constraint = constraint.borrow();
+        if !constraint.is_active() || !constraint.is_lock() {
+            return;
+        }
+        // an active lock was destroyed, warp the pointer where the client asked to
+        let warp = match (constraint.cursor_hint(), &inner.focus) {
+            (Some((hx, hy)), Some((surface, (sx, sy)))) if surface == constraint.surface() => {
+                Some((sx + hx, sy + hy))
+            }
+            _ => None,
+        };
+        if let Some(location) = warp {
+            inner.location = location;
+        }
+    }
+
+    #[cfg(feature = "wayland_input_protocols")]
+    pub(crate) fn new_relative_pointer(&self, pointer: ZwpRelativePointerV1) {
+        self.inner.borrow_mut().relative_pointers.push(pointer);
+    }
+
+    #[cfg(feature = "wayland_input_protocols")]
+    pub(crate) fn remove_relative_pointer(&self, pointer: &ZwpRelativePointerV1) {
+        self.inner
+            .borrow_mut()
+            .relative_pointers
+            .retain(|p| !p.as_ref().equals(pointer.as_ref()));
+    }
+
+    /// Notify the focused client of a relative motion of the pointer
+    ///
+    /// You provide the delta of the motion, both after and before pointer acceleration, and its
+    /// timestamp in microseconds. It is sent regardless of the pointer grab and constraints,
+    /// to the clients that bound the [`relative_pointer`](crate::wayland::relative_pointer)
+    /// global.
+    pub fn relative_motion(&self, delta: (f64, f64), delta_unaccel: (f64, f64), utime: u64) {
+        let inner = self.inner.borrow();
+        if let Some((ref focus, _)) = inner.focus {
+            for pointer in &inner.relative_pointers {
+                if pointer.as_ref().same_client_as(focus.as_ref()) {
+                    pointer.relative_motion(
+                        (utime >> 32) as u32,
+                        utime as u32,
+                        delta.0,
+                        delta.1,
+                        delta_unaccel.0,
+                        delta_unaccel.1,
+                    );
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "wayland_input_protocols")]
+    pub(crate) fn new_swipe_gesture(&self, gesture: ZwpPointerGestureSwipeV1) {
+        self.inner.borrow_mut().swipe_gestures.push(gesture);
+    }
+
+    #[cfg(feature = "wayland_input_protocols")]
+    pub(crate) fn remove_swipe_gesture(&self, gesture: &ZwpPointerGestureSwipeV1) {
+        self.inner
+            .borrow_mut()
+            .swipe_gestures
+            .retain(|g| !g.as_ref().equals(gesture.as_ref()));
+    }
+
+    #[cfg(feature = "wayland_input_protocols")]
+    pub(crate) fn new_pinch_gesture(&self, gesture: ZwpPointerGesturePinchV1) {
+        self.inner.borrow_mut().pinch_gestures.push(gesture);
+    }
+
+    #[cfg(feature = "wayland_input_protocols")]
+    pub(crate) fn remove_pinch_gesture(&self, gesture: &ZwpPointerGesturePinchV1) {
+        self.inner
+            .borrow_mut()
+            .pinch_gestures
+            .retain(|g| !g.as_ref().equals(gesture.as_ref()));
+    }
+
+    /// Notify the focused client that a swipe gesture began
+    ///
+    /// The whole gesture is sent to the surface having the pointer focus when it begins, to the
+    /// clients that bound the [`pointer_gestures`](crate::wayland::pointer_gestures) global.
+    pub fn gesture_swipe_begin(&self, serial: Serial, time: u32, fingers: u32) {
+        let mut inner = self.inner.borrow_mut();
+        inner.gesture_focus = inner.focus.as_ref().map(|&(ref surface, _)| surface.clone());
+        if let Some(ref focus) = inner.gesture_focus {
+            for gesture in &inner.swipe_gestures {
+                if gesture.as_ref().same_client_as(focus.as_ref()) {
+                    gesture.begin(serial.into(), time, focus, fingers);
+                }
+            }
+        }
+    }
+
+    /// Notify the client of the current swipe gesture of a motion of its fingers
+    pub fn gesture_swipe_update(&self, time: u32, delta: (f64, f64)) {
+        let inner = self.inner.borrow();
+        if let Some(ref focus) = inner.gesture_focus {
+            for gesture in &inner.swipe_gestures {
+                if gesture.as_ref().same_client_as(focus.as_ref()) {
+                    gesture.update(time, delta.0, delta.1);
+                }
+            }
+        }
+    }
+
+    /// Notify the client of the current swipe gesture that it ended
+    pub fn gesture_swipe_end(&self, serial: Serial, time: u32, cancelled: bool) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(focus) = inner.gesture_focus.take() {
+            for gesture in &inner.swipe_gestures {
+                if gesture.as_ref().same_client_as(focus.as_ref()) {
+                    gesture.end(serial.into(), time, cancelled as i32);
+                }
+            }
+        }
+    }
+
+    /// Notify the focused client that a pinch gesture began
+    ///
+    /// The whole gesture is sent to the surface having the pointer focus when it begins, to the
+    /// clients that bound the [`pointer_gestures`](crate::wayland::pointer_gestures) global.
+    pub fn gesture_pinch_begin(&self, serial: Serial, time: u32, fingers: u32) {
+        let mut inner = self.inner.borrow_mut();
+        inner.gesture_focus = inner.focus.as_ref().map(|&(ref surface, _)| surface.clone());
+        if let Some(ref focus) = inner.gesture_focus {
+            for gesture in &inner.pinch_gestures {
+                if gesture.as_ref().same_client_as(focus.as_ref()) {
+                    gesture.begin(serial.into(), time, focus, fingers);
+                }
+            }
+        }
+    }
+
+    /// Notify the client of the current pinch gesture of a motion of its fingers
+    ///
+    /// The `scale` is relative to the distance between the fingers when the gesture began,
+    /// and the `rotation` is the angle in degrees the fingers rotated clockwise since the last
+    /// update.
+    pub fn gesture_pinch_update(&self, time: u32, delta: (f64, f64), scale: f64, rotation: f64) {
+        let inner = self.inner.borrow();
+        if let Some(ref focus) = inner.gesture_focus {
+            for gesture in &inner.pinch_gestures {
+                if gesture.as_ref().same_client_as(focus.as_ref()) {
+                    gesture.update(time, delta.0, delta.1, scale, rotation);
+                }
+            }
+        }
+    }
+
+    /// Notify the client of the current pinch gesture that it ended
+    pub fn gesture_pinch_end(&self, serial: Serial, time: u32, cancelled: bool) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(focus) = inner.gesture_focus.take() {
+            for gesture in &inner.pinch_gestures {
+                if gesture.as_ref().same_client_as(focus.as_ref()) {
+                    gesture.end(serial.into(), time, cancelled as i32);
+                }
+            }
+        }
+    }
+
+    /// Check whether the pointer is currently locked in place by a client
+    ///
+    /// See the [`pointer_constraints`](crate::wayland::pointer_constraints) module.
+    pub fn is_locked(&self) -> bool {
+        self.inner
+            .borrow()
+            .active_constraint()
+            .map(|c| c.borrow().is_lock())
+            .unwrap_or(false)
+    }
+
+    /// Check whether the pointer is currently confined to a region by a client
+    ///
+    /// See the [`pointer_constraints`](crate::wayland::pointer_constraints) module.
+    pub fn is_confined(&self) -> bool {
+        self.inner
+            .borrow()
+            .active_constraint()
+            .map(|c| !c.borrow().is_lock())
+            .unwrap_or(false)
+    }
+
+    /// Deactivate the active lock or confinement of the pointer, if any
+    ///
+    /// It is not reactivated until the pointer leaves its surface and enters it again.
+    pub fn deactivate_constraint(&self) {
+        if let Some(constraint) = self.inner.borrow().active_constraint() {
+            constraint.borrow_mut().deactivate(true);
+        }
+    }
+
     /// Change the current grab on this pointer to the provided grab
     ///
     /// Overwrites any current grab.
@@ -186,6 +446,9 @@ impl PointerHandle {
     ///
     /// This will internally take care of notifying the appropriate client objects
     /// of enter/motion/leave events.
+    ///
+    /// If a client constrained the pointer, the location and focus you provide are adjusted
+    /// to honor the constraint, see [`PointerHandle::current_location`] for the resulting location.
     pub fn motion(
         &self,
         location: (f64, f64),
@@ -233,6 +496,15 @@ impl PointerHandle {
     pub fn current_location(&self) -> (f64, f64) {
         self.inner.borrow().location
     }
+
+    /// Access the surface that currently has pointer focus, if any
+    pub fn current_focus(&self) -> Option<WlSurface> {
+        self.inner
+            .borrow()
+            .focus
+            .as_ref()
+            .map(|&(ref surface, _)| surface.clone())
+    }
 }
 
 /// Data about the event that started the grab.
@@ -349,6 +621,30 @@ impl<'a> PointerInnerHandle<'a> {
         serial: Serial,
         time: u32,
     ) {
+        let (mut x, mut y) = (x, y);
+        let mut focus = focus;
+        if let Some(constraint) = self.inner.active_constraint() {
+            let constraint = constraint.borrow();
+            let surface = constraint.surface().clone();
+            // the surface may have moved
+            let origin = match (&focus, &self.inner.focus) {
+                (Some((focus, origin)), _) if focus == &surface => *origin,
+                (_, Some((_, origin))) => *origin,
+                (_, None) => (0.0, 0.0),
+            };
+            if constraint.is_lock() {
+                // the pointer does not move and no motion event is sent
+                self.inner.focus = Some((surface, origin));
+                return;
+            }
+            let (old_x, old_y) = self.inner.location;
+            let (lx, ly) =
+                constraint.confine((old_x - origin.0, old_y - origin.1), (x - origin.0, y - origin.1));
+            x = lx + origin.0;
+            y = ly + origin.1;
+            focus = Some((surface, origin));
+        }
+
         // do we leave a surface ?
         let mut leave = true;
         self.inner.location = (x, y);
@@ -393,6 +689,7 @@ impl<'a> PointerInnerHandle<'a> {
                 })
             }
         }
+        self.inner.update_constraints();
     }
 
     /// Notify that a button was pressed
@@ -615,6 +912,8 @@ where
     });
 
     if let Some(h) = handle {
+        let user_data = h.clone();
+        pointer.as_ref().user_data().set(move || user_data);
         let inner = h.inner.clone();
         pointer.assign_destructor(Filter::new(move |pointer: WlPointer, _, _| {
             inner
@@ -714,3 +1013,67 @@ impl PointerGrab for ClickGrab {
         &self.start_data
     }
 }
+
+/// A drag grab, forwarding the movements of the pointer to a callback
+///
+/// While it is active, the pointer focus stays on the surface that had it when the grab started
+/// and clients do not receive motion events. Each time the pointer moves, the callback is invoked
+/// with the offset of the pointer relative to the location that started the grab, which is
+/// typically used to interactively move a window.
+///
+/// The grab ends once all buttons are released.
+pub struct DragGrab<F> {
+    start_data: GrabStartData,
+    callback: F,
+}
+
+impl<F> DragGrab<F>
+where
+    F: FnMut((f64, f64)),
+{
+    /// Create a new drag grab
+    ///
+    /// `start_data` is typically retrieved from [`PointerHandle::grab_start_data`] when a client
+    /// requests an interactive move.
+    pub fn new(start_data: GrabStartData, callback: F) -> DragGrab<F> {
+        DragGrab { start_data, callback }
+    }
+}
+
+impl<F> PointerGrab for DragGrab<F>
+where
+    F: FnMut((f64, f64)),
+{
+    fn motion(
+        &mut self,
+        _handle: &mut PointerInnerHandle<'_>,
+        location: (f64, f64),
+        _focus: Option<(WlSurface, (f64, f64))>,
+        _serial: Serial,
+        _time: u32,
+    ) {
+        let dx = location.0 - self.start_data.location.0;
+        let dy = location.1 - self.start_data.location.1;
+        (self.callback)((dx, dy));
+    }
+    fn button(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        button: u32,
+        state: ButtonState,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.button(button, state, serial, time);
+        if handle.current_pressed().is_empty() {
+            // no more buttons are pressed, release the grab
+            handle.unset_grab(serial, time);
+        }
+    }
+    fn axis(&mut self, handle: &mut PointerInnerHandle<'_>, details: AxisFrame) {
+        handle.axis(details);
+    }
+    fn start_data(&self) -> &GrabStartData {
+        &self.start_data
+    }
+}
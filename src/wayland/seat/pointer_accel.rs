@@ -0,0 +1,149 @@
+use crate::backend::input::AccelProfile;
+
+// motions separated by more than this many milliseconds start a new movement
+const MOTION_TIMEOUT: u32 = 100;
+// time assumed between the first motion of a movement and the previous one
+const DEFAULT_INTERVAL: u32 = 10;
+// how fast the acceleration factor grows with the velocity above the threshold
+const INCLINE: f64 = 1.1;
+// smallest factor of the flat profile, so that the pointer still moves at the lowest speed
+const MIN_FLAT_FACTOR: f64 = 0.005;
+
+/// Software acceleration of the relative motions of a pointer
+///
+/// Input backends like libinput already accelerate the motions of pointer devices, according to
+/// the [`AccelProfile`] configured on the device. Other sources of relative motions, such as
+/// virtual pointers of remote desktop clients or nested backends, report raw deltas: feed them
+/// to [`accelerate`](PointerAccel::accelerate) before moving the pointer to get a similar
+/// behavior.
+///
+/// - With the [`Flat`](AccelProfile::Flat) profile, all motions are scaled by the same factor.
+/// - With the [`Adaptive`](AccelProfile::Adaptive) profile, slow motions are left unchanged
+///   for precision, and fast motions are amplified up to a maximum factor.
+///
+/// In both cases, the speed in the `[-1.0, 1.0]` range tunes the factors, `0.0` being the
+/// default speed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointerAccel {
+    profile: AccelProfile,
+    speed: f64,
+    last_time: Option<u32>,
+}
+
+impl PointerAccel {
+    /// Create a new pointer acceleration with given profile and speed
+    pub fn new(profile: AccelProfile, speed: f64) -> PointerAccel {
+        PointerAccel {
+            profile,
+            speed: speed.max(-1.0).min(1.0),
+            last_time: None,
+        }
+    }
+
+    /// The acceleration profile
+    pub fn profile(&self) -> AccelProfile {
+        self.profile
+    }
+
+    /// Change the acceleration profile
+    pub fn set_profile(&mut self, profile: AccelProfile) {
+        self.profile = profile;
+    }
+
+    /// The speed, in the `[-1.0, 1.0]` range
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Change the speed, it is clamped to the `[-1.0, 1.0]` range
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(-1.0).min(1.0);
+    }
+
+    /// Accelerate a relative motion
+    ///
+    /// `time` is the timestamp of the motion in milliseconds, it is used to compute the velocity
+    /// of the pointer with the adaptive profile.
+    pub fn accelerate(&mut self, (dx, dy): (f64, f64), time: u32) -> (f64, f64) {
+        let interval = match self.last_time {
+            Some(last_time) if time.wrapping_sub(last_time) <= MOTION_TIMEOUT => {
+                time.wrapping_sub(last_time).max(1)
+            }
+            _ => DEFAULT_INTERVAL,
+        };
+        self.last_time = Some(time);
+        let factor = match self.profile {
+            AccelProfile::Flat => (1.0 + self.speed).max(MIN_FLAT_FACTOR),
+            AccelProfile::Adaptive => {
+                let velocity = (dx * dx + dy * dy).sqrt() / f64::from(interval);
+                self.adaptive_factor(velocity)
+            }
+        };
+        (dx * factor, dy * factor)
+    }
+
+    /// Forget the previous motions
+    ///
+    /// The next motion is considered as the start of a new movement.
+    pub fn reset(&mut self) {
+        self.last_time = None;
+    }
+
+    // the factor of the adaptive profile for a velocity in units per millisecond
+    fn adaptive_factor(&self, velocity: f64) -> f64 {
+        // faster speeds accelerate sooner and further
+        let threshold = 0.4 - 0.25 * self.speed;
+        let max_factor = 2.0 + 1.5 * self.speed;
+        if velocity <= threshold {
+            1.0
+        } else {
+            (1.0 + INCLINE * (velocity - threshold)).min(max_factor).max(1.0)
+        }
+    }
+}
+
+impl Default for PointerAccel {
+    fn default() -> Self {
+        PointerAccel::new(AccelProfile::Adaptive, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PointerAccel;
+    use crate::backend::input::AccelProfile;
+
+    #[test]
+    fn flat_scales_all_motions() {
+        let mut accel = PointerAccel::new(AccelProfile::Flat, 0.5);
+        assert_eq!(accel.accelerate((2.0, -4.0), 0), (3.0, -6.0));
+        assert_eq!(accel.accelerate((100.0, 0.0), 1), (150.0, 0.0));
+        accel.set_speed(-3.0);
+        assert_eq!(accel.speed(), -1.0);
+        // the slowest speed still moves the pointer
+        assert_eq!(accel.accelerate((2.0, 2.0), 2), (0.01, 0.01));
+    }
+
+    #[test]
+    fn adaptive_keeps_slow_motions() {
+        let mut accel = PointerAccel::default();
+        // 1 unit every 10ms is below the threshold
+        assert_eq!(accel.accelerate((1.0, 0.0), 0), (1.0, 0.0));
+        assert_eq!(accel.accelerate((0.0, 1.0), 10), (0.0, 1.0));
+    }
+
+    #[test]
+    fn adaptive_amplifies_fast_motions() {
+        let mut accel = PointerAccel::default();
+        accel.accelerate((1.0, 0.0), 0);
+        let (dx, _) = accel.accelerate((10.0, 0.0), 10);
+        assert!(dx > 10.0);
+        // the factor is capped
+        let (dx, _) = accel.accelerate((1000.0, 0.0), 20);
+        assert_eq!(dx, 2000.0);
+        // after a pause, the interval since the last motion is not known
+        accel.reset();
+        let (dx, _) = accel.accelerate((1.0, 0.0), 5000);
+        assert_eq!(dx, 1.0);
+    }
+}
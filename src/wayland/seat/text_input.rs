@@ -0,0 +1,523 @@
+//! Text input support, version 3
+//!
+//! Clients use `zwp_text_input_v3` to receive text composed outside of the key events, from
+//! an on-screen keyboard or an input method for languages such as Chinese or Japanese. Each
+//! text input belongs to a seat, and is entered whenever its client gets the keyboard focus
+//! of that seat.
+//!
+//! Text inputs are handled on the seats for which [`init_text_input_v3`] was called. The
+//! compositor is notified when the focused client enables or disables a text input, and
+//! when it updates its state, such as the text surrounding the cursor. It then sends the
+//! composed text through the returned [`TextInputHandle`]:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # extern crate smithay;
+//! use smithay::wayland::seat::{
+//!     init_text_input_manager_v3, init_text_input_v3, KeyboardHandle, Seat, TextInputEvent,
+//! };
+//! # let mut display = wayland_server::Display::new();
+//! # let seat: Seat = unimplemented!();
+//! # let keyboard: KeyboardHandle = unimplemented!();
+//!
+//! init_text_input_manager_v3(&mut display, None);
+//! let text_input = init_text_input_v3(&seat, &keyboard, |event| match event {
+//!     TextInputEvent::Enabled(state) => { /* show the on-screen keyboard */ }
+//!     TextInputEvent::Updated(state) => { /* move it next to state.cursor_rectangle */ }
+//!     TextInputEvent::Disabled => { /* hide it */ }
+//! });
+//!
+//! // once the user composed some text
+//! text_input.preedit_string(None, 0, 0);
+//! text_input.commit_string(Some("你好".into()));
+//! text_input.done();
+//! ```
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use wayland_protocols::unstable::text_input::v3::server::{
+    zwp_text_input_manager_v3::{self, ZwpTextInputManagerV3},
+    zwp_text_input_v3::{self, ChangeCause, ContentHint, ContentPurpose, ZwpTextInputV3},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+
+use super::{KeyboardHandle, Seat};
+use crate::utils::{DoubleBuffered, Rectangle};
+
+/// The text surrounding the cursor of a text input
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurroundingText {
+    /// The text, usually limited to the current paragraph
+    pub text: String,
+    /// Byte offset of the cursor in the text
+    pub cursor: i32,
+    /// Byte offset of the other end of the selection, equal to `cursor` if nothing is selected
+    pub anchor: i32,
+}
+
+/// The state of an enabled text input, as of the last commit of its client
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextInputState {
+    /// The text surrounding the cursor, if provided by the client
+    pub surrounding_text: Option<SurroundingText>,
+    /// What caused the last change of the surrounding text
+    pub change_cause: ChangeCause,
+    /// Hints about the expected content
+    pub content_hint: ContentHint,
+    /// The purpose of the input field
+    pub content_purpose: ContentPurpose,
+    /// Location of the cursor, in surface-local coordinates
+    pub cursor_rectangle: Option<Rectangle>,
+}
+
+impl Default for TextInputState {
+    fn default() -> TextInputState {
+        TextInputState {
+            surrounding_text: None,
+            change_cause: ChangeCause::InputMethod,
+            content_hint: ContentHint::empty(),
+            content_purpose: ContentPurpose::Normal,
+            cursor_rectangle: None,
+        }
+    }
+}
+
+/// A change of the text input of the focused client
+#[derive(Debug, Clone, PartialEq)]
+pub enum TextInputEvent {
+    /// A text input was enabled and text can be sent to it
+    Enabled(TextInputState),
+    /// The enabled text input committed a new state
+    Updated(TextInputState),
+    /// The text input was disabled or destroyed, or its client lost the keyboard focus
+    Disabled,
+}
+
+struct TextInput {
+    resource: ZwpTextInputV3,
+    // `None` while disabled
+    state: DoubleBuffered<Option<TextInputState>>,
+    // number of commit requests, echoed in the done events
+    serial: u32,
+    entered: Option<WlSurface>,
+}
+
+struct TextInputInner {
+    text_inputs: Vec<TextInput>,
+    focus: Option<WlSurface>,
+    active: Option<ZwpTextInputV3>,
+    // events not delivered to the callback yet
+    events: VecDeque<TextInputEvent>,
+    log: ::slog::Logger,
+}
+
+impl TextInputInner {
+    fn get_mut(&mut self, resource: &ZwpTextInputV3) -> Option<&mut TextInput> {
+        self.text_inputs
+            .iter_mut()
+            .find(|t| t.resource.as_ref().equals(resource.as_ref()))
+    }
+
+    fn is_active(&self, resource: &ZwpTextInputV3) -> bool {
+        self.active
+            .as_ref()
+            .map(|a| a.as_ref().equals(resource.as_ref()))
+            .unwrap_or(false)
+    }
+
+    fn active(&self) -> Option<&TextInput> {
+        let active = self.active.as_ref()?;
+        self.text_inputs
+            .iter()
+            .find(|t| t.resource.as_ref().equals(active.as_ref()))
+    }
+}
+
+/// A handle to the text inputs of a seat
+///
+/// It is created by [`init_text_input_v3`], and can be cloned. Its methods act on the enabled
+/// text input of the client with keyboard focus, and do nothing if there is none.
+#[derive(Clone)]
+pub struct TextInputHandle {
+    inner: Rc<RefCell<TextInputInner>>,
+    callback: Rc<RefCell<dyn FnMut(TextInputEvent)>>,
+}
+
+impl TextInputHandle {
+    fn notify(&self, event: Option<TextInputEvent>) {
+        if let Some(event) = event {
+            self.inner.borrow_mut().events.push_back(event);
+        }
+        // the callback may use the handle, or change the keyboard focus which disables the
+        // text input: events sent while it runs are delivered once it returns
+        let mut callback = match self.callback.try_borrow_mut() {
+            Ok(callback) => callback,
+            Err(_) => return,
+        };
+        loop {
+            // release the state before running the callback
+            let event = self.inner.borrow_mut().events.pop_front();
+            match event {
+                Some(event) => (&mut *callback)(event),
+                None => break,
+            }
+        }
+    }
+
+    fn set_focus(&self, focus: Option<&WlSurface>) {
+        let event = {
+            let mut inner = self.inner.borrow_mut();
+            for text_input in &mut inner.text_inputs {
+                if let Some(surface) = text_input.entered.take() {
+                    text_input.resource.leave(&surface);
+                    // the client has to enable the text input again once it is entered
+                    text_input.state = DoubleBuffered::new(None);
+                }
+                if let Some(surface) = focus {
+                    if text_input.resource.as_ref().same_client_as(surface.as_ref()) {
+                        text_input.resource.enter(surface);
+                        text_input.entered = Some(surface.clone());
+                    }
+                }
+            }
+            inner.focus = focus.cloned();
+            inner.active.take().map(|_| TextInputEvent::Disabled)
+        };
+        self.notify(event);
+    }
+
+    fn add_text_input(&self, resource: ZwpTextInputV3) {
+        let mut inner = self.inner.borrow_mut();
+        trace!(inner.log, "New text input");
+        let entered = inner
+            .focus
+            .clone()
+            .filter(|surface| resource.as_ref().same_client_as(surface.as_ref()));
+        if let Some(ref surface) = entered {
+            resource.enter(surface);
+        }
+        inner.text_inputs.push(TextInput {
+            resource,
+            state: DoubleBuffered::new(None),
+            serial: 0,
+            entered,
+        });
+    }
+
+    fn remove_text_input(&self, resource: &ZwpTextInputV3) {
+        let event = {
+            let mut inner = self.inner.borrow_mut();
+            inner
+                .text_inputs
+                .retain(|t| !t.resource.as_ref().equals(resource.as_ref()));
+            if inner.is_active(resource) {
+                inner.active = None;
+                Some(TextInputEvent::Disabled)
+            } else {
+                None
+            }
+        };
+        self.notify(event);
+    }
+
+    fn with_pending<F>(&self, resource: &ZwpTextInputV3, f: F)
+    where
+        F: FnOnce(&mut DoubleBuffered<Option<TextInputState>>),
+    {
+        if let Some(text_input) = self.inner.borrow_mut().get_mut(resource) {
+            f(&mut text_input.state);
+        }
+    }
+
+    fn commit(&self, resource: &ZwpTextInputV3) {
+        let event = {
+            let mut inner = self.inner.borrow_mut();
+            let was_active = inner.is_active(resource);
+            let (state, entered) = match inner.get_mut(resource) {
+                Some(text_input) => {
+                    text_input.serial = text_input.serial.wrapping_add(1);
+                    text_input.state.commit();
+                    (text_input.state.current().clone(), text_input.entered.is_some())
+                }
+                None => return,
+            };
+            match state {
+                // only the text input of the focused client can be enabled
+                Some(state) if entered => {
+                    inner.active = Some(resource.clone());
+                    if was_active {
+                        Some(TextInputEvent::Updated(state))
+                    } else {
+                        trace!(inner.log, "Text input enabled");
+                        Some(TextInputEvent::Enabled(state))
+                    }
+                }
+                _ if was_active => {
+                    trace!(inner.log, "Text input disabled");
+                    inner.active = None;
+                    Some(TextInputEvent::Disabled)
+                }
+                _ => None,
+            }
+        };
+        self.notify(event);
+    }
+
+    fn with_active<F: FnOnce(&TextInput)>(&self, f: F) {
+        if let Some(text_input) = self.inner.borrow().active() {
+            f(text_input);
+        }
+    }
+
+    /// Whether a text input of the focused client is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.inner.borrow().active.is_some()
+    }
+
+    /// The state of the enabled text input, if any
+    pub fn state(&self) -> Option<TextInputState> {
+        self.inner
+            .borrow()
+            .active()
+            .and_then(|text_input| text_input.state.current().clone())
+    }
+
+    /// Set the text being composed, displayed at the cursor of the text input
+    ///
+    /// `cursor_begin` and `cursor_end` are byte offsets in the text of the cursor or the
+    /// highlighted part of the text. Both are `-1` to hide the cursor.
+    pub fn preedit_string(&self, text: Option<String>, cursor_begin: i32, cursor_end: i32) {
+        self.with_active(|t| t.resource.preedit_string(text, cursor_begin, cursor_end));
+    }
+
+    /// Insert text at the cursor, replacing the text being composed
+    pub fn commit_string(&self, text: Option<String>) {
+        self.with_active(|t| t.resource.commit_string(text));
+    }
+
+    /// Delete text around the cursor, or around the selection if there is one
+    ///
+    /// The lengths are in bytes, and must not split a character of the surrounding text.
+    pub fn delete_surrounding_text(&self, before_length: u32, after_length: u32) {
+        self.with_active(|t| t.resource.delete_surrounding_text(before_length, after_length));
+    }
+
+    /// Apply the preedit, commit and delete events sent since the previous call
+    ///
+    /// The client applies them atomically, in the order mandated by the protocol: delete the
+    /// surrounding text, insert the committed text, then display the preedit text.
+    pub fn done(&self) {
+        self.with_active(|t| t.resource.done(t.serial));
+    }
+}
+
+impl ::std::fmt::Debug for TextInputHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("TextInputHandle")
+            .field("text_inputs", &inner.text_inputs.len())
+            .field("focus", &inner.focus)
+            .field("enabled", &inner.active.is_some())
+            .finish()
+    }
+}
+
+/// Handle the text inputs created on a seat
+///
+/// The text inputs are entered and left following the focus of the given keyboard. The
+/// callback is notified whenever the text input of the focused client is enabled, updated
+/// or disabled. It can use the returned [`TextInputHandle`], which is also stored in the
+/// [`user_data`](Seat::user_data) of the seat.
+pub fn init_text_input_v3<F>(seat: &Seat, keyboard: &KeyboardHandle, callback: F) -> TextInputHandle
+where
+    F: FnMut(TextInputEvent) + 'static,
+{
+    let log = seat.arc.log.new(o!("smithay_module" => "text_input_v3_handler"));
+    let handle = TextInputHandle {
+        inner: Rc::new(RefCell::new(TextInputInner {
+            text_inputs: Vec::new(),
+            focus: None,
+            active: None,
+            events: VecDeque::new(),
+            log,
+        })),
+        callback: Rc::new(RefCell::new(callback)),
+    };
+    handle.set_focus(keyboard.current_focus().as_ref());
+    let focus_handle = handle.clone();
+    keyboard.add_focus_listener(move |focus| focus_handle.set_focus(focus));
+    seat.user_data().insert_if_missing(|| handle.clone());
+    handle
+}
+
+/// Create the text input manager global, version 3
+///
+/// Text inputs are only handled on the seats set up with [`init_text_input_v3`].
+pub fn init_text_input_manager_v3<L>(display: &mut Display, logger: L) -> Global<ZwpTextInputManagerV3>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "text_input_v3_handler"));
+
+    display.create_global::<ZwpTextInputManagerV3, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpTextInputManagerV3>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_text_input_manager_v3::Request::GetTextInput { id, seat } => {
+                        let handle = Seat::from_resource(&seat)
+                            .and_then(|seat| seat.user_data().get::<TextInputHandle>().cloned());
+                        if handle.is_none() {
+                            warn!(log, "Text input created on a seat without text input support");
+                        }
+                        implement_text_input(id, handle);
+                    }
+                    zwp_text_input_manager_v3::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+fn implement_text_input(text_input: Main<ZwpTextInputV3>, handle: Option<TextInputHandle>) {
+    let handle = match handle {
+        Some(handle) => handle,
+        None => {
+            // an inert text input, that is never entered
+            text_input.quick_assign(|_, _, _| {});
+            return;
+        }
+    };
+    let destructor_handle = handle.clone();
+    text_input.quick_assign(move |text_input, request, _| {
+        let text_input = &*text_input;
+        match request {
+            zwp_text_input_v3::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            zwp_text_input_v3::Request::Enable => {
+                // enabling resets the whole state
+                handle.with_pending(text_input, |state| {
+                    state.set_pending(Some(TextInputState::default()))
+                })
+            }
+            zwp_text_input_v3::Request::Disable => {
+                handle.with_pending(text_input, |state| state.set_pending(None))
+            }
+            zwp_text_input_v3::Request::SetSurroundingText { text, cursor, anchor } => {
+                handle.with_pending(text_input, |state| {
+                    if let Some(state) = state.pending_mut() {
+                        state.surrounding_text = Some(SurroundingText { text, cursor, anchor });
+                    }
+                })
+            }
+            zwp_text_input_v3::Request::SetTextChangeCause { cause } => {
+                handle.with_pending(text_input, |state| {
+                    if let Some(state) = state.pending_mut() {
+                        state.change_cause = cause;
+                    }
+                })
+            }
+            zwp_text_input_v3::Request::SetContentType { hint, purpose } => {
+                handle.with_pending(text_input, |state| {
+                    if let Some(state) = state.pending_mut() {
+                        state.content_hint = hint;
+                        state.content_purpose = purpose;
+                    }
+                })
+            }
+            zwp_text_input_v3::Request::SetCursorRectangle { x, y, width, height } => {
+                handle.with_pending(text_input, |state| {
+                    if let Some(state) = state.pending_mut() {
+                        state.cursor_rectangle = Some(Rectangle { x, y, width, height });
+                    }
+                })
+            }
+            zwp_text_input_v3::Request::Commit => handle.commit(text_input),
+            _ => unreachable!(),
+        }
+    });
+    text_input.assign_destructor(Filter::new(move |text_input: ZwpTextInputV3, _, _| {
+        destructor_handle.remove_text_input(&text_input);
+    }));
+    handle.add_text_input((*text_input).clone());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{seat::keyboard::xkb::XkbState, SERIAL_COUNTER};
+    use std::os::unix::{io::IntoRawFd, net::UnixStream};
+
+    const KEYMAP: &str = r#"xkb_keymap {
+    xkb_keycodes {
+        minimum = 8;
+        maximum = 255;
+        <AD01> = 24;
+    };
+    xkb_types {
+        type "ONE_LEVEL" {
+            modifiers = none;
+            level_name[Level1] = "Any";
+        };
+    };
+    xkb_compatibility {
+    };
+    xkb_symbols {
+        key <AD01> { [ q ] };
+    };
+};"#;
+
+    #[test]
+    fn callback_changing_focus() {
+        let log = ::slog::Logger::root(::slog::Discard, o!());
+        let mut display = Display::new();
+        let (server, _client) = UnixStream::pair().unwrap();
+        let client = unsafe { display.create_client(server.into_raw_fd(), &mut ()) };
+        let first = client.create_resource::<WlSurface>(4).unwrap();
+        let second = client.create_resource::<WlSurface>(4).unwrap();
+
+        let keyboard = KeyboardHandle::from_xkb(
+            XkbState::new_from_string(KEYMAP).unwrap(),
+            200,
+            25,
+            log.clone(),
+            |_| {},
+        );
+        let seat = Seat::with_name("seat0".into(), &log);
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let callback_events = events.clone();
+        let callback_keyboard = keyboard.clone();
+        let callback_first = (*first).clone();
+        // the text input keeps the focus on the first surface
+        let handle = init_text_input_v3(&seat, &keyboard, move |event| {
+            if event == TextInputEvent::Disabled {
+                callback_keyboard.set_focus(Some(&callback_first), SERIAL_COUNTER.next_serial());
+            }
+            callback_events.borrow_mut().push(event);
+        });
+
+        keyboard.set_focus(Some(&*first), SERIAL_COUNTER.next_serial());
+        let text_input = client.create_resource::<ZwpTextInputV3>(1).unwrap();
+        handle.add_text_input((*text_input).clone());
+        handle.with_pending(&text_input, |state| {
+            state.set_pending(Some(TextInputState::default()))
+        });
+        handle.commit(&text_input);
+        assert!(handle.is_enabled());
+
+        keyboard.set_focus(Some(&*second), SERIAL_COUNTER.next_serial());
+        assert_eq!(keyboard.current_focus(), Some((*first).clone()));
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                TextInputEvent::Enabled(TextInputState::default()),
+                TextInputEvent::Disabled
+            ]
+        );
+    }
+}
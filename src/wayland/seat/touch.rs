@@ -0,0 +1,659 @@
+use std::{cell::RefCell, collections::HashMap, ops::Deref as _, rc::Rc};
+
+use wayland_server::{
+    protocol::{
+        wl_surface::WlSurface,
+        wl_touch::{Request, WlTouch},
+    },
+    Filter, Main,
+};
+
+use super::{LongPressConfig, LongPressEmulation, LongPressProgress};
+use crate::backend::input::TouchSlot;
+use crate::wayland::Serial;
+
+enum GrabStatus {
+    None,
+    Active(Serial, Box<dyn TouchGrab>),
+    Borrowed,
+}
+
+// A touch point currently in contact with the device
+struct TouchPoint {
+    // the id identifying this point to clients
+    id: i32,
+    focus: Option<(WlSurface, (f64, f64))>,
+}
+
+struct TouchInternal {
+    known_touches: Vec<WlTouch>,
+    points: HashMap<Option<TouchSlot>, TouchPoint>,
+    // touch objects that received events since the last frame
+    pending_frame: Vec<WlTouch>,
+    grab: GrabStatus,
+    long_press: Option<LongPressEmulation>,
+}
+
+impl TouchInternal {
+    fn new() -> TouchInternal {
+        TouchInternal {
+            known_touches: Vec::new(),
+            points: HashMap::new(),
+            pending_frame: Vec::new(),
+            grab: GrabStatus::None,
+            long_press: None,
+        }
+    }
+
+    fn next_id(&self) -> i32 {
+        (0..)
+            .find(|id| self.points.values().all(|point| point.id != *id))
+            .unwrap()
+    }
+
+    fn with_touches_of<F>(&mut self, surface: &WlSurface, mut f: F)
+    where
+        F: FnMut(&WlTouch),
+    {
+        let TouchInternal {
+            ref known_touches,
+            ref mut pending_frame,
+            ..
+        } = *self;
+        for touch in known_touches {
+            if touch.as_ref().same_client_as(surface.as_ref()) {
+                f(touch);
+                if !pending_frame.iter().any(|t| t.as_ref().equals(touch.as_ref())) {
+                    pending_frame.push(touch.clone());
+                }
+            }
+        }
+    }
+
+    fn with_grab<F>(&mut self, f: F)
+    where
+        F: FnOnce(TouchInnerHandle<'_>, &mut dyn TouchGrab),
+    {
+        let mut grab = ::std::mem::replace(&mut self.grab, GrabStatus::Borrowed);
+        match grab {
+            GrabStatus::Borrowed => panic!("Accessed a touch grab from within a touch grab access."),
+            GrabStatus::Active(_, ref mut handler) => {
+                f(TouchInnerHandle { inner: self }, &mut **handler);
+            }
+            GrabStatus::None => {
+                f(TouchInnerHandle { inner: self }, &mut DefaultGrab);
+            }
+        }
+
+        if let GrabStatus::Borrowed = self.grab {
+            // the grab has not been ended nor replaced, put it back in place
+            self.grab = grab;
+        }
+    }
+}
+
+/// An handle to a touch handler
+///
+/// It can be cloned and all clones manipulate the same internal state.
+///
+/// This handle gives you access to an interface to send touch events to your
+/// clients. Each touch point is identified by the [`TouchSlot`] reported by the
+/// input backend, and delivered to the surface it went down on until it is lifted.
+///
+/// When sending events using this handle, they will be intercepted by a touch
+/// grab if any is active. See the [`TouchGrab`] trait for details.
+///
+/// When a touch point goes down while no grab is active, it starts an implicit grab with the
+/// serial of its down event, which lasts until this point is lifted. Clients use this serial to
+/// request interactive moves, resizes or drag'n'drops, which you can check with
+/// [`has_grab`](TouchHandle::has_grab) and [`grab_start_data`](TouchHandle::grab_start_data)
+/// like for the pointer.
+///
+/// The handle can also detect touch points being held still, to emulate right-clicks for
+/// surfaces without touch support, see [`set_long_press`](TouchHandle::set_long_press).
+#[derive(Clone)]
+pub struct TouchHandle {
+    inner: Rc<RefCell<TouchInternal>>,
+}
+
+impl TouchHandle {
+    pub(crate) fn new_touch(&self, touch: WlTouch) {
+        self.inner.borrow_mut().known_touches.push(touch);
+    }
+
+    /// Change the current grab on this touch device to the provided grab
+    ///
+    /// Overwrites any current grab.
+    pub fn set_grab<G: TouchGrab + 'static>(&self, grab: G, serial: Serial) {
+        self.inner.borrow_mut().grab = GrabStatus::Active(serial, Box::new(grab));
+    }
+
+    /// Remove any current grab on this touch device, reseting it to the default behavior
+    pub fn unset_grab(&self) {
+        self.inner.borrow_mut().grab = GrabStatus::None;
+    }
+
+    /// Check if this touch device is currently grabbed with this serial
+    pub fn has_grab(&self, serial: Serial) -> bool {
+        match self.inner.borrow().grab {
+            GrabStatus::Active(s, _) => s == serial,
+            _ => false,
+        }
+    }
+
+    /// Check if this touch device is currently being grabbed
+    pub fn is_grabbed(&self) -> bool {
+        match self.inner.borrow().grab {
+            GrabStatus::None => false,
+            _ => true,
+        }
+    }
+
+    /// Returns the start data for the grab, if any.
+    pub fn grab_start_data(&self) -> Option<TouchGrabStartData> {
+        match self.inner.borrow().grab {
+            GrabStatus::Active(_, ref g) => Some(g.start_data().clone()),
+            _ => None,
+        }
+    }
+
+    /// Notify that a new touch point went down
+    ///
+    /// You provide the location of the touch point in the global compositor space, and the
+    /// surface under it along with the coordinates of its origin in the global compositor
+    /// space (or `None` if the touch point is not on top of a client surface).
+    ///
+    /// The touch point keeps delivering its events to this surface until it is lifted.
+    pub fn down(
+        &self,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(ref mut long_press) = inner.long_press {
+            long_press.touch_down(slot, location, time);
+        }
+        inner.with_grab(move |mut handle, grab| {
+            grab.down(&mut handle, slot, location, focus, serial, time);
+        });
+    }
+
+    /// Notify that a touch point was lifted
+    ///
+    /// Returns `true` if this touch point triggered an emulated right-click, see
+    /// [`set_long_press`](TouchHandle::set_long_press). The up event is delivered to clients
+    /// in any case.
+    pub fn up(&self, slot: Option<TouchSlot>, serial: Serial, time: u32) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        let consumed = match inner.long_press {
+            Some(ref mut long_press) => long_press.touch_up(slot),
+            None => false,
+        };
+        inner.with_grab(move |mut handle, grab| {
+            grab.up(&mut handle, slot, serial, time);
+        });
+        consumed
+    }
+
+    /// Notify that a touch point moved
+    ///
+    /// The location is given in the global compositor space, and the surface under the touch
+    /// point is provided as for [`down`](TouchHandle::down). Clients keep receiving the motions
+    /// on the surface the point went down on, the new focus is only used by grabs such as
+    /// drag'n'drop.
+    pub fn motion(
+        &self,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        time: u32,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(ref mut long_press) = inner.long_press {
+            long_press.touch_motion(slot, location);
+        }
+        inner.with_grab(move |mut handle, grab| {
+            grab.motion(&mut handle, slot, location, focus, time);
+        });
+    }
+
+    /// Notify the end of a set of events that logically belong together
+    ///
+    /// Input backends report a frame event after each batch of down, up and motion events.
+    pub fn frame(&self) {
+        self.inner.borrow_mut().with_grab(|mut handle, grab| {
+            grab.frame(&mut handle);
+        });
+    }
+
+    /// Notify that the current touch sequence was cancelled
+    ///
+    /// This typically happens when the compositor recognizes a gesture, all touch points
+    /// are forgotten and clients are told to ignore them.
+    pub fn cancel(&self) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(ref mut long_press) = inner.long_press {
+            long_press.touch_cancel();
+        }
+        inner.with_grab(|mut handle, grab| {
+            grab.cancel(&mut handle);
+        });
+    }
+
+    /// Check whether any touch point is currently down
+    pub fn is_active(&self) -> bool {
+        !self.inner.borrow().points.is_empty()
+    }
+
+    /// Enable or disable the long-press emulation of right-clicks on this touch device
+    ///
+    /// While enabled, the touch events sent through this handle are fed to a
+    /// [`LongPressEmulation`] with the given configuration. Regularly call
+    /// [`long_press_update`](TouchHandle::long_press_update) while a long-press is
+    /// [pending](TouchHandle::long_press_progress), and send the right-click with
+    /// [`emulate_right_click`](super::emulate_right_click) if the surface under the touch
+    /// point does not support touch input.
+    ///
+    /// Changing the configuration of an enabled emulation only affects touch sequences
+    /// started afterwards.
+    pub fn set_long_press(&self, config: Option<LongPressConfig>) {
+        let mut inner = self.inner.borrow_mut();
+        if let (Some(config), Some(long_press)) = (config, inner.long_press.as_mut()) {
+            long_press.set_config(config);
+            return;
+        }
+        inner.long_press = config.map(LongPressEmulation::new);
+    }
+
+    /// Check if the pending long-press has elapsed
+    ///
+    /// Returns the location at which the right-click should happen, see
+    /// [`LongPressEmulation::update`]. Always returns `None` if the emulation is disabled.
+    pub fn long_press_update(&self, time: u32) -> Option<(f64, f64)> {
+        self.inner
+            .borrow_mut()
+            .long_press
+            .as_mut()
+            .and_then(|long_press| long_press.update(time))
+    }
+
+    /// Progress of the currently pending long-press, if any
+    pub fn long_press_progress(&self, time: u32) -> Option<LongPressProgress> {
+        self.inner
+            .borrow()
+            .long_press
+            .as_ref()
+            .and_then(|long_press| long_press.progress(time))
+    }
+}
+
+/// Data about the event that started the grab.
+#[derive(Clone)]
+pub struct TouchGrabStartData {
+    /// The focused surface and its location, if any, at the start of the grab.
+    ///
+    /// The location coordinates are in the global compositor space.
+    pub focus: Option<(WlSurface, (f64, f64))>,
+    /// The touch point that initiated the grab.
+    pub slot: Option<TouchSlot>,
+    /// The location of the touch point that initiated the grab, in the global compositor space.
+    pub location: (f64, f64),
+}
+
+/// A trait to implement a touch grab
+///
+/// In some context, it is necessary to temporarily change the behavior of the touch device,
+/// for example to move a window or during a drag'n'drop operation started by a touch point.
+///
+/// This trait is the interface to intercept regular touch events and change them as needed, its
+/// interface mimics the [`TouchHandle`] interface.
+///
+/// If your logic decides that the grab should end, both [`TouchInnerHandle`] and [`TouchHandle`] have
+/// a method to change it.
+///
+/// When your grab ends (either as you requested it or if it was forcefully cancelled by the server),
+/// the struct implementing this trait will be dropped. As such you should put clean-up logic in the destructor,
+/// rather than trying to guess when the grab will end.
+pub trait TouchGrab {
+    /// A new touch point went down
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    );
+    /// A touch point was lifted
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, slot: Option<TouchSlot>, serial: Serial, time: u32);
+    /// A touch point moved
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        time: u32,
+    );
+    /// A set of touch events ended
+    fn frame(&mut self, handle: &mut TouchInnerHandle<'_>);
+    /// The touch sequence was cancelled
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>);
+    /// The data about the event that started the grab.
+    fn start_data(&self) -> &TouchGrabStartData;
+}
+
+/// This inner handle is accessed from inside a touch grab logic, and directly
+/// sends event to the client
+pub struct TouchInnerHandle<'a> {
+    inner: &'a mut TouchInternal,
+}
+
+impl<'a> TouchInnerHandle<'a> {
+    /// Change the current grab on this touch device to the provided grab
+    ///
+    /// Overwrites any current grab.
+    pub fn set_grab<G: TouchGrab + 'static>(&mut self, serial: Serial, grab: G) {
+        self.inner.grab = GrabStatus::Active(serial, Box::new(grab));
+    }
+
+    /// Remove any current grab on this touch device, resetting it to the default behavior
+    pub fn unset_grab(&mut self) {
+        self.inner.grab = GrabStatus::None;
+    }
+
+    /// Access the surface a touch point is delivered to, if the point is down
+    pub fn current_focus(&self, slot: Option<TouchSlot>) -> Option<&(WlSurface, (f64, f64))> {
+        self.inner
+            .points
+            .get(&slot)
+            .and_then(|point| point.focus.as_ref())
+    }
+
+    /// The number of touch points currently down
+    pub fn current_points(&self) -> usize {
+        self.inner.points.len()
+    }
+
+    /// Send a down event for a new touch point to the surface under it
+    pub fn down(
+        &mut self,
+        slot: Option<TouchSlot>,
+        (x, y): (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        let id = self.inner.next_id();
+        if let Some((ref surface, (sx, sy))) = focus {
+            self.inner.with_touches_of(surface, |touch| {
+                touch.down(serial.into(), time, surface, id, x - sx, y - sy);
+            });
+        }
+        self.inner.points.insert(slot, TouchPoint { id, focus });
+    }
+
+    /// Send an up event for a touch point to the surface it went down on
+    pub fn up(&mut self, slot: Option<TouchSlot>, serial: Serial, time: u32) {
+        if let Some(TouchPoint { id, focus }) = self.inner.points.remove(&slot) {
+            if let Some((ref surface, _)) = focus {
+                self.inner.with_touches_of(surface, |touch| {
+                    touch.up(serial.into(), time, id);
+                });
+            }
+        }
+    }
+
+    /// Send a motion event for a touch point to the surface it went down on
+    pub fn motion(&mut self, slot: Option<TouchSlot>, (x, y): (f64, f64), time: u32) {
+        let (id, focus) = match self.inner.points.get(&slot) {
+            Some(&TouchPoint {
+                id,
+                focus: Some(ref focus),
+            }) => (id, focus.clone()),
+            _ => return,
+        };
+        let (ref surface, (sx, sy)) = focus;
+        self.inner.with_touches_of(surface, |touch| {
+            touch.motion(time, id, x - sx, y - sy);
+        });
+    }
+
+    /// Send a frame event to all clients that received touch events since the last frame
+    pub fn frame(&mut self) {
+        for touch in self.inner.pending_frame.drain(..) {
+            touch.frame();
+        }
+    }
+
+    /// Send a cancel event to all clients with touch points down, and forget all touch points
+    pub fn cancel(&mut self) {
+        let points = ::std::mem::replace(&mut self.inner.points, HashMap::new());
+        let mut cancelled: Vec<WlTouch> = Vec::new();
+        for (ref surface, _) in points.into_iter().filter_map(|(_, point)| point.focus) {
+            for touch in &self.inner.known_touches {
+                if touch.as_ref().same_client_as(surface.as_ref())
+                    && !cancelled.iter().any(|t| t.as_ref().equals(touch.as_ref()))
+                {
+                    touch.cancel();
+                    cancelled.push(touch.clone());
+                }
+            }
+        }
+        self.inner.pending_frame.clear();
+    }
+}
+
+pub(crate) fn create_touch_handler() -> TouchHandle {
+    TouchHandle {
+        inner: Rc::new(RefCell::new(TouchInternal::new())),
+    }
+}
+
+pub(crate) fn implement_touch(touch: Main<WlTouch>, handle: Option<&TouchHandle>) -> WlTouch {
+    touch.quick_assign(|_touch, request, _data| {
+        match request {
+            Request::Release => {
+                // Our destructors already handle it
+            }
+            _ => unreachable!(),
+        }
+    });
+
+    if let Some(h) = handle {
+        let inner = h.inner.clone();
+        touch.assign_destructor(Filter::new(move |touch: WlTouch, _, _| {
+            let mut inner = inner.borrow_mut();
+            inner
+                .known_touches
+                .retain(|t| !t.as_ref().equals(&touch.as_ref()));
+            inner
+                .pending_frame
+                .retain(|t| !t.as_ref().equals(&touch.as_ref()));
+        }))
+    }
+
+    touch.deref().clone()
+}
+
+/*
+ * Grabs definition
+ */
+
+// The default grab, the behavior when no particular grab is in progress
+struct DefaultGrab;
+
+impl TouchGrab for DefaultGrab {
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.down(slot, location, focus.clone(), serial, time);
+        handle.set_grab(
+            serial,
+            TouchDownGrab {
+                start_data: TouchGrabStartData {
+                    focus,
+                    slot,
+                    location,
+                },
+            },
+        );
+    }
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, slot: Option<TouchSlot>, serial: Serial, time: u32) {
+        handle.up(slot, serial, time);
+    }
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        _focus: Option<(WlSurface, (f64, f64))>,
+        time: u32,
+    ) {
+        handle.motion(slot, location, time);
+    }
+    fn frame(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.frame();
+    }
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.cancel();
+    }
+    fn start_data(&self) -> &TouchGrabStartData {
+        unreachable!()
+    }
+}
+
+// The implicit grab of a touch point, that lasts until this point is lifted
+//
+// Events are delivered as usual, this grab only keeps track of the touch point
+// that started it, so that clients can request an interactive action with its serial.
+struct TouchDownGrab {
+    start_data: TouchGrabStartData,
+}
+
+impl TouchGrab for TouchDownGrab {
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.down(slot, location, focus, serial, time);
+    }
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, slot: Option<TouchSlot>, serial: Serial, time: u32) {
+        handle.up(slot, serial, time);
+        if slot == self.start_data.slot {
+            // the touch point that started the grab was lifted, release the grab
+            handle.unset_grab();
+        }
+    }
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        _focus: Option<(WlSurface, (f64, f64))>,
+        time: u32,
+    ) {
+        handle.motion(slot, location, time);
+    }
+    fn frame(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.frame();
+    }
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.cancel();
+        handle.unset_grab();
+    }
+    fn start_data(&self) -> &TouchGrabStartData {
+        &self.start_data
+    }
+}
+
+/// A touch drag grab, forwarding the movements of a touch point to a callback
+///
+/// This is the touch counterpart of the pointer [`DragGrab`](super::DragGrab): the touch point
+/// that started the grab no longer sends motion events to clients, instead the callback is
+/// invoked with the offset of the point relative to the location where it went down. The other
+/// touch points are delivered as usual.
+///
+/// The grab ends once the touch point that started it is lifted.
+pub struct TouchDragGrab<F> {
+    start_data: TouchGrabStartData,
+    callback: F,
+}
+
+impl<F> TouchDragGrab<F>
+where
+    F: FnMut((f64, f64)),
+{
+    /// Create a new touch drag grab
+    ///
+    /// `start_data` is typically retrieved from [`TouchHandle::grab_start_data`] when a client
+    /// requests an interactive move.
+    pub fn new(start_data: TouchGrabStartData, callback: F) -> TouchDragGrab<F> {
+        TouchDragGrab { start_data, callback }
+    }
+}
+
+impl<F> TouchGrab for TouchDragGrab<F>
+where
+    F: FnMut((f64, f64)),
+{
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.down(slot, location, focus, serial, time);
+    }
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, slot: Option<TouchSlot>, serial: Serial, time: u32) {
+        handle.up(slot, serial, time);
+        if slot == self.start_data.slot {
+            // the touch point that started the grab was lifted, release the grab
+            handle.unset_grab();
+        }
+    }
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        slot: Option<TouchSlot>,
+        location: (f64, f64),
+        _focus: Option<(WlSurface, (f64, f64))>,
+        time: u32,
+    ) {
+        if slot == self.start_data.slot {
+            let dx = location.0 - self.start_data.location.0;
+            let dy = location.1 - self.start_data.location.1;
+            (self.callback)((dx, dy));
+        } else {
+            handle.motion(slot, location, time);
+        }
+    }
+    fn frame(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.frame();
+    }
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.cancel();
+        handle.unset_grab();
+    }
+    fn start_data(&self) -> &TouchGrabStartData {
+        &self.start_data
+    }
+}
@@ -0,0 +1,316 @@
+//! Handler for the fullscreen shell protocol
+//!
+//! This protocol is meant for kiosk-style compositors, which only ever display a single
+//! surface per output. Clients do not create windows, but ask for a surface to be presented
+//! on an output, describing how it should be scaled if its size does not match the output.
+//! They can also ask for the output mode to be changed to match their surface.
+//!
+//! The handler forwards these requests to your implementation, which decides what to display.
+//! [`PresentMethod::geometry`] computes where a surface is displayed according to the method
+//! the client requested.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # extern crate smithay;
+//! use smithay::wayland::shell::fullscreen::{
+//!     fullscreen_shell_init, FullscreenShellConfig, FullscreenShellRequest,
+//! };
+//! # let mut display = wayland_server::Display::new();
+//!
+//! fullscreen_shell_init(
+//!     &mut display,
+//!     FullscreenShellConfig::default(),
+//!     |request| match request {
+//!         FullscreenShellRequest::PresentSurface { surface, method, output } => {
+//!             /* display the surface on the output, or on all of them */
+//!         }
+//!         FullscreenShellRequest::PresentSurfaceForMode { feedback, .. } => {
+//!             /* try to switch the output to a mode matching the surface */
+//!             feedback.failed();
+//!         }
+//!     },
+//!     None, /* insert a logger here */
+//! );
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::fullscreen_shell::v1::server::{
+    zwp_fullscreen_shell_mode_feedback_v1::ZwpFullscreenShellModeFeedbackV1,
+    zwp_fullscreen_shell_v1::{self, Capability, ZwpFullscreenShellV1},
+};
+use wayland_server::{
+    protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    Display, Filter, Global, Main,
+};
+
+use crate::utils::Rectangle;
+
+/// How a surface should be presented if its size does not match the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMethod {
+    /// No preference, the compositor applies its default policy
+    Default,
+    /// Center the surface on the output, without scaling it
+    Center,
+    /// Scale the surface, preserving its aspect ratio, to the largest size fitting the output
+    Zoom,
+    /// Scale the surface, preserving its aspect ratio, to fill the output, cropping it if needed
+    ZoomCrop,
+    /// Scale the surface to the size of the output, ignoring its aspect ratio
+    Stretch,
+}
+
+impl PresentMethod {
+    fn from_raw(method: u32) -> Option<PresentMethod> {
+        use self::zwp_fullscreen_shell_v1::PresentMethod as Raw;
+        Raw::from_raw(method).map(|method| match method {
+            Raw::Default => PresentMethod::Default,
+            Raw::Center => PresentMethod::Center,
+            Raw::Zoom => PresentMethod::Zoom,
+            Raw::ZoomCrop => PresentMethod::ZoomCrop,
+            Raw::Stretch => PresentMethod::Stretch,
+        })
+    }
+
+    /// Where a surface of the given size is displayed on an output of the given size
+    ///
+    /// The returned rectangle is relative to the output, and may extend past its edges when
+    /// cropping. [`PresentMethod::Default`] is resolved to `default`, which falls back to
+    /// centering if it is itself `Default`.
+    pub fn geometry(self, default: PresentMethod, surface: (i32, i32), output: (i32, i32)) -> Rectangle {
+        let method = match self {
+            PresentMethod::Default => default,
+            method => method,
+        };
+        let (sw, sh) = (surface.0.max(1), surface.1.max(1));
+        let (ow, oh) = output;
+        let (width, height) = match method {
+            PresentMethod::Default | PresentMethod::Center => (sw, sh),
+            PresentMethod::Stretch => (ow, oh),
+            PresentMethod::Zoom | PresentMethod::ZoomCrop => {
+                let scale_x = f64::from(ow) / f64::from(sw);
+                let scale_y = f64::from(oh) / f64::from(sh);
+                let scale = if method == PresentMethod::Zoom {
+                    scale_x.min(scale_y)
+                } else {
+                    scale_x.max(scale_y)
+                };
+                (
+                    (f64::from(sw) * scale).round() as i32,
+                    (f64::from(sh) * scale).round() as i32,
+                )
+            }
+        };
+        Rectangle {
+            x: (ow - width) / 2,
+            y: (oh - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+/// Configuration of the fullscreen shell
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FullscreenShellConfig {
+    /// Whether the compositor can switch outputs to almost any mode
+    ///
+    /// Clients use it to decide whether to request a mode matching their surface.
+    pub arbitrary_modes: bool,
+    /// Whether the compositor has a separate cursor plane
+    pub cursor_plane: bool,
+}
+
+/// The result of a request to present a surface with a specific mode
+///
+/// Exactly one of its methods should be called. If none is, the client is told the
+/// presentation was cancelled once this handle is dropped.
+pub struct ModeFeedback {
+    feedback: Option<ZwpFullscreenShellModeFeedbackV1>,
+}
+
+impl ModeFeedback {
+    fn send<F: FnOnce(&ZwpFullscreenShellModeFeedbackV1)>(mut self, f: F) {
+        if let Some(feedback) = self.feedback.take() {
+            if feedback.as_ref().is_alive() {
+                f(&feedback);
+            }
+        }
+    }
+
+    /// The output mode was switched and the surface is presented
+    pub fn successful(self) {
+        self.send(|feedback| feedback.mode_successful());
+    }
+
+    /// The output mode could not be switched, the surface is not presented
+    pub fn failed(self) {
+        self.send(|feedback| feedback.mode_failed());
+    }
+
+    /// Another surface was presented on the output before the mode switch completed
+    pub fn cancelled(self) {
+        self.send(|feedback| feedback.present_cancelled());
+    }
+}
+
+impl Drop for ModeFeedback {
+    fn drop(&mut self) {
+        if let Some(feedback) = self.feedback.take() {
+            if feedback.as_ref().is_alive() {
+                feedback.present_cancelled();
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Debug for ModeFeedback {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("ModeFeedback")
+            .field("pending", &self.feedback.is_some())
+            .finish()
+    }
+}
+
+/// A request of a client of the fullscreen shell
+#[derive(Debug)]
+pub enum FullscreenShellRequest {
+    /// Present a surface on an output
+    PresentSurface {
+        /// The surface to present, or `None` to stop presenting the surface of this client
+        surface: Option<WlSurface>,
+        /// How to scale the surface if it does not fit the output
+        method: PresentMethod,
+        /// The output to present the surface on, or `None` to let the compositor choose
+        output: Option<WlOutput>,
+    },
+    /// Present a surface on an output, switching the output to a mode matching its size
+    ///
+    /// The mode should be chosen for the size of the next buffer committed to the surface.
+    PresentSurfaceForMode {
+        /// The surface to present
+        surface: WlSurface,
+        /// The output to switch the mode of
+        output: WlOutput,
+        /// The requested refresh rate in mHz, 0 for no preference
+        framerate: i32,
+        /// Handle to report the result of the mode switch to the client
+        feedback: ModeFeedback,
+    },
+}
+
+/// Create a new fullscreen shell global
+pub fn fullscreen_shell_init<Impl, L>(
+    display: &mut Display,
+    config: FullscreenShellConfig,
+    implementation: Impl,
+    logger: L,
+) -> Global<ZwpFullscreenShellV1>
+where
+    Impl: FnMut(FullscreenShellRequest) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "fullscreen_shell_handler"));
+    let implementation = Rc::new(RefCell::new(implementation));
+
+    display.create_global::<ZwpFullscreenShellV1, _>(
+        1,
+        Filter::new(
+            move |(shell, _version): (Main<ZwpFullscreenShellV1>, u32), _, _| {
+                let implementation = implementation.clone();
+                let log = log.clone();
+                shell.quick_assign(move |shell, request, _| {
+                    let request = match request {
+                        zwp_fullscreen_shell_v1::Request::Release => {
+                            // Nothing to do
+                            return;
+                        }
+                        zwp_fullscreen_shell_v1::Request::PresentSurface {
+                            surface,
+                            method,
+                            output,
+                        } => {
+                            let method = match PresentMethod::from_raw(method) {
+                                Some(method) => method,
+                                None => {
+                                    shell.as_ref().post_error(
+                                        zwp_fullscreen_shell_v1::Error::InvalidMethod as u32,
+                                        format!("Unknown present method {}.", method),
+                                    );
+                                    return;
+                                }
+                            };
+                            FullscreenShellRequest::PresentSurface {
+                                surface,
+                                method,
+                                output,
+                            }
+                        }
+                        zwp_fullscreen_shell_v1::Request::PresentSurfaceForMode {
+                            surface,
+                            output,
+                            framerate,
+                            feedback,
+                        } => {
+                            // the feedback object has no requests
+                            feedback.quick_assign(|_, _, _| {});
+                            trace!(log, "Mode switch requested"; "framerate" => framerate);
+                            FullscreenShellRequest::PresentSurfaceForMode {
+                                surface,
+                                output,
+                                framerate,
+                                feedback: ModeFeedback {
+                                    feedback: Some((*feedback).clone()),
+                                },
+                            }
+                        }
+                        _ => unreachable!(),
+                    };
+                    (&mut *implementation.borrow_mut())(request);
+                });
+                if config.arbitrary_modes {
+                    shell.capability(Capability::ArbitraryModes.to_raw());
+                }
+                if config.cursor_plane {
+                    shell.capability(Capability::CursorPlane.to_raw());
+                }
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PresentMethod;
+    use crate::utils::Rectangle;
+
+    #[test]
+    fn present_methods() {
+        let output = (1920, 1080);
+        let surface = (960, 720);
+        let rect = |x, y, width, height| Rectangle { x, y, width, height };
+
+        assert_eq!(
+            PresentMethod::Center.geometry(PresentMethod::Default, surface, output),
+            rect(480, 180, 960, 720)
+        );
+        assert_eq!(
+            PresentMethod::Zoom.geometry(PresentMethod::Default, surface, output),
+            rect(240, 0, 1440, 1080)
+        );
+        assert_eq!(
+            PresentMethod::ZoomCrop.geometry(PresentMethod::Default, surface, output),
+            rect(0, -180, 1920, 1440)
+        );
+        assert_eq!(
+            PresentMethod::Stretch.geometry(PresentMethod::Default, surface, output),
+            rect(0, 0, 1920, 1080)
+        );
+        // the default method resolves to the compositor policy
+        assert_eq!(
+            PresentMethod::Default.geometry(PresentMethod::Stretch, surface, output),
+            rect(0, 0, 1920, 1080)
+        );
+    }
+}
@@ -13,6 +13,81 @@
 //!   the current standard for desktop apps
 //! - The [`legacy`](legacy/index.html) module provides handlers for the `wl_shell` protocol, which
 //!   is now deprecated. You only need it if you want to support apps predating `xdg_shell`.
+//!
+//! Both handlers give you a handle to the toplevel surfaces created by clients. If you support
+//! both protocols, [`Toplevel`] wraps either of them, so that the rest of your compositor
+//! (window management, focus, rendering) does not need to care which protocol a window uses.
+//!
+//! Kiosk-style compositors displaying a single surface per output can use the
+//! [`fullscreen`](fullscreen/index.html) module instead, which handles the fullscreen shell
+//! protocol.
+//!
+//! The [`placement`](placement/index.html) module helps you choose where new windows are
+//! displayed when they are mapped.
 
+use crate::wayland::compositor::roles::Role;
+use wayland_server::protocol::wl_surface;
+
+pub mod fullscreen;
 pub mod legacy;
+pub mod placement;
 pub mod xdg;
+
+/// A toplevel surface, created through either `xdg_shell` or `wl_shell`
+pub enum Toplevel<R> {
+    /// A toplevel surface of the `xdg_shell` protocol
+    Xdg(xdg::ToplevelSurface<R>),
+    /// A toplevel surface of the `wl_shell` protocol
+    Wl(legacy::ShellSurface<R>),
+}
+
+// We implement Clone manually because #[derive(..)] would require R: Clone.
+impl<R> Clone for Toplevel<R> {
+    fn clone(&self) -> Self {
+        match self {
+            Toplevel::Xdg(xdg) => Toplevel::Xdg(xdg.clone()),
+            Toplevel::Wl(wl) => Toplevel::Wl(wl.clone()),
+        }
+    }
+}
+
+impl<R> Toplevel<R>
+where
+    R: Role<xdg::XdgSurfaceRole> + Role<legacy::ShellSurfaceRole> + 'static,
+{
+    /// Is the toplevel surface referred by this handle still alive?
+    pub fn alive(&self) -> bool {
+        match *self {
+            Toplevel::Xdg(ref t) => t.alive(),
+            Toplevel::Wl(ref t) => t.alive(),
+        }
+    }
+
+    /// Access the underlying `wl_surface` of this toplevel surface
+    ///
+    /// Returns `None` if the toplevel surface actually no longer exists.
+    pub fn get_surface(&self) -> Option<&wl_surface::WlSurface> {
+        match *self {
+            Toplevel::Xdg(ref t) => t.get_surface(),
+            Toplevel::Wl(ref t) => t.get_surface(),
+        }
+    }
+
+    /// Do this handle and the other one actually refer to the same toplevel surface?
+    pub fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Toplevel::Xdg(a), Toplevel::Xdg(b)) => a.equals(b),
+            (Toplevel::Wl(a), Toplevel::Wl(b)) => a.equals(b),
+            _ => false,
+        }
+    }
+
+    /// Ask the client to close this toplevel surface
+    ///
+    /// `wl_shell` has no such request, this does nothing for its surfaces.
+    pub fn send_close(&self) {
+        if let Toplevel::Xdg(ref t) = *self {
+            t.send_close();
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! Initial placement of new windows
+//!
+//! When a toplevel is mapped for the first time, the client gives no hint about where it should
+//! be displayed, so this is left to the compositor. Floating window managers typically want
+//! the window to be placed somewhere sensible without the user having to move it.
+//!
+//! This module provides several [`PlacementStrategy`]s, and lets you select a different
+//! strategy for some windows with [`PlacementRules`]. Custom strategies can be plugged in by
+//! implementing the [`Placement`] trait, which is implemented for closures as well.
+//!
+//! ```
+//! # extern crate smithay;
+//! use smithay::utils::Rectangle;
+//! use smithay::wayland::shell::placement::{PlacementContext, PlacementRules, PlacementStrategy, WindowInfo};
+//!
+//! let mut rules = PlacementRules::new(PlacementStrategy::Smart);
+//! // dialogs of this application are opened under the pointer
+//! rules.add_rule(
+//!     |window: &WindowInfo| window.app_id.as_deref() == Some("org.example.Editor"),
+//!     PlacementStrategy::UnderPointer,
+//! );
+//!
+//! let window = WindowInfo { app_id: Some("org.example.Editor".into()), title: None };
+//! let location = rules.place(&window, &PlacementContext {
+//!     area: Rectangle { x: 0, y: 0, width: 1920, height: 1080 },
+//!     size: (800, 600),
+//!     windows: &[],
+//!     pointer: Some((100.0, 100.0)),
+//! });
+//! assert_eq!(location, (0, 0));
+//! ```
+
+use crate::utils::Rectangle;
+
+/// Offset between two windows placed by [`PlacementStrategy::Cascade`]
+const CASCADE_STEP: i32 = 32;
+
+/// Information about a window being placed, to match it against [`PlacementRules`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowInfo {
+    /// The application id of the window, if any
+    pub app_id: Option<String>,
+    /// The title of the window, if any
+    pub title: Option<String>,
+}
+
+/// Everything a [`Placement`] needs to know to place a window
+#[derive(Debug, Clone, Copy)]
+pub struct PlacementContext<'a> {
+    /// The area the window should be placed in, typically the usable area of an output
+    pub area: Rectangle,
+    /// The size of the window
+    pub size: (i32, i32),
+    /// The geometry of the windows already mapped in this area
+    pub windows: &'a [Rectangle],
+    /// The location of the pointer, if any
+    pub pointer: Option<(f64, f64)>,
+}
+
+/// A way to choose the initial location of new windows
+pub trait Placement {
+    /// Compute the location of the top-left corner of a new window
+    fn place(&mut self, context: &PlacementContext<'_>) -> (i32, i32);
+}
+
+impl<F> Placement for F
+where
+    F: FnMut(&PlacementContext<'_>) -> (i32, i32),
+{
+    fn place(&mut self, context: &PlacementContext<'_>) -> (i32, i32) {
+        self(context)
+    }
+}
+
+/// The placement strategies provided by smithay
+///
+/// All of them keep the window inside the placement area, as long as it fits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementStrategy {
+    /// Center the window in the area
+    Centered,
+    /// Place the windows in a diagonal cascade from the top-left corner of the area
+    ///
+    /// The window takes the first step of the cascade where no other window is.
+    Cascade,
+    /// Place the window where it overlaps the least with the other windows
+    Smart,
+    /// Center the window under the pointer
+    ///
+    /// Falls back to [`PlacementStrategy::Centered`] if there is no pointer.
+    UnderPointer,
+}
+
+impl Placement for PlacementStrategy {
+    fn place(&mut self, context: &PlacementContext<'_>) -> (i32, i32) {
+        let area = context.area;
+        let (width, height) = context.size;
+        let location = match *self {
+            PlacementStrategy::Centered => centered(context),
+            PlacementStrategy::Cascade => {
+                // the first free step of the cascade, starting from the top-left corner
+                let mut step = 0;
+                loop {
+                    let location = (area.x + step * CASCADE_STEP, area.y + step * CASCADE_STEP);
+                    let taken = context.windows.iter().any(|w| (w.x, w.y) == location);
+                    let fits = location.0 + width <= area.x + area.width
+                        && location.1 + height <= area.y + area.height;
+                    if !taken || (step > 0 && !fits) {
+                        break if fits { location } else { (area.x, area.y) };
+                    }
+                    step += 1;
+                }
+            }
+            PlacementStrategy::Smart => smart(context),
+            PlacementStrategy::UnderPointer => match context.pointer {
+                Some((x, y)) => (x as i32 - width / 2, y as i32 - height / 2),
+                None => centered(context),
+            },
+        };
+        constrain(location, context)
+    }
+}
+
+/// A set of rules choosing the placement strategy of each new window
+///
+/// Rules are checked in the order they were added, windows not matching any of them are placed
+/// with the default strategy.
+pub struct PlacementRules {
+    rules: Vec<(Box<dyn Fn(&WindowInfo) -> bool>, Box<dyn Placement>)>,
+    default: Box<dyn Placement>,
+}
+
+impl PlacementRules {
+    /// Create a new set of rules, placing all windows with the given strategy
+    pub fn new<P>(default: P) -> PlacementRules
+    where
+        P: Placement + 'static,
+    {
+        PlacementRules {
+            rules: Vec::new(),
+            default: Box::new(default),
+        }
+    }
+
+    /// Use the given strategy for the windows matching a rule
+    pub fn add_rule<M, P>(&mut self, matches: M, placement: P)
+    where
+        M: Fn(&WindowInfo) -> bool + 'static,
+        P: Placement + 'static,
+    {
+        self.rules.push((Box::new(matches), Box::new(placement)));
+    }
+
+    /// Change the strategy used for windows not matching any rule
+    pub fn set_default<P>(&mut self, default: P)
+    where
+        P: Placement + 'static,
+    {
+        self.default = Box::new(default);
+    }
+
+    /// Compute the location of a new window
+    pub fn place(&mut self, window: &WindowInfo, context: &PlacementContext<'_>) -> (i32, i32) {
+        let placement = self
+            .rules
+            .iter_mut()
+            .find(|(matches, _)| matches(window))
+            .map(|(_, placement)| placement)
+            .unwrap_or(&mut self.default);
+        placement.place(context)
+    }
+}
+
+impl ::std::fmt::Debug for PlacementRules {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("PlacementRules")
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+fn centered(context: &PlacementContext<'_>) -> (i32, i32) {
+    let area = context.area;
+    (
+        area.x + (area.width - context.size.0) / 2,
+        area.y + (area.height - context.size.1) / 2,
+    )
+}
+
+fn smart(context: &PlacementContext<'_>) -> (i32, i32) {
+    let area = context.area;
+    let (width, height) = context.size;
+    // the best locations are against the edges of the area or of the other windows
+    let mut xs = vec![area.x, area.x + area.width - width];
+    let mut ys = vec![area.y, area.y + area.height - height];
+    for w in context.windows {
+        xs.extend_from_slice(&[w.x + w.width, w.x - width]);
+        ys.extend_from_slice(&[w.y + w.height, w.y - height]);
+    }
+    xs.retain(|&x| x >= area.x && x + width <= area.x + area.width);
+    ys.retain(|&y| y >= area.y && y + height <= area.y + area.height);
+    xs.sort_unstable();
+    ys.sort_unstable();
+
+    let mut best = None;
+    for &y in &ys {
+        for &x in &xs {
+            let candidate = Rectangle { x, y, width, height };
+            let overlap: i64 = context.windows.iter().map(|w| overlap_area(&candidate, w)).sum();
+            // keep the first candidate on ties, which is the top-left most one
+            if best
+                .map(|(_, best_overlap)| overlap < best_overlap)
+                .unwrap_or(true)
+            {
+                best = Some(((x, y), overlap));
+            }
+        }
+    }
+    best.map(|(location, _)| location).unwrap_or((area.x, area.y))
+}
+
+fn overlap_area(a: &Rectangle, b: &Rectangle) -> i64 {
+    let width = (a.x + a.width).min(b.x + b.width) - a.x.max(b.x);
+    let height = (a.y + a.height).min(b.y + b.height) - a.y.max(b.y);
+    if width <= 0 || height <= 0 {
+        0
+    } else {
+        width as i64 * height as i64
+    }
+}
+
+// keep the window inside the area, or at its top-left corner if it is too big
+fn constrain(location: (i32, i32), context: &PlacementContext<'_>) -> (i32, i32) {
+    let area = context.area;
+    let x = location.0.min(area.x + area.width - context.size.0).max(area.x);
+    let y = location.1.min(area.y + area.height - context.size.1).max(area.y);
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(windows: &[Rectangle]) -> PlacementContext<'_> {
+        PlacementContext {
+            area: Rectangle {
+                x: 0,
+                y: 0,
+                width: 1000,
+                height: 800,
+            },
+            size: (400, 300),
+            windows,
+            pointer: None,
+        }
+    }
+
+    #[test]
+    fn centered_and_pointer() {
+        assert_eq!(PlacementStrategy::Centered.place(&context(&[])), (300, 250));
+        // without pointer, the window is centered
+        assert_eq!(PlacementStrategy::UnderPointer.place(&context(&[])), (300, 250));
+        let mut ctx = context(&[]);
+        ctx.pointer = Some((950.0, 10.0));
+        // kept inside the area
+        assert_eq!(PlacementStrategy::UnderPointer.place(&ctx), (600, 0));
+    }
+
+    #[test]
+    fn cascade() {
+        let windows = [
+            Rectangle {
+                x: 0,
+                y: 0,
+                width: 400,
+                height: 300,
+            },
+            Rectangle {
+                x: 32,
+                y: 32,
+                width: 400,
+                height: 300,
+            },
+        ];
+        assert_eq!(PlacementStrategy::Cascade.place(&context(&[])), (0, 0));
+        assert_eq!(PlacementStrategy::Cascade.place(&context(&windows)), (64, 64));
+    }
+
+    #[test]
+    fn smart_avoids_overlap() {
+        let windows = [Rectangle {
+            x: 0,
+            y: 0,
+            width: 500,
+            height: 800,
+        }];
+        assert_eq!(PlacementStrategy::Smart.place(&context(&windows)), (500, 0));
+    }
+
+    #[test]
+    fn rules() {
+        let mut rules = PlacementRules::new(PlacementStrategy::Centered);
+        rules.add_rule(
+            |w: &WindowInfo| w.title.as_deref() == Some("left"),
+            |_: &PlacementContext<'_>| (0, 0),
+        );
+        let left = WindowInfo {
+            app_id: None,
+            title: Some("left".into()),
+        };
+        assert_eq!(rules.place(&left, &context(&[])), (0, 0));
+        assert_eq!(rules.place(&WindowInfo::default(), &context(&[])), (300, 250));
+    }
+}
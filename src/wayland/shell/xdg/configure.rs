@@ -0,0 +1,286 @@
+//! State machine of the configure lifecycle of xdg surfaces
+//!
+//! `xdg_shell` requires a precise sequence between client and compositor before a
+//! surface can be displayed:
+//!
+//! 1. the client creates the role object and does an initial commit without a buffer,
+//! 2. the compositor answers with a configure event,
+//! 3. the client acks the configure and can then commit a buffer, which maps the surface.
+//!
+//! Committing a null buffer unmaps the surface again, after which the whole sequence
+//! starts over. The [`ConfigureLifecycle`] type tracks this sequence and reports
+//! invalid client behavior as [`ConfigureError`]s.
+
+use crate::wayland::Serial;
+
+/// Current step of the configure lifecycle of a surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleState {
+    /// The surface did not do its initial commit yet
+    Uninitialized,
+    /// The initial commit was done, the surface waits for a configure to be acked
+    WaitingForAck,
+    /// A configure was acked, but no buffer was committed yet
+    Configured,
+    /// A buffer was committed after a configure was acked, the surface is mapped
+    Mapped,
+}
+
+/// Change of the attached buffer that happened with a commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BufferChange {
+    /// No buffer was attached since the last commit
+    Unchanged,
+    /// A new buffer was attached
+    Attached,
+    /// A null buffer was attached
+    Removed,
+}
+
+/// Noteworthy transitions resulting from a commit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitTransition {
+    /// Nothing changed regarding the lifecycle
+    None,
+    /// This was the initial commit, the surface now needs to be sent its initial configure
+    InitialCommit,
+    /// The surface got its first buffer and should now be displayed
+    Mapped,
+    /// The surface removed its buffer and should no longer be displayed
+    ///
+    /// It will need a new initial configure before being mapped again.
+    Unmapped,
+}
+
+/// Protocol violations detected by the [`ConfigureLifecycle`]
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigureError {
+    /// A buffer was committed before the surface acked a configure
+    #[error("A buffer was committed before the surface was configured")]
+    UnconfiguredBuffer,
+    /// A configure was acked that was never sent or was already superseded
+    #[error("Wrong configure serial: {0:?}")]
+    InvalidSerial(Serial),
+}
+
+/// Tracker of the configure lifecycle of an xdg surface
+#[derive(Debug, Clone)]
+pub struct ConfigureLifecycle {
+    state: LifecycleState,
+    pending: Vec<Serial>,
+    last_acked: Option<Serial>,
+}
+
+impl Default for ConfigureLifecycle {
+    fn default() -> Self {
+        ConfigureLifecycle {
+            state: LifecycleState::Uninitialized,
+            pending: Vec::new(),
+            last_acked: None,
+        }
+    }
+}
+
+impl ConfigureLifecycle {
+    /// Current step of the lifecycle
+    pub fn state(&self) -> LifecycleState {
+        self.state
+    }
+
+    /// Has the surface acked a configure since its last (re)initialization?
+    pub fn is_configured(&self) -> bool {
+        match self.state {
+            LifecycleState::Configured | LifecycleState::Mapped => true,
+            LifecycleState::Uninitialized | LifecycleState::WaitingForAck => false,
+        }
+    }
+
+    /// Is the surface currently mapped?
+    pub fn is_mapped(&self) -> bool {
+        self.state == LifecycleState::Mapped
+    }
+
+    /// Serials of the configures sent to the client but not acked yet
+    pub fn pending_configures(&self) -> &[Serial] {
+        &self.pending
+    }
+
+    /// Serial of the last acked configure, if any
+    pub fn last_acked(&self) -> Option<Serial> {
+        self.last_acked
+    }
+
+    /// Record that a configure was sent to the client
+    pub fn send_configure(&mut self, serial: Serial) {
+        self.pending.push(serial);
+    }
+
+    /// Process an ack of a configure by the client
+    ///
+    /// Acking a configure also implicitly acks all configures sent before it.
+    pub fn ack_configure(&mut self, serial: Serial) -> Result<(), ConfigureError> {
+        if !self.pending.contains(&serial) {
+            return Err(ConfigureError::InvalidSerial(serial));
+        }
+        self.pending.retain(|&s| s > serial);
+        self.last_acked = Some(serial);
+        if !self.is_configured() {
+            self.state = LifecycleState::Configured;
+        }
+        Ok(())
+    }
+
+    /// Process a commit of the surface
+    pub fn commit(&mut self, buffer: BufferChange) -> Result<CommitTransition, ConfigureError> {
+        match (self.state, buffer) {
+            (LifecycleState::Uninitialized, BufferChange::Attached)
+            | (LifecycleState::WaitingForAck, BufferChange::Attached) => {
+                Err(ConfigureError::UnconfiguredBuffer)
+            }
+            (LifecycleState::Uninitialized, _) => {
+                self.state = LifecycleState::WaitingForAck;
+                Ok(CommitTransition::InitialCommit)
+            }
+            (LifecycleState::Configured, BufferChange::Attached) => {
+                self.state = LifecycleState::Mapped;
+                Ok(CommitTransition::Mapped)
+            }
+            (LifecycleState::Mapped, BufferChange::Removed) => {
+                self.reset();
+                Ok(CommitTransition::Unmapped)
+            }
+            _ => Ok(CommitTransition::None),
+        }
+    }
+
+    /// Reset the lifecycle, e.g. because the role object of the surface was destroyed
+    pub fn reset(&mut self) {
+        *self = ConfigureLifecycle::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regular_sequence() {
+        let mut lc = ConfigureLifecycle::default();
+        assert_eq!(
+            lc.commit(BufferChange::Unchanged),
+            Ok(CommitTransition::InitialCommit)
+        );
+        assert_eq!(lc.state(), LifecycleState::WaitingForAck);
+        lc.send_configure(Serial::from(1));
+        assert!(!lc.is_configured());
+        assert_eq!(lc.ack_configure(Serial::from(1)), Ok(()));
+        assert!(lc.is_configured());
+        assert_eq!(lc.commit(BufferChange::Attached), Ok(CommitTransition::Mapped));
+        assert!(lc.is_mapped());
+        assert_eq!(lc.commit(BufferChange::Attached), Ok(CommitTransition::None));
+    }
+
+    #[test]
+    fn buffer_before_configure() {
+        let mut lc = ConfigureLifecycle::default();
+        assert_eq!(
+            lc.commit(BufferChange::Attached),
+            Err(ConfigureError::UnconfiguredBuffer)
+        );
+        assert_eq!(
+            lc.commit(BufferChange::Unchanged),
+            Ok(CommitTransition::InitialCommit)
+        );
+        lc.send_configure(Serial::from(1));
+        assert_eq!(
+            lc.commit(BufferChange::Attached),
+            Err(ConfigureError::UnconfiguredBuffer)
+        );
+        assert_eq!(lc.commit(BufferChange::Unchanged), Ok(CommitTransition::None));
+    }
+
+    #[test]
+    fn ack_of_early_configure() {
+        // the compositor may configure before the initial commit
+        let mut lc = ConfigureLifecycle::default();
+        lc.send_configure(Serial::from(1));
+        assert_eq!(lc.ack_configure(Serial::from(1)), Ok(()));
+        assert_eq!(lc.commit(BufferChange::Attached), Ok(CommitTransition::Mapped));
+    }
+
+    #[test]
+    fn ack_discards_older_configures() {
+        let mut lc = ConfigureLifecycle::default();
+        lc.commit(BufferChange::Unchanged).unwrap();
+        lc.send_configure(Serial::from(1));
+        lc.send_configure(Serial::from(2));
+        lc.send_configure(Serial::from(3));
+        assert_eq!(lc.ack_configure(Serial::from(2)), Ok(()));
+        assert_eq!(lc.pending_configures(), &[Serial::from(3)]);
+        assert_eq!(lc.last_acked(), Some(Serial::from(2)));
+        // serial 1 was superseded by the ack of serial 2
+        assert_eq!(
+            lc.ack_configure(Serial::from(1)),
+            Err(ConfigureError::InvalidSerial(Serial::from(1)))
+        );
+        // serial 2 can not be acked twice
+        assert_eq!(
+            lc.ack_configure(Serial::from(2)),
+            Err(ConfigureError::InvalidSerial(Serial::from(2)))
+        );
+    }
+
+    #[test]
+    fn ack_of_unknown_serial() {
+        let mut lc = ConfigureLifecycle::default();
+        assert_eq!(
+            lc.ack_configure(Serial::from(42)),
+            Err(ConfigureError::InvalidSerial(Serial::from(42)))
+        );
+        assert!(!lc.is_configured());
+    }
+
+    #[test]
+    fn unmap_and_remap() {
+        let mut lc = ConfigureLifecycle::default();
+        lc.commit(BufferChange::Unchanged).unwrap();
+        lc.send_configure(Serial::from(1));
+        lc.ack_configure(Serial::from(1)).unwrap();
+        lc.commit(BufferChange::Attached).unwrap();
+        assert_eq!(lc.commit(BufferChange::Removed), Ok(CommitTransition::Unmapped));
+        assert_eq!(lc.state(), LifecycleState::Uninitialized);
+        assert!(lc.pending_configures().is_empty());
+        // a new initial commit and configure is required to map again
+        assert_eq!(
+            lc.commit(BufferChange::Attached),
+            Err(ConfigureError::UnconfiguredBuffer)
+        );
+        assert_eq!(
+            lc.commit(BufferChange::Unchanged),
+            Ok(CommitTransition::InitialCommit)
+        );
+        lc.send_configure(Serial::from(2));
+        lc.ack_configure(Serial::from(2)).unwrap();
+        assert_eq!(lc.commit(BufferChange::Attached), Ok(CommitTransition::Mapped));
+    }
+
+    #[test]
+    fn removing_buffer_before_mapping() {
+        let mut lc = ConfigureLifecycle::default();
+        lc.commit(BufferChange::Unchanged).unwrap();
+        lc.send_configure(Serial::from(1));
+        lc.ack_configure(Serial::from(1)).unwrap();
+        assert_eq!(lc.commit(BufferChange::Removed), Ok(CommitTransition::None));
+        assert_eq!(lc.state(), LifecycleState::Configured);
+    }
+
+    #[test]
+    fn reset_on_role_destruction() {
+        let mut lc = ConfigureLifecycle::default();
+        lc.send_configure(Serial::from(1));
+        lc.ack_configure(Serial::from(1)).unwrap();
+        lc.reset();
+        assert!(!lc.is_configured());
+        assert_eq!(lc.last_acked(), None);
+    }
+}
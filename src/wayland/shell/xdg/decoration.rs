@@ -0,0 +1,219 @@
+//! Handler for the xdg-decoration protocol
+//!
+//! This protocol allows the compositor and clients to negotiate whether the decorations
+//! (title bar, borders, ...) of a toplevel surface are drawn by the client itself, or by the
+//! compositor. Clients that do not bind this global draw their own decorations.
+//!
+//! Once a client created a decoration object for one of its toplevels, you are notified through
+//! the [`XdgDecorationRequest`]s, and decide which mode the toplevel should use. The client may
+//! express a preference, but the choice is ultimately yours. Send your decision using
+//! [`ToplevelDecoration::set_mode`], followed by a configure of the toplevel.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! #
+//! # use smithay::wayland::compositor::roles::*;
+//! # use smithay::wayland::shell::xdg::XdgSurfaceRole;
+//! use smithay::wayland::shell::xdg::decoration::{init_xdg_decoration_manager, Mode, XdgDecorationRequest};
+//! # define_roles!(MyRoles => [XdgSurface, XdgSurfaceRole]);
+//! #
+//! # let mut display = wayland_server::Display::new();
+//! init_xdg_decoration_manager::<MyRoles, _, _>(
+//!     &mut display,
+//!     |request| match request {
+//!         XdgDecorationRequest::NewToplevelDecoration { decoration, .. } => {
+//!             // we always draw decorations ourselves
+//!             decoration.set_mode(Mode::ServerSide);
+//!         }
+//!         _ => { /* honor or ignore the preference of the client */ }
+//!     },
+//!     None /* You can insert a logger here */
+//! );
+//! ```
+
+use std::{cell::RefCell, ops::Deref as _, rc::Rc};
+
+use wayland_protocols::{
+    unstable::xdg_decoration::v1::server::{
+        zxdg_decoration_manager_v1::{self, ZxdgDecorationManagerV1},
+        zxdg_toplevel_decoration_v1::{self, ZxdgToplevelDecorationV1},
+    },
+    xdg_shell::server::xdg_toplevel::XdgToplevel,
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+pub use wayland_protocols::unstable::xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Mode;
+
+use super::{xdg_handlers::make_toplevel_handle, ToplevelSurface, XdgSurfaceRole};
+use crate::wayland::compositor::roles::Role;
+
+/// A handle to the decoration object of a toplevel surface
+#[derive(Clone)]
+pub struct ToplevelDecoration {
+    decoration: ZxdgToplevelDecorationV1,
+}
+
+impl ToplevelDecoration {
+    /// Is the decoration object referred by this handle still alive?
+    pub fn alive(&self) -> bool {
+        self.decoration.as_ref().is_alive()
+    }
+
+    /// Do this handle and the other one actually refer to the same decoration object?
+    pub fn equals(&self, other: &Self) -> bool {
+        self.decoration.as_ref().equals(other.decoration.as_ref())
+    }
+
+    /// Tell the client which decoration mode its toplevel should use
+    ///
+    /// The mode only takes effect once the toplevel is configured, so you need to send
+    /// a configure to the toplevel afterwards.
+    pub fn set_mode(&self, mode: Mode) {
+        self.decoration.configure(mode);
+    }
+}
+
+/// Events generated by the xdg-decoration protocol
+pub enum XdgDecorationRequest<R> {
+    /// A decoration object was created for a toplevel
+    ///
+    /// Until you set a mode, the client assumes client-side decorations.
+    NewToplevelDecoration {
+        /// The toplevel surface
+        toplevel: ToplevelSurface<R>,
+        /// Its decoration object
+        decoration: ToplevelDecoration,
+    },
+    /// The client would like its toplevel to use a given decoration mode
+    SetMode {
+        /// The toplevel surface
+        toplevel: ToplevelSurface<R>,
+        /// Its decoration object
+        decoration: ToplevelDecoration,
+        /// The preferred mode
+        mode: Mode,
+    },
+    /// The client no longer has a preference, and lets you choose the decoration mode
+    UnsetMode {
+        /// The toplevel surface
+        toplevel: ToplevelSurface<R>,
+        /// Its decoration object
+        decoration: ToplevelDecoration,
+    },
+}
+
+/// Create a new xdg-decoration global
+///
+/// Only toplevels of the stable `xdg_shell` protocol can be decorated.
+pub fn init_xdg_decoration_manager<R, Impl, L>(
+    display: &mut Display,
+    implementation: Impl,
+    logger: L,
+) -> Global<ZxdgDecorationManagerV1>
+where
+    R: Role<XdgSurfaceRole> + 'static,
+    Impl: FnMut(XdgDecorationRequest<R>) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xdg_decoration_handler"));
+    let implementation = Rc::new(RefCell::new(implementation));
+    // toplevels which currently have a decoration object
+    let decorated = Rc::new(RefCell::new(Vec::<XdgToplevel>::new()));
+
+    display.create_global::<ZxdgDecorationManagerV1, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZxdgDecorationManagerV1>, u32), _, _| {
+                let implementation = implementation.clone();
+                let decorated = decorated.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zxdg_decoration_manager_v1::Request::GetToplevelDecoration { id, toplevel } => {
+                        if decorated
+                            .borrow()
+                            .iter()
+                            .any(|t| t.as_ref().equals(toplevel.as_ref()))
+                        {
+                            id.quick_assign(|_, _, _| {});
+                            id.as_ref().post_error(
+                                zxdg_toplevel_decoration_v1::Error::AlreadyConstructed as u32,
+                                "This toplevel already has a decoration object.".into(),
+                            );
+                            return;
+                        }
+                        decorated.borrow_mut().push(toplevel.clone());
+                        let decoration = implement_decoration::<R, Impl>(
+                            id,
+                            toplevel.clone(),
+                            implementation.clone(),
+                            decorated.clone(),
+                        );
+                        trace!(log, "New toplevel decoration");
+                        let mut user_impl = implementation.borrow_mut();
+                        (&mut *user_impl)(XdgDecorationRequest::NewToplevelDecoration {
+                            toplevel: make_toplevel_handle(&toplevel),
+                            decoration,
+                        });
+                    }
+                    zxdg_decoration_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+fn implement_decoration<R, Impl>(
+    decoration: Main<ZxdgToplevelDecorationV1>,
+    toplevel: XdgToplevel,
+    implementation: Rc<RefCell<Impl>>,
+    decorated: Rc<RefCell<Vec<XdgToplevel>>>,
+) -> ToplevelDecoration
+where
+    R: Role<XdgSurfaceRole> + 'static,
+    Impl: FnMut(XdgDecorationRequest<R>) + 'static,
+{
+    let destructor_toplevel = toplevel.clone();
+    decoration.quick_assign(move |decoration, request, _| {
+        if let zxdg_toplevel_decoration_v1::Request::Destroy = request {
+            // All is already handled by our destructor
+            return;
+        }
+        if !toplevel.as_ref().is_alive() {
+            decoration.as_ref().post_error(
+                zxdg_toplevel_decoration_v1::Error::Orphaned as u32,
+                "The toplevel of this decoration object was destroyed.".into(),
+            );
+            return;
+        }
+        let handle = ToplevelDecoration {
+            decoration: decoration.deref().clone(),
+        };
+        let request = match request {
+            zxdg_toplevel_decoration_v1::Request::SetMode { mode } => XdgDecorationRequest::SetMode {
+                toplevel: make_toplevel_handle(&toplevel),
+                decoration: handle,
+                mode,
+            },
+            zxdg_toplevel_decoration_v1::Request::UnsetMode => XdgDecorationRequest::UnsetMode {
+                toplevel: make_toplevel_handle(&toplevel),
+                decoration: handle,
+            },
+            _ => unreachable!(),
+        };
+        let mut user_impl = implementation.borrow_mut();
+        (&mut *user_impl)(request);
+    });
+    decoration.assign_destructor(Filter::new(move |_decoration: ZxdgToplevelDecorationV1, _, _| {
+        decorated
+            .borrow_mut()
+            .retain(|t| t.as_ref().is_alive() && !t.as_ref().equals(destructor_toplevel.as_ref()));
+    }));
+
+    ToplevelDecoration {
+        decoration: decoration.deref().clone(),
+    }
+}
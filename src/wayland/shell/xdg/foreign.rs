@@ -0,0 +1,342 @@
+//! Handler for the xdg-foreign protocol
+//!
+//! This protocol lets a client reference a toplevel surface of another client, so that it can
+//! stack its own toplevels above it. It is used for out-of-process dialogs, for example when a
+//! sandboxed application asks a desktop portal to open a file chooser: the portal displays the
+//! file chooser as a child of the window of the application.
+//!
+//! A client exports one of its toplevels and receives an opaque handle, which it passes to
+//! the other client by some other mean, such as D-Bus. The other client imports the toplevel
+//! with this handle, and can then set it as the parent of its own toplevels. The parent is
+//! stored in the [`ToplevelState`](super::ToplevelState) of the child, exactly as if the child
+//! had set it through `xdg_toplevel.set_parent`.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::CompositorToken;
+//! # use smithay::wayland::compositor::roles::*;
+//! # use smithay::wayland::shell::xdg::XdgSurfaceRole;
+//! use smithay::wayland::shell::xdg::foreign::init_xdg_foreign;
+//! # define_roles!(MyRoles => [XdgSurface, XdgSurfaceRole]);
+//! # let mut display = wayland_server::Display::new();
+//! # let compositor_token: CompositorToken<MyRoles> = unimplemented!();
+//! let (foreign_handle, _exporter, _importer) = init_xdg_foreign(
+//!     &mut display,
+//!     compositor_token,
+//!     None, /* insert a logger here */
+//! );
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    rc::Rc,
+};
+
+use wayland_protocols::unstable::xdg_foreign::v2::server::{
+    zxdg_exported_v2::{self, ZxdgExportedV2},
+    zxdg_exporter_v2::{self, ZxdgExporterV2},
+    zxdg_imported_v2::{self, ZxdgImportedV2},
+    zxdg_importer_v2::{self, ZxdgImporterV2},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+
+use super::{XdgSurfacePendingState, XdgSurfaceRole};
+use crate::wayland::compositor::{roles::Role, CompositorToken};
+
+struct Export {
+    handle: String,
+    surface: WlSurface,
+    exported: ZxdgExportedV2,
+}
+
+struct Import {
+    handle: String,
+    imported: ZxdgImportedV2,
+    // the toplevels of the importing client parented to the imported surface
+    children: Vec<WlSurface>,
+}
+
+#[derive(Default)]
+struct ForeignState {
+    exports: Vec<Export>,
+    imports: Vec<Import>,
+    exported_count: u64,
+}
+
+// Handles must not be guessable by other clients, the counter only guarantees their uniqueness.
+// The hasher keys are seeded randomly by the standard library.
+fn new_handle(count: u64) -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(count);
+    format!("{:016x}{:08x}", hasher.finish(), count)
+}
+
+fn toplevel_parent<R>(token: CompositorToken<R>, surface: &WlSurface) -> Option<Option<WlSurface>>
+where
+    R: Role<XdgSurfaceRole> + 'static,
+{
+    token
+        .with_role_data::<XdgSurfaceRole, _, _>(surface, |data| match data.pending_state {
+            XdgSurfacePendingState::Toplevel(ref state) => Some(state.parent.clone()),
+            _ => None,
+        })
+        .ok()
+        .and_then(|x| x)
+}
+
+fn set_toplevel_parent<R>(token: CompositorToken<R>, surface: &WlSurface, parent: Option<WlSurface>)
+where
+    R: Role<XdgSurfaceRole> + 'static,
+{
+    let _ = token.with_role_data::<XdgSurfaceRole, _, _>(surface, |data| {
+        if let XdgSurfacePendingState::Toplevel(ref mut state) = data.pending_state {
+            state.parent = parent;
+        }
+    });
+}
+
+impl Import {
+    // undo the relationships established through this import
+    fn unparent_children<R>(&mut self, token: CompositorToken<R>, parent: &WlSurface)
+    where
+        R: Role<XdgSurfaceRole> + 'static,
+    {
+        for child in self.children.drain(..).filter(|child| child.as_ref().is_alive()) {
+            // the client may have already set another parent to its toplevel
+            if toplevel_parent(token, &child) == Some(Some(parent.clone())) {
+                set_toplevel_parent(token, &child, None);
+            }
+        }
+    }
+}
+
+/// A handle to the toplevels exported by the clients
+///
+/// It can be cloned and all clones access the same exports.
+#[derive(Clone)]
+pub struct XdgForeignHandle {
+    state: Rc<RefCell<ForeignState>>,
+}
+
+impl XdgForeignHandle {
+    /// The surface exported with a handle, if it is still exported
+    pub fn exported_surface(&self, handle: &str) -> Option<WlSurface> {
+        self.state
+            .borrow()
+            .exports
+            .iter()
+            .find(|export| export.handle == handle && export.surface.as_ref().is_alive())
+            .map(|export| export.surface.clone())
+    }
+
+    /// The handles a surface is currently exported with
+    pub fn handles_of(&self, surface: &WlSurface) -> Vec<String> {
+        self.state
+            .borrow()
+            .exports
+            .iter()
+            .filter(|export| export.surface == *surface)
+            .map(|export| export.handle.clone())
+            .collect()
+    }
+}
+
+impl ::std::fmt::Debug for XdgForeignHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let state = self.state.borrow();
+        f.debug_struct("XdgForeignHandle")
+            .field("exports", &state.exports.len())
+            .field("imports", &state.imports.len())
+            .finish()
+    }
+}
+
+/// Create new xdg-foreign exporter and importer globals
+///
+/// Returns a handle to look up the exported toplevels, along with the two globals.
+pub fn init_xdg_foreign<R, L>(
+    display: &mut Display,
+    token: CompositorToken<R>,
+    logger: L,
+) -> (XdgForeignHandle, Global<ZxdgExporterV2>, Global<ZxdgImporterV2>)
+where
+    R: Role<XdgSurfaceRole> + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xdg_foreign_handler"));
+    let handle = XdgForeignHandle {
+        state: Rc::new(RefCell::new(ForeignState::default())),
+    };
+
+    let exporter_state = handle.state.clone();
+    let exporter_log = log.clone();
+    let exporter = display.create_global::<ZxdgExporterV2, _>(
+        1,
+        Filter::new(move |(exporter, _version): (Main<ZxdgExporterV2>, u32), _, _| {
+            let state = exporter_state.clone();
+            let log = exporter_log.clone();
+            exporter.quick_assign(move |exporter, request, _| match request {
+                zxdg_exporter_v2::Request::ExportToplevel { id, surface } => {
+                    if toplevel_parent(token, &surface).is_none() {
+                        // the error enum of the exporter is not part of the bundled protocol yet,
+                        // this is its invalid_surface value
+                        exporter
+                            .as_ref()
+                            .post_error(0, "Surface is not an xdg_toplevel.".into());
+                        return;
+                    }
+                    id.quick_assign(|_, request, _| match request {
+                        zxdg_exported_v2::Request::Destroy => {
+                            // All is already handled by our destructor
+                        }
+                        _ => unreachable!(),
+                    });
+                    let destructor_state = state.clone();
+                    id.assign_destructor(Filter::new(move |exported: ZxdgExportedV2, _, _| {
+                        let mut state = destructor_state.borrow_mut();
+                        let export = match state
+                            .exports
+                            .iter()
+                            .position(|export| export.exported.as_ref().equals(exported.as_ref()))
+                        {
+                            Some(index) => state.exports.remove(index),
+                            None => return,
+                        };
+                        // the imports of this handle are no longer valid
+                        let (invalid, valid) = state
+                            .imports
+                            .drain(..)
+                            .partition::<Vec<_>, _>(|import| import.handle == export.handle);
+                        state.imports = valid;
+                        for mut import in invalid {
+                            import.unparent_children(token, &export.surface);
+                            if import.imported.as_ref().is_alive() {
+                                import.imported.destroyed();
+                            }
+                        }
+                    }));
+
+                    let mut state = state.borrow_mut();
+                    state.exported_count += 1;
+                    let handle = new_handle(state.exported_count);
+                    trace!(log, "Exported a toplevel"; "handle" => &handle);
+                    id.handle(handle.clone());
+                    state.exports.push(Export {
+                        handle,
+                        surface,
+                        exported: (*id).clone(),
+                    });
+                }
+                zxdg_exporter_v2::Request::Destroy => {
+                    // Nothing to do
+                }
+                _ => unreachable!(),
+            });
+        }),
+    );
+
+    let importer_state = handle.state.clone();
+    let importer = display.create_global::<ZxdgImporterV2, _>(
+        1,
+        Filter::new(move |(importer, _version): (Main<ZxdgImporterV2>, u32), _, _| {
+            let state = importer_state.clone();
+            let log = log.clone();
+            importer.quick_assign(move |_importer, request, _| match request {
+                zxdg_importer_v2::Request::ImportToplevel { id, handle } => {
+                    let request_state = state.clone();
+                    id.quick_assign(move |imported, request, _| match request {
+                        zxdg_imported_v2::Request::SetParentOf { surface } => {
+                            let mut state = request_state.borrow_mut();
+                            let state = &mut *state;
+                            let import = match state
+                                .imports
+                                .iter_mut()
+                                .find(|import| import.imported.as_ref().equals(imported.as_ref()))
+                            {
+                                Some(import) => import,
+                                // the export is gone and the client was already told so
+                                None => return,
+                            };
+                            let parent =
+                                match state.exports.iter().find(|export| export.handle == import.handle) {
+                                    Some(export) => export.surface.clone(),
+                                    None => return,
+                                };
+                            if toplevel_parent(token, &surface).is_none() {
+                                // only toplevels can be given a parent
+                                return;
+                            }
+                            set_toplevel_parent(token, &surface, Some(parent));
+                            if !import.children.contains(&surface) {
+                                import.children.push(surface);
+                            }
+                        }
+                        zxdg_imported_v2::Request::Destroy => {
+                            // All is already handled by our destructor
+                        }
+                        _ => unreachable!(),
+                    });
+
+                    let destructor_state = state.clone();
+                    id.assign_destructor(Filter::new(move |imported: ZxdgImportedV2, _, _| {
+                        let mut state = destructor_state.borrow_mut();
+                        let index = state
+                            .imports
+                            .iter()
+                            .position(|import| import.imported.as_ref().equals(imported.as_ref()));
+                        if let Some(index) = index {
+                            let mut import = state.imports.remove(index);
+                            let parent = state
+                                .exports
+                                .iter()
+                                .find(|export| export.handle == import.handle)
+                                .map(|export| export.surface.clone());
+                            if let Some(parent) = parent {
+                                import.unparent_children(token, &parent);
+                            }
+                        }
+                    }));
+
+                    let mut state = state.borrow_mut();
+                    let exported = state
+                        .exports
+                        .iter()
+                        .any(|export| export.handle == handle && export.surface.as_ref().is_alive());
+                    if !exported {
+                        trace!(log, "Import of an unknown handle"; "handle" => &handle);
+                        id.destroyed();
+                        return;
+                    }
+                    trace!(log, "Imported a toplevel"; "handle" => &handle);
+                    state.imports.push(Import {
+                        handle,
+                        imported: (*id).clone(),
+                        children: Vec::new(),
+                    });
+                }
+                zxdg_importer_v2::Request::Destroy => {
+                    // Nothing to do
+                }
+                _ => unreachable!(),
+            });
+        }),
+    );
+
+    (handle, exporter, importer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::new_handle;
+
+    #[test]
+    fn handles_are_unique() {
+        let handles = (0..100).map(new_handle).collect::<Vec<_>>();
+        for (i, handle) in handles.iter().enumerate() {
+            assert!(!handles[i + 1..].contains(handle));
+        }
+    }
+}
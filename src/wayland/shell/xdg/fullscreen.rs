@@ -0,0 +1,277 @@
+//! Helper for borderless windowed fullscreen
+//!
+//! Many games and video players offer a "borderless window" mode as an alternative to real
+//! fullscreen: the window covers the whole output, but stays a regular window that other
+//! windows can be stacked above and that does not take exclusive control of the output.
+//!
+//! [`FullscreenState`] tracks which of these modes a toplevel is in, remembers its
+//! geometry to restore it afterwards and decides how fullscreen requests of the client
+//! are handled. It returns [`FullscreenPlacement`]s, describing where the window should be
+//! placed and how it should be configured, for you to apply.
+
+use wayland_protocols::xdg_shell::server::xdg_toplevel;
+
+use super::ToplevelConfigure;
+use crate::utils::Rectangle;
+use crate::wayland::Serial;
+
+/// Fullscreen mode of a toplevel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// A regular window
+    Windowed,
+    /// A window covering the whole output, but stacked like regular windows
+    Borderless,
+    /// A real fullscreen window, displayed above everything else on its output
+    Exclusive,
+}
+
+/// How fullscreen requests of clients are handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenPolicy {
+    /// Fullscreen requests make the window exclusive fullscreen
+    ///
+    /// A borderless window requesting fullscreen is upgraded to exclusive fullscreen,
+    /// and returns to borderless when requesting to leave fullscreen.
+    Exclusive,
+    /// Fullscreen requests are turned into borderless windows
+    Borderless,
+}
+
+/// Where and how a window should be placed after a change of its fullscreen mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullscreenPlacement {
+    /// The new mode of the window
+    pub mode: FullscreenMode,
+    /// The geometry of the window in the global compositor space
+    ///
+    /// For a window returning to [`FullscreenMode::Windowed`], this is the geometry
+    /// it had before entering fullscreen.
+    pub geometry: Rectangle,
+    /// The states to configure the window with
+    ///
+    /// Add other states, like `Activated`, as needed before sending the configure.
+    pub states: Vec<xdg_toplevel::State>,
+}
+
+impl FullscreenPlacement {
+    /// Create the configure to send to the window for this placement
+    pub fn configure(&self, serial: Serial) -> ToplevelConfigure {
+        ToplevelConfigure {
+            size: Some((self.geometry.width, self.geometry.height)),
+            states: self.states.clone(),
+            serial,
+        }
+    }
+
+    /// Whether the window should be raised above all other surfaces of its output,
+    /// including panels
+    ///
+    /// Only exclusive fullscreen windows do that, borderless windows stay in the
+    /// normal stacking order.
+    pub fn is_exclusive(&self) -> bool {
+        self.mode == FullscreenMode::Exclusive
+    }
+}
+
+/// Fullscreen state of a toplevel
+///
+/// Both borderless and exclusive fullscreen windows are configured with the
+/// `fullscreen` state, so that clients hide their decorations, the difference only
+/// lies in how you stack and display them.
+#[derive(Debug, Clone)]
+pub struct FullscreenState {
+    policy: FullscreenPolicy,
+    mode: FullscreenMode,
+    // mode to return to when leaving exclusive fullscreen
+    previous: FullscreenMode,
+    output: Option<Rectangle>,
+    restore: Option<Rectangle>,
+}
+
+impl FullscreenState {
+    /// Create the state of a new, windowed, toplevel
+    pub fn new(policy: FullscreenPolicy) -> FullscreenState {
+        FullscreenState {
+            policy,
+            mode: FullscreenMode::Windowed,
+            previous: FullscreenMode::Windowed,
+            output: None,
+            restore: None,
+        }
+    }
+
+    /// Current mode of the window
+    pub fn mode(&self) -> FullscreenMode {
+        self.mode
+    }
+
+    /// Geometry of the output the window covers, if it is not windowed
+    pub fn output(&self) -> Option<Rectangle> {
+        self.output
+    }
+
+    /// Change how fullscreen requests of the client are handled
+    ///
+    /// This does not affect the current mode of the window.
+    pub fn set_policy(&mut self, policy: FullscreenPolicy) {
+        self.policy = policy;
+    }
+
+    /// Make the window borderless, e.g. following a keybinding of the user
+    ///
+    /// `current` is the current geometry of the window, which will be restored when leaving
+    /// borderless mode. Does nothing and returns `None` if the window already is borderless
+    /// on this output.
+    pub fn set_borderless(&mut self, output: Rectangle, current: Rectangle) -> Option<FullscreenPlacement> {
+        if self.mode == FullscreenMode::Borderless && self.output == Some(output) {
+            return None;
+        }
+        self.enter(FullscreenMode::Borderless, output, current);
+        Some(self.placement())
+    }
+
+    /// Return the window to the windowed mode, restoring its previous geometry
+    ///
+    /// Returns `None` if the window already is windowed.
+    pub fn set_windowed(&mut self) -> Option<FullscreenPlacement> {
+        if self.mode == FullscreenMode::Windowed {
+            return None;
+        }
+        let restore = self.restore.take();
+        self.mode = FullscreenMode::Windowed;
+        self.previous = FullscreenMode::Windowed;
+        self.output = None;
+        Some(FullscreenPlacement {
+            mode: FullscreenMode::Windowed,
+            geometry: restore.unwrap_or_default(),
+            states: Vec::new(),
+        })
+    }
+
+    /// Handle a fullscreen request of the client
+    ///
+    /// `output` is the geometry of the output the window should cover, typically the one
+    /// requested by the client or the one the window is currently on.
+    pub fn fullscreen_request(
+        &mut self,
+        output: Rectangle,
+        current: Rectangle,
+    ) -> Option<FullscreenPlacement> {
+        let mode = match self.policy {
+            FullscreenPolicy::Exclusive => FullscreenMode::Exclusive,
+            FullscreenPolicy::Borderless => FullscreenMode::Borderless,
+        };
+        if self.mode == mode && self.output == Some(output) {
+            return None;
+        }
+        self.enter(mode, output, current);
+        Some(self.placement())
+    }
+
+    /// Handle a request of the client to leave fullscreen
+    ///
+    /// An exclusive fullscreen window returns to the mode it was in before, other
+    /// windows become windowed.
+    pub fn unfullscreen_request(&mut self) -> Option<FullscreenPlacement> {
+        match (self.mode, self.previous) {
+            (FullscreenMode::Exclusive, FullscreenMode::Borderless) => {
+                self.mode = FullscreenMode::Borderless;
+                self.previous = FullscreenMode::Windowed;
+                Some(self.placement())
+            }
+            _ => self.set_windowed(),
+        }
+    }
+
+    /// Notify that the geometry of the output covered by the window changed
+    ///
+    /// Returns the new placement of the window, if it is affected.
+    pub fn output_changed(&mut self, output: Rectangle) -> Option<FullscreenPlacement> {
+        if self.mode == FullscreenMode::Windowed || self.output == Some(output) {
+            return None;
+        }
+        self.output = Some(output);
+        Some(self.placement())
+    }
+
+    fn enter(&mut self, mode: FullscreenMode, output: Rectangle, current: Rectangle) {
+        if self.mode == FullscreenMode::Windowed {
+            self.restore = Some(current);
+        }
+        self.previous = if mode == FullscreenMode::Exclusive && self.mode != FullscreenMode::Exclusive {
+            self.mode
+        } else {
+            self.previous
+        };
+        self.mode = mode;
+        self.output = Some(output);
+    }
+
+    fn placement(&self) -> FullscreenPlacement {
+        FullscreenPlacement {
+            mode: self.mode,
+            geometry: self.output.unwrap_or_default(),
+            states: vec![xdg_toplevel::State::Fullscreen],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: Rectangle = Rectangle {
+        x: 1920,
+        y: 0,
+        width: 2560,
+        height: 1440,
+    };
+    const WINDOW: Rectangle = Rectangle {
+        x: 2000,
+        y: 100,
+        width: 800,
+        height: 600,
+    };
+
+    #[test]
+    fn borderless_and_back() {
+        let mut state = FullscreenState::new(FullscreenPolicy::Exclusive);
+        let placement = state.set_borderless(OUTPUT, WINDOW).unwrap();
+        assert_eq!(placement.mode, FullscreenMode::Borderless);
+        assert_eq!(placement.geometry, OUTPUT);
+        assert!(!placement.is_exclusive());
+        assert!(state.set_borderless(OUTPUT, OUTPUT).is_none());
+        let placement = state.set_windowed().unwrap();
+        assert_eq!(placement.geometry, WINDOW);
+        assert!(placement.states.is_empty());
+        assert!(state.set_windowed().is_none());
+    }
+
+    #[test]
+    fn fullscreen_request_while_borderless() {
+        let mut state = FullscreenState::new(FullscreenPolicy::Exclusive);
+        state.set_borderless(OUTPUT, WINDOW);
+        let placement = state.fullscreen_request(OUTPUT, OUTPUT).unwrap();
+        assert!(placement.is_exclusive());
+        let placement = state.unfullscreen_request().unwrap();
+        assert_eq!(placement.mode, FullscreenMode::Borderless);
+        let placement = state.unfullscreen_request().unwrap();
+        assert_eq!(placement.mode, FullscreenMode::Windowed);
+        assert_eq!(placement.geometry, WINDOW);
+    }
+
+    #[test]
+    fn borderless_policy() {
+        let mut state = FullscreenState::new(FullscreenPolicy::Borderless);
+        let placement = state.fullscreen_request(OUTPUT, WINDOW).unwrap();
+        assert_eq!(placement.mode, FullscreenMode::Borderless);
+        let resized = Rectangle {
+            width: 1920,
+            height: 1080,
+            ..OUTPUT
+        };
+        assert_eq!(state.output_changed(resized).unwrap().geometry, resized);
+        assert_eq!(state.unfullscreen_request().unwrap().geometry, WINDOW);
+    }
+}
@@ -87,7 +87,7 @@
 //! that you are given (in an `Arc<Mutex<_>>`) as return value of the `init` function.
 
 use crate::utils::Rectangle;
-use crate::wayland::compositor::{roles::Role, CompositorToken};
+use crate::wayland::compositor::{roles::Role, BufferAssignment, CompositorToken};
 use crate::wayland::Serial;
 use std::{
     cell::RefCell,
@@ -103,11 +103,20 @@ use wayland_server::{
     Display, Filter, Global, UserDataMap,
 };
 
+mod configure;
+pub mod decoration;
+pub mod foreign;
+mod fullscreen;
 // handlers for the xdg_shell protocol
 mod xdg_handlers;
 // compatibility handlers for the zxdg_shell_v6 protocol, its earlier version
 mod zxdgv6_handlers;
 
+pub use self::configure::{
+    BufferChange, CommitTransition, ConfigureError, ConfigureLifecycle, LifecycleState,
+};
+pub use self::fullscreen::{FullscreenMode, FullscreenPlacement, FullscreenPolicy, FullscreenState};
+
 /// Metadata associated with the `xdg_surface` role
 pub struct XdgSurfaceRole {
     /// Pending state as requested by the client
@@ -124,18 +133,15 @@ pub struct XdgSurfaceRole {
     /// By default, you should consider the full contents of the
     /// buffers of this surface and its subsurfaces.
     pub window_geometry: Option<Rectangle>,
-    /// List of non-acked configures pending
-    ///
-    /// Whenever a configure is acked by the client, all configure
-    /// older than it are discarded as well. As such, this `Vec` contains
-    /// the serials of all the configure send to this surface that are
-    /// newer than the last ack received.
-    pub pending_configures: Vec<u32>,
-    /// Has this surface acked at least one configure?
-    ///
-    /// `xdg_shell` defines it as illegal to commit on a surface that has
-    /// not yet acked a configure.
-    pub configured: bool,
+    /// Configure lifecycle of this surface
+    ///
+    /// Tracks the configures sent to this surface that were not acked yet, and
+    /// whether the surface was configured and mapped.
+    pub lifecycle: ConfigureLifecycle,
+    /// Configure queued by the compositor and not yet sent
+    pub(crate) queued_configure: Option<ToplevelConfigure>,
+    /// Size and states of the last configure sent to this surface
+    pub(crate) last_configure: Option<(Option<(i32, i32)>, Vec<xdg_toplevel::State>)>,
 }
 
 #[derive(Clone, Debug)]
@@ -220,6 +226,23 @@ pub struct ToplevelState {
     pub max_size: (i32, i32),
 }
 
+impl ToplevelState {
+    /// Constrain a size to the minimum and maximum sizes requested by the client
+    ///
+    /// Useful to compute the new size of a window during an interactive resize. The
+    /// returned size is at least 1x1.
+    pub fn constrain_size(&self, (width, height): (i32, i32)) -> (i32, i32) {
+        let constrain = |value: i32, min: i32, max: i32| {
+            let value = if max > 0 { value.min(max) } else { value };
+            value.max(min).max(1)
+        };
+        (
+            constrain(width, self.min_size.0, self.max_size.0),
+            constrain(height, self.min_size.1, self.max_size.1),
+        )
+    }
+}
+
 impl Clone for ToplevelState {
     fn clone(&self) -> ToplevelState {
         ToplevelState {
@@ -344,6 +367,15 @@ where
     pub fn popup_surfaces(&self) -> &[PopupSurface<R>] {
         &self.known_popups[..]
     }
+
+    /// Send the queued configures of all known toplevel surfaces
+    ///
+    /// See [`ToplevelSurface::flush_configure`]. You'll typically call this once per frame.
+    pub fn flush_configures(&self) {
+        for toplevel in &self.known_toplevels {
+            toplevel.flush_configure();
+        }
+    }
 }
 
 /*
@@ -556,6 +588,37 @@ where
         })
     }
 
+    /// Register a hook to be invoked when this toplevel surface is destroyed
+    ///
+    /// Use it to remove the window from your own data structures. The hook is invoked with
+    /// the underlying `wl_surface`, which may be destroyed as well, but can still be compared
+    /// to other surfaces. Nothing happens if the toplevel surface is already destroyed.
+    pub fn add_destruction_hook<F>(&self, hook: F)
+    where
+        F: FnOnce(&wl_surface::WlSurface) + 'static,
+    {
+        if !self.alive() {
+            return;
+        }
+        let hooks = match self.shell_surface {
+            ToplevelKind::Xdg(ref s) => {
+                &s.as_ref()
+                    .user_data()
+                    .get::<self::xdg_handlers::ShellSurfaceUserData<R>>()
+                    .unwrap()
+                    .destruction_hooks
+            }
+            ToplevelKind::ZxdgV6(ref s) => {
+                &s.as_ref()
+                    .user_data()
+                    .get::<self::zxdgv6_handlers::ShellSurfaceUserData<R>>()
+                    .unwrap()
+                    .destruction_hooks
+            }
+        };
+        hooks.add(Box::new(hook));
+    }
+
     /// Send a configure event to this toplevel surface to suggest it a new configuration
     ///
     /// The serial of this configure will be tracked waiting for the client to ACK it.
@@ -563,12 +626,74 @@ where
         if !self.alive() {
             return;
         }
+        let _ = self
+            .token
+            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| {
+                data.queued_configure = None;
+                data.last_configure = Some((cfg.size, cfg.states.clone()));
+            });
         match self.shell_surface {
             ToplevelKind::Xdg(ref s) => self::xdg_handlers::send_toplevel_configure::<R>(s, cfg),
             ToplevelKind::ZxdgV6(ref s) => self::zxdgv6_handlers::send_toplevel_configure::<R>(s, cfg),
         }
     }
 
+    /// Queue a configure event for this toplevel surface
+    ///
+    /// Unlike [`send_configure`](ToplevelSurface::send_configure), the configure is not sent
+    /// right away but replaces any previously queued configure, and is only sent on the next
+    /// call to [`flush_configure`](ToplevelSurface::flush_configure).
+    ///
+    /// Use this during continuous resizes or layout animations, so that clients receive at
+    /// most one configure per frame reflecting the latest state instead of being flooded.
+    pub fn queue_configure(&self, cfg: ToplevelConfigure) {
+        if !self.alive() {
+            return;
+        }
+        let _ = self
+            .token
+            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| {
+                data.queued_configure = Some(cfg);
+            });
+    }
+
+    /// Send the configure previously queued with [`queue_configure`](ToplevelSurface::queue_configure)
+    ///
+    /// The configure is dropped without being sent if it has the same size and states
+    /// as the last configure sent to the client.
+    ///
+    /// Returns `true` if a configure was actually sent.
+    pub fn flush_configure(&self) -> bool {
+        if !self.alive() {
+            return false;
+        }
+        let cfg = self
+            .token
+            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| {
+                match data.queued_configure.take() {
+                    Some(ref cfg)
+                        if data
+                            .last_configure
+                            .as_ref()
+                            .map(|&(size, ref states)| size == cfg.size && *states == cfg.states)
+                            .unwrap_or(false) =>
+                    {
+                        None
+                    }
+                    cfg => cfg,
+                }
+            })
+            .ok()
+            .and_then(|x| x);
+        match cfg {
+            Some(cfg) => {
+                self.send_configure(cfg);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Make sure this surface was configured
     ///
     /// Returns `true` if it was, if not, returns `false` and raise
@@ -583,7 +708,7 @@ where
         }
         let configured = self
             .token
-            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| data.configured)
+            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| data.lifecycle.is_configured())
             .expect("A shell surface object exists but the surface does not have the shell_surface role ?!");
         if !configured {
             match self.shell_surface {
@@ -614,6 +739,49 @@ where
         configured
     }
 
+    /// Process a commit of the underlying `wl_surface`
+    ///
+    /// Call this from the commit handler of your compositor, before consuming the attached
+    /// buffer, to advance the [`ConfigureLifecycle`] of this surface. The returned transition
+    /// tells you when to send the initial configure and when to map or unmap the surface.
+    ///
+    /// If the client violated the protocol (by committing a buffer before acking a configure),
+    /// a protocol error is raised and `None` is returned. Also returns `None` if the surface is
+    /// already destroyed.
+    pub fn handle_commit(&self) -> Option<CommitTransition> {
+        if !self.alive() {
+            return None;
+        }
+        match process_commit(self.token, &self.wl_surface) {
+            Ok(transition) => Some(transition),
+            Err(err) => {
+                match self.shell_surface {
+                    ToplevelKind::Xdg(ref s) => {
+                        let data = s
+                            .as_ref()
+                            .user_data()
+                            .get::<self::xdg_handlers::ShellSurfaceUserData<R>>()
+                            .unwrap();
+                        data.xdg_surface
+                            .as_ref()
+                            .post_error(xdg_surface::Error::UnconfiguredBuffer as u32, err.to_string());
+                    }
+                    ToplevelKind::ZxdgV6(ref s) => {
+                        let data = s
+                            .as_ref()
+                            .user_data()
+                            .get::<self::zxdgv6_handlers::ShellSurfaceUserData<R>>()
+                            .unwrap();
+                        data.xdg_surface
+                            .as_ref()
+                            .post_error(zxdg_surface_v6::Error::UnconfiguredBuffer as u32, err.to_string());
+                    }
+                }
+                None
+            }
+        }
+    }
+
     /// Send a "close" event to the client
     pub fn send_close(&self) {
         match self.shell_surface {
@@ -747,7 +915,7 @@ where
         }
         let configured = self
             .token
-            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| data.configured)
+            .with_role_data::<XdgSurfaceRole, _, _>(&self.wl_surface, |data| data.lifecycle.is_configured())
             .expect("A shell surface object exists but the surface does not have the shell_surface role ?!");
         if !configured {
             match self.shell_surface {
@@ -778,6 +946,49 @@ where
         configured
     }
 
+    /// Process a commit of the underlying `wl_surface`
+    ///
+    /// Call this from the commit handler of your compositor, before consuming the attached
+    /// buffer, to advance the [`ConfigureLifecycle`] of this surface. The returned transition
+    /// tells you when to send the initial configure and when to map or unmap the surface.
+    ///
+    /// If the client violated the protocol (by committing a buffer before acking a configure),
+    /// a protocol error is raised and `None` is returned. Also returns `None` if the surface is
+    /// already destroyed.
+    pub fn handle_commit(&self) -> Option<CommitTransition> {
+        if !self.alive() {
+            return None;
+        }
+        match process_commit(self.token, &self.wl_surface) {
+            Ok(transition) => Some(transition),
+            Err(err) => {
+                match self.shell_surface {
+                    PopupKind::Xdg(ref s) => {
+                        let data = s
+                            .as_ref()
+                            .user_data()
+                            .get::<self::xdg_handlers::ShellSurfaceUserData<R>>()
+                            .unwrap();
+                        data.xdg_surface
+                            .as_ref()
+                            .post_error(xdg_surface::Error::UnconfiguredBuffer as u32, err.to_string());
+                    }
+                    PopupKind::ZxdgV6(ref s) => {
+                        let data = s
+                            .as_ref()
+                            .user_data()
+                            .get::<self::zxdgv6_handlers::ShellSurfaceUserData<R>>()
+                            .unwrap();
+                        data.xdg_surface
+                            .as_ref()
+                            .post_error(zxdg_surface_v6::Error::UnconfiguredBuffer as u32, err.to_string());
+                    }
+                }
+                None
+            }
+        }
+    }
+
     /// Send a `popup_done` event to the popup surface
     ///
     /// It means that the use has dismissed the popup surface, or that
@@ -817,6 +1028,23 @@ where
     }
 }
 
+fn process_commit<R>(
+    token: CompositorToken<R>,
+    surface: &wl_surface::WlSurface,
+) -> Result<CommitTransition, ConfigureError>
+where
+    R: Role<XdgSurfaceRole> + 'static,
+{
+    let buffer = token.with_surface_data(surface, |attrs| match attrs.buffer {
+        Some(BufferAssignment::NewBuffer { .. }) => BufferChange::Attached,
+        Some(BufferAssignment::Removed) => BufferChange::Removed,
+        None => BufferChange::Unchanged,
+    });
+    token
+        .with_role_data::<XdgSurfaceRole, _, _>(surface, |data| data.lifecycle.commit(buffer))
+        .expect("A shell surface object exists but the surface does not have the shell_surface role ?!")
+}
+
 /// A configure message for toplevel surfaces
 pub struct ToplevelConfigure {
     /// A suggestion for a new size for the surface
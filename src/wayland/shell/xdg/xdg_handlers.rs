@@ -1,13 +1,13 @@
 use std::{cell::RefCell, ops::Deref as _, sync::Mutex};
 
 use crate::wayland::compositor::{roles::*, CompositorToken};
-use crate::wayland::Serial;
+use crate::wayland::{debug_name, Serial};
 use wayland_protocols::xdg_shell::server::{
     xdg_popup, xdg_positioner, xdg_surface, xdg_toplevel, xdg_wm_base,
 };
 use wayland_server::{protocol::wl_surface, Filter, Main};
 
-use crate::utils::Rectangle;
+use crate::utils::{DestructionHooks, Rectangle};
 
 use super::{
     make_shell_client_data, PopupConfigure, PopupKind, PopupState, PositionerState, ShellClient,
@@ -69,8 +69,9 @@ where
             let role_data = XdgSurfaceRole {
                 pending_state: XdgSurfacePendingState::None,
                 window_geometry: None,
-                pending_configures: Vec::new(),
-                configured: false,
+                lifecycle: Default::default(),
+                queued_configure: None,
+                last_configure: None,
             };
             if data
                 .shell_data
@@ -254,6 +255,7 @@ where
                 wl_surface: data.wl_surface.clone(),
                 xdg_surface: xdg_surface.clone(),
                 wm_base: data.wm_base.clone(),
+                destruction_hooks: DestructionHooks::new(),
             });
 
             data.shell_data
@@ -302,6 +304,7 @@ where
                 wl_surface: data.wl_surface.clone(),
                 xdg_surface: xdg_surface.clone(),
                 wm_base: data.wm_base.clone(),
+                destruction_hooks: DestructionHooks::new(),
             });
 
             data.shell_data
@@ -316,6 +319,13 @@ where
             (&mut *user_impl)(XdgRequest::NewPopup { surface: handle });
         }
         xdg_surface::Request::SetWindowGeometry { x, y, width, height } => {
+            if width <= 0 || height <= 0 {
+                // the protocol forbids empty geometries, but does not define an error for it
+                warn!(data.shell_data.log, "Ignoring invalid window geometry";
+                    "width" => width, "height" => height,
+                    "surface" => debug_name::describe_surface(data.shell_data.compositor_token, &data.wl_surface));
+                return;
+            }
             data.shell_data
                 .compositor_token
                 .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
@@ -327,21 +337,12 @@ where
             data.shell_data
                 .compositor_token
                 .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |role_data| {
-                    let mut found = false;
-                    role_data.pending_configures.retain(|&s| {
-                        if s == serial {
-                            found = true;
-                        }
-                        s > serial
-                    });
-                    if !found {
+                    if let Err(err) = role_data.lifecycle.ack_configure(Serial::from(serial)) {
                         // client responded to a non-existing configure
-                        data.wm_base.as_ref().post_error(
-                            xdg_wm_base::Error::InvalidSurfaceState as u32,
-                            format!("Wrong configure serial: {}", serial),
-                        );
+                        data.wm_base
+                            .as_ref()
+                            .post_error(xdg_wm_base::Error::InvalidSurfaceState as u32, err.to_string());
                     }
-                    role_data.configured = true;
                 })
                 .expect("xdg_surface exists but surface has not shell_surface role?!");
 
@@ -365,6 +366,8 @@ pub(crate) struct ShellSurfaceUserData<R> {
     pub(crate) wl_surface: wl_surface::WlSurface,
     pub(crate) wm_base: xdg_wm_base::XdgWmBase,
     pub(crate) xdg_surface: xdg_surface::XdgSurface,
+    // only used by toplevels
+    pub(crate) destruction_hooks: DestructionHooks<dyn FnOnce(&wl_surface::WlSurface)>,
 }
 
 // Utility functions allowing to factor out a lot of the upcoming logic
@@ -413,12 +416,14 @@ where
     data.shell_data
         .compositor_token
         .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
-            data.pending_configures.push(serial.into())
+            data.lifecycle.send_configure(serial)
         })
         .expect("xdg_toplevel exists but surface has not shell_surface role?!");
 }
 
-fn make_toplevel_handle<R: 'static>(resource: &xdg_toplevel::XdgToplevel) -> super::ToplevelSurface<R> {
+pub(crate) fn make_toplevel_handle<R: 'static>(
+    resource: &xdg_toplevel::XdgToplevel,
+) -> super::ToplevelSurface<R> {
     let data = resource
         .as_ref()
         .user_data()
@@ -458,11 +463,15 @@ where
             });
         }
         xdg_toplevel::Request::SetTitle { title } => {
+            debug_name::suggest_surface_name(data.shell_data.compositor_token, &data.wl_surface, &title);
             with_surface_toplevel_data(&data.shell_data, &toplevel, |toplevel_data| {
                 toplevel_data.title = title;
             });
         }
         xdg_toplevel::Request::SetAppId { app_id } => {
+            if let Some(client) = toplevel.as_ref().client() {
+                debug_name::suggest_client_name(&client, &app_id);
+            }
             with_surface_toplevel_data(&data.shell_data, &toplevel, |toplevel_data| {
                 toplevel_data.app_id = app_id;
             });
@@ -500,11 +509,23 @@ where
             });
         }
         xdg_toplevel::Request::SetMaxSize { width, height } => {
+            if width < 0 || height < 0 {
+                warn!(data.shell_data.log, "Ignoring negative max size";
+                    "width" => width, "height" => height,
+                    "surface" => debug_name::describe_surface(data.shell_data.compositor_token, &data.wl_surface));
+                return;
+            }
             with_surface_toplevel_data(&data.shell_data, &toplevel, |toplevel_data| {
                 toplevel_data.max_size = (width, height);
             });
         }
         xdg_toplevel::Request::SetMinSize { width, height } => {
+            if width < 0 || height < 0 {
+                warn!(data.shell_data.log, "Ignoring negative min size";
+                    "width" => width, "height" => height,
+                    "surface" => debug_name::describe_surface(data.shell_data.compositor_token, &data.wl_surface));
+                return;
+            }
             with_surface_toplevel_data(&data.shell_data, &toplevel, |toplevel_data| {
                 toplevel_data.min_size = (width, height);
             });
@@ -559,7 +580,7 @@ where
             .compositor_token
             .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
                 data.pending_state = XdgSurfacePendingState::None;
-                data.configured = false;
+                data.lifecycle.reset();
             })
             .expect("xdg_toplevel exists but surface has not shell_surface role?!");
     }
@@ -570,6 +591,7 @@ where
         .unwrap()
         .known_toplevels
         .retain(|other| other.alive());
+    data.destruction_hooks.invoke(&data.wl_surface);
 }
 
 /*
@@ -594,7 +616,7 @@ where
     data.shell_data
         .compositor_token
         .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
-            data.pending_configures.push(serial.into())
+            data.lifecycle.send_configure(serial)
         })
         .expect("xdg_toplevel exists but surface has not shell_surface role?!");
 }
@@ -657,7 +679,7 @@ where
             .compositor_token
             .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
                 data.pending_state = XdgSurfacePendingState::None;
-                data.configured = false;
+                data.lifecycle.reset();
             })
             .expect("xdg_popup exists but surface has not shell_surface role?!");
     }
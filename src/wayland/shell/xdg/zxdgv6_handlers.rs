@@ -1,7 +1,7 @@
 use std::{cell::RefCell, ops::Deref as _, sync::Mutex};
 
 use crate::wayland::compositor::{roles::*, CompositorToken};
-use crate::wayland::Serial;
+use crate::wayland::{debug_name, Serial};
 use wayland_protocols::{
     unstable::xdg_shell::v6::server::{
         zxdg_popup_v6, zxdg_positioner_v6, zxdg_shell_v6, zxdg_surface_v6, zxdg_toplevel_v6,
@@ -10,7 +10,7 @@ use wayland_protocols::{
 };
 use wayland_server::{protocol::wl_surface, Filter, Main};
 
-use crate::utils::Rectangle;
+use crate::utils::{DestructionHooks, Rectangle};
 
 use super::{
     make_shell_client_data, PopupConfigure, PopupKind, PopupState, PositionerState, ShellClient,
@@ -72,8 +72,9 @@ where
             let role_data = XdgSurfaceRole {
                 pending_state: XdgSurfacePendingState::None,
                 window_geometry: None,
-                pending_configures: Vec::new(),
-                configured: false,
+                lifecycle: Default::default(),
+                queued_configure: None,
+                last_configure: None,
             };
             if data
                 .shell_data
@@ -274,6 +275,7 @@ fn xdg_surface_implementation<R>(
                 wl_surface: data.wl_surface.clone(),
                 shell: data.shell.clone(),
                 xdg_surface: xdg_surface.clone(),
+                destruction_hooks: DestructionHooks::new(),
             });
 
             data.shell_data
@@ -319,6 +321,7 @@ fn xdg_surface_implementation<R>(
                 wl_surface: data.wl_surface.clone(),
                 shell: data.shell.clone(),
                 xdg_surface: xdg_surface.clone(),
+                destruction_hooks: DestructionHooks::new(),
             });
 
             data.shell_data
@@ -333,6 +336,13 @@ fn xdg_surface_implementation<R>(
             (&mut *user_impl)(XdgRequest::NewPopup { surface: handle });
         }
         zxdg_surface_v6::Request::SetWindowGeometry { x, y, width, height } => {
+            if width <= 0 || height <= 0 {
+                // the protocol forbids empty geometries, but does not define an error for it
+                warn!(data.shell_data.log, "Ignoring invalid window geometry";
+                    "width" => width, "height" => height,
+                    "surface" => debug_name::describe_surface(data.shell_data.compositor_token, &data.wl_surface));
+                return;
+            }
             data.shell_data
                 .compositor_token
                 .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
@@ -344,21 +354,12 @@ fn xdg_surface_implementation<R>(
             data.shell_data
                 .compositor_token
                 .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |role_data| {
-                    let mut found = false;
-                    role_data.pending_configures.retain(|&s| {
-                        if s == serial {
-                            found = true;
-                        }
-                        s > serial
-                    });
-                    if !found {
+                    if let Err(err) = role_data.lifecycle.ack_configure(Serial::from(serial)) {
                         // client responded to a non-existing configure
-                        data.shell.as_ref().post_error(
-                            zxdg_shell_v6::Error::InvalidSurfaceState as u32,
-                            format!("Wrong configure serial: {}", serial),
-                        );
+                        data.shell
+                            .as_ref()
+                            .post_error(zxdg_shell_v6::Error::InvalidSurfaceState as u32, err.to_string());
                     }
-                    role_data.configured = true;
                 })
                 .expect("xdg_surface exists but surface has not shell_surface role?!");
 
@@ -382,6 +383,8 @@ pub struct ShellSurfaceUserData<R> {
     pub(crate) wl_surface: wl_surface::WlSurface,
     pub(crate) shell: zxdg_shell_v6::ZxdgShellV6,
     pub(crate) xdg_surface: zxdg_surface_v6::ZxdgSurfaceV6,
+    // only used by toplevels
+    pub(crate) destruction_hooks: DestructionHooks<dyn FnOnce(&wl_surface::WlSurface)>,
 }
 
 // Utility functions allowing to factor out a lot of the upcoming logic
@@ -430,7 +433,7 @@ where
     data.shell_data
         .compositor_token
         .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
-            data.pending_configures.push(serial.into())
+            data.lifecycle.send_configure(serial)
         })
         .expect("xdg_toplevel exists but surface has not shell_surface role?!");
 }
@@ -476,11 +479,15 @@ where
             });
         }
         zxdg_toplevel_v6::Request::SetTitle { title } => {
+            debug_name::suggest_surface_name(data.shell_data.compositor_token, &data.wl_surface, &title);
             with_surface_toplevel_data::<R, _>(&toplevel, |toplevel_data| {
                 toplevel_data.title = title;
             });
         }
         zxdg_toplevel_v6::Request::SetAppId { app_id } => {
+            if let Some(client) = toplevel.as_ref().client() {
+                debug_name::suggest_client_name(&client, &app_id);
+            }
             with_surface_toplevel_data::<R, _>(&toplevel, |toplevel_data| {
                 toplevel_data.app_id = app_id;
             });
@@ -520,11 +527,23 @@ where
             });
         }
         zxdg_toplevel_v6::Request::SetMaxSize { width, height } => {
+            if width < 0 || height < 0 {
+                warn!(data.shell_data.log, "Ignoring negative max size";
+                    "width" => width, "height" => height,
+                    "surface" => debug_name::describe_surface(data.shell_data.compositor_token, &data.wl_surface));
+                return;
+            }
             with_surface_toplevel_data::<R, _>(&toplevel, |toplevel_data| {
                 toplevel_data.max_size = (width, height);
             });
         }
         zxdg_toplevel_v6::Request::SetMinSize { width, height } => {
+            if width < 0 || height < 0 {
+                warn!(data.shell_data.log, "Ignoring negative min size";
+                    "width" => width, "height" => height,
+                    "surface" => debug_name::describe_surface(data.shell_data.compositor_token, &data.wl_surface));
+                return;
+            }
             with_surface_toplevel_data::<R, _>(&toplevel, |toplevel_data| {
                 toplevel_data.min_size = (width, height);
             });
@@ -579,7 +598,7 @@ where
             .compositor_token
             .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
                 data.pending_state = XdgSurfacePendingState::None;
-                data.configured = false;
+                data.lifecycle.reset();
             })
             .expect("xdg_toplevel exists but surface has not shell_surface role?!");
     }
@@ -590,6 +609,7 @@ where
         .unwrap()
         .known_toplevels
         .retain(|other| other.alive());
+    data.destruction_hooks.invoke(&data.wl_surface);
 }
 
 /*
@@ -614,7 +634,7 @@ where
     data.shell_data
         .compositor_token
         .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
-            data.pending_configures.push(serial.into())
+            data.lifecycle.send_configure(serial)
         })
         .expect("xdg_toplevel exists but surface has not shell_surface role?!");
 }
@@ -677,7 +697,7 @@ where
             .compositor_token
             .with_role_data::<XdgSurfaceRole, _, _>(&data.wl_surface, |data| {
                 data.pending_state = XdgSurfacePendingState::None;
-                data.configured = false;
+                data.lifecycle.reset();
             })
             .expect("xdg_popup exists but surface has not shell_surface role?!");
     }
@@ -72,6 +72,13 @@
 //! by using a SIGBUS handler.
 //!
 //! If you are already using an handler for this signal, you probably don't want to use this handler.
+//!
+//! ## Uploading large buffers
+//!
+//! Reading large buffers out of their pool can stall your rendering. The [`ShmUploadQueue`] copies
+//! them on worker threads, so that you can upload the copies instead, see its documentation for
+//! details. With the `renderer_gl` feature, [`StagedBuffer::upload_to_texture`] uploads such a copy
+//! into an OpenGL texture on the render thread.
 
 use self::pool::{Pool, ResizeError};
 use std::{ops::Deref as _, rc::Rc, sync::Arc};
@@ -81,6 +88,11 @@ use wayland_server::{
 };
 
 mod pool;
+mod upload;
+
+#[cfg(feature = "renderer_gl")]
+pub use self::upload::TextureUploadError;
+pub use self::upload::{ShmUploadQueue, StagedBuffer, UploadQueueConfig};
 
 #[derive(Clone)]
 /// Internal data storage of `ShmGlobal`
@@ -136,9 +148,10 @@ where
 }
 
 /// Error that can occur when accessing an SHM buffer
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum BufferAccessError {
     /// This buffer is not managed by the SHM handler
+    #[error("The buffer is not managed by the SHM handler")]
     NotManaged,
     /// An error occurred while accessing the memory map
     ///
@@ -146,6 +159,7 @@ pub enum BufferAccessError {
     /// for the memory map.
     ///
     /// If this error occurs, the client has been killed as a result.
+    #[error("Accessing the memory map of the buffer failed")]
     BadMap,
 }
 
@@ -157,6 +171,9 @@ pub enum BufferAccessError {
 /// - The first argument is a data slice of the contents of the pool
 /// - The second argument is the specification of this buffer is this pool
 ///
+/// The specification is validated when the buffer is created, the range of the pool described
+/// by it (see [`BufferData::byte_range`]) is thus always contained in the slice.
+///
 /// If the buffer is not managed by the provided `ShmGlobal`, the closure is not called
 /// and this method will return `Err(())` (this will be the case for an EGL buffer for example).
 pub fn with_buffer_contents<F, T>(buffer: &wl_buffer::WlBuffer, f: F) -> Result<T, BufferAccessError>
@@ -180,6 +197,46 @@ where
     }
 }
 
+/// Copy the contents of a buffer out of its pool, with tightly packed rows
+pub(crate) fn copy_buffer_contents(
+    buffer: &wl_buffer::WlBuffer,
+) -> Result<(BufferData, Vec<u8>), BufferAccessError> {
+    with_buffer_contents(buffer, upload::stage).and_then(|r| r.map_err(|()| BufferAccessError::BadMap))
+}
+
+/// The layout of a shm buffer in its pool, if the buffer is managed by the SHM handler
+#[cfg(feature = "wayland_wlr_protocols")]
+pub(crate) fn buffer_data(buffer: &wl_buffer::WlBuffer) -> Option<BufferData> {
+    buffer
+        .as_ref()
+        .user_data()
+        .get::<InternalBufferData>()
+        .map(|data| data.data)
+}
+
+/// Overwrite the contents of a shm buffer, laid out as described by its [`BufferData`]
+///
+/// Contents longer than the buffer are truncated.
+#[cfg(feature = "wayland_wlr_protocols")]
+pub(crate) fn write_buffer_contents(
+    buffer: &wl_buffer::WlBuffer,
+    contents: &[u8],
+) -> Result<(), BufferAccessError> {
+    let data = match buffer.as_ref().user_data().get::<InternalBufferData>() {
+        Some(d) => d,
+        None => return Err(BufferAccessError::NotManaged),
+    };
+    let len = data.data.byte_range().len().min(contents.len());
+    data.pool
+        .write(data.data.offset as usize, &contents[..len])
+        .map_err(|()| BufferAccessError::BadMap)
+}
+
+/// Check if the given buffer is managed by the SHM handler
+pub(crate) fn is_shm_buffer(buffer: &wl_buffer::WlBuffer) -> bool {
+    buffer.as_ref().user_data().get::<InternalBufferData>().is_some()
+}
+
 impl ShmGlobalData {
     fn receive_shm_message(&mut self, request: wl_shm::Request, shm: wl_shm::WlShm) {
         use self::wl_shm::{Error, Request};
@@ -229,6 +286,60 @@ pub struct BufferData {
     pub format: wl_shm::Format,
 }
 
+impl BufferData {
+    /// Range of the pool containing the contents of this buffer
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        let start = self.offset as usize;
+        start..start + self.stride as usize * self.height as usize
+    }
+}
+
+// Size of a pixel in bytes, for the single-plane formats we know about
+pub(crate) fn bytes_per_pixel(format: wl_shm::Format) -> Option<i32> {
+    use self::wl_shm::Format;
+    match format {
+        Format::C8 | Format::Rgb332 | Format::Bgr233 => Some(1),
+        Format::Rgb565
+        | Format::Bgr565
+        | Format::Xrgb1555
+        | Format::Argb1555
+        | Format::Xrgb4444
+        | Format::Argb4444 => Some(2),
+        Format::Rgb888 | Format::Bgr888 => Some(3),
+        Format::Argb8888
+        | Format::Xrgb8888
+        | Format::Abgr8888
+        | Format::Xbgr8888
+        | Format::Rgba8888
+        | Format::Rgbx8888
+        | Format::Bgra8888
+        | Format::Bgrx8888
+        | Format::Xrgb2101010
+        | Format::Argb2101010
+        | Format::Xbgr2101010
+        | Format::Abgr2101010 => Some(4),
+        _ => None,
+    }
+}
+
+// Check that a buffer specification is sane and fits into its pool
+fn validate_buffer(data: &BufferData, pool_size: usize) -> bool {
+    if data.offset < 0 || data.width <= 0 || data.height <= 0 || data.stride <= 0 {
+        return false;
+    }
+    if let Some(bpp) = bytes_per_pixel(data.format) {
+        match data.width.checked_mul(bpp) {
+            Some(min_stride) if data.stride >= min_stride => {}
+            _ => return false,
+        }
+    }
+    (data.stride as usize)
+        .checked_mul(data.height as usize)
+        .and_then(|size| size.checked_add(data.offset as usize))
+        .map(|end| end <= pool_size)
+        .unwrap_or(false)
+}
+
 struct InternalBufferData {
     pool: Arc<Pool>,
     data: BufferData,
@@ -256,15 +367,23 @@ impl ShmGlobalData {
                     );
                     return;
                 }
+                let data = BufferData {
+                    offset,
+                    width,
+                    height,
+                    stride,
+                    format,
+                };
+                if !validate_buffer(&data, arc_pool.size()) {
+                    pool.as_ref().post_error(
+                        wl_shm::Error::InvalidStride as u32,
+                        format!("Invalid buffer specification: {:?}.", data),
+                    );
+                    return;
+                }
                 let data = InternalBufferData {
                     pool: arc_pool.clone(),
-                    data: BufferData {
-                        offset,
-                        width,
-                        height,
-                        stride,
-                        format,
-                    },
+                    data,
                 };
                 buffer.quick_assign(|_, _, _| {});
                 buffer.as_ref().user_data().set(|| data);
@@ -287,3 +406,32 @@ impl ShmGlobalData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_buffer, BufferData};
+    use wayland_server::protocol::wl_shm::Format;
+
+    fn buffer(offset: i32, width: i32, height: i32, stride: i32) -> BufferData {
+        BufferData {
+            offset,
+            width,
+            height,
+            stride,
+            format: Format::Argb8888,
+        }
+    }
+
+    #[test]
+    fn buffer_validation() {
+        assert!(validate_buffer(&buffer(0, 10, 10, 40), 400));
+        assert!(validate_buffer(&buffer(100, 10, 10, 48), 580));
+        // stride too small for the width
+        assert!(!validate_buffer(&buffer(0, 10, 10, 39), 400));
+        // does not fit in the pool
+        assert!(!validate_buffer(&buffer(4, 10, 10, 40), 400));
+        assert!(!validate_buffer(&buffer(-4, 10, 10, 40), 400));
+        assert!(!validate_buffer(&buffer(0, 10, 0, 40), 400));
+        assert!(!validate_buffer(&buffer(0, i32::MAX, 10, 40), 400));
+    }
+}
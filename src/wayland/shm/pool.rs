@@ -53,6 +53,10 @@ impl Pool {
         })
     }
 
+    pub fn size(&self) -> usize {
+        self.map.read().unwrap().size()
+    }
+
     pub fn with_data_slice<T, F: FnOnce(&[u8]) -> T>(&self, f: F) -> Result<T, ()> {
         // Place the sigbus handler
         SIGBUS_INIT.call_once(|| unsafe {
@@ -88,6 +92,27 @@ impl Pool {
             }
         })
     }
+
+    /// Write data into the pool, through its file descriptor as our map is read-only
+    #[cfg(feature = "wayland_wlr_protocols")]
+    pub fn write(&self, offset: usize, mut data: &[u8]) -> Result<(), ()> {
+        let mut offset = offset as libc::off_t;
+        while !data.is_empty() {
+            match nix::sys::uio::pwrite(self.fd, data, offset) {
+                Ok(0) => return Err(()),
+                Ok(written) => {
+                    data = &data[written..];
+                    offset += written as libc::off_t;
+                }
+                Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => {}
+                Err(err) => {
+                    debug!(self.log, "Write to shm pool failed"; "fd" => self.fd, "error" => ?err);
+                    return Err(());
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Pool {
@@ -155,6 +180,14 @@ impl MemMap {
     }
 }
 
+// The pointer is owned by the map and only replaced through `&mut self` (`resize`, under the
+// write lock of the pool), while the memory is only ever exposed as shared slices under the read
+// lock. Nothing writes through the map: `Pool::write` goes through the file descriptor, and
+// modifications of the underlying file are expected anyway, as the client can write to it at any
+// time. The SIGBUS handler relies on a thread-local guard, so it works from any thread.
+unsafe impl Send for MemMap {}
+unsafe impl Sync for MemMap {}
+
 impl Drop for MemMap {
     fn drop(&mut self) {
         if !self.ptr.is_null() {
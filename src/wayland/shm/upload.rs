@@ -0,0 +1,366 @@
+//! Staging of shm buffer contents on worker threads
+//!
+//! Reading a large shm buffer out of the client memory pool can take several milliseconds,
+//! for example when its pages were swapped out or when a faulty client makes the read fail,
+//! which makes the compositor miss a frame. An [`ShmUploadQueue`] copies the contents of
+//! buffers on worker threads, into tightly packed memory owned by the compositor. The render
+//! thread then uploads these staged contents with its renderer as it would upload the buffer
+//! itself, without touching the client memory. With the `renderer_gl` feature,
+//! [`StagedBuffer::upload_to_texture`] does this upload into an OpenGL texture.
+//!
+//! This adds a copy of the contents compared to uploading from the pool directly, so it only
+//! pays off for large buffers, see [`UploadQueueConfig::min_size`].
+//!
+//! The client must not modify a buffer while it is being copied, so only release a buffer
+//! once its [`StagedBuffer`] was returned by the queue.
+
+#[cfg(feature = "renderer_gl")]
+use std::os::raw::c_uint;
+use std::{
+    collections::HashMap,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use wayland_server::protocol::{wl_buffer, wl_shm};
+
+use super::{bytes_per_pixel, BufferAccessError, BufferData, InternalBufferData, Pool};
+#[cfg(feature = "renderer_gl")]
+use crate::backend::graphics::gl::{ffi as gl_ffi, Gles2};
+
+/// Configuration of an [`ShmUploadQueue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadQueueConfig {
+    /// Number of worker threads
+    pub threads: usize,
+    /// Buffers smaller than this size, in bytes, are copied synchronously
+    ///
+    /// Dispatching small copies to a worker costs more than it saves.
+    pub min_size: usize,
+}
+
+impl Default for UploadQueueConfig {
+    fn default() -> Self {
+        UploadQueueConfig {
+            threads: 2,
+            min_size: 512 * 1024,
+        }
+    }
+}
+
+/// Contents of a shm buffer, copied out of its pool
+pub struct StagedBuffer {
+    /// The buffer these contents were copied from
+    pub buffer: wl_buffer::WlBuffer,
+    /// Layout of the staged contents
+    ///
+    /// The offset is always 0, and rows are tightly packed for the formats whose pixel size
+    /// is known, otherwise the stride of the original buffer is kept.
+    pub data: BufferData,
+    /// The staged contents
+    pub pixels: Vec<u8>,
+}
+
+/// Error that can happen when uploading a [`StagedBuffer`] into a texture
+#[cfg(feature = "renderer_gl")]
+#[derive(Debug, thiserror::Error)]
+pub enum TextureUploadError {
+    /// The format of the buffer cannot be uploaded
+    #[error("Buffers of format {0:?} cannot be uploaded")]
+    UnsupportedFormat(wl_shm::Format),
+    /// OpenGL reported an error during the upload
+    #[error("Uploading the texture failed with GL error {0:#x}")]
+    Gl(u32),
+}
+
+#[cfg(feature = "renderer_gl")]
+impl StagedBuffer {
+    /// Upload the staged contents into an OpenGL texture
+    ///
+    /// `Argb8888` and `Xrgb8888` buffers are supported. Their pixels are uploaded as is and the
+    /// texture swizzle is set to return them as RGBA when sampled, with an opaque alpha for
+    /// `Xrgb8888`.
+    ///
+    /// This only temporarily modifies the texture binding and the unpack row length, which are
+    /// restored before returning.
+    ///
+    /// # Safety
+    ///
+    /// This must be called on the render thread, with the context `gl` was loaded from being
+    /// current, and `tex_id` needs to be a valid texture of this context.
+    pub unsafe fn upload_to_texture(&self, gl: &Gles2, tex_id: c_uint) -> Result<(), TextureUploadError> {
+        let alpha = match self.data.format {
+            wl_shm::Format::Argb8888 => gl_ffi::ALPHA,
+            wl_shm::Format::Xrgb8888 => gl_ffi::ONE,
+            format => return Err(TextureUploadError::UnsupportedFormat(format)),
+        };
+
+        let mut old_tex_id: i32 = 0;
+        gl.GetIntegerv(gl_ffi::TEXTURE_BINDING_2D, &mut old_tex_id);
+        gl.BindTexture(gl_ffi::TEXTURE_2D, tex_id);
+        // the pixels are little endian words, so BGRA in memory
+        gl.TexParameteri(gl_ffi::TEXTURE_2D, gl_ffi::TEXTURE_SWIZZLE_R, gl_ffi::BLUE as i32);
+        gl.TexParameteri(gl_ffi::TEXTURE_2D, gl_ffi::TEXTURE_SWIZZLE_B, gl_ffi::RED as i32);
+        gl.TexParameteri(gl_ffi::TEXTURE_2D, gl_ffi::TEXTURE_SWIZZLE_A, alpha as i32);
+        gl.PixelStorei(gl_ffi::UNPACK_ROW_LENGTH, self.data.stride / 4);
+        gl.TexImage2D(
+            gl_ffi::TEXTURE_2D,
+            0,
+            gl_ffi::RGBA as i32,
+            self.data.width,
+            self.data.height,
+            0,
+            gl_ffi::RGBA,
+            gl_ffi::UNSIGNED_BYTE,
+            self.pixels.as_ptr() as *const _,
+        );
+        let res = match gl.GetError() {
+            gl_ffi::NO_ERROR => Ok(()),
+            err => Err(TextureUploadError::Gl(err)),
+        };
+        gl.PixelStorei(gl_ffi::UNPACK_ROW_LENGTH, 0);
+        gl.BindTexture(gl_ffi::TEXTURE_2D, old_tex_id as u32);
+        res
+    }
+}
+
+struct Job {
+    id: u64,
+    pool: Arc<Pool>,
+    data: BufferData,
+}
+
+struct Done {
+    id: u64,
+    result: Result<(BufferData, Vec<u8>), ()>,
+}
+
+/// A queue copying the contents of shm buffers on worker threads
+///
+/// Dropping the queue waits for the copies in progress and stops the worker threads.
+pub struct ShmUploadQueue {
+    config: UploadQueueConfig,
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<Done>,
+    workers: Vec<thread::JoinHandle<()>>,
+    pending: HashMap<u64, wl_buffer::WlBuffer>,
+    ready: Vec<StagedBuffer>,
+    next_id: u64,
+    log: ::slog::Logger,
+}
+
+impl ShmUploadQueue {
+    /// Create a new upload queue and start its worker threads
+    pub fn new<L>(config: UploadQueueConfig, logger: L) -> ShmUploadQueue
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "shm_upload_queue"));
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..config.threads.max(1))
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+                thread::Builder::new()
+                    .name(format!("shm-upload-{}", i))
+                    .spawn(move || loop {
+                        let job = match job_receiver.lock().unwrap().recv() {
+                            Ok(job) => job,
+                            // the queue was dropped
+                            Err(_) => return,
+                        };
+                        let data = job.data;
+                        let result = job
+                            .pool
+                            .with_data_slice(|slice| stage(slice, data))
+                            .and_then(|r| r);
+                        if result_sender.send(Done { id: job.id, result }).is_err() {
+                            return;
+                        }
+                    })
+                    .expect("Failed to spawn a shm upload thread")
+            })
+            .collect();
+
+        ShmUploadQueue {
+            config,
+            jobs: Some(jobs),
+            results,
+            workers,
+            pending: HashMap::new(),
+            ready: Vec::new(),
+            next_id: 0,
+            log,
+        }
+    }
+
+    /// Access the configuration of this queue
+    pub fn config(&self) -> UploadQueueConfig {
+        self.config
+    }
+
+    /// Start copying the contents of a buffer
+    ///
+    /// The staged contents will be returned by [`ShmUploadQueue::poll`] or
+    /// [`ShmUploadQueue::wait`].
+    pub fn submit(&mut self, buffer: &wl_buffer::WlBuffer) -> Result<(), BufferAccessError> {
+        let internal = match buffer.as_ref().user_data().get::<InternalBufferData>() {
+            Some(d) => d,
+            None => return Err(BufferAccessError::NotManaged),
+        };
+        let data = internal.data;
+
+        if data.byte_range().len() < self.config.min_size {
+            let result = internal.pool.with_data_slice(|slice| stage(slice, data));
+            return match result {
+                Ok(Ok((data, pixels))) => {
+                    self.ready.push(StagedBuffer {
+                        buffer: buffer.clone(),
+                        data,
+                        pixels,
+                    });
+                    Ok(())
+                }
+                _ => {
+                    bad_map(buffer);
+                    Err(BufferAccessError::BadMap)
+                }
+            };
+        }
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        trace!(self.log, "Staging shm buffer on a worker"; "id" => id, "size" => data.byte_range().len());
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send(Job {
+                id,
+                pool: internal.pool.clone(),
+                data,
+            })
+            .expect("The shm upload threads died");
+        self.pending.insert(id, buffer.clone());
+        Ok(())
+    }
+
+    /// Number of buffers currently being copied
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Retrieve the buffers whose copy finished, without blocking
+    ///
+    /// Buffers whose pool could not be accessed are not returned, their client is killed.
+    pub fn poll(&mut self) -> Vec<StagedBuffer> {
+        while let Ok(done) = self.results.try_recv() {
+            self.finish(done);
+        }
+        ::std::mem::take(&mut self.ready)
+    }
+
+    /// Wait for all pending copies to finish and retrieve them
+    pub fn wait(&mut self) -> Vec<StagedBuffer> {
+        while !self.pending.is_empty() {
+            match self.results.recv() {
+                Ok(done) => self.finish(done),
+                Err(_) => break,
+            }
+        }
+        self.poll()
+    }
+
+    fn finish(&mut self, done: Done) {
+        let buffer = match self.pending.remove(&done.id) {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        match done.result {
+            Ok((data, pixels)) => self.ready.push(StagedBuffer { buffer, data, pixels }),
+            Err(()) => {
+                debug!(self.log, "Failed to stage shm buffer"; "id" => done.id);
+                bad_map(&buffer);
+            }
+        }
+    }
+}
+
+impl Drop for ShmUploadQueue {
+    fn drop(&mut self) {
+        // closing the channel stops the workers once they are done
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl ::std::fmt::Debug for ShmUploadQueue {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("ShmUploadQueue")
+            .field("config", &self.config)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+fn bad_map(buffer: &wl_buffer::WlBuffer) {
+    buffer
+        .as_ref()
+        .post_error(wl_shm::Error::InvalidFd as u32, "Bad pool size.".into());
+}
+
+// Copy the contents of a buffer out of its pool, removing the padding between rows
+pub(super) fn stage(slice: &[u8], data: BufferData) -> Result<(BufferData, Vec<u8>), ()> {
+    let stride = data.stride as usize;
+    let row = bytes_per_pixel(data.format)
+        .map(|bpp| (data.width * bpp) as usize)
+        .unwrap_or(stride);
+    let height = data.height as usize;
+    let offset = data.offset as usize;
+
+    let mut pixels = Vec::with_capacity(row * height);
+    for i in 0..height {
+        let start = offset + i * stride;
+        // the pool may have been emptied if remapping it failed
+        pixels.extend_from_slice(slice.get(start..start + row).ok_or(())?);
+    }
+    Ok((
+        BufferData {
+            offset: 0,
+            stride: row as i32,
+            ..data
+        },
+        pixels,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stage, BufferData};
+    use wayland_server::protocol::wl_shm::Format;
+
+    #[test]
+    fn staging_removes_padding() {
+        // 2x2 pixels, with 4 bytes of padding per row, after 2 bytes of offset
+        let pool: Vec<u8> = (0..26).collect();
+        let data = BufferData {
+            offset: 2,
+            width: 2,
+            height: 2,
+            stride: 12,
+            format: Format::Argb8888,
+        };
+        let (staged, pixels) = stage(&pool, data).unwrap();
+        assert_eq!(staged.offset, 0);
+        assert_eq!(staged.stride, 8);
+        assert_eq!(
+            pixels,
+            vec![2, 3, 4, 5, 6, 7, 8, 9, 14, 15, 16, 17, 18, 19, 20, 21]
+        );
+        assert!(stage(&pool[..16], data).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Handler for the tablet protocol
+//!
+//! This protocol lets clients receive the events of graphics tablets: the position of the
+//! tools (pens, erasers, airbrushes...) along with their pressure, tilt or distance from the
+//! tablet, and the buttons, rings and strips of the pads of the tablets.
+//!
+//! Once the global is created with [`init_tablet_manager_global`], get the
+//! [`TabletSeatHandle`] of a seat with [`tablet_seat`], and add the devices as your input
+//! backend reports them. Tablets are given a [`TabletDescriptor`](crate::backend::input::TabletDescriptor),
+//! and tools are added the first time they come into proximity, from the descriptor of
+//! their events.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! # extern crate smithay;
+//! use smithay::wayland::tablet_manager::{init_tablet_manager_global, tablet_seat};
+//! # use smithay::wayland::seat::Seat;
+//! # use smithay::backend::input::TabletDescriptor;
+//! # let mut display = wayland_server::Display::new();
+//! # let seat: Seat = unimplemented!();
+//! # let tablet_desc: TabletDescriptor = unimplemented!();
+//!
+//! init_tablet_manager_global(&mut display, None /* insert a logger here */);
+//!
+//! let tablets = tablet_seat(&seat);
+//! // when the input backend reports a new tablet
+//! let tablet = tablets.add_tablet(&tablet_desc);
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::tablet::v2::server::{
+    zwp_tablet_manager_v2::{self, ZwpTabletManagerV2},
+    zwp_tablet_seat_v2::{self, ZwpTabletSeatV2},
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+use crate::backend::input::{TabletDescriptor, TabletPadDescriptor, TabletToolDescriptor};
+use crate::wayland::{
+    compositor::{roles::Role, CompositorToken},
+    seat::{CursorImageRole, CursorImageStatus, Seat},
+};
+
+mod pad;
+mod tablet;
+mod tool;
+
+pub use self::pad::TabletPadHandle;
+pub use self::tablet::TabletHandle;
+pub use self::tool::TabletToolHandle;
+
+struct TabletSeatInner {
+    instances: Vec<ZwpTabletSeatV2>,
+    tablets: Vec<TabletHandle>,
+    tools: Vec<TabletToolHandle>,
+    pads: Vec<TabletPadHandle>,
+    log: ::slog::Logger,
+}
+
+/// A handle to the tablets of a seat
+///
+/// It is obtained with [`tablet_seat`], and can be cloned. The devices added to it are
+/// announced to all the clients, including the ones binding the seat later on.
+#[derive(Clone)]
+pub struct TabletSeatHandle {
+    inner: Rc<RefCell<TabletSeatInner>>,
+}
+
+impl TabletSeatHandle {
+    fn new_instance(&self, instance: Main<ZwpTabletSeatV2>) {
+        instance.quick_assign(|_, request, _| match request {
+            zwp_tablet_seat_v2::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            _ => unreachable!(),
+        });
+        let inner = self.inner.clone();
+        instance.assign_destructor(Filter::new(move |instance: ZwpTabletSeatV2, _, _| {
+            inner
+                .borrow_mut()
+                .instances
+                .retain(|i| !i.as_ref().equals(instance.as_ref()));
+        }));
+
+        let mut inner = self.inner.borrow_mut();
+        trace!(inner.log, "New tablet seat");
+        // the tablets are announced first, so that the pads and tools can refer to them
+        for tablet in &inner.tablets {
+            tablet.new_instance(&instance);
+        }
+        for tool in &inner.tools {
+            tool.new_instance(&instance);
+        }
+        for pad in &inner.pads {
+            pad.new_instance(&instance);
+        }
+        inner.instances.push((*instance).clone());
+    }
+
+    /// Add a tablet to this seat, and announce it to the clients
+    ///
+    /// If a tablet with the same descriptor was already added, its handle is returned.
+    pub fn add_tablet(&self, descriptor: &TabletDescriptor) -> TabletHandle {
+        if let Some(tablet) = self.get_tablet(descriptor) {
+            return tablet;
+        }
+        let mut inner = self.inner.borrow_mut();
+        debug!(inner.log, "New tablet"; "name" => &descriptor.name);
+        let tablet = TabletHandle::new(descriptor);
+        for instance in &inner.instances {
+            tablet.new_instance(instance);
+        }
+        inner.tablets.push(tablet.clone());
+        tablet
+    }
+
+    /// Access a tablet of this seat from its descriptor
+    pub fn get_tablet(&self, descriptor: &TabletDescriptor) -> Option<TabletHandle> {
+        self.inner
+            .borrow()
+            .tablets
+            .iter()
+            .find(|tablet| tablet.descriptor() == descriptor)
+            .cloned()
+    }
+
+    /// Remove a tablet from this seat
+    ///
+    /// Clients will be appropriately notified.
+    pub fn remove_tablet(&self, descriptor: &TabletDescriptor) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(pos) = inner.tablets.iter().position(|t| t.descriptor() == descriptor) {
+            debug!(inner.log, "Tablet removed"; "name" => &descriptor.name);
+            inner.tablets.remove(pos).removed();
+        }
+    }
+
+    /// Add a tool to this seat, and announce it to the clients
+    ///
+    /// The callback is notified when the focused client sets the cursor image of the tool.
+    /// If a tool with the same descriptor was already added, its handle is returned and the
+    /// callback is dropped.
+    pub fn add_tool<R, F>(
+        &self,
+        descriptor: &TabletToolDescriptor,
+        token: CompositorToken<R>,
+        cb: F,
+    ) -> TabletToolHandle
+    where
+        R: Role<CursorImageRole> + 'static,
+        F: FnMut(CursorImageStatus) + 'static,
+    {
+        if let Some(tool) = self.get_tool(descriptor) {
+            return tool;
+        }
+        let mut inner = self.inner.borrow_mut();
+        debug!(inner.log, "New tablet tool"; "type" => ?descriptor.tool_type);
+        let tool = TabletToolHandle::new(descriptor, token, cb);
+        for instance in &inner.instances {
+            tool.new_instance(instance);
+        }
+        inner.tools.push(tool.clone());
+        tool
+    }
+
+    /// Access a tool of this seat from its descriptor
+    pub fn get_tool(&self, descriptor: &TabletToolDescriptor) -> Option<TabletToolHandle> {
+        self.inner
+            .borrow()
+            .tools
+            .iter()
+            .find(|tool| tool.descriptor() == descriptor)
+            .cloned()
+    }
+
+    /// Remove a tool from this seat
+    ///
+    /// Clients will be appropriately notified.
+    pub fn remove_tool(&self, descriptor: &TabletToolDescriptor) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(pos) = inner.tools.iter().position(|t| t.descriptor() == descriptor) {
+            debug!(inner.log, "Tablet tool removed"; "type" => ?descriptor.tool_type);
+            inner.tools.remove(pos).removed();
+        }
+    }
+
+    /// Add a tablet pad to this seat, and announce it to the clients
+    ///
+    /// If a pad with the same descriptor was already added, its handle is returned.
+    pub fn add_pad(&self, descriptor: &TabletPadDescriptor) -> TabletPadHandle {
+        if let Some(pad) = self.get_pad(descriptor) {
+            return pad;
+        }
+        let mut inner = self.inner.borrow_mut();
+        debug!(inner.log, "New tablet pad"; "name" => &descriptor.name);
+        let pad = TabletPadHandle::new(descriptor);
+        for instance in &inner.instances {
+            pad.new_instance(instance);
+        }
+        inner.pads.push(pad.clone());
+        pad
+    }
+
+    /// Access a tablet pad of this seat from its descriptor
+    pub fn get_pad(&self, descriptor: &TabletPadDescriptor) -> Option<TabletPadHandle> {
+        self.inner
+            .borrow()
+            .pads
+            .iter()
+            .find(|pad| pad.descriptor() == descriptor)
+            .cloned()
+    }
+
+    /// Remove a tablet pad from this seat
+    ///
+    /// Clients will be appropriately notified.
+    pub fn remove_pad(&self, descriptor: &TabletPadDescriptor) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(pos) = inner.pads.iter().position(|p| p.descriptor() == descriptor) {
+            debug!(inner.log, "Tablet pad removed"; "name" => &descriptor.name);
+            inner.pads.remove(pos).removed();
+        }
+    }
+}
+
+impl ::std::fmt::Debug for TabletSeatHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("TabletSeatHandle")
+            .field("instances", &inner.instances.len())
+            .field("tablets", &inner.tablets)
+            .field("tools", &inner.tools)
+            .field("pads", &inner.pads)
+            .finish()
+    }
+}
+
+/// Access the tablets of a seat
+///
+/// The handle is stored in the [`user_data`](Seat::user_data) of the seat, and created the
+/// first time this function is called.
+pub fn tablet_seat(seat: &Seat) -> TabletSeatHandle {
+    seat.user_data().insert_if_missing(|| TabletSeatHandle {
+        inner: Rc::new(RefCell::new(TabletSeatInner {
+            instances: Vec::new(),
+            tablets: Vec::new(),
+            tools: Vec::new(),
+            pads: Vec::new(),
+            log: seat.arc.log.new(o!("smithay_module" => "tablet_manager_handler")),
+        })),
+    });
+    seat.user_data().get::<TabletSeatHandle>().unwrap().clone()
+}
+
+/// Create a new tablet manager global
+pub fn init_tablet_manager_global<L>(display: &mut Display, logger: L) -> Global<ZwpTabletManagerV2>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "tablet_manager_handler"));
+
+    display.create_global::<ZwpTabletManagerV2, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpTabletManagerV2>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_tablet_manager_v2::Request::GetTabletSeat {
+                        tablet_seat: id,
+                        seat,
+                    } => {
+                        match Seat::from_resource(&seat) {
+                            Some(seat) => tablet_seat(&seat).new_instance(id),
+                            None => {
+                                // the seat was destroyed, the tablet seat stays empty
+                                warn!(log, "Tablet seat requested for a destroyed seat");
+                                id.quick_assign(|_, _, _| {});
+                            }
+                        }
+                    }
+                    zwp_tablet_manager_v2::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
@@ -0,0 +1,271 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::tablet::v2::server::{
+    zwp_tablet_pad_group_v2::{self, ZwpTabletPadGroupV2},
+    zwp_tablet_pad_ring_v2::{self, ZwpTabletPadRingV2},
+    zwp_tablet_pad_strip_v2::{self, ZwpTabletPadStripV2},
+    zwp_tablet_pad_v2::{self, ButtonState, ZwpTabletPadV2},
+    zwp_tablet_seat_v2::ZwpTabletSeatV2,
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Filter};
+
+use super::TabletHandle;
+use crate::backend::input::{MouseButtonState, TabletPadAxisSource, TabletPadDescriptor};
+use crate::wayland::Serial;
+
+// the objects describing a pad to a client
+struct PadInstance {
+    pad: ZwpTabletPadV2,
+    group: ZwpTabletPadGroupV2,
+    rings: Vec<ZwpTabletPadRingV2>,
+    strips: Vec<ZwpTabletPadStripV2>,
+}
+
+struct PadInner {
+    instances: Vec<PadInstance>,
+    focus: Option<WlSurface>,
+    mode: u32,
+}
+
+impl PadInner {
+    fn with_focused_pads<F>(&self, mut f: F)
+    where
+        F: FnMut(&PadInstance),
+    {
+        if let Some(ref surface) = self.focus {
+            for instance in &self.instances {
+                if instance.pad.as_ref().same_client_as(surface.as_ref()) {
+                    f(instance);
+                }
+            }
+        }
+    }
+}
+
+/// A handle to the pad of a graphics tablet, as announced to the clients
+///
+/// It is created by [`TabletSeatHandle::add_pad`](super::TabletSeatHandle::add_pad), and can be
+/// cloned. Pads are not bound to a pointer location: their events are sent to the surface
+/// given to [`set_focus`](TabletPadHandle::set_focus), usually the one with keyboard focus.
+#[derive(Clone)]
+pub struct TabletPadHandle {
+    descriptor: Rc<TabletPadDescriptor>,
+    inner: Rc<RefCell<PadInner>>,
+}
+
+impl TabletPadHandle {
+    pub(super) fn new(descriptor: &TabletPadDescriptor) -> TabletPadHandle {
+        TabletPadHandle {
+            descriptor: Rc::new(descriptor.clone()),
+            inner: Rc::new(RefCell::new(PadInner {
+                instances: Vec::new(),
+                focus: None,
+                mode: 0,
+            })),
+        }
+    }
+
+    /// The description of this pad
+    pub fn descriptor(&self) -> &TabletPadDescriptor {
+        &self.descriptor
+    }
+
+    // announce this pad to the client of a tablet seat
+    pub(super) fn new_instance(&self, seat: &ZwpTabletSeatV2) {
+        let client = match seat.as_ref().client() {
+            Some(client) => client,
+            None => return,
+        };
+        let version = seat.as_ref().version();
+        let pad = match client.create_resource::<ZwpTabletPadV2>(version) {
+            Some(pad) => pad,
+            None => return,
+        };
+        let group = match client.create_resource::<ZwpTabletPadGroupV2>(version) {
+            Some(group) => group,
+            None => return,
+        };
+        let rings = (0..self.descriptor.rings)
+            .filter_map(|_| client.create_resource::<ZwpTabletPadRingV2>(version))
+            .collect::<Vec<_>>();
+        let strips = (0..self.descriptor.strips)
+            .filter_map(|_| client.create_resource::<ZwpTabletPadStripV2>(version))
+            .collect::<Vec<_>>();
+
+        // the feedback strings are only informative, and ignored
+        pad.quick_assign(|_, request, _| match request {
+            zwp_tablet_pad_v2::Request::SetFeedback { .. } => {}
+            zwp_tablet_pad_v2::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            _ => unreachable!(),
+        });
+        let inner = self.inner.clone();
+        pad.assign_destructor(Filter::new(move |pad: ZwpTabletPadV2, _, _| {
+            inner
+                .borrow_mut()
+                .instances
+                .retain(|i| !i.pad.as_ref().equals(pad.as_ref()));
+        }));
+        group.quick_assign(|_, request, _| match request {
+            zwp_tablet_pad_group_v2::Request::Destroy => {}
+            _ => unreachable!(),
+        });
+        for ring in &rings {
+            ring.quick_assign(|_, request, _| match request {
+                zwp_tablet_pad_ring_v2::Request::SetFeedback { .. } => {}
+                zwp_tablet_pad_ring_v2::Request::Destroy => {}
+                _ => unreachable!(),
+            });
+        }
+        for strip in &strips {
+            strip.quick_assign(|_, request, _| match request {
+                zwp_tablet_pad_strip_v2::Request::SetFeedback { .. } => {}
+                zwp_tablet_pad_strip_v2::Request::Destroy => {}
+                _ => unreachable!(),
+            });
+        }
+
+        seat.pad_added(&pad);
+        if let Some(ref path) = self.descriptor.syspath {
+            pad.path(path.to_string_lossy().into_owned());
+        }
+        pad.buttons(self.descriptor.buttons);
+        pad.group(&group);
+        // all buttons belong to the single group of the pad
+        let buttons = (0..self.descriptor.buttons)
+            .flat_map(|button| button.to_ne_bytes().to_vec())
+            .collect::<Vec<u8>>();
+        group.buttons(buttons);
+        for ring in &rings {
+            group.ring(ring);
+        }
+        for strip in &strips {
+            group.strip(strip);
+        }
+        group.modes(self.descriptor.modes);
+        group.done();
+        pad.done();
+
+        self.inner.borrow_mut().instances.push(PadInstance {
+            pad: (*pad).clone(),
+            group: (*group).clone(),
+            rings: rings.iter().map(|ring| (**ring).clone()).collect(),
+            strips: strips.iter().map(|strip| (**strip).clone()).collect(),
+        });
+    }
+
+    pub(super) fn removed(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.focus = None;
+        for instance in inner.instances.drain(..) {
+            instance.pad.removed();
+        }
+    }
+
+    /// The surface the pad is currently focused on
+    pub fn current_focus(&self) -> Option<WlSurface> {
+        self.inner.borrow().focus.clone()
+    }
+
+    /// Change the surface the events of the pad are sent to
+    ///
+    /// The previously focused surface is left, and the new one entered. Nothing happens if
+    /// the focus does not change.
+    pub fn set_focus(&self, focus: Option<&WlSurface>, tablet: &TabletHandle, serial: Serial) {
+        let mut inner = self.inner.borrow_mut();
+        let same = match (&inner.focus, focus) {
+            (Some(old), Some(new)) => old.as_ref().equals(new.as_ref()),
+            (None, None) => true,
+            _ => false,
+        };
+        if same {
+            return;
+        }
+        if let Some(old) = inner.focus.clone() {
+            inner.with_focused_pads(|instance| instance.pad.leave(serial.into(), &old));
+        }
+        inner.focus = focus.cloned();
+        let mode = inner.mode;
+        inner.with_focused_pads(|instance| {
+            if let Some(surface) = focus {
+                if let Some(tablet) = tablet.instance_for(instance.pad.as_ref()) {
+                    instance.pad.enter(serial.into(), &tablet, surface);
+                    // let the client know the current mode of the pad
+                    instance.group.mode_switch(0, serial.into(), mode);
+                }
+            }
+        });
+    }
+
+    /// Notify a button of the pad was pressed or released
+    pub fn button(&self, button: u32, state: MouseButtonState, time: u32) {
+        let state = match state {
+            MouseButtonState::Pressed => ButtonState::Pressed,
+            MouseButtonState::Released => ButtonState::Released,
+        };
+        self.inner
+            .borrow()
+            .with_focused_pads(|instance| instance.pad.button(time, button, state));
+    }
+
+    /// Notify the position of a ring of the pad, in degrees
+    ///
+    /// A `None` position notifies that the finger was lifted from the ring.
+    pub fn ring(&self, number: u32, position: Option<f64>, source: TabletPadAxisSource, time: u32) {
+        self.inner.borrow().with_focused_pads(|instance| {
+            if let Some(ring) = instance.rings.get(number as usize) {
+                if source == TabletPadAxisSource::Finger {
+                    ring.source(zwp_tablet_pad_ring_v2::Source::Finger);
+                }
+                match position {
+                    Some(degrees) => ring.angle(degrees),
+                    None => ring.stop(),
+                }
+                ring.frame(time);
+            }
+        });
+    }
+
+    /// Notify the position of a strip of the pad, normalized between 0 and 1
+    ///
+    /// A `None` position notifies that the finger was lifted from the strip.
+    pub fn strip(&self, number: u32, position: Option<f64>, source: TabletPadAxisSource, time: u32) {
+        self.inner.borrow().with_focused_pads(|instance| {
+            if let Some(strip) = instance.strips.get(number as usize) {
+                if source == TabletPadAxisSource::Finger {
+                    strip.source(zwp_tablet_pad_strip_v2::Source::Finger);
+                }
+                match position {
+                    Some(position) => strip.position((position.max(0.0).min(1.0) * 65535.0) as u32),
+                    None => strip.stop(),
+                }
+                strip.frame(time);
+            }
+        });
+    }
+
+    /// Notify the pad switched to another mode
+    ///
+    /// Nothing happens if the pad already is in this mode.
+    pub fn mode_switch(&self, mode: u32, serial: Serial, time: u32) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.mode == mode {
+            return;
+        }
+        inner.mode = mode;
+        inner.with_focused_pads(|instance| instance.group.mode_switch(time, serial.into(), mode));
+    }
+}
+
+impl ::std::fmt::Debug for TabletPadHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("TabletPadHandle")
+            .field("descriptor", &self.descriptor)
+            .field("instances", &inner.instances.len())
+            .field("focus", &inner.focus)
+            .field("mode", &inner.mode)
+            .finish()
+    }
+}
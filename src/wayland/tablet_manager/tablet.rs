@@ -0,0 +1,95 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::tablet::v2::server::{
+    zwp_tablet_seat_v2::ZwpTabletSeatV2,
+    zwp_tablet_v2::{self, ZwpTabletV2},
+};
+use wayland_server::{Filter, Resource};
+
+use crate::backend::input::TabletDescriptor;
+
+/// A handle to a graphics tablet, as announced to the clients
+///
+/// It is created by [`TabletSeatHandle::add_tablet`](super::TabletSeatHandle::add_tablet), and
+/// can be cloned.
+#[derive(Clone)]
+pub struct TabletHandle {
+    descriptor: Rc<TabletDescriptor>,
+    instances: Rc<RefCell<Vec<ZwpTabletV2>>>,
+}
+
+impl TabletHandle {
+    pub(super) fn new(descriptor: &TabletDescriptor) -> TabletHandle {
+        TabletHandle {
+            descriptor: Rc::new(descriptor.clone()),
+            instances: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// The description of this tablet
+    pub fn descriptor(&self) -> &TabletDescriptor {
+        &self.descriptor
+    }
+
+    // announce this tablet to the client of a tablet seat
+    pub(super) fn new_instance(&self, seat: &ZwpTabletSeatV2) {
+        let tablet = match seat
+            .as_ref()
+            .client()
+            .and_then(|client| client.create_resource::<ZwpTabletV2>(seat.as_ref().version()))
+        {
+            Some(tablet) => tablet,
+            None => return,
+        };
+        tablet.quick_assign(|_, request, _| match request {
+            zwp_tablet_v2::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            _ => unreachable!(),
+        });
+        let instances = self.instances.clone();
+        tablet.assign_destructor(Filter::new(move |tablet: ZwpTabletV2, _, _| {
+            instances
+                .borrow_mut()
+                .retain(|t| !t.as_ref().equals(tablet.as_ref()));
+        }));
+
+        seat.tablet_added(&tablet);
+        tablet.name(self.descriptor.name.clone());
+        if let Some((vid, pid)) = self.descriptor.usb_id {
+            tablet.id(vid, pid);
+        }
+        if let Some(ref path) = self.descriptor.syspath {
+            tablet.path(path.to_string_lossy().into_owned());
+        }
+        tablet.done();
+        self.instances.borrow_mut().push((*tablet).clone());
+    }
+
+    // the instance of this tablet known to the client of a resource
+    pub(super) fn instance_for<I>(&self, resource: &Resource<I>) -> Option<ZwpTabletV2>
+    where
+        I: wayland_server::Interface,
+    {
+        self.instances
+            .borrow()
+            .iter()
+            .find(|t| t.as_ref().same_client_as(resource))
+            .cloned()
+    }
+
+    pub(super) fn removed(&self) {
+        for tablet in self.instances.borrow_mut().drain(..) {
+            tablet.removed();
+        }
+    }
+}
+
+impl ::std::fmt::Debug for TabletHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("TabletHandle")
+            .field("descriptor", &self.descriptor)
+            .field("instances", &self.instances.borrow().len())
+            .finish()
+    }
+}
@@ -0,0 +1,395 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::unstable::tablet::v2::server::{
+    zwp_tablet_seat_v2::ZwpTabletSeatV2,
+    zwp_tablet_tool_v2::{self, ButtonState, Capability, Type, ZwpTabletToolV2},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Filter};
+
+use super::TabletHandle;
+use crate::backend::input::{MouseButtonState, TabletToolDescriptor, TabletToolEvent, TabletToolType};
+use crate::wayland::{
+    compositor::{roles::Role, CompositorToken},
+    seat::{CursorImageRole, CursorImageStatus},
+    Serial,
+};
+
+// assign the cursor role to a surface, returning false if it already has another role
+type CursorRoleAssigner = Box<dyn Fn(&WlSurface, (i32, i32)) -> bool>;
+
+struct ToolInner {
+    instances: Vec<ZwpTabletToolV2>,
+    // the focused surface and its location
+    focus: Option<(WlSurface, (f64, f64))>,
+    down: bool,
+    give_role: CursorRoleAssigner,
+    image_callback: Box<dyn FnMut(CursorImageStatus)>,
+}
+
+impl ToolInner {
+    fn with_focused_tools<F>(&self, mut f: F)
+    where
+        F: FnMut(&ZwpTabletToolV2, &WlSurface),
+    {
+        if let Some((ref surface, _)) = self.focus {
+            for tool in &self.instances {
+                if tool.as_ref().same_client_as(surface.as_ref()) {
+                    f(tool, surface);
+                }
+            }
+        }
+    }
+}
+
+/// A handle to a tablet tool, as announced to the clients
+///
+/// It is created by [`TabletSeatHandle::add_tool`](super::TabletSeatHandle::add_tool), and can
+/// be cloned. Events are sent to the client whose surface the tool is over, and grouped in
+/// frames: call [`frame`](TabletToolHandle::frame) once all the changes of a hardware event
+/// were sent.
+#[derive(Clone)]
+pub struct TabletToolHandle {
+    descriptor: TabletToolDescriptor,
+    inner: Rc<RefCell<ToolInner>>,
+}
+
+impl TabletToolHandle {
+    pub(super) fn new<R, F>(descriptor: &TabletToolDescriptor, token: CompositorToken<R>, cb: F) -> Self
+    where
+        R: Role<CursorImageRole> + 'static,
+        F: FnMut(CursorImageStatus) + 'static,
+    {
+        let give_role = move |surface: &WlSurface, hotspot: (i32, i32)| {
+            let role_data = CursorImageRole { hotspot };
+            // surfaces are usually reused, and may already be cursors
+            token.with_role_data(surface, |data| *data = role_data).is_ok()
+                || token.give_role_with(surface, role_data).is_ok()
+        };
+        TabletToolHandle {
+            descriptor: *descriptor,
+            inner: Rc::new(RefCell::new(ToolInner {
+                instances: Vec::new(),
+                focus: None,
+                down: false,
+                give_role: Box::new(give_role),
+                image_callback: Box::new(cb),
+            })),
+        }
+    }
+
+    /// The description of this tool
+    pub fn descriptor(&self) -> &TabletToolDescriptor {
+        &self.descriptor
+    }
+
+    // announce this tool to the client of a tablet seat
+    pub(super) fn new_instance(&self, seat: &ZwpTabletSeatV2) {
+        let tool = match seat
+            .as_ref()
+            .client()
+            .and_then(|client| client.create_resource::<ZwpTabletToolV2>(seat.as_ref().version()))
+        {
+            Some(tool) => tool,
+            None => return,
+        };
+        let inner = self.inner.clone();
+        tool.quick_assign(move |tool, request, _| match request {
+            zwp_tablet_tool_v2::Request::SetCursor {
+                surface,
+                hotspot_x,
+                hotspot_y,
+                ..
+            } => {
+                let mut guard = inner.borrow_mut();
+                // only the focused client can set the cursor image
+                let focused = guard
+                    .focus
+                    .as_ref()
+                    .map(|(focus, _)| focus.as_ref().same_client_as(tool.as_ref()))
+                    .unwrap_or(false);
+                if !focused {
+                    return;
+                }
+                let ToolInner {
+                    ref give_role,
+                    ref mut image_callback,
+                    ..
+                } = *guard;
+                match surface {
+                    Some(surface) => {
+                        if !give_role(&surface, (hotspot_x, hotspot_y)) {
+                            tool.as_ref().post_error(
+                                zwp_tablet_tool_v2::Error::Role as u32,
+                                "Given wl_surface has another role.".into(),
+                            );
+                            return;
+                        }
+                        image_callback(CursorImageStatus::Image(surface));
+                    }
+                    None => image_callback(CursorImageStatus::Hidden),
+                }
+            }
+            zwp_tablet_tool_v2::Request::Destroy => {
+                // All is already handled by our destructor
+            }
+            _ => unreachable!(),
+        });
+        let inner = self.inner.clone();
+        tool.assign_destructor(Filter::new(move |tool: ZwpTabletToolV2, _, _| {
+            inner
+                .borrow_mut()
+                .instances
+                .retain(|t| !t.as_ref().equals(tool.as_ref()));
+        }));
+
+        seat.tool_added(&tool);
+        tool._type(match self.descriptor.tool_type {
+            TabletToolType::Pen | TabletToolType::Unknown => Type::Pen,
+            TabletToolType::Eraser => Type::Eraser,
+            TabletToolType::Brush => Type::Brush,
+            TabletToolType::Pencil => Type::Pencil,
+            TabletToolType::Airbrush => Type::Airbrush,
+            TabletToolType::Mouse => Type::Mouse,
+            TabletToolType::Lens => Type::Lens,
+        });
+        let serial = self.descriptor.hardware_serial;
+        if serial != 0 {
+            tool.hardware_serial((serial >> 32) as u32, serial as u32);
+        }
+        let id = self.descriptor.hardware_id_wacom;
+        if id != 0 {
+            tool.hardware_id_wacom((id >> 32) as u32, id as u32);
+        }
+        let caps = self.descriptor.capabilities;
+        for &(supported, capability) in &[
+            (caps.tilt, Capability::Tilt),
+            (caps.pressure, Capability::Pressure),
+            (caps.distance, Capability::Distance),
+            (caps.rotation, Capability::Rotation),
+            (caps.slider, Capability::Slider),
+            (caps.wheel, Capability::Wheel),
+        ] {
+            if supported {
+                tool.capability(capability);
+            }
+        }
+        tool.done();
+        self.inner.borrow_mut().instances.push((*tool).clone());
+    }
+
+    pub(super) fn removed(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.focus = None;
+        for tool in inner.instances.drain(..) {
+            tool.removed();
+        }
+    }
+
+    /// The surface the tool is currently over, and its location
+    pub fn current_focus(&self) -> Option<(WlSurface, (f64, f64))> {
+        self.inner.borrow().focus.clone()
+    }
+
+    /// Whether the tip of the tool is currently down
+    pub fn is_down(&self) -> bool {
+        self.inner.borrow().down
+    }
+
+    /// Notify that the tool came in proximity of a tablet
+    ///
+    /// `focus` is the surface under the tool along with its location, and `location` the
+    /// location of the tool, both in the global compositor space.
+    pub fn proximity_in(
+        &self,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        tablet: &TabletHandle,
+        serial: Serial,
+        time: u32,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        inner.focus = focus;
+        inner.with_focused_tools(|tool, surface| {
+            if let Some(tablet) = tablet.instance_for(tool.as_ref()) {
+                tool.proximity_in(serial.into(), &tablet, surface);
+            }
+        });
+        send_motion(&inner, location);
+        inner.with_focused_tools(|tool, _| tool.frame(time));
+    }
+
+    /// Notify that the tool left the proximity of its tablet
+    ///
+    /// If the tip of the tool is still down, it is released first.
+    pub fn proximity_out(&self, time: u32) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.down {
+            inner.with_focused_tools(|tool, _| tool.up());
+            inner.down = false;
+        }
+        inner.with_focused_tools(|tool, _| {
+            tool.proximity_out();
+            tool.frame(time);
+        });
+        inner.focus = None;
+    }
+
+    /// Notify a motion of the tool
+    ///
+    /// If the surface under the tool changed, the previous one is left and the new one
+    /// entered. While the tip is down, the focus stays on the surface it touched.
+    pub fn motion(
+        &self,
+        location: (f64, f64),
+        focus: Option<(WlSurface, (f64, f64))>,
+        tablet: &TabletHandle,
+        serial: Serial,
+        time: u32,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        let same = match (&inner.focus, &focus) {
+            (Some((old, _)), Some((new, _))) => old.as_ref().equals(new.as_ref()),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same && !inner.down {
+            inner.with_focused_tools(|tool, _| {
+                tool.proximity_out();
+                tool.frame(time);
+            });
+            inner.focus = focus;
+            inner.with_focused_tools(|tool, surface| {
+                if let Some(tablet) = tablet.instance_for(tool.as_ref()) {
+                    tool.proximity_in(serial.into(), &tablet, surface);
+                }
+            });
+        } else if same {
+            // the surface may have moved
+            inner.focus = focus;
+        }
+        send_motion(&inner, location);
+    }
+
+    /// Notify that the tip of the tool touched the tablet
+    pub fn tip_down(&self, serial: Serial) {
+        let mut inner = self.inner.borrow_mut();
+        inner.with_focused_tools(|tool, _| tool.down(serial.into()));
+        inner.down = true;
+    }
+
+    /// Notify that the tip of the tool left the tablet
+    pub fn tip_up(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.with_focused_tools(|tool, _| tool.up());
+        inner.down = false;
+    }
+
+    /// Notify the pressure applied on the tip, normalized between 0 and 1
+    pub fn pressure(&self, pressure: f64) {
+        let value = normalized(pressure);
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.pressure(value));
+    }
+
+    /// Notify the distance of the tool from the tablet, normalized between 0 and 1
+    pub fn distance(&self, distance: f64) {
+        let value = normalized(distance);
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.distance(value));
+    }
+
+    /// Notify the tilt of the tool, in degrees
+    pub fn tilt(&self, (tilt_x, tilt_y): (f64, f64)) {
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.tilt(tilt_x, tilt_y));
+    }
+
+    /// Notify the rotation of the tool, in degrees
+    pub fn rotation(&self, degrees: f64) {
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.rotation(degrees));
+    }
+
+    /// Notify the position of the slider of the tool, normalized between -1 and 1
+    pub fn slider_position(&self, position: f64) {
+        let value = (position.max(-1.0).min(1.0) * 65535.0) as i32;
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.slider(value));
+    }
+
+    /// Notify a movement of the wheel of the tool
+    pub fn wheel(&self, degrees: f64, clicks: i32) {
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.wheel(degrees, clicks));
+    }
+
+    /// Notify a button of the tool was pressed or released
+    pub fn button(&self, button: u32, state: MouseButtonState, serial: Serial) {
+        let state = match state {
+            MouseButtonState::Pressed => ButtonState::Pressed,
+            MouseButtonState::Released => ButtonState::Released,
+        };
+        self.inner
+            .borrow()
+            .with_focused_tools(|tool, _| tool.button(serial.into(), button, state));
+    }
+
+    /// Notify all the axes of an event of the tool that changed, according to its capabilities
+    ///
+    /// The position of the tool is not handled, use [`motion`](TabletToolHandle::motion).
+    pub fn axes<E: TabletToolEvent>(&self, event: &E) {
+        let caps = self.descriptor.capabilities;
+        if caps.pressure && event.pressure_has_changed() {
+            self.pressure(event.pressure());
+        }
+        if caps.distance && event.distance_has_changed() {
+            self.distance(event.distance());
+        }
+        if caps.tilt && event.tilt_has_changed() {
+            self.tilt(event.tilt());
+        }
+        if caps.rotation && event.rotation_has_changed() {
+            self.rotation(event.rotation());
+        }
+        if caps.slider && event.slider_has_changed() {
+            self.slider_position(event.slider_position());
+        }
+        if caps.wheel && event.wheel_has_changed() {
+            self.wheel(event.wheel_delta(), event.wheel_delta_discrete());
+        }
+    }
+
+    /// Terminate the group of events describing a single hardware event
+    pub fn frame(&self, time: u32) {
+        self.inner.borrow().with_focused_tools(|tool, _| tool.frame(time));
+    }
+}
+
+impl ::std::fmt::Debug for TabletToolHandle {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("TabletToolHandle")
+            .field("descriptor", &self.descriptor)
+            .field("instances", &inner.instances.len())
+            .field("focus", &inner.focus)
+            .field("down", &inner.down)
+            .finish()
+    }
+}
+
+fn send_motion(inner: &ToolInner, (x, y): (f64, f64)) {
+    if let Some((_, (sx, sy))) = inner.focus {
+        inner.with_focused_tools(|tool, _| tool.motion(x - sx, y - sy));
+    }
+}
+
+// scale a value between 0 and 1 to the range of the protocol
+fn normalized(value: f64) -> u32 {
+    (value.max(0.0).min(1.0) * 65535.0) as u32
+}
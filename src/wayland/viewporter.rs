@@ -0,0 +1,289 @@
+//! Handler for the viewporter protocol
+//!
+//! This protocol lets clients crop and scale the contents of their surfaces independently of
+//! the size of their buffers, which video players use to display a video at any size without
+//! having to rescale it themselves.
+//!
+//! The viewport of a surface is stored in the [`viewport`](::wayland::compositor::SurfaceAttributes::viewport)
+//! field of its attributes, and applies on commit like the rest of the surface state. Use
+//! [`Viewport::surface_size`] to compute the size of the surface and [`Viewport::source_region`]
+//! to know which part of the buffer to draw.
+//!
+//! ```
+//! # extern crate wayland_server;
+//! # #[macro_use] extern crate smithay;
+//! # use smithay::wayland::compositor::compositor_init;
+//! use smithay::wayland::viewporter::init_viewporter;
+//! # define_roles!(Roles);
+//! # let mut display = wayland_server::Display::new();
+//! # let (compositor_token, _, _) = compositor_init::<Roles, _, _>(&mut display, |_, _, _| {}, None);
+//! init_viewporter(&mut display, compositor_token, None /* insert a logger here */);
+//! ```
+
+use std::cell::RefCell;
+
+use wayland_protocols::viewporter::server::{
+    wp_viewport::{self, WpViewport},
+    wp_viewporter::{self, WpViewporter},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+
+use crate::{
+    utils::DoubleBuffered,
+    wayland::compositor::{BufferAssignment, CompositorToken, SurfaceAttributes},
+};
+
+/// A rectangle with fractional coordinates, in surface-local coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceRect {
+    /// Horizontal position of the top-left corner
+    pub x: f64,
+    /// Vertical position of the top-left corner
+    pub y: f64,
+    /// Width of the rectangle
+    pub width: f64,
+    /// Height of the rectangle
+    pub height: f64,
+}
+
+/// Cropping and scaling of the contents of a surface
+///
+/// The source rectangle is expressed in the coordinates of the buffer after its
+/// `buffer_transform` and `buffer_scale` are applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Viewport {
+    /// The part of the buffer to display, the whole buffer if `None`
+    pub source: Option<SourceRect>,
+    /// The size of the surface, the size of the source rectangle if `None`
+    pub destination: Option<(i32, i32)>,
+}
+
+impl Viewport {
+    /// Size of the surface
+    ///
+    /// `buffer_size` is the size of the buffer after its `buffer_transform` and `buffer_scale`
+    /// are applied.
+    pub fn surface_size(&self, buffer_size: (i32, i32)) -> (i32, i32) {
+        match (self.destination, self.source) {
+            (Some(size), _) => size,
+            (None, Some(src)) => (src.width as i32, src.height as i32),
+            (None, None) => buffer_size,
+        }
+    }
+
+    /// The part of the buffer to draw, normalized to the `[0, 1]` range
+    ///
+    /// `buffer_size` is the size of the buffer after its `buffer_transform` and `buffer_scale`
+    /// are applied. The coordinates can directly be used as texture coordinates, provided
+    /// the transform of the buffer is handled separately.
+    pub fn source_region(&self, buffer_size: (i32, i32)) -> SourceRect {
+        let (width, height) = (buffer_size.0.max(1) as f64, buffer_size.1.max(1) as f64);
+        match self.source {
+            Some(src) => SourceRect {
+                x: src.x / width,
+                y: src.y / height,
+                width: src.width / width,
+                height: src.height / height,
+            },
+            None => SourceRect {
+                x: 0.0,
+                y: 0.0,
+                width: 1.0,
+                height: 1.0,
+            },
+        }
+    }
+}
+
+// the viewport object of a surface, if any, and its double-buffered state
+#[derive(Default)]
+struct ViewportObject {
+    object: RefCell<Option<WpViewport>>,
+    state: RefCell<DoubleBuffered<Viewport>>,
+}
+
+/// Create a new viewporter global
+pub fn init_viewporter<R, L>(
+    display: &mut Display,
+    token: CompositorToken<R>,
+    logger: L,
+) -> Global<WpViewporter>
+where
+    R: 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "viewporter_handler"));
+
+    display.create_global::<WpViewporter, _>(
+        1,
+        Filter::new(move |(viewporter, _version): (Main<WpViewporter>, u32), _, _| {
+            let log = log.clone();
+            viewporter.quick_assign(move |_viewporter, request, _| match request {
+                wp_viewporter::Request::GetViewport { id, surface } => {
+                    let exists = token.with_surface_data(&surface, |attrs| {
+                        attrs.user_data.insert_if_missing(ViewportObject::default);
+                        let object = attrs.user_data.get::<ViewportObject>().unwrap();
+                        let exists = object.object.borrow().is_some();
+                        if !exists {
+                            *object.object.borrow_mut() = Some((*id).clone());
+                        }
+                        exists
+                    });
+                    if exists {
+                        id.quick_assign(|_, _, _| {});
+                        surface.as_ref().post_error(
+                            wp_viewporter::Error::ViewportExists as u32,
+                            "This surface already has a viewport.".into(),
+                        );
+                        return;
+                    }
+                    trace!(log, "New viewport");
+                    implement_viewport(id, surface, token);
+                }
+                wp_viewporter::Request::Destroy => {
+                    // Nothing to do
+                }
+                _ => unreachable!(),
+            });
+        }),
+    )
+}
+
+fn implement_viewport<R: 'static>(viewport: Main<WpViewport>, surface: WlSurface, token: CompositorToken<R>) {
+    let destructor_surface = surface.clone();
+    viewport.quick_assign(move |viewport, request, _| {
+        if let wp_viewport::Request::Destroy = request {
+            // All is already handled by our destructor
+            return;
+        }
+        if !surface.as_ref().is_alive() {
+            viewport.as_ref().post_error(
+                wp_viewport::Error::NoSurface as u32,
+                "The surface of this viewport was destroyed.".into(),
+            );
+            return;
+        }
+        match request {
+            wp_viewport::Request::SetSource { x, y, width, height } => {
+                let source = if x == -1.0 && y == -1.0 && width == -1.0 && height == -1.0 {
+                    None
+                } else if x < 0.0 || y < 0.0 || width <= 0.0 || height <= 0.0 {
+                    viewport.as_ref().post_error(
+                        wp_viewport::Error::BadValue as u32,
+                        "Invalid source rectangle.".into(),
+                    );
+                    return;
+                } else {
+                    Some(SourceRect { x, y, width, height })
+                };
+                with_pending(&surface, token, |viewport| viewport.source = source);
+            }
+            wp_viewport::Request::SetDestination { width, height } => {
+                let destination = if width == -1 && height == -1 {
+                    None
+                } else if width <= 0 || height <= 0 {
+                    viewport.as_ref().post_error(
+                        wp_viewport::Error::BadValue as u32,
+                        "Invalid destination size.".into(),
+                    );
+                    return;
+                } else {
+                    Some((width, height))
+                };
+                with_pending(&surface, token, |viewport| viewport.destination = destination);
+            }
+            _ => unreachable!(),
+        }
+    });
+    viewport.assign_destructor(Filter::new(move |_viewport: WpViewport, _, _| {
+        if destructor_surface.as_ref().is_alive() {
+            // the viewport is removed on the next commit
+            token.with_surface_data(&destructor_surface, |attrs| {
+                if let Some(object) = attrs.user_data.get::<ViewportObject>() {
+                    *object.object.borrow_mut() = None;
+                    object.state.borrow_mut().set_pending(Viewport::default());
+                }
+            });
+        }
+    }));
+}
+
+fn with_pending<R: 'static, F>(surface: &WlSurface, token: CompositorToken<R>, f: F)
+where
+    F: FnOnce(&mut Viewport),
+{
+    token.with_surface_data(surface, |attrs| {
+        if let Some(object) = attrs.user_data.get::<ViewportObject>() {
+            f(object.state.borrow_mut().pending_mut());
+        }
+    });
+}
+
+/// Apply and check the viewport of a surface on commit
+///
+/// The viewport of a synchronized sub-surface is cached instead, until [`apply_cached`] is
+/// called when its parent is committed.
+pub(crate) fn apply_commit(attrs: &mut SurfaceAttributes, sync: bool) {
+    let (viewport, committed) = match attrs.user_data.get::<ViewportObject>() {
+        Some(object) => {
+            let mut state = object.state.borrow_mut();
+            if sync {
+                state.cache();
+            } else if state.commit() {
+                attrs.viewport = *state.current();
+            }
+            match *object.object.borrow() {
+                Some(ref viewport) => (viewport.clone(), *state.pending()),
+                None => return,
+            }
+        }
+        None => return,
+    };
+    let src = match committed.source {
+        Some(src) => src,
+        None => return,
+    };
+    if committed.destination.is_none() && (src.width.fract() != 0.0 || src.height.fract() != 0.0) {
+        viewport.as_ref().post_error(
+            wp_viewport::Error::BadSize as u32,
+            "The source size must be integer when no destination is set.".into(),
+        );
+        return;
+    }
+    // the size of the buffer is only known for shm buffers
+    if let Some(BufferAssignment::NewBuffer { ref buffer, .. }) = attrs.buffer {
+        if let Ok(data) = crate::wayland::shm::with_buffer_contents(buffer, |_, data| data) {
+            let scale = attrs.buffer_scale.max(1) as f64;
+            let (width, height) = (data.width as f64 / scale, data.height as f64 / scale);
+            let (width, height) = if transform_swaps_axes(attrs.buffer_transform) {
+                (height, width)
+            } else {
+                (width, height)
+            };
+            if src.x + src.width > width || src.y + src.height > height {
+                viewport.as_ref().post_error(
+                    wp_viewport::Error::OutOfBuffer as u32,
+                    "The source rectangle extends outside of the buffer.".into(),
+                );
+            }
+        }
+    }
+}
+
+/// Apply the viewport cached by a synchronized sub-surface, once its parent is committed
+pub(crate) fn apply_cached(attrs: &mut SurfaceAttributes) {
+    if let Some(object) = attrs.user_data.get::<ViewportObject>() {
+        let mut state = object.state.borrow_mut();
+        if state.apply_cached() {
+            attrs.viewport = *state.current();
+        }
+    }
+}
+
+fn transform_swaps_axes(transform: wayland_server::protocol::wl_output::Transform) -> bool {
+    use wayland_server::protocol::wl_output::Transform;
+    matches!(
+        transform,
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270
+    )
+}
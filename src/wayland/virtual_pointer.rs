@@ -0,0 +1,340 @@
+//! Handler for the wlr virtual pointer protocol
+//!
+//! This protocol lets clients such as remote desktop servers or automation tools emulate a
+//! pointer device. Their events are forwarded to your implementation as
+//! [`VirtualPointerEvent`]s, which you should process like the events of your input backend:
+//! compute the new pointer focus and forward them to the [`PointerHandle`] of the seat, so
+//! that they go through the same grabs and focus handling as physical devices.
+//!
+//! Absolute motions are reported in normalized device coordinates, which you can map to the
+//! output of the virtual pointer (if any) with an [`AbsoluteMapping`]. Relative motions are
+//! raw deltas, which you may accelerate with a [`PointerAccel`].
+//!
+//! [`PointerHandle`]: crate::wayland::seat::PointerHandle
+//! [`AbsoluteMapping`]: crate::wayland::seat::AbsoluteMapping
+//! [`PointerAccel`]: crate::wayland::seat::PointerAccel
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::virtual_pointer::{init_virtual_pointer_manager, VirtualPointerEvent};
+//! # let mut display = wayland_server::Display::new();
+//! init_virtual_pointer_manager(
+//!     &mut display,
+//!     |event, pointer| match event {
+//!         VirtualPointerEvent::Motion { time, delta } => {
+//!             /* move the pointer of pointer.seat(), or of your default seat */
+//!         }
+//!         _ => { /* process the other events likewise */ }
+//!     },
+//!     None, /* insert a logger here */
+//! );
+//! ```
+
+use std::{cell::RefCell, rc::Rc};
+
+use wayland_protocols::wlr::unstable::virtual_pointer::v1::server::{
+    zwlr_virtual_pointer_manager_v1::{self, ZwlrVirtualPointerManagerV1},
+    zwlr_virtual_pointer_v1::{self, ZwlrVirtualPointerV1},
+};
+use wayland_server::{
+    protocol::{
+        wl_output::WlOutput,
+        wl_pointer::{Axis, AxisSource, ButtonState},
+        wl_seat::WlSeat,
+    },
+    Display, Filter, Global, Main,
+};
+
+use crate::wayland::seat::{AxisFrame, Seat};
+
+/// A virtual pointer created by a client
+#[derive(Clone)]
+pub struct VirtualPointer {
+    pointer: ZwlrVirtualPointerV1,
+    seat: Option<Seat>,
+    output: Option<WlOutput>,
+}
+
+impl VirtualPointer {
+    /// The seat the client wants the events to be sent to
+    ///
+    /// `None` if the client let the compositor choose.
+    pub fn seat(&self) -> Option<&Seat> {
+        self.seat.as_ref()
+    }
+
+    /// The output the client wants the absolute motions to be mapped to
+    ///
+    /// `None` if the client let the compositor choose.
+    pub fn output(&self) -> Option<&WlOutput> {
+        self.output.as_ref()
+    }
+
+    /// Is the virtual pointer referred by this handle still alive?
+    pub fn alive(&self) -> bool {
+        self.pointer.as_ref().is_alive()
+    }
+
+    /// Do this handle and the other one actually refer to the same virtual pointer?
+    pub fn equals(&self, other: &Self) -> bool {
+        self.pointer.as_ref().equals(other.pointer.as_ref())
+    }
+}
+
+impl ::std::fmt::Debug for VirtualPointer {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        f.debug_struct("VirtualPointer")
+            .field("pointer", &self.pointer)
+            .field("output", &self.output)
+            .finish()
+    }
+}
+
+/// An event emulated by a virtual pointer
+#[derive(Debug)]
+pub enum VirtualPointerEvent {
+    /// The pointer moved
+    Motion {
+        /// Timestamp of the event in milliseconds
+        time: u32,
+        /// Displacement of the pointer
+        delta: (f64, f64),
+    },
+    /// The pointer moved to an absolute position
+    MotionAbsolute {
+        /// Timestamp of the event in milliseconds
+        time: u32,
+        /// Position of the pointer in normalized device coordinates
+        ///
+        /// `(0.0, 0.0)` is the top-left and `(1.0, 1.0)` the bottom-right corner of the area
+        /// the pointer is mapped to.
+        position: (f64, f64),
+    },
+    /// A button was pressed or released
+    Button {
+        /// Timestamp of the event in milliseconds
+        time: u32,
+        /// The button, as a linux input event code
+        button: u32,
+        /// Whether the button was pressed or released
+        state: ButtonState,
+    },
+    /// The axes of the pointer were actionned
+    ///
+    /// All the axis events the client sent until the end of its frame are grouped.
+    Axis(AxisFrame),
+    /// The end of a group of events that belong together
+    Frame,
+}
+
+// the axis events of the current frame of a virtual pointer
+#[derive(Debug, Default)]
+struct PendingAxis {
+    time: u32,
+    source: Option<AxisSource>,
+    value: (f64, f64),
+    discrete: (i32, i32),
+    stop: (bool, bool),
+    dirty: bool,
+}
+
+impl PendingAxis {
+    fn update<F: FnOnce(&mut f64, &mut i32, &mut bool)>(&mut self, time: u32, axis: Axis, f: F) {
+        self.time = time;
+        self.dirty = true;
+        match axis {
+            Axis::HorizontalScroll => f(&mut self.value.0, &mut self.discrete.0, &mut self.stop.0),
+            Axis::VerticalScroll => f(&mut self.value.1, &mut self.discrete.1, &mut self.stop.1),
+            _ => {}
+        }
+    }
+
+    fn take_frame(&mut self) -> Option<AxisFrame> {
+        if !self.dirty {
+            return None;
+        }
+        let pending = ::std::mem::take(self);
+        let mut frame = AxisFrame::new(pending.time);
+        if let Some(source) = pending.source {
+            frame = frame.source(source);
+        }
+        for &(axis, value, discrete, stop) in &[
+            (
+                Axis::HorizontalScroll,
+                pending.value.0,
+                pending.discrete.0,
+                pending.stop.0,
+            ),
+            (
+                Axis::VerticalScroll,
+                pending.value.1,
+                pending.discrete.1,
+                pending.stop.1,
+            ),
+        ] {
+            if value != 0.0 {
+                frame = frame.value(axis, value);
+            }
+            if discrete != 0 {
+                frame = frame.discrete(axis, discrete);
+            }
+            if stop {
+                frame = frame.stop(axis);
+            }
+        }
+        Some(frame)
+    }
+}
+
+fn implement_virtual_pointer<Impl>(
+    pointer: Main<ZwlrVirtualPointerV1>,
+    seat: Option<WlSeat>,
+    output: Option<WlOutput>,
+    implementation: Rc<RefCell<Impl>>,
+    log: &::slog::Logger,
+) where
+    Impl: FnMut(VirtualPointerEvent, &VirtualPointer) + 'static,
+{
+    let handle = VirtualPointer {
+        pointer: (*pointer).clone(),
+        seat: seat.as_ref().and_then(Seat::from_resource),
+        output,
+    };
+    trace!(log, "New virtual pointer");
+    let mut axis = PendingAxis::default();
+    pointer.quick_assign(move |_pointer, request, _| {
+        let event = match request {
+            zwlr_virtual_pointer_v1::Request::Motion { time, dx, dy } => VirtualPointerEvent::Motion {
+                time,
+                delta: (dx, dy),
+            },
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute {
+                time,
+                x,
+                y,
+                x_extent,
+                y_extent,
+            } => {
+                if x_extent == 0 || y_extent == 0 {
+                    return;
+                }
+                VirtualPointerEvent::MotionAbsolute {
+                    time,
+                    position: (
+                        f64::from(x) / f64::from(x_extent),
+                        f64::from(y) / f64::from(y_extent),
+                    ),
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::Button { time, button, state } => {
+                VirtualPointerEvent::Button { time, button, state }
+            }
+            zwlr_virtual_pointer_v1::Request::Axis { time, axis: a, value } => {
+                axis.update(time, a, |v, _, _| *v += value);
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource { axis_source } => {
+                axis.source = Some(axis_source);
+                axis.dirty = true;
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop { time, axis: a } => {
+                axis.update(time, a, |_, _, stop| *stop = true);
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete {
+                time,
+                axis: a,
+                value,
+                discrete,
+            } => {
+                axis.update(time, a, |v, d, _| {
+                    *v += value;
+                    *d += discrete;
+                });
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::Frame => {
+                let mut implementation = implementation.borrow_mut();
+                if let Some(frame) = axis.take_frame() {
+                    (&mut *implementation)(VirtualPointerEvent::Axis(frame), &handle);
+                }
+                (&mut *implementation)(VirtualPointerEvent::Frame, &handle);
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::Destroy => {
+                // Nothing to do
+                return;
+            }
+            _ => unreachable!(),
+        };
+        (&mut *implementation.borrow_mut())(event, &handle);
+    });
+}
+
+/// Create a new virtual pointer manager global
+///
+/// The implementation receives the events of all the virtual pointers, along with the virtual
+/// pointer that emitted them.
+pub fn init_virtual_pointer_manager<Impl, L>(
+    display: &mut Display,
+    implementation: Impl,
+    logger: L,
+) -> Global<ZwlrVirtualPointerManagerV1>
+where
+    Impl: FnMut(VirtualPointerEvent, &VirtualPointer) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "virtual_pointer_handler"));
+    let implementation = Rc::new(RefCell::new(implementation));
+
+    display.create_global::<ZwlrVirtualPointerManagerV1, _>(
+        2,
+        Filter::new(
+            move |(manager, _version): (Main<ZwlrVirtualPointerManagerV1>, u32), _, _| {
+                let implementation = implementation.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { seat, id } => {
+                        implement_virtual_pointer(id, seat, None, implementation.clone(), &log);
+                    }
+                    zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput {
+                        seat,
+                        output,
+                        id,
+                    } => {
+                        implement_virtual_pointer(id, seat, output, implementation.clone(), &log);
+                    }
+                    zwlr_virtual_pointer_manager_v1::Request::Destroy => {
+                        // Nothing to do
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PendingAxis;
+    use wayland_server::protocol::wl_pointer::Axis;
+
+    #[test]
+    fn axis_events_are_grouped_by_frame() {
+        let mut pending = PendingAxis::default();
+        assert!(pending.take_frame().is_none());
+        pending.update(10, Axis::VerticalScroll, |v, _, _| *v += 5.0);
+        pending.update(12, Axis::VerticalScroll, |v, d, _| {
+            *v += 10.0;
+            *d += 1;
+        });
+        assert_eq!(pending.value, (0.0, 15.0));
+        assert_eq!(pending.discrete, (0, 1));
+        assert_eq!(pending.time, 12);
+        assert!(pending.take_frame().is_some());
+        // the next frame starts empty
+        assert!(pending.take_frame().is_none());
+        assert_eq!(pending.value, (0.0, 0.0));
+    }
+}